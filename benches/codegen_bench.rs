@@ -61,6 +61,7 @@ fn create_function_ast() -> Vec<AstNode> {
             attributes: vec![],
             is_static: false,
             visibility: Visibility::Public,
+            doc_comment: None,
         })
     ]
 }
@@ -73,6 +74,7 @@ fn create_class_ast() -> Vec<AstNode> {
             name: "Calculator".to_string(),
             extends: None,
             implements: vec![],
+            traits: vec![],
             properties: vec![
                 PropertyDecl {
                     name: "precision".to_string(),
@@ -81,6 +83,7 @@ fn create_class_ast() -> Vec<AstNode> {
                     visibility: Visibility::Private,
                     is_static: false,
                     is_readonly: false,
+                    doc_comment: None,
                 }
             ],
             methods: vec![
@@ -100,6 +103,7 @@ fn create_class_ast() -> Vec<AstNode> {
                     attributes: vec![],
                     is_static: false,
                     visibility: Visibility::Public,
+                    doc_comment: None,
                 }
             ],
             constants: vec![],
@@ -176,6 +180,7 @@ fn bench_generate_large_ir(c: &mut Criterion) {
             attributes: vec![],
             is_static: false,
             visibility: Visibility::Public,
+            doc_comment: None,
         }));
     }
     
@@ -0,0 +1,736 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Refcounted arrays of compiled code and their iteration protocol.
+//!
+//! Generated code handles PHP arrays as `%php.array*` pointers to
+//! [`PhpArray`] values, refcounted like strings; a null pointer is the
+//! empty array. `foreach` takes a `%php.iter*` snapshot of the entries
+//! and reads the current key and value with the accessor matching the
+//! static type of the loop variables. Looking an element up for `??`
+//! gives an iterator over that single element, or over nothing when the
+//! element is missing or null.
+//!
+//! Element writes go through a `%php.value*` pointer to the element in
+//! place, taken from the variable holding the array so that a shared array
+//! is copied before it is modified.
+
+use crate::interp::{array_get, array_key, array_set, to_float, to_int, to_php_string, truthy, Key};
+use crate::mixed::{from_value, PhpMixed};
+use crate::runtime::{Array, ArrayType, Value};
+use crate::strings::PhpString;
+
+/// Runtime array with a reference count
+#[derive(Debug)]
+pub struct PhpArray {
+    refcount: usize,
+    array: Array,
+}
+
+impl PhpArray {
+    /// Allocate an array with one reference
+    pub fn new(array: Array) -> *mut PhpArray {
+        Box::into_raw(Box::new(PhpArray { refcount: 1, array }))
+    }
+
+    /// Elements of the array
+    pub fn array(&self) -> &Array {
+        &self.array
+    }
+
+    /// Number of references to the array
+    pub fn refcount(&self) -> usize {
+        self.refcount
+    }
+}
+
+/// Iteration over a snapshot of an array's entries, as `foreach` by value sees them
+#[derive(Debug)]
+pub struct PhpArrayIter {
+    entries: Vec<(Value, Value)>,
+    position: usize,
+}
+
+impl PhpArrayIter {
+    fn current(&self) -> Option<&(Value, Value)> {
+        self.entries.get(self.position)
+    }
+}
+
+// FFI functions called by generated code
+
+/// # Safety
+///
+/// `a` must be null or a live array.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_addref(a: *mut PhpArray) {
+    if let Some(a) = a.as_mut() {
+        a.refcount += 1;
+    }
+}
+
+/// Drop a reference, freeing the array with the last one
+///
+/// # Safety
+///
+/// `a` must be null or a live array; it must not be used after its last
+/// reference is released.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_release(a: *mut PhpArray) {
+    let Some(array) = a.as_mut() else {
+        return;
+    };
+    array.refcount -= 1;
+    if array.refcount == 0 {
+        drop(Box::from_raw(a));
+    }
+}
+
+/// PHP truthiness: false for the empty array
+///
+/// # Safety
+///
+/// `a` must be null or a live array.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_truthy(a: *const PhpArray) -> bool {
+    a.as_ref().is_some_and(|a| !a.array.is_empty())
+}
+
+/// Create an empty array, packed when its keys are known to be positions
+#[no_mangle]
+pub extern "C" fn php_array_new(packed: bool) -> *mut PhpArray {
+    PhpArray::new(Array::new(if packed { ArrayType::Packed } else { ArrayType::Associative }))
+}
+
+/// Start iterating over an array
+///
+/// # Safety
+///
+/// `a` must be null or a live array. The iterator must be freed with
+/// [`php_array_iter_free`].
+#[no_mangle]
+pub unsafe extern "C" fn php_array_iter(a: *const PhpArray) -> *mut PhpArrayIter {
+    let entries = match a.as_ref() {
+        Some(a) => a.array.iter().map(|(key, value)| (key, value.clone())).collect(),
+        None => Vec::new(),
+    };
+    Box::into_raw(Box::new(PhpArrayIter { entries, position: 0 }))
+}
+
+/// Iterator over the element at an integer key, as `isset()` sees it
+///
+/// # Safety
+///
+/// `a` must be null or a live array. The iterator must be freed with
+/// [`php_array_iter_free`].
+#[no_mangle]
+pub unsafe extern "C" fn php_array_lookup_int(a: *const PhpArray, key: i64) -> *mut PhpArrayIter {
+    lookup(a, Key::Int(key))
+}
+
+/// Iterator over the element at a string key; numeric strings are integer keys
+///
+/// # Safety
+///
+/// `a` and `key` must be null or live. The iterator must be freed with
+/// [`php_array_iter_free`].
+#[no_mangle]
+pub unsafe extern "C" fn php_array_lookup_string(a: *const PhpArray, key: *const PhpString) -> *mut PhpArrayIter {
+    lookup(a, string_key(key))
+}
+
+/// Element at an integer key, or null with a warning when it is missing
+///
+/// # Safety
+///
+/// `a` must be null or a live array. The element is valid until the array
+/// is next modified or released.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_get_int(a: *const PhpArray, key: i64) -> *const Value {
+    get(a, Key::Int(key))
+}
+
+/// Element at a string key, or null with a warning when it is missing
+///
+/// # Safety
+///
+/// As for [`php_array_get_int`]; `key` must be null or a live string.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_get_string(a: *const PhpArray, key: *const PhpString) -> *const Value {
+    get(a, string_key(key))
+}
+
+unsafe fn get(a: *const PhpArray, key: Key) -> *const Value {
+    match a.as_ref().and_then(|a| array_get(&a.array, &key)) {
+        Some(value) => value,
+        None => {
+            match key {
+                Key::Int(n) => eprintln!("PHP Warning:  Undefined array key {}", n),
+                Key::Str(s) => eprintln!("PHP Warning:  Undefined array key \"{}\"", s),
+            }
+            std::ptr::null()
+        }
+    }
+}
+
+/// Normalized key for a string
+unsafe fn string_key(key: *const PhpString) -> Key {
+    let key = match key.as_ref() {
+        Some(key) => String::from_utf8_lossy(key.as_bytes()).into_owned(),
+        None => String::new(),
+    };
+    // Only non-scalar keys are rejected
+    array_key(&Value::String(key)).unwrap_or(Key::Str(String::new()))
+}
+
+unsafe fn lookup(a: *const PhpArray, key: Key) -> *mut PhpArrayIter {
+    let entries = a.as_ref()
+        .and_then(|a| array_get(&a.array, &key))
+        .filter(|value| !matches!(value, Value::Null))
+        .map(|value| {
+            let key = match key {
+                Key::Int(n) => Value::Int(n),
+                Key::Str(s) => Value::String(s),
+            };
+            vec![(key, value.clone())]
+        })
+        .unwrap_or_default();
+    Box::into_raw(Box::new(PhpArrayIter { entries, position: 0 }))
+}
+
+/// Element at an integer key of the array held in `slot`, added as null when missing
+///
+/// A shared array is copied and a null one created first, replacing the
+/// reference in `slot`.
+///
+/// # Safety
+///
+/// `slot` must point to a null or live array reference owned by the caller.
+/// The element is valid until the array is next modified or released.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_element_int(slot: *mut *mut PhpArray, key: i64) -> *mut Value {
+    element(slot, Key::Int(key))
+}
+
+/// Element at a string key of the array held in `slot`, added as null when missing
+///
+/// # Safety
+///
+/// As for [`php_array_element_int`]; `key` must be null or a live string.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_element_string(slot: *mut *mut PhpArray, key: *const PhpString) -> *mut Value {
+    element(slot, string_key(key))
+}
+
+/// New element after the last integer key of the array held in `slot`, as by `$a[] = null`
+///
+/// # Safety
+///
+/// As for [`php_array_element_int`].
+#[no_mangle]
+pub unsafe extern "C" fn php_array_append(slot: *mut *mut PhpArray) -> *mut Value {
+    append(unique(slot))
+}
+
+fn append(array: &mut Array) -> *mut Value {
+    if array_set(array, None, Value::Null).is_err() {
+        return std::ptr::null_mut();
+    }
+    let last = array.len() - 1;
+    array.get_mut(last).map_or(std::ptr::null_mut(), |value| value as *mut Value)
+}
+
+/// Add the elements of `source` to the array held in `slot`, as `...$source`
+/// does in an array literal
+///
+/// Integer keys are renumbered after the last one; string keys are kept.
+///
+/// # Safety
+///
+/// As for [`php_array_element_int`]; `source` must be null or a live array.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_spread(slot: *mut *mut PhpArray, source: *const PhpArray) {
+    let Some(source) = source.as_ref() else {
+        return;
+    };
+    // Copied first, as the source may be the array itself
+    let entries: Vec<(Value, Value)> = source.array.iter().map(|(key, value)| (key, value.clone())).collect();
+    let array = unique(slot);
+    for (key, value) in entries {
+        let key = match key {
+            Value::String(s) => Some(Key::Str(s)),
+            _ => None,
+        };
+        // Storing under a scalar key cannot fail
+        let _ = array_set(array, key, value);
+    }
+}
+
+/// Element at an integer key of the array in an element, added as null
+/// when missing, for writes such as `$a['x'][1] = $v`
+///
+/// A null element becomes an empty array first. Other values cannot hold
+/// elements, and give null with a warning.
+///
+/// # Safety
+///
+/// `value` must be null or an element from [`php_array_element_int`] or
+/// one of the element functions.
+#[no_mangle]
+pub unsafe extern "C" fn php_value_element_int(value: *mut Value, key: i64) -> *mut Value {
+    nested(value, Some(Key::Int(key)))
+}
+
+/// Element at a string key of the array in an element, added as null when missing
+///
+/// # Safety
+///
+/// As for [`php_value_element_int`]; `key` must be null or a live string.
+#[no_mangle]
+pub unsafe extern "C" fn php_value_element_string(value: *mut Value, key: *const PhpString) -> *mut Value {
+    nested(value, Some(string_key(key)))
+}
+
+/// New element after the last integer key of the array in an element
+///
+/// # Safety
+///
+/// As for [`php_value_element_int`].
+#[no_mangle]
+pub unsafe extern "C" fn php_value_append(value: *mut Value) -> *mut Value {
+    nested(value, None)
+}
+
+unsafe fn nested(value: *mut Value, key: Option<Key>) -> *mut Value {
+    let Some(value) = value.as_mut() else {
+        return std::ptr::null_mut();
+    };
+    if matches!(value, Value::Null) {
+        *value = Value::Array(Array::new(ArrayType::Packed));
+    }
+    let Value::Array(array) = value else {
+        eprintln!("PHP Warning:  Cannot use a scalar value as an array");
+        return std::ptr::null_mut();
+    };
+    match key {
+        Some(key) => find_or_insert(array, key),
+        None => append(array),
+    }
+}
+
+/// Array held in `slot`, copied first when shared and created when null
+unsafe fn unique<'a>(slot: *mut *mut PhpArray) -> &'a mut Array {
+    match (*slot).as_mut() {
+        Some(array) if array.refcount == 1 => &mut array.array,
+        shared => {
+            let copy = shared.map_or_else(|| Array::new(ArrayType::Packed), |a| a.array.clone());
+            php_array_release(*slot);
+            *slot = PhpArray::new(copy);
+            &mut (**slot).array
+        }
+    }
+}
+
+unsafe fn element(slot: *mut *mut PhpArray, key: Key) -> *mut Value {
+    find_or_insert(unique(slot), key)
+}
+
+fn find_or_insert(array: &mut Array, key: Key) -> *mut Value {
+    let name = key.to_key_string();
+    let index = match key {
+        Key::Int(n) => usize::try_from(n).ok(),
+        Key::Str(_) => None,
+    };
+    if array_get(array, &key).is_none() {
+        // Storing a scalar under a scalar key cannot fail
+        let _ = array_set(array, Some(key), Value::Null);
+    }
+    let value = match index {
+        Some(i) if array.is_packed() => array.get_mut(i),
+        _ => array.get_by_key_mut(&name),
+    };
+    value.map_or(std::ptr::null_mut(), |value| value as *mut Value)
+}
+
+/// # Safety
+///
+/// `value` must be null or an element from [`php_array_element_int`] or
+/// [`php_array_element_string`]; the same holds for the other setters.
+#[no_mangle]
+pub unsafe extern "C" fn php_value_set_int(value: *mut Value, n: i64) {
+    set(value, Value::Int(n));
+}
+
+/// # Safety
+///
+/// See [`php_value_set_int`].
+#[no_mangle]
+pub unsafe extern "C" fn php_value_set_float(value: *mut Value, x: f64) {
+    set(value, Value::Float(x));
+}
+
+/// # Safety
+///
+/// See [`php_value_set_int`].
+#[no_mangle]
+pub unsafe extern "C" fn php_value_set_bool(value: *mut Value, b: bool) {
+    set(value, Value::Bool(b));
+}
+
+/// Store a copy of a string; the caller keeps its reference
+///
+/// # Safety
+///
+/// See [`php_value_set_int`]; `s` must be null or a live string.
+#[no_mangle]
+pub unsafe extern "C" fn php_value_set_string(value: *mut Value, s: *const PhpString) {
+    let bytes = s.as_ref().map_or(&[][..], |s| s.as_bytes());
+    set(value, Value::String(String::from_utf8_lossy(bytes).into_owned()));
+}
+
+/// Store a copy of an array; the caller keeps its reference
+///
+/// # Safety
+///
+/// See [`php_value_set_int`]; `a` must be null or a live array.
+#[no_mangle]
+pub unsafe extern "C" fn php_value_set_array(value: *mut Value, a: *const PhpArray) {
+    set(value, Value::Array(a.as_ref().map_or_else(|| Array::new(ArrayType::Packed), |a| a.array.clone())));
+}
+
+/// # Safety
+///
+/// See [`php_value_set_int`].
+#[no_mangle]
+pub unsafe extern "C" fn php_value_set_null(value: *mut Value) {
+    set(value, Value::Null);
+}
+
+/// Value as an integer; values convert like `(int)`
+///
+/// # Safety
+///
+/// `value` must be null or a live value, such as a generator's current
+/// value; the same holds for the other getters.
+#[no_mangle]
+pub unsafe extern "C" fn php_value_get_int(value: *const Value) -> i64 {
+    value.as_ref().map_or(0, |value| to_int(value).unwrap_or(0))
+}
+
+/// # Safety
+///
+/// See [`php_value_get_int`].
+#[no_mangle]
+pub unsafe extern "C" fn php_value_get_float(value: *const Value) -> f64 {
+    value.as_ref().map_or(0.0, |value| to_float(value).unwrap_or(0.0))
+}
+
+/// # Safety
+///
+/// See [`php_value_get_int`].
+#[no_mangle]
+pub unsafe extern "C" fn php_value_get_bool(value: *const Value) -> bool {
+    value.as_ref().is_some_and(truthy)
+}
+
+/// Value as a new string reference
+///
+/// # Safety
+///
+/// See [`php_value_get_int`].
+#[no_mangle]
+pub unsafe extern "C" fn php_value_get_string(value: *const Value) -> *mut PhpString {
+    string(value.as_ref())
+}
+
+/// Value as a new array reference; other values give an empty array
+///
+/// # Safety
+///
+/// See [`php_value_get_int`].
+#[no_mangle]
+pub unsafe extern "C" fn php_value_get_array(value: *const Value) -> *mut PhpArray {
+    match value.as_ref() {
+        Some(Value::Array(array)) => PhpArray::new(array.clone()),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+unsafe fn set(slot: *mut Value, value: Value) {
+    if let Some(slot) = slot.as_mut() {
+        *slot = value;
+    }
+}
+
+/// Whether the iterator is at an entry
+///
+/// # Safety
+///
+/// `it` must be a live iterator.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_iter_valid(it: *const PhpArrayIter) -> bool {
+    (*it).current().is_some()
+}
+
+/// # Safety
+///
+/// `it` must be a live iterator.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_iter_next(it: *mut PhpArrayIter) {
+    (*it).position += 1;
+}
+
+/// # Safety
+///
+/// `it` must be null or a live iterator; it must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_iter_free(it: *mut PhpArrayIter) {
+    if !it.is_null() {
+        drop(Box::from_raw(it));
+    }
+}
+
+/// Current key as an integer; string keys convert like `(int)`
+///
+/// # Safety
+///
+/// `it` must be a live iterator at an entry.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_iter_key_int(it: *const PhpArrayIter) -> i64 {
+    (*it).current().map_or(0, |(key, _)| to_int(key).unwrap_or(0))
+}
+
+/// Current key as a new string reference
+///
+/// # Safety
+///
+/// `it` must be a live iterator at an entry.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_iter_key_string(it: *const PhpArrayIter) -> *mut PhpString {
+    string((*it).current().map(|(key, _)| key))
+}
+
+/// # Safety
+///
+/// `it` must be a live iterator at an entry.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_iter_value_int(it: *const PhpArrayIter) -> i64 {
+    (*it).current().map_or(0, |(_, value)| to_int(value).unwrap_or(0))
+}
+
+/// # Safety
+///
+/// `it` must be a live iterator at an entry.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_iter_value_float(it: *const PhpArrayIter) -> f64 {
+    (*it).current().map_or(0.0, |(_, value)| to_float(value).unwrap_or(0.0))
+}
+
+/// # Safety
+///
+/// `it` must be a live iterator at an entry.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_iter_value_bool(it: *const PhpArrayIter) -> bool {
+    (*it).current().is_some_and(|(_, value)| truthy(value))
+}
+
+/// Current value as a new string reference
+///
+/// # Safety
+///
+/// `it` must be a live iterator at an entry.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_iter_value_string(it: *const PhpArrayIter) -> *mut PhpString {
+    string((*it).current().map(|(_, value)| value))
+}
+
+/// Current value as a new array reference; other values give an empty array
+///
+/// # Safety
+///
+/// `it` must be a live iterator at an entry.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_iter_value_array(it: *const PhpArrayIter) -> *mut PhpArray {
+    match (*it).current() {
+        Some((_, Value::Array(array))) => PhpArray::new(array.clone()),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// Current key, boxed as an integer or a string
+///
+/// # Safety
+///
+/// `it` must be a live iterator at an entry.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_iter_key_mixed(it: *const PhpArrayIter) -> PhpMixed {
+    (*it).current().map_or(PhpMixed::NULL, |(key, _)| from_value(key.clone()))
+}
+
+/// Current value, boxed as a new reference
+///
+/// # Safety
+///
+/// `it` must be a live iterator at an entry.
+#[no_mangle]
+pub unsafe extern "C" fn php_array_iter_value_mixed(it: *const PhpArrayIter) -> PhpMixed {
+    (*it).current().map_or(PhpMixed::NULL, |(_, value)| from_value(value.clone()))
+}
+
+fn string(value: Option<&Value>) -> *mut PhpString {
+    let s = value.and_then(|value| to_php_string(value).ok()).unwrap_or_default();
+    PhpString::new(s.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::ArrayType;
+
+    #[test]
+    fn test_iteration() {
+        let mut array = Array::new(ArrayType::Associative);
+        array.set_by_key("a", Value::Int(1)).unwrap();
+        array.set_by_key("7", Value::String("2.5".to_string())).unwrap();
+        let array = PhpArray::new(array);
+
+        unsafe {
+            let it = php_array_iter(array);
+            php_array_release(array);
+
+            let mut entries = Vec::new();
+            while php_array_iter_valid(it) {
+                let key = php_array_iter_key_string(it);
+                entries.push((
+                    String::from_utf8_lossy((*key).as_bytes()).into_owned(),
+                    php_array_iter_key_int(it),
+                    php_array_iter_value_float(it),
+                ));
+                crate::strings::php_string_release(key);
+                php_array_iter_next(it);
+            }
+            php_array_iter_free(it);
+            assert_eq!(entries, [("a".to_string(), 0, 1.0), ("7".to_string(), 7, 2.5)]);
+
+            let empty = php_array_iter(std::ptr::null());
+            assert!(!php_array_iter_valid(empty));
+            php_array_iter_free(empty);
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        let mut array = Array::new(ArrayType::Associative);
+        array.set_by_key("7", Value::Int(42)).unwrap();
+        array.set_by_key("none", Value::Null).unwrap();
+        let array = PhpArray::new(array);
+        let key = |s: &str| PhpString::new(s.as_bytes().to_vec());
+
+        unsafe {
+            let (seven, none, missing) = (key("7"), key("none"), key("missing"));
+            for (it, found) in [
+                (php_array_lookup_int(array, 7), true),
+                (php_array_lookup_string(array, seven), true),
+                (php_array_lookup_string(array, none), false),
+                (php_array_lookup_string(array, missing), false),
+                (php_array_lookup_int(std::ptr::null(), 7), false),
+            ] {
+                assert_eq!(php_array_iter_valid(it), found);
+                if found {
+                    assert_eq!(php_array_iter_value_int(it), 42);
+                }
+                php_array_iter_free(it);
+            }
+            for s in [seven, none, missing] {
+                crate::strings::php_string_release(s);
+            }
+            php_array_release(array);
+        }
+    }
+
+    #[test]
+    fn test_element_writes() {
+        unsafe {
+            let mut slot: *mut PhpArray = std::ptr::null_mut();
+            php_value_set_int(php_array_element_int(&mut slot, 0), 1);
+            let shared = slot;
+            php_array_addref(shared);
+
+            // Writing to a shared array copies it first
+            let key = PhpString::new(b"x".to_vec());
+            php_value_set_float(php_array_element_string(&mut slot, key), 2.5);
+            crate::strings::php_string_release(key);
+            assert_ne!(slot, shared);
+            assert_eq!((*shared).array().len(), 1);
+            assert!(matches!((*slot).array().get_by_key("x"), Some(Value::Float(x)) if *x == 2.5));
+            assert!(matches!((*slot).array().get_by_key("0"), Some(Value::Int(1))));
+
+            let element = php_array_element_int(&mut slot, 0);
+            php_value_set_int(element, to_int(&*element).unwrap() + 41);
+            let it = php_array_lookup_int(slot, 0);
+            assert_eq!(php_array_iter_value_int(it), 42);
+            php_array_iter_free(it);
+            php_array_release(shared);
+            php_array_release(slot);
+        }
+    }
+
+    #[test]
+    fn test_append_and_spread() {
+        unsafe {
+            // [5 => 'a', 'b', ...['k' => 1, 7 => 2], ...itself]
+            let mut slot = php_array_new(false);
+            let a = PhpString::new(b"a".to_vec());
+            php_value_set_string(php_array_element_int(&mut slot, 5), a);
+            php_value_set_bool(php_array_append(&mut slot), true);
+            let mut source = Array::new(ArrayType::Associative);
+            source.set_by_key("k", Value::Int(1)).unwrap();
+            source.set_by_key("7", Value::Int(2)).unwrap();
+            let source = PhpArray::new(source);
+            php_array_spread(&mut slot, source);
+            php_array_spread(&mut slot, slot);
+
+            let keys: Vec<String> = (*slot).array().iter().map(|(key, _)| to_php_string(&key).unwrap()).collect();
+            assert_eq!(keys, ["5", "6", "k", "7", "8", "9", "10"]);
+            assert!(matches!((*slot).array().get_by_key("7"), Some(Value::Int(2))));
+            crate::strings::php_string_release(a);
+            php_array_release(source);
+            php_array_release(slot);
+        }
+    }
+
+    #[test]
+    fn test_nested_elements() {
+        unsafe {
+            // $a['x'][] = 1; $a['x']['y'] = 2;
+            let mut slot: *mut PhpArray = std::ptr::null_mut();
+            let x = PhpString::new(b"x".to_vec());
+            let y = PhpString::new(b"y".to_vec());
+            php_value_set_int(php_value_append(php_array_element_string(&mut slot, x)), 1);
+            php_value_set_int(php_value_element_string(php_array_element_string(&mut slot, x), y), 2);
+
+            let inner = php_array_get_string(slot, x);
+            assert_eq!(php_value_get_int(php_value_element_int(inner as *mut Value, 0)), 1);
+            assert!(php_array_get_string(slot, y).is_null());
+            // Scalars cannot be indexed into
+            let scalar = php_value_element_int(inner as *mut Value, 0);
+            assert!(php_value_append(scalar).is_null());
+            crate::strings::php_string_release(x);
+            crate::strings::php_string_release(y);
+            php_array_release(slot);
+        }
+    }
+}
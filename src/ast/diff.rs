@@ -0,0 +1,230 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Declaration-level diffing of two parses of the same file.
+//!
+//! Each top-level declaration is reduced to a fingerprint; comparing the
+//! fingerprint maps tells watch mode which functions and classes an edit
+//! changed, and whether it changed any at all. Loose top-level code is
+//! grouped into a single `{main}` entry.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use super::AstNode;
+
+/// Name used for top-level code outside any declaration
+pub const MAIN_NAME: &str = "{main}";
+
+/// Kind of a top-level declaration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DeclKind {
+    Function,
+    Class,
+    Interface,
+    Trait,
+    Enum,
+    Script,
+}
+
+/// Identity of a top-level declaration (namespace-qualified)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DeclKey {
+    pub kind: DeclKind,
+    pub name: String,
+}
+
+impl fmt::Display for DeclKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {}", self.kind, self.name)
+    }
+}
+
+/// Declarations that differ between two parses
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AstDiff {
+    pub added: Vec<DeclKey>,
+    pub removed: Vec<DeclKey>,
+    pub modified: Vec<DeclKey>,
+    pub unchanged: Vec<DeclKey>,
+}
+
+impl AstDiff {
+    /// Check if nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    /// Declarations that are new or differ from the old parse
+    pub fn changed(&self) -> impl Iterator<Item = &DeclKey> {
+        self.added.iter().chain(self.modified.iter())
+    }
+
+    /// Names of added or modified functions
+    pub fn changed_functions(&self) -> Vec<&str> {
+        self.changed()
+            .filter(|key| key.kind == DeclKind::Function)
+            .map(|key| key.name.as_str())
+            .collect()
+    }
+}
+
+/// Compare two parses of a file
+pub fn diff(old: &[AstNode], new: &[AstNode]) -> AstDiff {
+    let old = fingerprints(old);
+    let new = fingerprints(new);
+    let mut result = AstDiff::default();
+
+    for (key, hash) in &new {
+        match old.get(key) {
+            None => result.added.push(key.clone()),
+            Some(old_hash) if old_hash != hash => result.modified.push(key.clone()),
+            Some(_) => result.unchanged.push(key.clone()),
+        }
+    }
+    result.removed = old.keys().filter(|key| !new.contains_key(key)).cloned().collect();
+
+    result
+}
+
+/// Fingerprint every top-level declaration in a parse
+pub fn fingerprints(ast: &[AstNode]) -> BTreeMap<DeclKey, u64> {
+    let mut decls = BTreeMap::new();
+    let mut main = DefaultHasher::new();
+    let mut has_main = false;
+    collect(ast, None, &mut decls, &mut main, &mut has_main);
+
+    if has_main {
+        decls.insert(
+            DeclKey { kind: DeclKind::Script, name: MAIN_NAME.to_string() },
+            main.finish(),
+        );
+    }
+    decls
+}
+
+/// Fingerprint a single node, hashing every field of its tree
+pub fn fingerprint(node: &AstNode) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    node.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn collect(
+    ast: &[AstNode],
+    namespace: Option<&str>,
+    decls: &mut BTreeMap<DeclKey, u64>,
+    main: &mut DefaultHasher,
+    has_main: &mut bool,
+) {
+    for node in ast {
+        let (kind, name) = match node {
+            AstNode::Program(nodes) => {
+                collect(nodes, namespace, decls, main, has_main);
+                continue;
+            }
+            AstNode::Namespace(ns) => {
+                collect(&ns.statements, ns.name.as_deref(), decls, main, has_main);
+                continue;
+            }
+            AstNode::Function(f) => (DeclKind::Function, &f.name),
+            AstNode::Class(c) => (DeclKind::Class, &c.name),
+            AstNode::Interface(i) => (DeclKind::Interface, &i.name),
+            AstNode::Trait(t) => (DeclKind::Trait, &t.name),
+            AstNode::Enum(e) => (DeclKind::Enum, &e.name),
+            _ => {
+                // Order matters for script code, so hash it sequentially
+                node.hash(main);
+                *has_main = true;
+                continue;
+            }
+        };
+
+        // Names may already be fully qualified by `names::resolve_names`
+        let name = match namespace {
+            Some(ns) if !name.starts_with(&format!("{}\\", ns)) => format!("{}\\{}", ns, name),
+            _ => name.clone(),
+        };
+        decls.insert(DeclKey { kind, name }, fingerprint(node));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Expression, FunctionDecl, Literal, NamespaceDecl, Statement, Visibility};
+
+    fn function(name: &str, value: i64) -> AstNode {
+        AstNode::Function(FunctionDecl {
+            name: name.to_string(),
+            parameters: vec![],
+            return_type: None,
+            body: Box::new(Statement::Return(Some(Box::new(Expression::Literal(Literal::Int(value)))))),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        })
+    }
+
+    #[test]
+    fn test_diff_detects_changes() {
+        let old = vec![function("a", 1), function("b", 2), function("c", 3)];
+        let new = vec![function("a", 1), function("b", 20), function("d", 4)];
+
+        let result = diff(&old, &new);
+        assert_eq!(result.changed_functions(), vec!["d", "b"]);
+        assert_eq!(result.removed[0].name, "c");
+        assert_eq!(result.unchanged[0].name, "a");
+    }
+
+    #[test]
+    fn test_fingerprint_is_structural() {
+        let float = |x: f64| AstNode::Expression(Box::new(Expression::Literal(Literal::Float(x))));
+        assert_eq!(fingerprint(&float(1.5)), fingerprint(&float(1.5)));
+        assert_ne!(fingerprint(&float(1.5)), fingerprint(&float(2.5)));
+        assert_ne!(fingerprint(&function("a", 1)), fingerprint(&function("a", 2)));
+    }
+
+    #[test]
+    fn test_identical_parses() {
+        let ast = vec![
+            function("a", 1),
+            AstNode::Expression(Box::new(Expression::Variable("x".to_string()))),
+        ];
+        assert!(diff(&ast, &ast.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_namespaced_and_main() {
+        let old = vec![AstNode::Namespace(NamespaceDecl {
+            name: Some("App".to_string()),
+            statements: vec![function("run", 1)],
+        })];
+        let new = vec![
+            AstNode::Namespace(NamespaceDecl {
+                name: Some("App".to_string()),
+                statements: vec![function("run", 1)],
+            }),
+            AstNode::Expression(Box::new(Expression::Variable("x".to_string()))),
+        ];
+
+        let result = diff(&old, &new);
+        assert_eq!(result.unchanged[0].name, "App\\run");
+        assert_eq!(result.added[0].name, MAIN_NAME);
+    }
+}
@@ -0,0 +1,602 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::fmt;
+use super::visit::{self, VisitorMut};
+use super::{AstNode, Expression, FunctionDecl, Literal, Span, Statement};
+use crate::types::Type;
+
+/// Stable identifier of an AST node within an [`AstIndex`]
+///
+/// Ids are assigned in pre-order, so indexing the same tree twice yields
+/// the same ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub u32);
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Borrowed reference to any indexed node
+#[derive(Debug, Clone, Copy)]
+pub enum NodeRef<'a> {
+    Node(&'a AstNode),
+    Function(&'a FunctionDecl),
+    Statement(&'a Statement),
+    Expression(&'a Expression),
+}
+
+impl<'a> NodeRef<'a> {
+    /// Short kind name, useful for diagnostics and debugging
+    pub fn kind(&self) -> &'static str {
+        match self {
+            NodeRef::Node(AstNode::Program(_)) => "program",
+            NodeRef::Node(AstNode::Class(_)) => "class",
+            NodeRef::Node(AstNode::Interface(_)) => "interface",
+            NodeRef::Node(AstNode::Trait(_)) => "trait",
+            NodeRef::Node(AstNode::Enum(_)) => "enum",
+            NodeRef::Node(AstNode::Namespace(_)) => "namespace",
+            NodeRef::Node(AstNode::Use(_)) => "use",
+            NodeRef::Node(AstNode::Attribute(_)) => "attribute",
+            NodeRef::Node(_) => "node",
+            NodeRef::Function(_) => "function",
+            NodeRef::Statement(_) => "statement",
+            NodeRef::Expression(_) => "expression",
+        }
+    }
+
+    /// Kind and address of the node, which identify it while the tree is borrowed
+    fn identity(&self) -> (u8, usize) {
+        match *self {
+            NodeRef::Node(node) => (0, node as *const AstNode as usize),
+            NodeRef::Function(decl) => (1, decl as *const FunctionDecl as usize),
+            NodeRef::Statement(stmt) => (2, stmt as *const Statement as usize),
+            NodeRef::Expression(expr) => (3, expr as *const Expression as usize),
+        }
+    }
+}
+
+/// Side-table indexing an AST with node ids, parent links, spans and types
+pub struct AstIndex<'a> {
+    /// Nodes by id
+    nodes: Vec<NodeRef<'a>>,
+
+    /// Parent of each node
+    parents: Vec<Option<NodeId>>,
+
+    /// Source span of each node, when known
+    spans: Vec<Option<Span>>,
+
+    /// Inferred type of each node, when known
+    types: Vec<Option<Type>>,
+
+    /// Ids of the borrowed nodes, by [`NodeRef::identity`]
+    ids: HashMap<(u8, usize), NodeId>,
+}
+
+impl<'a> AstIndex<'a> {
+    /// Index a whole program
+    pub fn build(ast: &'a [AstNode]) -> Self {
+        let mut index = Self::empty();
+        for node in ast {
+            index.visit_node(node, None);
+        }
+        index
+    }
+
+    fn empty() -> Self {
+        Self {
+            nodes: Vec::new(),
+            parents: Vec::new(),
+            spans: Vec::new(),
+            types: Vec::new(),
+            ids: HashMap::new(),
+        }
+    }
+
+    /// Number of indexed nodes
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Check if the index is empty
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Get the node for an id
+    pub fn node(&self, id: NodeId) -> Option<NodeRef<'a>> {
+        self.nodes.get(id.0 as usize).copied()
+    }
+
+    /// Get the parent of a node
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.parents.get(id.0 as usize).copied().flatten()
+    }
+
+    /// Get the direct children of a node
+    pub fn children(&self, id: NodeId) -> Vec<NodeId> {
+        self.parents.iter()
+            .enumerate()
+            .filter(|(_, parent)| **parent == Some(id))
+            .map(|(i, _)| NodeId(i as u32))
+            .collect()
+    }
+
+    /// Get all ancestors of a node, nearest first
+    pub fn ancestors(&self, id: NodeId) -> Vec<NodeId> {
+        let mut ancestors = Vec::new();
+        let mut current = self.parent(id);
+        while let Some(parent) = current {
+            ancestors.push(parent);
+            current = self.parent(parent);
+        }
+        ancestors
+    }
+
+    /// Iterate over all node ids in pre-order
+    pub fn ids(&self) -> impl Iterator<Item = NodeId> {
+        (0..self.nodes.len() as u32).map(NodeId)
+    }
+
+    /// Id of a function or method declaration of the indexed tree
+    pub fn function_id(&self, decl: &FunctionDecl) -> Option<NodeId> {
+        self.ids.get(&NodeRef::Function(decl).identity()).copied()
+    }
+
+    /// Id of a statement of the indexed tree
+    pub fn statement_id(&self, stmt: &Statement) -> Option<NodeId> {
+        self.ids.get(&NodeRef::Statement(stmt).identity()).copied()
+    }
+
+    /// Id of an expression of the indexed tree
+    pub fn expression_id(&self, expr: &Expression) -> Option<NodeId> {
+        self.ids.get(&NodeRef::Expression(expr).identity()).copied()
+    }
+
+    /// Record the source span of a node
+    pub fn set_span(&mut self, id: NodeId, span: Span) {
+        if let Some(slot) = self.spans.get_mut(id.0 as usize) {
+            *slot = Some(span);
+        }
+    }
+
+    /// Get the source span of a node
+    pub fn span(&self, id: NodeId) -> Option<Span> {
+        self.spans.get(id.0 as usize).copied().flatten()
+    }
+
+    /// Record the inferred type of a node
+    pub fn set_type(&mut self, id: NodeId, typ: Type) {
+        if let Some(slot) = self.types.get_mut(id.0 as usize) {
+            *slot = Some(typ);
+        }
+    }
+
+    /// Get the inferred type of a node
+    pub fn type_of(&self, id: NodeId) -> Option<&Type> {
+        self.types.get(id.0 as usize).and_then(|t| t.as_ref())
+    }
+
+    /// Find the innermost node whose span contains a byte offset
+    pub fn find_node_at(&self, offset: usize) -> Option<NodeId> {
+        self.ids()
+            .filter_map(|id| self.span(id).map(|span| (id, span)))
+            .filter(|(_, span)| span.contains(offset))
+            .min_by_key(|(id, span)| (span.len(), std::cmp::Reverse(*id)))
+            .map(|(id, _)| id)
+    }
+
+    /// Allocate an id for a node
+    fn push(&mut self, node: NodeRef<'a>, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len() as u32);
+        self.ids.insert(node.identity(), id);
+        self.nodes.push(node);
+        self.parents.push(parent);
+        self.spans.push(None);
+        self.types.push(None);
+        id
+    }
+
+    fn visit_node(&mut self, node: &'a AstNode, parent: Option<NodeId>) {
+        let id = self.push(NodeRef::Node(node), parent);
+        match node {
+            AstNode::Program(nodes) => {
+                for node in nodes {
+                    self.visit_node(node, Some(id));
+                }
+            }
+            AstNode::Expression(expr) => self.visit_expression(expr, Some(id)),
+            AstNode::Statement(stmt) => self.visit_statement(stmt, Some(id)),
+            AstNode::Function(func_decl) => self.visit_function(func_decl, Some(id)),
+            AstNode::Class(class_decl) => {
+                for prop in &class_decl.properties {
+                    if let Some(default) = &prop.default_value {
+                        self.visit_expression(default, Some(id));
+                    }
+                }
+                for constant in &class_decl.constants {
+                    self.visit_expression(&constant.value, Some(id));
+                }
+                for method in &class_decl.methods {
+                    self.visit_function(method, Some(id));
+                }
+            }
+            AstNode::Interface(interface_decl) => {
+                for constant in &interface_decl.constants {
+                    self.visit_expression(&constant.value, Some(id));
+                }
+                for method in &interface_decl.methods {
+                    self.visit_function(method, Some(id));
+                }
+            }
+            AstNode::Trait(trait_decl) => {
+                for prop in &trait_decl.properties {
+                    if let Some(default) = &prop.default_value {
+                        self.visit_expression(default, Some(id));
+                    }
+                }
+                for method in &trait_decl.methods {
+                    self.visit_function(method, Some(id));
+                }
+            }
+            AstNode::Enum(enum_decl) => {
+                for case in &enum_decl.cases {
+                    if let Some(value) = &case.value {
+                        self.visit_expression(value, Some(id));
+                    }
+                }
+                for method in &enum_decl.methods {
+                    self.visit_function(method, Some(id));
+                }
+            }
+            AstNode::Namespace(namespace) => {
+                for node in &namespace.statements {
+                    self.visit_node(node, Some(id));
+                }
+            }
+            AstNode::Use(_) => {}
+            AstNode::Attribute(attribute) => {
+                for arg in &attribute.arguments {
+                    self.visit_expression(arg, Some(id));
+                }
+            }
+        }
+    }
+
+    fn visit_function(&mut self, func_decl: &'a FunctionDecl, parent: Option<NodeId>) {
+        let id = self.push(NodeRef::Function(func_decl), parent);
+        for param in &func_decl.parameters {
+            if let Some(default) = &param.default_value {
+                self.visit_expression(default, Some(id));
+            }
+        }
+        self.visit_statement(&func_decl.body, Some(id));
+    }
+
+    fn visit_statement(&mut self, stmt: &'a Statement, parent: Option<NodeId>) {
+        let id = Some(self.push(NodeRef::Statement(stmt), parent));
+        match stmt {
+            Statement::Expression(expr)
+            | Statement::Throw(expr)
+            | Statement::Print(expr)
+            | Statement::Empty(expr) => self.visit_expression(expr, id),
+            Statement::Block(statements) => {
+                for stmt in statements {
+                    self.visit_statement(stmt, id);
+                }
+            }
+            Statement::If { condition, then_branch, else_branch } => {
+                self.visit_expression(condition, id);
+                self.visit_statement(then_branch, id);
+                if let Some(else_stmt) = else_branch {
+                    self.visit_statement(else_stmt, id);
+                }
+            }
+            Statement::While { condition, body } => {
+                self.visit_expression(condition, id);
+                self.visit_statement(body, id);
+            }
+            Statement::DoWhile { body, condition } => {
+                self.visit_statement(body, id);
+                self.visit_expression(condition, id);
+            }
+            Statement::For { init, condition, update, body } => {
+                for expr in init.iter().chain(condition).chain(update) {
+                    self.visit_expression(expr, id);
+                }
+                self.visit_statement(body, id);
+            }
+            Statement::Foreach { array, body, .. } => {
+                self.visit_expression(array, id);
+                self.visit_statement(body, id);
+            }
+            Statement::Switch { expression, cases } => {
+                self.visit_expression(expression, id);
+                for case in cases {
+                    if let Some(condition) = &case.condition {
+                        self.visit_expression(condition, id);
+                    }
+                    for stmt in &case.statements {
+                        self.visit_statement(stmt, id);
+                    }
+                }
+            }
+            Statement::Match { expression, arms, .. } => {
+                self.visit_expression(expression, id);
+                for arm in arms {
+                    for pattern in &arm.patterns {
+                        self.visit_expression(pattern, id);
+                    }
+                    self.visit_statement(&arm.body, id);
+                }
+            }
+            Statement::Try { try_block, catch_blocks, finally_block } => {
+                self.visit_statement(try_block, id);
+                for catch in catch_blocks {
+                    self.visit_statement(&catch.body, id);
+                }
+                if let Some(finally) = finally_block {
+                    self.visit_statement(finally, id);
+                }
+            }
+            Statement::Return(expr)
+            | Statement::Break(expr)
+            | Statement::Continue(expr)
+            | Statement::Die(expr) => {
+                if let Some(expr) = expr {
+                    self.visit_expression(expr, id);
+                }
+            }
+            Statement::Echo(exprs)
+            | Statement::Unset(exprs)
+            | Statement::Isset(exprs) => {
+                for expr in exprs {
+                    self.visit_expression(expr, id);
+                }
+            }
+            Statement::Declare { directives, body } => {
+                for directive in directives {
+                    self.visit_expression(&directive.value, id);
+                }
+                self.visit_statement(body, id);
+            }
+            Statement::Declaration(node) => self.visit_node(node, id),
+            Statement::Global(_) | Statement::Static(_) => {}
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &'a Expression, parent: Option<NodeId>) {
+        let id = Some(self.push(NodeRef::Expression(expr), parent));
+        match expr {
+            Expression::Literal(Literal::Array(elements))
+            | Expression::Array { elements } => {
+                for element in elements {
+                    if let Some(key) = &element.key {
+                        self.visit_expression(key, id);
+                    }
+                    self.visit_expression(&element.value, id);
+                }
+            }
+            Expression::Literal(_) | Expression::Variable(_) | Expression::Constant(_) => {}
+            Expression::VariableVariable(inner)
+            | Expression::Clone(inner)
+            | Expression::UnaryOp { expr: inner, .. }
+            | Expression::Cast { expr: inner, .. }
+            | Expression::PropertyAccess { object: inner, .. }
+            | Expression::StaticPropertyAccess { class: inner, .. }
+            | Expression::ClassConstant { class: inner, .. }
+            | Expression::Include { file: inner, .. } => self.visit_expression(inner, id),
+            Expression::BinaryOp { left, right, .. }
+            | Expression::ShortTernary { condition: left, false_expr: right }
+            | Expression::NullCoalescing { left, right } => {
+                self.visit_expression(left, id);
+                self.visit_expression(right, id);
+            }
+            Expression::FunctionCall { name, arguments } => {
+                self.visit_expression(name, id);
+                for arg in arguments {
+                    self.visit_expression(arg, id);
+                }
+            }
+            Expression::MethodCall { object, arguments, .. } | Expression::StaticCall { class: object, arguments, .. } => {
+                self.visit_expression(object, id);
+                for arg in arguments {
+                    self.visit_expression(arg, id);
+                }
+            }
+            Expression::New { class, arguments } => {
+                self.visit_expression(class, id);
+                for arg in arguments {
+                    self.visit_expression(arg, id);
+                }
+            }
+            Expression::ArrayAccess { array, index } => {
+                self.visit_expression(array, id);
+                self.visit_expression(index, id);
+            }
+            Expression::ArrayAppend { array } => self.visit_expression(array, id),
+            Expression::Assignment { target, value, .. } => {
+                self.visit_expression(target, id);
+                self.visit_expression(value, id);
+            }
+            Expression::Ternary { condition, true_expr, false_expr } => {
+                self.visit_expression(condition, id);
+                self.visit_expression(true_expr, id);
+                self.visit_expression(false_expr, id);
+            }
+            Expression::InstanceOf { expr, class } => {
+                self.visit_expression(expr, id);
+                self.visit_expression(class, id);
+            }
+            Expression::Yield { key, value } => {
+                if let Some(key) = key {
+                    self.visit_expression(key, id);
+                }
+                if let Some(value) = value {
+                    self.visit_expression(value, id);
+                }
+            }
+            Expression::List { variables } => {
+                for variable in variables {
+                    self.visit_expression(variable, id);
+                }
+            }
+            Expression::Closure(closure) => {
+                for param in &closure.parameters {
+                    if let Some(default) = &param.default_value {
+                        self.visit_expression(default, id);
+                    }
+                }
+                self.visit_statement(&closure.body, id);
+            }
+        }
+    }
+}
+
+/// Rewrite the expressions of a program with the given ids in place
+///
+/// Ids are those of an [`AstIndex`] of the program. Each expression is
+/// passed to `rewrite` after its children, so rewriting a node never hides
+/// a target below it.
+pub fn rewrite_expressions<F>(ast: &mut [AstNode], ids: impl IntoIterator<Item = NodeId>, rewrite: F)
+where
+    F: FnMut(NodeId, &mut Expression),
+{
+    let targets = targets(ast, ids);
+    let mut rewriter = ExpressionRewriter { targets, rewrite };
+    ast.iter_mut().for_each(|node| rewriter.visit_node(node));
+}
+
+/// Rewrite the statements of a program with the given ids in place, like
+/// [`rewrite_expressions`]
+pub fn rewrite_statements<F>(ast: &mut [AstNode], ids: impl IntoIterator<Item = NodeId>, rewrite: F)
+where
+    F: FnMut(NodeId, &mut Statement),
+{
+    let targets = targets(ast, ids);
+    let mut rewriter = StatementRewriter { targets, rewrite };
+    ast.iter_mut().for_each(|node| rewriter.visit_node(node));
+}
+
+/// Ids of the nodes to rewrite, by [`NodeRef::identity`]
+fn targets(ast: &[AstNode], ids: impl IntoIterator<Item = NodeId>) -> HashMap<(u8, usize), NodeId> {
+    let index = AstIndex::build(ast);
+    ids.into_iter()
+        .filter_map(|id| index.node(id).map(|node| (node.identity(), id)))
+        .collect()
+}
+
+struct ExpressionRewriter<F> {
+    targets: HashMap<(u8, usize), NodeId>,
+    rewrite: F,
+}
+
+impl<F: FnMut(NodeId, &mut Expression)> VisitorMut for ExpressionRewriter<F> {
+    fn visit_expression(&mut self, expr: &mut Expression) {
+        let id = self.targets.remove(&NodeRef::Expression(expr).identity());
+        visit::walk_expression(self, expr);
+        if let Some(id) = id {
+            (self.rewrite)(id, expr);
+        }
+    }
+}
+
+struct StatementRewriter<F> {
+    targets: HashMap<(u8, usize), NodeId>,
+    rewrite: F,
+}
+
+impl<F: FnMut(NodeId, &mut Statement)> VisitorMut for StatementRewriter<F> {
+    fn visit_statement(&mut self, stmt: &mut Statement) {
+        let id = self.targets.remove(&NodeRef::Statement(stmt).identity());
+        visit::walk_statement(self, stmt);
+        if let Some(id) = id {
+            (self.rewrite)(id, stmt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::BinaryOperator;
+
+    fn sample_ast() -> Vec<AstNode> {
+        vec![AstNode::Program(vec![AstNode::Statement(Box::new(Statement::Echo(vec![
+            Expression::BinaryOp {
+                left: Box::new(Expression::Literal(Literal::Int(1))),
+                op: BinaryOperator::Add,
+                right: Box::new(Expression::Literal(Literal::Int(2))),
+            },
+        ])))])]
+    }
+
+    #[test]
+    fn test_ids_and_parents() {
+        let ast = sample_ast();
+        let index = AstIndex::build(&ast);
+
+        // program, statement node, echo, binary op, two literals
+        assert_eq!(index.len(), 6);
+        assert_eq!(index.parent(NodeId(0)), None);
+        assert_eq!(index.parent(NodeId(3)), Some(NodeId(2)));
+        assert_eq!(index.children(NodeId(3)), vec![NodeId(4), NodeId(5)]);
+        assert_eq!(index.ancestors(NodeId(5)), vec![NodeId(3), NodeId(2), NodeId(1), NodeId(0)]);
+        assert_eq!(index.node(NodeId(2)).unwrap().kind(), "statement");
+    }
+
+    #[test]
+    fn test_find_node_at() {
+        let ast = sample_ast();
+        let mut index = AstIndex::build(&ast);
+        index.set_span(NodeId(2), Span::new(0, 11));
+        index.set_span(NodeId(3), Span::new(5, 10));
+        index.set_span(NodeId(5), Span::new(9, 10));
+
+        assert_eq!(index.find_node_at(9), Some(NodeId(5)));
+        assert_eq!(index.find_node_at(6), Some(NodeId(3)));
+        assert_eq!(index.find_node_at(1), Some(NodeId(2)));
+        assert_eq!(index.find_node_at(42), None);
+    }
+
+    #[test]
+    fn test_type_side_table() {
+        let ast = sample_ast();
+        let mut index = AstIndex::build(&ast);
+        index.set_type(NodeId(3), Type::Int);
+        assert_eq!(index.type_of(NodeId(3)), Some(&Type::Int));
+        assert_eq!(index.type_of(NodeId(4)), None);
+    }
+
+    #[test]
+    fn test_ids_survive_clones() {
+        let ast = sample_ast();
+        let index = AstIndex::build(&ast);
+        let Some(NodeRef::Expression(literal)) = index.node(NodeId(5)) else { panic!() };
+        assert_eq!(index.expression_id(literal), Some(NodeId(5)));
+        assert_eq!(index.expression_id(&Expression::Literal(Literal::Int(2))), None);
+
+        // An id found in one copy rewrites the same node of another
+        let mut copy = ast.clone();
+        rewrite_expressions(&mut copy, [NodeId(5)], |_, expr| *expr = Expression::Literal(Literal::Int(40)));
+        let index = AstIndex::build(&copy);
+        assert!(matches!(index.node(NodeId(5)), Some(NodeRef::Expression(Expression::Literal(Literal::Int(40))))));
+    }
+}
@@ -17,8 +17,44 @@
 use std::fmt;
 use crate::types::Type;
 
+pub mod diff;
+pub mod index;
+pub mod visit;
+
+pub use diff::{AstDiff, DeclKey, DeclKind};
+pub use index::{AstIndex, NodeId, NodeRef};
+pub use visit::{Visitor, VisitorMut};
+
+/// Byte range of a node in its source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+    
+    /// Length in bytes
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+    
+    /// Check if the span is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
+    /// Check if a byte offset falls inside the span
+    pub fn contains(&self, offset: usize) -> bool {
+        offset >= self.start && offset < self.end
+    }
+}
+
 /// PHP AST node
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub enum AstNode {
     /// Program root
     Program(Vec<AstNode>),
@@ -43,7 +79,7 @@ pub enum AstNode {
 }
 
 /// Expression node
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub enum Expression {
     /// Literals
     Literal(Literal),
@@ -52,6 +88,9 @@ pub enum Expression {
     Variable(String),
     VariableVariable(Box<Expression>),
     
+    /// Constant fetch (e.g. `PHP_EOL`)
+    Constant(String),
+    
     /// Binary operations
     BinaryOp {
         left: Box<Expression>,
@@ -76,12 +115,35 @@ pub enum Expression {
         object: Box<Expression>,
         method: String,
         arguments: Vec<Expression>,
+        /// `?->`: the rest of the chain is skipped when the object is null
+        nullsafe: bool,
+    },
+    
+    /// Static method call (e.g. `Factory::create()`, `static::create()`)
+    StaticCall {
+        class: Box<Expression>,
+        method: String,
+        arguments: Vec<Expression>,
     },
     
     /// Property access
     PropertyAccess {
         object: Box<Expression>,
         property: String,
+        /// `?->`: the rest of the chain is skipped when the object is null
+        nullsafe: bool,
+    },
+    
+    /// Static property access (e.g. `Counter::$count`, `static::$instances`)
+    StaticPropertyAccess {
+        class: Box<Expression>,
+        property: String,
+    },
+    
+    /// Class constant or enum case (e.g. `Suit::Hearts`)
+    ClassConstant {
+        class: Box<Expression>,
+        name: String,
     },
     
     /// Array access
@@ -90,6 +152,11 @@ pub enum Expression {
         index: Box<Expression>,
     },
     
+    /// `$array[]`, which only appears as the target of a write
+    ArrayAppend {
+        array: Box<Expression>,
+    },
+    
     /// Assignment
     Assignment {
         target: Box<Expression>,
@@ -104,6 +171,12 @@ pub enum Expression {
         false_expr: Box<Expression>,
     },
     
+    /// Short ternary `a ?: b`, evaluating `a` once
+    ShortTernary {
+        condition: Box<Expression>,
+        false_expr: Box<Expression>,
+    },
+    
     /// Null coalescing
     NullCoalescing {
         left: Box<Expression>,
@@ -152,10 +225,13 @@ pub enum Expression {
     List {
         variables: Vec<Expression>,
     },
+    
+    /// Closure or arrow function
+    Closure(Box<ClosureDecl>),
 }
 
 /// Statement node
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub enum Statement {
     /// Expression statement
     Expression(Box<Expression>),
@@ -208,6 +284,9 @@ pub enum Statement {
     Match {
         expression: Box<Expression>,
         arms: Vec<MatchArm>,
+        /// Every value the subject can take has an arm, so no
+        /// `UnhandledMatchError` path is needed
+        exhaustive: bool,
     },
     
     /// Try-catch
@@ -258,6 +337,9 @@ pub enum Statement {
         directives: Vec<DeclareDirective>,
         body: Box<Statement>,
     },
+
+    /// Function/class declared inside a block (conditional definition)
+    Declaration(Box<AstNode>),
 }
 
 /// Literal values
@@ -272,7 +354,7 @@ pub enum Literal {
 }
 
 /// Binary operators
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Hash)]
 pub enum BinaryOperator {
     Add,        // +
     Sub,        // -
@@ -302,7 +384,7 @@ pub enum BinaryOperator {
 }
 
 /// Unary operators
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Hash)]
 pub enum UnaryOperator {
     Plus,       // +
     Minus,      // -
@@ -316,7 +398,7 @@ pub enum UnaryOperator {
 }
 
 /// Assignment operators
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Hash)]
 pub enum AssignmentOperator {
     Assign,     // =
     AddAssign,  // +=
@@ -335,7 +417,7 @@ pub enum AssignmentOperator {
 }
 
 /// Function declaration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct FunctionDecl {
     pub name: String,
     pub parameters: Vec<Parameter>,
@@ -344,14 +426,38 @@ pub struct FunctionDecl {
     pub attributes: Vec<Attribute>,
     pub is_static: bool,
     pub visibility: Visibility,
+    /// Docblock preceding the declaration
+    pub doc_comment: Option<String>,
+}
+
+/// Anonymous function (`function () use (...) {}` or `fn () => ...`)
+#[derive(Debug, Clone, Hash)]
+pub struct ClosureDecl {
+    pub parameters: Vec<Parameter>,
+    pub return_type: Option<Type>,
+    /// Variables captured by `use`; arrow functions capture by value implicitly
+    pub uses: Vec<ClosureUse>,
+    /// Body; an arrow function's expression is wrapped in a `return`
+    pub body: Box<Statement>,
+    pub is_arrow: bool,
+    pub is_static: bool,
+}
+
+/// Variable captured by a closure's `use` clause
+#[derive(Debug, Clone, Hash)]
+pub struct ClosureUse {
+    pub name: String,
+    pub by_reference: bool,
 }
 
 /// Class declaration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct ClassDecl {
     pub name: String,
     pub extends: Option<String>,
     pub implements: Vec<String>,
+    /// `use` of traits in the class body
+    pub traits: Vec<TraitUse>,
     pub properties: Vec<PropertyDecl>,
     pub methods: Vec<FunctionDecl>,
     pub constants: Vec<ConstantDecl>,
@@ -363,8 +469,33 @@ pub struct ClassDecl {
     pub is_enum: bool,
 }
 
+/// `use A, B { ... }` inside a class body
+#[derive(Debug, Clone, Hash)]
+pub struct TraitUse {
+    pub traits: Vec<String>,
+    pub adaptations: Vec<TraitAdaptation>,
+}
+
+/// Rule in the block of a trait `use`
+#[derive(Debug, Clone, Hash)]
+pub enum TraitAdaptation {
+    /// `A::method insteadof B, C;`
+    Precedence {
+        trait_name: String,
+        method: String,
+        instead_of: Vec<String>,
+    },
+    /// `[A::]method as [visibility] [alias];`
+    Alias {
+        trait_name: Option<String>,
+        method: String,
+        alias: Option<String>,
+        visibility: Option<Visibility>,
+    },
+}
+
 /// Parameter declaration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct Parameter {
     pub name: String,
     pub typ: Option<Type>,
@@ -374,7 +505,7 @@ pub struct Parameter {
 }
 
 /// Property declaration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct PropertyDecl {
     pub name: String,
     pub typ: Option<Type>,
@@ -382,10 +513,12 @@ pub struct PropertyDecl {
     pub visibility: Visibility,
     pub is_static: bool,
     pub is_readonly: bool,
+    /// Docblock preceding the declaration
+    pub doc_comment: Option<String>,
 }
 
 /// Constant declaration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct ConstantDecl {
     pub name: String,
     pub value: Expression,
@@ -393,7 +526,7 @@ pub struct ConstantDecl {
 }
 
 /// Interface declaration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct InterfaceDecl {
     pub name: String,
     pub extends: Vec<String>,
@@ -402,7 +535,7 @@ pub struct InterfaceDecl {
 }
 
 /// Trait declaration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct TraitDecl {
     pub name: String,
     pub properties: Vec<PropertyDecl>,
@@ -411,7 +544,7 @@ pub struct TraitDecl {
 }
 
 /// Enum declaration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct EnumDecl {
     pub name: String,
     pub backing_type: Option<Type>,
@@ -420,35 +553,35 @@ pub struct EnumDecl {
 }
 
 /// Enum case
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct EnumCase {
     pub name: String,
     pub value: Option<Expression>,
 }
 
 /// Namespace declaration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct NamespaceDecl {
     pub name: Option<String>,
     pub statements: Vec<AstNode>,
 }
 
 /// Use declaration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct UseDecl {
     pub uses: Vec<UseClause>,
     pub kind: UseKind,
 }
 
 /// Use clause
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct UseClause {
     pub name: String,
     pub alias: Option<String>,
 }
 
 /// Use kind
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub enum UseKind {
     Normal,
     Function,
@@ -456,7 +589,7 @@ pub enum UseKind {
 }
 
 /// Visibility
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Hash)]
 pub enum Visibility {
     Public,
     Protected,
@@ -464,36 +597,38 @@ pub enum Visibility {
 }
 
 /// Attribute
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct Attribute {
     pub name: String,
     pub arguments: Vec<Expression>,
 }
 
 /// Array element
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct ArrayElement {
     pub key: Option<Expression>,
     pub value: Expression,
     pub is_reference: bool,
+    /// `...$value`, which adds the elements of the value
+    pub is_spread: bool,
 }
 
 /// Switch case
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct SwitchCase {
     pub condition: Option<Expression>,
     pub statements: Vec<Statement>,
 }
 
 /// Match arm
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct MatchArm {
     pub patterns: Vec<Expression>,
     pub body: Box<Statement>,
 }
 
 /// Catch block
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct CatchBlock {
     pub types: Vec<Type>,
     pub variable: Option<String>,
@@ -501,14 +636,14 @@ pub struct CatchBlock {
 }
 
 /// Declare directive
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct DeclareDirective {
     pub name: String,
     pub value: Expression,
 }
 
 /// Include kind
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub enum IncludeKind {
     Include,
     IncludeOnce,
@@ -516,6 +651,42 @@ pub enum IncludeKind {
     RequireOnce,
 }
 
+// Floats hash by their bits, so every field of a literal is covered
+impl std::hash::Hash for Literal {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Literal::Int(n) => n.hash(state),
+            Literal::Float(x) => x.to_bits().hash(state),
+            Literal::String(s) => s.hash(state),
+            Literal::Bool(b) => b.hash(state),
+            Literal::Null => {}
+            Literal::Array(elements) => elements.hash(state),
+        }
+    }
+}
+
+impl AssignmentOperator {
+    /// Binary operator applied by a compound assignment
+    pub fn binary_operator(&self) -> Option<BinaryOperator> {
+        Some(match self {
+            AssignmentOperator::AddAssign => BinaryOperator::Add,
+            AssignmentOperator::SubAssign => BinaryOperator::Sub,
+            AssignmentOperator::MulAssign => BinaryOperator::Mul,
+            AssignmentOperator::DivAssign => BinaryOperator::Div,
+            AssignmentOperator::ModAssign => BinaryOperator::Mod,
+            AssignmentOperator::PowAssign => BinaryOperator::Pow,
+            AssignmentOperator::ConcatAssign => BinaryOperator::Concat,
+            AssignmentOperator::BitwiseAndAssign => BinaryOperator::BitwiseAnd,
+            AssignmentOperator::BitwiseOrAssign => BinaryOperator::BitwiseOr,
+            AssignmentOperator::BitwiseXorAssign => BinaryOperator::BitwiseXor,
+            AssignmentOperator::ShiftLeftAssign => BinaryOperator::ShiftLeft,
+            AssignmentOperator::ShiftRightAssign => BinaryOperator::ShiftRight,
+            AssignmentOperator::Assign | AssignmentOperator::CoalesceAssign => return None,
+        })
+    }
+}
+
 impl fmt::Display for BinaryOperator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -0,0 +1,546 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! AST traversal for analyses and rewriting passes.
+//!
+//! Implement [`Visitor`] to read the tree or [`VisitorMut`] to rewrite it,
+//! and override the hooks of interest; call the matching `walk_*_ref` or
+//! `walk_*` function from an override to keep descending.
+
+use super::{ArrayElement, AstNode, Expression, FunctionDecl, Statement};
+
+/// Mutable visitor over the AST
+pub trait VisitorMut {
+    fn visit_node(&mut self, node: &mut AstNode) {
+        walk_node(self, node);
+    }
+
+    fn visit_function(&mut self, function: &mut FunctionDecl) {
+        walk_function(self, function);
+    }
+
+    fn visit_statement(&mut self, stmt: &mut Statement) {
+        walk_statement(self, stmt);
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression) {
+        walk_expression(self, expr);
+    }
+}
+
+/// Visit the children of a top-level node
+pub fn walk_node<V: VisitorMut + ?Sized>(visitor: &mut V, node: &mut AstNode) {
+    match node {
+        AstNode::Program(nodes) => nodes.iter_mut().for_each(|n| visitor.visit_node(n)),
+        AstNode::Expression(expr) => visitor.visit_expression(expr),
+        AstNode::Statement(stmt) => visitor.visit_statement(stmt),
+        AstNode::Function(function) => visitor.visit_function(function),
+        AstNode::Class(class) => {
+            for property in &mut class.properties {
+                if let Some(default) = &mut property.default_value {
+                    visitor.visit_expression(default);
+                }
+            }
+            for constant in &mut class.constants {
+                visitor.visit_expression(&mut constant.value);
+            }
+            class.methods.iter_mut().for_each(|m| visitor.visit_function(m));
+        }
+        AstNode::Interface(interface) => {
+            for constant in &mut interface.constants {
+                visitor.visit_expression(&mut constant.value);
+            }
+            interface.methods.iter_mut().for_each(|m| visitor.visit_function(m));
+        }
+        AstNode::Trait(t) => {
+            for property in &mut t.properties {
+                if let Some(default) = &mut property.default_value {
+                    visitor.visit_expression(default);
+                }
+            }
+            for constant in &mut t.constants {
+                visitor.visit_expression(&mut constant.value);
+            }
+            t.methods.iter_mut().for_each(|m| visitor.visit_function(m));
+        }
+        AstNode::Enum(e) => {
+            for case in &mut e.cases {
+                if let Some(value) = &mut case.value {
+                    visitor.visit_expression(value);
+                }
+            }
+            e.methods.iter_mut().for_each(|m| visitor.visit_function(m));
+        }
+        AstNode::Namespace(ns) => ns.statements.iter_mut().for_each(|n| visitor.visit_node(n)),
+        AstNode::Use(_) => {}
+        AstNode::Attribute(attribute) => {
+            attribute.arguments.iter_mut().for_each(|a| visitor.visit_expression(a));
+        }
+    }
+}
+
+/// Visit parameter defaults and the body of a function
+pub fn walk_function<V: VisitorMut + ?Sized>(visitor: &mut V, function: &mut FunctionDecl) {
+    for param in &mut function.parameters {
+        if let Some(default) = &mut param.default_value {
+            visitor.visit_expression(default);
+        }
+    }
+    visitor.visit_statement(&mut function.body);
+}
+
+/// Visit the children of a statement
+pub fn walk_statement<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Statement) {
+    match stmt {
+        Statement::Expression(expr) | Statement::Throw(expr) | Statement::Print(expr) | Statement::Empty(expr) => {
+            visitor.visit_expression(expr);
+        }
+        Statement::Block(stmts) => stmts.iter_mut().for_each(|s| visitor.visit_statement(s)),
+        Statement::If { condition, then_branch, else_branch } => {
+            visitor.visit_expression(condition);
+            visitor.visit_statement(then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_statement(else_branch);
+            }
+        }
+        Statement::While { condition, body } | Statement::DoWhile { body, condition } => {
+            visitor.visit_expression(condition);
+            visitor.visit_statement(body);
+        }
+        Statement::For { init, condition, update, body } => {
+            init.iter_mut()
+                .chain(condition.iter_mut())
+                .chain(update.iter_mut())
+                .for_each(|e| visitor.visit_expression(e));
+            visitor.visit_statement(body);
+        }
+        Statement::Foreach { array, body, .. } => {
+            visitor.visit_expression(array);
+            visitor.visit_statement(body);
+        }
+        Statement::Switch { expression, cases } => {
+            visitor.visit_expression(expression);
+            for case in cases {
+                if let Some(condition) = &mut case.condition {
+                    visitor.visit_expression(condition);
+                }
+                case.statements.iter_mut().for_each(|s| visitor.visit_statement(s));
+            }
+        }
+        Statement::Match { expression, arms, .. } => {
+            visitor.visit_expression(expression);
+            for arm in arms {
+                arm.patterns.iter_mut().for_each(|p| visitor.visit_expression(p));
+                visitor.visit_statement(&mut arm.body);
+            }
+        }
+        Statement::Try { try_block, catch_blocks, finally_block } => {
+            visitor.visit_statement(try_block);
+            for catch in catch_blocks {
+                visitor.visit_statement(&mut catch.body);
+            }
+            if let Some(finally_block) = finally_block {
+                visitor.visit_statement(finally_block);
+            }
+        }
+        Statement::Return(expr) | Statement::Break(expr) | Statement::Continue(expr) | Statement::Die(expr) => {
+            if let Some(expr) = expr {
+                visitor.visit_expression(expr);
+            }
+        }
+        Statement::Echo(exprs) | Statement::Unset(exprs) | Statement::Isset(exprs) => {
+            exprs.iter_mut().for_each(|e| visitor.visit_expression(e));
+        }
+        Statement::Declare { directives, body } => {
+            directives.iter_mut().for_each(|d| visitor.visit_expression(&mut d.value));
+            visitor.visit_statement(body);
+        }
+        Statement::Declaration(node) => visitor.visit_node(node),
+        Statement::Global(_) | Statement::Static(_) => {}
+    }
+}
+
+/// Visit the children of an expression
+pub fn walk_expression<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expression) {
+    match expr {
+        Expression::Literal(super::Literal::Array(elements)) | Expression::Array { elements } => {
+            walk_elements(visitor, elements);
+        }
+        Expression::Literal(_) | Expression::Variable(_) | Expression::Constant(_) => {}
+        Expression::VariableVariable(inner) | Expression::Clone(inner) => visitor.visit_expression(inner),
+        Expression::BinaryOp { left, right, .. }
+        | Expression::ShortTernary { condition: left, false_expr: right }
+        | Expression::NullCoalescing { left, right } => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        }
+        Expression::UnaryOp { expr, .. } | Expression::Cast { expr, .. } => visitor.visit_expression(expr),
+        Expression::FunctionCall { name, arguments } => {
+            visitor.visit_expression(name);
+            arguments.iter_mut().for_each(|a| visitor.visit_expression(a));
+        }
+        Expression::MethodCall { object, arguments, .. } | Expression::StaticCall { class: object, arguments, .. } => {
+            visitor.visit_expression(object);
+            arguments.iter_mut().for_each(|a| visitor.visit_expression(a));
+        }
+        Expression::PropertyAccess { object, .. }
+        | Expression::StaticPropertyAccess { class: object, .. }
+        | Expression::ClassConstant { class: object, .. } => {
+            visitor.visit_expression(object)
+        }
+        Expression::ArrayAccess { array, index } => {
+            visitor.visit_expression(array);
+            visitor.visit_expression(index);
+        }
+        Expression::ArrayAppend { array } => visitor.visit_expression(array),
+        Expression::Assignment { target, value, .. } => {
+            visitor.visit_expression(target);
+            visitor.visit_expression(value);
+        }
+        Expression::Ternary { condition, true_expr, false_expr } => {
+            visitor.visit_expression(condition);
+            visitor.visit_expression(true_expr);
+            visitor.visit_expression(false_expr);
+        }
+        Expression::InstanceOf { expr, class } => {
+            visitor.visit_expression(expr);
+            visitor.visit_expression(class);
+        }
+        Expression::New { class, arguments } => {
+            visitor.visit_expression(class);
+            arguments.iter_mut().for_each(|a| visitor.visit_expression(a));
+        }
+        Expression::Include { file, .. } => visitor.visit_expression(file),
+        Expression::Yield { key, value } => {
+            if let Some(key) = key {
+                visitor.visit_expression(key);
+            }
+            if let Some(value) = value {
+                visitor.visit_expression(value);
+            }
+        }
+        Expression::List { variables } => variables.iter_mut().for_each(|v| visitor.visit_expression(v)),
+        Expression::Closure(closure) => {
+            for param in &mut closure.parameters {
+                if let Some(default) = &mut param.default_value {
+                    visitor.visit_expression(default);
+                }
+            }
+            visitor.visit_statement(&mut closure.body);
+        }
+    }
+}
+
+fn walk_elements<V: VisitorMut + ?Sized>(visitor: &mut V, elements: &mut [ArrayElement]) {
+    for element in elements {
+        if let Some(key) = &mut element.key {
+            visitor.visit_expression(key);
+        }
+        visitor.visit_expression(&mut element.value);
+    }
+}
+
+/// Read-only visitor over the AST
+pub trait Visitor {
+    fn visit_node(&mut self, node: &AstNode) {
+        walk_node_ref(self, node);
+    }
+
+    fn visit_function(&mut self, function: &FunctionDecl) {
+        walk_function_ref(self, function);
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement) {
+        walk_statement_ref(self, stmt);
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        walk_expression_ref(self, expr);
+    }
+}
+
+/// Visit the children of a top-level node
+pub fn walk_node_ref<V: Visitor + ?Sized>(visitor: &mut V, node: &AstNode) {
+    match node {
+        AstNode::Program(nodes) => nodes.iter().for_each(|n| visitor.visit_node(n)),
+        AstNode::Expression(expr) => visitor.visit_expression(expr),
+        AstNode::Statement(stmt) => visitor.visit_statement(stmt),
+        AstNode::Function(function) => visitor.visit_function(function),
+        AstNode::Class(class) => {
+            for property in &class.properties {
+                if let Some(default) = &property.default_value {
+                    visitor.visit_expression(default);
+                }
+            }
+            for constant in &class.constants {
+                visitor.visit_expression(&constant.value);
+            }
+            class.methods.iter().for_each(|m| visitor.visit_function(m));
+        }
+        AstNode::Interface(interface) => {
+            for constant in &interface.constants {
+                visitor.visit_expression(&constant.value);
+            }
+            interface.methods.iter().for_each(|m| visitor.visit_function(m));
+        }
+        AstNode::Trait(t) => {
+            for property in &t.properties {
+                if let Some(default) = &property.default_value {
+                    visitor.visit_expression(default);
+                }
+            }
+            for constant in &t.constants {
+                visitor.visit_expression(&constant.value);
+            }
+            t.methods.iter().for_each(|m| visitor.visit_function(m));
+        }
+        AstNode::Enum(e) => {
+            for case in &e.cases {
+                if let Some(value) = &case.value {
+                    visitor.visit_expression(value);
+                }
+            }
+            e.methods.iter().for_each(|m| visitor.visit_function(m));
+        }
+        AstNode::Namespace(ns) => ns.statements.iter().for_each(|n| visitor.visit_node(n)),
+        AstNode::Use(_) => {}
+        AstNode::Attribute(attribute) => {
+            attribute.arguments.iter().for_each(|a| visitor.visit_expression(a));
+        }
+    }
+}
+
+/// Visit parameter defaults and the body of a function
+pub fn walk_function_ref<V: Visitor + ?Sized>(visitor: &mut V, function: &FunctionDecl) {
+    for param in &function.parameters {
+        if let Some(default) = &param.default_value {
+            visitor.visit_expression(default);
+        }
+    }
+    visitor.visit_statement(&function.body);
+}
+
+/// Visit the children of a statement
+pub fn walk_statement_ref<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Statement) {
+    match stmt {
+        Statement::Expression(expr) | Statement::Throw(expr) | Statement::Print(expr) | Statement::Empty(expr) => {
+            visitor.visit_expression(expr);
+        }
+        Statement::Block(stmts) => stmts.iter().for_each(|s| visitor.visit_statement(s)),
+        Statement::If { condition, then_branch, else_branch } => {
+            visitor.visit_expression(condition);
+            visitor.visit_statement(then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_statement(else_branch);
+            }
+        }
+        Statement::While { condition, body } | Statement::DoWhile { body, condition } => {
+            visitor.visit_expression(condition);
+            visitor.visit_statement(body);
+        }
+        Statement::For { init, condition, update, body } => {
+            init.iter()
+                .chain(condition.iter())
+                .chain(update.iter())
+                .for_each(|e| visitor.visit_expression(e));
+            visitor.visit_statement(body);
+        }
+        Statement::Foreach { array, body, .. } => {
+            visitor.visit_expression(array);
+            visitor.visit_statement(body);
+        }
+        Statement::Switch { expression, cases } => {
+            visitor.visit_expression(expression);
+            for case in cases {
+                if let Some(condition) = &case.condition {
+                    visitor.visit_expression(condition);
+                }
+                case.statements.iter().for_each(|s| visitor.visit_statement(s));
+            }
+        }
+        Statement::Match { expression, arms, .. } => {
+            visitor.visit_expression(expression);
+            for arm in arms {
+                arm.patterns.iter().for_each(|p| visitor.visit_expression(p));
+                visitor.visit_statement(&arm.body);
+            }
+        }
+        Statement::Try { try_block, catch_blocks, finally_block } => {
+            visitor.visit_statement(try_block);
+            for catch in catch_blocks {
+                visitor.visit_statement(&catch.body);
+            }
+            if let Some(finally_block) = finally_block {
+                visitor.visit_statement(finally_block);
+            }
+        }
+        Statement::Return(expr) | Statement::Break(expr) | Statement::Continue(expr) | Statement::Die(expr) => {
+            if let Some(expr) = expr {
+                visitor.visit_expression(expr);
+            }
+        }
+        Statement::Echo(exprs) | Statement::Unset(exprs) | Statement::Isset(exprs) => {
+            exprs.iter().for_each(|e| visitor.visit_expression(e));
+        }
+        Statement::Declare { directives, body } => {
+            directives.iter().for_each(|d| visitor.visit_expression(&d.value));
+            visitor.visit_statement(body);
+        }
+        Statement::Declaration(node) => visitor.visit_node(node),
+        Statement::Global(_) | Statement::Static(_) => {}
+    }
+}
+
+/// Visit the children of an expression
+pub fn walk_expression_ref<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expression) {
+    match expr {
+        Expression::Literal(super::Literal::Array(elements)) | Expression::Array { elements } => {
+            walk_elements_ref(visitor, elements);
+        }
+        Expression::Literal(_) | Expression::Variable(_) | Expression::Constant(_) => {}
+        Expression::VariableVariable(inner) | Expression::Clone(inner) => visitor.visit_expression(inner),
+        Expression::BinaryOp { left, right, .. }
+        | Expression::ShortTernary { condition: left, false_expr: right }
+        | Expression::NullCoalescing { left, right } => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        }
+        Expression::UnaryOp { expr, .. } | Expression::Cast { expr, .. } => visitor.visit_expression(expr),
+        Expression::FunctionCall { name, arguments } => {
+            visitor.visit_expression(name);
+            arguments.iter().for_each(|a| visitor.visit_expression(a));
+        }
+        Expression::MethodCall { object, arguments, .. } | Expression::StaticCall { class: object, arguments, .. } => {
+            visitor.visit_expression(object);
+            arguments.iter().for_each(|a| visitor.visit_expression(a));
+        }
+        Expression::PropertyAccess { object, .. }
+        | Expression::StaticPropertyAccess { class: object, .. }
+        | Expression::ClassConstant { class: object, .. } => {
+            visitor.visit_expression(object)
+        }
+        Expression::ArrayAccess { array, index } => {
+            visitor.visit_expression(array);
+            visitor.visit_expression(index);
+        }
+        Expression::ArrayAppend { array } => visitor.visit_expression(array),
+        Expression::Assignment { target, value, .. } => {
+            visitor.visit_expression(target);
+            visitor.visit_expression(value);
+        }
+        Expression::Ternary { condition, true_expr, false_expr } => {
+            visitor.visit_expression(condition);
+            visitor.visit_expression(true_expr);
+            visitor.visit_expression(false_expr);
+        }
+        Expression::InstanceOf { expr, class } => {
+            visitor.visit_expression(expr);
+            visitor.visit_expression(class);
+        }
+        Expression::New { class, arguments } => {
+            visitor.visit_expression(class);
+            arguments.iter().for_each(|a| visitor.visit_expression(a));
+        }
+        Expression::Include { file, .. } => visitor.visit_expression(file),
+        Expression::Yield { key, value } => {
+            if let Some(key) = key {
+                visitor.visit_expression(key);
+            }
+            if let Some(value) = value {
+                visitor.visit_expression(value);
+            }
+        }
+        Expression::List { variables } => variables.iter().for_each(|v| visitor.visit_expression(v)),
+        Expression::Closure(closure) => {
+            for param in &closure.parameters {
+                if let Some(default) = &param.default_value {
+                    visitor.visit_expression(default);
+                }
+            }
+            visitor.visit_statement(&closure.body);
+        }
+    }
+}
+
+fn walk_elements_ref<V: Visitor + ?Sized>(visitor: &mut V, elements: &[ArrayElement]) {
+    for element in elements {
+        if let Some(key) = &element.key {
+            visitor.visit_expression(key);
+        }
+        visitor.visit_expression(&element.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Literal;
+
+    struct Renamer;
+
+    impl VisitorMut for Renamer {
+        fn visit_expression(&mut self, expr: &mut Expression) {
+            if let Expression::Variable(name) = expr {
+                name.push_str("_renamed");
+            }
+            walk_expression(self, expr);
+        }
+    }
+
+    #[test]
+    fn test_visitor_rewrites_nested_expressions() {
+        let mut node = AstNode::Statement(Box::new(Statement::If {
+            condition: Box::new(Expression::Variable("a".to_string())),
+            then_branch: Box::new(Statement::Echo(vec![Expression::BinaryOp {
+                left: Box::new(Expression::Variable("b".to_string())),
+                op: crate::ast::BinaryOperator::Add,
+                right: Box::new(Expression::Literal(Literal::Int(1))),
+            }])),
+            else_branch: None,
+        }));
+
+        Renamer.visit_node(&mut node);
+        let rendered = format!("{:?}", node);
+        assert!(rendered.contains("\"a_renamed\""));
+        assert!(rendered.contains("\"b_renamed\""));
+    }
+
+    struct Variables(Vec<String>);
+
+    impl Visitor for Variables {
+        fn visit_expression(&mut self, expr: &Expression) {
+            if let Expression::Variable(name) = expr {
+                self.0.push(name.clone());
+            }
+            walk_expression_ref(self, expr);
+        }
+    }
+
+    #[test]
+    fn test_visitor_reads_nested_expressions() {
+        let stmt = Statement::While {
+            condition: Box::new(Expression::Variable("a".to_string())),
+            body: Box::new(Statement::Echo(vec![Expression::ArrayAccess {
+                array: Box::new(Expression::Variable("b".to_string())),
+                index: Box::new(Expression::Variable("c".to_string())),
+            }])),
+        };
+
+        let mut variables = Variables(Vec::new());
+        variables.visit_statement(&stmt);
+        assert_eq!(variables.0, vec!["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,490 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Compile-time Composer autoloading.
+//!
+//! Classes the program uses but neither declares nor includes are looked up
+//! in the project's PSR-4 mappings, as Composer's autoloader would at
+//! runtime, and the declarations of their files are compiled in, together
+//! with whatever those classes need in turn. The mappings come from
+//! `vendor/composer/autoload_psr4.php` when `composer install` has run,
+//! which covers every installed package, and otherwise from the `autoload`
+//! section of the root `composer.json`.
+//!
+//! Only references that trigger autoloading in PHP are followed: `new`,
+//! static member access, `extends`, `implements` and trait `use`. Type
+//! declarations, `instanceof` and `catch` never load a class.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use log::{debug, info};
+use crate::ast::visit::{self, VisitorMut};
+use crate::ast::{AstNode, Expression};
+use crate::definitions::DefinitionRegistry;
+use crate::error::{CompileError, CompileResult};
+use crate::includes::collect_declarations;
+use crate::utils::path::normalize;
+
+/// Composer's generated PSR-4 map, relative to the vendor directory
+const AUTOLOAD_PSR4: &str = "composer/autoload_psr4.php";
+
+/// PSR-4 namespace prefixes and the directories they map to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Psr4Map {
+    /// Longest prefix first, so the most specific mapping wins
+    prefixes: Vec<(String, Vec<PathBuf>)>,
+}
+
+impl Psr4Map {
+    /// Mappings of the Composer project containing `file`, if there is one
+    pub fn find(file: &Path) -> CompileResult<Option<Self>> {
+        let start = file.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let start = std::fs::canonicalize(start).unwrap_or_else(|_| start.to_path_buf());
+        let Some(root) = start.ancestors().find(|dir| dir.join("composer.json").is_file()) else {
+            return Ok(None);
+        };
+
+        let manifest = root.join("composer.json");
+        let json = std::fs::read_to_string(&manifest)?;
+        let composer: serde_json::Value = serde_json::from_str(&json)
+            .map_err(|e| CompileError::Configuration(format!("{}: {}", manifest.display(), e)))?;
+        let vendor_dir = root.join(composer["config"]["vendor-dir"].as_str().unwrap_or("vendor"));
+
+        let generated = vendor_dir.join(AUTOLOAD_PSR4);
+        let map = match std::fs::read_to_string(&generated) {
+            Ok(source) => {
+                info!("Autoloading classes with {}", generated.display());
+                Self::from_autoload_psr4(&source, &vendor_dir, root)
+                    .map_err(|e| CompileError::Configuration(format!("{}: {}", generated.display(), e)))?
+            }
+            Err(_) => {
+                info!("Autoloading classes with {}", manifest.display());
+                Self::from_composer_json(&composer, root)
+            }
+        };
+        Ok(Some(map))
+    }
+
+    /// Mappings of the `autoload.psr-4` section of a `composer.json`
+    pub fn from_composer_json(composer: &serde_json::Value, root: &Path) -> Self {
+        let mut map = Self::default();
+        if let Some(section) = composer["autoload"]["psr-4"].as_object() {
+            for (prefix, dirs) in section {
+                let dirs = match dirs {
+                    serde_json::Value::Array(dirs) => dirs.iter().filter_map(|d| d.as_str()).collect(),
+                    dir => dir.as_str().into_iter().collect::<Vec<_>>(),
+                };
+                map.insert(prefix, dirs.into_iter().map(|dir| normalize(root.join(dir))).collect());
+            }
+        }
+        map
+    }
+
+    /// Mappings of a Composer-generated `autoload_psr4.php`
+    ///
+    /// The file is read as the fixed-format array Composer writes, since it
+    /// only ever combines quoted strings with `$vendorDir` and `$baseDir`.
+    pub fn from_autoload_psr4(source: &str, vendor_dir: &Path, base_dir: &Path) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        let start = tokens.windows(2)
+            .position(|pair| pair[0] == Token::Word("return".to_string()) && pair[1] != Token::Symbol(';'))
+            .ok_or("no `return array(...)` found")?;
+        let mut parser = ArrayParser { tokens: &tokens[start + 1..], vendor_dir, base_dir };
+
+        let mut map = Self::default();
+        for (prefix, dirs) in parser.mappings()? {
+            map.insert(&prefix, dirs.iter().map(normalize).collect());
+        }
+        Ok(map)
+    }
+
+    fn insert(&mut self, prefix: &str, dirs: Vec<PathBuf>) {
+        let at = self.prefixes.partition_point(|(p, _)| p.len() >= prefix.len());
+        self.prefixes.insert(at, (prefix.to_string(), dirs));
+    }
+
+    /// Files that may declare a fully-qualified class, most specific first
+    pub fn candidates<'a>(&'a self, class: &'a str) -> impl Iterator<Item = PathBuf> + 'a {
+        let class = class.trim_start_matches('\\');
+        self.prefixes.iter()
+            .filter(move |(prefix, _)| class.starts_with(prefix.as_str()))
+            .flat_map(move |(prefix, dirs)| {
+                let relative = format!("{}.php", class[prefix.len()..].replace('\\', "/"));
+                dirs.iter().map(move |dir| dir.join(&relative))
+            })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+}
+
+/// Compiles in the files of classes used but not declared
+pub struct Autoloader<L> {
+    map: Psr4Map,
+
+    /// Parses and annotates one file
+    load: L,
+
+    /// Lowercased names already looked up
+    attempted: HashSet<String>,
+
+    /// Files compiled in so far
+    loaded: Vec<PathBuf>,
+}
+
+impl<L> Autoloader<L>
+where
+    L: FnMut(&Path) -> CompileResult<Vec<AstNode>>,
+{
+    pub fn new(map: Psr4Map, load: L) -> Self {
+        Self { map, load, attempted: HashSet::new(), loaded: Vec::new() }
+    }
+
+    /// Add the declarations of every autoloadable class `ast` depends on
+    ///
+    /// Loaded declarations come before `ast`, those of dependencies first.
+    /// Classes without a file are left for later stages to report.
+    pub fn resolve(&mut self, mut ast: Vec<AstNode>, file: &Path) -> CompileResult<Vec<AstNode>> {
+        let mut definitions = DefinitionRegistry::new();
+        definitions.register(&ast, &file.display().to_string())?;
+
+        let mut rounds = Vec::new();
+        let mut references = class_references(&mut ast);
+        while !references.is_empty() {
+            let mut round = Vec::new();
+            for class in references {
+                if definitions.class(&class).is_some() || !self.attempted.insert(class.to_ascii_lowercase()) {
+                    continue;
+                }
+                if let Some((path, nodes)) = self.load_class(&class)? {
+                    definitions.register(&nodes, &path.display().to_string())?;
+                    nodes.into_iter().for_each(|node| collect_declarations(node, &mut round));
+                    self.loaded.push(path);
+                }
+            }
+            references = class_references(&mut round);
+            rounds.push(round);
+        }
+
+        Ok(rounds.into_iter().rev().flatten().chain(ast).collect())
+    }
+
+    /// Files compiled in by autoloading, in load order
+    pub fn loaded_files(&self) -> impl Iterator<Item = &PathBuf> {
+        self.loaded.iter()
+    }
+
+    fn load_class(&mut self, class: &str) -> CompileResult<Option<(PathBuf, Vec<AstNode>)>> {
+        for path in self.map.candidates(class).collect::<Vec<_>>() {
+            match (self.load)(&path) {
+                Ok(nodes) => {
+                    debug!("Autoloaded {} from {}", class, path.display());
+                    return Ok(Some((path, nodes)));
+                }
+                Err(CompileError::Io(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        debug!("No autoload mapping for {}", class);
+        Ok(None)
+    }
+}
+
+/// Fully-qualified names of the classes `ast` would autoload, in order of use
+fn class_references(ast: &mut [AstNode]) -> Vec<String> {
+    let mut references = ClassReferences::default();
+    ast.iter_mut().for_each(|node| references.visit_node(node));
+    references.names
+}
+
+#[derive(Default)]
+struct ClassReferences {
+    names: Vec<String>,
+    seen: HashSet<String>,
+}
+
+impl ClassReferences {
+    fn add(&mut self, name: &str) {
+        let name = name.trim_start_matches('\\');
+        let reserved = ["self", "static", "parent"].iter().any(|r| name.eq_ignore_ascii_case(r));
+        if !reserved && self.seen.insert(name.to_ascii_lowercase()) {
+            self.names.push(name.to_string());
+        }
+    }
+}
+
+impl VisitorMut for ClassReferences {
+    fn visit_node(&mut self, node: &mut AstNode) {
+        match node {
+            AstNode::Class(class) => {
+                class.extends.iter().chain(&class.implements).for_each(|name| self.add(name));
+                class.traits.iter().flat_map(|t| &t.traits).for_each(|name| self.add(name));
+            }
+            AstNode::Interface(interface) => interface.extends.iter().for_each(|name| self.add(name)),
+            _ => {}
+        }
+        visit::walk_node(self, node);
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression) {
+        match expr {
+            Expression::New { class, .. }
+            | Expression::StaticCall { class, .. }
+            | Expression::StaticPropertyAccess { class, .. }
+            | Expression::ClassConstant { class, .. } => {
+                if let Expression::Constant(name) = class.as_ref() {
+                    self.add(name);
+                }
+            }
+            _ => {}
+        }
+        visit::walk_expression(self, expr);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Str(String),
+    Variable(String),
+    Word(String),
+    Symbol(char),
+    Arrow,
+}
+
+/// Tokens of a PHP array file, without comments and the open tag
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let source = source.trim_start().strip_prefix("<?php").unwrap_or(source);
+    let mut chars = source.chars().peekable();
+    let mut tokens = Vec::new();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            '#' => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                chars.by_ref().find(|&c| std::mem::replace(&mut previous, c) == '*' && c == '/');
+            }
+            '\'' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next().ok_or("unterminated string")? {
+                        '\'' => break,
+                        '\\' if matches!(chars.peek(), Some('\\' | '\'')) => text.push(chars.next().unwrap_or('\\')),
+                        c => text.push(c),
+                    }
+                }
+                tokens.push(Token::Str(text));
+            }
+            '=' if chars.peek() == Some(&'>') => {
+                chars.next();
+                tokens.push(Token::Arrow);
+            }
+            '$' | '_' | 'a'..='z' | 'A'..='Z' => {
+                let mut word = String::from(c);
+                while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphanumeric() || **c == '_') {
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.strip_prefix('$') {
+                    Some(name) => Token::Variable(name.to_string()),
+                    None => Token::Word(word),
+                });
+            }
+            c => tokens.push(Token::Symbol(c)),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Reads `array('Prefix\\' => array($baseDir . '/src'), ...)`
+struct ArrayParser<'a> {
+    tokens: &'a [Token],
+    vendor_dir: &'a Path,
+    base_dir: &'a Path,
+}
+
+impl ArrayParser<'_> {
+    fn mappings(&mut self) -> Result<Vec<(String, Vec<PathBuf>)>, String> {
+        let mut mappings = Vec::new();
+        let close = self.open()?;
+        while !self.eat(&Token::Symbol(close)) {
+            let prefix = match self.next()? {
+                Token::Str(prefix) => prefix,
+                token => return Err(format!("expected a namespace prefix, found {:?}", token)),
+            };
+            self.expect(&Token::Arrow)?;
+            let inner = self.open()?;
+            let mut dirs = Vec::new();
+            while !self.eat(&Token::Symbol(inner)) {
+                dirs.push(PathBuf::from(self.path()?));
+                self.eat(&Token::Symbol(','));
+            }
+            mappings.push((prefix, dirs));
+            self.eat(&Token::Symbol(','));
+        }
+        Ok(mappings)
+    }
+
+    /// `array(` or `[`, returning the closing symbol
+    fn open(&mut self) -> Result<char, String> {
+        if self.eat(&Token::Word("array".to_string())) {
+            self.expect(&Token::Symbol('('))?;
+            Ok(')')
+        } else {
+            self.expect(&Token::Symbol('['))?;
+            Ok(']')
+        }
+    }
+
+    /// Concatenation of strings and directory variables
+    fn path(&mut self) -> Result<String, String> {
+        let mut path = String::new();
+        loop {
+            match self.next()? {
+                Token::Str(text) => path.push_str(&text),
+                Token::Variable(name) if name == "vendorDir" => path.push_str(&self.vendor_dir.display().to_string()),
+                Token::Variable(name) if name == "baseDir" => path.push_str(&self.base_dir.display().to_string()),
+                token => return Err(format!("unsupported path expression {:?}", token)),
+            }
+            if !self.eat(&Token::Symbol('.')) {
+                return Ok(path);
+            }
+        }
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let (token, rest) = self.tokens.split_first().ok_or("unexpected end of file")?;
+        self.tokens = rest;
+        Ok(token.clone())
+    }
+
+    fn eat(&mut self, expected: &Token) -> bool {
+        let matches = self.tokens.first() == Some(expected);
+        if matches {
+            self.tokens = &self.tokens[1..];
+        }
+        matches
+    }
+
+    fn expect(&mut self, expected: &Token) -> Result<(), String> {
+        match self.next()? {
+            token if token == *expected => Ok(()),
+            token => Err(format!("expected {:?}, found {:?}", expected, token)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::ast::{ClassDecl, Statement};
+
+    const GENERATED: &str = r#"<?php
+
+// autoload_psr4.php @generated by Composer
+
+$vendorDir = dirname(__DIR__);
+$baseDir = dirname($vendorDir);
+
+return array(
+    'Psr\\Log\\' => array($vendorDir . '/psr/log/src'),
+    'App\\Models\\' => array($baseDir . '/src/Models', $baseDir . '/lib/models'),
+    'App\\' => array($baseDir . '/src'),
+);
+"#;
+
+    fn class(name: &str, extends: Option<&str>) -> AstNode {
+        AstNode::Class(ClassDecl {
+            name: name.to_string(),
+            extends: extends.map(str::to_string),
+            implements: vec![],
+            traits: vec![],
+            properties: vec![],
+            methods: vec![],
+            constants: vec![],
+            attributes: vec![],
+            is_abstract: false,
+            is_final: false,
+            is_trait: false,
+            is_interface: false,
+            is_enum: false,
+        })
+    }
+
+    fn new_object(class: &str) -> AstNode {
+        AstNode::Statement(Box::new(Statement::Expression(Box::new(Expression::New {
+            class: Box::new(Expression::Constant(class.to_string())),
+            arguments: vec![],
+        }))))
+    }
+
+    #[test]
+    fn test_read_generated_map() {
+        let map = Psr4Map::from_autoload_psr4(GENERATED, Path::new("/app/vendor"), Path::new("/app")).unwrap();
+        let candidates: Vec<PathBuf> = map.candidates("App\\Models\\User").collect();
+        assert_eq!(candidates, [
+            PathBuf::from("/app/src/Models/User.php"),
+            PathBuf::from("/app/lib/models/User.php"),
+            PathBuf::from("/app/src/Models/User.php"),
+        ]);
+        assert_eq!(map.candidates("\\Psr\\Log\\LoggerInterface").next(), Some(PathBuf::from("/app/vendor/psr/log/src/LoggerInterface.php")));
+        assert_eq!(map.candidates("Other\\Thing").count(), 0);
+        assert!(Psr4Map::from_autoload_psr4("<?php return array('A\\\\' => array($x));", Path::new("v"), Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn test_read_composer_json() {
+        let composer = serde_json::json!({
+            "autoload": { "psr-4": { "App\\": "src/", "Lib\\": ["lib/", "extra/"] } }
+        });
+        let map = Psr4Map::from_composer_json(&composer, Path::new("/app"));
+        assert_eq!(map.candidates("App\\Http\\Kernel").next(), Some(PathBuf::from("/app/src/Http/Kernel.php")));
+        assert_eq!(map.candidates("Lib\\Util").count(), 2);
+        assert!(Psr4Map::from_composer_json(&serde_json::json!({}), Path::new("/app")).is_empty());
+    }
+
+    #[test]
+    fn test_loads_dependency_closure() {
+        let map = Psr4Map::from_autoload_psr4(GENERATED, Path::new("/app/vendor"), Path::new("/app")).unwrap();
+        let files = HashMap::from([
+            ("/app/src/Models/User.php", vec![class("App\\Models\\User", Some("App\\Model"))]),
+            ("/app/src/Model.php", vec![class("App\\Model", None)]),
+        ]);
+        let mut autoloader = Autoloader::new(map, |path: &Path| {
+            files.get(path.to_str().unwrap()).cloned().ok_or_else(|| {
+                CompileError::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "no such file"))
+            })
+        });
+
+        let ast = vec![new_object("App\\Models\\User"), new_object("App\\Missing"), new_object("self")];
+        let ast = autoloader.resolve(ast, Path::new("/app/index.php")).unwrap();
+        let classes: Vec<&str> = ast.iter().filter_map(|node| match node {
+            AstNode::Class(class) => Some(class.name.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(classes, ["App\\Model", "App\\Models\\User"]);
+        assert_eq!(ast.len(), 5);
+        assert_eq!(autoloader.loaded_files().count(), 2);
+    }
+}
@@ -0,0 +1,539 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Code generation backends.
+//!
+//! A backend lowers the analyzed AST to its own IR and turns that IR into a
+//! native object file. The LLVM backend emits textual LLVM IR and compiles
+//! it to an object or assembly file with `llc`, or assembles it to bitcode
+//! with `llvm-as`. The Cranelift backend (behind the `cranelift` feature)
+//! needs no external toolchain and compiles much faster, at the cost of
+//! fewer optimizations and a smaller supported language subset.
+
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+use log::{debug, info};
+use crate::ast::{AstNode, FunctionDecl};
+use crate::bundle::Bundle;
+use crate::compiler::CompilerOptions;
+use crate::embed::read_embedded_files;
+use crate::error::{CompileError, CompileResult};
+use crate::ir::IrGenerator;
+use crate::link::LtoMode;
+use crate::profile::Profile;
+
+/// Available code generation backends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackendKind {
+    /// Textual LLVM IR compiled with `llc`
+    #[default]
+    Llvm,
+
+    /// In-process Cranelift code generation
+    Cranelift,
+}
+
+impl BackendKind {
+    /// Whether this build can create the backend
+    pub fn is_available(&self) -> bool {
+        match self {
+            BackendKind::Llvm => true,
+            BackendKind::Cranelift => cfg!(feature = "cranelift"),
+        }
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "llvm" => Ok(BackendKind::Llvm),
+            "cranelift" => Ok(BackendKind::Cranelift),
+            _ => Err(format!("unknown backend '{}' (expected: llvm, cranelift)", s)),
+        }
+    }
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendKind::Llvm => write!(f, "llvm"),
+            BackendKind::Cranelift => write!(f, "cranelift"),
+        }
+    }
+}
+
+/// Lowers an AST to IR and IR to object code
+pub trait Backend {
+    fn kind(&self) -> BackendKind;
+
+    /// Extension of files holding this backend's textual IR
+    fn ir_extension(&self) -> &'static str;
+
+    /// Lower an AST, returning the textual IR
+    fn generate(&mut self, ast: &[AstNode]) -> CompileResult<String>;
+
+    /// Declare a function implemented outside the program, resolved at link time
+    fn declare_external(&mut self, _decl: &FunctionDecl) -> CompileResult<()> {
+        Ok(())
+    }
+
+    /// Program text the interpreter fallback runs, when it was not read
+    /// from the input file
+    fn set_fallback_source(&mut self, _source: &str) {}
+
+    /// Compile the IR returned by the last `generate` call into an object file
+    fn emit_object(&mut self, ir: &str, obj_file: &Path) -> CompileResult<()>;
+
+    /// Write the IR returned by the last `generate` call as LLVM bitcode
+    fn emit_bitcode(&mut self, _ir: &str, _bc_file: &Path) -> CompileResult<()> {
+        Err(CompileError::Configuration(format!(
+            "the {} backend cannot emit LLVM bitcode",
+            self.kind()
+        )))
+    }
+
+    /// Compile the IR returned by the last `generate` call to target assembly
+    fn emit_assembly(&mut self, _ir: &str, _asm_file: &Path) -> CompileResult<()> {
+        Err(CompileError::Configuration(format!(
+            "the {} backend cannot emit assembly",
+            self.kind()
+        )))
+    }
+}
+
+/// Machine code file written from IR
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeFile {
+    /// Relocatable object file
+    Object,
+
+    /// Target assembly (`.s`)
+    Assembly,
+}
+
+impl fmt::Display for CodeFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodeFile::Object => write!(f, "object file"),
+            CodeFile::Assembly => write!(f, "assembly file"),
+        }
+    }
+}
+
+/// Create the backend selected in the options
+pub fn create_backend(options: &CompilerOptions, source_file: &Path) -> CompileResult<Box<dyn Backend>> {
+    match options.backend {
+        BackendKind::Llvm => {
+            let mut generator = IrGenerator::new()?
+                .with_int_width(options.resolved_int_width())
+                .with_target(options.resolved_target())
+                .with_instrumentation(options.instrument)
+                .with_source_file(source_file.display().to_string())
+                .with_embedded_files(read_embedded_files(&options.embed)?);
+            if options.interpreter_fallback && !Bundle::is_bundle_path(source_file) {
+                // The fallback embeds the program, so it needs a readable source
+                match std::fs::read_to_string(source_file) {
+                    Ok(source) => generator = generator.with_interpreter_fallback(source),
+                    Err(e) => debug!("Interpreter fallback disabled, cannot read {}: {}", source_file.display(), e),
+                }
+            }
+            let backend = LlvmBackend::new(generator, &options.optimization_level)
+                .with_passes(options.passes.clone())
+                .with_profile(Profile::from_options(options)?)
+                .with_lto(options.lto)
+                .with_sections(options.release_size);
+            Ok(Box::new(backend))
+        }
+        BackendKind::Cranelift if options.passes.is_some() => Err(CompileError::Configuration(
+            "the cranelift backend does not run LLVM pass pipelines".to_string(),
+        )),
+        BackendKind::Cranelift if !options.embed.is_empty() => Err(CompileError::Configuration(
+            "the cranelift backend does not support embedded files".to_string(),
+        )),
+        BackendKind::Cranelift if options.lto.is_some() => Err(CompileError::Configuration(
+            "the cranelift backend does not support link-time optimization".to_string(),
+        )),
+        BackendKind::Cranelift if Profile::from_options(options)? != Profile::None => Err(CompileError::Configuration(
+            "the cranelift backend does not support profile-guided optimization".to_string(),
+        )),
+        #[cfg(feature = "cranelift")]
+        BackendKind::Cranelift => Ok(Box::new(
+            crate::cranelift::CraneliftBackend::new(&options.optimization_level)
+                .with_int_width(options.resolved_int_width())
+                .with_target(options.resolved_target()),
+        )),
+        #[cfg(not(feature = "cranelift"))]
+        BackendKind::Cranelift => Err(CompileError::Configuration(
+            "php2ir was built without the `cranelift` feature".to_string(),
+        )),
+    }
+}
+
+/// Textual LLVM IR compiled with `llc`
+pub struct LlvmBackend {
+    generator: IrGenerator,
+    optimization_level: String,
+    passes: Option<String>,
+    profile: Profile,
+    lto: Option<LtoMode>,
+    sections: bool,
+}
+
+impl LlvmBackend {
+    pub fn new(generator: IrGenerator, optimization_level: &str) -> Self {
+        Self {
+            generator,
+            optimization_level: optimization_level.to_string(),
+            passes: None,
+            profile: Profile::None,
+            lto: None,
+            sections: false,
+        }
+    }
+
+    /// Run a custom pass pipeline instead of the one of the `-O` level
+    pub fn with_passes(mut self, passes: Option<String>) -> Self {
+        self.passes = passes;
+        self
+    }
+
+    /// Instrument for, or optimize with, a profile
+    pub fn with_profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Emit bitcode objects for link-time optimization
+    pub fn with_lto(mut self, lto: Option<LtoMode>) -> Self {
+        self.lto = lto;
+        self
+    }
+
+    /// Put every function and global in a section of its own, for the
+    /// linker to drop the unused ones
+    pub fn with_sections(mut self, sections: bool) -> Self {
+        self.sections = sections;
+        self
+    }
+
+    /// Verify, optimize and compile `ir` to `out_file` through the LLVM C API
+    #[cfg(feature = "inkwell")]
+    pub fn compile_ir(
+        ir: &str,
+        out_file: &Path,
+        optimization_level: &str,
+        passes: Option<&str>,
+        profile: &Profile,
+        kind: CodeFile,
+        sections: bool,
+    ) -> CompileResult<()> {
+        crate::llvm::compile_ir(ir, out_file, optimization_level, passes, profile, kind, sections)
+    }
+
+    /// Check `ir`, write it next to `out_file` and compile it with `llc`
+    ///
+    /// `opt` runs the pass pipeline first, instrumenting the module for or
+    /// optimizing it with a profile; only an unprofiled `-O0` build skips it.
+    /// With `sections`, every function and global gets a section of its own.
+    #[cfg(not(feature = "inkwell"))]
+    pub fn compile_ir(
+        ir: &str,
+        out_file: &Path,
+        optimization_level: &str,
+        passes: Option<&str>,
+        profile: &Profile,
+        kind: CodeFile,
+        sections: bool,
+    ) -> CompileResult<()> {
+        let ir = profile.prepare_ir(ir);
+        crate::verifier::verify_module(&ir)?;
+        info!("Generating {}", kind);
+
+        let ir_file = out_file.with_extension("ll");
+        std::fs::write(&ir_file, &ir)?;
+        let pipeline = format!("{}{}", profile.passes(), pass_pipeline(optimization_level, passes));
+        let input = if pipeline == "default<O0>" {
+            ir_file
+        } else {
+            let bc_file = ir_file.with_extension("opt.bc");
+            Self::run_opt(&ir_file, &bc_file, &pipeline, profile, &[])?;
+            bc_file
+        };
+
+        let mut cmd = Command::new("llc");
+        // Position-independent, like the in-process path, for PIE links
+        cmd.arg(match kind {
+                CodeFile::Object => "-filetype=obj",
+                CodeFile::Assembly => "-filetype=asm",
+            })
+            .arg("-relocation-model=pic")
+            .arg(format!("-O{}", codegen_level(optimization_level)));
+        if sections {
+            cmd.args(["-function-sections", "-data-sections"]);
+        }
+        cmd.arg("-o")
+            .arg(out_file)
+            .arg(&input);
+
+        let output = cmd.output()
+            .map_err(|e| CompileError::Internal(format!("Failed to run llc: {}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(CompileError::LlvmCompilation(stderr.to_string()));
+        }
+
+        info!("{} generated: {}", kind, out_file.display());
+        Ok(())
+    }
+
+    /// Check `ir` and compile it to a bitcode object for link-time optimization
+    ///
+    /// `opt` writes the object with either build, as the LLVM C API cannot
+    /// attach a ThinLTO summary. Custom `passes` replace the pre-link pipeline.
+    pub fn compile_lto_object(
+        ir: &str,
+        obj_file: &Path,
+        optimization_level: &str,
+        passes: Option<&str>,
+        profile: &Profile,
+        mode: LtoMode,
+    ) -> CompileResult<()> {
+        let ir = profile.prepare_ir(ir);
+        crate::verifier::verify_module(&ir)?;
+        info!("Generating {} LTO bitcode object", mode);
+
+        let ir_file = obj_file.with_extension("ll");
+        std::fs::write(&ir_file, &ir)?;
+        let pre_link = passes.map(str::to_string).unwrap_or_else(|| mode.pre_link_pipeline(optimization_level));
+        let pipeline = format!("{}{}", profile.passes(), pre_link);
+        Self::run_opt(&ir_file, obj_file, &pipeline, profile, mode.bitcode_options())?;
+
+        info!("Bitcode object generated: {}", obj_file.display());
+        Ok(())
+    }
+
+    /// Verify `ir` and run the pass pipeline over it, returning the optimized IR
+    #[cfg(feature = "inkwell")]
+    pub fn optimize_module(ir: &str, _scratch: &Path, optimization_level: &str, passes: Option<&str>) -> CompileResult<String> {
+        crate::llvm::optimize_ir(ir, optimization_level, passes)
+    }
+
+    /// Check `ir` and run the pass pipeline over it with `opt`, returning the
+    /// optimized IR
+    ///
+    /// The module passes through files next to `scratch`, removed afterwards.
+    #[cfg(not(feature = "inkwell"))]
+    pub fn optimize_module(ir: &str, scratch: &Path, optimization_level: &str, passes: Option<&str>) -> CompileResult<String> {
+        crate::verifier::verify_module(ir)?;
+        let ir_file = scratch.with_extension("pre-opt.ll");
+        let opt_file = scratch.with_extension("opt.ll");
+        std::fs::write(&ir_file, ir)?;
+        let result = Self::run_opt(&ir_file, &opt_file, &pass_pipeline(optimization_level, passes), &Profile::None, &["-S"])
+            .and_then(|()| Ok(std::fs::read_to_string(&opt_file)?));
+        let _ = std::fs::remove_file(&ir_file);
+        let _ = std::fs::remove_file(&opt_file);
+        result
+    }
+
+    /// Run `pipeline` over `ir_file` with `opt`, writing bitcode to `output`
+    fn run_opt(ir_file: &Path, output: &Path, pipeline: &str, profile: &Profile, options: &[&str]) -> CompileResult<()> {
+        if *profile == Profile::Generate {
+            let version = Command::new("opt").arg("--version").output()
+                .map_err(|e| CompileError::Internal(format!("Failed to run opt: {}", e)))?;
+            crate::profile::check_llvm_version(&String::from_utf8_lossy(&version.stdout))?;
+        }
+
+        debug!("Running opt -passes={}", pipeline);
+        let result = Command::new("opt")
+            .arg(format!("-passes={}", pipeline))
+            .args(profile.llvm_options())
+            .args(options)
+            .arg("-o")
+            .arg(output)
+            .arg(ir_file)
+            .output()
+            .map_err(|e| CompileError::Internal(format!("Failed to run opt: {}", e)))?;
+
+        if !result.status.success() {
+            let stderr = String::from_utf8_lossy(&result.stderr);
+            return Err(CompileError::LlvmCompilation(stderr.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Verify `ir` and write it to `bc_file` as bitcode through the LLVM C API
+    #[cfg(feature = "inkwell")]
+    pub fn compile_bitcode(ir: &str, bc_file: &Path) -> CompileResult<()> {
+        crate::llvm::write_bitcode(ir, bc_file)
+    }
+
+    /// Check `ir`, write it next to `bc_file` and assemble it with `llvm-as`
+    #[cfg(not(feature = "inkwell"))]
+    pub fn compile_bitcode(ir: &str, bc_file: &Path) -> CompileResult<()> {
+        crate::verifier::verify_module(ir)?;
+        info!("Generating bitcode file");
+
+        let ir_file = bc_file.with_extension("ll");
+        std::fs::write(&ir_file, ir)?;
+
+        let output = Command::new("llvm-as")
+            .arg("-o")
+            .arg(bc_file)
+            .arg(&ir_file)
+            .output()
+            .map_err(|e| CompileError::Internal(format!("Failed to run llvm-as: {}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(CompileError::LlvmCompilation(stderr.to_string()));
+        }
+
+        info!("Bitcode file generated: {}", bc_file.display());
+        Ok(())
+    }
+}
+
+impl Backend for LlvmBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Llvm
+    }
+
+    fn ir_extension(&self) -> &'static str {
+        "ll"
+    }
+
+    fn generate(&mut self, ast: &[AstNode]) -> CompileResult<String> {
+        self.generator.generate(ast)
+    }
+
+    fn declare_external(&mut self, decl: &FunctionDecl) -> CompileResult<()> {
+        self.generator.declare_external(decl)
+    }
+
+    fn set_fallback_source(&mut self, source: &str) {
+        self.generator.set_interpreter_fallback(source);
+    }
+
+    fn emit_object(&mut self, ir: &str, obj_file: &Path) -> CompileResult<()> {
+        match self.lto {
+            Some(mode) => Self::compile_lto_object(ir, obj_file, &self.optimization_level, self.passes.as_deref(), &self.profile, mode),
+            None => Self::compile_ir(ir, obj_file, &self.optimization_level, self.passes.as_deref(), &self.profile, CodeFile::Object, self.sections),
+        }
+    }
+
+    fn emit_bitcode(&mut self, ir: &str, bc_file: &Path) -> CompileResult<()> {
+        Self::compile_bitcode(ir, bc_file)
+    }
+
+    fn emit_assembly(&mut self, ir: &str, asm_file: &Path) -> CompileResult<()> {
+        Self::compile_ir(ir, asm_file, &self.optimization_level, self.passes.as_deref(), &self.profile, CodeFile::Assembly, self.sections)
+    }
+}
+
+/// Pass pipeline run over a module before code generation
+///
+/// `passes` (the `--passes` option, in `opt -passes=` syntax) replaces
+/// LLVM's standard pipeline for the `-O` level.
+pub(crate) fn pass_pipeline(optimization_level: &str, passes: Option<&str>) -> String {
+    match passes {
+        Some(passes) => passes.to_string(),
+        None => format!("default<{}>", pipeline_level(optimization_level)),
+    }
+}
+
+/// Code generator level for an `-O` option, which `llc` takes as `-O0` to `-O3`
+pub(crate) fn codegen_level(optimization_level: &str) -> char {
+    match optimization_level {
+        "O0" => '0',
+        "O1" => '1',
+        "O3" => '3',
+        _ => '2',
+    }
+}
+
+/// Level of the `default<...>` pass pipeline for an `-O` option
+pub(crate) fn pipeline_level(optimization_level: &str) -> &'static str {
+    match optimization_level {
+        "O0" => "O0",
+        "O1" => "O1",
+        "O3" => "O3",
+        "Os" => "Os",
+        "Oz" => "Oz",
+        _ => "O2",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_kind_from_str() {
+        assert_eq!("llvm".parse::<BackendKind>(), Ok(BackendKind::Llvm));
+        assert_eq!("cranelift".parse::<BackendKind>(), Ok(BackendKind::Cranelift));
+        assert!("gcc".parse::<BackendKind>().is_err());
+        assert_eq!(BackendKind::default().to_string(), "llvm");
+    }
+
+    #[test]
+    fn test_create_backend() {
+        let options = CompilerOptions::default();
+        let mut backend = create_backend(&options, Path::new("input.php")).unwrap();
+        assert_eq!(backend.kind(), BackendKind::Llvm);
+        assert!(backend.generate(&[]).unwrap().contains("define i32 @main("));
+
+        let options = CompilerOptions { backend: BackendKind::Cranelift, ..Default::default() };
+        assert_eq!(create_backend(&options, Path::new("input.php")).is_ok(), BackendKind::Cranelift.is_available());
+    }
+
+    #[test]
+    fn test_bitcode_rejects_invalid_ir() {
+        let bc_file = std::env::temp_dir().join("php2ir-invalid.bc");
+        let result = LlvmBackend::compile_bitcode("define i32 @f() {\n  ret i64 0\n}\n", &bc_file);
+        assert!(matches!(result, Err(CompileError::IrGeneration(_))));
+        assert!(!bc_file.exists());
+    }
+
+    #[test]
+    fn test_assembly_rejects_invalid_ir() {
+        let asm_file = std::env::temp_dir().join("php2ir-invalid.s");
+        let result = LlvmBackend::compile_ir("define i32 @f() {\n  ret i64 0\n}\n", &asm_file, "O0", None, &Profile::None, CodeFile::Assembly, false);
+        assert!(matches!(result, Err(CompileError::IrGeneration(_))));
+        assert!(!asm_file.exists());
+        assert_eq!(CodeFile::Assembly.to_string(), "assembly file");
+    }
+
+    #[test]
+    fn test_pass_pipeline() {
+        assert_eq!(pass_pipeline("O3", None), "default<O3>");
+        assert_eq!(pass_pipeline("Oz", None), "default<Oz>");
+        assert_eq!(pass_pipeline("O2", Some("function(mem2reg,instcombine)")), "function(mem2reg,instcombine)");
+        assert_eq!(codegen_level("Oz"), '2');
+        assert_eq!(codegen_level("O0"), '0');
+
+        let options = CompilerOptions {
+            backend: BackendKind::Cranelift,
+            passes: Some("mem2reg".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(create_backend(&options, Path::new("input.php")), Err(CompileError::Configuration(_))));
+    }
+}
@@ -0,0 +1,233 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! PHP stack traces of compiled code.
+//!
+//! Every module registers a table with a row per native function compiled
+//! from PHP: its address with the PHP function, file and line it stands
+//! for. Walking the stack, each return address is mapped through the
+//! unwind tables to the start of the function containing it and that to
+//! its row, so frames of the runtime and of C code drop out. The uncaught
+//! exception handler prints the frames as PHP's stack traces, and
+//! `debug_backtrace()` returns them as arrays.
+//!
+//! Lines are 0, and left out of traces, until the AST carries source spans.
+
+use std::collections::BTreeMap;
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+use std::sync::Mutex;
+use crate::arrays::PhpArray;
+use crate::runtime::{Array, ArrayType, Value};
+
+/// Name of the frame running a file's top-level code
+pub const MAIN: &str = "{main}";
+
+/// Row of a module's frame table; the table ends with a null address
+#[repr(C)]
+#[derive(Debug)]
+pub struct PhpFrameInfo {
+    address: *const c_void,
+    function: *const c_char,
+    file: *const c_char,
+    line: u32,
+}
+
+/// PHP function running in a native frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// `name`, `Class::name` or `Class->name`, or [`MAIN`]
+    pub function: String,
+    pub file: String,
+    pub line: u32,
+}
+
+impl Frame {
+    /// `file:line`, as the site of a throw
+    pub fn location(&self) -> String {
+        match self.line {
+            0 => self.file.clone(),
+            line => format!("{}:{}", self.file, line),
+        }
+    }
+
+    /// `file(line)`, as the site of a call
+    fn call_site(&self) -> String {
+        match self.line {
+            0 => self.file.clone(),
+            line => format!("{}({})", self.file, line),
+        }
+    }
+
+    /// Class, call operator and name of a method
+    fn method(&self) -> Option<(&str, &str, &str)> {
+        ["->", "::"].into_iter().find_map(|operator| {
+            let (class, name) = self.function.split_once(operator)?;
+            Some((class, operator, name))
+        })
+    }
+}
+
+/// Registered frames by the address of their native function
+static FRAMES: Mutex<BTreeMap<usize, Frame>> = Mutex::new(BTreeMap::new());
+
+// The system unwinder (libgcc_s or libunwind)
+
+type TraceFunction = unsafe extern "C" fn(*mut c_void, *mut c_void) -> i32;
+
+extern "C" {
+    fn _Unwind_Backtrace(trace: TraceFunction, parameter: *mut c_void) -> i32;
+    fn _Unwind_GetIP(context: *mut c_void) -> usize;
+    fn _Unwind_FindEnclosingFunction(pc: *mut c_void) -> *mut c_void;
+}
+
+const URC_NO_REASON: i32 = 0;
+
+/// Collect the start of the function of every frame the unwinder visits
+unsafe extern "C" fn visit(context: *mut c_void, parameter: *mut c_void) -> i32 {
+    let starts = &mut *(parameter as *mut Vec<usize>);
+    // The return address may be the start of the next function after a
+    // call that does not return
+    match _Unwind_GetIP(context) {
+        0 => {}
+        ip => starts.push(_Unwind_FindEnclosingFunction((ip - 1) as *mut c_void) as usize),
+    }
+    URC_NO_REASON
+}
+
+/// PHP frames of the current stack, innermost first
+pub fn capture() -> Vec<Frame> {
+    let mut starts: Vec<usize> = Vec::new();
+    unsafe {
+        _Unwind_Backtrace(visit, &mut starts as *mut Vec<usize> as *mut c_void);
+    }
+    let frames = FRAMES.lock().unwrap();
+    starts.iter().filter_map(|start| frames.get(start).cloned()).collect()
+}
+
+/// Frames as the `#0 file(line): function()` lines of a PHP stack trace
+///
+/// Each line names a called function with the site of the call in its
+/// caller; the trace ends with the top-level code.
+pub fn format_trace(frames: &[Frame]) -> String {
+    let mut lines = Vec::new();
+    for (index, frame) in frames.iter().enumerate() {
+        match frames.get(index + 1) {
+            Some(caller) if frame.function != MAIN => {
+                lines.push(format!("#{} {}: {}()", index, caller.call_site(), frame.function));
+            }
+            _ => {
+                lines.push(format!("#{} {}", index, MAIN));
+                break;
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+/// Read a NUL-terminated string of a frame table
+unsafe fn table_string(s: *const c_char) -> String {
+    if s.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(s).to_string_lossy().into_owned()
+}
+
+// FFI functions called by generated code
+
+/// Register a module's frame table
+///
+/// # Safety
+///
+/// `table` must point to rows ending with one whose address is null.
+#[no_mangle]
+pub unsafe extern "C" fn php_frames_register(table: *const PhpFrameInfo) {
+    let mut frames = FRAMES.lock().unwrap();
+    let mut row = table;
+    while !(*row).address.is_null() {
+        let info = &*row;
+        frames.insert(info.address as usize, Frame {
+            function: table_string(info.function),
+            file: table_string(info.file),
+            line: info.line,
+        });
+        row = row.add(1);
+    }
+}
+
+/// `debug_backtrace()`: the calls leading to the caller, innermost first
+///
+/// Each call is an array with the `file` and `line` of the call site and
+/// the `function` called, with its `class` and call `type` for a method.
+#[no_mangle]
+pub extern "C" fn php_debug_backtrace() -> *mut PhpArray {
+    let frames = capture();
+    let mut trace = Array::new(ArrayType::Packed);
+    for (frame, caller) in frames.iter().zip(frames.iter().skip(1)) {
+        if frame.function == MAIN {
+            break;
+        }
+        let mut call = Array::new(ArrayType::Associative);
+        let mut set = |key: &str, value: Value| call.set_by_key(key, value).expect("string keys are valid");
+        set("file", Value::String(caller.file.clone()));
+        set("line", Value::Int(caller.line as i64));
+        match frame.method() {
+            Some((class, operator, name)) => {
+                set("function", Value::String(name.to_string()));
+                set("class", Value::String(class.to_string()));
+                set("type", Value::String(operator.to_string()));
+            }
+            None => set("function", Value::String(frame.function.clone())),
+        }
+        trace.push(Value::Array(call));
+    }
+    PhpArray::new(trace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(function: &str, line: u32) -> Frame {
+        Frame { function: function.to_string(), file: "app.php".to_string(), line }
+    }
+
+    #[test]
+    fn test_format_trace() {
+        let frames = [frame("inner", 0), frame("Shape->area", 3), frame(MAIN, 9)];
+        assert_eq!(format_trace(&frames), "#0 app.php(3): inner()\n#1 app.php(9): Shape->area()\n#2 {main}");
+        assert_eq!(format_trace(&frames[2..]), "#0 {main}");
+        assert_eq!(frames[1].location(), "app.php:3");
+        assert_eq!(frames[1].method(), Some(("Shape", "->", "area")));
+        assert_eq!(frames[0].method(), None);
+    }
+
+    #[inline(never)]
+    fn probe() -> Vec<Frame> {
+        capture()
+    }
+
+    #[test]
+    fn test_capture() {
+        let table = [
+            PhpFrameInfo { address: probe as *const c_void, function: c"probe".as_ptr(), file: c"probe.php".as_ptr(), line: 2 },
+            PhpFrameInfo { address: std::ptr::null(), function: std::ptr::null(), file: std::ptr::null(), line: 0 },
+        ];
+        unsafe { php_frames_register(table.as_ptr()) };
+        // Frames of the test harness are not registered
+        assert_eq!(probe(), [Frame { function: "probe".to_string(), file: "probe.php".to_string(), line: 2 }]);
+    }
+}
@@ -0,0 +1,239 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Project builds.
+//!
+//! `php2ir build` compiles every source of a project, the manifest's entry,
+//! modules and the files its `sources` patterns match, as a separate
+//! object. A source depends on another when it includes it or references
+//! a function or class the other declares. Sources are compiled, and their
+//! top-level code run, dependencies first and otherwise with the entry
+//! last. Sources another source includes are compiled into it instead of
+//! on their own.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use log::debug;
+use crate::ast::AstNode;
+use crate::compiler::CompilerOptions;
+use crate::deadcode::declared_and_referenced;
+use crate::error::{CompileError, CompileResult};
+use crate::includes::IncludeResolver;
+use crate::manifest::{Manifest, MANIFEST_FILE};
+use crate::utils::path::normalize;
+
+/// Sources of a project build in compilation order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildPlan {
+    /// Dependencies first
+    pub order: Vec<PathBuf>,
+
+    /// Sources each source of `order`, at the same position, depends on
+    pub dependencies: Vec<Vec<PathBuf>>,
+}
+
+/// A source with the names it declares and references
+struct Unit {
+    path: PathBuf,
+    includes: Vec<PathBuf>,
+    declared: HashSet<String>,
+    referenced: HashSet<String>,
+}
+
+impl BuildPlan {
+    /// Order `sources`, whose last entry is the program's entry point
+    ///
+    /// `load` parses one file, as for [`IncludeResolver`].
+    pub fn new<L>(sources: &[PathBuf], include_paths: &[PathBuf], mut load: L) -> CompileResult<Self>
+    where
+        L: FnMut(&Path) -> CompileResult<Vec<AstNode>>,
+    {
+        let mut units: Vec<Unit> = Vec::new();
+        let mut included = HashSet::new();
+        for source in sources.iter().map(normalize) {
+            if units.iter().any(|unit| unit.path == source) {
+                continue;
+            }
+            let mut resolver = IncludeResolver::new(&mut load).with_include_paths(include_paths.to_vec());
+            let mut ast = resolver.resolve(&source)?;
+            let includes: Vec<PathBuf> = resolver.included_files().filter(|file| **file != source).cloned().collect();
+            included.extend(includes.iter().cloned());
+
+            let (declared, referenced) = declared_and_referenced(&mut ast);
+            units.push(Unit { path: source, includes, declared, referenced });
+        }
+        // Included sources are compiled into the sources including them
+        units.retain(|unit| !included.contains(&unit.path));
+
+        let dependencies: Vec<Vec<usize>> = units.iter().enumerate().map(|(i, unit)| {
+            (0..units.len())
+                .filter(|&j| j != i)
+                .filter(|&j| {
+                    unit.includes.contains(&units[j].path)
+                        || !unit.referenced.is_disjoint(&units[j].declared)
+                })
+                .collect()
+        }).collect();
+
+        let mut order = Vec::with_capacity(units.len());
+        let mut visited = vec![false; units.len()];
+        for index in 0..units.len() {
+            visit(index, &dependencies, &mut visited, &mut order);
+        }
+
+        let plan = Self {
+            dependencies: order.iter()
+                .map(|&i| dependencies[i].iter().map(|&j| units[j].path.clone()).collect())
+                .collect(),
+            order: order.iter().map(|&i| units[i].path.clone()).collect(),
+        };
+        for (source, dependencies) in plan.order.iter().zip(&plan.dependencies) {
+            debug!("{} depends on {:?}", source.display(), dependencies);
+        }
+        Ok(plan)
+    }
+}
+
+/// Options of a project build, from `manifest` or else the `php2ir.toml`
+/// of the current directory or one of its parents
+///
+/// The binary and the objects go to the manifest's `build-dir`, `build`
+/// next to it by default; the binary is named after the entry unless the
+/// manifest sets an `output`.
+pub fn project_options(manifest: Option<&Path>) -> CompileResult<CompilerOptions> {
+    let (project, root, file) = match manifest {
+        Some(file) => {
+            let root = file.parent().map(Path::to_path_buf).unwrap_or_default();
+            (Manifest::load(file)?, root, file.to_path_buf())
+        }
+        None => {
+            let (project, root) = Manifest::find(Path::new(""))?.ok_or_else(|| {
+                CompileError::Configuration(format!("no {} found in this directory or its parents", MANIFEST_FILE))
+            })?;
+            let file = root.join(MANIFEST_FILE);
+            (project, root, file)
+        }
+    };
+    let entry = project.entry.as_ref().ok_or_else(|| {
+        CompileError::Configuration(format!("{} has no `entry`", file.display()))
+    })?;
+
+    let build_dir = normalize(root.join(project.build_dir.as_deref().unwrap_or(Path::new("build"))));
+    let output = match &project.output {
+        Some(output) => normalize(root.join(output)),
+        None => build_dir.join(entry.file_stem().unwrap_or_default()),
+    };
+    Ok(CompilerOptions {
+        input: PathBuf::new(),
+        output,
+        manifest: Some(file),
+        build_dir: Some(build_dir),
+        ..CompilerOptions::default()
+    })
+}
+
+/// Append `index` to `order` after its dependencies
+///
+/// A source is marked before its dependencies are visited, so a cycle is
+/// broken where it is first entered.
+fn visit(index: usize, dependencies: &[Vec<usize>], visited: &mut [bool], order: &mut Vec<usize>) {
+    if visited[index] {
+        return;
+    }
+    visited[index] = true;
+    for &dependency in &dependencies[index] {
+        visit(dependency, dependencies, visited, order);
+    }
+    order.push(index);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::ast::{ClassDecl, Expression, FunctionDecl, IncludeKind, Literal, Statement, Visibility};
+    use crate::error::CompileError;
+
+    fn statement(expr: Expression) -> AstNode {
+        AstNode::Statement(Box::new(Statement::Expression(Box::new(expr))))
+    }
+
+    fn call(name: &str) -> Expression {
+        Expression::FunctionCall { name: Box::new(Expression::Constant(name.to_string())), arguments: Vec::new() }
+    }
+
+    fn function(name: &str, body: Vec<Statement>) -> FunctionDecl {
+        FunctionDecl {
+            name: name.to_string(),
+            parameters: Vec::new(),
+            return_type: None,
+            body: Box::new(Statement::Block(body)),
+            attributes: Vec::new(),
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        }
+    }
+
+    fn class(name: &str, methods: Vec<FunctionDecl>) -> AstNode {
+        AstNode::Class(ClassDecl {
+            name: name.to_string(),
+            extends: None,
+            implements: Vec::new(),
+            traits: Vec::new(),
+            properties: Vec::new(),
+            methods,
+            constants: Vec::new(),
+            attributes: Vec::new(),
+            is_abstract: false,
+            is_final: false,
+            is_trait: false,
+            is_interface: false,
+            is_enum: false,
+        })
+    }
+
+    #[test]
+    fn test_dependencies_first() {
+        let files: HashMap<&str, Vec<AstNode>> = HashMap::from([
+            ("app/util.php", vec![
+                statement(Expression::Include {
+                    kind: IncludeKind::RequireOnce,
+                    file: Box::new(Expression::Literal(Literal::String("inc.php".to_string()))),
+                }),
+                AstNode::Function(function("helper", Vec::new())),
+            ]),
+            ("app/inc.php", vec![AstNode::Function(function("format", Vec::new()))]),
+            ("app/db.php", vec![
+                class("Db", vec![function("query", vec![Statement::Expression(Box::new(call("helper")))])]),
+            ]),
+            ("app/main.php", vec![
+                statement(Expression::New { class: Box::new(Expression::Constant("Db".to_string())), arguments: Vec::new() }),
+                statement(call("format")),
+            ]),
+        ]);
+        let load = |path: &Path| files.get(path.to_str().unwrap()).cloned().ok_or_else(|| {
+            CompileError::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "no such file"))
+        });
+
+        let sources: Vec<PathBuf> = ["app/db.php", "app/util.php", "app/inc.php", "app/main.php"]
+            .iter().map(PathBuf::from).collect();
+        let plan = BuildPlan::new(&sources, &[], load).unwrap();
+        let path = |p: &str| PathBuf::from(p);
+        assert_eq!(plan.order, [path("app/util.php"), path("app/db.php"), path("app/main.php")]);
+        assert_eq!(plan.dependencies, [vec![], vec![path("app/util.php")], vec![path("app/db.php"), path("app/util.php")]]);
+    }
+}
@@ -0,0 +1,225 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use log::info;
+use crate::error::{CompileError, CompileResult};
+
+/// URL scheme used for files inside a bundle
+pub const PHAR_SCHEME: &str = "phar://";
+
+/// Entry point names probed in order when the bundle does not specify one
+const DEFAULT_ENTRY_POINTS: &[&str] = &["index.php", "main.php", "bin/main.php"];
+
+/// Single-file application bundle (zip or zip-based phar)
+#[derive(Debug, Clone)]
+pub struct Bundle {
+    /// Path of the archive on disk
+    path: PathBuf,
+
+    /// PHP sources keyed by their path inside the archive
+    sources: BTreeMap<String, String>,
+}
+
+impl Bundle {
+    /// Check if a path looks like a bundle rather than a PHP source file
+    pub fn is_bundle_path<P: AsRef<Path>>(path: P) -> bool {
+        matches!(
+            crate::utils::file::get_extension(path).as_deref(),
+            Some("phar") | Some("zip")
+        )
+    }
+
+    /// Open a bundle and load every contained `.php` file
+    pub fn open<P: AsRef<Path>>(path: P) -> CompileResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        info!("Opening bundle {}", path.display());
+
+        let file = std::fs::File::open(&path)?;
+        let mut archive = zip::ZipArchive::new(file)
+            .map_err(|e| invalid_bundle(&path, e))?;
+
+        let mut sources = BTreeMap::new();
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).map_err(|e| invalid_bundle(&path, e))?;
+            if entry.is_dir() || !crate::utils::file::is_php_file(entry.name()) {
+                continue;
+            }
+
+            let name = normalize_entry_name(entry.name());
+            let mut source = String::new();
+            entry.read_to_string(&mut source)?;
+            sources.insert(name, source);
+        }
+
+        info!("Bundle contains {} PHP files", sources.len());
+        Ok(Self { path, sources })
+    }
+
+    /// Build a bundle from in-memory sources
+    pub fn from_sources<P: AsRef<Path>>(path: P, sources: BTreeMap<String, String>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            sources: sources.into_iter()
+                .map(|(name, source)| (normalize_entry_name(&name), source))
+                .collect(),
+        }
+    }
+
+    /// Path of the archive on disk
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Names of all contained PHP files, sorted
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.sources.keys().map(|s| s.as_str())
+    }
+
+    /// Find the file executed when the bundle is run
+    pub fn entry_point(&self) -> CompileResult<&str> {
+        for candidate in DEFAULT_ENTRY_POINTS {
+            if let Some((name, _)) = self.sources.get_key_value(*candidate) {
+                return Ok(name);
+            }
+        }
+
+        match self.sources.len() {
+            1 => Ok(self.sources.keys().next().unwrap()),
+            _ => Err(CompileError::Configuration(format!(
+                "Bundle {} has no entry point (expected one of: {})",
+                self.path.display(),
+                DEFAULT_ENTRY_POINTS.join(", ")
+            ))),
+        }
+    }
+
+    /// Get the `phar://` URL of a file inside the bundle
+    pub fn url(&self, name: &str) -> String {
+        format!("{}{}/{}", PHAR_SCHEME, self.path.display(), normalize_entry_name(name))
+    }
+
+    /// Read a file by archive-relative name or `phar://` URL
+    pub fn read(&self, name_or_url: &str) -> CompileResult<&str> {
+        let name = match parse_phar_url(name_or_url) {
+            Some((archive, name)) if archive == self.path => name,
+            Some((archive, _)) => {
+                return Err(CompileError::Configuration(format!(
+                    "{} does not refer to bundle {}",
+                    archive.display(),
+                    self.path.display()
+                )));
+            }
+            None => normalize_entry_name(name_or_url),
+        };
+
+        self.sources.get(&name)
+            .map(|s| s.as_str())
+            .ok_or_else(|| CompileError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} not found in bundle {}", name, self.path.display()),
+            )))
+    }
+}
+
+/// Split a `phar://archive.phar/inner/file.php` URL into archive path and inner name
+///
+/// The archive part ends at the first path component with a bundle extension.
+pub fn parse_phar_url(url: &str) -> Option<(PathBuf, String)> {
+    let rest = url.strip_prefix(PHAR_SCHEME)?;
+    let mut archive = PathBuf::new();
+    let mut components = rest.split('/');
+
+    if rest.starts_with('/') {
+        archive.push("/");
+        components.next();
+    }
+
+    for component in components.by_ref() {
+        archive.push(component);
+        if Bundle::is_bundle_path(&archive) {
+            let inner: Vec<&str> = components.collect();
+            return Some((archive, normalize_entry_name(&inner.join("/"))));
+        }
+    }
+    None
+}
+
+/// Normalize an archive entry name (strip `./` and leading slashes, resolve `..`)
+fn normalize_entry_name(name: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in name.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Convert an archive error into a compile error
+fn invalid_bundle(path: &Path, error: zip::result::ZipError) -> CompileError {
+    CompileError::Configuration(format!("Invalid bundle {}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_parse_phar_url() {
+        let (archive, inner) = parse_phar_url("phar:///srv/app.phar/src/./lib.php").unwrap();
+        assert_eq!(archive, PathBuf::from("/srv/app.phar"));
+        assert_eq!(inner, "src/lib.php");
+        assert!(parse_phar_url("file:///srv/app.php").is_none());
+    }
+
+    #[test]
+    fn test_open_zip_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.phar");
+
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let options = zip::write::FileOptions::default();
+        writer.start_file("index.php", options).unwrap();
+        writer.write_all(b"<?php echo 1;").unwrap();
+        writer.start_file("src/lib.php", options).unwrap();
+        writer.write_all(b"<?php function lib() {}").unwrap();
+        writer.start_file("README.md", options).unwrap();
+        writer.write_all(b"docs").unwrap();
+        writer.finish().unwrap();
+
+        let bundle = Bundle::open(&path).unwrap();
+        assert_eq!(bundle.files().collect::<Vec<_>>(), vec!["index.php", "src/lib.php"]);
+        assert_eq!(bundle.entry_point().unwrap(), "index.php");
+        assert_eq!(bundle.read(&bundle.url("src/lib.php")).unwrap(), "<?php function lib() {}");
+    }
+
+    #[test]
+    fn test_missing_entry_point() {
+        let mut sources = BTreeMap::new();
+        sources.insert("a.php".to_string(), String::new());
+        sources.insert("b.php".to_string(), String::new());
+        let bundle = Bundle::from_sources("app.zip", sources);
+        assert!(bundle.entry_point().is_err());
+    }
+}
@@ -0,0 +1,260 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Scalar parameter type checking.
+//!
+//! Implements PHP's rules for passing a value to a typed parameter. In the
+//! default coercive mode scalars are converted between `int`, `float`,
+//! `string` and `bool` where PHP allows it; under `declare(strict_types=1)`
+//! only exact matches and the `int` to `float` widening are accepted.
+//! Anything else is a `TypeError`.
+
+use log::warn;
+use crate::ast::{AstNode, Expression, Literal, Parameter, Statement};
+use crate::interp::{format_float, parse_numeric, truthy, type_name, Num};
+use crate::runtime::Value;
+use crate::types::Type;
+
+/// Whether scalar arguments are coerced or must match exactly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TypeMode {
+    #[default]
+    Coercive,
+    Strict,
+}
+
+impl TypeMode {
+    /// Mode selected by a top-level `declare(strict_types=1)` in one file's nodes
+    ///
+    /// The mode applies to calls made in that file only. `Program` nodes
+    /// among the nodes are files of their own, spliced in by includes, so
+    /// walks entering one switch to its mode, as [`TypeMode::enter`] does.
+    pub fn of(ast: &[AstNode]) -> Self {
+        let strict = ast.iter().any(|node| match node {
+            AstNode::Statement(stmt) => match stmt.as_ref() {
+                Statement::Declare { directives, .. } => directives.iter().any(|directive| {
+                    directive.name.eq_ignore_ascii_case("strict_types")
+                        && matches!(directive.value, Expression::Literal(Literal::Int(1)))
+                }),
+                _ => false,
+            },
+            _ => false,
+        });
+        if strict { TypeMode::Strict } else { TypeMode::Coercive }
+    }
+
+    /// Switch to the mode of the file a `Program` node holds, returning the
+    /// mode to restore when leaving it
+    pub fn enter(&mut self, file: &[AstNode]) -> TypeMode {
+        std::mem::replace(self, TypeMode::of(file))
+    }
+}
+
+/// Convert an argument to a parameter's declared type
+///
+/// Returns the type name of the rejected value when PHP would throw a
+/// `TypeError`. Untyped parameters and class types are passed through.
+pub fn coerce_argument(value: Value, parameter: &Parameter, mode: TypeMode) -> Result<Value, &'static str> {
+    let Some(typ) = &parameter.typ else { return Ok(value) };
+    if matches!(value, Value::Null)
+        && matches!(parameter.default_value, Some(Expression::Literal(Literal::Null)))
+    {
+        return Ok(value);
+    }
+    coerce(value, typ, mode)
+}
+
+/// Convert a value to a declared type, as [`coerce_argument`] does
+pub fn coerce(value: Value, typ: &Type, mode: TypeMode) -> Result<Value, &'static str> {
+    let members = match typ {
+        Type::Union(members) => members.as_slice(),
+        Type::Object(_) | Type::Intersection(_) | Type::Function(..) | Type::Callable(_) | Type::Generic(..) | Type::Unknown => {
+            return Ok(value)
+        }
+        single => std::slice::from_ref(single),
+    };
+    if members.iter().any(|member| accepts(member, &value)) {
+        return Ok(value);
+    }
+    if members.iter().any(|member| matches!(member, Type::Object(_) | Type::Intersection(_) | Type::Function(..) | Type::Callable(_) | Type::Unknown)) {
+        return Ok(value);
+    }
+
+    // Widening int to float is allowed in both modes
+    if let Value::Int(n) = value {
+        if members.contains(&Type::Float) {
+            return Ok(Value::Float(n as f64));
+        }
+    }
+    if mode == TypeMode::Strict {
+        return Err(type_name(&value));
+    }
+
+    // PHP tries int, float, string and bool in that order
+    for target in [Type::Int, Type::Float, Type::String, Type::Bool] {
+        if members.contains(&target) {
+            if let Some(converted) = convert(&value, &target) {
+                return Ok(converted);
+            }
+        }
+    }
+    Err(type_name(&value))
+}
+
+/// Whether a value already has the given type
+fn accepts(typ: &Type, value: &Value) -> bool {
+    matches!(
+        (typ, value),
+        (Type::Int, Value::Int(_))
+            | (Type::Float, Value::Float(_))
+            | (Type::String, Value::String(_))
+            | (Type::Bool, Value::Bool(_))
+            | (Type::Null, Value::Null)
+            | (Type::Array(_) | Type::AssociativeArray(_), Value::Array(_))
+    )
+}
+
+/// Coercive-mode conversion of a scalar, `None` when PHP rejects it
+fn convert(value: &Value, target: &Type) -> Option<Value> {
+    Some(match (target, value) {
+        (Type::Int, Value::Bool(b)) => Value::Int(*b as i64),
+        (Type::Int, Value::Float(x)) => Value::Int(float_to_int(*x)?),
+        (Type::Int, Value::String(s)) => match numeric_string(s)? {
+            Num::Int(n) => Value::Int(n),
+            Num::Float(x) => Value::Int(float_to_int(x)?),
+        },
+        (Type::Float, Value::Bool(b)) => Value::Float(*b as i64 as f64),
+        (Type::Float, Value::String(s)) => match numeric_string(s)? {
+            Num::Int(n) => Value::Float(n as f64),
+            Num::Float(x) => Value::Float(x),
+        },
+        (Type::String, Value::Bool(b)) => Value::String(if *b { "1" } else { "" }.to_string()),
+        (Type::String, Value::Int(n)) => Value::String(n.to_string()),
+        (Type::String, Value::Float(x)) => Value::String(format_float(*x)),
+        (Type::Bool, Value::Int(_) | Value::Float(_) | Value::String(_)) => Value::Bool(truthy(value)),
+        _ => return None,
+    })
+}
+
+/// Numeric value of a string argument; leading-numeric strings are accepted with a warning
+fn numeric_string(s: &str) -> Option<Num> {
+    let (number, whole) = parse_numeric(s)?;
+    if !whole {
+        warn!("A non-numeric value encountered: \"{}\"", s);
+    }
+    Some(number)
+}
+
+fn float_to_int(x: f64) -> Option<i64> {
+    if !x.is_finite() || x < i64::MIN as f64 || x >= i64::MAX as f64 {
+        return None;
+    }
+    if x.fract() != 0.0 {
+        warn!("Implicit conversion from float {} to int loses precision", format_float(x));
+    }
+    Some(x as i64)
+}
+
+/// Message of the `TypeError` thrown for a rejected argument
+pub fn argument_error(function: &str, position: usize, parameter: &Parameter, given: &str) -> String {
+    format!("{}, {} given", argument_expectation(function, position, parameter), given)
+}
+
+/// The message of [`argument_error`] up to the expected type, for compiled
+/// code to complete once the argument is known
+pub fn argument_expectation(function: &str, position: usize, parameter: &Parameter) -> String {
+    let expected = parameter.typ.as_ref().map_or_else(|| "mixed".to_string(), declared_name);
+    format!("{}(): Argument #{} (${}) must be of type {}", function, position, parameter.name, expected)
+}
+
+/// Type as written in PHP source, e.g. `?int`, `int|string` or `(A&B)|null`
+pub fn declared_name(typ: &Type) -> String {
+    match typ {
+        Type::Array(_) | Type::AssociativeArray(_) => "array".to_string(),
+        Type::Union(members) => match members.as_slice() {
+            [single, Type::Null] | [Type::Null, single] if !matches!(single, Type::Intersection(_)) => {
+                format!("?{}", declared_name(single))
+            }
+            _ => members.iter()
+                .map(|member| match member {
+                    Type::Intersection(_) => format!("({})", declared_name(member)),
+                    member => declared_name(member),
+                })
+                .collect::<Vec<_>>()
+                .join("|"),
+        },
+        Type::Intersection(members) => members.iter().map(declared_name).collect::<Vec<_>>().join("&"),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameter(typ: Type) -> Parameter {
+        Parameter {
+            name: "n".to_string(),
+            typ: Some(typ),
+            default_value: None,
+            is_reference: false,
+            is_variadic: false,
+        }
+    }
+
+    #[test]
+    fn test_coercive_mode() {
+        let int = parameter(Type::Int);
+        assert!(matches!(coerce_argument(Value::String("42".into()), &int, TypeMode::Coercive), Ok(Value::Int(42))));
+        assert!(matches!(coerce_argument(Value::Float(3.0), &int, TypeMode::Coercive), Ok(Value::Int(3))));
+        assert!(matches!(coerce_argument(Value::Bool(true), &int, TypeMode::Coercive), Ok(Value::Int(1))));
+        assert_eq!(coerce_argument(Value::String("abc".into()), &int, TypeMode::Coercive).err(), Some("string"));
+        assert_eq!(coerce_argument(Value::Null, &int, TypeMode::Coercive).err(), Some("null"));
+
+        let string = parameter(Type::String);
+        assert!(matches!(
+            coerce_argument(Value::Float(1.5), &string, TypeMode::Coercive),
+            Ok(Value::String(s)) if s == "1.5"
+        ));
+
+        let nullable = parameter(Type::Union(vec![Type::Int, Type::Null]));
+        assert!(matches!(coerce_argument(Value::Null, &nullable, TypeMode::Coercive), Ok(Value::Null)));
+    }
+
+    #[test]
+    fn test_strict_mode() {
+        let float = parameter(Type::Float);
+        assert!(matches!(coerce_argument(Value::Int(2), &float, TypeMode::Strict), Ok(Value::Float(x)) if x == 2.0));
+
+        let int = parameter(Type::Int);
+        assert_eq!(coerce_argument(Value::String("42".into()), &int, TypeMode::Strict).err(), Some("string"));
+        assert_eq!(coerce_argument(Value::Float(3.0), &int, TypeMode::Strict).err(), Some("float"));
+        assert_eq!(
+            argument_error("square", 1, &int, "string"),
+            "square(): Argument #1 ($n) must be of type int, string given"
+        );
+
+        let ast = vec![AstNode::Statement(Box::new(Statement::Declare {
+            directives: vec![crate::ast::DeclareDirective {
+                name: "strict_types".to_string(),
+                value: Expression::Literal(Literal::Int(1)),
+            }],
+            body: Box::new(Statement::Block(vec![])),
+        }))];
+        assert_eq!(TypeMode::of(&ast), TypeMode::Strict);
+        assert_eq!(TypeMode::of(&[]), TypeMode::Coercive);
+    }
+}
@@ -14,14 +14,48 @@
  * limitations under the License.
  */
 
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
-use std::process::Command;
-use log::{info, warn, error};
-use crate::ast::AstNode;
-use crate::error::{CompileError, CompileResult};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use log::{debug, info, warn, error};
+use crate::ast::{self, AstDiff, AstIndex, AstNode, NodeId, Span};
+use crate::backend::{self, Backend, BackendKind, CodeFile, LlvmBackend};
+use crate::build::BuildPlan;
+use crate::bundle::Bundle;
+use crate::coercion::{self, TypeMode};
+use crate::deadcode::eliminate_dead_code;
+use crate::defines::substitute_defines;
+use crate::definitions::DefinitionRegistry;
+use crate::devirtualize::devirtualize;
+use crate::fallback::{FallbackReport, FallbackReporter};
+use crate::header;
+use crate::diagnostics::{codes, Diagnostic, DiagnosticReport};
+use crate::embed::read_embedded_files;
+use crate::error::{CompileError, CompileResult, ErrorContext};
+use crate::autoload::{Autoloader, Psr4Map};
+use crate::includes::IncludeResolver;
+use crate::link::{self, Link, Linker, LtoLinker, LtoMode};
+use crate::interp::Interpreter;
 use crate::parser::{Parser, DefaultParser};
-use crate::types::TypeContext;
+use crate::profile::Profile;
+use crate::session::Hooks;
+use crate::trace::Instrumentation;
+use crate::types::{IntWidth, ScopeKind, TypeContext};
 use crate::ir::IrGenerator;
+use crate::library::StaticLibrary;
+use crate::literals::{mark_exhaustive_matches, LiteralChecker};
+use crate::manifest::apply_manifest;
+use crate::variance::VarianceChecker;
+use crate::module::{self, ModuleInfo};
+use crate::specialize::specialize;
+use crate::stubs::{is_stub_path, stub_declarations, stub_functions, STUB_EXTENSION};
+use crate::tailcall::eliminate_tail_calls;
+use crate::target::TargetSpec;
+use crate::timings::{Phase, Timings};
+use crate::unreachable::UnreachableCodeEliminator;
+use crate::utils::process;
+use crate::utils::time::measure_time;
 
 /// Compiler options
 #[derive(Debug, Clone)]
@@ -38,22 +72,51 @@ pub struct CompilerOptions {
     /// Whether to emit LLVM IR only (no object file)
     pub emit_llvm_only: bool,
     
+    /// Whether to emit LLVM bitcode only (no object file)
+    pub emit_bitcode: bool,
+    
+    /// Whether to emit target assembly only (no object file)
+    pub emit_asm: bool,
+    
+    /// Whether to emit only a C header declaring the exported functions
+    pub emit_header: bool,
+    
+    /// Whether to build a static library, with its header and pkg-config
+    /// file, instead of an executable
+    pub emit_library: bool,
+    
+    /// Version of the program, written to the pkg-config file of a library
+    pub version: Option<String>,
+    
     /// Optimization level
     pub optimization_level: String,
     
-    /// LTO mode
-    pub lto: Option<String>,
+    /// Pass pipeline replacing the one of the optimization level
+    pub passes: Option<String>,
+    
+    /// Link-time optimization over bitcode objects
+    pub lto: Option<LtoMode>,
+    
+    /// Build for the smallest binary: `-Oz`, unused sections dropped by the
+    /// linker and symbols stripped
+    pub release_size: bool,
     
-    /// PGO generation mode
+    /// Instrument the program to write a profile when it runs
     pub pgo_gen: bool,
     
-    /// PGO use mode with profile data
+    /// Profile merged by `llvm-profdata` to optimize with
     pub pgo_use: Option<PathBuf>,
     
     /// Target triple
     pub target: Option<String>,
     
-    /// Standard library path
+    /// Root of the target's C libraries and start files
+    pub sysroot: Option<PathBuf>,
+    
+    /// Compiler driver or linker running the link
+    pub linker: Option<PathBuf>,
+    
+    /// Runtime library archive, or a directory containing it
     pub stdlib: Option<PathBuf>,
     
     /// Disable runtime library
@@ -61,6 +124,53 @@ pub struct CompilerOptions {
     
     /// Sanitizer
     pub sanitizer: Option<String>,
+    
+    /// Width of PHP `int` (derived from the target when unset)
+    pub int_width: Option<IntWidth>,
+    
+    /// Instrumentation inserted into generated functions
+    pub instrument: Option<Instrumentation>,
+    
+    /// Additional PHP files compiled as separate objects and linked in
+    pub modules: Vec<PathBuf>,
+    
+    /// Code generation backend
+    pub backend: BackendKind,
+    
+    /// Run functions that code generation cannot handle yet in the interpreter
+    pub interpreter_fallback: bool,
+    
+    /// Report places that fall back to boxed values or dynamic dispatch
+    pub report_dynamic: Option<FallbackReport>,
+    
+    /// Signature-only `.phpstub` files declaring symbols implemented elsewhere
+    pub stubs: Vec<PathBuf>,
+    
+    /// Directories searched for relative `include`/`require` targets
+    pub include_paths: Vec<PathBuf>,
+    
+    /// Constants defined before the program runs, their uses replaced by
+    /// their values at compile time
+    pub defines: Vec<(String, ast::Literal)>,
+    
+    /// `php2ir.toml` to read instead of the one found next to the input
+    pub manifest: Option<PathBuf>,
+    
+    /// Further sources of a project build, besides the input and the modules
+    pub sources: Vec<PathBuf>,
+    
+    /// Directory for intermediate files, kept between builds, instead of a
+    /// temporary directory
+    pub build_dir: Option<PathBuf>,
+    
+    /// Files compiled into the program for `embedded_file()`, by name
+    pub embed: Vec<(String, PathBuf)>,
+    
+    /// Keep the intermediate files of a compilation instead of removing them
+    pub keep_temps: bool,
+    
+    /// Write the IR handed to code generation next to the output
+    pub save_ir: bool,
 }
 
 impl Default for CompilerOptions {
@@ -70,109 +180,872 @@ impl Default for CompilerOptions {
             output: PathBuf::from("output"),
             emit_llvm: false,
             emit_llvm_only: false,
+            emit_bitcode: false,
+            emit_asm: false,
+            emit_header: false,
+            emit_library: false,
+            version: None,
             optimization_level: "O2".to_string(),
+            passes: None,
             lto: None,
+            release_size: false,
             pgo_gen: false,
             pgo_use: None,
             target: None,
+            sysroot: None,
+            linker: None,
             stdlib: None,
             no_runtime: false,
             sanitizer: None,
+            int_width: None,
+            instrument: None,
+            modules: Vec::new(),
+            backend: BackendKind::default(),
+            interpreter_fallback: true,
+            report_dynamic: None,
+            stubs: Vec::new(),
+            include_paths: Vec::new(),
+            defines: Vec::new(),
+            manifest: None,
+            sources: Vec::new(),
+            build_dir: None,
+            embed: Vec::new(),
+            keep_temps: false,
+            save_ir: false,
         }
     }
 }
 
+impl CompilerOptions {
+    /// Effective width of PHP `int` for this compilation
+    pub fn resolved_int_width(&self) -> IntWidth {
+        self.int_width.unwrap_or_else(|| {
+            IntWidth::from_target(self.target.as_deref().unwrap_or("native"))
+        })
+    }
+    
+    /// Target triple and data layout, the host for `native` or no target
+    pub fn resolved_target(&self) -> TargetSpec {
+        TargetSpec::from_target(self.target.as_deref().unwrap_or("native"))
+    }
+}
+
+/// Output of [`Compiler::compile_source`]
+#[derive(Debug, Clone)]
+pub struct Artifacts {
+    /// IR of the program, in the backend's textual format
+    pub ir: String,
+    
+    /// Warnings and errors reported while compiling it
+    pub diagnostics: DiagnosticReport,
+}
+
 /// Main compiler struct
 pub struct Compiler {
     options: CompilerOptions,
     parser: DefaultParser,
     type_context: TypeContext,
-    ir_generator: IrGenerator,
+    backend: Box<dyn Backend>,
+    previous_ast: Option<Vec<AstNode>>,
+    definitions: DefinitionRegistry,
+    diagnostics: DiagnosticReport,
+    
+    /// File being checked, for diagnostics
+    current_file: String,
+
+    /// Source spans of each file's nodes, by id in an [`AstIndex`] of its AST
+    spans: HashMap<String, HashMap<NodeId, Span>>,
+
+    /// Argument checking mode of the file being checked
+    type_mode: TypeMode,
+
+    /// Parameters of the file's top-level functions, by lowercase name
+    signatures: HashMap<String, (String, Vec<ast::Parameter>)>,
+
+    /// Declarations from stub files
+    stubs: Vec<AstNode>,
+
+    /// Directory of the intermediate files of the compilation running
+    temp_dir: Option<PathBuf>,
+
+    /// Phase timings and counts of the last compilation
+    timings: Timings,
+
+    /// Progress reporting and cancellation of a [`CompileSession`](crate::session::CompileSession)
+    hooks: Hooks,
 }
 
 impl Compiler {
     /// Create a new compiler instance
-    pub fn new(options: CompilerOptions) -> CompileResult<Self> {
+    ///
+    /// Options still at their default are taken from the project's
+    /// `php2ir.toml`, if there is one.
+    pub fn new(mut options: CompilerOptions) -> CompileResult<Self> {
+        apply_manifest(&mut options)?;
+        Self::create(options, true)
+    }
+    
+    /// Create a compiler for [`compile_source`](Self::compile_source) that
+    /// reads no files of its own
+    ///
+    /// No manifest is looked for, and the interpreter fallback embeds the
+    /// source given to `compile_source` instead of reading the input. Stub
+    /// and embedded files in `options` are still read.
+    pub fn in_memory(options: CompilerOptions) -> CompileResult<Self> {
+        Self::create(options, false)
+    }
+    
+    /// Create a compiler for `options`, whose interpreter fallback reads the
+    /// input if `read_input`
+    fn create(mut options: CompilerOptions, read_input: bool) -> CompileResult<Self> {
+        if options.release_size {
+            if !matches!(options.optimization_level.as_str(), "O2" | "Oz") {
+                warn!("--release-size optimizes with -Oz instead of -{}", options.optimization_level);
+            }
+            options.optimization_level = "Oz".to_string();
+        }
         let parser = DefaultParser::new();
         let type_context = TypeContext::new();
-        let ir_generator = IrGenerator::new()?;
+        let int_width = options.resolved_int_width();
+        info!("Using {} integers", int_width);
+        info!("Using the {} backend", options.backend);
+        let backend = if read_input {
+            backend::create_backend(&options, &options.input)?
+        } else {
+            let options = CompilerOptions { interpreter_fallback: false, ..options.clone() };
+            backend::create_backend(&options, &options.input)?
+        };
+        let stubs = load_stubs(&parser, &options.stubs)?;
         
         Ok(Self {
             options,
             parser,
             type_context,
-            ir_generator,
+            backend,
+            previous_ast: None,
+            definitions: DefinitionRegistry::new(),
+            diagnostics: DiagnosticReport::new(),
+            current_file: String::new(),
+            spans: HashMap::new(),
+            type_mode: TypeMode::default(),
+            signatures: HashMap::new(),
+            stubs,
+            temp_dir: None,
+            timings: Timings::new(),
+            hooks: Hooks::default(),
         })
     }
     
+    /// Options of this compilation, with the manifest applied
+    pub fn options(&self) -> &CompilerOptions {
+        &self.options
+    }
+    
+    /// Phase timings and counts of the last compilation or build, for `--timings`
+    pub fn timings(&self) -> &Timings {
+        &self.timings
+    }
+    
+    /// Record the source spans of a file's nodes, by id in an [`AstIndex`]
+    /// of the AST compiled for it, so diagnostics can point into the source
+    ///
+    /// The built-in parser does not record spans yet.
+    pub fn set_spans(&mut self, file: impl Into<String>, spans: HashMap<NodeId, Span>) {
+        self.spans.insert(file.into(), spans);
+    }
+    
+    pub(crate) fn hooks_mut(&mut self) -> &mut Hooks {
+        &mut self.hooks
+    }
+    
+    /// Record the time of a phase that just ended and report it; fails once
+    /// the compilation is cancelled
+    fn end_phase(&self, phase: Phase, duration: Duration) -> CompileResult<()> {
+        self.timings.add(phase, duration);
+        self.hooks.phase_ended(&self.timings, phase, duration)
+    }
+    
     /// Run the full compilation pipeline
+    ///
+    /// Intermediate files go to a temporary directory, removed afterwards
+    /// unless they are to be kept, or to the build directory if there is one.
     pub fn compile(&mut self) -> CompileResult<()> {
         info!("Starting compilation of {}", self.options.input.display());
+        self.diagnostics.clear();
+        self.timings.clear();
+        
+        self.create_temp_dir()?;
+        let result = self.compile_input();
+        self.remove_temp_dir();
+        result
+    }
+    
+    /// Compile the input, and the modules if there are any
+    fn compile_input(&mut self) -> CompileResult<()> {
+        if self.options.emit_header {
+            return self.write_header();
+        }
+        if !self.options.modules.is_empty() || self.options.emit_library {
+            return self.compile_modules();
+        }
         
         // 1. Parse PHP source
         let ast = self.parse()?;
         info!("Parsing completed, {} AST nodes generated", ast.len());
         
         // 2. Type checking and semantic analysis
-        self.type_check(&ast)?;
+        let input = self.options.input.clone();
+        self.type_check(&ast, &input)?;
         info!("Type checking completed");
         
         // 3. Generate LLVM IR
-        let ir = self.generate_ir()?;
+        let ir = self.lower(ast)?;
         info!("LLVM IR generation completed");
         
         // 4. Optimize IR
-        if self.options.optimization_level != "O0" {
-            self.optimize_ir(&ir)?;
-            info!("IR optimization completed");
+        let (ir, duration) = measure_time(|| self.optimize_ir(ir, &self.options.output));
+        self.end_phase(Phase::Optimize, duration)?;
+        let ir = ir?;
+        if self.options.save_ir && !self.options.emit_llvm_only {
+            self.save_ir(&ir, self.options.output.with_extension(self.backend.ir_extension()))?;
         }
         
         // 5. Generate object file or final binary
-        if self.options.emit_llvm_only {
-            self.write_ir_file(&ir)?;
+        let (written, duration) = measure_time(|| self.write_output(&ir));
+        self.end_phase(Phase::Codegen, duration)?;
+        written?;
+        if self.links() {
+            self.link_binary()?;
+            info!("Binary generation completed: {}", self.options.output.display());
+        }
+        
+        info!("Compilation completed successfully");
+        Ok(())
+    }
+    
+    /// Write the requested output of a single-file compilation, the object
+    /// file if it is to be linked
+    fn write_output(&mut self, ir: &str) -> CompileResult<()> {
+        if self.options.emit_bitcode {
+            self.write_bitcode_file(ir)?;
+            info!("LLVM bitcode written to {}", self.options.output.display());
+        } else if self.options.emit_asm {
+            self.write_assembly_file(ir)?;
+            info!("Assembly written to {}", self.options.output.display());
+        } else if self.options.emit_llvm_only {
+            self.write_ir_file(ir)?;
             info!("LLVM IR written to {}", self.options.output.display());
         } else {
-            self.generate_object_file(&ir)?;
-            if !self.options.emit_llvm {
-                self.link_binary()?;
-                info!("Binary generation completed: {}", self.options.output.display());
+            self.generate_object_file(ir)?;
+        }
+        Ok(())
+    }
+    
+    /// Compile the input and every extra module to separate objects and link them
+    ///
+    /// Modules run their top-level code in command-line order, starting with
+    /// the main input.
+    fn compile_modules(&mut self) -> CompileResult<()> {
+        let paths: Vec<PathBuf> = std::iter::once(self.options.input.clone())
+            .chain(self.options.modules.iter().cloned())
+            .collect();
+        self.link_modules(&paths)
+    }
+    
+    /// Order the sources of a project build by their dependencies
+    ///
+    /// The sources are the modules and further sources, then the input.
+    pub fn plan_build(&self) -> CompileResult<BuildPlan> {
+        // Timings of a build start with the parsing done to plan it
+        self.timings.clear();
+        let sources: Vec<PathBuf> = self.options.modules.iter()
+            .chain(&self.options.sources)
+            .chain(std::iter::once(&self.options.input))
+            .cloned()
+            .collect();
+        let plan = BuildPlan::new(&sources, &self.options.include_paths, |file: &std::path::Path| self.load_file(file))?;
+        info!("Build order: {}", plan.order.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", "));
+        Ok(plan)
+    }
+    
+    /// Compile the sources of a plan to separate objects and link them
+    pub fn build(&mut self, plan: &BuildPlan) -> CompileResult<()> {
+        info!("Building {} source(s)", plan.order.len());
+        self.diagnostics.clear();
+        if let Some(dir) = &self.options.build_dir {
+            std::fs::create_dir_all(dir)?;
+        }
+        self.create_temp_dir()?;
+        let result = self.link_modules(&plan.order);
+        self.remove_temp_dir();
+        result
+    }
+    
+    /// Whether the compilation ends with a link or an archive, whose inputs
+    /// are intermediate files
+    fn links(&self) -> bool {
+        !self.options.emit_llvm && !self.options.emit_llvm_only && !self.options.emit_bitcode && !self.options.emit_asm
+            && !self.options.emit_header
+    }
+    
+    /// Create the temporary directory of a compilation that links, unless
+    /// its intermediate files go to the build directory
+    fn create_temp_dir(&mut self) -> CompileResult<()> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        self.temp_dir = None;
+        if !self.links() {
+            return Ok(());
+        }
+        if let Some(dir) = &self.options.build_dir {
+            std::fs::create_dir_all(dir)?;
+            return Ok(());
+        }
+        let name = format!("php2ir-{}-{}", std::process::id(), COUNT.fetch_add(1, Ordering::Relaxed));
+        let dir = std::env::temp_dir().join(name);
+        std::fs::create_dir_all(&dir)?;
+        debug!("Writing intermediate files to {}", dir.display());
+        self.temp_dir = Some(dir);
+        Ok(())
+    }
+    
+    /// Remove the temporary directory, or with `keep_temps` tell where it is
+    fn remove_temp_dir(&mut self) {
+        let Some(dir) = self.temp_dir.take() else { return };
+        if self.options.keep_temps {
+            info!("Intermediate files kept in {}", dir.display());
+        } else if let Err(e) = std::fs::remove_dir_all(&dir) {
+            warn!("Cannot remove {}: {}", dir.display(), e);
+        }
+    }
+    
+    /// `path` moved to the temporary or build directory, if the compilation has one
+    fn intermediate_path(&self, path: PathBuf) -> PathBuf {
+        let dir = self.temp_dir.as_ref().or(self.options.build_dir.as_ref().filter(|_| self.links()));
+        match (dir, path.file_name()) {
+            (Some(dir), Some(file)) => dir.join(file),
+            _ => path,
+        }
+    }
+    
+    /// Write IR for `save_ir`
+    fn save_ir(&self, ir: &str, path: PathBuf) -> CompileResult<()> {
+        std::fs::write(&path, ir)?;
+        info!("IR saved to {}", path.display());
+        Ok(())
+    }
+    
+    /// Compile files to separate objects, running their top-level code in
+    /// order, and link or archive them unless only code is requested
+    fn link_modules(&mut self, paths: &[PathBuf]) -> CompileResult<()> {
+        if self.options.backend != BackendKind::Llvm {
+            return Err(CompileError::Configuration(format!(
+                "multi-object builds are not supported by the {} backend",
+                self.options.backend
+            )));
+        }
+        
+        let mut asts = Vec::new();
+        for (index, path) in paths.iter().enumerate() {
+            let mut ast = self.parse_path(path)?;
+            // The first module runs first, so its constants are defined for all
+            if index == 0 {
+                self.define_constants(&mut ast);
             }
+            self.type_check(&ast, path)?;
+            let (_, duration) = measure_time(|| self.eliminate_unreachable(&mut ast));
+            self.end_phase(Phase::Optimize, duration)?;
+            asts.push(ast);
         }
+        // The modules are linked together, so reachability spans all of them
+        let (_, duration) = measure_time(|| self.eliminate_dead_code(&mut asts));
+        self.end_phase(Phase::Optimize, duration)?;
         
-        info!("Compilation completed successfully");
+        let target = self.options.resolved_target();
+        let library = self.options.emit_library.then(|| StaticLibrary::new(&self.options.output, target.clone()));
+        let library_header = match &library {
+            Some(library) => Some(header::generate_header(
+                &asts.concat(),
+                &library.name,
+                self.options.resolved_int_width(),
+                Some(&library.symbol_prefix()),
+            )?),
+            None => None,
+        };
+        
+        let mut modules = Vec::new();
+        let mut objects = Vec::new();
+        for (path, mut ast) in paths.iter().zip(asts) {
+            let info = ModuleInfo::new(path);
+            let (_, duration) = measure_time(|| {
+                self.devirtualize(&mut ast);
+                self.specialize(&mut ast);
+                self.eliminate_tail_calls(&mut ast);
+                self.mark_exhaustive_matches(&mut ast);
+            });
+            self.timings.count_ast(&mut ast);
+            self.end_phase(Phase::Optimize, duration)?;
+            
+            let mut generator = self.module_generator(path)?.with_module(info.clone());
+            // The first module carries the embedded files
+            if objects.is_empty() && !self.options.embed.is_empty() {
+                generator = generator.with_embedded_files(read_embedded_files(&self.options.embed)?);
+            }
+            for decl in external_functions(&self.stubs, &ast) {
+                generator.declare_external(decl)?;
+            }
+            let (ir, duration) = measure_time(|| generator.generate(&ast));
+            self.end_phase(Phase::IrGen, duration)?;
+            let ir = ir.with_context(|| path.display().to_string())?;
+            self.timings.count_ir(&ir);
+            
+            objects.push(self.emit_module(&ir, &info.prefix)?);
+            modules.push(info);
+        }
+        
+        if let (Some(library), Some(header)) = (library, library_header) {
+            let entry = module::generate_library_entry(&modules, self.options.instrument, &target, &library.symbol_prefix());
+            objects.push(self.emit_module(&entry, "entry")?);
+            if self.links() {
+                self.write_library(&library, &objects, &header)?;
+            }
+            return Ok(());
+        }
+        
+        let driver = module::generate_driver(&modules, self.options.instrument, &target);
+        objects.push(self.emit_module(&driver, "driver")?);
+        
+        if self.links() {
+            self.link_objects(&objects)?;
+            info!("Binary generation completed: {}", self.options.output.display());
+        }
+        Ok(())
+    }
+    
+    /// Archive the objects of a static library and write its header and
+    /// pkg-config file next to the archive
+    fn write_library(&self, library: &StaticLibrary, objects: &[PathBuf], header: &str) -> CompileResult<()> {
+        if self.options.lto.is_some() {
+            warn!("The objects of {} are LLVM bitcode; programs must be linked with LTO to use it", library.name);
+        }
+        let archive = library.archive_path();
+        if archive.exists() {
+            std::fs::remove_file(&archive)?;
+        }
+        let mut cmd = library.archive_command(objects)?;
+        debug!("Archiving with {:?}", cmd);
+        let (result, duration) = measure_time(|| cmd.output());
+        self.end_phase(Phase::Link, duration)?;
+        let result = result
+            .map_err(|e| CompileError::Internal(format!("Failed to run the archiver: {}", e)))?;
+        if !result.status.success() {
+            let stderr = String::from_utf8_lossy(&result.stderr);
+            return Err(CompileError::Linking(stderr.to_string()));
+        }
+        
+        std::fs::write(library.header_path(), header)?;
+        let runtime = if self.options.no_runtime {
+            None
+        } else {
+            let target = self.options.resolved_target();
+            Some(std::fs::canonicalize(link::find_runtime(self.options.stdlib.as_deref(), &target)?)?)
+        };
+        let prefix = std::fs::canonicalize(&archive)?
+            .parent()
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        let version = self.options.version.as_deref().unwrap_or("0.0.0");
+        std::fs::write(library.pkg_config_path(), library.pkg_config(&prefix, runtime.as_deref(), version))?;
+        info!(
+            "Static library written to {}, with {} and {}",
+            archive.display(), library.header_path().display(), library.pkg_config_path().display()
+        );
+        Ok(())
+    }
+    
+    /// IR generator configured like the main one, for another source file
+    fn module_generator(&self, path: &std::path::Path) -> CompileResult<IrGenerator> {
+        Ok(IrGenerator::new()?
+            .with_int_width(self.options.resolved_int_width())
+            .with_target(self.options.resolved_target())
+            .with_instrumentation(self.options.instrument)
+            .with_source_file(path.display().to_string()))
+    }
+    
+    /// Write a module's IR next to the output and, unless only IR is requested, compile it
+    ///
+    /// An object that is only linked is an intermediate file.
+    fn emit_module(&self, ir: &str, name: &str) -> CompileResult<PathBuf> {
+        let (file, duration) = measure_time(|| self.write_module(ir, name));
+        self.end_phase(Phase::Codegen, duration)?;
+        file
+    }
+    
+    fn write_module(&self, ir: &str, name: &str) -> CompileResult<PathBuf> {
+        let ir_file = self.artifact_path(name, "ll");
+        let obj_file = self.intermediate_path(self.artifact_path(name, "o"));
+        if self.options.save_ir && !self.options.emit_llvm_only {
+            self.save_ir(ir, ir_file.clone())?;
+        }
+        
+        if self.options.emit_bitcode {
+            let bc_file = self.artifact_path(name, "bc");
+            LlvmBackend::compile_bitcode(&self.optimize_ir(ir.to_string(), &bc_file)?, &bc_file)?;
+            return Ok(bc_file);
+        }
+        if self.options.emit_llvm_only {
+            std::fs::write(&ir_file, self.optimize_ir(ir.to_string(), &ir_file)?)?;
+            return Ok(ir_file);
+        }
+        let passes = self.options.passes.as_deref();
+        let profile = Profile::from_options(&self.options)?;
+        if self.options.emit_asm {
+            let asm_file = self.artifact_path(name, "s");
+            LlvmBackend::compile_ir(ir, &asm_file, &self.options.optimization_level, passes, &profile, CodeFile::Assembly, self.options.release_size)?;
+            return Ok(asm_file);
+        }
+        match self.options.lto {
+            Some(mode) => LlvmBackend::compile_lto_object(ir, &obj_file, &self.options.optimization_level, passes, &profile, mode)?,
+            None => LlvmBackend::compile_ir(ir, &obj_file, &self.options.optimization_level, passes, &profile, CodeFile::Object, self.options.release_size)?,
+        }
+        Ok(obj_file)
+    }
+    
+    /// File a module's code is written to: `<output>.<name>.<extension>`,
+    /// in the build directory if there is one
+    fn artifact_path(&self, name: &str, extension: &str) -> PathBuf {
+        let path = self.options.output.with_extension(format!("{}.{}", name, extension));
+        match (&self.options.build_dir, path.file_name()) {
+            (Some(dir), Some(file)) => dir.join(file),
+            _ => path,
+        }
+    }
+    
+    /// Run the input with the tree-walking interpreter instead of compiling it
+    ///
+    /// Returns the program's exit status.
+    pub fn interpret(&mut self) -> CompileResult<i32> {
+        self.diagnostics.clear();
+        let ast = self.parse()?;
+        let input = self.options.input.clone();
+        self.type_check(&ast, &input)?;
+        
+        let int_width = self.options.resolved_int_width();
+        Interpreter::new()
+            .and_then(|interpreter| interpreter.with_int_width(int_width).run(&ast))
+            .map_err(|e| CompileError::Runtime(e.to_string()))
+    }
+    
+    /// Parse and type-check the input and extra modules without generating code
+    ///
+    /// Diagnostics are collected in [`diagnostics`](Self::diagnostics); errors
+    /// among them do not make the check itself fail.
+    pub fn check(&mut self) -> CompileResult<()> {
+        self.diagnostics.clear();
+        let paths: Vec<PathBuf> = std::iter::once(self.options.input.clone())
+            .chain(self.options.modules.iter().cloned())
+            .collect();
+        for path in &paths {
+            let mut ast = self.parse_path(path)?;
+            if *path == self.options.input {
+                self.define_constants(&mut ast);
+            }
+            self.type_check(&ast, path)?;
+            self.eliminate_unreachable(&mut ast);
+        }
+        info!("Checked {} file(s)", paths.len());
         Ok(())
     }
     
     /// Parse PHP source code
     pub fn parse(&self) -> CompileResult<Vec<AstNode>> {
-        self.parser.parse_file(&self.options.input)
+        let mut ast = self.parse_path(&self.options.input)?;
+        self.define_constants(&mut ast);
+        Ok(ast)
+    }
+    
+    /// Define the configured constants ahead of the program's own code
+    fn define_constants(&self, ast: &mut Vec<AstNode>) {
+        let defines = self.options.defines.iter().map(|(name, value)| {
+            AstNode::Statement(Box::new(ast::Statement::Expression(Box::new(ast::Expression::FunctionCall {
+                name: Box::new(ast::Expression::Constant("define".to_string())),
+                arguments: vec![
+                    ast::Expression::Literal(ast::Literal::String(name.clone())),
+                    ast::Expression::Literal(value.clone()),
+                ],
+            }))))
+        });
+        ast.splice(0..0, defines);
+    }
+    
+    /// Parse a PHP file or bundle and run the AST annotation passes
+    ///
+    /// Uses of the configured constants are replaced by their values.
+    fn parse_path(&self, path: &std::path::Path) -> CompileResult<Vec<AstNode>> {
+        // Files are parsed while they are resolved; the rest is resolution
+        let start = Instant::now();
+        let parsing = self.timings.duration(Phase::Parse);
+        let mut ast = if Bundle::is_bundle_path(path) {
+            let bundle = Bundle::open(path)?;
+            self.parse_bundle(&bundle)?
+        } else {
+            // Splice in constant include/require targets
+            let ast = IncludeResolver::new(|file: &std::path::Path| self.load_file(file))
+                .with_include_paths(self.options.include_paths.clone())
+                .resolve(path)?;
+            
+            // Compile in the classes Composer would autoload
+            match Psr4Map::find(path)? {
+                Some(map) => Autoloader::new(map, |file: &std::path::Path| self.load_file(file)).resolve(ast, path)?,
+                None => ast,
+            }
+        };
+        let substituted = substitute_defines(&mut ast, &self.options.defines);
+        if substituted > 0 {
+            debug!("Replaced {} use(s) of defined constants in {}", substituted, path.display());
+        }
+        let parsed = self.timings.duration(Phase::Parse).saturating_sub(parsing);
+        self.end_phase(Phase::Resolve, start.elapsed().saturating_sub(parsed))?;
+        Ok(ast)
+    }
+    
+    /// Parse one PHP file and run the AST annotation passes
+    fn load_file(&self, file: &std::path::Path) -> CompileResult<Vec<AstNode>> {
+        let source = std::fs::read_to_string(file)?;
+        let mut ast = self.parse_source(&source)
+            .with_context(|| file.display().to_string())?;
+        annotate(&mut ast);
+        Ok(ast)
+    }
+    
+    /// Parse the source of one file, timing and counting it
+    fn parse_source(&self, source: &str) -> CompileResult<Vec<AstNode>> {
+        let (ast, duration) = measure_time(|| self.parser.parse(source));
+        self.timings.count_file();
+        self.end_phase(Phase::Parse, duration)?;
+        ast
+    }
+    
+    /// Re-parse the input and report which declarations changed since the last call
+    ///
+    /// On the first call every declaration is reported as added.
+    pub fn parse_changes(&mut self) -> CompileResult<(Vec<AstNode>, AstDiff)> {
+        let ast = self.parse()?;
+        let changes = ast::diff::diff(self.previous_ast.as_deref().unwrap_or(&[]), &ast);
+        info!(
+            "{} declarations added, {} modified, {} removed",
+            changes.added.len(),
+            changes.modified.len(),
+            changes.removed.len()
+        );
+        
+        self.previous_ast = Some(ast.clone());
+        Ok((ast, changes))
+    }
+    
+    /// Parse every PHP file of a bundle into a single compilation unit
+    ///
+    /// The entry point is resolved like a file on disk, with the files it
+    /// includes read from the bundle by their `phar://` URLs. Files it never
+    /// includes only contribute their declarations, as a class map would.
+    fn parse_bundle(&self, bundle: &Bundle) -> CompileResult<Vec<AstNode>> {
+        let entry = bundle.entry_point()?;
+        info!("Compiling bundle {} with entry point {}", bundle.path().display(), entry);
+        
+        let mut resolver = IncludeResolver::new(|file: &std::path::Path| {
+            let url = file.display().to_string();
+            let mut nodes = self.parse_source(bundle.read(&url)?).with_context(|| url.clone())?;
+            annotate(&mut nodes);
+            Ok(nodes)
+        });
+        let entry_nodes = resolver.resolve(std::path::Path::new(&bundle.url(entry)))?;
+        let included: HashSet<String> = resolver.included_files()
+            .map(|file| file.display().to_string())
+            .collect();
+        
+        let mut ast = Vec::new();
+        let mut definitions = DefinitionRegistry::new();
+        definitions.register(&entry_nodes, &bundle.url(entry))?;
+        for name in bundle.files().filter(|name| !included.contains(&bundle.url(name))) {
+            let mut nodes = self.parse_source(bundle.read(name)?)
+                .with_context(|| bundle.url(name))?;
+            annotate(&mut nodes);
+            definitions.register(&nodes, &bundle.url(name))?;
+            for node in nodes {
+                collect_declarations(node, &mut ast);
+            }
+        }
+        ast.extend(entry_nodes);
+        Ok(ast)
+    }
+    
+    /// Diagnostics reported by the last compilation
+    pub fn diagnostics(&self) -> &DiagnosticReport {
+        &self.diagnostics
+    }
+    
+    /// Remove unreachable statements and report them as warnings
+    fn eliminate_unreachable(&mut self, ast: &mut [AstNode]) {
+        let mut eliminator = {
+            let mut index = AstIndex::build(ast);
+            for (&id, &span) in self.spans.get(&self.current_file).into_iter().flatten() {
+                index.set_span(id, span);
+            }
+            UnreachableCodeEliminator::new().with_index(&index)
+        };
+        let warnings = eliminator.run(ast);
+        for warning in warnings {
+            let mut diagnostic = Diagnostic::warning(codes::UNREACHABLE_CODE, warning.message)
+                .with_file(self.current_file.clone())
+                .with_span(warning.span);
+            if let Some(function) = &warning.function {
+                diagnostic = diagnostic.with_note(format!("in function {}", function));
+            }
+            self.diagnostics.push(diagnostic);
+        }
+    }
+    
+    /// Drop the functions and classes no entry point of the program reaches
+    ///
+    /// Skipped at `-O0`.
+    fn eliminate_dead_code(&self, asts: &mut [Vec<AstNode>]) {
+        if self.options.optimization_level == "O0" {
+            return;
+        }
+        let removed = eliminate_dead_code(asts);
+        if removed.functions + removed.classes > 0 {
+            info!("Removed {} unreferenced function(s) and {} class(es)", removed.functions, removed.classes);
+        }
+    }
+    
+    /// Turn calls through variables holding a known function into direct calls
+    fn devirtualize(&self, ast: &mut [AstNode]) {
+        let rewritten = devirtualize(ast, self.options.resolved_int_width());
+        if rewritten > 0 {
+            info!("Devirtualized {} call(s) through callable variables", rewritten);
+        }
+    }
+    
+    /// Generate typed copies of untyped functions for calls with scalar arguments
+    ///
+    /// Skipped at `-O0`.
+    fn specialize(&self, ast: &mut Vec<AstNode>) {
+        if self.options.optimization_level == "O0" {
+            return;
+        }
+        let rewritten = specialize(ast, self.options.resolved_int_width());
+        if rewritten > 0 {
+            info!("Specialized {} call(s) with scalar arguments", rewritten);
+        }
+    }
+    
+    /// Turn self tail calls into loops
+    ///
+    /// Skipped at `-O0`, which keeps a frame for every call.
+    fn eliminate_tail_calls(&self, ast: &mut [AstNode]) {
+        if self.options.optimization_level == "O0" {
+            return;
+        }
+        let rewritten = eliminate_tail_calls(ast);
+        if rewritten > 0 {
+            info!("Turned {} self tail call(s) into loops", rewritten);
+        }
+    }
+    
+    /// Flag `match` statements that cover every value of their subject
+    fn mark_exhaustive_matches(&self, ast: &mut [AstNode]) {
+        let marked = mark_exhaustive_matches(ast, self.options.resolved_int_width());
+        if marked > 0 {
+            info!("Marked {} exhaustive match statement(s)", marked);
+        }
     }
     
     /// Type checking and semantic analysis
-    fn type_check(&mut self, ast: &[AstNode]) -> CompileResult<()> {
+    ///
+    /// Declarations are registered against those of previously checked
+    /// files, so redeclarations across modules are reported.
+    fn type_check(&mut self, ast: &[AstNode], file: &std::path::Path) -> CompileResult<()> {
+        let (result, duration) = measure_time(|| self.analyze_file(ast, file));
+        self.end_phase(Phase::TypeCheck, duration)?;
+        result
+    }
+    
+    fn analyze_file(&mut self, ast: &[AstNode], file: &std::path::Path) -> CompileResult<()> {
         info!("Performing type checking and semantic analysis");
         
+        self.current_file = file.display().to_string();
+        let registered = self.definitions.register(ast, &self.current_file);
+        self.diagnostics.extend(self.definitions.take_diagnostics());
+        registered?;
+        
+        self.type_mode = TypeMode::of(ast);
+        self.signatures.clear();
+        collect_signatures(&self.stubs, &mut self.signatures);
+        collect_signatures(ast, &mut self.signatures);
+        self.register_stubs();
+        
         for node in ast {
             self.analyze_node(node)?;
         }
         
+        let mut literals = LiteralChecker::new(self.current_file.clone())
+            .with_int_width(self.options.resolved_int_width())
+            .with_type_mode(self.type_mode)
+            .with_stubs(&self.stubs);
+        literals.check(ast);
+        self.diagnostics.extend(literals.take_diagnostics());
+        
+        let mut variance = VarianceChecker::new(self.current_file.clone());
+        variance.check(ast);
+        self.diagnostics.extend(variance.take_diagnostics());
+        
+        if let Some(report) = self.options.report_dynamic {
+            let mut fallbacks = FallbackReporter::new(self.current_file.clone(), report)
+                .with_int_width(self.options.resolved_int_width())
+                .with_stubs(&self.stubs);
+            fallbacks.check(ast);
+            self.diagnostics.extend(fallbacks.take_diagnostics());
+        }
+        
         Ok(())
     }
     
+    /// Register the functions and classes of stub files for type checking
+    fn register_stubs(&mut self) {
+        self.type_context.register_declarations(&self.stubs);
+        for decl in stub_functions(&self.stubs) {
+            let func_type = crate::types::Type::Function(
+                decl.parameters.iter()
+                    .map(|p| p.typ.clone().unwrap_or(crate::types::Type::Unknown))
+                    .collect(),
+                Box::new(decl.return_type.clone().unwrap_or(crate::types::Type::Unknown))
+            );
+            self.type_context.register_function(decl.name.clone(), func_type);
+        }
+    }
+    
     /// Analyze a single AST node
     fn analyze_node(&mut self, node: &AstNode) -> CompileResult<()> {
         match node {
             AstNode::Program(statements) => {
-                for stmt in statements {
-                    self.analyze_node(stmt)?;
-                }
+                let outer = self.type_mode.enter(statements);
+                let result = statements.iter().try_for_each(|stmt| self.analyze_node(stmt));
+                self.type_mode = outer;
+                result?;
             }
             AstNode::Function(func_decl) => {
-                self.analyze_function(func_decl)?;
+                self.check_class_scope_types(func_decl, None);
+                self.analyze_function(func_decl, None)?;
             }
             AstNode::Class(class_decl) => {
                 self.analyze_class(class_decl)?;
             }
+            AstNode::Interface(interface_decl) => {
+                let mut class_info = crate::types::ClassInfo::new(interface_decl.name.clone());
+                for parent in &interface_decl.extends {
+                    class_info.add_interface(parent.clone());
+                }
+                self.type_context.register_class(interface_decl.name.clone(), class_info);
+            }
             AstNode::Expression(expr) => {
                 self.analyze_expression(expr)?;
             }
@@ -188,7 +1061,9 @@ impl Compiler {
     }
     
     /// Analyze function declaration
-    fn analyze_function(&mut self, func_decl: &crate::ast::FunctionDecl) -> CompileResult<()> {
+    ///
+    /// `this` is the class of a non-static method.
+    fn analyze_function(&mut self, func_decl: &crate::ast::FunctionDecl, this: Option<&str>) -> CompileResult<()> {
         // Register function in type context
         let func_type = crate::types::Type::Function(
             func_decl.parameters.iter()
@@ -199,32 +1074,61 @@ impl Compiler {
         
         self.type_context.register_function(func_decl.name.clone(), func_type);
         
-        // Analyze function body
-        self.analyze_statement(&func_decl.body)?;
+        // Analyze function body in a scope of its own
+        self.type_context.push_scope(ScopeKind::Function);
+        if let Some(class) = this {
+            self.type_context.register_variable("this".to_string(), crate::types::Type::Object(class.to_string()));
+        }
+        self.register_parameters(&func_decl.parameters);
+        let result = self.analyze_statement(&func_decl.body);
+        self.type_context.pop_scope();
         
-        Ok(())
+        result
+    }
+    
+    fn register_parameters(&mut self, parameters: &[ast::Parameter]) {
+        for param in parameters {
+            let typ = match (&param.typ, param.is_variadic) {
+                (_, true) => crate::types::Type::Array(Box::new(param.typ.clone().unwrap_or(crate::types::Type::Unknown))),
+                (Some(typ), false) => typ.clone(),
+                (None, false) => crate::types::Type::Unknown,
+            };
+            self.type_context.register_variable(param.name.clone(), typ);
+        }
     }
     
     /// Analyze class declaration
     fn analyze_class(&mut self, class_decl: &crate::ast::ClassDecl) -> CompileResult<()> {
         let mut class_info = crate::types::ClassInfo::new(class_decl.name.clone());
+        if let Some(parent) = &class_decl.extends {
+            class_info.set_parent(parent.clone());
+        }
+        for interface in &class_decl.implements {
+            class_info.add_interface(interface.clone());
+        }
         
-        // Analyze properties
+        // Analyze properties; `self` and `parent` are bound now, `static`
+        // stays late-bound to the class a member is used through
         for prop in &class_decl.properties {
             let prop_type = prop.typ.clone().unwrap_or(crate::types::Type::Unknown);
-            class_info.add_property(prop.name.clone(), prop_type);
+            if let Some(typ) = &prop.typ {
+                self.check_relative_type(typ, Some(class_decl), false, &format!("{}::${}", class_decl.name, prop.name));
+            }
+            class_info.add_property(prop.name.clone(), class_info.bind_relative(&prop_type, None));
         }
         
         // Analyze methods
         for method in &class_decl.methods {
-            self.analyze_function(method)?;
+            let this = (!method.is_static).then_some(class_decl.name.as_str());
+            self.check_class_scope_types(method, Some(class_decl));
+            self.analyze_function(method, this)?;
             let method_type = crate::types::Type::Function(
                 method.parameters.iter()
                     .map(|p| p.typ.clone().unwrap_or(crate::types::Type::Unknown))
                     .collect(),
                 Box::new(method.return_type.clone().unwrap_or(crate::types::Type::Unknown))
             );
-            class_info.add_method(method.name.clone(), method_type);
+            class_info.add_method(method.name.clone(), class_info.bind_relative(&method_type, None));
         }
         
         // Register class in type context
@@ -233,8 +1137,45 @@ impl Compiler {
         Ok(())
     }
     
+    /// Report `self`, `parent` and `static` in a signature where they cannot
+    /// refer to a class
+    fn check_class_scope_types(&mut self, func_decl: &crate::ast::FunctionDecl, class: Option<&crate::ast::ClassDecl>) {
+        let owner = match class {
+            Some(class) => format!("{}::{}()", class.name, func_decl.name),
+            None => format!("{}()", func_decl.name),
+        };
+        for parameter in &func_decl.parameters {
+            if let Some(typ) = &parameter.typ {
+                self.check_relative_type(typ, class, false, &owner);
+            }
+        }
+        if let Some(typ) = &func_decl.return_type {
+            self.check_relative_type(typ, class, true, &owner);
+        }
+    }
+    
+    fn check_relative_type(&mut self, typ: &crate::types::Type, class: Option<&crate::ast::ClassDecl>, is_return: bool, owner: &str) {
+        let mut names = Vec::new();
+        relative_class_types(typ, &mut names);
+        for name in names {
+            let message = match (name.as_str(), class) {
+                (_, None) => format!("Cannot use \"{}\" when no class scope is active", name),
+                ("parent", Some(class)) if class.extends.is_none() => {
+                    "Cannot use \"parent\" when current class scope has no parent".to_string()
+                }
+                ("static", Some(_)) if !is_return => "\"static\" can only be used as a return type".to_string(),
+                _ => continue,
+            };
+            self.diagnostics.push(
+                Diagnostic::error(codes::CLASS_SCOPE_TYPE, message)
+                    .with_file(self.current_file.clone())
+                    .with_note(format!("in {}", owner)),
+            );
+        }
+    }
+    
     /// Analyze expression
-    fn analyze_expression(&self, expr: &crate::ast::Expression) -> CompileResult<()> {
+    fn analyze_expression(&mut self, expr: &crate::ast::Expression) -> CompileResult<()> {
         // TODO: Implement expression analysis
         match expr {
             crate::ast::Expression::Literal(_) => {
@@ -243,9 +1184,35 @@ impl Compiler {
             crate::ast::Expression::Variable(name) => {
                 // Check if variable is declared
                 if self.type_context.get_variable_type(name).is_none() {
-                    warn!("Variable '{}' may be undefined", name);
+                    self.diagnostics.push(
+                        Diagnostic::warning(codes::UNDEFINED_VARIABLE, format!("Variable ${} may be undefined", name))
+                            .with_file(self.current_file.clone()),
+                    );
+                }
+            }
+            crate::ast::Expression::FunctionCall { name, arguments } => {
+                self.check_arguments(name, arguments);
+            }
+            crate::ast::Expression::Assignment { target, op, value } => {
+                self.analyze_expression(value)?;
+                match target.as_ref() {
+                    crate::ast::Expression::Variable(name) if *op == ast::AssignmentOperator::Assign => {
+                        self.type_context.register_variable(name.clone(), crate::types::Type::Unknown);
+                    }
+                    target => self.analyze_expression(target)?,
                 }
             }
+            crate::ast::Expression::Closure(closure) => {
+                if closure.is_arrow {
+                    self.type_context.push_scope(ScopeKind::ArrowFunction);
+                } else {
+                    self.type_context.push_closure_scope(&closure.uses);
+                }
+                self.register_parameters(&closure.parameters);
+                let result = self.analyze_statement(&closure.body);
+                self.type_context.pop_scope();
+                result?;
+            }
             _ => {
                 // TODO: Implement analysis for other expression types
                 warn!("Expression analysis not yet implemented for {:?}", expr);
@@ -254,8 +1221,40 @@ impl Compiler {
         Ok(())
     }
     
+    /// Report literal arguments that a call to a known function would reject with a `TypeError`
+    fn check_arguments(&mut self, name: &crate::ast::Expression, arguments: &[crate::ast::Expression]) {
+        let crate::ast::Expression::Constant(name) = name else { return };
+        let Some((function, parameters)) = self.signatures.get(&name.trim_start_matches('\\').to_lowercase()) else {
+            return;
+        };
+        for (i, (argument, parameter)) in arguments.iter().zip(parameters).enumerate() {
+            if parameter.is_variadic {
+                break;
+            }
+            let value = match argument {
+                crate::ast::Expression::Literal(ast::Literal::Int(n)) => crate::runtime::Value::Int(*n),
+                crate::ast::Expression::Literal(ast::Literal::Float(x)) => crate::runtime::Value::Float(*x),
+                crate::ast::Expression::Literal(ast::Literal::String(s)) => crate::runtime::Value::String(s.clone()),
+                crate::ast::Expression::Literal(ast::Literal::Bool(b)) => crate::runtime::Value::Bool(*b),
+                crate::ast::Expression::Literal(ast::Literal::Null) => crate::runtime::Value::Null,
+                _ => continue,
+            };
+            if let Err(given) = coercion::coerce_argument(value, parameter, self.type_mode) {
+                let mut diagnostic = Diagnostic::error(
+                    codes::ARGUMENT_TYPE,
+                    coercion::argument_error(function, i + 1, parameter, given),
+                )
+                .with_file(self.current_file.clone());
+                if self.type_mode == TypeMode::Strict {
+                    diagnostic = diagnostic.with_note("strict_types=1 is declared, so scalar arguments are not coerced");
+                }
+                self.diagnostics.push(diagnostic);
+            }
+        }
+    }
+    
     /// Analyze statement
-    fn analyze_statement(&self, stmt: &crate::ast::Statement) -> CompileResult<()> {
+    fn analyze_statement(&mut self, stmt: &crate::ast::Statement) -> CompileResult<()> {
         // TODO: Implement statement analysis
         match stmt {
             crate::ast::Statement::Expression(expr) => {
@@ -266,6 +1265,23 @@ impl Compiler {
                     self.analyze_statement(stmt)?;
                 }
             }
+            crate::ast::Statement::If { condition, then_branch, else_branch } => {
+                self.analyze_expression(condition)?;
+                for branch in std::iter::once(then_branch).chain(else_branch) {
+                    self.type_context.push_scope(ScopeKind::Block);
+                    let result = self.analyze_statement(branch);
+                    self.type_context.pop_scope();
+                    result?;
+                }
+            }
+            crate::ast::Statement::Global(names) => {
+                for name in names {
+                    self.type_context.declare_global(name.clone());
+                }
+            }
+            crate::ast::Statement::Return(Some(expr)) => {
+                self.analyze_expression(expr)?;
+            }
             _ => {
                 // TODO: Implement analysis for other statement types
                 warn!("Statement analysis not yet implemented for {:?}", stmt);
@@ -274,19 +1290,112 @@ impl Compiler {
         Ok(())
     }
     
-    /// Generate LLVM IR
+    /// Generate the backend's IR (LLVM IR by default)
     pub fn generate_ir(&mut self) -> CompileResult<String> {
         let ast = self.parse()?;
-        self.ir_generator.generate(&ast)
+        self.lower(ast)
+    }
+    
+    /// Generate LLVM IR for PHP source held in memory, without type checking
+    ///
+    /// Like [`generate_ir`](Self::generate_ir), with `source` in place of
+    /// the input file; see [`compile_source`](Self::compile_source).
+    pub fn generate_ir_from_source(&mut self, source: &str) -> CompileResult<String> {
+        let ast = self.parse_from_source(source)?;
+        self.lower(ast)
+    }
+    
+    /// Compile PHP source held in memory to IR, without reading or writing files
+    ///
+    /// The input path only names the source in diagnostics. With no file to
+    /// resolve them against, includes are not spliced in and classes are not
+    /// autoloaded. The IR is returned before the LLVM pass pipeline, which
+    /// runs when it is compiled.
+    pub fn compile_source(&mut self, source: &str) -> CompileResult<Artifacts> {
+        self.diagnostics.clear();
+        self.timings.clear();
+        let ast = self.parse_from_source(source)?;
+        let input = self.options.input.clone();
+        self.type_check(&ast, &input)?;
+        let ir = self.lower(ast)?;
+        Ok(Artifacts { ir, diagnostics: self.diagnostics.clone() })
+    }
+    
+    /// Parse source held in memory as the input
+    fn parse_from_source(&mut self, source: &str) -> CompileResult<Vec<AstNode>> {
+        let name = self.options.input.display().to_string();
+        let mut ast = self.parse_source(source).with_context(|| name)?;
+        annotate(&mut ast);
+        substitute_defines(&mut ast, &self.options.defines);
+        self.define_constants(&mut ast);
+        if self.options.interpreter_fallback {
+            self.backend.set_fallback_source(source);
+        }
+        Ok(ast)
+    }
+    
+    /// Run the AST passes on a parsed input and generate its IR
+    fn lower(&mut self, mut ast: Vec<AstNode>) -> CompileResult<String> {
+        let (_, duration) = measure_time(|| {
+            self.eliminate_unreachable(&mut ast);
+            self.eliminate_dead_code(std::slice::from_mut(&mut ast));
+            self.devirtualize(&mut ast);
+            self.specialize(&mut ast);
+            self.eliminate_tail_calls(&mut ast);
+            self.mark_exhaustive_matches(&mut ast);
+        });
+        self.timings.count_ast(&mut ast);
+        self.end_phase(Phase::Optimize, duration)?;
+        for decl in external_functions(&self.stubs, &ast) {
+            self.backend.declare_external(decl)?;
+        }
+        let (ir, duration) = measure_time(|| self.backend.generate(&ast));
+        self.end_phase(Phase::IrGen, duration)?;
+        let ir = ir?;
+        self.timings.count_ir(&ir);
+        Ok(ir)
     }
     
     /// Optimize LLVM IR
-    fn optimize_ir(&self, ir: &str) -> CompileResult<()> {
-        info!("Optimizing LLVM IR with level {}", self.options.optimization_level);
+    ///
+    /// Only IR written out as such is optimized here; modules compiled to
+    /// code run the pipeline while they are compiled, along with any
+    /// profile instrumentation.
+    fn optimize_ir(&self, ir: String, scratch: &std::path::Path) -> CompileResult<String> {
+        let writes_ir = self.options.emit_llvm_only || self.options.emit_bitcode;
+        if self.options.backend != BackendKind::Llvm || !writes_ir {
+            return Ok(ir);
+        }
+        if self.options.optimization_level == "O0" && self.options.passes.is_none() {
+            return Ok(ir);
+        }
         
-        // TODO: Implement IR optimization passes
-        // This would typically involve running LLVM optimization passes
+        let pipeline = backend::pass_pipeline(&self.options.optimization_level, self.options.passes.as_deref());
+        info!("Optimizing LLVM IR with {}", pipeline);
+        LlvmBackend::optimize_module(&ir, scratch, &self.options.optimization_level, self.options.passes.as_deref())
+    }
+    
+    /// Write a C header declaring the exported functions of the input and modules
+    fn write_header(&mut self) -> CompileResult<()> {
+        let paths: Vec<PathBuf> = std::iter::once(self.options.input.clone())
+            .chain(self.options.modules.iter().cloned())
+            .collect();
+        let mut ast = Vec::new();
+        for path in &paths {
+            let nodes = self.parse_path(path)?;
+            self.type_check(&nodes, path)?;
+            ast.extend(nodes);
+        }
         
+        let output_path = if self.options.output.extension().is_some() {
+            self.options.output.clone()
+        } else {
+            self.options.output.with_extension("h")
+        };
+        let name = output_path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let header = header::generate_header(&ast, &name, self.options.resolved_int_width(), None)?;
+        std::fs::write(&output_path, header)?;
+        info!("C header written to {}", output_path.display());
         Ok(())
     }
     
@@ -295,7 +1404,7 @@ impl Compiler {
         let output_path = if self.options.output.extension().is_some() {
             self.options.output.clone()
         } else {
-            self.options.output.with_extension("ll")
+            self.options.output.with_extension(self.backend.ir_extension())
         };
         
         std::fs::write(&output_path, ir)
@@ -304,66 +1413,88 @@ impl Compiler {
         Ok(())
     }
     
+    /// Write IR to file as LLVM bitcode
+    fn write_bitcode_file(&mut self, ir: &str) -> CompileResult<()> {
+        let output_path = if self.options.output.extension().is_some() {
+            self.options.output.clone()
+        } else {
+            self.options.output.with_extension("bc")
+        };
+        self.backend.emit_bitcode(ir, &output_path)
+    }
+    
+    /// Compile IR to a target assembly file
+    fn write_assembly_file(&mut self, ir: &str) -> CompileResult<()> {
+        let output_path = if self.options.output.extension().is_some() {
+            self.options.output.clone()
+        } else {
+            self.options.output.with_extension("s")
+        };
+        self.backend.emit_assembly(ir, &output_path)
+    }
+    
     /// Generate object file from IR
-    fn generate_object_file(&self, ir: &str) -> CompileResult<()> {
-        info!("Generating object file");
-        
-        let ir_file = self.options.output.with_extension("ll");
-        let obj_file = self.options.output.with_extension("o");
-        
-        // Write IR to temporary file
-        std::fs::write(&ir_file, ir)
-            .map_err(|e| CompileError::Io(e))?;
-        
-        // Use llc to generate object file
-        let mut cmd = Command::new("llc");
-        cmd.arg("-filetype=obj")
-            .arg("-o")
-            .arg(&obj_file)
-            .arg(&ir_file);
-        
-        if self.options.optimization_level != "O0" {
-            cmd.arg(format!("-O{}", &self.options.optimization_level[1..]));
-        }
-        
-        let output = cmd.output()
-            .map_err(|e| CompileError::Internal(format!("Failed to run llc: {}", e)))?;
-        
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(CompileError::LlvmCompilation(stderr.to_string()));
-        }
-        
-        info!("Object file generated: {}", obj_file.display());
-        Ok(())
+    fn generate_object_file(&mut self, ir: &str) -> CompileResult<()> {
+        let obj_file = self.object_file();
+        self.backend.emit_object(ir, &obj_file)
+    }
+    
+    /// Object file of a single-file compilation, an intermediate file when it is linked
+    fn object_file(&self) -> PathBuf {
+        self.intermediate_path(self.options.output.with_extension("o"))
     }
     
     /// Link binary from object file
     fn link_binary(&self) -> CompileResult<()> {
-        info!("Linking binary");
-        
-        let obj_file = self.options.output.with_extension("o");
-        
-        // Use lld to link binary
-        let mut cmd = Command::new("ld.lld");
-        cmd.arg("-o")
-            .arg(&self.options.output)
-            .arg(&obj_file);
+        self.link_objects(&[self.object_file()])
+    }
+    
+    /// Link binary from object files
+    fn link_objects(&self, objects: &[PathBuf]) -> CompileResult<()> {
+        let (result, duration) = measure_time(|| self.run_linker(objects));
+        self.end_phase(Phase::Link, duration)?;
+        result
+    }
+    
+    fn run_linker(&self, objects: &[PathBuf]) -> CompileResult<()> {
+        info!("Linking binary from {} objects", objects.len());
         
-        // Add runtime library if not disabled
-        if !self.options.no_runtime {
-            // TODO: Add runtime library linking
-        }
+        let target = self.options.resolved_target();
+        let linker = Linker::select(self.options.linker.as_deref(), &target)?;
+        let lto = match self.options.lto {
+            Some(_) => Some(LtoLinker::for_linker(&linker, &self.options.optimization_level)?),
+            None => None,
+        };
+        let runtime = if self.options.no_runtime {
+            None
+        } else {
+            Some(link::find_runtime(self.options.stdlib.as_deref(), &target)?)
+        };
+        let runtime = match (&lto, runtime) {
+            (Some(lto), Some(runtime)) => Some(lto.runtime(&runtime, &self.intermediate_path(self.options.output.with_extension("rt.a")))?),
+            (_, runtime) => runtime,
+        };
+        let output = link::executable_path(&self.options.output, &target);
+        let mut cmd = Link::new(target, linker)
+            .with_sysroot(self.options.sysroot.clone())
+            .with_runtime(runtime)
+            .with_lto(lto)
+            .with_release_size(self.options.release_size)
+            .command(objects, &output)?;
+        debug!("Linking with {:?}", cmd);
         
-        let output = cmd.output()
-            .map_err(|e| CompileError::Internal(format!("Failed to run lld: {}", e)))?;
+        let result = cmd.output()
+            .map_err(|e| CompileError::Internal(format!("Failed to run the linker: {}", e)))?;
         
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
+        if !result.status.success() {
+            let stderr = String::from_utf8_lossy(&result.stderr);
             return Err(CompileError::Linking(stderr.to_string()));
         }
         
-        info!("Binary linked: {}", self.options.output.display());
+        info!("Binary linked: {}", output.display());
+        if self.options.release_size {
+            report_size(&output);
+        }
         Ok(())
     }
     
@@ -380,6 +1511,9 @@ impl Compiler {
             "x86_64-pc-windows-gnu",
             "aarch64-unknown-linux-gnu",
             "aarch64-apple-darwin",
+            "i686-unknown-linux-gnu",
+            "armv7-unknown-linux-gnueabihf",
+            "wasm32-unknown-unknown",
         ]
     }
     
@@ -389,6 +1523,111 @@ impl Compiler {
     }
 }
 
+/// Run the AST annotation passes over one parsed file
+pub(crate) fn annotate(ast: &mut [AstNode]) {
+    // Type untyped declarations from their docblocks
+    crate::phpdoc::apply(ast);
+    
+    // Rewrite names to their fully-qualified form
+    crate::names::resolve_names(ast);
+}
+
+/// `self`, `parent` and `static` used in a type, lowercased
+fn relative_class_types(typ: &crate::types::Type, out: &mut Vec<String>) {
+    use crate::types::Type;
+    match typ {
+        Type::Object(name) => {
+            let name = name.to_ascii_lowercase();
+            if matches!(name.as_str(), "self" | "parent" | "static") {
+                out.push(name);
+            }
+        }
+        Type::Union(members) | Type::Intersection(members) => {
+            members.iter().for_each(|member| relative_class_types(member, out));
+        }
+        _ => {}
+    }
+}
+
+/// Record the parameters of top-level functions for argument checking
+fn collect_signatures(ast: &[AstNode], out: &mut HashMap<String, (String, Vec<ast::Parameter>)>) {
+    for node in ast {
+        match node {
+            AstNode::Function(decl) => {
+                out.insert(decl.name.to_lowercase(), (decl.name.clone(), decl.parameters.clone()));
+            }
+            AstNode::Program(nodes) => collect_signatures(nodes, out),
+            AstNode::Namespace(ns) => collect_signatures(&ns.statements, out),
+            _ => {}
+        }
+    }
+}
+
+/// Log the size of a `--release-size` binary, with how to compress it further
+fn report_size(binary: &std::path::Path) {
+    if let Ok(metadata) = std::fs::metadata(binary) {
+        info!("Binary size: {} bytes", metadata.len());
+    }
+    if process::command_exists("upx") {
+        info!("Compress it further with `upx --best --lzma {}`", binary.display());
+    } else {
+        debug!("An executable packer such as UPX can compress the binary further");
+    }
+}
+
+/// Stub functions a program does not define itself
+fn external_functions<'a>(stubs: &'a [AstNode], ast: &[AstNode]) -> Vec<&'a ast::FunctionDecl> {
+    let mut defined = HashMap::new();
+    collect_signatures(ast, &mut defined);
+    stub_functions(stubs).into_iter()
+        .filter(|decl| !defined.contains_key(&decl.name.to_lowercase()))
+        .collect()
+}
+
+/// Parse stub files into their declarations
+fn load_stubs(parser: &DefaultParser, paths: &[PathBuf]) -> CompileResult<Vec<AstNode>> {
+    let mut stubs = Vec::new();
+    for path in paths {
+        if !is_stub_path(path) {
+            return Err(CompileError::Configuration(format!(
+                "stub file {} must have the .{} extension",
+                path.display(),
+                STUB_EXTENSION
+            )));
+        }
+        let file = path.display().to_string();
+        let source = std::fs::read_to_string(path)?;
+        let mut ast = parser.parse(&source).with_context(|| file.clone())?;
+        annotate(&mut ast);
+        for node in ast {
+            stub_declarations(node, &file, &mut stubs);
+        }
+    }
+    if !stubs.is_empty() {
+        info!("Loaded {} declarations from {} stub file(s)", stubs.len(), paths.len());
+    }
+    Ok(stubs)
+}
+
+/// Keep only the declarations of a library file
+fn collect_declarations(node: AstNode, out: &mut Vec<AstNode>) {
+    match node {
+        AstNode::Program(nodes) => {
+            for node in nodes {
+                collect_declarations(node, out);
+            }
+        }
+        AstNode::Function(_)
+        | AstNode::Class(_)
+        | AstNode::Interface(_)
+        | AstNode::Trait(_)
+        | AstNode::Enum(_)
+        | AstNode::Namespace(_)
+        | AstNode::Use(_) => out.push(node),
+        _ => {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -400,6 +1639,8 @@ mod tests {
         assert_eq!(options.optimization_level, "O2");
         assert!(!options.emit_llvm);
         assert!(!options.emit_llvm_only);
+        assert!(!options.emit_bitcode);
+        assert!(!options.emit_asm);
     }
 
     #[test]
@@ -409,6 +1650,273 @@ mod tests {
         assert!(compiler.is_ok());
     }
 
+    #[test]
+    fn test_stubs() {
+        use crate::ast::*;
+        use crate::types::Type;
+        
+        let options = CompilerOptions { stubs: vec![PathBuf::from("redis.php")], ..CompilerOptions::default() };
+        assert!(matches!(Compiler::new(options), Err(CompileError::Configuration(_))));
+        
+        let stub = AstNode::Function(FunctionDecl {
+            name: "redis_incr".to_string(),
+            parameters: vec![Parameter {
+                name: "by".to_string(),
+                typ: Some(Type::Int),
+                default_value: None,
+                is_reference: false,
+                is_variadic: false,
+            }],
+            return_type: Some(Type::Int),
+            body: Box::new(Statement::Block(vec![])),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        });
+        let call = AstNode::Expression(Box::new(Expression::FunctionCall {
+            name: Box::new(Expression::Constant("redis_incr".to_string())),
+            arguments: vec![Expression::Literal(Literal::String("one".to_string()))],
+        }));
+        let mut compiler = Compiler::new(CompilerOptions::default()).unwrap();
+        compiler.stubs = vec![stub];
+        let ast = vec![call];
+        compiler.type_check(&ast, std::path::Path::new("app.php")).unwrap();
+        let errors: Vec<_> = compiler.diagnostics().with_code(codes::ARGUMENT_TYPE).collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "redis_incr(): Argument #1 ($by) must be of type int, string given");
+        assert!(compiler.type_context.get_function_type("redis_incr").is_some());
+        assert_eq!(external_functions(&compiler.stubs, &ast).len(), 1);
+    }
+
+    #[test]
+    fn test_type_check_diagnostics() {
+        use crate::ast::{Expression, Statement};
+        
+        let mut compiler = Compiler::new(CompilerOptions::default()).unwrap();
+        let mut ast = vec![
+            AstNode::Expression(Box::new(Expression::Variable("x".to_string()))),
+            AstNode::Statement(Box::new(Statement::Block(vec![
+                Statement::Return(None),
+                Statement::Echo(vec![]),
+            ]))),
+        ];
+        compiler.type_check(&ast, std::path::Path::new("app.php")).unwrap();
+        compiler.eliminate_unreachable(&mut ast);
+        
+        let report = compiler.diagnostics();
+        let undefined: Vec<_> = report.with_code(codes::UNDEFINED_VARIABLE).collect();
+        assert_eq!(undefined.len(), 1);
+        assert_eq!(undefined[0].message, "Variable $x may be undefined");
+        assert_eq!(undefined[0].file.as_deref(), Some("app.php"));
+        assert_eq!(report.with_code(codes::UNREACHABLE_CODE).count(), 1);
+        assert!(!report.has_errors());
+    }
+
+    #[test]
+    fn test_unreachable_code_spans() {
+        use crate::ast::{Expression, Literal, Statement};
+        
+        let mut compiler = Compiler::new(CompilerOptions::default()).unwrap();
+        let mut ast = vec![AstNode::Statement(Box::new(Statement::Block(vec![
+            Statement::Return(None),
+            Statement::Echo(vec![Expression::Literal(Literal::Int(1))]),
+        ])))];
+        // node, block, return, echo, literal
+        compiler.set_spans("app.php", HashMap::from([(NodeId(3), Span::new(17, 25))]));
+        compiler.type_check(&ast, std::path::Path::new("app.php")).unwrap();
+        compiler.eliminate_unreachable(&mut ast);
+        
+        let unreachable: Vec<_> = compiler.diagnostics().with_code(codes::UNREACHABLE_CODE).collect();
+        assert_eq!(unreachable.len(), 1);
+        assert_eq!(unreachable[0].span, Some(Span::new(17, 25)));
+    }
+
+    #[test]
+    fn test_function_scopes() {
+        use crate::ast::*;
+        
+        let function = |name: &str, body: Vec<Statement>| AstNode::Function(FunctionDecl {
+            name: name.to_string(),
+            parameters: vec![],
+            return_type: None,
+            body: Box::new(Statement::Block(body)),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        });
+        let assign_x = Statement::Expression(Box::new(Expression::Assignment {
+            target: Box::new(Expression::Variable("x".to_string())),
+            op: AssignmentOperator::Assign,
+            value: Box::new(Expression::Literal(Literal::Int(1))),
+        }));
+        let read_x = Statement::Return(Some(Box::new(Expression::Variable("x".to_string()))));
+        
+        // $x of `first` is not visible in `second`, nor at the top level
+        let ast = vec![
+            function("first", vec![assign_x.clone(), read_x.clone()]),
+            function("second", vec![read_x]),
+            AstNode::Expression(Box::new(Expression::Variable("x".to_string()))),
+        ];
+        let mut compiler = Compiler::new(CompilerOptions::default()).unwrap();
+        compiler.type_check(&ast, std::path::Path::new("app.php")).unwrap();
+        assert_eq!(compiler.diagnostics().with_code(codes::UNDEFINED_VARIABLE).count(), 2);
+    }
+
+    #[test]
+    fn test_class_scope_types() {
+        use crate::ast::*;
+        use crate::types::Type;
+
+        let method = |name: &str, parameter: Option<Type>, returns: &str| FunctionDecl {
+            name: name.to_string(),
+            parameters: parameter.into_iter().map(|typ| Parameter {
+                name: "other".to_string(),
+                typ: Some(typ),
+                default_value: None,
+                is_reference: false,
+                is_variadic: false,
+            }).collect(),
+            return_type: Some(Type::Object(returns.to_string())),
+            body: Box::new(Statement::Block(vec![])),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        };
+        let class = |name: &str, extends: Option<&str>, methods: Vec<FunctionDecl>| AstNode::Class(ClassDecl {
+            name: name.to_string(),
+            extends: extends.map(str::to_string),
+            implements: vec![],
+            traits: vec![],
+            properties: vec![],
+            methods,
+            constants: vec![],
+            attributes: vec![],
+            is_abstract: false,
+            is_final: false,
+            is_trait: false,
+            is_interface: false,
+            is_enum: false,
+        });
+
+        let ast = vec![
+            class("Builder", None, vec![
+                method("where", Some(Type::Object("self".to_string())), "static"),
+                method("base", None, "parent"),
+                method("merge", Some(Type::Object("static".to_string())), "self"),
+            ]),
+            class("QueryBuilder", Some("Builder"), vec![method("base", None, "parent")]),
+            AstNode::Function(method("make", None, "self")),
+        ];
+        let mut compiler = Compiler::new(CompilerOptions::default()).unwrap();
+        compiler.type_check(&ast, std::path::Path::new("app.php")).unwrap();
+        let messages: Vec<_> = compiler.diagnostics()
+            .with_code(codes::CLASS_SCOPE_TYPE)
+            .map(|d| d.message.as_str())
+            .collect();
+        assert_eq!(messages, [
+            "Cannot use \"parent\" when current class scope has no parent",
+            "\"static\" can only be used as a return type",
+            "Cannot use \"self\" when no class scope is active",
+        ]);
+    }
+
+    #[test]
+    fn test_argument_type_diagnostics() {
+        use crate::ast::*;
+        use crate::types::Type;
+        
+        let function = AstNode::Function(FunctionDecl {
+            name: "square".to_string(),
+            parameters: vec![Parameter {
+                name: "n".to_string(),
+                typ: Some(Type::Int),
+                default_value: None,
+                is_reference: false,
+                is_variadic: false,
+            }],
+            return_type: Some(Type::Int),
+            body: Box::new(Statement::Block(vec![])),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        });
+        let call = |argument: &str| AstNode::Expression(Box::new(Expression::FunctionCall {
+            name: Box::new(Expression::Constant("square".to_string())),
+            arguments: vec![Expression::Literal(Literal::String(argument.to_string()))],
+        }));
+        let strict = AstNode::Statement(Box::new(Statement::Declare {
+            directives: vec![DeclareDirective {
+                name: "strict_types".to_string(),
+                value: Expression::Literal(Literal::Int(1)),
+            }],
+            body: Box::new(Statement::Block(vec![])),
+        }));
+        
+        // Coercive mode only rejects non-numeric strings
+        let mut compiler = Compiler::new(CompilerOptions::default()).unwrap();
+        let ast = vec![function.clone(), call("4"), call("four")];
+        compiler.type_check(&ast, std::path::Path::new("a.php")).unwrap();
+        let errors: Vec<_> = compiler.diagnostics().with_code(codes::ARGUMENT_TYPE).collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "square(): Argument #1 ($n) must be of type int, string given");
+        
+        let mut compiler = Compiler::new(CompilerOptions::default()).unwrap();
+        let ast = vec![strict, function, call("4")];
+        compiler.type_check(&ast, std::path::Path::new("b.php")).unwrap();
+        assert_eq!(compiler.diagnostics().with_code(codes::ARGUMENT_TYPE).count(), 1);
+        assert!(compiler.diagnostics().has_errors());
+    }
+
+    #[test]
+    fn test_check_without_toolchain() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("app.php");
+        std::fs::write(&input, "<?php\necho 1;\n").unwrap();
+        
+        let options = CompilerOptions {
+            input: input.clone(),
+            output: dir.path().join("app"),
+            ..CompilerOptions::default()
+        };
+        let mut compiler = Compiler::new(options.clone()).unwrap();
+        compiler.check().unwrap();
+        assert!(!compiler.diagnostics().has_errors());
+        assert!(!dir.path().join("app").exists());
+        
+        let options = CompilerOptions { modules: vec![dir.path().join("missing.php")], ..options };
+        assert!(Compiler::new(options).unwrap().check().is_err());
+    }
+
+    #[test]
+    fn test_compile_source() {
+        let options = CompilerOptions {
+            input: PathBuf::from("memory.php"),
+            ..CompilerOptions::default()
+        };
+        let mut compiler = Compiler::in_memory(options).unwrap();
+        let artifacts = compiler.compile_source("<?php\necho 'Hello, World!';\n").unwrap();
+        assert!(artifacts.ir.contains("define"));
+        assert!(!artifacts.diagnostics.has_errors());
+        assert_eq!(compiler.timings().counts().files, 1);
+        
+        let ir = compiler.generate_ir_from_source("<?php\necho 'Hello, World!';\n").unwrap();
+        assert!(ir.contains("Hello, World!"));
+    }
+    
+    #[test]
+    fn test_int_width_from_options() {
+        let mut options = CompilerOptions::default();
+        options.target = Some("armv7-unknown-linux-gnueabihf".to_string());
+        assert_eq!(options.resolved_int_width(), IntWidth::W32);
+        
+        options.int_width = Some(IntWidth::W64);
+        assert_eq!(options.resolved_int_width(), IntWidth::W64);
+    }
+
     #[test]
     fn test_supported_targets() {
         let targets = Compiler::supported_targets();
@@ -0,0 +1,797 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Cranelift backend.
+//!
+//! Lowers the integer subset of PHP (int/bool values, arithmetic and
+//! comparisons, user functions, `if`/`while`/`for`, `echo`) straight to a
+//! native object without an LLVM toolchain. Every PHP value is a machine
+//! integer of the configured width; anything outside the subset is reported
+//! as unsupported rather than miscompiled.
+
+use std::collections::HashMap;
+use std::path::Path;
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{types, AbiParam, Block, InstBuilder, Type, Value};
+use cranelift_codegen::isa::OwnedTargetIsa;
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_module::{default_libcall_names, DataDescription, FuncId, Linkage, Module};
+use cranelift_object::{ObjectBuilder, ObjectModule};
+use log::{info, warn};
+use crate::ast::{
+    AssignmentOperator, AstNode, BinaryOperator, Expression, FunctionDecl, Literal, Parameter, Statement,
+    UnaryOperator,
+};
+use crate::backend::{Backend, BackendKind};
+use crate::directives::CodegenDirectives;
+use crate::error::{CompileError, CompileResult};
+use crate::target::TargetSpec;
+use crate::types::IntWidth;
+
+/// Cranelift code generation backend
+pub struct CraneliftBackend {
+    optimization_level: String,
+    int_width: IntWidth,
+    target: TargetSpec,
+
+    /// Object produced by the last `generate` call
+    object: Option<Vec<u8>>,
+}
+
+impl CraneliftBackend {
+    pub fn new(optimization_level: &str) -> Self {
+        Self {
+            optimization_level: optimization_level.to_string(),
+            int_width: IntWidth::default(),
+            target: TargetSpec::default(),
+            object: None,
+        }
+    }
+
+    /// Set the width of PHP `int`
+    pub fn with_int_width(mut self, int_width: IntWidth) -> Self {
+        self.int_width = int_width;
+        self
+    }
+
+    /// Set the target; only the host gets its CPU features
+    pub fn with_target(mut self, target: TargetSpec) -> Self {
+        self.target = target;
+        self
+    }
+
+    fn isa(&self) -> CompileResult<OwnedTargetIsa> {
+        let mut flags = settings::builder();
+        let opt_level = if self.optimization_level == "O0" { "none" } else { "speed" };
+        flags.set("opt_level", opt_level).map_err(codegen_error)?;
+        flags.set("is_pic", "true").map_err(codegen_error)?;
+
+        let builder = if self.target.is_host() {
+            cranelift_native::builder()
+                .map_err(|e| CompileError::Configuration(format!("unsupported host for cranelift: {}", e)))?
+        } else {
+            cranelift_codegen::isa::lookup_by_name(self.target.triple()).map_err(|e| {
+                CompileError::Configuration(format!("unsupported target {} for cranelift: {}", self.target.triple(), e))
+            })?
+        };
+        builder.finish(settings::Flags::new(flags))
+            .map_err(codegen_error)
+    }
+}
+
+impl Backend for CraneliftBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Cranelift
+    }
+
+    fn ir_extension(&self) -> &'static str {
+        "clif"
+    }
+
+    fn generate(&mut self, ast: &[AstNode]) -> CompileResult<String> {
+        info!("Generating Cranelift IR from {} AST nodes", ast.len());
+
+        let builder = ObjectBuilder::new(self.isa()?, "php2ir", default_libcall_names())
+            .map_err(codegen_error)?;
+        let mut lowering = ModuleLowering {
+            module: ObjectModule::new(builder),
+            int: int_type(self.int_width),
+            int_width: self.int_width,
+            functions: HashMap::new(),
+            clif: String::new(),
+        };
+
+        let mut functions = Vec::new();
+        let mut code = Vec::new();
+        split_top_level(ast, &mut functions, &mut code);
+
+        for function in &functions {
+            lowering.declare_function(function)?;
+        }
+        for function in &functions {
+            lowering.define_function(function)?;
+        }
+        lowering.define_main(&code)?;
+
+        let clif = std::mem::take(&mut lowering.clif);
+        let object = lowering.module.finish().emit().map_err(codegen_error)?;
+        self.object = Some(object);
+        Ok(clif)
+    }
+
+    fn emit_object(&mut self, _ir: &str, obj_file: &Path) -> CompileResult<()> {
+        let object = self.object.take().ok_or_else(|| {
+            CompileError::Internal("cranelift backend: emit_object called before generate".to_string())
+        })?;
+        std::fs::write(obj_file, object)?;
+        info!("Object file generated: {}", obj_file.display());
+        Ok(())
+    }
+}
+
+fn codegen_error(e: impl std::fmt::Display) -> CompileError {
+    CompileError::IrGeneration(format!("cranelift: {}", e))
+}
+
+fn unsupported(what: impl std::fmt::Display) -> CompileError {
+    CompileError::Unsupported(format!("{} in the cranelift backend", what))
+}
+
+fn int_type(int_width: IntWidth) -> Type {
+    match int_width.bits() {
+        32 => types::I32,
+        _ => types::I64,
+    }
+}
+
+/// Separate function declarations from top-level code
+fn split_top_level<'a>(nodes: &'a [AstNode], functions: &mut Vec<&'a FunctionDecl>, code: &mut Vec<&'a Statement>) {
+    for node in nodes {
+        match node {
+            AstNode::Program(nodes) => split_top_level(nodes, functions, code),
+            AstNode::Namespace(ns) => split_top_level(&ns.statements, functions, code),
+            AstNode::Function(function) => functions.push(function),
+            AstNode::Statement(stmt) => code.push(stmt),
+            AstNode::Use(_) => {}
+            _ => warn!("Cranelift backend skips {:?}", node),
+        }
+    }
+}
+
+/// A user function known to the module
+struct FunctionEntry<'a> {
+    id: FuncId,
+    parameters: &'a [Parameter],
+}
+
+/// Module-wide lowering state
+struct ModuleLowering<'a> {
+    module: ObjectModule,
+    int: Type,
+    int_width: IntWidth,
+
+    /// User functions keyed by lowercased name
+    functions: HashMap<String, FunctionEntry<'a>>,
+
+    /// Textual IR of every defined function
+    clif: String,
+}
+
+impl<'a> ModuleLowering<'a> {
+    fn declare_function(&mut self, function: &'a FunctionDecl) -> CompileResult<()> {
+        let directives = CodegenDirectives::from_attributes(&function.attributes)?;
+        let linkage = if directives.export { Linkage::Export } else { Linkage::Hidden };
+
+        let mut signature = self.module.make_signature();
+        for parameter in &function.parameters {
+            if parameter.is_reference || parameter.is_variadic {
+                return Err(unsupported(format!("parameter ${} of {}()", parameter.name, function.name)));
+            }
+            signature.params.push(AbiParam::new(self.int));
+        }
+        signature.returns.push(AbiParam::new(self.int));
+
+        let id = self.module
+            .declare_function(&directives.symbol(&function.name), linkage, &signature)
+            .map_err(codegen_error)?;
+        self.functions.insert(
+            function.name.to_ascii_lowercase(),
+            FunctionEntry { id, parameters: &function.parameters },
+        );
+        Ok(())
+    }
+
+    fn define_function(&mut self, function: &FunctionDecl) -> CompileResult<()> {
+        let id = self.functions[&function.name.to_ascii_lowercase()].id;
+        let mut ctx = self.module.make_context();
+        ctx.func.signature = self.module.declarations().get_function_decl(id).signature.clone();
+
+        let mut builder_context = FunctionBuilderContext::new();
+        let builder = FunctionBuilder::new(&mut ctx.func, &mut builder_context);
+        let mut lowering = FunctionLowering::new(builder, self, false);
+
+        let entry = lowering.builder.create_block();
+        lowering.builder.append_block_params_for_function_params(entry);
+        lowering.builder.switch_to_block(entry);
+        for (i, parameter) in function.parameters.iter().enumerate() {
+            let value = lowering.builder.block_params(entry)[i];
+            let var = lowering.variable(&parameter.name);
+            lowering.builder.def_var(var, value);
+        }
+
+        lowering.statement(&function.body)?;
+        let zero = lowering.builder.ins().iconst(lowering.int, 0);
+        lowering.builder.ins().return_(&[zero]);
+        lowering.finish();
+
+        self.clif.push_str(&format!("; {}()\n{}\n", function.name, ctx.func.display()));
+        self.module.define_function(id, &mut ctx).map_err(codegen_error)
+    }
+
+    /// Define `main`, running the top-level code between runtime init and cleanup
+    fn define_main(&mut self, code: &[&Statement]) -> CompileResult<()> {
+        let mut signature = self.module.make_signature();
+        signature.params.push(AbiParam::new(types::I32));
+        signature.params.push(AbiParam::new(self.module.target_config().pointer_type()));
+        signature.returns.push(AbiParam::new(types::I32));
+        let id = self.module
+            .declare_function("main", Linkage::Export, &signature)
+            .map_err(codegen_error)?;
+
+        let mut ctx = self.module.make_context();
+        ctx.func.signature = signature;
+        let mut builder_context = FunctionBuilderContext::new();
+        let builder = FunctionBuilder::new(&mut ctx.func, &mut builder_context);
+        let mut lowering = FunctionLowering::new(builder, self, true);
+
+        let entry = lowering.builder.create_block();
+        lowering.builder.append_block_params_for_function_params(entry);
+        lowering.builder.switch_to_block(entry);
+        lowering.call_runtime("php_runtime_init", &[], &[])?;
+
+        for stmt in code {
+            lowering.statement(stmt)?;
+        }
+        lowering.exit_main()?;
+        lowering.finish();
+
+        self.clif.push_str(&format!("; main\n{}\n", ctx.func.display()));
+        self.module.define_function(id, &mut ctx).map_err(codegen_error)
+    }
+}
+
+/// Break and continue targets of an enclosing loop
+struct LoopTargets {
+    break_block: Block,
+    continue_block: Block,
+}
+
+/// Lowering state for one function body
+struct FunctionLowering<'m, 'f, 'a> {
+    builder: FunctionBuilder<'f>,
+    module: &'m mut ModuleLowering<'a>,
+    int: Type,
+    is_main: bool,
+    variables: HashMap<String, Variable>,
+    loops: Vec<LoopTargets>,
+}
+
+impl<'m, 'f, 'a> FunctionLowering<'m, 'f, 'a> {
+    fn new(builder: FunctionBuilder<'f>, module: &'m mut ModuleLowering<'a>, is_main: bool) -> Self {
+        let int = module.int;
+        Self { builder, module, int, is_main, variables: HashMap::new(), loops: Vec::new() }
+    }
+
+    fn finish(mut self) {
+        self.builder.seal_all_blocks();
+        self.builder.finalize();
+    }
+
+    /// Variable for a PHP local, declared on first use
+    ///
+    /// Reading a variable before assigning it yields 0, matching `null`.
+    fn variable(&mut self, name: &str) -> Variable {
+        let next = Variable::from_u32(self.variables.len() as u32);
+        let int = self.int;
+        let builder = &mut self.builder;
+        *self.variables.entry(name.to_string()).or_insert_with(|| {
+            builder.declare_var(next, int);
+            next
+        })
+    }
+
+    /// Continue in a fresh block after a terminator
+    fn start_unreachable_block(&mut self) {
+        let block = self.builder.create_block();
+        self.builder.switch_to_block(block);
+    }
+
+    fn exit_main(&mut self) -> CompileResult<()> {
+        self.call_runtime("php_runtime_cleanup", &[], &[])?;
+        let status = self.builder.ins().iconst(types::I32, 0);
+        self.builder.ins().return_(&[status]);
+        Ok(())
+    }
+
+    /// Call a runtime function returning a C `int` status, ignoring the result
+    fn call_runtime(&mut self, name: &str, params: &[Type], args: &[Value]) -> CompileResult<()> {
+        let mut signature = self.module.module.make_signature();
+        signature.params.extend(params.iter().map(|t| AbiParam::new(*t)));
+        signature.returns.push(AbiParam::new(types::I32));
+
+        let id = self.module.module
+            .declare_function(name, Linkage::Import, &signature)
+            .map_err(codegen_error)?;
+        let func_ref = self.module.module.declare_func_in_func(id, self.builder.func);
+        self.builder.ins().call(func_ref, args);
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> CompileResult<()> {
+        match stmt {
+            Statement::Expression(expr) => {
+                self.expression(expr)?;
+            }
+            Statement::Block(stmts) => {
+                for stmt in stmts {
+                    self.statement(stmt)?;
+                }
+            }
+            Statement::If { condition, then_branch, else_branch } => {
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                let merge = self.builder.create_block();
+
+                let condition = self.expression(condition)?;
+                self.builder.ins().brif(condition, then_block, &[], else_block, &[]);
+
+                self.builder.switch_to_block(then_block);
+                self.statement(then_branch)?;
+                self.builder.ins().jump(merge, &[]);
+
+                self.builder.switch_to_block(else_block);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch)?;
+                }
+                self.builder.ins().jump(merge, &[]);
+                self.builder.switch_to_block(merge);
+            }
+            Statement::While { condition, body } => {
+                self.lower_loop(&[], Some(condition), &[], body)?;
+            }
+            Statement::For { init, condition, update, body } => {
+                for expr in init {
+                    self.expression(expr)?;
+                }
+                // All conditions are evaluated; the last one decides
+                let (last, rest) = match condition.split_last() {
+                    Some((last, rest)) => (Some(last), rest),
+                    None => (None, &[][..]),
+                };
+                self.lower_loop(rest, last, update, body)?;
+            }
+            Statement::Break(levels) | Statement::Continue(levels) => {
+                let depth = match levels.as_deref() {
+                    None => 1,
+                    Some(Expression::Literal(Literal::Int(n))) if *n >= 1 => *n as usize,
+                    Some(_) => return Err(unsupported("non-constant break/continue level")),
+                };
+                let targets = self.loops.len().checked_sub(depth)
+                    .map(|i| &self.loops[i])
+                    .ok_or_else(|| CompileError::Type {
+                        message: format!("Cannot 'break' {} levels", depth),
+                        location: None,
+                    })?;
+                let target = match stmt {
+                    Statement::Break(_) => targets.break_block,
+                    _ => targets.continue_block,
+                };
+                self.builder.ins().jump(target, &[]);
+                self.start_unreachable_block();
+            }
+            Statement::Return(expr) => {
+                let value = match expr {
+                    Some(expr) => self.expression(expr)?,
+                    None => self.builder.ins().iconst(self.int, 0),
+                };
+                if self.is_main {
+                    self.exit_main()?;
+                } else {
+                    self.builder.ins().return_(&[value]);
+                }
+                self.start_unreachable_block();
+            }
+            Statement::Echo(exprs) => {
+                for expr in exprs {
+                    self.echo(expr)?;
+                }
+            }
+            Statement::Print(expr) => self.echo(expr)?,
+            other => return Err(unsupported(format!("statement {:?}", other))),
+        }
+        Ok(())
+    }
+
+    /// Lower a pre-tested loop; `continue` jumps to the update expressions
+    fn lower_loop(
+        &mut self,
+        pre_conditions: &[Expression],
+        condition: Option<&Expression>,
+        update: &[Expression],
+        body: &Statement,
+    ) -> CompileResult<()> {
+        let header = self.builder.create_block();
+        let body_block = self.builder.create_block();
+        let update_block = self.builder.create_block();
+        let exit = self.builder.create_block();
+
+        self.builder.ins().jump(header, &[]);
+        self.builder.switch_to_block(header);
+        for expr in pre_conditions {
+            self.expression(expr)?;
+        }
+        match condition {
+            Some(condition) => {
+                let condition = self.expression(condition)?;
+                self.builder.ins().brif(condition, body_block, &[], exit, &[]);
+            }
+            None => {
+                self.builder.ins().jump(body_block, &[]);
+            }
+        }
+
+        self.builder.switch_to_block(body_block);
+        self.loops.push(LoopTargets { break_block: exit, continue_block: update_block });
+        self.statement(body)?;
+        self.loops.pop();
+        self.builder.ins().jump(update_block, &[]);
+
+        self.builder.switch_to_block(update_block);
+        for expr in update {
+            self.expression(expr)?;
+        }
+        self.builder.ins().jump(header, &[]);
+        self.builder.switch_to_block(exit);
+        Ok(())
+    }
+
+    fn echo(&mut self, expr: &Expression) -> CompileResult<()> {
+        match expr {
+            Expression::Literal(Literal::String(s)) => {
+                let pointer = self.module.module.target_config().pointer_type();
+                let data = self.module.module.declare_anonymous_data(false, false).map_err(codegen_error)?;
+                let mut description = DataDescription::new();
+                let mut bytes = s.as_bytes().to_vec();
+                bytes.push(0);
+                description.define(bytes.into_boxed_slice());
+                self.module.module.define_data(data, &description).map_err(codegen_error)?;
+
+                let global = self.module.module.declare_data_in_func(data, self.builder.func);
+                let address = self.builder.ins().global_value(pointer, global);
+                self.call_runtime("php_print_string", &[pointer], &[address])?;
+            }
+            expr => {
+                let value = self.expression(expr)?;
+                let value = if self.int == types::I64 { value } else { self.builder.ins().sextend(types::I64, value) };
+                self.call_runtime("php_print_int", &[types::I64], &[value])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: &Expression) -> CompileResult<Value> {
+        let int = self.int;
+        match expr {
+            Expression::Literal(Literal::Int(n)) if self.module.int_width.fits(*n) => {
+                Ok(self.builder.ins().iconst(int, *n))
+            }
+            Expression::Literal(Literal::Bool(b)) => Ok(self.builder.ins().iconst(int, *b as i64)),
+            Expression::Literal(Literal::Null) => Ok(self.builder.ins().iconst(int, 0)),
+            Expression::Constant(name) => {
+                let value = match name.to_ascii_lowercase().as_str() {
+                    "true" => 1,
+                    "false" | "null" => 0,
+                    _ => self.module.int_width.constant(name)
+                        .ok_or_else(|| unsupported(format!("constant {}", name)))?,
+                };
+                Ok(self.builder.ins().iconst(int, value))
+            }
+            Expression::Variable(name) => {
+                let var = self.variable(name);
+                Ok(self.builder.use_var(var))
+            }
+            Expression::Assignment { target, op, value } => {
+                let Expression::Variable(name) = target.as_ref() else {
+                    return Err(unsupported("assignment to a non-variable"));
+                };
+                let value = match op {
+                    AssignmentOperator::Assign => self.expression(value)?,
+                    op => {
+                        let op = compound_operator(op).ok_or_else(|| unsupported(format!("operator {:?}", op)))?;
+                        self.binary(target, &op, value)?
+                    }
+                };
+                let var = self.variable(name);
+                self.builder.def_var(var, value);
+                Ok(value)
+            }
+            Expression::BinaryOp { left, op, right } => self.binary(left, op, right),
+            Expression::UnaryOp { op, expr } => self.unary(op, expr),
+            Expression::Ternary { condition, true_expr, false_expr } => {
+                let condition = self.expression(condition)?;
+                self.select(condition, |this| this.expression(true_expr), |this| this.expression(false_expr))
+            }
+            Expression::FunctionCall { name, arguments } => self.call(name, arguments),
+            other => Err(unsupported(format!("expression {:?}", other))),
+        }
+    }
+
+    fn binary(&mut self, left: &Expression, op: &BinaryOperator, right: &Expression) -> CompileResult<Value> {
+        let int = self.int;
+        match op {
+            BinaryOperator::And => {
+                let left = self.expression(left)?;
+                return self.select(left, |this| this.truth(right), |this| Ok(this.builder.ins().iconst(int, 0)));
+            }
+            BinaryOperator::Or => {
+                let left = self.expression(left)?;
+                return self.select(left, |this| Ok(this.builder.ins().iconst(int, 1)), |this| this.truth(right));
+            }
+            _ => {}
+        }
+
+        let a = self.expression(left)?;
+        let b = self.expression(right)?;
+        let ins = self.builder.ins();
+        let value = match op {
+            BinaryOperator::Add => ins.iadd(a, b),
+            BinaryOperator::Sub => ins.isub(a, b),
+            BinaryOperator::Mul => ins.imul(a, b),
+            BinaryOperator::Div => ins.sdiv(a, b),
+            BinaryOperator::Mod => ins.srem(a, b),
+            BinaryOperator::BitwiseAnd => ins.band(a, b),
+            BinaryOperator::BitwiseOr => ins.bor(a, b),
+            BinaryOperator::BitwiseXor => ins.bxor(a, b),
+            BinaryOperator::ShiftLeft => ins.ishl(a, b),
+            BinaryOperator::ShiftRight => ins.sshr(a, b),
+            BinaryOperator::Spaceship => {
+                let greater = self.compare(IntCC::SignedGreaterThan, a, b);
+                let less = self.compare(IntCC::SignedLessThan, a, b);
+                self.builder.ins().isub(greater, less)
+            }
+            BinaryOperator::Xor => {
+                let a = self.compare_zero(IntCC::NotEqual, a);
+                let b = self.compare_zero(IntCC::NotEqual, b);
+                self.builder.ins().bxor(a, b)
+            }
+            op => match comparison(op) {
+                Some(cc) => self.compare(cc, a, b),
+                None => return Err(unsupported(format!("operator {}", op))),
+            },
+        };
+        Ok(value)
+    }
+
+    fn unary(&mut self, op: &UnaryOperator, expr: &Expression) -> CompileResult<Value> {
+        if let UnaryOperator::PreInc | UnaryOperator::PreDec | UnaryOperator::PostInc | UnaryOperator::PostDec = op {
+            let Expression::Variable(name) = expr else {
+                return Err(unsupported("increment of a non-variable"));
+            };
+            let var = self.variable(name);
+            let old = self.builder.use_var(var);
+            let delta = if matches!(op, UnaryOperator::PreInc | UnaryOperator::PostInc) { 1 } else { -1 };
+            let new = self.builder.ins().iadd_imm(old, delta);
+            self.builder.def_var(var, new);
+            return Ok(if matches!(op, UnaryOperator::PreInc | UnaryOperator::PreDec) { new } else { old });
+        }
+
+        let value = self.expression(expr)?;
+        Ok(match op {
+            UnaryOperator::Plus | UnaryOperator::ErrorSuppress => value,
+            UnaryOperator::Minus => self.builder.ins().ineg(value),
+            UnaryOperator::BitwiseNot => self.builder.ins().bnot(value),
+            _ => self.compare_zero(IntCC::Equal, value),
+        })
+    }
+
+    fn call(&mut self, name: &Expression, arguments: &[Expression]) -> CompileResult<Value> {
+        let Expression::Constant(name) = name else {
+            return Err(unsupported("dynamic function call"));
+        };
+        let key = name.trim_start_matches('\\').to_ascii_lowercase();
+        let Some(entry) = self.module.functions.get(&key) else {
+            return Err(unsupported(format!("call to undefined or builtin function {}()", name)));
+        };
+        let (id, parameters) = (entry.id, entry.parameters);
+
+        let mut args = Vec::with_capacity(parameters.len());
+        for (i, argument) in arguments.iter().enumerate() {
+            let value = self.expression(argument)?;
+            // Extra arguments are evaluated for their side effects only
+            if i < parameters.len() {
+                args.push(value);
+            }
+        }
+        for parameter in &parameters[args.len()..] {
+            let default = parameter.default_value.as_ref().ok_or_else(|| CompileError::Type {
+                message: format!("Too few arguments to function {}()", name),
+                location: None,
+            })?;
+            args.push(self.expression(default)?);
+        }
+
+        let func_ref = self.module.module.declare_func_in_func(id, self.builder.func);
+        let call = self.builder.ins().call(func_ref, &args);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// `condition ? then() : otherwise()`, evaluating only the taken branch
+    fn select(
+        &mut self,
+        condition: Value,
+        then: impl FnOnce(&mut Self) -> CompileResult<Value>,
+        otherwise: impl FnOnce(&mut Self) -> CompileResult<Value>,
+    ) -> CompileResult<Value> {
+        let then_block = self.builder.create_block();
+        let else_block = self.builder.create_block();
+        let merge = self.builder.create_block();
+        let result = self.builder.append_block_param(merge, self.int);
+
+        self.builder.ins().brif(condition, then_block, &[], else_block, &[]);
+
+        self.builder.switch_to_block(then_block);
+        let value = then(self)?;
+        self.builder.ins().jump(merge, &[value]);
+
+        self.builder.switch_to_block(else_block);
+        let value = otherwise(self)?;
+        self.builder.ins().jump(merge, &[value]);
+
+        self.builder.switch_to_block(merge);
+        Ok(result)
+    }
+
+    /// Boolean value (0 or 1) of an expression
+    fn truth(&mut self, expr: &Expression) -> CompileResult<Value> {
+        let value = self.expression(expr)?;
+        Ok(self.compare_zero(IntCC::NotEqual, value))
+    }
+
+    fn compare(&mut self, cc: IntCC, a: Value, b: Value) -> Value {
+        let flag = self.builder.ins().icmp(cc, a, b);
+        self.builder.ins().uextend(self.int, flag)
+    }
+
+    fn compare_zero(&mut self, cc: IntCC, value: Value) -> Value {
+        let flag = self.builder.ins().icmp_imm(cc, value, 0);
+        self.builder.ins().uextend(self.int, flag)
+    }
+}
+
+fn comparison(op: &BinaryOperator) -> Option<IntCC> {
+    Some(match op {
+        BinaryOperator::Equal | BinaryOperator::Identical => IntCC::Equal,
+        BinaryOperator::NotEqual | BinaryOperator::NotIdentical => IntCC::NotEqual,
+        BinaryOperator::Less => IntCC::SignedLessThan,
+        BinaryOperator::LessEqual => IntCC::SignedLessThanOrEqual,
+        BinaryOperator::Greater => IntCC::SignedGreaterThan,
+        BinaryOperator::GreaterEqual => IntCC::SignedGreaterThanOrEqual,
+        _ => return None,
+    })
+}
+
+/// Binary operator applied by a compound assignment
+fn compound_operator(op: &AssignmentOperator) -> Option<BinaryOperator> {
+    Some(match op {
+        AssignmentOperator::AddAssign => BinaryOperator::Add,
+        AssignmentOperator::SubAssign => BinaryOperator::Sub,
+        AssignmentOperator::MulAssign => BinaryOperator::Mul,
+        AssignmentOperator::DivAssign => BinaryOperator::Div,
+        AssignmentOperator::ModAssign => BinaryOperator::Mod,
+        AssignmentOperator::BitwiseAndAssign => BinaryOperator::BitwiseAnd,
+        AssignmentOperator::BitwiseOrAssign => BinaryOperator::BitwiseOr,
+        AssignmentOperator::BitwiseXorAssign => BinaryOperator::BitwiseXor,
+        AssignmentOperator::ShiftLeftAssign => BinaryOperator::ShiftLeft,
+        AssignmentOperator::ShiftRightAssign => BinaryOperator::ShiftRight,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Visibility;
+
+    fn var(name: &str) -> Box<Expression> {
+        Box::new(Expression::Variable(name.to_string()))
+    }
+
+    fn int(n: i64) -> Box<Expression> {
+        Box::new(Expression::Literal(Literal::Int(n)))
+    }
+
+    /// `function fact($n) { $r = 1; while ($n > 1) { $r *= $n; $n--; } return $r; }`
+    fn factorial() -> AstNode {
+        AstNode::Function(FunctionDecl {
+            name: "fact".to_string(),
+            parameters: vec![Parameter {
+                name: "n".to_string(),
+                typ: None,
+                default_value: None,
+                is_reference: false,
+                is_variadic: false,
+            }],
+            return_type: None,
+            body: Box::new(Statement::Block(vec![
+                Statement::Expression(Box::new(Expression::Assignment {
+                    target: var("r"),
+                    op: AssignmentOperator::Assign,
+                    value: int(1),
+                })),
+                Statement::While {
+                    condition: Box::new(Expression::BinaryOp { left: var("n"), op: BinaryOperator::Greater, right: int(1) }),
+                    body: Box::new(Statement::Block(vec![
+                        Statement::Expression(Box::new(Expression::Assignment {
+                            target: var("r"),
+                            op: AssignmentOperator::MulAssign,
+                            value: var("n"),
+                        })),
+                        Statement::Expression(Box::new(Expression::UnaryOp { op: UnaryOperator::PostDec, expr: var("n") })),
+                    ])),
+                },
+                Statement::Return(Some(var("r"))),
+            ])),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        })
+    }
+
+    #[test]
+    fn test_generate_object() {
+        let ast = vec![
+            factorial(),
+            AstNode::Statement(Box::new(Statement::Echo(vec![
+                Expression::FunctionCall {
+                    name: Box::new(Expression::Constant("fact".to_string())),
+                    arguments: vec![Expression::Literal(Literal::Int(5))],
+                },
+                Expression::Literal(Literal::String("\n".to_string())),
+            ]))),
+        ];
+
+        let mut backend = CraneliftBackend::new("O2");
+        let clif = backend.generate(&ast).unwrap();
+        assert!(clif.contains("; fact()"));
+        assert!(clif.contains("imul"));
+        assert!(clif.contains("; main"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let obj_file = dir.path().join("out.o");
+        backend.emit_object(&clif, &obj_file).unwrap();
+        assert!(std::fs::metadata(&obj_file).unwrap().len() > 0);
+        assert!(backend.emit_object(&clif, &obj_file).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_is_reported() {
+        let ast = vec![AstNode::Statement(Box::new(Statement::Echo(vec![Expression::Literal(Literal::Float(1.5))])))];
+        let err = CraneliftBackend::new("O0").generate(&ast).unwrap_err();
+        assert!(matches!(err, CompileError::Unsupported(_)), "{}", err);
+    }
+}
@@ -0,0 +1,620 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Whole-program dead code elimination.
+//!
+//! Top-level code and the declarations marked `#[Export]` or `#[NoMangle]`,
+//! which code outside the program may call, are the entry points. A
+//! declaration is kept when an entry point reaches it through the names
+//! the code mentions: calls, `new`, static access, type declarations,
+//! `extends`, `implements`, trait `use`, attributes, and string literals,
+//! which may name a callable or a class. Classes are kept whole, with every
+//! method. Unreached top-level functions, classes, interfaces, traits and
+//! enums are dropped before IR generation, and with them the string
+//! constants only their code interned.
+//!
+//! Names are compared by their last segment, case-insensitively, so a
+//! reference to `A\helper` keeps every function named `helper`. A name
+//! computed at runtime cannot be followed: a call through anything but a
+//! name, a string or a closure, or a non-literal callback passed to a
+//! function such as `call_user_func` or `usort`, keeps every function;
+//! `new`, a static call or a class constant on a computed class, a
+//! non-literal `class_exists` and the like, and `unserialize` keep every
+//! class.
+
+use std::collections::{HashMap, HashSet};
+use crate::ast::visit::{walk_expression, walk_function, walk_node, walk_statement};
+use crate::ast::{ArrayElement, AstNode, Attribute, Expression, FunctionDecl, Literal, Statement, VisitorMut};
+use crate::directives::CodegenDirectives;
+use crate::types::{LiteralType, Type};
+
+/// Functions taking a callable, with the position of that argument
+const CALLBACK_ARGUMENTS: &[(&str, usize)] = &[
+    ("array_filter", 1),
+    ("array_map", 0),
+    ("array_reduce", 1),
+    ("array_walk", 1),
+    ("array_walk_recursive", 1),
+    ("call_user_func", 0),
+    ("call_user_func_array", 0),
+    ("forward_static_call", 0),
+    ("forward_static_call_array", 0),
+    ("function_exists", 0),
+    ("is_callable", 0),
+    ("iterator_apply", 1),
+    ("ob_start", 0),
+    ("preg_replace_callback", 1),
+    ("register_shutdown_function", 0),
+    ("register_tick_function", 0),
+    ("reflectionfunction", 0),
+    ("set_error_handler", 0),
+    ("set_exception_handler", 0),
+    ("spl_autoload_register", 0),
+    ("uasort", 1),
+    ("uksort", 1),
+    ("usort", 1),
+];
+
+/// Functions and classes looking up a class by name, with the position of the name
+const CLASS_NAME_ARGUMENTS: &[(&str, usize)] = &[
+    ("class_exists", 0),
+    ("enum_exists", 0),
+    ("get_class_methods", 0),
+    ("get_class_vars", 0),
+    ("interface_exists", 0),
+    ("reflectionclass", 0),
+    ("reflectionenum", 0),
+    ("reflectionmethod", 0),
+    ("trait_exists", 0),
+];
+
+/// Declarations removed by [`eliminate_dead_code`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Removed {
+    pub functions: usize,
+    /// Classes, interfaces, traits and enums
+    pub classes: usize,
+}
+
+/// Drop the top-level declarations no entry point of the programs reaches
+///
+/// The programs are linked together, so each may call into the others.
+pub fn eliminate_dead_code(programs: &mut [Vec<AstNode>]) -> Removed {
+    let mut graph = Graph::default();
+    for program in programs.iter_mut() {
+        graph.collect(program);
+    }
+    let reachable = graph.reachable();
+
+    let mut removed = Removed::default();
+    let mut index = 0;
+    for program in programs.iter_mut() {
+        prune(program, &reachable, &mut index, &mut removed);
+    }
+    removed
+}
+
+/// Functions and classes a program declares at the top level, and every
+/// name it references, compared as this module compares them
+pub(crate) fn declared_and_referenced(program: &mut [AstNode]) -> (HashSet<String>, HashSet<String>) {
+    let mut graph = Graph::default();
+    graph.collect(program);
+    let declared = graph.declarations.iter().map(|declaration| declaration.key.clone()).collect();
+    let mut referenced = graph.roots.names;
+    for declaration in graph.declarations {
+        referenced.extend(declaration.references.names);
+    }
+    (declared, referenced)
+}
+
+/// Whether a declaration is a function or a class-like type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Function,
+    Class,
+}
+
+/// Name and kind of a top-level declaration
+fn declaration(node: &AstNode) -> Option<(Kind, &str)> {
+    match node {
+        AstNode::Function(decl) => Some((Kind::Function, &decl.name)),
+        AstNode::Class(class) => Some((Kind::Class, &class.name)),
+        AstNode::Interface(interface) => Some((Kind::Class, &interface.name)),
+        AstNode::Trait(t) => Some((Kind::Class, &t.name)),
+        AstNode::Enum(e) => Some((Kind::Class, &e.name)),
+        _ => None,
+    }
+}
+
+/// Whether code outside the program may call a declaration
+fn is_entry_point(node: &AstNode) -> bool {
+    let exported = |attributes: &[Attribute]| {
+        // Invalid directives are reported by code generation; keep the declaration for it
+        CodegenDirectives::from_attributes(attributes).map_or(true, |d| d.export || d.no_mangle)
+    };
+    let any_method = |methods: &[FunctionDecl]| methods.iter().any(|method| exported(&method.attributes));
+    match node {
+        AstNode::Function(decl) => exported(&decl.attributes),
+        AstNode::Class(class) => exported(&class.attributes) || any_method(&class.methods),
+        AstNode::Trait(t) => any_method(&t.methods),
+        AstNode::Enum(e) => any_method(&e.methods),
+        _ => false,
+    }
+}
+
+/// Key names are compared by: the last segment, lowercase
+fn key(name: &str) -> String {
+    name.rsplit('\\').next().unwrap_or(name).to_ascii_lowercase()
+}
+
+/// Top-level declarations and the names each one references
+#[derive(Default)]
+struct Graph {
+    declarations: Vec<Declaration>,
+    /// References of the top-level code
+    roots: References,
+}
+
+struct Declaration {
+    kind: Kind,
+    key: String,
+    entry_point: bool,
+    references: References,
+}
+
+impl Graph {
+    fn collect(&mut self, nodes: &mut [AstNode]) {
+        for node in nodes {
+            match node {
+                AstNode::Program(nodes) => self.collect(nodes),
+                AstNode::Namespace(namespace) => self.collect(&mut namespace.statements),
+                node => match declaration(node).map(|(kind, name)| (kind, key(name))) {
+                    Some((kind, key)) => {
+                        let entry_point = is_entry_point(node);
+                        let mut references = References::default();
+                        references.visit_node(node);
+                        self.declarations.push(Declaration { kind, key, entry_point, references });
+                    }
+                    None => self.roots.visit_node(node),
+                },
+            }
+        }
+    }
+
+    /// Which declarations, in collection order, the entry points reach
+    fn reachable(&self) -> Vec<bool> {
+        let mut by_key: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (index, declaration) in self.declarations.iter().enumerate() {
+            by_key.entry(declaration.key.as_str()).or_default().push(index);
+        }
+        let mut walk = Walk {
+            graph: self,
+            by_key,
+            reached: vec![false; self.declarations.len()],
+            queue: Vec::new(),
+            all_functions: false,
+            all_classes: false,
+        };
+
+        walk.follow(&self.roots);
+        for (index, declaration) in self.declarations.iter().enumerate() {
+            if declaration.entry_point {
+                walk.reach(index);
+            }
+        }
+        while let Some(index) = walk.queue.pop() {
+            walk.follow(&self.declarations[index].references);
+        }
+        walk.reached
+    }
+}
+
+/// Worklist of [`Graph::reachable`]
+struct Walk<'g> {
+    graph: &'g Graph,
+    by_key: HashMap<&'g str, Vec<usize>>,
+    reached: Vec<bool>,
+    queue: Vec<usize>,
+    all_functions: bool,
+    all_classes: bool,
+}
+
+impl Walk<'_> {
+    fn reach(&mut self, index: usize) {
+        if !self.reached[index] {
+            self.reached[index] = true;
+            self.queue.push(index);
+        }
+    }
+
+    fn reach_kind(&mut self, kind: Kind) {
+        for index in 0..self.graph.declarations.len() {
+            if self.graph.declarations[index].kind == kind {
+                self.reach(index);
+            }
+        }
+    }
+
+    fn follow(&mut self, references: &References) {
+        for name in &references.names {
+            for index in self.by_key.get(name.as_str()).cloned().unwrap_or_default() {
+                self.reach(index);
+            }
+        }
+        if references.dynamic_functions && !self.all_functions {
+            self.all_functions = true;
+            self.reach_kind(Kind::Function);
+        }
+        if references.dynamic_classes && !self.all_classes {
+            self.all_classes = true;
+            self.reach_kind(Kind::Class);
+        }
+    }
+}
+
+/// Names a piece of code references
+#[derive(Default)]
+struct References {
+    /// Keys of the referenced names
+    names: HashSet<String>,
+    /// Calls a function named at runtime
+    dynamic_functions: bool,
+    /// Uses a class named at runtime
+    dynamic_classes: bool,
+}
+
+impl References {
+    fn add(&mut self, name: &str) {
+        self.names.insert(key(name));
+    }
+
+    /// A string may name a function, a class or a static method (`A::f`)
+    fn add_string(&mut self, s: &str) {
+        if let Some(name) = s.split("::").next() {
+            self.add(name);
+        }
+    }
+
+    fn add_type(&mut self, typ: &Type) {
+        match typ {
+            Type::Object(name) => self.add(name),
+            Type::Generic(name, arguments) => {
+                self.add(name);
+                arguments.iter().for_each(|argument| self.add_type(argument));
+            }
+            Type::Array(element) | Type::AssociativeArray(element) => self.add_type(element),
+            Type::Union(types) | Type::Intersection(types) => types.iter().for_each(|t| self.add_type(t)),
+            Type::Function(parameters, result) => {
+                parameters.iter().for_each(|t| self.add_type(t));
+                self.add_type(result);
+            }
+            Type::Callable(signature) => {
+                signature.parameters.iter().for_each(|parameter| self.add_type(&parameter.typ));
+                self.add_type(&signature.return_type);
+                if let Some(target) = &signature.target {
+                    self.add_string(target);
+                }
+            }
+            Type::Literal(LiteralType::EnumCase(name, _)) => self.add(name),
+            _ => {}
+        }
+    }
+
+    fn add_attributes(&mut self, attributes: &[Attribute]) {
+        attributes.iter().for_each(|attribute| self.add(&attribute.name));
+    }
+
+    /// Names in the header of a declaration, which the AST walk does not visit
+    fn add_declaration(&mut self, node: &AstNode) {
+        match node {
+            AstNode::Class(class) => {
+                self.add_attributes(&class.attributes);
+                class.extends.iter().chain(&class.implements).for_each(|name| self.add(name));
+                for trait_use in &class.traits {
+                    trait_use.traits.iter().for_each(|name| self.add(name));
+                }
+                for property in &class.properties {
+                    if let Some(typ) = &property.typ {
+                        self.add_type(typ);
+                    }
+                }
+            }
+            AstNode::Interface(interface) => interface.extends.iter().for_each(|name| self.add(name)),
+            AstNode::Trait(t) => {
+                for property in &t.properties {
+                    if let Some(typ) = &property.typ {
+                        self.add_type(typ);
+                    }
+                }
+            }
+            AstNode::Use(decl) => decl.uses.iter().for_each(|clause| self.add(&clause.name)),
+            AstNode::Attribute(attribute) => self.add(&attribute.name),
+            _ => {}
+        }
+    }
+
+    /// Note a call to `function` that may name a function or class at runtime
+    fn check_arguments(&mut self, function: &str, arguments: &[Expression]) {
+        let function = key(function);
+        if function == "unserialize" {
+            self.dynamic_classes = true;
+        }
+        let argument = |table: &[(&str, usize)]| {
+            table.iter().find(|(name, _)| *name == function).and_then(|(_, position)| arguments.get(*position))
+        };
+        if argument(CALLBACK_ARGUMENTS).is_some_and(|callback| !is_static_callable(callback)) {
+            self.dynamic_functions = true;
+        }
+        if argument(CLASS_NAME_ARGUMENTS).is_some_and(|name| !is_static_name(name)) {
+            self.dynamic_classes = true;
+        }
+    }
+}
+
+/// Whether a class expression names its class in the source
+fn is_static_name(expr: &Expression) -> bool {
+    match expr {
+        Expression::Constant(_) | Expression::Literal(Literal::String(_)) => true,
+        Expression::ClassConstant { class, name } => name.eq_ignore_ascii_case("class") && is_static_name(class),
+        _ => false,
+    }
+}
+
+/// Whether a callable names its function in the source: a string, a
+/// closure, or `[$object or class, 'method']`
+fn is_static_callable(expr: &Expression) -> bool {
+    match expr {
+        Expression::Literal(Literal::String(_) | Literal::Null) | Expression::Closure(_) => true,
+        Expression::Literal(Literal::Array(elements)) | Expression::Array { elements } => match elements.as_slice() {
+            [_, ArrayElement { value: method, .. }] => matches!(method, Expression::Literal(Literal::String(_))),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+impl VisitorMut for References {
+    fn visit_node(&mut self, node: &mut AstNode) {
+        self.add_declaration(node);
+        walk_node(self, node);
+    }
+
+    fn visit_function(&mut self, function: &mut FunctionDecl) {
+        self.add_attributes(&function.attributes);
+        for parameter in &function.parameters {
+            if let Some(typ) = &parameter.typ {
+                self.add_type(typ);
+            }
+        }
+        if let Some(typ) = &function.return_type {
+            self.add_type(typ);
+        }
+        walk_function(self, function);
+    }
+
+    fn visit_statement(&mut self, stmt: &mut Statement) {
+        if let Statement::Try { catch_blocks, .. } = stmt {
+            for catch in catch_blocks.iter() {
+                catch.types.iter().for_each(|typ| self.add_type(typ));
+            }
+        }
+        walk_statement(self, stmt);
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression) {
+        match expr {
+            Expression::Constant(name) => self.add(name),
+            Expression::Literal(Literal::String(s)) => self.add_string(s),
+            Expression::FunctionCall { name, arguments } => match name.as_ref() {
+                Expression::Constant(function) => self.check_arguments(function, arguments),
+                Expression::Literal(Literal::String(_)) | Expression::Closure(_) => {}
+                _ => self.dynamic_functions = true,
+            },
+            Expression::New { class, arguments } => match class.as_ref() {
+                Expression::Constant(name) => self.check_arguments(name, arguments),
+                // Anonymous classes and `new static` are declared in the source
+                Expression::Closure(_) => {}
+                _ => self.dynamic_classes = true,
+            },
+            Expression::StaticCall { class, .. } | Expression::StaticPropertyAccess { class, .. } => {
+                if !is_static_name(class) {
+                    self.dynamic_classes = true;
+                }
+            }
+            Expression::ClassConstant { class, name } => {
+                if !is_static_name(class) && !name.eq_ignore_ascii_case("class") {
+                    self.dynamic_classes = true;
+                }
+            }
+            Expression::Cast { target_type, .. } => self.add_type(target_type),
+            Expression::Closure(closure) => {
+                for parameter in &closure.parameters {
+                    if let Some(typ) = &parameter.typ {
+                        self.add_type(typ);
+                    }
+                }
+                if let Some(typ) = &closure.return_type {
+                    self.add_type(typ);
+                }
+            }
+            _ => {}
+        }
+        walk_expression(self, expr);
+    }
+}
+
+/// Remove the unreached declarations, counted in collection order
+fn prune(nodes: &mut Vec<AstNode>, reachable: &[bool], index: &mut usize, removed: &mut Removed) {
+    nodes.retain_mut(|node| match node {
+        AstNode::Program(nodes) => {
+            prune(nodes, reachable, index, removed);
+            true
+        }
+        AstNode::Namespace(namespace) => {
+            prune(&mut namespace.statements, reachable, index, removed);
+            true
+        }
+        node => match declaration(node) {
+            Some((kind, _)) => {
+                let keep = reachable[*index];
+                *index += 1;
+                if !keep {
+                    match kind {
+                        Kind::Function => removed.functions += 1,
+                        Kind::Class => removed.classes += 1,
+                    }
+                }
+                keep
+            }
+            None => true,
+        },
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{ClassDecl, InterfaceDecl, NamespaceDecl, Parameter, Visibility};
+
+    fn string(s: &str) -> Expression {
+        Expression::Literal(Literal::String(s.to_string()))
+    }
+
+    fn call(name: Expression, arguments: Vec<Expression>) -> Statement {
+        Statement::Expression(Box::new(Expression::FunctionCall { name: Box::new(name), arguments }))
+    }
+
+    fn constant(name: &str) -> Expression {
+        Expression::Constant(name.to_string())
+    }
+
+    fn function(name: &str, body: Vec<Statement>) -> AstNode {
+        AstNode::Function(FunctionDecl {
+            name: name.to_string(),
+            parameters: Vec::new(),
+            return_type: None,
+            body: Box::new(Statement::Block(body)),
+            attributes: Vec::new(),
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        })
+    }
+
+    fn class(name: &str, extends: Option<&str>, implements: &[&str], methods: Vec<FunctionDecl>) -> AstNode {
+        AstNode::Class(ClassDecl {
+            name: name.to_string(),
+            extends: extends.map(str::to_string),
+            implements: implements.iter().map(|name| name.to_string()).collect(),
+            traits: Vec::new(),
+            properties: Vec::new(),
+            methods,
+            constants: Vec::new(),
+            attributes: Vec::new(),
+            is_abstract: false,
+            is_final: false,
+            is_trait: false,
+            is_interface: false,
+            is_enum: false,
+        })
+    }
+
+    fn method(name: &str, body: Vec<Statement>) -> FunctionDecl {
+        match function(name, body) {
+            AstNode::Function(decl) => decl,
+            _ => unreachable!(),
+        }
+    }
+
+    fn names(program: &[AstNode]) -> Vec<String> {
+        program.iter().flat_map(|node| match node {
+            AstNode::Program(nodes) => names(nodes),
+            AstNode::Namespace(namespace) => names(&namespace.statements),
+            node => declaration(node).map(|(_, name)| name.to_string()).into_iter().collect(),
+        }).collect()
+    }
+
+    #[test]
+    fn test_unreached_declarations_removed() {
+        let shape = AstNode::Interface(InterfaceDecl {
+            name: "Shape".to_string(),
+            extends: Vec::new(),
+            constants: Vec::new(),
+            methods: Vec::new(),
+        });
+        let mut api = method("api", Vec::new());
+        api.attributes.push(Attribute { name: "Export".to_string(), arguments: Vec::new() });
+        let mut typed = method("typed", Vec::new());
+        typed.parameters.push(Parameter {
+            name: "p".to_string(),
+            typ: Some(Type::Object("App\\Point".to_string())),
+            default_value: None,
+            is_reference: false,
+            is_variadic: false,
+        });
+        let mut programs = vec![vec![AstNode::Program(vec![AstNode::Namespace(NamespaceDecl {
+            name: Some("App".to_string()),
+            statements: vec![
+                shape,
+                class("Base", None, &[], Vec::new()),
+                class("Square", Some("Base"), &["Shape"], vec![method("area", vec![call(constant("helper"), Vec::new())])]),
+                class("Point", None, &[], Vec::new()),
+                class("Unused", None, &[], Vec::new()),
+                function("helper", Vec::new()),
+                function("unused", vec![Statement::Echo(vec![string("dead string")])]),
+                AstNode::Function(api),
+                AstNode::Function(typed),
+                function("by_name", Vec::new()),
+                AstNode::Statement(Box::new(Statement::Expression(Box::new(Expression::New {
+                    class: Box::new(constant("\\App\\Square")),
+                    arguments: Vec::new(),
+                })))),
+                AstNode::Statement(Box::new(call(constant("usort"), vec![Expression::Variable("a".to_string()), string("by_name")]))),
+                AstNode::Statement(Box::new(call(constant("TYPED"), Vec::new()))),
+            ],
+        })])]];
+
+        let removed = eliminate_dead_code(&mut programs);
+        assert_eq!(removed, Removed { functions: 1, classes: 1 });
+        assert_eq!(names(&programs[0]), ["Shape", "Base", "Square", "Point", "helper", "api", "typed", "by_name"]);
+    }
+
+    #[test]
+    fn test_dynamic_names_keep_declarations() {
+        let program = |statement: Statement| vec![
+            function("f", Vec::new()),
+            class("C", None, &[], Vec::new()),
+            AstNode::Statement(Box::new(statement)),
+        ];
+
+        let mut programs = vec![program(call(Expression::Variable("f".to_string()), Vec::new()))];
+        assert_eq!(eliminate_dead_code(&mut programs), Removed { functions: 0, classes: 1 });
+
+        let new = Expression::New { class: Box::new(Expression::Variable("class".to_string())), arguments: Vec::new() };
+        let mut programs = vec![program(Statement::Expression(Box::new(new)))];
+        assert_eq!(eliminate_dead_code(&mut programs), Removed { functions: 1, classes: 0 });
+
+        let mut programs = vec![program(call(constant("call_user_func"), vec![Expression::Variable("f".to_string())]))];
+        assert_eq!(eliminate_dead_code(&mut programs), Removed { functions: 0, classes: 1 });
+
+        // Another program of the build calls into this one
+        let mut programs = vec![
+            vec![function("f", Vec::new())],
+            vec![AstNode::Statement(Box::new(call(constant("F"), Vec::new())))],
+        ];
+        assert_eq!(eliminate_dead_code(&mut programs), Removed::default());
+    }
+}
@@ -0,0 +1,240 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Compile-time defines.
+//!
+//! Constants given with `-D NAME=value` or the `[defines]` table of
+//! `php2ir.toml` are defined before the program runs, and their uses are
+//! replaced by their values when the program is compiled: `NAME` becomes
+//! the literal and `defined('NAME')` becomes `true`. An `if` whose
+//! condition is constant after that keeps only the branch taken, so a
+//! build with `-D DEBUG=false` carries no code for
+//!
+//! ```php
+//! if (DEBUG) {
+//!     dump_state();
+//! }
+//! ```
+//!
+//! Names are case-sensitive, as for `define()`. A function or class named
+//! like a define is unaffected.
+
+use crate::ast::visit::{walk_expression, walk_statement};
+use crate::ast::{AstNode, Expression, Literal, Statement, UnaryOperator, VisitorMut};
+use crate::unreachable::constant_truthiness;
+
+/// Replace the uses of `defines` by their values, folding the branches
+/// they decide
+///
+/// Returns the number of uses replaced.
+pub fn substitute_defines(ast: &mut [AstNode], defines: &[(String, Literal)]) -> usize {
+    if defines.is_empty() {
+        return 0;
+    }
+    let mut substitution = Substitution { defines, substituted: 0 };
+    for node in ast.iter_mut() {
+        substitution.visit_node(node);
+    }
+    substitution.substituted
+}
+
+/// PHP value of a define given on the command line
+///
+/// `true`, `false` and `null` in any case, integers and floats keep their
+/// type; anything else is a string, with one pair of surrounding quotes
+/// removed.
+pub fn define_literal(value: &str) -> Literal {
+    match value.to_ascii_lowercase().as_str() {
+        "true" => return Literal::Bool(true),
+        "false" => return Literal::Bool(false),
+        "null" => return Literal::Null,
+        _ => {}
+    }
+    if let Ok(n) = value.parse::<i64>() {
+        return Literal::Int(n);
+    }
+    let numeric = value.chars().any(|c| c.is_ascii_digit())
+        && value.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E'));
+    if let Some(x) = value.parse::<f64>().ok().filter(|_| numeric) {
+        return Literal::Float(x);
+    }
+    let unquoted = ['"', '\''].iter().find_map(|&quote| {
+        value.strip_prefix(quote).and_then(|rest| rest.strip_suffix(quote))
+    });
+    Literal::String(unquoted.unwrap_or(value).to_string())
+}
+
+/// Whether `name` can be used as a constant in PHP source
+pub fn is_constant_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+struct Substitution<'a> {
+    defines: &'a [(String, Literal)],
+    substituted: usize,
+}
+
+impl Substitution<'_> {
+    /// Value of the define a constant name refers to
+    fn lookup(&self, name: &str) -> Option<&Literal> {
+        let name = name.strip_prefix('\\').unwrap_or(name);
+        self.defines.iter().find(|(defined, _)| defined == name).map(|(_, value)| value)
+    }
+
+    /// Visit a class position, which only names a class when it is a constant
+    fn visit_class(&mut self, class: &mut Expression) {
+        if !matches!(class, Expression::Constant(_)) {
+            self.visit_expression(class);
+        }
+    }
+}
+
+impl VisitorMut for Substitution<'_> {
+    fn visit_statement(&mut self, stmt: &mut Statement) {
+        let Statement::If { condition, then_branch, else_branch } = stmt else {
+            walk_statement(self, stmt);
+            return;
+        };
+        let before = self.substituted;
+        self.visit_expression(condition);
+        self.visit_statement(then_branch);
+        if let Some(else_branch) = else_branch {
+            self.visit_statement(else_branch);
+        }
+        // Only conditions made constant here; unreachable code elimination
+        // reports the ones that were constant in the source
+        if self.substituted == before {
+            return;
+        }
+        match constant_truthiness(condition) {
+            Some(true) => *stmt = std::mem::replace(then_branch.as_mut(), Statement::Block(Vec::new())),
+            Some(false) => *stmt = else_branch.take().map_or(Statement::Block(Vec::new()), |branch| *branch),
+            None => {}
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression) {
+        match expr {
+            Expression::Constant(name) => {
+                if let Some(value) = self.lookup(name) {
+                    *expr = Expression::Literal(value.clone());
+                    self.substituted += 1;
+                }
+            }
+            Expression::FunctionCall { name, arguments } => {
+                let is_defined = matches!(name.as_ref(), Expression::Constant(f)
+                    if f.trim_start_matches('\\').eq_ignore_ascii_case("defined"));
+                if let (true, [Expression::Literal(Literal::String(constant))]) = (is_defined, arguments.as_slice()) {
+                    if self.lookup(constant).is_some() {
+                        *expr = Expression::Literal(Literal::Bool(true));
+                        self.substituted += 1;
+                        return;
+                    }
+                }
+                self.visit_class(name);
+                arguments.iter_mut().for_each(|a| self.visit_expression(a));
+            }
+            Expression::New { class, arguments } | Expression::StaticCall { class, arguments, .. } => {
+                self.visit_class(class);
+                arguments.iter_mut().for_each(|a| self.visit_expression(a));
+            }
+            Expression::StaticPropertyAccess { class, .. } | Expression::ClassConstant { class, .. } => {
+                self.visit_class(class);
+            }
+            Expression::InstanceOf { expr: inner, class } => {
+                self.visit_expression(inner);
+                self.visit_class(class);
+            }
+            Expression::UnaryOp { op: UnaryOperator::Not, expr: inner } => {
+                let before = self.substituted;
+                self.visit_expression(inner);
+                // `!DEBUG` decides a branch as well as `DEBUG` does
+                if let Some(truthy) = constant_truthiness(inner).filter(|_| self.substituted > before) {
+                    *expr = Expression::Literal(Literal::Bool(!truthy));
+                }
+            }
+            _ => walk_expression(self, expr),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constant(name: &str) -> Expression {
+        Expression::Constant(name.to_string())
+    }
+
+    fn echo(s: &str) -> Statement {
+        Statement::Echo(vec![Expression::Literal(Literal::String(s.to_string()))])
+    }
+
+    #[test]
+    fn test_substitute_defines() {
+        let defines = vec![
+            ("DEBUG".to_string(), Literal::Bool(false)),
+            ("VERSION".to_string(), Literal::String("1.2.0".to_string())),
+        ];
+        let mut ast = vec![
+            AstNode::Statement(Box::new(Statement::If {
+                condition: Box::new(constant("DEBUG")),
+                then_branch: Box::new(echo("debug")),
+                else_branch: None,
+            })),
+            AstNode::Statement(Box::new(Statement::If {
+                condition: Box::new(Expression::UnaryOp { op: UnaryOperator::Not, expr: Box::new(constant("DEBUG")) }),
+                then_branch: Box::new(echo("release")),
+                else_branch: Some(Box::new(echo("debug"))),
+            })),
+            AstNode::Statement(Box::new(Statement::Echo(vec![
+                Expression::FunctionCall {
+                    name: Box::new(constant("defined")),
+                    arguments: vec![Expression::Literal(Literal::String("VERSION".to_string()))],
+                },
+                constant("\\VERSION"),
+                constant("debug"),
+                Expression::FunctionCall { name: Box::new(constant("VERSION")), arguments: Vec::new() },
+            ]))),
+        ];
+
+        assert_eq!(substitute_defines(&mut ast, &defines), 4);
+        assert!(matches!(ast[0], AstNode::Statement(ref s) if matches!(**s, Statement::Block(ref b) if b.is_empty())));
+        assert!(matches!(ast[1], AstNode::Statement(ref s) if matches!(**s, Statement::Echo(_))));
+        let AstNode::Statement(ref echo) = ast[2] else { panic!("expected a statement") };
+        let Statement::Echo(ref values) = **echo else { panic!("expected echo") };
+        assert!(matches!(values[0], Expression::Literal(Literal::Bool(true))));
+        assert!(matches!(values[1], Expression::Literal(Literal::String(ref s)) if s == "1.2.0"));
+        assert!(matches!(values[2], Expression::Constant(_)));
+        assert!(matches!(values[3], Expression::FunctionCall { .. }));
+    }
+
+    #[test]
+    fn test_define_literal() {
+        assert!(matches!(define_literal("false"), Literal::Bool(false)));
+        assert!(matches!(define_literal("TRUE"), Literal::Bool(true)));
+        assert!(matches!(define_literal("null"), Literal::Null));
+        assert!(matches!(define_literal("-42"), Literal::Int(-42)));
+        assert!(matches!(define_literal("1.5"), Literal::Float(x) if x == 1.5));
+        assert!(matches!(define_literal("inf"), Literal::String(ref s) if s == "inf"));
+        assert!(matches!(define_literal("'1.2.0'"), Literal::String(ref s) if s == "1.2.0"));
+        assert!(matches!(define_literal("1.2.0"), Literal::String(ref s) if s == "1.2.0"));
+        assert!(is_constant_name("APP_ENV"));
+        assert!(!is_constant_name("2FAST"));
+    }
+}
@@ -0,0 +1,355 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Function and class registration across the include graph.
+//!
+//! Declarations are registered in source order, file by file. Redeclaring a
+//! function or class unconditionally is a compile error naming both sites.
+//! Declarations nested in `if (!function_exists('f'))` / `if
+//! (!class_exists('C'))` follow PHP's conditional-definition semantics: the
+//! first definition wins and later guarded ones are skipped.
+
+use std::fmt;
+use indexmap::IndexMap;
+use log::debug;
+use crate::ast::{AstNode, Expression, Literal, Statement, UnaryOperator};
+use crate::diagnostics::{codes, Diagnostic};
+use crate::error::{CompileError, CompileResult};
+
+/// Symbol table a declaration lives in
+///
+/// Classes, interfaces, traits and enums share one table in PHP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolTable {
+    Function,
+    Class,
+}
+
+/// Where a declaration appears
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefinitionSite {
+    /// File or bundle URL
+    pub file: String,
+
+    /// 1-based position among the declarations of the file
+    pub ordinal: usize,
+}
+
+impl fmt::Display for DefinitionSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (declaration #{})", self.file, self.ordinal)
+    }
+}
+
+/// How a declaration is reached at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    /// Top-level: always declared
+    Always,
+
+    /// Inside a block: declared only if the block runs
+    Conditional,
+
+    /// Guarded by `function_exists`/`class_exists` on its own name
+    Guarded,
+}
+
+/// A registered function or class
+#[derive(Debug, Clone)]
+pub struct Definition {
+    /// Declared name, fully qualified
+    pub name: String,
+
+    /// `function`, `class`, `interface`, `trait` or `enum`
+    pub kind: &'static str,
+
+    pub site: DefinitionSite,
+    pub condition: Condition,
+}
+
+/// Order-preserving registry of every declared function and class
+#[derive(Debug, Default)]
+pub struct DefinitionRegistry {
+    /// Keyed by table and lowercased name, in registration order
+    definitions: IndexMap<(SymbolTable, String), Definition>,
+
+    /// Warnings about declarations that may collide at runtime
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl DefinitionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the declarations of one file
+    ///
+    /// Fails on the first unconditional redeclaration.
+    pub fn register(&mut self, ast: &[AstNode], file: &str) -> CompileResult<()> {
+        let mut collector = Collector { file, ordinal: 0, found: Vec::new() };
+        collector.nodes(ast, Condition::Always, None);
+
+        for (table, definition) in collector.found {
+            self.insert(table, definition)?;
+        }
+        Ok(())
+    }
+
+    /// Registered definition of a function
+    pub fn function(&self, name: &str) -> Option<&Definition> {
+        self.definitions.get(&(SymbolTable::Function, name.to_ascii_lowercase()))
+    }
+
+    /// Registered definition of a class, interface, trait or enum
+    pub fn class(&self, name: &str) -> Option<&Definition> {
+        self.definitions.get(&(SymbolTable::Class, name.to_ascii_lowercase()))
+    }
+
+    /// All definitions in registration order
+    pub fn definitions(&self) -> impl Iterator<Item = &Definition> {
+        self.definitions.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.definitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+
+    /// Take the diagnostics reported since the last call
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn insert(&mut self, table: SymbolTable, definition: Definition) -> CompileResult<()> {
+        let key = (table, definition.name.to_ascii_lowercase());
+        let Some(previous) = self.definitions.get(&key) else {
+            self.definitions.insert(key, definition);
+            return Ok(());
+        };
+
+        match (previous.condition, definition.condition) {
+            (Condition::Always, Condition::Always) => Err(CompileError::Type {
+                message: format!(
+                    "Cannot redeclare {} {} (previously declared in {}, redeclared in {})",
+                    definition.kind, definition.name, previous.site, definition.site
+                ),
+                location: None,
+            }),
+            (_, Condition::Guarded) => {
+                debug!(
+                    "Skipping guarded {} {} at {}: already declared in {}",
+                    definition.kind, definition.name, definition.site, previous.site
+                );
+                Ok(())
+            }
+            (Condition::Conditional, Condition::Conditional) => Ok(()),
+            _ => {
+                let diagnostic = Diagnostic::warning(
+                    codes::CONDITIONAL_REDECLARATION,
+                    format!("{} {} may be redeclared at runtime", definition.kind, definition.name),
+                )
+                .with_file(definition.site.file.clone())
+                .with_note(format!("previously declared in {}", previous.site));
+                self.diagnostics.push(diagnostic);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Walks one file collecting declarations with their condition
+struct Collector<'f> {
+    file: &'f str,
+    ordinal: usize,
+    found: Vec<(SymbolTable, Definition)>,
+}
+
+impl Collector<'_> {
+    fn nodes(&mut self, nodes: &[AstNode], condition: Condition, guard: Option<&(SymbolTable, String)>) {
+        for node in nodes {
+            self.node(node, condition, guard);
+        }
+    }
+
+    fn node(&mut self, node: &AstNode, condition: Condition, guard: Option<&(SymbolTable, String)>) {
+        let (table, kind, name) = match node {
+            AstNode::Program(nodes) => return self.nodes(nodes, condition, guard),
+            AstNode::Namespace(ns) => return self.nodes(&ns.statements, condition, guard),
+            AstNode::Statement(stmt) => return self.statement(stmt, condition, guard),
+            AstNode::Function(f) => (SymbolTable::Function, "function", &f.name),
+            AstNode::Class(c) => (SymbolTable::Class, "class", &c.name),
+            AstNode::Interface(i) => (SymbolTable::Class, "interface", &i.name),
+            AstNode::Trait(t) => (SymbolTable::Class, "trait", &t.name),
+            AstNode::Enum(e) => (SymbolTable::Class, "enum", &e.name),
+            AstNode::Expression(_) | AstNode::Use(_) | AstNode::Attribute(_) => return,
+        };
+
+        self.ordinal += 1;
+        let guarded = guard.is_some_and(|(t, n)| *t == table && n.eq_ignore_ascii_case(name));
+        self.found.push((table, Definition {
+            name: name.clone(),
+            kind,
+            site: DefinitionSite { file: self.file.to_string(), ordinal: self.ordinal },
+            condition: if guarded { Condition::Guarded } else { condition },
+        }));
+
+        // Functions declared inside a function body exist once it is called
+        if let AstNode::Function(f) = node {
+            self.statement(&f.body, Condition::Conditional, None);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement, condition: Condition, guard: Option<&(SymbolTable, String)>) {
+        match stmt {
+            Statement::Declaration(node) => self.node(node, condition, guard),
+            Statement::Block(stmts) => stmts.iter().for_each(|s| self.statement(s, condition, guard)),
+            Statement::If { condition: test, then_branch, else_branch } => {
+                let then_guard = existence_guard(test);
+                self.statement(then_branch, Condition::Conditional, then_guard.as_ref().or(guard));
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch, Condition::Conditional, guard);
+                }
+            }
+            Statement::While { body, .. }
+            | Statement::DoWhile { body, .. }
+            | Statement::For { body, .. }
+            | Statement::Foreach { body, .. } => self.statement(body, Condition::Conditional, guard),
+            Statement::Declare { body, .. } => self.statement(body, condition, guard),
+            Statement::Switch { cases, .. } => {
+                for case in cases {
+                    case.statements.iter().for_each(|s| self.statement(s, Condition::Conditional, guard));
+                }
+            }
+            Statement::Try { try_block, catch_blocks, finally_block } => {
+                self.statement(try_block, Condition::Conditional, guard);
+                for catch in catch_blocks {
+                    self.statement(&catch.body, Condition::Conditional, guard);
+                }
+                if let Some(finally_block) = finally_block {
+                    self.statement(finally_block, condition, guard);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Name checked by `!function_exists('f')` or `!class_exists('C')`
+fn existence_guard(condition: &Expression) -> Option<(SymbolTable, String)> {
+    let Expression::UnaryOp { op: UnaryOperator::Not, expr } = condition else {
+        return None;
+    };
+    let Expression::FunctionCall { name, arguments } = expr.as_ref() else {
+        return None;
+    };
+    let Expression::Constant(function) = name.as_ref() else {
+        return None;
+    };
+
+    let table = match function.trim_start_matches('\\').to_ascii_lowercase().as_str() {
+        "function_exists" => SymbolTable::Function,
+        "class_exists" | "interface_exists" | "trait_exists" | "enum_exists" => SymbolTable::Class,
+        _ => return None,
+    };
+    match arguments.first() {
+        Some(Expression::Literal(Literal::String(checked))) => {
+            Some((table, checked.trim_start_matches('\\').to_string()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{FunctionDecl, Visibility};
+
+    fn function(name: &str) -> AstNode {
+        AstNode::Function(FunctionDecl {
+            name: name.to_string(),
+            parameters: vec![],
+            return_type: None,
+            body: Box::new(Statement::Block(vec![])),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        })
+    }
+
+    fn not_exists(checker: &str, name: &str) -> Expression {
+        Expression::UnaryOp {
+            op: UnaryOperator::Not,
+            expr: Box::new(Expression::FunctionCall {
+                name: Box::new(Expression::Constant(checker.to_string())),
+                arguments: vec![Expression::Literal(Literal::String(name.to_string()))],
+            }),
+        }
+    }
+
+    fn if_not_exists(checker: &str, name: &str, declaration: AstNode) -> AstNode {
+        AstNode::Statement(Box::new(Statement::If {
+            condition: Box::new(not_exists(checker, name)),
+            then_branch: Box::new(Statement::Block(vec![Statement::Declaration(Box::new(declaration))])),
+            else_branch: None,
+        }))
+    }
+
+    #[test]
+    fn test_duplicate_names_both_sites() {
+        let mut registry = DefinitionRegistry::new();
+        registry.register(&[function("helper"), function("other")], "a.php").unwrap();
+
+        let err = registry.register(&[function("Helper")], "b.php").unwrap_err().to_string();
+        assert!(err.contains("Cannot redeclare function Helper"));
+        assert!(err.contains("a.php (declaration #1)"));
+        assert!(err.contains("b.php (declaration #1)"));
+    }
+
+    #[test]
+    fn test_guarded_definition_keeps_first() {
+        let mut registry = DefinitionRegistry::new();
+        registry.register(&[function("helper")], "a.php").unwrap();
+        registry.register(&[if_not_exists("function_exists", "helper", function("helper"))], "b.php").unwrap();
+
+        let definition = registry.function("HELPER").unwrap();
+        assert_eq!(definition.site.file, "a.php");
+        assert_eq!(definition.condition, Condition::Always);
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn test_guard_must_match_name_and_table() {
+        assert_eq!(
+            existence_guard(&not_exists("\\class_exists", "\\App\\User")),
+            Some((SymbolTable::Class, "App\\User".to_string()))
+        );
+
+        // A class_exists guard does not protect a function
+        let mut registry = DefinitionRegistry::new();
+        registry.register(&[function("helper")], "a.php").unwrap();
+        registry.register(&[if_not_exists("class_exists", "helper", function("helper"))], "b.php").unwrap();
+        assert_eq!(registry.function("helper").unwrap().site.file, "a.php");
+
+        let diagnostics = registry.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, codes::CONDITIONAL_REDECLARATION);
+        assert_eq!(diagnostics[0].file.as_deref(), Some("b.php"));
+    }
+}
@@ -0,0 +1,170 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Direct calls through callable variables.
+//!
+//! A call `$f(...)` where `$f` is proven to hold the name of a user function
+//! is rewritten to a direct call of that function, so code generation emits
+//! a plain call instead of a lookup by name at runtime.
+//!
+//! The proof comes from [`LiteralChecker`]. Variables that can change
+//! behind its back are never rewritten: names listed in a `global`
+//! statement, variables captured or stored by reference, and every variable
+//! of a program that uses `$GLOBALS` or variable variables.
+
+use std::collections::{HashMap, HashSet};
+use crate::ast::visit::{walk_expression_ref, walk_statement_ref};
+use crate::ast::index::rewrite_expressions;
+use crate::ast::{AstNode, Expression, NodeId, Statement, Visitor};
+use crate::literals::LiteralChecker;
+use crate::types::IntWidth;
+
+/// Rewrite provable calls through variables into direct calls
+///
+/// Returns the number of calls rewritten.
+pub fn devirtualize(ast: &mut [AstNode], int_width: IntWidth) -> usize {
+    let mut checker = LiteralChecker::new(String::new()).with_int_width(int_width);
+    checker.check(ast);
+    let calls = checker.take_direct_calls();
+    if calls.is_empty() {
+        return 0;
+    }
+
+    let mut aliases = Aliases::default();
+    ast.iter().for_each(|node| aliases.visit_node(node));
+    if aliases.dynamic_scope {
+        return 0;
+    }
+    let calls: HashMap<NodeId, String> = calls.into_iter()
+        .filter(|(_, (variable, _))| !aliases.variables.contains(variable))
+        .map(|(id, (_, function))| (id, function))
+        .collect();
+
+    let mut rewritten = 0;
+    rewrite_expressions(ast, calls.keys().copied(), |id, expr| {
+        if let Expression::FunctionCall { name, .. } = expr {
+            **name = Expression::Constant(calls[&id].clone());
+            rewritten += 1;
+        }
+    });
+    rewritten
+}
+
+/// Variables that may be written without an assignment to their name
+#[derive(Default)]
+pub(crate) struct Aliases {
+    pub(crate) variables: HashSet<String>,
+
+    /// `$GLOBALS` or `$$name` is used, so any variable may change
+    pub(crate) dynamic_scope: bool,
+}
+
+impl Visitor for Aliases {
+    fn visit_statement(&mut self, stmt: &Statement) {
+        if let Statement::Global(names) = stmt {
+            self.variables.extend(names.iter().cloned());
+        }
+        walk_statement_ref(self, stmt);
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Variable(name) if name == "GLOBALS" => self.dynamic_scope = true,
+            Expression::VariableVariable(_) => self.dynamic_scope = true,
+            Expression::Closure(closure) => {
+                let by_reference = closure.uses.iter().filter(|captured| captured.by_reference);
+                self.variables.extend(by_reference.map(|captured| captured.name.clone()));
+            }
+            Expression::Array { elements } | Expression::Literal(crate::ast::Literal::Array(elements)) => {
+                for element in elements.iter().filter(|element| element.is_reference) {
+                    if let Expression::Variable(name) = &element.value {
+                        self.variables.insert(name.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+        walk_expression_ref(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{AssignmentOperator, FunctionDecl, Literal, Visibility};
+
+    fn assign(name: &str, value: Expression) -> AstNode {
+        AstNode::Expression(Box::new(Expression::Assignment {
+            target: Box::new(Expression::Variable(name.to_string())),
+            op: AssignmentOperator::Assign,
+            value: Box::new(value),
+        }))
+    }
+
+    fn call(variable: &str) -> AstNode {
+        AstNode::Expression(Box::new(Expression::FunctionCall {
+            name: Box::new(Expression::Variable(variable.to_string())),
+            arguments: vec![],
+        }))
+    }
+
+    fn program(extra: Vec<AstNode>) -> Vec<AstNode> {
+        let mut ast = vec![
+            AstNode::Function(FunctionDecl {
+                name: "greet".to_string(),
+                parameters: vec![],
+                return_type: None,
+                body: Box::new(Statement::Block(vec![])),
+                attributes: vec![],
+                is_static: false,
+                visibility: Visibility::Public,
+                doc_comment: None,
+            }),
+            assign("f", Expression::Literal(Literal::String("greet".to_string()))),
+            call("f"),
+        ];
+        ast.extend(extra);
+        ast
+    }
+
+    fn callee(node: &AstNode) -> &Expression {
+        match node {
+            AstNode::Expression(expr) => match expr.as_ref() {
+                Expression::FunctionCall { name, .. } => name,
+                other => panic!("not a call: {:?}", other),
+            },
+            other => panic!("not an expression: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_direct_call() {
+        let mut ast = program(vec![
+            assign("f", Expression::Variable("other".to_string())),
+            call("f"),
+        ]);
+        assert_eq!(devirtualize(&mut ast, IntWidth::default()), 1);
+        assert!(matches!(callee(&ast[2]), Expression::Constant(name) if name == "greet"));
+        assert!(matches!(callee(&ast[4]), Expression::Variable(_)));
+    }
+
+    #[test]
+    fn test_aliased_variable_is_kept() {
+        let mut ast = program(vec![AstNode::Statement(Box::new(Statement::Global(vec!["f".to_string()])))]);
+        assert_eq!(devirtualize(&mut ast, IntWidth::default()), 0);
+        assert!(matches!(callee(&ast[2]), Expression::Variable(_)));
+    }
+}
@@ -0,0 +1,597 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Structured compiler diagnostics.
+//!
+//! Semantic analysis reports its findings as [`Diagnostic`]s carrying a
+//! stable code, a severity, an optional primary span and notes. They are
+//! collected into a [`DiagnosticReport`] that the CLI renders and tests can
+//! inspect. Errors that stop a compilation become diagnostics too, with
+//! [`Diagnostic::from_error`].
+//!
+//! With the source at hand a diagnostic shows the offending line, the span
+//! underlined with carets and related locations labelled below theirs:
+//!
+//! ```text
+//! error[T0309]: Declaration of B::f() must be compatible with A::f(): int
+//!   --> app.php:15:21
+//!    |
+//! 15 |     public function f(): string {}
+//!    |                     ^
+//!   ::: app.php:4:21
+//!    |
+//!  4 |     public function f(): int {}
+//!    |                     - overridden method declared here
+//! ```
+//!
+//! [`Diagnostic::render_colored`] adds ANSI colors for terminals.
+
+use std::fmt;
+use crate::ast::Span;
+use crate::error::CompileError;
+
+/// Diagnostic codes
+///
+/// Codes are stable across releases; `php2ir --explain CODE` describes
+/// each one. The letter gives the stage that reports it:
+///
+/// - `P`: parsing and includes
+/// - `T`: type checking and semantic analysis
+/// - `G`: IR and code generation
+/// - `L`: linking
+/// - `R`: the interpreted program
+/// - `C`: options, manifest and toolchain
+/// - `U`: unsupported features
+/// - `F`: file access
+/// - `I`: compiler bugs
+/// - `S`: compile sessions of build servers and editors
+pub mod codes {
+    /// The source cannot be parsed, or an include cannot be resolved
+    pub const PARSE: &str = "P0001";
+
+    /// A type error stopped the compilation
+    pub const TYPE: &str = "T0001";
+
+    /// A variable is read before any assignment in its scope
+    pub const UNDEFINED_VARIABLE: &str = "T0301";
+
+    /// A function or class may be declared twice depending on runtime conditions
+    pub const CONDITIONAL_REDECLARATION: &str = "T0302";
+
+    /// Code after `return`, `throw`, `exit`, `break` or `continue`, or in a constant-false branch
+    pub const UNREACHABLE_CODE: &str = "T0303";
+
+    /// A literal argument is rejected by the parameter's declared type
+    pub const ARGUMENT_TYPE: &str = "T0304";
+
+    /// A `match` without a default arm misses a value its subject can take
+    pub const UNHANDLED_MATCH: &str = "T0305";
+
+    /// Duplicate, lossy or illegal array key
+    pub const ARRAY_KEY: &str = "T0306";
+
+    /// A `printf`-family format string does not fit its arguments
+    pub const FORMAT_STRING: &str = "T0307";
+
+    /// A call through a variable does not fit the callable it holds
+    pub const CALLABLE_CALL: &str = "T0308";
+
+    /// An overriding method is incompatible with the method it overrides
+    pub const METHOD_SIGNATURE: &str = "T0309";
+
+    /// A write to a property breaks its declared type or `readonly`
+    pub const PROPERTY_WRITE: &str = "T0310";
+
+    /// `self`, `parent` or `static` used where it cannot refer to a class
+    pub const CLASS_SCOPE_TYPE: &str = "T0311";
+
+    /// Code falls back to boxed values or dynamic dispatch (opt-in)
+    pub const DYNAMIC_FALLBACK: &str = "T0312";
+
+    /// The backend cannot generate IR for the program
+    pub const IR_GENERATION: &str = "G0001";
+
+    /// LLVM rejected the IR or failed to generate code
+    pub const CODE_GENERATION: &str = "G0002";
+
+    /// The linker failed
+    pub const LINKING: &str = "L0001";
+
+    /// The interpreted program failed
+    pub const RUNTIME: &str = "R0001";
+
+    /// Invalid options, manifest or toolchain
+    pub const CONFIGURATION: &str = "C0001";
+
+    /// The program uses a feature the compiler does not support
+    pub const UNSUPPORTED: &str = "U0001";
+
+    /// A file cannot be read or written
+    pub const IO: &str = "F0001";
+
+    /// A bug in the compiler
+    pub const INTERNAL: &str = "I0001";
+
+    /// The compilation was cancelled
+    pub const CANCELLED: &str = "S0001";
+
+    /// Every code, in the order above
+    pub const ALL: &[&str] = &[
+        PARSE,
+        TYPE,
+        UNDEFINED_VARIABLE,
+        CONDITIONAL_REDECLARATION,
+        UNREACHABLE_CODE,
+        ARGUMENT_TYPE,
+        UNHANDLED_MATCH,
+        ARRAY_KEY,
+        FORMAT_STRING,
+        CALLABLE_CALL,
+        METHOD_SIGNATURE,
+        PROPERTY_WRITE,
+        CLASS_SCOPE_TYPE,
+        DYNAMIC_FALLBACK,
+        IR_GENERATION,
+        CODE_GENERATION,
+        LINKING,
+        RUNTIME,
+        CONFIGURATION,
+        UNSUPPORTED,
+        IO,
+        INTERNAL,
+        CANCELLED,
+    ];
+}
+
+/// Diagnostic severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Note => write!(f, "note"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A single finding about the program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+
+    /// File the primary span points into
+    pub file: Option<String>,
+
+    /// Primary span, when the AST carries one
+    pub span: Option<Span>,
+
+    /// Additional context shown below the message
+    pub notes: Vec<String>,
+
+    /// Other locations involved, such as a conflicting declaration
+    pub related: Vec<Related>,
+}
+
+/// Secondary location of a diagnostic
+#[derive(Debug, Clone, PartialEq)]
+pub struct Related {
+    pub message: String,
+    pub file: Option<String>,
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            severity,
+            message: message.into(),
+            file: None,
+            span: None,
+            notes: Vec::new(),
+            related: Vec::new(),
+        }
+    }
+
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, code, message)
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, code, message)
+    }
+
+    pub fn with_file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    pub fn with_span(mut self, span: Option<Span>) -> Self {
+        self.span = span;
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Add a secondary location, e.g. the declaration this one conflicts with
+    pub fn with_related(mut self, message: impl Into<String>, file: Option<String>, span: Option<Span>) -> Self {
+        self.related.push(Related { message: message.into(), file, span });
+        self
+    }
+
+    /// Diagnostic for an error that stopped the compilation
+    ///
+    /// `source` is the text of the file the error points into, if any, so a
+    /// line and column can be turned into a span.
+    pub fn from_error(error: &CompileError, source: Option<&str>) -> Self {
+        let (code, message) = match error {
+            CompileError::Parse { message, .. } => (codes::PARSE, message.clone()),
+            CompileError::Type { message, .. } => (codes::TYPE, message.clone()),
+            CompileError::Io(e) => (codes::IO, e.to_string()),
+            CompileError::IrGeneration(_) => (codes::IR_GENERATION, error.to_string()),
+            CompileError::LlvmCompilation(_) => (codes::CODE_GENERATION, error.to_string()),
+            CompileError::Linking(_) => (codes::LINKING, error.to_string()),
+            CompileError::Runtime(_) => (codes::RUNTIME, error.to_string()),
+            CompileError::Configuration(_) => (codes::CONFIGURATION, error.to_string()),
+            CompileError::Unsupported(_) => (codes::UNSUPPORTED, error.to_string()),
+            CompileError::Internal(_) => (codes::INTERNAL, error.to_string()),
+            CompileError::Cancelled => (codes::CANCELLED, error.to_string()),
+        };
+        let position = match error {
+            CompileError::Parse { line: Some(line), column: Some(column), .. } => Some((*line, *column)),
+            CompileError::Type { location: Some(location), .. } => Some((location.line, location.column)),
+            _ => None,
+        };
+        let span = position.zip(source).map(|((line, column), source)| {
+            let start = offset(source, line, column);
+            Span::new(start, start + source[start..].chars().next().map_or(0, char::len_utf8))
+        });
+        let diagnostic = Self::error(code, message).with_span(span);
+        match error.file() {
+            Some(file) => diagnostic.with_file(file.display().to_string()),
+            None => diagnostic,
+        }
+    }
+
+    /// Render the diagnostic, showing its span in `source` when it is given
+    ///
+    /// Without the source, spans are shown as byte offsets.
+    pub fn render(&self, source: Option<&str>) -> String {
+        self.render_styled(source, Style::PLAIN)
+    }
+
+    /// Like [`render`](Self::render), with ANSI colors for a terminal
+    pub fn render_colored(&self, source: Option<&str>) -> String {
+        self.render_styled(source, Style::ANSI)
+    }
+
+    fn render_styled(&self, source: Option<&str>, style: Style) -> String {
+        let color = style.severity(self.severity);
+        let mut out = format!(
+            "{}: {}\n",
+            style.paint(&format!("{}[{}]", self.severity, self.code), color),
+            style.paint(&self.message, Style::BOLD)
+        );
+        // Only spans into the primary file can be resolved against `source`
+        let related_source = |related: &Related| source.filter(|_| related.file.is_none() || related.file == self.file);
+        let lines = std::iter::once((self.span, source))
+            .chain(self.related.iter().map(|related| (related.span, related_source(related))))
+            .filter_map(|(span, source)| Some(line_column(source?, span?.start).0));
+        let gutter = Gutter { width: lines.max().map_or(0, |line| line.to_string().len()).max(2), style };
+
+        out.push_str(&location("  -->", self.file.as_deref(), self.span, source, style));
+        if let (Some(span), Some(source)) = (self.span, source) {
+            out.push_str(&gutter.snippet(source, span, '^', None, color));
+        }
+        for note in &self.notes {
+            out.push_str(&gutter.note(note));
+        }
+        for related in &self.related {
+            match (related.span, related_source(related)) {
+                (Some(span), Some(source)) => {
+                    out.push_str(&location("  :::", related.file.as_deref().or(self.file.as_deref()), Some(span), Some(source), style));
+                    out.push_str(&gutter.snippet(source, span, '-', Some(&related.message), Style::BLUE));
+                }
+                _ => {
+                    out.push_str(&gutter.note(&related.message));
+                    out.push_str(&location("     -->", related.file.as_deref(), related.span, None, style));
+                }
+            }
+        }
+        out
+    }
+}
+
+/// ANSI styling of rendered diagnostics, or none
+#[derive(Clone, Copy)]
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    const PLAIN: Style = Style { color: false };
+    const ANSI: Style = Style { color: true };
+
+    const BOLD: &'static str = "1";
+    const BLUE: &'static str = "1;34";
+
+    fn severity(self, severity: Severity) -> &'static str {
+        match severity {
+            Severity::Note => "1;36",
+            Severity::Warning => "1;33",
+            Severity::Error => "1;31",
+        }
+    }
+
+    fn paint(self, text: &str, code: &str) -> String {
+        if self.color && !text.is_empty() {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Line-number gutter of a diagnostic's snippets and notes
+struct Gutter {
+    width: usize,
+    style: Style,
+}
+
+impl Gutter {
+    /// `N | line` of the span's first line, underlined with `marker`
+    fn snippet(&self, source: &str, span: Span, marker: char, label: Option<&str>, color: &str) -> String {
+        let (line, _) = line_column(source, span.start);
+        let start = span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |newline| newline + 1);
+        let line_end = source[start..].find('\n').map_or(source.len(), |newline| start + newline);
+        let text = source[line_start..line_end].trim_end_matches('\r');
+
+        // Tabs are kept so the underline lines up however they are shown
+        let indent: String = source[line_start..start].chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        let end = span.end.clamp(start, line_end);
+        let width = source[start..end].chars().count().max(1);
+        let underline = marker.to_string().repeat(width);
+        let underline = match label {
+            Some(label) => format!("{} {}", underline, label),
+            None => underline,
+        };
+
+        let bar = self.style.paint("|", Style::BLUE);
+        format!(
+            "{:w$} {}\n{} {} {}\n{:w$} {} {}{}\n",
+            "", bar,
+            self.style.paint(&format!("{:>w$}", line, w = self.width), Style::BLUE), bar, text,
+            "", bar, indent, self.style.paint(&underline, color),
+            w = self.width
+        )
+    }
+
+    fn note(&self, note: &str) -> String {
+        format!("{:w$} {} {}\n", "", self.style.paint("= note:", Style::BOLD), note, w = self.width)
+    }
+}
+
+/// `--> file:line:column` line for a span, empty when nothing is known
+fn location(arrow: &str, file: Option<&str>, span: Option<Span>, source: Option<&str>, style: Style) -> String {
+    let position = match (span, source) {
+        (Some(span), Some(source)) => {
+            let (line, column) = line_column(source, span.start);
+            Some(format!("{}:{}", line, column))
+        }
+        (Some(span), None) => Some(format!("{}..{}", span.start, span.end)),
+        (None, _) => None,
+    };
+    let arrow = style.paint(arrow, Style::BLUE);
+    match (file, position) {
+        (Some(file), Some(position)) => format!("{} {}:{}\n", arrow, file, position),
+        (Some(file), None) => format!("{} {}\n", arrow, file),
+        (None, Some(position)) => format!("{} {}\n", arrow, position),
+        (None, None) => String::new(),
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(None).trim_end())
+    }
+}
+
+/// Diagnostics collected over a compilation
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticReport {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl DiagnosticReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn extend(&mut self, diagnostics: impl IntoIterator<Item = Diagnostic>) {
+        self.diagnostics.extend(diagnostics);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.diagnostics.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    /// Diagnostics with the given code
+    pub fn with_code<'a>(&'a self, code: &'a str) -> impl Iterator<Item = &'a Diagnostic> + 'a {
+        self.diagnostics.iter().filter(move |d| d.code == code)
+    }
+
+    /// Number of diagnostics of the given severity
+    pub fn count(&self, severity: Severity) -> usize {
+        self.diagnostics.iter().filter(|d| d.severity == severity).count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+
+    pub fn clear(&mut self) {
+        self.diagnostics.clear();
+    }
+
+    /// Render every diagnostic followed by a summary line
+    ///
+    /// `source` returns the text of a file so spans can be shown as
+    /// line:column; byte offsets are shown when it returns `None`.
+    pub fn render(&self, source: impl Fn(&str) -> Option<String>) -> String {
+        self.render_styled(source, Style::PLAIN)
+    }
+
+    /// Like [`render`](Self::render), with ANSI colors for a terminal
+    pub fn render_colored(&self, source: impl Fn(&str) -> Option<String>) -> String {
+        self.render_styled(source, Style::ANSI)
+    }
+
+    fn render_styled(&self, source: impl Fn(&str) -> Option<String>, style: Style) -> String {
+        let mut out = String::new();
+        for diagnostic in &self.diagnostics {
+            let text = diagnostic.file.as_deref().and_then(&source);
+            out.push_str(&diagnostic.render_styled(text.as_deref(), style));
+            out.push('\n');
+        }
+        let (errors, warnings) = (self.count(Severity::Error), self.count(Severity::Warning));
+        if errors + warnings > 0 {
+            out.push_str(&format!("{} error(s), {} warning(s) emitted\n", errors, warnings));
+        }
+        out
+    }
+}
+
+/// Byte offset of a 1-based line and column, clamped to the source
+fn offset(source: &str, line: usize, column: usize) -> usize {
+    let line_start = match line.saturating_sub(1) {
+        0 => 0,
+        n => source.match_indices('\n').nth(n - 1).map_or(source.len(), |(newline, _)| newline + 1),
+    };
+    let text = &source[line_start..];
+    let line_len = text.find('\n').unwrap_or(text.len());
+    line_start + text[..line_len].char_indices().nth(column.saturating_sub(1)).map_or(line_len, |(i, _)| i)
+}
+
+/// 1-based line and column, in characters, of a byte offset
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before[before.rfind('\n').map_or(0, |newline| newline + 1)..].chars().count() + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_with_span() {
+        let diagnostic = Diagnostic::warning(codes::UNDEFINED_VARIABLE, "Variable $x may be undefined")
+            .with_file("app.php")
+            .with_span(Some(Span::new(12, 14)))
+            .with_note("in function main");
+
+        let source = "<?php\necho 1;$x;\n";
+        assert_eq!(
+            diagnostic.render(Some(source)),
+            "warning[T0301]: Variable $x may be undefined\n  --> app.php:2:7\n   |\n 2 | echo 1;$x;\n   |       ^^\n   \
+             = note: in function main\n"
+        );
+        assert_eq!(
+            diagnostic.to_string(),
+            "warning[T0301]: Variable $x may be undefined\n  --> app.php:12..14\n   = note: in function main"
+        );
+    }
+
+    #[test]
+    fn test_render_related() {
+        let diagnostic = Diagnostic::error(codes::METHOD_SIGNATURE, "Declaration of B::f() must be compatible with A::f(): int")
+            .with_file("app.php")
+            .with_span(Some(Span::new(21, 22)))
+            .with_related("overridden method declared here", Some("app.php".to_string()), Some(Span::new(6, 7)));
+
+        let source = "<?php\nf;\n\n\n\n\n\n\n\n\n\n\n\n\nf;\n";
+        assert_eq!(
+            diagnostic.render(Some(source)),
+            "error[T0309]: Declaration of B::f() must be compatible with A::f(): int\n  --> app.php:15:1\n   |\n15 | f;\n   | ^\n  \
+             ::: app.php:2:1\n   |\n 2 | f;\n   | - overridden method declared here\n"
+        );
+        assert_eq!(
+            diagnostic.render(None),
+            "error[T0309]: Declaration of B::f() must be compatible with A::f(): int\n  --> app.php:21..22\n   \
+             = note: overridden method declared here\n     --> app.php:6..7\n"
+        );
+    }
+
+    #[test]
+    fn test_from_error() {
+        let error = CompileError::Parse {
+            file: Some("app.php".into()),
+            message: "unexpected '}'".to_string(),
+            line: Some(2),
+            column: Some(4),
+        };
+        let source = "<?php\n\t$é}\n";
+        let diagnostic = Diagnostic::from_error(&error, Some(source));
+        assert_eq!(diagnostic.code, codes::PARSE);
+        assert_eq!(diagnostic.span, Some(Span::new(10, 11)));
+        assert_eq!(
+            diagnostic.render(Some(source)),
+            "error[P0001]: unexpected '}'\n  --> app.php:2:4\n   |\n 2 | \t$é}\n   | \t  ^\n"
+        );
+        assert!(diagnostic.render_colored(Some(source)).starts_with("\x1b[1;31merror[P0001]\x1b[0m: "));
+
+        let diagnostic = Diagnostic::from_error(&CompileError::Linking("undefined reference".to_string()), None);
+        assert_eq!(diagnostic.to_string(), "error[L0001]: Linking error: undefined reference");
+    }
+
+    #[test]
+    fn test_report() {
+        let mut report = DiagnosticReport::new();
+        report.push(Diagnostic::warning(codes::UNREACHABLE_CODE, "Unreachable code"));
+        report.push(Diagnostic::error(codes::CONDITIONAL_REDECLARATION, "Cannot redeclare"));
+
+        assert_eq!(report.len(), 2);
+        assert!(report.has_errors());
+        assert_eq!(report.with_code(codes::UNREACHABLE_CODE).count(), 1);
+        assert!(report.render(|_| None).ends_with("1 error(s), 1 warning(s) emitted\n"));
+    }
+}
@@ -0,0 +1,243 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Compiler-specific attributes that steer code generation.
+//!
+//! ```php
+//! #[Inline]            // alwaysinline (#[Inline(false)] or #[NoInline]: noinline)
+//! #[Hot]               // hot, and alwaysinline unless inlining is turned off
+//! #[NoMangle]          // emit the PHP name as-is instead of `php.<name>`
+//! #[Export("my_sym")]  // default visibility, optionally under another symbol
+//! function f() {}
+//! ```
+//!
+//! Attributes may also be written with the `Php2Ir\` namespace prefix. On a
+//! class they apply to every method that does not set its own. Functions
+//! left without an inlining directive get one from their size during code
+//! generation.
+
+use crate::ast::{Attribute, Expression, Literal};
+use crate::error::{CompileError, CompileResult};
+
+/// Namespace accepted in front of directive names
+const DIRECTIVE_NAMESPACE: &str = "Php2Ir\\";
+
+/// Prefix of mangled function symbols
+pub const MANGLE_PREFIX: &str = "php.";
+
+/// Inlining hint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InlineHint {
+    Always,
+    /// Candidate the optimizer should favor (`inlinehint`)
+    Candidate,
+    Never,
+}
+
+/// Codegen directives collected from the attributes of a declaration
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodegenDirectives {
+    pub inline: Option<InlineHint>,
+    pub hot: bool,
+    pub no_mangle: bool,
+    pub export: bool,
+    /// Symbol name given to `#[Export("name")]`
+    pub export_name: Option<String>,
+}
+
+impl CodegenDirectives {
+    /// Read directives from a list of attributes, ignoring unrelated ones
+    pub fn from_attributes(attributes: &[Attribute]) -> CompileResult<Self> {
+        let mut directives = Self::default();
+
+        for attribute in attributes {
+            let name = attribute.name.trim_start_matches('\\');
+            let name = name.strip_prefix(DIRECTIVE_NAMESPACE).unwrap_or(name);
+
+            match name {
+                "Inline" => {
+                    directives.inline = match attribute.arguments.as_slice() {
+                        [] => Some(InlineHint::Always),
+                        [Expression::Literal(Literal::Bool(true))] => Some(InlineHint::Always),
+                        [Expression::Literal(Literal::Bool(false))] => Some(InlineHint::Never),
+                        _ => return Err(invalid_arguments(name, "a boolean")),
+                    };
+                }
+                "NoInline" => {
+                    expect_no_arguments(attribute, name)?;
+                    directives.inline = Some(InlineHint::Never);
+                }
+                "Hot" => {
+                    expect_no_arguments(attribute, name)?;
+                    directives.hot = true;
+                }
+                "NoMangle" => {
+                    expect_no_arguments(attribute, name)?;
+                    directives.no_mangle = true;
+                }
+                "Export" => {
+                    directives.export = true;
+                    directives.export_name = match attribute.arguments.as_slice() {
+                        [] => None,
+                        [Expression::Literal(Literal::String(symbol))] if is_valid_symbol(symbol) => {
+                            Some(symbol.clone())
+                        }
+                        _ => return Err(invalid_arguments(name, "a valid symbol name")),
+                    };
+                }
+                _ => {}
+            }
+        }
+        if directives.hot {
+            directives.inline = directives.inline.or(Some(InlineHint::Always));
+        }
+
+        Ok(directives)
+    }
+
+    /// Fill unset directives from an enclosing class
+    pub fn inherit(mut self, class: &CodegenDirectives) -> Self {
+        self.inline = self.inline.or(class.inline);
+        self.hot |= class.hot;
+        self.no_mangle |= class.no_mangle;
+        self.export |= class.export;
+        self
+    }
+
+    /// Use an inlining hint when no directive sets one
+    pub fn or_inline(mut self, hint: Option<InlineHint>) -> Self {
+        self.inline = self.inline.or(hint);
+        self
+    }
+
+    /// Symbol name for a PHP function
+    pub fn symbol(&self, php_name: &str) -> String {
+        if let Some(name) = &self.export_name {
+            return name.clone();
+        }
+
+        let name = php_name.trim_start_matches('\\');
+        if self.no_mangle {
+            name.replace('\\', "_")
+        } else {
+            format!("{}{}", MANGLE_PREFIX, name.replace('\\', "."))
+        }
+    }
+
+    /// Linkage and visibility keywords placed after `define`
+    pub fn linkage(&self) -> &'static str {
+        if self.export {
+            ""
+        } else {
+            "hidden "
+        }
+    }
+
+    /// LLVM function attributes placed after the parameter list
+    pub fn function_attributes(&self) -> String {
+        let inline = match self.inline {
+            Some(InlineHint::Always) => " alwaysinline",
+            Some(InlineHint::Candidate) => " inlinehint",
+            Some(InlineHint::Never) => " noinline",
+            None => "",
+        };
+        format!("{}{}", inline, if self.hot { " hot" } else { "" })
+    }
+}
+
+fn expect_no_arguments(attribute: &Attribute, name: &str) -> CompileResult<()> {
+    if attribute.arguments.is_empty() {
+        Ok(())
+    } else {
+        Err(invalid_arguments(name, "no arguments"))
+    }
+}
+
+fn invalid_arguments(name: &str, expected: &str) -> CompileError {
+    CompileError::Type {
+        message: format!("#[{}] expects {}", name, expected),
+        location: None,
+    }
+}
+
+/// Check that a string is usable as an unquoted LLVM/C symbol
+pub(crate) fn is_valid_symbol(symbol: &str) -> bool {
+    let mut chars = symbol.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attribute(name: &str, arguments: Vec<Expression>) -> Attribute {
+        Attribute { name: name.to_string(), arguments }
+    }
+
+    #[test]
+    fn test_directives_from_attributes() {
+        let directives = CodegenDirectives::from_attributes(&[
+            attribute("Inline", vec![]),
+            attribute("\\Php2Ir\\Export", vec![Expression::Literal(Literal::String("php_add".to_string()))]),
+            attribute("Deprecated", vec![]),
+        ]).unwrap();
+
+        assert_eq!(directives.inline, Some(InlineHint::Always));
+        assert_eq!(directives.symbol("add"), "php_add");
+        assert_eq!(directives.linkage(), "");
+        assert_eq!(directives.function_attributes(), " alwaysinline");
+    }
+
+    #[test]
+    fn test_symbol_mangling() {
+        let directives = CodegenDirectives::default();
+        assert_eq!(directives.symbol("App\\run"), "php.App.run");
+        assert_eq!(directives.linkage(), "hidden ");
+
+        let no_mangle = CodegenDirectives { no_mangle: true, ..Default::default() };
+        assert_eq!(no_mangle.symbol("run"), "run");
+    }
+
+    #[test]
+    fn test_inline_hints() {
+        let hot = CodegenDirectives::from_attributes(&[attribute("Hot", vec![])]).unwrap();
+        assert_eq!(hot.function_attributes(), " alwaysinline hot");
+
+        let cold = CodegenDirectives::from_attributes(&[
+            attribute("Hot", vec![]),
+            attribute("Inline", vec![Expression::Literal(Literal::Bool(false))]),
+        ]).unwrap();
+        assert_eq!(cold.function_attributes(), " noinline hot");
+
+        // A directive wins over the size heuristic
+        assert_eq!(cold.or_inline(Some(InlineHint::Always)).inline, Some(InlineHint::Never));
+        let plain = CodegenDirectives::default().or_inline(Some(InlineHint::Candidate));
+        assert_eq!(plain.function_attributes(), " inlinehint");
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert!(CodegenDirectives::from_attributes(&[attribute("NoMangle", vec![Expression::Variable("x".to_string())])]).is_err());
+        assert!(CodegenDirectives::from_attributes(&[attribute("Export", vec![Expression::Literal(Literal::String("a-b".to_string()))])]).is_err());
+
+        assert!(CodegenDirectives::from_attributes(&[attribute("Hot", vec![Expression::Literal(Literal::Bool(true))])]).is_err());
+
+        let method = CodegenDirectives::default()
+            .inherit(&CodegenDirectives { export: true, ..Default::default() });
+        assert!(method.export);
+    }
+}
@@ -0,0 +1,125 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Files embedded into the program at compile time.
+//!
+//! The files of the `[embed]` table of `php2ir.toml`, or passed with
+//! `--embed NAME=PATH`, are read when the program is compiled and stored
+//! in its read-only data. `embedded_file($name)` returns the contents of
+//! the file embedded under a name, or `false` for any other name. The
+//! module carrying the files registers their table when it starts.
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use crate::error::{CompileError, CompileResult};
+use crate::mixed::PhpMixed;
+use crate::strings::PhpString;
+
+/// Row of a table of embedded files; the table ends with a null name
+#[repr(C)]
+#[derive(Debug)]
+pub struct PhpEmbeddedFile {
+    name: *const c_char,
+    data: *const u8,
+    len: i64,
+}
+
+/// Registered files: name, address and length of the contents
+static FILES: Mutex<Vec<(String, usize, usize)>> = Mutex::new(Vec::new());
+
+/// Contents of the files to embed, by name
+pub fn read_embedded_files(files: &[(String, PathBuf)]) -> CompileResult<Vec<(String, Vec<u8>)>> {
+    files.iter()
+        .map(|(name, path)| match std::fs::read(path) {
+            Ok(contents) => Ok((name.clone(), contents)),
+            Err(e) => Err(CompileError::Configuration(format!("cannot embed {}: {}", path.display(), e))),
+        })
+        .collect()
+}
+
+// FFI functions called by generated code
+
+/// Register a table of embedded files
+///
+/// # Safety
+///
+/// `table` must point to rows ending with one whose name is null, whose
+/// names and contents stay valid for the rest of the program.
+#[no_mangle]
+pub unsafe extern "C" fn php_embed_register(table: *const PhpEmbeddedFile) {
+    let mut files = FILES.lock().unwrap();
+    let mut row = table;
+    while !(*row).name.is_null() {
+        let info = &*row;
+        let name = CStr::from_ptr(info.name).to_string_lossy().into_owned();
+        if !files.iter().any(|(n, _, _)| *n == name) {
+            files.push((name, info.data as usize, info.len as usize));
+        }
+        row = row.add(1);
+    }
+}
+
+/// `embedded_file($name)`: a new string with the contents, or `false`
+///
+/// # Safety
+///
+/// `name` must be null or point to a live string.
+#[no_mangle]
+pub unsafe extern "C" fn php_embedded_file(name: *const PhpString) -> PhpMixed {
+    let bytes = name.as_ref().map_or(&[][..], |s| s.as_bytes());
+    let name = String::from_utf8_lossy(bytes);
+    let files = FILES.lock().unwrap();
+    match files.iter().find(|(n, _, _)| *n == name) {
+        Some(&(_, data, len)) => {
+            let contents = std::slice::from_raw_parts(data as *const u8, len);
+            PhpMixed::string(PhpString::new(contents.to_vec()))
+        }
+        None => PhpMixed::bool(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mixed::TAG_STRING;
+    use crate::strings::php_string_release;
+
+    #[test]
+    fn test_embedded_file() {
+        let contents = b"{\"debug\": false}\0ok";
+        let table = [
+            PhpEmbeddedFile { name: b"test/config.json\0".as_ptr() as *const c_char, data: contents.as_ptr(), len: contents.len() as i64 },
+            PhpEmbeddedFile { name: std::ptr::null(), data: std::ptr::null(), len: 0 },
+        ];
+        unsafe {
+            php_embed_register(table.as_ptr());
+
+            let name = PhpString::new(b"test/config.json".to_vec());
+            let file = php_embedded_file(name);
+            assert_eq!(file.tag, TAG_STRING);
+            let string = file.payload as *mut PhpString;
+            assert_eq!((*string).as_bytes(), contents);
+            php_string_release(string);
+            php_string_release(name);
+
+            let missing = PhpString::new(b"test/missing.json".to_vec());
+            assert_eq!(php_embedded_file(missing), PhpMixed::bool(false));
+            php_string_release(missing);
+        }
+    }
+}
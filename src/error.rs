@@ -15,7 +15,7 @@
  */
 
 use std::fmt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 /// Main error type for the php2ir compiler
@@ -68,6 +68,21 @@ pub enum CompileError {
     /// Internal compiler error
     #[error("Internal compiler error: {0}")]
     Internal(String),
+
+    /// The compilation was cancelled through its session
+    #[error("Compilation cancelled")]
+    Cancelled,
+}
+
+impl CompileError {
+    /// Source file the error points into, if it is known
+    pub fn file(&self) -> Option<&Path> {
+        match self {
+            CompileError::Parse { file, .. } => file.as_deref(),
+            CompileError::Type { location, .. } => location.as_ref().map(|location| location.file.as_path()),
+            _ => None,
+        }
+    }
 }
 
 /// Source location information
@@ -0,0 +1,431 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Exceptions of compiled code.
+//!
+//! `throw` passes the object to [`php_throw`], which wraps it in an Itanium
+//! ABI exception and starts a forced unwind. Generated functions use the C
+//! personality, which runs every landing pad on the way: a function's pad
+//! releases its variables and resumes unwinding, and a `try` block's pad
+//! tests the object against its catch types and either takes it with
+//! [`php_exception_catch`] or resumes. An exception that reaches the end of
+//! the stack is reported as uncaught, with the PHP stack trace captured
+//! when it was thrown, and ends the program.
+//!
+//! The runtime provides the `Throwable` interface and the standard
+//! exception classes. Their objects, and those of compiled subclasses,
+//! start with a [`PhpException`].
+
+use std::ffi::c_void;
+use std::os::raw::c_char;
+use crate::backtrace::{self, Frame};
+use crate::objects::{php_object_addref, php_object_new, php_object_release, PhpClass, PhpInterface, PhpItable, PhpObject};
+use crate::runtime::{self, RuntimeError, RuntimeErrorType};
+use crate::strings::{php_string_addref, php_string_new, php_string_release, PhpString};
+
+/// Fields of a throwable object, after the object header
+#[repr(C)]
+#[derive(Debug)]
+pub struct PhpException {
+    header: PhpObject,
+    message: *mut PhpString,
+    code: isize,
+    previous: *mut PhpObject,
+}
+
+impl PhpException {
+    /// Message given to the constructor
+    pub fn message(&self) -> String {
+        match unsafe { self.message.as_ref() } {
+            Some(message) => String::from_utf8_lossy(message.as_bytes()).into_owned(),
+            None => String::new(),
+        }
+    }
+
+    /// Code given to the constructor
+    pub fn code(&self) -> isize {
+        self.code
+    }
+}
+
+/// Method table for an array of functions of different types
+#[repr(transparent)]
+struct MethodTable<const N: usize>([*const c_void; N]);
+
+unsafe impl<const N: usize> Sync for MethodTable<N> {}
+
+/// The `Throwable` interface, implemented by every exception class
+#[export_name = "php.interface.Throwable"]
+pub static THROWABLE: PhpInterface = PhpInterface { name: c"Throwable".as_ptr() };
+
+/// `Throwable` methods in interface order: `getMessage`, `getCode`, `getPrevious`
+static THROWABLE_METHODS: MethodTable<3> = MethodTable([
+    php_throwable_get_message as *const c_void,
+    php_throwable_get_code as *const c_void,
+    php_throwable_get_previous as *const c_void,
+]);
+
+static THROWABLE_ITABLES: [PhpItable; 2] = [
+    PhpItable { interface: &THROWABLE, methods: THROWABLE_METHODS.0.as_ptr() },
+    PhpItable { interface: std::ptr::null(), methods: std::ptr::null() },
+];
+
+macro_rules! exception_classes {
+    ($($class:ident $name:literal $(extends $parent:ident)?;)*) => {
+        $(
+            #[export_name = concat!("php.class.", $name)]
+            pub static $class: PhpClass = PhpClass {
+                name: concat!($name, "\0").as_ptr() as *const c_char,
+                size: std::mem::size_of::<PhpException>() as u64,
+                parent: exception_classes!(@parent $($parent)?),
+                free: Some(php_exception_free),
+                clone: None,
+                vtable: std::ptr::null(),
+                itables: THROWABLE_ITABLES.as_ptr(),
+                properties: std::ptr::null(),
+                methods: std::ptr::null(),
+            };
+        )*
+
+        /// The runtime's exception classes, each after its parent
+        pub static EXCEPTION_CLASSES: &[&PhpClass] = &[$(&$class),*];
+    };
+    (@parent) => { std::ptr::null() };
+    (@parent $parent:ident) => { &$parent };
+}
+
+exception_classes! {
+    EXCEPTION "Exception";
+    ERROR_EXCEPTION "ErrorException" extends EXCEPTION;
+    LOGIC_EXCEPTION "LogicException" extends EXCEPTION;
+    BAD_FUNCTION_CALL_EXCEPTION "BadFunctionCallException" extends LOGIC_EXCEPTION;
+    BAD_METHOD_CALL_EXCEPTION "BadMethodCallException" extends BAD_FUNCTION_CALL_EXCEPTION;
+    DOMAIN_EXCEPTION "DomainException" extends LOGIC_EXCEPTION;
+    INVALID_ARGUMENT_EXCEPTION "InvalidArgumentException" extends LOGIC_EXCEPTION;
+    LENGTH_EXCEPTION "LengthException" extends LOGIC_EXCEPTION;
+    OUT_OF_RANGE_EXCEPTION "OutOfRangeException" extends LOGIC_EXCEPTION;
+    RUNTIME_EXCEPTION "RuntimeException" extends EXCEPTION;
+    OUT_OF_BOUNDS_EXCEPTION "OutOfBoundsException" extends RUNTIME_EXCEPTION;
+    OVERFLOW_EXCEPTION "OverflowException" extends RUNTIME_EXCEPTION;
+    RANGE_EXCEPTION "RangeException" extends RUNTIME_EXCEPTION;
+    UNDERFLOW_EXCEPTION "UnderflowException" extends RUNTIME_EXCEPTION;
+    UNEXPECTED_VALUE_EXCEPTION "UnexpectedValueException" extends RUNTIME_EXCEPTION;
+    ERROR "Error";
+    TYPE_ERROR "TypeError" extends ERROR;
+    ARGUMENT_COUNT_ERROR "ArgumentCountError" extends TYPE_ERROR;
+    VALUE_ERROR "ValueError" extends ERROR;
+    ARITHMETIC_ERROR "ArithmeticError" extends ERROR;
+    DIVISION_BY_ZERO_ERROR "DivisionByZeroError" extends ARITHMETIC_ERROR;
+    UNHANDLED_MATCH_ERROR "UnhandledMatchError" extends ERROR;
+}
+
+// The system unwinder (libgcc_s or libunwind)
+
+/// `_Unwind_Exception`, the header the unwinder knows an exception by
+#[repr(C, align(16))]
+struct UnwindException {
+    class: u64,
+    cleanup: Option<unsafe extern "C" fn(i32, *mut UnwindException)>,
+    private: [usize; 2],
+}
+
+type StopFunction = unsafe extern "C" fn(i32, i32, u64, *mut UnwindException, *mut c_void, *mut c_void) -> i32;
+
+extern "C-unwind" {
+    fn _Unwind_ForcedUnwind(exception: *mut UnwindException, stop: StopFunction, parameter: *mut c_void) -> i32;
+}
+
+extern "C" {
+    fn _Unwind_DeleteException(exception: *mut UnwindException);
+}
+
+const URC_NO_REASON: i32 = 0;
+const UA_END_OF_STACK: i32 = 16;
+
+/// `PHP\0IR\0\0`, the vendor and language of our exceptions
+const EXCEPTION_CLASS: u64 = u64::from_be_bytes(*b"PHP\0IR\0\0");
+
+/// An exception in flight: the unwinder's header, the thrown object and
+/// the PHP frames it was thrown from
+#[repr(C)]
+struct Thrown {
+    header: UnwindException,
+    object: *mut PhpObject,
+    trace: Vec<Frame>,
+}
+
+/// Called by the unwinder for every frame of a forced unwind
+unsafe extern "C" fn stop(
+    _version: i32,
+    actions: i32,
+    _class: u64,
+    exception: *mut UnwindException,
+    _context: *mut c_void,
+    _parameter: *mut c_void,
+) -> i32 {
+    if actions & UA_END_OF_STACK != 0 {
+        uncaught(&*(exception as *mut Thrown));
+    }
+    URC_NO_REASON
+}
+
+/// Free an exception that was not caught, with the object it holds
+unsafe extern "C" fn cleanup(_reason: i32, exception: *mut UnwindException) {
+    let thrown = Box::from_raw(exception as *mut Thrown);
+    php_object_release(thrown.object);
+}
+
+/// Report an exception nothing caught, with its stack trace, and end the program
+unsafe fn uncaught(thrown: &Thrown) -> ! {
+    let exception = &*(thrown.object as *const PhpException);
+    let class = exception.header.class().name();
+    let mut report = match exception.message().as_str() {
+        "" => format!("Uncaught {}", class),
+        message => format!("Uncaught {}: {}", class, message),
+    };
+    if let Some(site) = thrown.trace.first() {
+        let thrown_in = match site.line {
+            0 => site.file.clone(),
+            line => format!("{} on line {}", site.file, line),
+        };
+        report.push_str(&format!(
+            " in {}\nStack trace:\n{}\n  thrown in {}",
+            site.location(), backtrace::format_trace(&thrown.trace), thrown_in
+        ));
+    }
+    fatal(&report)
+}
+
+/// Throw a new exception of one of the runtime's classes from runtime code
+///
+/// # Safety
+///
+/// Every frame between the caller and the matching `try` must have unwind
+/// information.
+pub(crate) unsafe fn throw_new(class: &'static PhpClass, message: &str) -> ! {
+    let object = php_object_new(class);
+    let message = php_string_new(message.as_ptr() as *const c_char, message.len() as i64);
+    php_exception_construct(object, message, 0, std::ptr::null_mut());
+    php_string_release(message);
+    php_throw(object)
+}
+
+/// Raise an error reported by the interpreter from runtime code: as an
+/// exception of the matching class, by ending the program for `exit`, or
+/// as a fatal error
+///
+/// # Safety
+///
+/// See [`throw_new`].
+pub(crate) unsafe fn raise(error: &RuntimeError) -> ! {
+    let class = match error.error_type {
+        RuntimeErrorType::TypeError => &TYPE_ERROR,
+        RuntimeErrorType::DivisionByZero => &DIVISION_BY_ZERO_ERROR,
+        RuntimeErrorType::Arithmetic => &ARITHMETIC_ERROR,
+        RuntimeErrorType::InvalidOperation | RuntimeErrorType::UndefinedFunction => &ERROR,
+        RuntimeErrorType::Exit => runtime::php_exit(error.code),
+        _ => fatal(&error.message),
+    };
+    throw_new(class, &error.message)
+}
+
+fn fatal(message: &str) -> ! {
+    let _ = runtime::flush_output();
+    eprintln!("PHP Fatal error:  {}", message);
+    std::process::exit(255);
+}
+
+// FFI functions called by generated code
+
+/// Throw an object, taking its reference
+///
+/// # Safety
+///
+/// `o` must be null or a live object, and every frame between the caller
+/// and the matching `try` must have unwind information.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_throw(o: *mut PhpObject) -> ! {
+    let Some(object) = o.as_ref() else {
+        fatal("Uncaught Error: Can only throw objects");
+    };
+    if object.class().itable(&THROWABLE).is_none() {
+        fatal("Uncaught Error: Cannot throw objects that do not implement Throwable");
+    }
+    let thrown = Box::into_raw(Box::new(Thrown {
+        header: UnwindException { class: EXCEPTION_CLASS, cleanup: Some(cleanup), private: [0; 2] },
+        object: o,
+        trace: backtrace::capture(),
+    }));
+    _Unwind_ForcedUnwind(thrown as *mut UnwindException, stop, std::ptr::null_mut());
+    // Unwinding could not start
+    uncaught(&*thrown)
+}
+
+/// Throw the `DivisionByZeroError` of `/` and `intdiv()` or, when
+/// `modulo` is set, of `%`
+///
+/// # Safety
+///
+/// See [`throw_new`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_throw_division_by_zero(modulo: bool) -> ! {
+    throw_new(&DIVISION_BY_ZERO_ERROR, if modulo { "Modulo by zero" } else { "Division by zero" })
+}
+
+/// Throw the `ArithmeticError` of `intdiv(PHP_INT_MIN, -1)`
+///
+/// # Safety
+///
+/// See [`throw_new`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_throw_intdiv_overflow() -> ! {
+    throw_new(&ARITHMETIC_ERROR, "Division of PHP_INT_MIN by -1 is not an integer")
+}
+
+/// Thrown object of an exception in flight, borrowed, or null for an
+/// unwind that is not a PHP exception, such as an intercepted `exit()`
+///
+/// # Safety
+///
+/// `exception` must be the exception pointer of a landing pad.
+#[no_mangle]
+pub unsafe extern "C" fn php_exception_object(exception: *mut c_void) -> *mut PhpObject {
+    let thrown = exception as *mut Thrown;
+    if (*thrown).header.class != EXCEPTION_CLASS {
+        return std::ptr::null_mut();
+    }
+    (*thrown).object
+}
+
+/// Stop an exception in a `catch` block, returning the reference to its object
+///
+/// # Safety
+///
+/// `exception` must be the exception pointer of a landing pad, and the
+/// exception must not be resumed afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn php_exception_catch(exception: *mut c_void) -> *mut PhpObject {
+    let thrown = exception as *mut Thrown;
+    let object = std::mem::replace(&mut (*thrown).object, std::ptr::null_mut());
+    _Unwind_DeleteException(exception as *mut UnwindException);
+    object
+}
+
+/// `Exception::__construct()` and `Error::__construct()`
+///
+/// Returns null, like the constructors of compiled classes, which have no
+/// declared return type.
+///
+/// # Safety
+///
+/// `o` must be a live throwable object; `message` and `previous` may be null.
+#[no_mangle]
+pub unsafe extern "C" fn php_exception_construct(
+    o: *mut PhpObject,
+    message: *mut PhpString,
+    code: isize,
+    previous: *mut PhpObject,
+) -> *mut c_void {
+    let exception = &mut *(o as *mut PhpException);
+    php_string_addref(message);
+    php_string_release(std::mem::replace(&mut exception.message, message));
+    exception.code = code;
+    php_object_addref(previous);
+    php_object_release(std::mem::replace(&mut exception.previous, previous));
+    std::ptr::null_mut()
+}
+
+/// Release the fields of a throwable object before it is freed
+///
+/// # Safety
+///
+/// `o` must be a throwable object whose last reference is being released.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_exception_free(o: *mut PhpObject) {
+    let exception = &mut *(o as *mut PhpException);
+    php_string_release(std::mem::replace(&mut exception.message, std::ptr::null_mut()));
+    php_object_release(std::mem::replace(&mut exception.previous, std::ptr::null_mut()));
+}
+
+/// `Throwable::getMessage()`
+///
+/// # Safety
+///
+/// `o` must be a live throwable object.
+#[no_mangle]
+pub unsafe extern "C" fn php_throwable_get_message(o: *mut PhpObject) -> *mut PhpString {
+    let message = (*(o as *mut PhpException)).message;
+    if message.is_null() {
+        return php_string_new(std::ptr::null(), 0);
+    }
+    php_string_addref(message);
+    message
+}
+
+/// `Throwable::getCode()`
+///
+/// # Safety
+///
+/// `o` must be a live throwable object.
+#[no_mangle]
+pub unsafe extern "C" fn php_throwable_get_code(o: *mut PhpObject) -> isize {
+    (*(o as *mut PhpException)).code
+}
+
+/// `Throwable::getPrevious()`
+///
+/// # Safety
+///
+/// `o` must be a live throwable object.
+#[no_mangle]
+pub unsafe extern "C" fn php_throwable_get_previous(o: *mut PhpObject) -> *mut PhpObject {
+    let previous = (*(o as *mut PhpException)).previous;
+    php_object_addref(previous);
+    previous
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{php_object_implements, php_object_instanceof};
+
+    #[test]
+    fn test_exception_objects() {
+        unsafe {
+            let previous = php_object_new(&RUNTIME_EXCEPTION);
+            let error = php_object_new(&DIVISION_BY_ZERO_ERROR);
+            let message = php_string_new(c"Division by zero".as_ptr(), 16);
+            php_exception_construct(error, message, 7, previous);
+            php_string_release(message);
+            php_object_release(previous);
+
+            assert!(php_object_instanceof(error, &ERROR));
+            assert!(!php_object_instanceof(error, &EXCEPTION));
+            assert!(php_object_implements(error, &THROWABLE));
+            assert_eq!((*(error as *mut PhpException)).message(), "Division by zero");
+            assert_eq!(php_throwable_get_code(error), 7);
+
+            let previous = php_throwable_get_previous(error);
+            assert!(php_object_instanceof(previous, &EXCEPTION));
+            assert_eq!((*previous).refcount(), 2);
+            let empty = php_throwable_get_message(previous);
+            assert!((*empty).as_bytes().is_empty());
+            php_string_release(empty);
+            php_object_release(previous);
+            php_object_release(error);
+        }
+    }
+}
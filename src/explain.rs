@@ -0,0 +1,417 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Extended explanations of diagnostic codes, shown by `php2ir --explain CODE`.
+
+use std::fmt;
+use crate::diagnostics::codes;
+
+/// What a diagnostic code means and how to fix what it reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Explanation {
+    pub code: &'static str,
+
+    /// One-line summary
+    pub title: &'static str,
+
+    /// Causes, an example and fixes
+    pub text: &'static str,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}", self.code, self.title)?;
+        writeln!(f)?;
+        write!(f, "{}", self.text)
+    }
+}
+
+/// Explanation of `code`, given in any case
+pub fn explain(code: &str) -> Option<&'static Explanation> {
+    EXPLANATIONS.iter().find(|explanation| explanation.code.eq_ignore_ascii_case(code.trim()))
+}
+
+/// Every explanation, in the order of [`codes::ALL`]
+pub const EXPLANATIONS: &[Explanation] = &[
+    Explanation {
+        code: codes::PARSE,
+        title: "the source cannot be parsed, or an include cannot be resolved",
+        text: "\
+The file is not valid PHP at the reported position, or an `include`/`require`
+with a constant path could not be spliced in: a required file is missing or
+files include each other in a cycle.
+
+To fix it:
+- correct the syntax at the reported line and column; `php -l file.php`
+  reports the same errors
+- check the path of the required file, relative to the including file or to
+  an `-I` include directory
+- break include cycles with `require_once`
+",
+    },
+    Explanation {
+        code: codes::TYPE,
+        title: "a type error stopped the compilation",
+        text: "\
+An error that PHP would report when the code is loaded, such as a function or
+class declared twice unconditionally, or an attribute given the wrong
+arguments.
+
+    function helper() {}
+    function helper() {}   // Cannot redeclare function helper
+
+To fix it:
+- rename or remove one of the declarations, or guard it with
+  `if (!function_exists('helper'))`
+- follow the message for attributes, e.g. `#[Hot]` takes no arguments
+",
+    },
+    Explanation {
+        code: codes::UNDEFINED_VARIABLE,
+        title: "a variable may be read before it is assigned",
+        text: "\
+Some path through the function reads the variable before any assignment.
+PHP warns at run time and uses `null`.
+
+    function total(array $items) {
+        foreach ($items as $item) {
+            $sum += $item;   // $sum is undefined on the first iteration
+        }
+        return $sum;
+    }
+
+To fix it:
+- initialize the variable before the branch or loop: `$sum = 0;`
+- check a possibly unset variable with `isset()` or `??`
+",
+    },
+    Explanation {
+        code: codes::CONDITIONAL_REDECLARATION,
+        title: "a function or class may be declared twice",
+        text: "\
+A declaration inside a branch has the same name as another one, so whether the
+program fails with \"Cannot redeclare\" depends on which branches run.
+
+    if (PHP_OS_FAMILY === 'Windows') {
+        function path_sep() { return '\\\\'; }
+    }
+    function path_sep() { return '/'; }
+
+To fix it:
+- guard the fallback with `if (!function_exists('path_sep'))` or
+  `if (!class_exists(...))`
+- give the declarations different names and choose one at run time
+",
+    },
+    Explanation {
+        code: codes::UNREACHABLE_CODE,
+        title: "code can never run",
+        text: "\
+The code follows a `return`, `throw`, `exit`, `break` or `continue`, or sits in
+a branch whose condition is constant. It is removed from the compiled program.
+
+    return $result;
+    log_result($result);   // never runs
+
+To fix it:
+- move the code before the statement that leaves the block
+- remove it if it is dead, or make the condition depend on run-time values
+- a branch decided by a `-D` define is folded away silently
+",
+    },
+    Explanation {
+        code: codes::ARGUMENT_TYPE,
+        title: "an argument is rejected by the parameter's type",
+        text: "\
+A literal or a `new` object passed to a function can never be accepted by the
+declared parameter type, so the call would throw a `TypeError`. Scalars are
+coerced unless the file declares `strict_types=1`.
+
+    function repeat(string $s, int $times) {}
+    repeat('ab', 'three');   // Argument #2 ($times) must be of type int
+
+To fix it:
+- pass a value of the declared type, or convert it explicitly: `(int) $x`
+- widen the parameter type, e.g. to a union `int|string`
+- for objects, pass an instance of a class that extends or implements the
+  declared class or interface
+",
+    },
+    Explanation {
+        code: codes::UNHANDLED_MATCH,
+        title: "a `match` does not handle every value of its subject",
+        text: "\
+The `match` has no `default` arm and some value the subject can take matches
+no arm: an enum case, `true`/`false`, or a literal of a union. That value
+throws `UnhandledMatchError` at run time.
+
+    enum Suit { case Hearts; case Spades; }
+    $color = match ($suit) {
+        Suit::Hearts => 'red',
+    };   // Suit::Spades is unhandled
+
+To fix it:
+- add an arm for each missing case listed in the diagnostic
+- add a `default` arm when the remaining values share a result
+",
+    },
+    Explanation {
+        code: codes::ARRAY_KEY,
+        title: "duplicate, lossy or illegal array key",
+        text: "\
+An array literal repeats a key, so the later element silently replaces the
+earlier one; a float key loses its fraction when it is converted to int; or
+a key has a type PHP cannot use as a key (arrays, objects, enum cases).
+
+    $limits = ['min' => 1, 'max' => 10, 'min' => 0];   // 'min' is duplicated
+    $table = [1.5 => 'a'];                             // the key becomes 1
+
+To fix it:
+- remove or rename the duplicated key
+- use an integer or string key, e.g. `(string) $x` for a float
+- key by an enum's value (`$case->value`) or an object's id
+",
+    },
+    Explanation {
+        code: codes::FORMAT_STRING,
+        title: "a format string does not fit its arguments",
+        text: "\
+A `printf`, `sprintf`, `fprintf` or `vsprintf` format is invalid, needs more
+arguments than are passed, or formats a non-numeric string with a numeric
+conversion (which prints 0).
+
+    printf('%s is %d years old', $name);   // 3 arguments are required, 2 given
+
+To fix it:
+- pass one argument per conversion, or use positional conversions like `%1$s`
+- escape a literal percent sign as `%%`
+- use `%s` for strings
+",
+    },
+    Explanation {
+        code: codes::CALLABLE_CALL,
+        title: "a call through a variable does not fit its callable",
+        text: "\
+The variable holds a closure or function name whose signature is known, and
+the call passes too few arguments; or the variable holds a value that is not
+callable at all, such as an int or an array.
+
+    $add = fn(int $a, int $b) => $a + $b;
+    $add(1);   // Too few arguments to function {closure}(), 1 passed
+
+To fix it:
+- pass every required argument, or give the parameter a default value
+- check what the variable holds; `is_callable()` guards dynamic values
+",
+    },
+    Explanation {
+        code: codes::METHOD_SIGNATURE,
+        title: "an overriding method is incompatible with its parent",
+        text: "\
+A method that overrides a parent class or interface method must accept every
+call the parent accepts: parameter types may only widen, return types may
+only narrow, and it may not require more parameters (PHP 7.4 variance).
+
+    class A { public function f(): int {} }
+    class B extends A { public function f(): string {} }
+
+To fix it:
+- make the return type the parent's or a subtype of it
+- make each parameter type the parent's or a supertype of it
+- give added parameters default values
+",
+    },
+    Explanation {
+        code: codes::PROPERTY_WRITE,
+        title: "a property write breaks its type or readonly",
+        text: "\
+A value assigned to a typed property, or its default value, is not of the
+declared type; or a `readonly` property is written from outside its class or
+after it was initialized.
+
+    class Point {
+        public function __construct(public readonly int $x) {}
+    }
+    $p = new Point(1);
+    $p->x = 2;   // Cannot modify readonly property Point::$x
+
+To fix it:
+- assign a value of the declared type, or change the type
+- initialize readonly properties once, in the constructor
+- create a modified copy (a `with*` method) instead of writing to it
+",
+    },
+    Explanation {
+        code: codes::CLASS_SCOPE_TYPE,
+        title: "`self`, `parent` or `static` cannot refer to a class here",
+        text: "\
+`self` and `static` are only meaningful inside a class, `parent` only in a
+class that extends another, and `static` only as a return type.
+
+    function make(): self {}                 // not in a class
+    class A { public function f(parent $p) {} }   // A has no parent
+
+To fix it:
+- name the class explicitly outside of classes
+- add the `extends` clause or use the parent's name
+- use `self` instead of `static` for parameter and property types
+",
+    },
+    Explanation {
+        code: codes::DYNAMIC_FALLBACK,
+        title: "code falls back to boxed values or dynamic dispatch",
+        text: "\
+Reported with `--report-dynamic`. The compiler cannot infer a precise type
+here, so it uses boxed values or run-time dispatch, which is slower than
+the native code it generates for typed code.
+
+To fix it:
+- declare parameter, return and property types
+- add `@var`, `@param` and `@return` docblocks where types cannot be declared
+- avoid mixing types in one variable
+",
+    },
+    Explanation {
+        code: codes::IR_GENERATION,
+        title: "the backend cannot generate IR for the program",
+        text: "\
+Lowering the checked program to LLVM IR (or Cranelift) failed, usually on a
+construct the backend does not handle in this position.
+
+To fix it:
+- rewrite the reported construct in a simpler form
+- try `--backend llvm` if another backend is selected
+- report the program if the message points to a compiler limitation
+",
+    },
+    Explanation {
+        code: codes::CODE_GENERATION,
+        title: "LLVM rejected the IR or failed to generate code",
+        text: "\
+The generated IR did not verify, or `llc`/LLVM failed to optimize it or emit
+code for the target.
+
+To fix it:
+- check that the LLVM tools are installed and match the supported version
+- check the `--target` triple and `--passes` pipeline
+- `--save-ir` keeps the IR for a bug report
+",
+    },
+    Explanation {
+        code: codes::LINKING,
+        title: "the linker failed",
+        text: "\
+The linker's own output follows the message. Common causes are a missing
+runtime library, a linker that cannot produce the target's format and
+undefined symbols from `extern` functions.
+
+To fix it:
+- pass the runtime built for the target with `--stdlib`, or use `--no-rt`
+- pick a linker for the target with `--linker`, and `--sysroot` when
+  cross-compiling
+- link libraries that provide undefined symbols
+",
+    },
+    Explanation {
+        code: codes::RUNTIME,
+        title: "the interpreted program failed",
+        text: "\
+With `--interpret`, the program threw an uncaught exception or hit a fatal
+error. The message is the one PHP would print.
+
+To fix it:
+- fix the program, or catch the exception
+- compare with `php file.php` to rule out interpreter differences
+",
+    },
+    Explanation {
+        code: codes::CONFIGURATION,
+        title: "invalid options, manifest or toolchain",
+        text: "\
+An option value, a `php2ir.toml` key or the toolchain is unusable: an unknown
+target, a linker that is not installed, or incompatible options.
+
+To fix it:
+- follow the message; `php2ir --help` lists the accepted values
+- check `php2ir.toml`, whose values apply to options left at their default
+- install the missing tool or point to it with its option
+",
+    },
+    Explanation {
+        code: codes::UNSUPPORTED,
+        title: "the program uses a feature the compiler does not support",
+        text: "\
+The construct is valid PHP but cannot be compiled yet.
+
+To fix it:
+- rewrite the code without the feature
+- without `--no-interpreter-fallback`, functions code generation cannot
+  handle are run by the embedded interpreter instead
+",
+    },
+    Explanation {
+        code: codes::IO,
+        title: "a file cannot be read or written",
+        text: "\
+An input file does not exist or is unreadable, or an output or intermediate
+file cannot be created.
+
+To fix it:
+- check the path and permissions of the file in the message
+- check that the output and `--build-dir` directories are writable
+",
+    },
+    Explanation {
+        code: codes::INTERNAL,
+        title: "a bug in the compiler",
+        text: "\
+The compiler reached a state it should not. This is never caused by the
+program being wrong.
+
+To fix it:
+- report it with the smallest program that shows it, and the output of
+  `php2ir --verbose`
+",
+    },
+    Explanation {
+        code: codes::CANCELLED,
+        title: "the compilation was cancelled",
+        text: "\
+The build server or editor driving the compilation cancelled it. The
+compilation stops at the end of the phase that was running, and leaves no
+output behind; intermediate files are removed as usual.
+
+To fix it:
+- nothing is wrong with the program; start the compilation again
+",
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_code_explained() {
+        let explained: Vec<&str> = EXPLANATIONS.iter().map(|explanation| explanation.code).collect();
+        assert_eq!(explained, codes::ALL);
+
+        let explanation = explain("t0304").unwrap();
+        assert_eq!(explanation.code, "T0304");
+        assert!(explanation.to_string().starts_with("T0304: an argument is rejected by the parameter's type\n\n"));
+        assert!(explain("X9999").is_none());
+    }
+}
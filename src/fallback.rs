@@ -0,0 +1,336 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Dynamic fallback reporting.
+//!
+//! Code generation handles what it cannot type with boxed values and
+//! dispatch at run time. With reporting enabled, every such place is
+//! reported with the reason, so code can be annotated step by step:
+//!
+//! - parameters, return values and properties without a declared type
+//! - functions using constructs code generation does not support yet, which
+//!   run in the interpreter
+//! - method calls on a receiver of unknown type and calls through values
+//!   not known to hold a named function
+
+use std::collections::HashSet;
+use std::str::FromStr;
+use crate::ast::{AstIndex, AstNode, Expression, FunctionDecl, NodeId, NodeRef, Span, Statement};
+use crate::diagnostics::{codes, Diagnostic};
+use crate::literals::LiteralChecker;
+use crate::types::IntWidth;
+
+/// How dynamic fallbacks are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FallbackReport {
+    /// Report them as warnings
+    Warn,
+
+    /// Report them as errors
+    Deny,
+}
+
+impl FromStr for FallbackReport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(FallbackReport::Warn),
+            "deny" => Ok(FallbackReport::Deny),
+            _ => Err(format!("unknown report mode '{}' (expected: warn, deny)", s)),
+        }
+    }
+}
+
+/// Reports places that fall back to boxed values or dynamic dispatch
+pub struct FallbackReporter<'i> {
+    file: String,
+    report: FallbackReport,
+    int_width: IntWidth,
+    index: Option<&'i AstIndex<'i>>,
+    stubs: &'i [AstNode],
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'i> FallbackReporter<'i> {
+    pub fn new(file: impl Into<String>, report: FallbackReport) -> Self {
+        Self {
+            file: file.into(),
+            report,
+            int_width: IntWidth::default(),
+            index: None,
+            stubs: &[],
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Set the width of PHP `int` used when inferring receivers and callees
+    pub fn with_int_width(mut self, int_width: IntWidth) -> Self {
+        self.int_width = int_width;
+        self
+    }
+
+    /// Take spans from an index of the checked program
+    pub fn with_index(mut self, index: &'i AstIndex<'i>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Resolve calls into functions and classes declared by stub files
+    pub fn with_stubs(mut self, stubs: &'i [AstNode]) -> Self {
+        self.stubs = stubs;
+        self
+    }
+
+    /// Check a program
+    pub fn check(&mut self, ast: &[AstNode]) {
+        let mut literals = LiteralChecker::new(self.file.clone())
+            .with_int_width(self.int_width)
+            .with_stubs(self.stubs);
+        literals.check(ast);
+        let dynamic_calls = literals.take_dynamic_calls();
+
+        // Nodes are numbered in the same order as in `self.index`
+        let index = AstIndex::build(ast);
+        let returning = returning_functions(&index);
+        for id in index.ids() {
+            match index.node(id) {
+                Some(NodeRef::Function(decl)) => self.function(&index, id, decl, &returning),
+                Some(NodeRef::Node(AstNode::Class(class))) => {
+                    for property in class.properties.iter().filter(|p| p.typ.is_none()) {
+                        self.report(
+                            id,
+                            format!("Property {}::${} has no type", class.name, property.name),
+                            "it is stored as a boxed value",
+                        );
+                    }
+                }
+                Some(NodeRef::Expression(expr)) => {
+                    if let Some(reason) = dynamic_calls.get(&id) {
+                        let message = match (expr, owner(&index, id)) {
+                            (Expression::MethodCall { method, .. }, Some(owner)) => {
+                                format!("Call of ->{}() in {} is dispatched at run time", method, owner)
+                            }
+                            (Expression::MethodCall { method, .. }, None) => {
+                                format!("Call of ->{}() is dispatched at run time", method)
+                            }
+                            (_, Some(owner)) => format!("Call in {} is resolved by name at run time", owner),
+                            (_, None) => "Call is resolved by name at run time".to_string(),
+                        };
+                        self.report(id, message, reason.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Take the diagnostics reported so far
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn function(&mut self, index: &AstIndex, id: NodeId, decl: &FunctionDecl, returning: &HashSet<NodeId>) {
+        let name = display_name(index, id, decl);
+        for parameter in decl.parameters.iter().filter(|p| p.typ.is_none()) {
+            self.report(
+                id,
+                format!("Parameter ${} of {} has no type", parameter.name, name),
+                "it is passed as a boxed value and operations on it are dispatched at run time",
+            );
+        }
+        if decl.return_type.is_none() && returning.contains(&id) {
+            self.report(id, format!("{} has no return type", name), "its result is returned as a boxed value");
+        }
+        // Only top-level functions are compiled so far
+        let top_level = index.parent(id)
+            .and_then(|parent| index.node(parent))
+            .is_some_and(|parent| matches!(parent, NodeRef::Node(AstNode::Function(_))));
+        if top_level && !crate::ir::is_compiled_statement(&decl.body, self.int_width) {
+            self.report(
+                id,
+                format!("{} uses constructs code generation does not support", name),
+                "it runs in the interpreter",
+            );
+        }
+    }
+
+    fn span(&self, id: NodeId) -> Option<Span> {
+        self.index?.span(id)
+    }
+
+    fn report(&mut self, id: NodeId, message: String, reason: impl Into<String>) {
+        let diagnostic = match self.report {
+            FallbackReport::Warn => Diagnostic::warning(codes::DYNAMIC_FALLBACK, message),
+            FallbackReport::Deny => Diagnostic::error(codes::DYNAMIC_FALLBACK, message),
+        };
+        self.diagnostics.push(diagnostic.with_file(self.file.clone()).with_span(self.span(id)).with_note(reason));
+    }
+}
+
+/// Functions with a `return` of a value, by id
+fn returning_functions(index: &AstIndex) -> HashSet<NodeId> {
+    let mut functions = HashSet::new();
+    for id in index.ids() {
+        if !matches!(index.node(id), Some(NodeRef::Statement(Statement::Return(Some(_))))) {
+            continue;
+        }
+        // The nearest function, unless a closure is nearer
+        let enclosing = index.ancestors(id).into_iter().find_map(|ancestor| match index.node(ancestor) {
+            Some(NodeRef::Function(_)) => Some(Some(ancestor)),
+            Some(NodeRef::Expression(Expression::Closure(_))) => Some(None),
+            _ => None,
+        });
+        if let Some(Some(function)) = enclosing {
+            functions.insert(function);
+        }
+    }
+    functions
+}
+
+/// `f()` or `Class::m()` for the function or method at `id`
+fn display_name(index: &AstIndex, id: NodeId, decl: &FunctionDecl) -> String {
+    let class = index.parent(id).and_then(|parent| match index.node(parent) {
+        Some(NodeRef::Node(AstNode::Class(class))) => Some(class.name.as_str()),
+        Some(NodeRef::Node(AstNode::Interface(interface))) => Some(interface.name.as_str()),
+        Some(NodeRef::Node(AstNode::Trait(trait_decl))) => Some(trait_decl.name.as_str()),
+        Some(NodeRef::Node(AstNode::Enum(enum_decl))) => Some(enum_decl.name.as_str()),
+        _ => None,
+    });
+    match class {
+        Some(class) => format!("{}::{}()", class, decl.name),
+        None => format!("{}()", decl.name),
+    }
+}
+
+/// Display name of the function enclosing the node at `id`
+fn owner(index: &AstIndex, id: NodeId) -> Option<String> {
+    index.ancestors(id).into_iter().find_map(|ancestor| match index.node(ancestor) {
+        Some(NodeRef::Function(decl)) => Some(display_name(index, ancestor, decl)),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{ClassDecl, Parameter, PropertyDecl, Visibility};
+    use crate::diagnostics::Severity;
+    use crate::types::Type;
+
+    fn function(name: &str, parameter: (&str, Option<Type>), return_type: Option<Type>, body: Vec<Statement>) -> FunctionDecl {
+        FunctionDecl {
+            name: name.to_string(),
+            parameters: vec![Parameter {
+                name: parameter.0.to_string(),
+                typ: parameter.1,
+                default_value: None,
+                is_reference: false,
+                is_variadic: false,
+            }],
+            return_type,
+            body: Box::new(Statement::Block(body)),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        }
+    }
+
+    fn return_call(variable: &str) -> Statement {
+        Statement::Return(Some(Box::new(Expression::MethodCall {
+            object: Box::new(Expression::Variable(variable.to_string())),
+            method: "run".to_string(),
+            arguments: vec![],
+            nullsafe: false,
+        })))
+    }
+
+    fn job() -> AstNode {
+        AstNode::Class(ClassDecl {
+            name: "Job".to_string(),
+            extends: None,
+            implements: vec![],
+            traits: vec![],
+            properties: vec![PropertyDecl {
+                name: "id".to_string(),
+                typ: None,
+                default_value: None,
+                visibility: Visibility::Public,
+                is_static: false,
+                is_readonly: false,
+                doc_comment: None,
+            }],
+            methods: vec![function(
+                "run",
+                ("retries", Some(Type::Int)),
+                Some(Type::Int),
+                vec![Statement::Return(Some(Box::new(Expression::Variable("retries".to_string()))))],
+            )],
+            constants: vec![],
+            attributes: vec![],
+            is_abstract: false,
+            is_final: false,
+            is_trait: false,
+            is_interface: false,
+            is_enum: false,
+        })
+    }
+
+    #[test]
+    fn test_fallbacks() {
+        let ast = vec![
+            job(),
+            AstNode::Function(function("dispatch", ("job", None), None, vec![return_call("job")])),
+            AstNode::Function(function("typed", ("job", Some(Type::Object("Job".to_string()))), Some(Type::Int), vec![return_call("job")])),
+        ];
+        let mut reporter = FallbackReporter::new("app.php", FallbackReport::Warn);
+        reporter.check(&ast);
+        let diagnostics = reporter.take_diagnostics();
+        assert!(diagnostics.iter().all(|d| d.code == codes::DYNAMIC_FALLBACK && d.severity == Severity::Warning));
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, [
+            "Property Job::$id has no type",
+            "Parameter $job of dispatch() has no type",
+            "dispatch() has no return type",
+            "dispatch() uses constructs code generation does not support",
+            "Call of ->run() in dispatch() is dispatched at run time",
+            "typed() uses constructs code generation does not support",
+        ]);
+        assert_eq!(diagnostics[4].notes, vec!["receiver of ->run() has type unknown".to_string()]);
+    }
+
+    #[test]
+    fn test_deny_with_spans() {
+        assert_eq!("deny".parse::<FallbackReport>(), Ok(FallbackReport::Deny));
+        assert!("strict".parse::<FallbackReport>().is_err());
+
+        let ast = vec![AstNode::Function(function("identity", ("value", None), Some(Type::Int), vec![]))];
+        let mut index = AstIndex::build(&ast);
+        let decl = match &ast[0] {
+            AstNode::Function(decl) => decl,
+            _ => unreachable!(),
+        };
+        index.set_span(index.function_id(decl).unwrap(), Span::new(6, 30));
+
+        let mut reporter = FallbackReporter::new("app.php", FallbackReport::Deny).with_index(&index);
+        reporter.check(&ast);
+        let diagnostics = reporter.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].span, Some(Span::new(6, 30)));
+    }
+}
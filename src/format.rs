@@ -0,0 +1,392 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `printf`-family formatting.
+//!
+//! A format string is parsed into [`Piece`]s: literal text, and conversions
+//! that each format one argument. Code generation compiles a constant
+//! format to its pieces, calling the runtime formatter for each
+//! conversion's argument type; the interpreter formats the pieces of any
+//! format at run time.
+
+use std::os::raw::c_char;
+use crate::strings::PhpString;
+
+/// `-`: justify to the left of the width
+pub const FLAG_LEFT: u32 = 1;
+
+/// `+`: print a sign before non-negative numbers too
+pub const FLAG_PLUS: u32 = 2;
+
+/// Most digits PHP prints after the point of a float
+const MAX_PRECISION: usize = 53;
+
+/// Literal text or a conversion of a format string
+#[derive(Debug, Clone, PartialEq)]
+pub enum Piece {
+    Text(String),
+    Conversion(Conversion),
+}
+
+/// A conversion specification, such as `%'*-10.2f`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conversion {
+    /// Index of the argument formatted, counting from the one after the format
+    pub argument: usize,
+
+    /// Conversion character, such as `d` or `s`
+    pub specifier: char,
+
+    /// `FLAG_*` bits
+    pub flags: u32,
+
+    /// Byte the width is padded with
+    pub pad: u8,
+
+    /// Minimum width in bytes
+    pub width: usize,
+
+    /// Digits after the point of a float, or bytes kept of a string
+    pub precision: Option<usize>,
+}
+
+/// Type an argument is converted to before formatting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentKind {
+    Int,
+    Float,
+    String,
+}
+
+impl Conversion {
+    pub fn kind(&self) -> ArgumentKind {
+        match self.specifier {
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' | 'h' | 'H' => ArgumentKind::Float,
+            's' => ArgumentKind::String,
+            _ => ArgumentKind::Int,
+        }
+    }
+
+    /// Whether the conversion gives the argument as it converts to a string,
+    /// as a `%d` or `%s` without flags, width or precision does
+    pub fn is_plain(&self) -> bool {
+        matches!(self.specifier, 'd' | 's') && self.flags == 0 && self.width == 0 && self.precision.is_none()
+    }
+}
+
+/// Parse a format string into its pieces
+///
+/// Widths and precisions taken from arguments (`*`) are not supported.
+pub fn parse(format: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut next = 0;
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            text.push(c);
+            continue;
+        }
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            text.push('%');
+            continue;
+        }
+
+        // Argument number (`%2$s`), or a width when no `$` follows; a
+        // leading `0` is the padding flag
+        let mut digits = String::new();
+        while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit() && (**d != '0' || !digits.is_empty())) {
+            digits.push(*d);
+            chars.next();
+        }
+        let mut argument = None;
+        if !digits.is_empty() && chars.peek() == Some(&'$') {
+            chars.next();
+            match digits.parse::<usize>() {
+                Ok(n) if n > 0 => argument = Some(n - 1),
+                _ => return Err("Argument number specifier must be greater than zero".to_string()),
+            }
+            digits.clear();
+        }
+
+        let (mut flags, mut pad) = (0, b' ');
+        if digits.is_empty() {
+            loop {
+                match chars.peek() {
+                    Some('-') => flags |= FLAG_LEFT,
+                    Some('+') => flags |= FLAG_PLUS,
+                    Some(' ') => pad = b' ',
+                    Some('0') => pad = b'0',
+                    Some('\'') => {
+                        chars.next();
+                        let Some(custom) = chars.peek() else { break };
+                        pad = u8::try_from(*custom).map_err(|_| "Padding must be a single byte".to_string())?;
+                    }
+                    _ => break,
+                }
+                chars.next();
+            }
+            while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                digits.push(*d);
+                chars.next();
+            }
+        }
+        if chars.peek() == Some(&'*') {
+            return Err("Width taken from an argument is not supported".to_string());
+        }
+        let width = if digits.is_empty() { 0 } else { digits.parse().map_err(|_| "Width must be an integer".to_string())? };
+        let mut precision = None;
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            if chars.peek() == Some(&'*') {
+                return Err("Precision taken from an argument is not supported".to_string());
+            }
+            let mut digits = String::new();
+            while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                digits.push(*d);
+                chars.next();
+            }
+            precision = Some(digits.parse().unwrap_or(0));
+        }
+        // Length modifiers are accepted and ignored
+        if chars.peek() == Some(&'l') {
+            chars.next();
+        }
+
+        match chars.next() {
+            Some(specifier @ ('b' | 'c' | 'd' | 'e' | 'E' | 'f' | 'F' | 'g' | 'G' | 'h' | 'H' | 'o' | 's' | 'u' | 'x' | 'X')) => {
+                let argument = argument.unwrap_or_else(|| {
+                    next += 1;
+                    next - 1
+                });
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(Piece::Conversion(Conversion { argument, specifier, flags, pad, width, precision }));
+            }
+            Some(other) => return Err(format!("Unknown format specifier \"{}\"", other)),
+            None => return Err("Missing format specifier at end of string".to_string()),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+/// Format an integer for a `b`, `c`, `d`, `o`, `u`, `x` or `X` conversion
+pub fn format_int(value: i64, conversion: &Conversion) -> Vec<u8> {
+    let text = match conversion.specifier {
+        // A byte, never padded
+        'c' => return vec![value as u8],
+        'd' if value < 0 => format!("-{}", value.unsigned_abs()),
+        'd' if conversion.flags & FLAG_PLUS != 0 => format!("+{}", value),
+        'd' => value.to_string(),
+        'o' => format!("{:o}", value as u64),
+        'x' => format!("{:x}", value as u64),
+        'X' => format!("{:X}", value as u64),
+        'b' => format!("{:b}", value as u64),
+        _ => (value as u64).to_string(),
+    };
+    let is_signed = conversion.specifier == 'd' && (value < 0 || conversion.flags & FLAG_PLUS != 0);
+    pad(text.into_bytes(), is_signed, conversion)
+}
+
+/// Format a float for an `e`, `E`, `f`, `F`, `g`, `G`, `h` or `H` conversion
+///
+/// Exponents are written without leading zeros, and `g` keeps a `.0` on
+/// the mantissa of an exponent, as PHP does.
+pub fn format_float(value: f64, conversion: &Conversion) -> Vec<u8> {
+    let precision = conversion.precision.unwrap_or(6).min(MAX_PRECISION);
+    let magnitude = value.abs();
+    let digits = if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        "Inf".to_string()
+    } else {
+        match conversion.specifier {
+            'e' | 'E' => exponential(magnitude, precision, false),
+            'g' | 'G' | 'h' | 'H' => {
+                let precision = precision.max(1);
+                let exponent = if magnitude == 0.0 { 0 } else { exponent_of(magnitude, precision - 1) };
+                if exponent < -4 || exponent >= precision as i32 {
+                    exponential(magnitude, precision - 1, true)
+                } else {
+                    let decimals = (precision as i32 - 1 - exponent).max(0) as usize;
+                    let fixed = format!("{:.*}", decimals, magnitude);
+                    match fixed.contains('.') {
+                        true => fixed.trim_end_matches('0').trim_end_matches('.').to_string(),
+                        false => fixed,
+                    }
+                }
+            }
+            _ => format!("{:.*}", precision, magnitude),
+        }
+    };
+    let digits = match conversion.specifier {
+        'E' | 'G' | 'H' => digits.replace('e', "E"),
+        _ => digits,
+    };
+    let is_negative = value.is_sign_negative() && !value.is_nan() && (magnitude != 0.0 || value.is_infinite());
+    let text = if is_negative {
+        format!("-{}", digits)
+    } else if conversion.flags & FLAG_PLUS != 0 {
+        format!("+{}", digits)
+    } else {
+        digits
+    };
+    pad(text.into_bytes(), is_negative || conversion.flags & FLAG_PLUS != 0, conversion)
+}
+
+/// Format bytes for an `s` conversion, keeping at most the precision
+pub fn format_bytes(bytes: &[u8], conversion: &Conversion) -> Vec<u8> {
+    let kept = conversion.precision.map_or(bytes.len(), |precision| precision.min(bytes.len()));
+    pad(bytes[..kept].to_vec(), false, conversion)
+}
+
+/// Decimal exponent of a positive number rounded to `decimals` digits after the first
+fn exponent_of(magnitude: f64, decimals: usize) -> i32 {
+    let formatted = format!("{:.*e}", decimals, magnitude);
+    formatted.split_once('e').and_then(|(_, exponent)| exponent.parse().ok()).unwrap_or(0)
+}
+
+/// `1.5e+3` notation with `decimals` digits after the point, or as few as
+/// needed, but at least one, when `trim` is set
+fn exponential(magnitude: f64, decimals: usize, trim: bool) -> String {
+    let formatted = format!("{:.*e}", decimals, magnitude);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let mantissa = if trim && mantissa.contains('.') {
+        let trimmed = mantissa.trim_end_matches('0');
+        if trimmed.ends_with('.') { format!("{}0", trimmed) } else { trimmed.to_string() }
+    } else if trim {
+        format!("{}.0", mantissa)
+    } else {
+        mantissa.to_string()
+    };
+    match exponent.strip_prefix('-') {
+        Some(exponent) => format!("{}e-{}", mantissa, exponent),
+        None => format!("{}e+{}", mantissa, exponent),
+    }
+}
+
+/// Pad formatted text to the conversion's width
+///
+/// Zero padding goes between the sign and the digits of a signed number.
+fn pad(text: Vec<u8>, is_signed: bool, conversion: &Conversion) -> Vec<u8> {
+    let Some(padding) = conversion.width.checked_sub(text.len()).filter(|padding| *padding > 0) else {
+        return text;
+    };
+    let mut padded = Vec::with_capacity(conversion.width);
+    if conversion.flags & FLAG_LEFT != 0 {
+        padded.extend_from_slice(&text);
+        padded.resize(conversion.width, conversion.pad);
+    } else if is_signed && conversion.pad == b'0' {
+        padded.push(text[0]);
+        padded.resize(padding + 1, b'0');
+        padded.extend_from_slice(&text[1..]);
+    } else {
+        padded.resize(padding, conversion.pad);
+        padded.extend_from_slice(&text);
+    }
+    padded
+}
+
+// FFI functions called by generated code
+
+/// Conversion described by the arguments of the formatting functions
+fn conversion(specifier: c_char, flags: u32, pad: c_char, width: i64, precision: i64) -> Conversion {
+    Conversion {
+        argument: 0,
+        specifier: specifier as u8 as char,
+        flags,
+        pad: pad as u8,
+        width: width.max(0) as usize,
+        precision: usize::try_from(precision).ok(),
+    }
+}
+
+/// Format an integer as a new string; a negative precision is none
+#[no_mangle]
+pub extern "C" fn php_format_int(value: i64, specifier: c_char, flags: u32, pad: c_char, width: i64, precision: i64) -> *mut PhpString {
+    PhpString::new(format_int(value, &conversion(specifier, flags, pad, width, precision)))
+}
+
+/// Format a float as a new string; a negative precision is none
+#[no_mangle]
+pub extern "C" fn php_format_float(value: f64, specifier: c_char, flags: u32, pad: c_char, width: i64, precision: i64) -> *mut PhpString {
+    PhpString::new(format_float(value, &conversion(specifier, flags, pad, width, precision)))
+}
+
+/// Format a string as a new string; a negative precision is none
+///
+/// # Safety
+///
+/// `s` must be null or a live string.
+#[no_mangle]
+pub unsafe extern "C" fn php_format_string(s: *const PhpString, specifier: c_char, flags: u32, pad: c_char, width: i64, precision: i64) -> *mut PhpString {
+    let bytes = s.as_ref().map_or(&[][..], |s| s.as_bytes());
+    PhpString::new(format_bytes(bytes, &conversion(specifier, flags, pad, width, precision)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Format arguments given as strings like the interpreter does
+    fn sprintf(format: &str, arguments: &[&str]) -> String {
+        let mut output = Vec::new();
+        for piece in parse(format).unwrap() {
+            match piece {
+                Piece::Text(text) => output.extend_from_slice(text.as_bytes()),
+                Piece::Conversion(conversion) => {
+                    let argument = arguments[conversion.argument];
+                    output.extend(match conversion.kind() {
+                        ArgumentKind::Int => format_int(argument.parse().unwrap(), &conversion),
+                        ArgumentKind::Float => format_float(argument.parse().unwrap(), &conversion),
+                        ArgumentKind::String => format_bytes(argument.as_bytes(), &conversion),
+                    });
+                }
+            }
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let pieces = parse("%2$s=%'*-6.2f%%").unwrap();
+        assert_eq!(pieces, vec![
+            Piece::Conversion(Conversion { argument: 1, specifier: 's', flags: 0, pad: b' ', width: 0, precision: None }),
+            Piece::Text("=".to_string()),
+            Piece::Conversion(Conversion { argument: 0, specifier: 'f', flags: FLAG_LEFT, pad: b'*', width: 6, precision: Some(2) }),
+            Piece::Text("%".to_string()),
+        ]);
+        assert!(parse("%05d%s").unwrap().iter().all(|piece| matches!(piece, Piece::Conversion(_))));
+        assert!(parse("%*d").is_err());
+        assert_eq!(parse("%y").unwrap_err(), "Unknown format specifier \"y\"");
+    }
+
+    #[test]
+    fn test_php_formatting() {
+        assert_eq!(sprintf("[%5d] [%-5d] [%05d] [%+d] [%+05d]", &["42", "42", "-42", "7", "7"]), "[   42] [42   ] [-0042] [+7] [+0007]");
+        assert_eq!(sprintf("%x %X %o %b %u %c", &["255", "255", "8", "5", "-1", "65"]), "ff FF 10 101 18446744073709551615 A");
+        assert_eq!(sprintf("%.2f %f %08.3f %-8.1f|", &["3.14159", "1.5", "-3.14159", "2.25"]), "3.14 1.500000 -003.142 2.2     |");
+        assert_eq!(sprintf("%e %.2E %.0e", &["1234.5678", "0.000123", "5"]), "1.234568e+3 1.23E-4 5e+0");
+        assert_eq!(sprintf("%g %g %g %G", &["0.0001", "0.00001", "1000000", "123456789"]), "0.0001 1.0e-5 1.0e+6 1.23457E+8");
+        assert_eq!(sprintf("%'.10s|%-10s|%.3s", &["right", "left", "truncate"]), ".....right|left      |tru");
+        assert_eq!(sprintf("%f %f", &["inf", "-inf"]), "Inf -Inf");
+    }
+}
@@ -0,0 +1,81 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Fuzzing entry points for the lexer and parser.
+//!
+//! Both take arbitrary bytes, decoded as UTF-8 with invalid sequences
+//! replaced, and must return for any input: a panic or a hang is a bug.
+//! The cargo-fuzz targets in `fuzz/` call them:
+//!
+//! ```text
+//! cargo +nightly fuzz run lex
+//! cargo +nightly fuzz run parse
+//! ```
+
+use crate::diagnostics::Diagnostic;
+use crate::interp::load_source;
+use crate::parser::{Lexer, Token};
+
+/// Tokenize `data` to the end; returns the number of tokens
+///
+/// Every token consumes at least one character, so a lexer producing more
+/// tokens than there are characters has stopped advancing, which panics
+/// rather than hanging the fuzzer.
+pub fn lex_fuzz(data: &[u8]) -> usize {
+    let source = String::from_utf8_lossy(data);
+    let limit = source.chars().count();
+    let mut lexer = Lexer::new(&source);
+    let mut tokens = 0;
+    while lexer.next_token() != Token::Eof {
+        tokens += 1;
+        assert!(tokens <= limit, "lexer produced more tokens than the {} characters of its input", limit);
+    }
+    assert_eq!(lexer.next_token(), Token::Eof, "lexer must keep returning EOF at the end");
+    tokens
+}
+
+/// Parse and annotate `data` as the compiler does; an error is rendered as
+/// a diagnostic against the source, as the CLI would show it
+pub fn parse_fuzz(data: &[u8]) {
+    let source = String::from_utf8_lossy(data);
+    if let Err(e) = load_source(&source) {
+        let _ = Diagnostic::from_error(&e, Some(&source)).render(Some(&source));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzz_entry_points() {
+        let inputs: &[&[u8]] = &[
+            b"",
+            b"<?php echo 'unterminated",
+            b"<?php /* unterminated comment",
+            b"\"\\",
+            b"1e+ 99999999999999999999 1.2.3 $",
+            b"\xff\xfe<?php \xc3\x28 function",
+            "<?php $ü = '日本';".as_bytes(),
+            b"<?php if (",
+        ];
+        for input in inputs {
+            assert!(lex_fuzz(input) <= input.len());
+            parse_fuzz(input);
+        }
+        assert_eq!(lex_fuzz(b"function f() {}"), 6);
+    }
+}
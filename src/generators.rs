@@ -0,0 +1,389 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Generators of compiled code.
+//!
+//! Calling a function that contains `yield` creates a generator object
+//! instead of running the body. The object starts with a [`PhpGenerator`]
+//! header, followed by a frame holding the function's variables. The body
+//! is compiled to a resume function that runs from where it left off up to
+//! the next `yield`, keeping its position in the header's state field.
+//!
+//! The runtime drives the resume function for the `Generator` methods and
+//! keeps the yielded keys and values, the values passed to `send()` and
+//! the returned value as boxed [`Value`]s, which generated code writes with
+//! the `php_value_set_*` functions and reads with `php_value_get_*`.
+
+use std::os::raw::c_char;
+use crate::exceptions::{throw_new, ERROR, EXCEPTION};
+use crate::objects::{PhpClass, PhpObject};
+use crate::runtime::Value;
+
+/// Function running a generator's body up to the next `yield`, returning
+/// whether it stopped at one rather than returning
+pub type ResumeFunction = unsafe extern "C-unwind" fn(*mut PhpObject) -> bool;
+
+/// Header of every generator object, before the frame of its function
+#[repr(C)]
+#[derive(Debug)]
+pub struct PhpGenerator {
+    header: PhpObject,
+    resume: Option<ResumeFunction>,
+    /// Resume point of the body, managed by generated code
+    state: i64,
+    values: *mut GeneratorValues,
+}
+
+/// Progress of a generator and the values it exchanges with its caller
+#[derive(Debug)]
+struct GeneratorValues {
+    current: Value,
+    key: Value,
+    sent: Value,
+    returned: Value,
+    /// Key of the next `yield` without one
+    next_key: i64,
+    started: bool,
+    /// Whether it moved past its first `yield`
+    advanced: bool,
+    running: bool,
+    finished: bool,
+    /// Whether it finished by returning rather than throwing
+    has_returned: bool,
+}
+
+impl GeneratorValues {
+    fn new() -> Self {
+        GeneratorValues {
+            current: Value::Null,
+            key: Value::Null,
+            sent: Value::Null,
+            returned: Value::Null,
+            next_key: 0,
+            started: false,
+            advanced: false,
+            running: false,
+            finished: false,
+            has_returned: false,
+        }
+    }
+}
+
+/// Base class of the classes generated for generator functions
+#[export_name = "php.class.Generator"]
+pub static GENERATOR: PhpClass = PhpClass {
+    name: c"Generator".as_ptr() as *const c_char,
+    size: std::mem::size_of::<PhpGenerator>() as u64,
+    parent: std::ptr::null(),
+    free: None,
+    clone: None,
+    vtable: std::ptr::null(),
+    itables: std::ptr::null(),
+    properties: std::ptr::null(),
+    methods: std::ptr::null(),
+};
+
+unsafe fn values<'a>(o: *mut PhpObject) -> &'a mut GeneratorValues {
+    &mut *(*(o as *mut PhpGenerator)).values
+}
+
+/// Run the body up to its next `yield` or its end
+///
+/// The generator counts as finished while the body runs, so one that
+/// throws stays finished.
+unsafe fn resume(o: *mut PhpObject) {
+    let values = values(o);
+    if values.finished {
+        return;
+    }
+    if values.running {
+        throw_new(&ERROR, "Cannot resume an already running generator");
+    }
+    values.started = true;
+    values.running = true;
+    values.finished = true;
+    let yielded = match (*(o as *mut PhpGenerator)).resume {
+        Some(resume) => resume(o),
+        None => false,
+    };
+
+    let values = self::values(o);
+    values.running = false;
+    values.finished = !yielded;
+    if yielded {
+        if let Value::Int(key) = values.key {
+            values.next_key = values.next_key.max(key.saturating_add(1));
+        }
+    } else {
+        values.has_returned = true;
+        values.current = Value::Null;
+        values.key = Value::Null;
+    }
+}
+
+/// Run a generator that has not started up to its first `yield`
+unsafe fn start(o: *mut PhpObject) {
+    if !values(o).started {
+        resume(o);
+    }
+}
+
+/// Move a generator past its current `yield`
+unsafe fn advance(o: *mut PhpObject) {
+    start(o);
+    if !values(o).finished {
+        values(o).advanced = true;
+        resume(o);
+    }
+}
+
+// FFI functions called by generated code
+
+/// Set up a new generator object with the resume function of its body
+///
+/// # Safety
+///
+/// `o` must be a new object of a class generated for a generator function;
+/// the same holds for the other functions taking a generator.
+#[no_mangle]
+pub unsafe extern "C" fn php_generator_init(o: *mut PhpObject, resume: ResumeFunction) {
+    let generator = &mut *(o as *mut PhpGenerator);
+    generator.resume = Some(resume);
+    generator.state = 0;
+    generator.values = Box::into_raw(Box::new(GeneratorValues::new()));
+}
+
+/// Drop the runtime's part of a generator before it is freed
+///
+/// # Safety
+///
+/// `o` must be a generator whose last reference is being released.
+#[no_mangle]
+pub unsafe extern "C" fn php_generator_free(o: *mut PhpObject) {
+    let generator = &mut *(o as *mut PhpGenerator);
+    if !generator.values.is_null() {
+        drop(Box::from_raw(std::mem::replace(&mut generator.values, std::ptr::null_mut())));
+    }
+}
+
+/// Slot for the value of a `yield`
+///
+/// # Safety
+///
+/// See [`php_generator_init`].
+#[no_mangle]
+pub unsafe extern "C" fn php_generator_yield_value(o: *mut PhpObject) -> *mut Value {
+    &mut values(o).current
+}
+
+/// Slot for the key of a `yield` with one
+///
+/// # Safety
+///
+/// See [`php_generator_init`].
+#[no_mangle]
+pub unsafe extern "C" fn php_generator_yield_key(o: *mut PhpObject) -> *mut Value {
+    &mut values(o).key
+}
+
+/// Give a `yield` without a key the next integer key
+///
+/// # Safety
+///
+/// See [`php_generator_init`].
+#[no_mangle]
+pub unsafe extern "C" fn php_generator_auto_key(o: *mut PhpObject) {
+    let values = values(o);
+    values.key = Value::Int(values.next_key);
+}
+
+/// Value passed to `send()`, which is the result of the `yield` the body
+/// resumes from; null when the generator was resumed by `next()`
+///
+/// # Safety
+///
+/// See [`php_generator_init`].
+#[no_mangle]
+pub unsafe extern "C" fn php_generator_sent(o: *mut PhpObject) -> *mut Value {
+    &mut values(o).sent
+}
+
+/// Slot for the value of `return` in the body
+///
+/// # Safety
+///
+/// See [`php_generator_init`].
+#[no_mangle]
+pub unsafe extern "C" fn php_generator_return_value(o: *mut PhpObject) -> *mut Value {
+    &mut values(o).returned
+}
+
+/// `Generator::current()`, borrowed from the generator
+///
+/// # Safety
+///
+/// See [`php_generator_init`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_generator_current(o: *mut PhpObject) -> *mut Value {
+    start(o);
+    &mut values(o).current
+}
+
+/// `Generator::key()`, borrowed from the generator
+///
+/// # Safety
+///
+/// See [`php_generator_init`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_generator_key(o: *mut PhpObject) -> *mut Value {
+    start(o);
+    &mut values(o).key
+}
+
+/// `Generator::next()`
+///
+/// # Safety
+///
+/// See [`php_generator_init`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_generator_next(o: *mut PhpObject) {
+    values(o).sent = Value::Null;
+    advance(o);
+}
+
+/// `Generator::send()` with the value already in [`php_generator_sent`],
+/// returning the new current value
+///
+/// A generator that has not started first runs to its first `yield`,
+/// which then receives the value.
+///
+/// # Safety
+///
+/// See [`php_generator_init`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_generator_send(o: *mut PhpObject) -> *mut Value {
+    advance(o);
+    values(o).sent = Value::Null;
+    &mut values(o).current
+}
+
+/// `Generator::valid()`
+///
+/// # Safety
+///
+/// See [`php_generator_init`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_generator_valid(o: *mut PhpObject) -> bool {
+    start(o);
+    !values(o).finished
+}
+
+/// `Generator::rewind()`, which only starts the generator
+///
+/// # Safety
+///
+/// See [`php_generator_init`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_generator_rewind(o: *mut PhpObject) {
+    start(o);
+    if values(o).advanced {
+        throw_new(&EXCEPTION, "Cannot rewind a generator that was already run");
+    }
+}
+
+/// `Generator::getReturn()`, borrowed from the generator
+///
+/// # Safety
+///
+/// See [`php_generator_init`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_generator_get_return(o: *mut PhpObject) -> *mut Value {
+    if !values(o).has_returned {
+        throw_new(&EXCEPTION, "Cannot get return value of a generator that hasn't returned");
+    }
+    &mut values(o).returned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arrays::{php_value_get_int, php_value_set_int};
+    use crate::objects::{php_object_instanceof, php_object_new, php_object_release};
+
+    /// Generator object with a frame of one integer, like generated code lays out
+    #[repr(C)]
+    struct Frame {
+        generator: PhpGenerator,
+        i: i64,
+    }
+
+    unsafe extern "C-unwind" fn free(o: *mut PhpObject) {
+        php_generator_free(o);
+    }
+
+    static COUNTER: PhpClass = PhpClass {
+        name: c"Generator".as_ptr() as *const c_char,
+        size: std::mem::size_of::<Frame>() as u64,
+        parent: &GENERATOR,
+        free: Some(free),
+        clone: None,
+        vtable: std::ptr::null(),
+        itables: std::ptr::null(),
+        properties: std::ptr::null(),
+        methods: std::ptr::null(),
+    };
+
+    /// `for ($i = 0; $i < 3; $i++) { $i += yield $i * 10; } return $i;`
+    unsafe extern "C-unwind" fn count(o: *mut PhpObject) -> bool {
+        let frame = &mut *(o as *mut Frame);
+        if frame.generator.state == 1 {
+            frame.i += php_value_get_int(php_generator_sent(o)) + 1;
+        }
+        if frame.i < 3 {
+            php_value_set_int(php_generator_yield_value(o), frame.i * 10);
+            php_generator_auto_key(o);
+            frame.generator.state = 1;
+            return true;
+        }
+        php_value_set_int(php_generator_return_value(o), frame.i);
+        false
+    }
+
+    #[test]
+    fn test_generator_protocol() {
+        unsafe {
+            let generator = php_object_new(&COUNTER);
+            php_generator_init(generator, count);
+            assert!(php_object_instanceof(generator, &GENERATOR));
+            assert_eq!(php_value_get_int(php_generator_current(generator)), 0);
+            assert_eq!(php_value_get_int(php_generator_key(generator)), 0);
+
+            php_generator_next(generator);
+            assert_eq!(php_value_get_int(php_generator_current(generator)), 10);
+            php_value_set_int(php_generator_sent(generator), 0);
+            assert_eq!(php_value_get_int(php_generator_send(generator)), 20);
+            assert_eq!(php_value_get_int(php_generator_key(generator)), 2);
+            assert!(php_generator_valid(generator));
+
+            php_value_set_int(php_generator_sent(generator), 5);
+            php_generator_send(generator);
+            assert!(!php_generator_valid(generator));
+            assert!(matches!(*php_generator_current(generator), Value::Null));
+            assert_eq!(php_value_get_int(php_generator_get_return(generator)), 8);
+            php_object_release(generator);
+        }
+    }
+}
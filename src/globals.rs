@@ -0,0 +1,175 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Script-level variables of compiled code.
+//!
+//! A top-level variable that a function imports with `global`, or that
+//! `$GLOBALS` may reach, lives in a `%php.mixed` global instead of a stack
+//! slot. Every module registers a table of the names and addresses of its
+//! globals; modules naming the same variable share its global. `$GLOBALS`
+//! with a key only known at run time looks variables up in the table, and
+//! assigning one that is not there adds it.
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::sync::Mutex;
+use crate::arrays::PhpArray;
+use crate::mixed::{php_mixed_addref, php_mixed_release, to_value, PhpMixed, TAG_NULL};
+use crate::runtime::{Array, ArrayType};
+use crate::strings::PhpString;
+
+/// Row of a module's globals table; the table ends with a null slot
+#[repr(C)]
+#[derive(Debug)]
+pub struct PhpGlobalInfo {
+    name: *const c_char,
+    slot: *mut PhpMixed,
+}
+
+/// Registered variables and the addresses of their values, in order of registration
+static GLOBALS: Mutex<Vec<(String, usize)>> = Mutex::new(Vec::new());
+
+/// Slot of a registered variable
+fn lookup(name: &str) -> Option<*mut PhpMixed> {
+    let globals = GLOBALS.lock().unwrap();
+    globals.iter().find(|(n, _)| n == name).map(|(_, slot)| *slot as *mut PhpMixed)
+}
+
+/// Name held by a string
+unsafe fn name(s: *const PhpString) -> String {
+    let bytes = s.as_ref().map_or(&[][..], |s| s.as_bytes());
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+// FFI functions called by generated code
+
+/// Register a module's globals table
+///
+/// # Safety
+///
+/// `table` must point to rows ending with one whose slot is null, whose
+/// slots stay valid for the rest of the program.
+#[no_mangle]
+pub unsafe extern "C" fn php_globals_register(table: *const PhpGlobalInfo) {
+    let mut globals = GLOBALS.lock().unwrap();
+    let mut row = table;
+    while !(*row).slot.is_null() {
+        let info = &*row;
+        let name = CStr::from_ptr(info.name).to_string_lossy().into_owned();
+        if !globals.iter().any(|(n, _)| *n == name) {
+            globals.push((name, info.slot as usize));
+        }
+        row = row.add(1);
+    }
+}
+
+/// `$GLOBALS[$name]`: the variable's value as a new reference, or null
+///
+/// # Safety
+///
+/// `name` must be null or a valid string.
+#[no_mangle]
+pub unsafe extern "C" fn php_globals_get(name: *const PhpString) -> PhpMixed {
+    match lookup(&self::name(name)) {
+        Some(slot) => {
+            php_mixed_addref(*slot);
+            *slot
+        }
+        None => PhpMixed::NULL,
+    }
+}
+
+/// Slot of the variable `$GLOBALS[$name] = ...` assigns, added when missing
+///
+/// # Safety
+///
+/// `name` must be null or a valid string.
+#[no_mangle]
+pub unsafe extern "C" fn php_globals_slot(name: *const PhpString) -> *mut PhpMixed {
+    let name = self::name(name);
+    if let Some(slot) = lookup(&name) {
+        return slot;
+    }
+    let slot = Box::into_raw(Box::new(PhpMixed::NULL));
+    GLOBALS.lock().unwrap().push((name, slot as usize));
+    slot
+}
+
+/// `$GLOBALS`: a copy of the variables holding values other than null
+///
+/// Objects are left out, as the runtime's arrays cannot hold them.
+#[no_mangle]
+pub extern "C" fn php_globals_array() -> *mut PhpArray {
+    let globals = GLOBALS.lock().unwrap();
+    let mut array = Array::new(ArrayType::Associative);
+    for (name, slot) in globals.iter() {
+        let value = unsafe { *(*slot as *const PhpMixed) };
+        if value.tag == TAG_NULL {
+            continue;
+        }
+        if let Some(value) = unsafe { to_value(value) } {
+            array.set_by_key(name, value).expect("string keys are valid");
+        }
+    }
+    PhpArray::new(array)
+}
+
+/// Release the values of all variables when the script ends
+#[no_mangle]
+pub extern "C-unwind" fn php_globals_release() {
+    let slots: Vec<usize> = GLOBALS.lock().unwrap().iter().map(|(_, slot)| *slot).collect();
+    for slot in slots {
+        // Destructors run here may read other variables, so each is cleared first
+        let slot = slot as *mut PhpMixed;
+        unsafe {
+            let value = std::mem::replace(&mut *slot, PhpMixed::NULL);
+            php_mixed_release(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Value;
+    use crate::strings::php_string_new;
+
+    #[test]
+    fn test_globals_table() {
+        static mut COUNT: PhpMixed = PhpMixed::NULL;
+        let table = [
+            PhpGlobalInfo { name: c"globals_test_count".as_ptr(), slot: unsafe { std::ptr::addr_of_mut!(COUNT) } },
+            PhpGlobalInfo { name: std::ptr::null(), slot: std::ptr::null_mut() },
+        ];
+        unsafe {
+            php_globals_register(table.as_ptr());
+            let key = "globals_test_count";
+            let count = php_string_new(key.as_ptr() as *const c_char, key.len() as i64);
+            *php_globals_slot(count) = PhpMixed::int(3);
+            assert_eq!(std::ptr::addr_of!(COUNT).read(), PhpMixed::int(3));
+            assert_eq!(php_globals_get(count), PhpMixed::int(3));
+
+            // Assigning a variable that is not registered adds it
+            let key = "globals_test_added";
+            let added = php_string_new(key.as_ptr() as *const c_char, key.len() as i64);
+            assert_eq!(php_globals_get(added), PhpMixed::NULL);
+            *php_globals_slot(added) = PhpMixed::bool(true);
+            assert_eq!(php_globals_get(added), PhpMixed::bool(true));
+            let array = php_globals_array();
+            assert!(matches!((*array).array().get_by_key("globals_test_count"), Some(Value::Int(3))));
+        }
+    }
+}
@@ -0,0 +1,275 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! C headers for exported functions, written by `--emit header`.
+//!
+//! Every top-level function marked `#[Export]` gets a prototype, so C, C++
+//! and Rust (through bindgen) code can call into the compiled program. PHP
+//! types map to the C types of the values generated code passes:
+//!
+//! | PHP                       | C                            |
+//! |---------------------------|------------------------------|
+//! | `int`                     | `int64_t` (`int32_t` with `--int-width 32`) |
+//! | `float`                   | `double`                     |
+//! | `bool`                    | `bool`                       |
+//! | `string`                  | `php2ir_string *`            |
+//! | `array`                   | `php2ir_array *`             |
+//! | a class, `?Class`, generators | `php2ir_object *`        |
+//! | `void`, `null` (result)   | `void`                       |
+//! | untyped, unions           | `php2ir_mixed` (by value)    |
+//! | anything else             | `void *`                     |
+//!
+//! Strings, arrays and objects are refcounted values of the runtime and
+//! only declared as opaque structs. The symbol of an exported function must
+//! be a C identifier: give it one with `#[Export("name")]` or `#[NoMangle]`;
+//! functions under a mangled `php.` symbol are skipped with a warning.
+
+use log::warn;
+use crate::ast::{AstNode, FunctionDecl};
+use crate::coercion::declared_name;
+use crate::directives::{is_valid_symbol, CodegenDirectives};
+use crate::error::CompileResult;
+use crate::ir::generator_type;
+use crate::types::{IntWidth, Type};
+
+/// C and C++ keywords that PHP allows as parameter names
+const KEYWORDS: &[&str] = &[
+    "auto", "bool", "char", "const", "delete", "double", "float", "inline", "int", "long",
+    "register", "restrict", "short", "signed", "sizeof", "struct", "template", "this",
+    "typedef", "union", "unsigned", "volatile",
+];
+
+/// Header declaring the exported functions of `ast`
+///
+/// `name` is the header's file stem, from which the include guard is made.
+/// The header of a static library also declares its init and cleanup
+/// functions, whose names start with `entry`.
+pub fn generate_header(ast: &[AstNode], name: &str, int_width: IntWidth, entry: Option<&str>) -> CompileResult<String> {
+    let mut functions = Vec::new();
+    collect_functions(ast, &mut functions);
+
+    let mut prototypes = String::new();
+    if let Some(prefix) = entry {
+        prototypes.push_str(&format!(
+            "/* Run the top-level code of the program; call it before any other function */\n\
+             void {0}_init(void);\n\
+             \n\
+             /* Release the program's variables and shut the runtime down */\n\
+             void {0}_cleanup(void);\n\
+             \n",
+            prefix
+        ));
+    }
+    for function in functions {
+        let directives = CodegenDirectives::from_attributes(&function.attributes)?;
+        if !directives.export {
+            continue;
+        }
+        let symbol = directives.symbol(&function.name);
+        if !is_valid_symbol(&symbol) {
+            warn!(
+                "Exported function {} is not declared in the header: its symbol {} is not a C identifier (use #[NoMangle] or #[Export(\"name\")])",
+                function.name, symbol
+            );
+            continue;
+        }
+        prototypes.push_str(&prototype(function, &symbol, int_width));
+    }
+
+    let guard = include_guard(name);
+    Ok(format!(
+        "/* Generated by php2ir. Do not edit. */\n\
+         #ifndef {guard}\n\
+         #define {guard}\n\
+         \n\
+         #include <stdbool.h>\n\
+         #include <stdint.h>\n\
+         \n\
+         #ifdef __cplusplus\n\
+         extern \"C\" {{\n\
+         #endif\n\
+         \n\
+         /* Refcounted values of the php2ir runtime */\n\
+         typedef struct php2ir_string php2ir_string;\n\
+         typedef struct php2ir_array php2ir_array;\n\
+         typedef struct php2ir_object php2ir_object;\n\
+         \n\
+         /* Value whose type is only known at run time: a type tag and its payload */\n\
+         typedef struct php2ir_mixed {{\n\
+         \x20   uint32_t tag;\n\
+         \x20   uint64_t payload;\n\
+         }} php2ir_mixed;\n\
+         \n\
+         {prototypes}\
+         #ifdef __cplusplus\n\
+         }}\n\
+         #endif\n\
+         \n\
+         #endif /* {guard} */\n"
+    ))
+}
+
+/// Top-level functions, including those in namespaces
+fn collect_functions<'a>(ast: &'a [AstNode], out: &mut Vec<&'a FunctionDecl>) {
+    for node in ast {
+        match node {
+            AstNode::Program(nodes) => collect_functions(nodes, out),
+            AstNode::Namespace(namespace) => collect_functions(&namespace.statements, out),
+            AstNode::Function(function) => out.push(function),
+            _ => {}
+        }
+    }
+}
+
+/// Comment with the PHP signature and the C prototype of a function
+fn prototype(function: &FunctionDecl, symbol: &str, int_width: IntWidth) -> String {
+    let result = if generator_type(function).is_some() {
+        "php2ir_object *".to_string()
+    } else {
+        match &function.return_type {
+            Some(Type::Null) => "void ".to_string(),
+            typ => c_type(typ.as_ref().unwrap_or(&Type::Unknown), int_width),
+        }
+    };
+    let parameters: Vec<String> = function.parameters.iter()
+        .map(|p| format!("{}{}", c_type(p.typ.as_ref().unwrap_or(&Type::Unknown), int_width), parameter_name(&p.name)))
+        .collect();
+    let parameters = if parameters.is_empty() { "void".to_string() } else { parameters.join(", ") };
+
+    let php_parameters: Vec<String> = function.parameters.iter()
+        .map(|p| match &p.typ {
+            Some(typ) => format!("{} ${}", declared_name(typ), p.name),
+            None => format!("${}", p.name),
+        })
+        .collect();
+    let php_result = function.return_type.as_ref().map(|typ| format!(": {}", declared_name(typ))).unwrap_or_default();
+    format!(
+        "/* function {}({}){} */\n{}{}({});\n\n",
+        function.name.trim_start_matches('\\'), php_parameters.join(", "), php_result,
+        result, symbol, parameters
+    )
+}
+
+/// C type of a PHP type as generated code passes it, followed by a space
+/// unless it ends in `*`
+///
+/// Mirrors the LLVM types of the IR generator.
+fn c_type(typ: &Type, int_width: IntWidth) -> String {
+    let c = match typ {
+        Type::Int => match int_width {
+            IntWidth::W32 => "int32_t ",
+            IntWidth::W64 => "int64_t ",
+        },
+        Type::Float => "double ",
+        Type::Bool => "bool ",
+        Type::String => "php2ir_string *",
+        Type::Array(_) | Type::AssociativeArray(_) => "php2ir_array *",
+        Type::Object(_) => "php2ir_object *",
+        Type::Generic(name, _) if name.trim_start_matches('\\').eq_ignore_ascii_case("generator") => "php2ir_object *",
+        Type::Literal(literal) => return c_type(&literal.base_type(), int_width),
+        Type::Union(_) => match typ.non_null_type() {
+            // The null pointer stands for null
+            Some(object @ Type::Object(_)) => return c_type(&object, int_width),
+            _ => "php2ir_mixed ",
+        },
+        Type::Unknown => "php2ir_mixed ",
+        _ => "void *",
+    };
+    c.to_string()
+}
+
+/// Parameter name, renamed when it is a C or C++ keyword
+fn parameter_name(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// `APP_H` for `app`; characters C macros cannot hold become `_`
+fn include_guard(name: &str) -> String {
+    let mut guard: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    if guard.starts_with(|c: char| c.is_ascii_digit()) || guard.is_empty() {
+        guard.insert(0, '_');
+    }
+    guard.push_str("_H");
+    guard
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Attribute, Expression, Literal, Parameter, Statement, Visibility};
+
+    fn function(name: &str, parameters: Vec<(&str, Option<Type>)>, return_type: Option<Type>, attributes: Vec<Attribute>) -> AstNode {
+        AstNode::Function(FunctionDecl {
+            name: name.to_string(),
+            parameters: parameters.into_iter().map(|(name, typ)| Parameter {
+                name: name.to_string(),
+                typ,
+                default_value: None,
+                is_reference: false,
+                is_variadic: false,
+            }).collect(),
+            return_type,
+            body: Box::new(Statement::Block(Vec::new())),
+            attributes,
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        })
+    }
+
+    fn attribute(name: &str, arguments: Vec<Expression>) -> Attribute {
+        Attribute { name: name.to_string(), arguments }
+    }
+
+    #[test]
+    fn test_generate_header() {
+        let ast = vec![
+            function(
+                "add",
+                vec![("a", Some(Type::Int)), ("double", Some(Type::Float))],
+                Some(Type::Float),
+                vec![attribute("Export", vec![Expression::Literal(Literal::String("calc_add".to_string()))])],
+            ),
+            function(
+                "greet",
+                vec![("name", Some(Type::String)), ("extra", None)],
+                Some(Type::Null),
+                vec![attribute("NoMangle", Vec::new()), attribute("Php2Ir\\Export", Vec::new())],
+            ),
+            function("mangled", Vec::new(), Some(Type::Bool), vec![attribute("Export", Vec::new())]),
+            function("internal", Vec::new(), Some(Type::Int), Vec::new()),
+        ];
+
+        let header = generate_header(&ast, "my-lib", IntWidth::W32, None).unwrap();
+        assert!(header.contains("#ifndef MY_LIB_H\n#define MY_LIB_H\n"));
+        assert!(header.contains("/* function add(int $a, float $double): float */\ndouble calc_add(int32_t a, double double_);\n"));
+        assert!(header.contains("void greet(php2ir_string *name, php2ir_mixed extra);\n"));
+        assert!(!header.contains("mangled"));
+        assert!(!header.contains("internal"));
+        assert!(header.ends_with("#endif /* MY_LIB_H */\n"));
+        assert_eq!(include_guard("2d"), "_2D_H");
+
+        let header = generate_header(&ast, "calc", IntWidth::W64, Some("calc")).unwrap();
+        assert!(header.contains("void calc_init(void);\n"));
+        assert!(header.contains("void calc_cleanup(void);\n"));
+    }
+}
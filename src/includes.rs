@@ -0,0 +1,426 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Compile-time `include`/`require` resolution.
+//!
+//! Includes whose target is a constant expression (string literals,
+//! `__DIR__`, `__FILE__`, `dirname()` and concatenations of those) are parsed
+//! at compile time. A top-level include statement is replaced by a program
+//! node holding the included file's nodes; includes in any other position
+//! only contribute the file's declarations. `*_once` includes are spliced at
+//! most once and include cycles without `_once` are rejected.
+//!
+//! Files inside a bundle are named by their `phar://` URLs, which resolve
+//! like paths below the archive.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use log::{debug, warn};
+use crate::ast::visit::{self, VisitorMut};
+use crate::ast::{AstNode, BinaryOperator, Expression, IncludeKind, Literal, Statement};
+use crate::bundle::PHAR_SCHEME;
+use crate::definitions::DefinitionRegistry;
+use crate::error::{CompileError, CompileResult};
+use crate::utils::path::normalize;
+
+/// Resolves constant includes starting from a root file
+pub struct IncludeResolver<L> {
+    /// Parses and annotates one file
+    load: L,
+
+    /// Files currently being spliced, outermost first
+    stack: Vec<PathBuf>,
+
+    /// Every file spliced so far
+    included: HashSet<PathBuf>,
+
+    /// Declarations of every file, for redeclaration diagnostics
+    definitions: DefinitionRegistry,
+
+    /// Directories searched for relative targets, like PHP's `include_path`
+    include_paths: Vec<PathBuf>,
+}
+
+impl<L> IncludeResolver<L>
+where
+    L: FnMut(&Path) -> CompileResult<Vec<AstNode>>,
+{
+    pub fn new(load: L) -> Self {
+        Self {
+            load,
+            stack: Vec::new(),
+            included: HashSet::new(),
+            definitions: DefinitionRegistry::new(),
+            include_paths: Vec::new(),
+        }
+    }
+
+    /// Search `paths` for relative targets before the including file's directory
+    pub fn with_include_paths(mut self, paths: Vec<PathBuf>) -> Self {
+        self.include_paths = paths;
+        self
+    }
+
+    /// Load `root` and splice in everything it includes
+    pub fn resolve(&mut self, root: &Path) -> CompileResult<Vec<AstNode>> {
+        let root = normalize_target(root);
+        let nodes = (self.load)(&root)?;
+        self.definitions.register(&nodes, &root.display().to_string())?;
+
+        self.included.insert(root.clone());
+        self.stack.push(root.clone());
+        let nodes = self.splice(nodes, &root)?;
+        self.stack.pop();
+        Ok(nodes)
+    }
+
+    /// Files spliced into the compilation unit, including the root
+    pub fn included_files(&self) -> impl Iterator<Item = &PathBuf> {
+        self.included.iter()
+    }
+
+    fn splice(&mut self, nodes: Vec<AstNode>, file: &Path) -> CompileResult<Vec<AstNode>> {
+        let mut out = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            match node {
+                AstNode::Program(nodes) => out.push(AstNode::Program(self.splice(nodes, file)?)),
+                AstNode::Namespace(mut ns) => {
+                    ns.statements = self.splice(std::mem::take(&mut ns.statements), file)?;
+                    out.push(AstNode::Namespace(ns));
+                }
+                AstNode::Statement(stmt) => match top_level_include(&stmt, file, &self.include_paths) {
+                    Some((kind, target)) => match self.include(&kind, &target, file)? {
+                        // A program of its own, which keeps the file's `strict_types` mode
+                        Some(nodes) => out.push(AstNode::Program(nodes)),
+                        None => out.push(AstNode::Statement(stmt)),
+                    },
+                    None => self.hoist_nested(AstNode::Statement(stmt), file, &mut out)?,
+                },
+                node => self.hoist_nested(node, file, &mut out)?,
+            }
+        }
+        Ok(out)
+    }
+
+    /// Keep a node, adding the declarations of the files it includes after it
+    fn hoist_nested(&mut self, mut node: AstNode, file: &Path, out: &mut Vec<AstNode>) -> CompileResult<()> {
+        let mut nested = NestedIncludes { file, include_paths: &self.include_paths, targets: Vec::new() };
+        nested.visit_node(&mut node);
+        out.push(node);
+
+        for (kind, target) in nested.targets {
+            if let Some(nodes) = self.include(&kind, &target, file)? {
+                warn!(
+                    "{} is included outside top-level code; only its declarations are compiled",
+                    target.display()
+                );
+                nodes.into_iter().for_each(|n| collect_declarations(n, out));
+            }
+        }
+        Ok(())
+    }
+
+    /// Load and splice one included file
+    ///
+    /// Returns `None` when a plain `include` target cannot be read, leaving
+    /// the include to fail at runtime as PHP would.
+    fn include(&mut self, kind: &IncludeKind, target: &Path, from: &Path) -> CompileResult<Option<Vec<AstNode>>> {
+        let once = matches!(kind, IncludeKind::IncludeOnce | IncludeKind::RequireOnce);
+        if once && self.included.contains(target) {
+            debug!("Skipping {}: already included", target.display());
+            return Ok(Some(Vec::new()));
+        }
+        if self.stack.iter().any(|f| f == target) {
+            let cycle: Vec<String> = self.stack.iter()
+                .chain(std::iter::once(&target.to_path_buf()))
+                .map(|f| f.display().to_string())
+                .collect();
+            return Err(CompileError::Parse {
+                file: Some(from.to_path_buf()),
+                message: format!("Include cycle: {}", cycle.join(" -> ")),
+                line: None,
+                column: None,
+            });
+        }
+
+        let nodes = match (self.load)(target) {
+            Ok(nodes) => nodes,
+            Err(CompileError::Io(e)) => {
+                if matches!(kind, IncludeKind::Require | IncludeKind::RequireOnce) {
+                    return Err(CompileError::Parse {
+                        file: Some(from.to_path_buf()),
+                        message: format!("Failed opening required '{}': {}", target.display(), e),
+                        line: None,
+                        column: None,
+                    });
+                }
+                warn!("Failed opening '{}' for inclusion: {}", target.display(), e);
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+        self.definitions.register(&nodes, &target.display().to_string())?;
+
+        self.included.insert(target.to_path_buf());
+        self.stack.push(target.to_path_buf());
+        let nodes = self.splice(nodes, target)?;
+        self.stack.pop();
+        Ok(Some(nodes))
+    }
+}
+
+/// Evaluate a constant include target to a path string
+pub fn constant_target(expr: &Expression, file: &Path) -> Option<String> {
+    match expr {
+        Expression::Literal(Literal::String(s)) => Some(s.clone()),
+        Expression::Constant(name) => match name.trim_start_matches('\\') {
+            "__FILE__" => Some(file.display().to_string()),
+            "__DIR__" => Some(directory_of(file).display().to_string()),
+            _ => None,
+        },
+        Expression::BinaryOp { left, op: BinaryOperator::Concat, right } => {
+            Some(constant_target(left, file)? + &constant_target(right, file)?)
+        }
+        Expression::FunctionCall { name, arguments } => match (name.as_ref(), arguments.as_slice()) {
+            (Expression::Constant(function), [argument])
+                if function.trim_start_matches('\\').eq_ignore_ascii_case("dirname") =>
+            {
+                let path = constant_target(argument, file)?;
+                Some(directory_of(Path::new(&path)).display().to_string())
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Path of an include target
+///
+/// As in PHP, a relative target not starting with `./` or `../` is looked
+/// up in the include paths first; otherwise, and when no include path has
+/// it, it is resolved against the including file.
+fn resolve_target(target: &str, from: &Path, include_paths: &[PathBuf]) -> PathBuf {
+    let url = target.starts_with(PHAR_SCHEME);
+    let target = Path::new(target);
+    if target.is_absolute() || url {
+        return normalize_target(target);
+    }
+    let explicitly_relative = target.starts_with(".") || target.starts_with("..");
+    if !explicitly_relative {
+        if let Some(found) = include_paths.iter().map(|dir| dir.join(target)).find(|path| path.is_file()) {
+            return normalize(found);
+        }
+    }
+    normalize_target(&directory_of(from).join(target))
+}
+
+/// Normalize a path, keeping the scheme of a `phar://` URL
+fn normalize_target(path: &Path) -> PathBuf {
+    match path.to_str().and_then(|path| path.strip_prefix(PHAR_SCHEME)) {
+        Some(rest) => PathBuf::from(format!("{}{}", PHAR_SCHEME, normalize(rest).display())),
+        None => normalize(path),
+    }
+}
+
+fn directory_of(file: &Path) -> PathBuf {
+    match file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Include statement at the top level of a file, if its target is constant
+fn top_level_include(stmt: &Statement, file: &Path, include_paths: &[PathBuf]) -> Option<(IncludeKind, PathBuf)> {
+    match stmt {
+        Statement::Expression(expr) => match expr.as_ref() {
+            Expression::Include { kind, file: target } => {
+                Some((kind.clone(), resolve_target(&constant_target(target, file)?, file, include_paths)))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Collects constant includes below the top level
+struct NestedIncludes<'f> {
+    file: &'f Path,
+    include_paths: &'f [PathBuf],
+    targets: Vec<(IncludeKind, PathBuf)>,
+}
+
+impl VisitorMut for NestedIncludes<'_> {
+    fn visit_expression(&mut self, expr: &mut Expression) {
+        if let Expression::Include { kind, file } = expr {
+            if let Some(target) = constant_target(file, self.file) {
+                self.targets.push((kind.clone(), resolve_target(&target, self.file, self.include_paths)));
+            }
+        }
+        visit::walk_expression(self, expr);
+    }
+}
+
+/// Keep only the function and class-like declarations of a node
+pub(crate) fn collect_declarations(node: AstNode, out: &mut Vec<AstNode>) {
+    match node {
+        AstNode::Program(nodes) => nodes.into_iter().for_each(|n| collect_declarations(n, out)),
+        AstNode::Namespace(ns) => ns.statements.into_iter().for_each(|n| collect_declarations(n, out)),
+        AstNode::Function(_)
+        | AstNode::Class(_)
+        | AstNode::Interface(_)
+        | AstNode::Trait(_)
+        | AstNode::Enum(_) => out.push(node),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::ast::{FunctionDecl, Visibility};
+
+    fn include(kind: IncludeKind, target: Expression) -> AstNode {
+        AstNode::Statement(Box::new(Statement::Expression(Box::new(Expression::Include {
+            kind,
+            file: Box::new(target),
+        }))))
+    }
+
+    fn string(s: &str) -> Expression {
+        Expression::Literal(Literal::String(s.to_string()))
+    }
+
+    fn function(name: &str) -> AstNode {
+        AstNode::Function(FunctionDecl {
+            name: name.to_string(),
+            parameters: vec![],
+            return_type: None,
+            body: Box::new(Statement::Block(vec![])),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        })
+    }
+
+    fn loader(files: HashMap<&'static str, Vec<AstNode>>) -> impl FnMut(&Path) -> CompileResult<Vec<AstNode>> {
+        move |path: &Path| {
+            files.get(path.to_str().unwrap()).cloned().ok_or_else(|| {
+                CompileError::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "no such file"))
+            })
+        }
+    }
+
+    #[test]
+    fn test_constant_target() {
+        let file = Path::new("app/src/main.php");
+        let dir_concat = Expression::BinaryOp {
+            left: Box::new(Expression::Constant("__DIR__".to_string())),
+            op: BinaryOperator::Concat,
+            right: Box::new(string("/lib.php")),
+        };
+        assert_eq!(constant_target(&dir_concat, file).as_deref(), Some("app/src/lib.php"));
+
+        let dirname = Expression::FunctionCall {
+            name: Box::new(Expression::Constant("dirname".to_string())),
+            arguments: vec![Expression::Constant("__DIR__".to_string())],
+        };
+        assert_eq!(constant_target(&dirname, file).as_deref(), Some("app"));
+        assert_eq!(constant_target(&Expression::Variable("f".to_string()), file), None);
+        assert_eq!(resolve_target("../lib/util.php", file, &[]), PathBuf::from("app/lib/util.php"));
+    }
+
+    #[test]
+    fn test_bundle_targets() {
+        let file = Path::new("phar:///srv/app.phar/bin/main.php");
+        assert_eq!(resolve_target("../src/lib.php", file, &[]), PathBuf::from("phar:///srv/app.phar/src/lib.php"));
+        let dir_concat = Expression::BinaryOp {
+            left: Box::new(Expression::Constant("__DIR__".to_string())),
+            op: BinaryOperator::Concat,
+            right: Box::new(string("/../config.php")),
+        };
+        let target = constant_target(&dir_concat, file).unwrap();
+        assert_eq!(resolve_target(&target, Path::new("elsewhere.php"), &[]), PathBuf::from("phar:///srv/app.phar/config.php"));
+
+        // Included bundle files keep their top-level code
+        let config = AstNode::Statement(Box::new(Statement::Echo(vec![string("configured")])));
+        let mut resolver = IncludeResolver::new(loader(HashMap::from([
+            ("phar:///srv/app.phar/bin/main.php", vec![include(IncludeKind::Require, dir_concat)]),
+            ("phar:///srv/app.phar/config.php", vec![config, function("helper")]),
+        ])));
+        let ast = resolver.resolve(file).unwrap();
+        assert!(matches!(ast.as_slice(), [AstNode::Program(nodes)] if matches!(nodes[0], AstNode::Statement(_)) && nodes.len() == 2));
+    }
+
+    #[test]
+    fn test_include_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("lib")).unwrap();
+        std::fs::write(dir.path().join("lib/util.php"), "<?php\n").unwrap();
+        let include_paths = [dir.path().join("lib")];
+        let file = Path::new("app/main.php");
+
+        assert_eq!(resolve_target("util.php", file, &include_paths), normalize(dir.path().join("lib/util.php")));
+        assert_eq!(resolve_target("./util.php", file, &include_paths), PathBuf::from("app/util.php"));
+        assert_eq!(resolve_target("other.php", file, &include_paths), PathBuf::from("app/other.php"));
+    }
+
+    #[test]
+    fn test_splices_includes_in_order() {
+        let mut resolver = IncludeResolver::new(loader(HashMap::from([
+            ("src/main.php", vec![
+                include(IncludeKind::RequireOnce, string("lib.php")),
+                include(IncludeKind::RequireOnce, string("./lib.php")),
+                function("main_helper"),
+            ]),
+            ("src/lib.php", vec![function("lib_helper")]),
+        ])));
+
+        let ast = resolver.resolve(Path::new("src/main.php")).unwrap();
+        // Each included file is a program of its own
+        let names: Vec<&str> = ast.iter().flat_map(|n| match n {
+            AstNode::Program(nodes) => nodes.iter().collect(),
+            n => vec![n],
+        }).filter_map(|n| match n {
+            AstNode::Function(f) => Some(f.name.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(names, ["lib_helper", "main_helper"]);
+        assert!(matches!(&ast[0], AstNode::Program(nodes) if nodes.len() == 1));
+        assert_eq!(resolver.included_files().count(), 2);
+    }
+
+    #[test]
+    fn test_cycle_and_missing_files() {
+        let mut resolver = IncludeResolver::new(loader(HashMap::from([
+            ("a.php", vec![include(IncludeKind::Require, string("b.php"))]),
+            ("b.php", vec![include(IncludeKind::Include, string("a.php"))]),
+        ])));
+        let err = resolver.resolve(Path::new("a.php")).unwrap_err().to_string();
+        assert!(err.contains("Include cycle: a.php -> b.php -> a.php"), "{}", err);
+
+        let mut resolver = IncludeResolver::new(loader(HashMap::from([
+            ("a.php", vec![include(IncludeKind::Include, string("missing.php"))]),
+        ])));
+        assert_eq!(resolver.resolve(Path::new("a.php")).unwrap().len(), 1);
+
+        let mut resolver = IncludeResolver::new(loader(HashMap::from([
+            ("a.php", vec![include(IncludeKind::Require, string("missing.php"))]),
+        ])));
+        assert!(resolver.resolve(Path::new("a.php")).unwrap_err().to_string().contains("Failed opening required"));
+    }
+}
@@ -0,0 +1,1716 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Tree-walking interpreter.
+//!
+//! Executes the analyzed AST directly on the runtime's value model. It backs
+//! `php2ir --interpret` for quick runs without an LLVM toolchain, and
+//! compiled binaries call into it (through `php2ir_interp_call`) for
+//! functions whose bodies code generation does not support yet.
+//!
+//! The interpreter covers procedural PHP: scalars, arrays, functions,
+//! globals/statics, control flow and a small set of builtins. Classes,
+//! generators and references are reported as unsupported.
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::sync::OnceLock;
+use log::warn;
+use crate::ast::*;
+use crate::coercion::{self, TypeMode};
+use crate::error::CompileResult;
+use crate::exceptions;
+use crate::mixed::{self, PhpMixed};
+use crate::parser::{DefaultParser, Parser};
+use crate::runtime::{Array, ArrayType, ExitMode, Object, RuntimeConfig, RuntimeContext, RuntimeError, RuntimeErrorType, Value};
+use crate::types::{self, IntWidth};
+
+/// Result of evaluating interpreted code
+pub type InterpResult<T> = Result<T, RuntimeError>;
+
+/// Tree-walking interpreter over a parsed program
+pub struct Interpreter<'a> {
+    runtime: RuntimeContext,
+    /// Functions with the mode of the file declaring them
+    functions: HashMap<String, (&'a FunctionDecl, TypeMode)>,
+    constants: HashMap<String, Value>,
+    globals: HashMap<String, Value>,
+    statics: HashMap<(String, String), Value>,
+    int_width: IntWidth,
+    type_mode: TypeMode,
+}
+
+/// How control leaves a statement
+enum Flow {
+    Normal,
+    Break(usize),
+    Continue(usize),
+    Return(Value),
+}
+
+/// Variables of the running function, or of the global scope
+#[derive(Default)]
+struct Frame {
+    function: Option<String>,
+    locals: HashMap<String, Value>,
+    globals: HashSet<String>,
+    statics: Vec<String>,
+}
+
+/// Normalized array key
+pub(crate) enum Key {
+    Int(i64),
+    Str(String),
+}
+
+/// Numeric value of an operand
+#[derive(Clone, Copy)]
+pub(crate) enum Num {
+    Int(i64),
+    Float(f64),
+}
+
+impl<'a> Interpreter<'a> {
+    /// Create an interpreter whose exit()/die() returns instead of ending the process
+    pub fn new() -> InterpResult<Self> {
+        let mut runtime = RuntimeContext::new(RuntimeConfig {
+            exit_mode: ExitMode::Return,
+            ..Default::default()
+        });
+        runtime.init()?;
+        Ok(Self {
+            runtime,
+            functions: HashMap::new(),
+            constants: HashMap::new(),
+            globals: HashMap::new(),
+            statics: HashMap::new(),
+            int_width: IntWidth::default(),
+            type_mode: TypeMode::default(),
+        })
+    }
+
+    /// Set the width of PHP `int`
+    pub fn with_int_width(mut self, int_width: IntWidth) -> Self {
+        self.int_width = int_width;
+        self
+    }
+
+    /// Redirect echo/print output into the given writer
+    pub fn with_output(mut self, output: Box<dyn std::io::Write>) -> Self {
+        self.runtime.set_output(output);
+        self
+    }
+
+    /// Register the program's unconditional function declarations
+    ///
+    /// A top-level `declare(strict_types=1)` switches argument checking to
+    /// strict mode for the calls made in its file.
+    pub fn load(&mut self, ast: &'a [AstNode]) {
+        self.type_mode = TypeMode::of(ast);
+        self.load_declarations(ast);
+    }
+
+    fn load_declarations(&mut self, ast: &'a [AstNode]) {
+        for node in ast {
+            match node {
+                AstNode::Function(decl) => self.declare(decl),
+                AstNode::Program(nodes) => {
+                    let outer = self.type_mode.enter(nodes);
+                    self.load_declarations(nodes);
+                    self.type_mode = outer;
+                }
+                AstNode::Namespace(ns) => self.load_declarations(&ns.statements),
+                _ => {}
+            }
+        }
+    }
+
+    /// Run the program's top-level code and return its exit status
+    pub fn run(&mut self, ast: &'a [AstNode]) -> InterpResult<i32> {
+        self.load(ast);
+        let mut frame = Frame::default();
+        let result = self.execute_nodes(ast, &mut frame);
+        self.runtime.flush()?;
+        match result {
+            Ok(_) => Ok(0),
+            Err(error) if error.error_type == RuntimeErrorType::Exit => Ok(error.code),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Call a function of the loaded program (or a builtin) by name
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> InterpResult<Value> {
+        let result = self.call_function(name, args);
+        self.runtime.flush()?;
+        result
+    }
+
+    fn declare(&mut self, decl: &'a FunctionDecl) {
+        self.functions.insert(decl.name.to_lowercase(), (decl, self.type_mode));
+    }
+
+    fn execute_nodes(&mut self, nodes: &'a [AstNode], frame: &mut Frame) -> InterpResult<Flow> {
+        for node in nodes {
+            let flow = match node {
+                AstNode::Statement(stmt) => self.execute(stmt, frame)?,
+                AstNode::Expression(expr) => {
+                    self.eval(expr, frame)?;
+                    Flow::Normal
+                }
+                AstNode::Program(nodes) => {
+                    let outer = self.type_mode.enter(nodes);
+                    let flow = self.execute_nodes(nodes, frame);
+                    self.type_mode = outer;
+                    flow?
+                }
+                AstNode::Namespace(ns) => self.execute_nodes(&ns.statements, frame)?,
+                _ => Flow::Normal,
+            };
+            if !matches!(flow, Flow::Normal) {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn execute_block(&mut self, statements: &'a [Statement], frame: &mut Frame) -> InterpResult<Flow> {
+        for stmt in statements {
+            let flow = self.execute(stmt, frame)?;
+            if !matches!(flow, Flow::Normal) {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn execute(&mut self, stmt: &'a Statement, frame: &mut Frame) -> InterpResult<Flow> {
+        self.runtime.check_time_limit()?;
+        match stmt {
+            Statement::Expression(expr) => {
+                self.eval(expr, frame)?;
+            }
+            Statement::Block(statements) => return self.execute_block(statements, frame),
+            Statement::If { condition, then_branch, else_branch } => {
+                if truthy(&self.eval(condition, frame)?) {
+                    return self.execute(then_branch, frame);
+                } else if let Some(else_branch) = else_branch {
+                    return self.execute(else_branch, frame);
+                }
+            }
+            Statement::While { condition, body } => {
+                while truthy(&self.eval(condition, frame)?) {
+                    match loop_flow(self.execute(body, frame)?) {
+                        Some(Flow::Normal) => {}
+                        Some(flow) => return Ok(flow),
+                        None => break,
+                    }
+                }
+            }
+            Statement::DoWhile { body, condition } => loop {
+                match loop_flow(self.execute(body, frame)?) {
+                    Some(Flow::Normal) => {}
+                    Some(flow) => return Ok(flow),
+                    None => break,
+                }
+                if !truthy(&self.eval(condition, frame)?) {
+                    break;
+                }
+            },
+            Statement::For { init, condition, update, body } => {
+                for expr in init {
+                    self.eval(expr, frame)?;
+                }
+                loop {
+                    // Like PHP, only the last condition expression decides
+                    let mut proceed = true;
+                    for expr in condition {
+                        proceed = truthy(&self.eval(expr, frame)?);
+                    }
+                    if !proceed {
+                        break;
+                    }
+                    match loop_flow(self.execute(body, frame)?) {
+                        Some(Flow::Normal) => {}
+                        Some(flow) => return Ok(flow),
+                        None => break,
+                    }
+                    for expr in update {
+                        self.eval(expr, frame)?;
+                    }
+                }
+            }
+            Statement::Foreach { array, key, value, body } => {
+                let entries: Vec<(Value, Value)> = match self.eval(array, frame)? {
+                    Value::Array(array) => array.iter().map(|(k, v)| (k, v.clone())).collect(),
+                    other => {
+                        warn!("foreach() argument must be of type array, {} given", type_name(&other));
+                        Vec::new()
+                    }
+                };
+                for (k, v) in entries {
+                    if let Some(key) = key {
+                        *self.variable_mut(frame, key) = k;
+                    }
+                    *self.variable_mut(frame, value) = v;
+                    match loop_flow(self.execute(body, frame)?) {
+                        Some(Flow::Normal) => {}
+                        Some(flow) => return Ok(flow),
+                        None => break,
+                    }
+                }
+            }
+            Statement::Switch { expression, cases } => {
+                let subject = self.eval(expression, frame)?;
+                let mut matched = None;
+                for (i, case) in cases.iter().enumerate() {
+                    if let Some(condition) = &case.condition {
+                        if loose_equal(&subject, &self.eval(condition, frame)?) {
+                            matched = Some(i);
+                            break;
+                        }
+                    }
+                }
+                let start = matched.or_else(|| cases.iter().position(|case| case.condition.is_none()));
+                if let Some(start) = start {
+                    // Cases fall through until a break; `continue` acts like `break`
+                    for case in &cases[start..] {
+                        match self.execute_block(&case.statements, frame)? {
+                            Flow::Normal => {}
+                            Flow::Break(0) | Flow::Continue(0) => break,
+                            Flow::Break(n) => return Ok(Flow::Break(n - 1)),
+                            Flow::Continue(n) => return Ok(Flow::Continue(n - 1)),
+                            flow => return Ok(flow),
+                        }
+                    }
+                }
+            }
+            Statement::Match { expression, arms, .. } => {
+                let subject = self.eval(expression, frame)?;
+                for arm in arms {
+                    let mut hit = arm.patterns.is_empty();
+                    for pattern in &arm.patterns {
+                        if identical(&subject, &self.eval(pattern, frame)?) {
+                            hit = true;
+                            break;
+                        }
+                    }
+                    if hit {
+                        return self.execute(&arm.body, frame);
+                    }
+                }
+                return Err(error(
+                    format!("Unhandled match case {}", export(&subject)),
+                    RuntimeErrorType::InvalidOperation,
+                ));
+            }
+            Statement::Try { try_block, catch_blocks, finally_block } => {
+                let mut result = self.execute(try_block, frame);
+                if let Err(thrown) = &result {
+                    let class = exception_class(thrown);
+                    let catch = class.and_then(|class| {
+                        catch_blocks.iter().find(|catch| catch.types.iter().any(|t| catches(t, class)))
+                    });
+                    if let (Some(catch), Some(class)) = (catch, class) {
+                        if let Some(variable) = &catch.variable {
+                            let mut exception = Object::new(class.to_string());
+                            exception.set_property("message", Value::String(thrown.message.clone()));
+                            *self.variable_mut(frame, variable) = Value::Object(exception);
+                        }
+                        result = self.execute(&catch.body, frame);
+                    }
+                }
+                if let Some(finally_block) = finally_block {
+                    let flow = self.execute(finally_block, frame)?;
+                    if !matches!(flow, Flow::Normal) {
+                        return Ok(flow);
+                    }
+                }
+                return result;
+            }
+            Statement::Throw(_) => return Err(unsupported("throwing exceptions")),
+            Statement::Return(expr) => {
+                let value = match expr {
+                    Some(expr) => self.eval(expr, frame)?,
+                    None => Value::Null,
+                };
+                return Ok(Flow::Return(value));
+            }
+            Statement::Break(levels) => return Ok(Flow::Break(self.levels(levels, frame)? - 1)),
+            Statement::Continue(levels) => return Ok(Flow::Continue(self.levels(levels, frame)? - 1)),
+            Statement::Global(names) => {
+                for name in names {
+                    frame.locals.remove(name);
+                    frame.globals.insert(name.clone());
+                }
+            }
+            Statement::Static(names) => {
+                let function = frame.function.clone().unwrap_or_default();
+                for name in names {
+                    if frame.statics.contains(name) {
+                        continue;
+                    }
+                    let value = self.statics.get(&(function.clone(), name.clone())).cloned().unwrap_or(Value::Null);
+                    frame.locals.insert(name.clone(), value);
+                    frame.statics.push(name.clone());
+                }
+            }
+            Statement::Echo(expressions) => {
+                for expr in expressions {
+                    let value = self.eval(expr, frame)?;
+                    self.output(&value)?;
+                }
+            }
+            Statement::Print(expr) => {
+                let value = self.eval(expr, frame)?;
+                self.output(&value)?;
+            }
+            Statement::Unset(targets) => {
+                for target in targets {
+                    match target {
+                        Expression::Variable(name) => {
+                            if frame.function.is_none() || frame.globals.contains(name) {
+                                self.globals.remove(name);
+                            } else {
+                                frame.locals.remove(name);
+                            }
+                        }
+                        _ => return Err(unsupported("unset() of array elements")),
+                    }
+                }
+            }
+            Statement::Isset(targets) => {
+                for target in targets {
+                    self.eval_quiet(target, frame)?;
+                }
+            }
+            Statement::Empty(expr) => {
+                self.eval_quiet(expr, frame)?;
+            }
+            Statement::Die(expr) => {
+                let status = match expr {
+                    Some(expr) => match self.eval(expr, frame)? {
+                        Value::Int(status) => status as i32,
+                        message => {
+                            self.output(&message)?;
+                            0
+                        }
+                    },
+                    None => 0,
+                };
+                self.runtime.exit(status)?;
+            }
+            Statement::Declare { body, .. } => return self.execute(body, frame),
+            Statement::Declaration(node) => match node.as_ref() {
+                AstNode::Function(decl) => self.declare(decl),
+                _ => return Err(unsupported("class declarations")),
+            },
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// Number of loops a `break`/`continue` leaves (at least 1)
+    fn levels(&mut self, levels: &'a Option<Box<Expression>>, frame: &mut Frame) -> InterpResult<usize> {
+        match levels {
+            Some(expr) => match self.eval(expr, frame)? {
+                Value::Int(n) if n >= 1 => Ok(n as usize),
+                _ => Err(error("'break' operator accepts only positive integers".to_string(), RuntimeErrorType::InvalidOperation)),
+            },
+            None => Ok(1),
+        }
+    }
+
+    fn eval(&mut self, expr: &'a Expression, frame: &mut Frame) -> InterpResult<Value> {
+        match expr {
+            Expression::Literal(literal) => self.eval_literal(literal, frame),
+            Expression::Array { elements } => self.eval_array(elements, frame),
+            Expression::Variable(_) | Expression::VariableVariable(_) | Expression::ArrayAccess { .. } => {
+                match self.eval_quiet(expr, frame)? {
+                    Some(value) => Ok(value),
+                    None => {
+                        warn!("Undefined variable or index in {:?}", expr);
+                        Ok(Value::Null)
+                    }
+                }
+            }
+            Expression::Constant(name) => self.constant(name),
+            Expression::BinaryOp { left, op, right } => match op {
+                BinaryOperator::And => Ok(Value::Bool(
+                    truthy(&self.eval(left, frame)?) && truthy(&self.eval(right, frame)?),
+                )),
+                BinaryOperator::Or => Ok(Value::Bool(
+                    truthy(&self.eval(left, frame)?) || truthy(&self.eval(right, frame)?),
+                )),
+                BinaryOperator::Coalesce => self.coalesce(left, right, frame),
+                _ => {
+                    let left = self.eval(left, frame)?;
+                    let right = self.eval(right, frame)?;
+                    self.binary(op, &left, &right)
+                }
+            },
+            Expression::UnaryOp { op, expr } => self.eval_unary(op, expr, frame),
+            Expression::FunctionCall { name, arguments } => {
+                let name = match name.as_ref() {
+                    Expression::Constant(name) => name.clone(),
+                    callable => to_php_string(&self.eval(callable, frame)?)?,
+                };
+                match name.to_lowercase().as_str() {
+                    "isset" => {
+                        for argument in arguments {
+                            if matches!(self.eval_quiet(argument, frame)?, None | Some(Value::Null)) {
+                                return Ok(Value::Bool(false));
+                            }
+                        }
+                        return Ok(Value::Bool(true));
+                    }
+                    "empty" => {
+                        let value = match arguments.first() {
+                            Some(argument) => self.eval_quiet(argument, frame)?,
+                            None => None,
+                        };
+                        return Ok(Value::Bool(!value.as_ref().is_some_and(truthy)));
+                    }
+                    _ => {}
+                }
+                let mut args = Vec::with_capacity(arguments.len());
+                for argument in arguments {
+                    args.push(self.eval(argument, frame)?);
+                }
+                self.call_function(&name, args)
+            }
+            Expression::Assignment { target, op, value } => self.eval_assignment(target, op, value, frame),
+            Expression::Ternary { condition, true_expr, false_expr } => {
+                if truthy(&self.eval(condition, frame)?) {
+                    self.eval(true_expr, frame)
+                } else {
+                    self.eval(false_expr, frame)
+                }
+            }
+            Expression::ShortTernary { condition, false_expr } => {
+                let value = self.eval(condition, frame)?;
+                if truthy(&value) {
+                    Ok(value)
+                } else {
+                    self.eval(false_expr, frame)
+                }
+            }
+            Expression::NullCoalescing { left, right } => self.coalesce(left, right, frame),
+            Expression::Cast { target_type, expr } => {
+                let value = self.eval(expr, frame)?;
+                self.cast(target_type, value)
+            }
+            Expression::List { .. } => Err(unsupported("list() outside of an assignment")),
+            Expression::ArrayAppend { .. } => Err(error("Cannot use [] for reading".to_string(), RuntimeErrorType::InvalidOperation)),
+            Expression::Include { .. } => Err(unsupported("include of a runtime-computed path")),
+            Expression::Closure(_) => Err(unsupported("closures")),
+            Expression::MethodCall { .. }
+            | Expression::StaticCall { .. }
+            | Expression::PropertyAccess { .. }
+            | Expression::StaticPropertyAccess { .. }
+            | Expression::ClassConstant { .. }
+            | Expression::InstanceOf { .. }
+            | Expression::New { .. }
+            | Expression::Clone(_) => Err(unsupported("objects")),
+            Expression::Yield { .. } => Err(unsupported("generators")),
+        }
+    }
+
+    fn eval_literal(&mut self, literal: &'a Literal, frame: &mut Frame) -> InterpResult<Value> {
+        Ok(match literal {
+            Literal::Int(n) => to_runtime(self.int_width.literal(*n)),
+            Literal::Float(x) => Value::Float(*x),
+            Literal::String(s) => Value::String(s.clone()),
+            Literal::Bool(b) => Value::Bool(*b),
+            Literal::Null => Value::Null,
+            Literal::Array(elements) => return self.eval_array(elements, frame),
+        })
+    }
+
+    fn eval_array(&mut self, elements: &'a [ArrayElement], frame: &mut Frame) -> InterpResult<Value> {
+        let mut array = Array::new(ArrayType::Packed);
+        for element in elements {
+            if element.is_reference {
+                return Err(unsupported("references"));
+            }
+            if element.is_spread {
+                let Value::Array(spread) = self.eval(&element.value, frame)? else {
+                    return Err(error("Only arrays can be unpacked".to_string(), RuntimeErrorType::TypeError));
+                };
+                // Integer keys are renumbered; string keys are kept
+                for (key, value) in spread.iter() {
+                    let key = match key {
+                        Value::String(s) => Some(Key::Str(s)),
+                        _ => None,
+                    };
+                    array_set(&mut array, key, value.clone())?;
+                }
+                continue;
+            }
+            let key = match &element.key {
+                Some(key) => Some(array_key(&self.eval(key, frame)?)?),
+                None => None,
+            };
+            let value = self.eval(&element.value, frame)?;
+            array_set(&mut array, key, value)?;
+        }
+        Ok(Value::Array(array))
+    }
+
+    /// Evaluate without undefined variable/index warnings; `None` when unset
+    fn eval_quiet(&mut self, expr: &'a Expression, frame: &mut Frame) -> InterpResult<Option<Value>> {
+        match expr {
+            Expression::Variable(name) => Ok(self.variable(frame, name).cloned()),
+            Expression::VariableVariable(inner) => {
+                let name = to_php_string(&self.eval(inner, frame)?)?;
+                Ok(self.variable(frame, &name).cloned())
+            }
+            Expression::ArrayAccess { array, index } => {
+                let key = array_key(&self.eval(index, frame)?)?;
+                // Look elements of variables up in place instead of copying the array
+                let owned;
+                let container = match array.as_ref() {
+                    Expression::Variable(name) => self.variable(frame, name),
+                    array => {
+                        owned = self.eval_quiet(array, frame)?;
+                        owned.as_ref()
+                    }
+                };
+                Ok(match container {
+                    Some(Value::Array(array)) => array_get(array, &key).cloned(),
+                    Some(Value::String(s)) => match key {
+                        Key::Int(i) => {
+                            let i = if i < 0 { s.len() as i64 + i } else { i };
+                            s.as_bytes().get(i as usize).map(|&b| Value::String((b as char).to_string()))
+                        }
+                        Key::Str(_) => None,
+                    },
+                    _ => None,
+                })
+            }
+            _ => self.eval(expr, frame).map(Some),
+        }
+    }
+
+    fn coalesce(&mut self, left: &'a Expression, right: &'a Expression, frame: &mut Frame) -> InterpResult<Value> {
+        match self.eval_quiet(left, frame)? {
+            Some(Value::Null) | None => self.eval(right, frame),
+            Some(value) => Ok(value),
+        }
+    }
+
+    fn eval_unary(&mut self, op: &UnaryOperator, expr: &'a Expression, frame: &mut Frame) -> InterpResult<Value> {
+        match op {
+            UnaryOperator::Plus => Ok(num_value(to_number(&self.eval(expr, frame)?)?)),
+            UnaryOperator::Minus => {
+                let value = self.eval(expr, frame)?;
+                self.binary(&BinaryOperator::Mul, &value, &Value::Int(-1))
+            }
+            UnaryOperator::Not => Ok(Value::Bool(!truthy(&self.eval(expr, frame)?))),
+            UnaryOperator::BitwiseNot => Ok(Value::Int(!to_int(&self.eval(expr, frame)?)?)),
+            UnaryOperator::ErrorSuppress => self.eval(expr, frame),
+            UnaryOperator::PreInc | UnaryOperator::PreDec | UnaryOperator::PostInc | UnaryOperator::PostDec => {
+                let old = self.eval_quiet(expr, frame)?.unwrap_or(Value::Null);
+                let increment = matches!(op, UnaryOperator::PreInc | UnaryOperator::PostInc);
+                let new = match (&old, increment) {
+                    // Incrementing null gives 1; decrementing it leaves null
+                    (Value::Null, true) => Value::Int(1),
+                    (Value::Null, false) => Value::Null,
+                    (_, true) => self.binary(&BinaryOperator::Add, &old, &Value::Int(1))?,
+                    (_, false) => self.binary(&BinaryOperator::Sub, &old, &Value::Int(1))?,
+                };
+                self.assign(expr, new.clone(), frame)?;
+                Ok(if matches!(op, UnaryOperator::PostInc | UnaryOperator::PostDec) { old } else { new })
+            }
+        }
+    }
+
+    fn eval_assignment(
+        &mut self,
+        target: &'a Expression,
+        op: &AssignmentOperator,
+        value: &'a Expression,
+        frame: &mut Frame,
+    ) -> InterpResult<Value> {
+        let binary = match op {
+            AssignmentOperator::Assign => None,
+            AssignmentOperator::CoalesceAssign => {
+                if let Some(current) = self.eval_quiet(target, frame)? {
+                    if !matches!(current, Value::Null) {
+                        return Ok(current);
+                    }
+                }
+                None
+            }
+            AssignmentOperator::AddAssign => Some(BinaryOperator::Add),
+            AssignmentOperator::SubAssign => Some(BinaryOperator::Sub),
+            AssignmentOperator::MulAssign => Some(BinaryOperator::Mul),
+            AssignmentOperator::DivAssign => Some(BinaryOperator::Div),
+            AssignmentOperator::ModAssign => Some(BinaryOperator::Mod),
+            AssignmentOperator::PowAssign => Some(BinaryOperator::Pow),
+            AssignmentOperator::ConcatAssign => Some(BinaryOperator::Concat),
+            AssignmentOperator::BitwiseAndAssign => Some(BinaryOperator::BitwiseAnd),
+            AssignmentOperator::BitwiseOrAssign => Some(BinaryOperator::BitwiseOr),
+            AssignmentOperator::BitwiseXorAssign => Some(BinaryOperator::BitwiseXor),
+            AssignmentOperator::ShiftLeftAssign => Some(BinaryOperator::ShiftLeft),
+            AssignmentOperator::ShiftRightAssign => Some(BinaryOperator::ShiftRight),
+        };
+        let mut value = self.eval(value, frame)?;
+        if let Some(op) = binary {
+            let current = self.eval(target, frame)?;
+            value = self.binary(&op, &current, &value)?;
+        }
+        self.assign(target, value.clone(), frame)?;
+        Ok(value)
+    }
+
+    /// Store into a variable, array element or list() destructuring
+    fn assign(&mut self, target: &'a Expression, value: Value, frame: &mut Frame) -> InterpResult<()> {
+        match target {
+            Expression::Variable(name) => *self.variable_mut(frame, name) = value,
+            Expression::VariableVariable(inner) => {
+                let name = to_php_string(&self.eval(inner, frame)?)?;
+                *self.variable_mut(frame, &name) = value;
+            }
+            Expression::ArrayAccess { array, index } => {
+                let key = array_key(&self.eval(index, frame)?)?;
+                self.assign_element(array, Some(key), value, frame)?;
+            }
+            Expression::ArrayAppend { array } => self.assign_element(array, None, value, frame)?,
+            Expression::List { variables } => {
+                let array = match value {
+                    Value::Array(array) => array,
+                    _ => Array::new(ArrayType::Packed),
+                };
+                for (i, variable) in variables.iter().enumerate() {
+                    let element = array_get(&array, &Key::Int(i as i64)).cloned().unwrap_or(Value::Null);
+                    self.assign(variable, element, frame)?;
+                }
+            }
+            _ => return Err(unsupported("this assignment target")),
+        }
+        Ok(())
+    }
+
+    /// Store into an element of an array, appending when `key` is `None`
+    ///
+    /// A missing or null array is created, so nested writes build the
+    /// arrays they go through.
+    fn assign_element(&mut self, array: &'a Expression, key: Option<Key>, value: Value, frame: &mut Frame) -> InterpResult<()> {
+        if let Expression::Variable(name) = array {
+            // Update arrays held in variables in place
+            let slot = self.variable_mut(frame, name);
+            if matches!(slot, Value::Null) {
+                *slot = Value::Array(Array::new(ArrayType::Packed));
+            }
+            return match slot {
+                Value::Array(container) => array_set(container, key, value),
+                other => Err(error(
+                    format!("Cannot use a scalar value of type {} as an array", type_name(other)),
+                    RuntimeErrorType::TypeError,
+                )),
+            };
+        }
+        let mut container = match self.eval_quiet(array, frame)? {
+            Some(Value::Array(array)) => array,
+            None | Some(Value::Null) => Array::new(ArrayType::Packed),
+            Some(other) => {
+                return Err(error(
+                    format!("Cannot use a scalar value of type {} as an array", type_name(&other)),
+                    RuntimeErrorType::TypeError,
+                ))
+            }
+        };
+        array_set(&mut container, key, value)?;
+        self.assign(array, Value::Array(container), frame)
+    }
+
+    fn variable<'f>(&'f self, frame: &'f Frame, name: &str) -> Option<&'f Value> {
+        if frame.function.is_none() || frame.globals.contains(name) {
+            self.globals.get(name)
+        } else {
+            frame.locals.get(name)
+        }
+    }
+
+    fn variable_mut<'f>(&'f mut self, frame: &'f mut Frame, name: &str) -> &'f mut Value {
+        if frame.function.is_none() || frame.globals.contains(name) {
+            self.globals.entry(name.to_string()).or_insert(Value::Null)
+        } else {
+            frame.locals.entry(name.to_string()).or_insert(Value::Null)
+        }
+    }
+
+    fn constant(&self, name: &str) -> InterpResult<Value> {
+        if let Some(value) = self.constants.get(name) {
+            return Ok(value.clone());
+        }
+        // Unqualified constants fall back to the global namespace
+        let global = name.rsplit('\\').next().unwrap_or(name);
+        if let Some(value) = self.constants.get(global) {
+            return Ok(value.clone());
+        }
+        if let Some(value) = self.int_width.constant(global) {
+            return Ok(Value::Int(value));
+        }
+        Ok(match global.to_lowercase().as_str() {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            "null" => Value::Null,
+            _ => match global {
+                "PHP_EOL" => Value::String("\n".to_string()),
+                "PHP_VERSION" => Value::String(crate::PHP_VERSION.to_string()),
+                "PHP_FLOAT_EPSILON" => Value::Float(f64::EPSILON),
+                "PHP_FLOAT_MAX" => Value::Float(f64::MAX),
+                "M_PI" => Value::Float(std::f64::consts::PI),
+                "NAN" => Value::Float(f64::NAN),
+                "INF" => Value::Float(f64::INFINITY),
+                _ => {
+                    return Err(error(
+                        format!("Undefined constant \"{}\"", name),
+                        RuntimeErrorType::InvalidOperation,
+                    ))
+                }
+            },
+        })
+    }
+
+    fn call_function(&mut self, name: &str, args: Vec<Value>) -> InterpResult<Value> {
+        let lower = name.to_lowercase();
+        // Unqualified calls fall back to the global namespace
+        let global = lower.rsplit('\\').next().unwrap_or(&lower).to_string();
+        if let Some(&(decl, mode)) = self.functions.get(&lower).or_else(|| self.functions.get(&global)) {
+            return self.call_user(decl, mode, args);
+        }
+        if let Some(result) = self.call_builtin(&global, &args)? {
+            return Ok(result);
+        }
+        self.runtime.call_function(&global, &args)
+    }
+
+    /// Call a user function; its arguments are checked in the caller's
+    /// mode and its body runs in `mode`, that of its own file
+    fn call_user(&mut self, decl: &'a FunctionDecl, mode: TypeMode, mut args: Vec<Value>) -> InterpResult<Value> {
+        let mut frame = Frame {
+            function: Some(decl.name.to_lowercase()),
+            ..Default::default()
+        };
+        let passed = args.len();
+        let mut args = args.drain(..);
+        for (i, parameter) in decl.parameters.iter().enumerate() {
+            if parameter.is_reference {
+                return Err(unsupported("by-reference parameters"));
+            }
+            let value = if parameter.is_variadic {
+                let mut rest = Array::new(ArrayType::Packed);
+                args.by_ref().for_each(|arg| rest.push(arg));
+                Value::Array(rest)
+            } else if let Some(arg) = args.next() {
+                coercion::coerce_argument(arg, parameter, self.type_mode).map_err(|given| {
+                    error(
+                        coercion::argument_error(&decl.name, i + 1, parameter, given),
+                        RuntimeErrorType::TypeError,
+                    )
+                })?
+            } else if let Some(default) = &parameter.default_value {
+                self.eval(default, &mut frame)?
+            } else {
+                let required = decl.parameters.iter()
+                    .filter(|p| p.default_value.is_none() && !p.is_variadic)
+                    .count();
+                return Err(error(
+                    format!(
+                        "Too few arguments to function {}(), {} passed and {} {} expected",
+                        decl.name, passed, if required < decl.parameters.len() { "at least" } else { "exactly" }, required
+                    ),
+                    RuntimeErrorType::InvalidOperation,
+                ).with_location(format!("argument {}", i + 1)));
+            };
+            frame.locals.insert(parameter.name.clone(), value);
+        }
+
+        let caller = std::mem::replace(&mut self.type_mode, mode);
+        let result = self.execute(&decl.body, &mut frame);
+        self.type_mode = caller;
+
+        let function = frame.function.clone().unwrap_or_default();
+        for name in frame.statics.drain(..) {
+            let value = frame.locals.remove(&name).unwrap_or(Value::Null);
+            self.statics.insert((function.clone(), name), value);
+        }
+
+        match result? {
+            Flow::Return(value) => Ok(value),
+            _ => Ok(Value::Null),
+        }
+    }
+
+    /// Builtins implemented by the interpreter; `None` defers to the runtime
+    fn call_builtin(&mut self, name: &str, args: &[Value]) -> InterpResult<Option<Value>> {
+        let arg = |i: usize| args.get(i).cloned().unwrap_or(Value::Null);
+        Ok(Some(match name {
+            "define" => {
+                let name = to_php_string(&arg(0))?;
+                let defined = !self.constants.contains_key(&name);
+                if defined {
+                    self.constants.insert(name, arg(1));
+                } else {
+                    warn!("Constant {} already defined", name);
+                }
+                Value::Bool(defined)
+            }
+            "defined" => Value::Bool(self.constant(&to_php_string(&arg(0))?).is_ok()),
+            "constant" => self.constant(&to_php_string(&arg(0))?)?,
+            "function_exists" => {
+                let name = to_php_string(&arg(0))?.to_lowercase();
+                let name = name.trim_start_matches('\\');
+                Value::Bool(self.functions.contains_key(name))
+            }
+            "intdiv" => {
+                let (a, b) = (to_int(&arg(0))?, to_int(&arg(1))?);
+                if b == 0 {
+                    return Err(error("Division by zero".to_string(), RuntimeErrorType::DivisionByZero));
+                }
+                match a.checked_div(b) {
+                    Some(n) => Value::Int(n),
+                    None => {
+                        return Err(error(
+                            "Division of PHP_INT_MIN by -1 is not an integer".to_string(),
+                            RuntimeErrorType::Arithmetic,
+                        ))
+                    }
+                }
+            }
+            // IEEE division, giving INF or NAN for a zero divisor
+            "fdiv" => Value::Float(to_float(&arg(0))? / to_float(&arg(1))?),
+            "max" | "min" => {
+                let values: Vec<Value> = match args {
+                    [Value::Array(array)] => array.iter().map(|(_, v)| v.clone()).collect(),
+                    _ => args.to_vec(),
+                };
+                let wanted = if name == "max" { Ordering::Greater } else { Ordering::Less };
+                let mut best: Option<Value> = None;
+                for value in values {
+                    if best.as_ref().is_none_or(|b| compare(&value, b) == Some(wanted)) {
+                        best = Some(value);
+                    }
+                }
+                best.ok_or_else(|| error(format!("{}() expects at least 1 argument", name), RuntimeErrorType::InvalidOperation))?
+            }
+            "floor" => Value::Float(to_float(&arg(0))?.floor()),
+            "ceil" => Value::Float(to_float(&arg(0))?.ceil()),
+            "round" => Value::Float(to_float(&arg(0))?.round()),
+            "sqrt" => Value::Float(to_float(&arg(0))?.sqrt()),
+            "strtoupper" => Value::String(to_php_string(&arg(0))?.to_uppercase()),
+            "strtolower" => Value::String(to_php_string(&arg(0))?.to_lowercase()),
+            "str_repeat" => Value::String(to_php_string(&arg(0))?.repeat(to_int(&arg(1))?.max(0) as usize)),
+            "implode" => {
+                let (separator, array) = match (arg(0), arg(1)) {
+                    (Value::Array(array), separator) | (separator, Value::Array(array)) => (separator, array),
+                    _ => return Err(error("implode(): Argument must be of type array".to_string(), RuntimeErrorType::TypeError)),
+                };
+                let separator = match separator {
+                    Value::Null => String::new(),
+                    other => to_php_string(&other)?,
+                };
+                let parts: Vec<String> = array.iter().map(|(_, v)| to_php_string(v)).collect::<InterpResult<_>>()?;
+                Value::String(parts.join(&separator))
+            }
+            "array_keys" | "array_values" => {
+                let mut result = Array::new(ArrayType::Packed);
+                if let Value::Array(array) = arg(0) {
+                    for (k, v) in array.iter() {
+                        result.push(if name == "array_keys" { k } else { v.clone() });
+                    }
+                }
+                Value::Array(result)
+            }
+            "in_array" => match arg(1) {
+                Value::Array(array) => {
+                    let needle = arg(0);
+                    let strict = truthy(&arg(2));
+                    Value::Bool(array.iter().any(|(_, v)| if strict { identical(v, &needle) } else { loose_equal(v, &needle) }))
+                }
+                _ => Value::Bool(false),
+            },
+            "is_int" | "is_integer" => Value::Bool(matches!(arg(0), Value::Int(_))),
+            "is_float" => Value::Bool(matches!(arg(0), Value::Float(_))),
+            "is_string" => Value::Bool(matches!(arg(0), Value::String(_))),
+            "is_bool" => Value::Bool(matches!(arg(0), Value::Bool(_))),
+            "is_array" => Value::Bool(matches!(arg(0), Value::Array(_))),
+            "is_null" => Value::Bool(matches!(arg(0), Value::Null)),
+            "is_numeric" => Value::Bool(match arg(0) {
+                Value::Int(_) | Value::Float(_) => true,
+                Value::String(s) => parse_numeric(&s).is_some_and(|(_, whole)| whole),
+                _ => false,
+            }),
+            "intval" => Value::Int(to_int(&arg(0))?),
+            "floatval" => Value::Float(to_float(&arg(0))?),
+            "strval" => Value::String(to_php_string(&arg(0))?),
+            "boolval" => Value::Bool(truthy(&arg(0))),
+            "gettype" => Value::String(match arg(0) {
+                Value::Float(_) => "double".to_string(),
+                Value::Null => "NULL".to_string(),
+                other => type_name(&other).to_string(),
+            }),
+            _ => return Ok(None),
+        }))
+    }
+
+    fn cast(&self, target: &types::Type, value: Value) -> InterpResult<Value> {
+        Ok(match target {
+            types::Type::Int => Value::Int(to_int(&value)?),
+            types::Type::Float => Value::Float(to_float(&value)?),
+            types::Type::Bool => Value::Bool(truthy(&value)),
+            types::Type::String => Value::String(to_php_string(&value)?),
+            types::Type::Null => Value::Null,
+            types::Type::Array(_) | types::Type::AssociativeArray(_) => match value {
+                Value::Array(array) => Value::Array(array),
+                Value::Null => Value::Array(Array::new(ArrayType::Packed)),
+                scalar => {
+                    let mut array = Array::new(ArrayType::Packed);
+                    array.push(scalar);
+                    Value::Array(array)
+                }
+            },
+            _ => return Err(unsupported("object casts")),
+        })
+    }
+
+    fn binary(&self, op: &BinaryOperator, left: &Value, right: &Value) -> InterpResult<Value> {
+        binary(op, left, right, self.int_width)
+    }
+
+    fn output(&self, value: &Value) -> InterpResult<()> {
+        self.runtime.print(&Value::String(to_php_string(value)?))
+    }
+}
+
+/// Apply a binary operator to two values with PHP semantics
+///
+/// Shared with the runtime of compiled code, which uses it for operands
+/// whose types are only known at run time.
+pub(crate) fn binary(op: &BinaryOperator, left: &Value, right: &Value, int_width: IntWidth) -> InterpResult<Value> {
+    Ok(match op {
+        BinaryOperator::Add | BinaryOperator::Sub | BinaryOperator::Mul => {
+            if let (BinaryOperator::Add, Value::Array(a), Value::Array(b)) = (op, left, right) {
+                // Array union keeps the left-hand keys
+                let mut union = a.clone();
+                for (key, value) in b.iter() {
+                    let key = array_key(&key)?;
+                    if array_get(&union, &key).is_none() {
+                        array_set(&mut union, Some(key), value.clone())?;
+                    }
+                }
+                return Ok(Value::Array(union));
+            }
+            match (to_number(left)?, to_number(right)?) {
+                (Num::Int(a), Num::Int(b)) => to_runtime(match op {
+                    BinaryOperator::Add => int_width.add(a, b),
+                    BinaryOperator::Sub => int_width.sub(a, b),
+                    _ => int_width.mul(a, b),
+                }),
+                (a, b) => {
+                    let (a, b) = (num_float(a), num_float(b));
+                    Value::Float(match op {
+                        BinaryOperator::Add => a + b,
+                        BinaryOperator::Sub => a - b,
+                        _ => a * b,
+                    })
+                }
+            }
+        }
+        BinaryOperator::Div => {
+            let (a, b) = (to_number(left)?, to_number(right)?);
+            if num_float(b) == 0.0 {
+                return Err(error("Division by zero".to_string(), RuntimeErrorType::DivisionByZero));
+            }
+            match (a, b) {
+                (Num::Int(a), Num::Int(b)) if a.checked_rem(b) == Some(0) => Value::Int(a / b),
+                (a, b) => Value::Float(num_float(a) / num_float(b)),
+            }
+        }
+        BinaryOperator::Mod => {
+            let (a, b) = (to_int(left)?, to_int(right)?);
+            if b == 0 {
+                return Err(error("Modulo by zero".to_string(), RuntimeErrorType::DivisionByZero));
+            }
+            Value::Int(a.wrapping_rem(b))
+        }
+        BinaryOperator::Pow => match (to_number(left)?, to_number(right)?) {
+            (Num::Int(a), Num::Int(b)) if b >= 0 => match u32::try_from(b).ok().and_then(|b| a.checked_pow(b)) {
+                Some(n) if int_width.fits(n) => Value::Int(n),
+                _ => Value::Float((a as f64).powf(b as f64)),
+            },
+            (a, b) => Value::Float(num_float(a).powf(num_float(b))),
+        },
+        BinaryOperator::Concat => Value::String(to_php_string(left)? + &to_php_string(right)?),
+        BinaryOperator::Equal => Value::Bool(loose_equal(left, right)),
+        BinaryOperator::NotEqual => Value::Bool(!loose_equal(left, right)),
+        BinaryOperator::Identical => Value::Bool(identical(left, right)),
+        BinaryOperator::NotIdentical => Value::Bool(!identical(left, right)),
+        BinaryOperator::Less => Value::Bool(compare(left, right) == Some(Ordering::Less)),
+        BinaryOperator::LessEqual => Value::Bool(matches!(compare(left, right), Some(Ordering::Less | Ordering::Equal))),
+        BinaryOperator::Greater => Value::Bool(compare(left, right) == Some(Ordering::Greater)),
+        BinaryOperator::GreaterEqual => Value::Bool(matches!(compare(left, right), Some(Ordering::Greater | Ordering::Equal))),
+        BinaryOperator::Spaceship => Value::Int(match compare(left, right) {
+            Some(Ordering::Less) => -1,
+            Some(Ordering::Equal) => 0,
+            _ => 1,
+        }),
+        BinaryOperator::And => Value::Bool(truthy(left) && truthy(right)),
+        BinaryOperator::Or => Value::Bool(truthy(left) || truthy(right)),
+        BinaryOperator::Xor => Value::Bool(truthy(left) != truthy(right)),
+        BinaryOperator::BitwiseAnd => Value::Int(to_int(left)? & to_int(right)?),
+        BinaryOperator::BitwiseOr => Value::Int(to_int(left)? | to_int(right)?),
+        BinaryOperator::BitwiseXor => Value::Int(to_int(left)? ^ to_int(right)?),
+        BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight => {
+            let (a, b) = (to_int(left)?, to_int(right)?);
+            if b < 0 {
+                return Err(error("Bit shift by negative number".to_string(), RuntimeErrorType::InvalidOperation));
+            }
+            let bits = int_width.bits() as i64;
+            Value::Int(match op {
+                BinaryOperator::ShiftLeft if b >= bits => 0,
+                BinaryOperator::ShiftLeft => a << b,
+                _ if b >= bits => if a < 0 { -1 } else { 0 },
+                _ => a >> b,
+            })
+        }
+        BinaryOperator::Coalesce => match left {
+            Value::Null => right.clone(),
+            _ => left.clone(),
+        },
+    })
+}
+
+/// Execute a function of a PHP program from compiled code
+///
+/// `source` is the program text embedded by the code generator, parsed on
+/// the first call, and `function` the name of a function whose body was not
+/// compiled. The boxed arguments in `argv` are borrowed and the boxed result
+/// is returned. Errors are raised as PHP raises them: as exceptions the
+/// caller may catch, or as fatal errors.
+///
+/// # Safety
+///
+/// `source` and `function` must be NUL-terminated strings, `source` the
+/// same on every call, and `argv` must point to `argc` live values (or be
+/// null when `argc` is 0).
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php2ir_interp_call(
+    source: *const c_char,
+    function: *const c_char,
+    argc: c_int,
+    argv: *const PhpMixed,
+) -> PhpMixed {
+    static PROGRAM: OnceLock<Result<Vec<AstNode>, String>> = OnceLock::new();
+    let program = PROGRAM.get_or_init(|| {
+        load_source(&CStr::from_ptr(source).to_string_lossy()).map_err(|e| e.to_string())
+    });
+    let function = CStr::from_ptr(function).to_string_lossy();
+    let args = if argv.is_null() {
+        &[][..]
+    } else {
+        std::slice::from_raw_parts(argv, argc.max(0) as usize)
+    };
+
+    let result = program.as_ref()
+        .map_err(|e| error(e.clone(), RuntimeErrorType::InvalidOperation))
+        .and_then(|ast| {
+            let args = args.iter()
+                .map(|&arg| mixed::to_value(arg).ok_or_else(|| unsupported("objects")))
+                .collect::<InterpResult<Vec<_>>>()?;
+            let mut interpreter = Interpreter::new()?.with_int_width(crate::runtime::int_width());
+            interpreter.load(ast);
+            interpreter.call(&function, args)
+        });
+    match result {
+        Ok(value) => mixed::from_value(value),
+        Err(e) => exceptions::raise(&e),
+    }
+}
+
+/// Parse and annotate PHP source the same way the compiler does
+pub fn load_source(source: &str) -> CompileResult<Vec<AstNode>> {
+    let mut ast = DefaultParser::new().parse(source)?;
+    crate::compiler::annotate(&mut ast);
+    Ok(ast)
+}
+
+fn error(message: String, error_type: RuntimeErrorType) -> RuntimeError {
+    RuntimeError::new(message, error_type)
+}
+
+fn unsupported(what: &str) -> RuntimeError {
+    error(format!("The interpreter does not support {} yet", what), RuntimeErrorType::InvalidOperation)
+}
+
+/// Map loop-body flow to the flow seen by the loop: `None` leaves the loop
+fn loop_flow(flow: Flow) -> Option<Flow> {
+    match flow {
+        Flow::Break(0) => None,
+        Flow::Break(n) => Some(Flow::Break(n - 1)),
+        Flow::Continue(0) => Some(Flow::Normal),
+        Flow::Continue(n) => Some(Flow::Continue(n - 1)),
+        flow => Some(flow),
+    }
+}
+
+/// PHP class of the exception a runtime error stands for
+fn exception_class(error: &RuntimeError) -> Option<&'static str> {
+    match error.error_type {
+        RuntimeErrorType::DivisionByZero => Some("DivisionByZeroError"),
+        RuntimeErrorType::Arithmetic => Some("ArithmeticError"),
+        RuntimeErrorType::TypeError => Some("TypeError"),
+        RuntimeErrorType::InvalidOperation | RuntimeErrorType::UndefinedFunction => Some("Error"),
+        _ => None,
+    }
+}
+
+/// Whether a catch type matches an exception class
+fn catches(typ: &types::Type, class: &str) -> bool {
+    let types::Type::Object(name) = typ else { return false };
+    let name = name.rsplit('\\').next().unwrap_or(name).to_lowercase();
+    name == "throwable" || name == "error" || name == class.to_lowercase()
+        || (name == "arithmeticerror" && class == "DivisionByZeroError")
+}
+
+fn to_runtime(value: types::Value) -> Value {
+    match value {
+        types::Value::Int(n) => Value::Int(n),
+        types::Value::Float(x) => Value::Float(x),
+        _ => Value::Null,
+    }
+}
+
+pub(crate) fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Int(_) => "int",
+        Value::Float(_) => "float",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+        Value::Resource(_) => "resource",
+    }
+}
+
+pub(crate) fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Int(n) => *n != 0,
+        Value::Float(x) => *x != 0.0,
+        Value::String(s) => !s.is_empty() && s != "0",
+        Value::Array(array) => !array.is_empty(),
+        Value::Object(_) | Value::Resource(_) => true,
+    }
+}
+
+/// Parse a leading number; the flag tells whether the whole string was numeric
+pub(crate) fn parse_numeric(s: &str) -> Option<(Num, bool)> {
+    let trimmed = s.trim_start_matches([' ', '\t', '\n', '\r', '\x0b', '\x0c']);
+    let bytes = trimmed.as_bytes();
+    let mut end = 0;
+    if matches!(bytes.first(), Some(b'+' | b'-')) {
+        end += 1;
+    }
+    let digits_start = end;
+    while bytes.get(end).is_some_and(u8::is_ascii_digit) {
+        end += 1;
+    }
+    let mut is_float = false;
+    if bytes.get(end) == Some(&b'.') {
+        let mut fraction = end + 1;
+        while bytes.get(fraction).is_some_and(u8::is_ascii_digit) {
+            fraction += 1;
+        }
+        if fraction > end + 1 || end > digits_start {
+            is_float = true;
+            end = fraction;
+        }
+    }
+    if end == digits_start || (end == digits_start + 1 && bytes[digits_start] == b'.') {
+        return None;
+    }
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let mut exponent = end + 1;
+        if matches!(bytes.get(exponent), Some(b'+' | b'-')) {
+            exponent += 1;
+        }
+        if bytes.get(exponent).is_some_and(u8::is_ascii_digit) {
+            while bytes.get(exponent).is_some_and(u8::is_ascii_digit) {
+                exponent += 1;
+            }
+            is_float = true;
+            end = exponent;
+        }
+    }
+    let text = &trimmed[..end];
+    let whole = trimmed[end..].trim_end_matches([' ', '\t', '\n', '\r', '\x0b', '\x0c']).is_empty();
+    let number = match text.parse::<i64>() {
+        Ok(n) if !is_float => Num::Int(n),
+        _ => Num::Float(text.parse().ok()?),
+    };
+    Some((number, whole))
+}
+
+fn to_number(value: &Value) -> InterpResult<Num> {
+    Ok(match value {
+        Value::Null => Num::Int(0),
+        Value::Bool(b) => Num::Int(*b as i64),
+        Value::Int(n) => Num::Int(*n),
+        Value::Float(x) => Num::Float(*x),
+        Value::String(s) => match parse_numeric(s) {
+            Some((number, whole)) => {
+                if !whole {
+                    warn!("A non-numeric value encountered: \"{}\"", s);
+                }
+                number
+            }
+            None => {
+                warn!("A non-numeric value encountered: \"{}\"", s);
+                Num::Int(0)
+            }
+        },
+        other => {
+            return Err(error(
+                format!("Unsupported operand types: {}", type_name(other)),
+                RuntimeErrorType::TypeError,
+            ))
+        }
+    })
+}
+
+fn num_float(number: Num) -> f64 {
+    match number {
+        Num::Int(n) => n as f64,
+        Num::Float(x) => x,
+    }
+}
+
+fn num_value(number: Num) -> Value {
+    match number {
+        Num::Int(n) => Value::Int(n),
+        Num::Float(x) => Value::Float(x),
+    }
+}
+
+pub(crate) fn to_int(value: &Value) -> InterpResult<i64> {
+    Ok(match value {
+        Value::Array(array) => !array.is_empty() as i64,
+        _ => match to_number(value)? {
+            Num::Int(n) => n,
+            Num::Float(x) if x.is_finite() => x as i64,
+            Num::Float(_) => 0,
+        },
+    })
+}
+
+pub(crate) fn to_float(value: &Value) -> InterpResult<f64> {
+    Ok(num_float(to_number(value)?))
+}
+
+/// String conversion as done by echo and string concatenation
+pub(crate) fn to_php_string(value: &Value) -> InterpResult<String> {
+    Ok(match value {
+        Value::Null | Value::Bool(false) => String::new(),
+        Value::Bool(true) => "1".to_string(),
+        Value::Int(n) => n.to_string(),
+        Value::Float(x) => format_float(*x),
+        Value::String(s) => s.clone(),
+        Value::Array(_) => {
+            warn!("Array to string conversion");
+            "Array".to_string()
+        }
+        Value::Object(_) => {
+            return Err(error(
+                "Object could not be converted to string".to_string(),
+                RuntimeErrorType::TypeError,
+            ))
+        }
+        Value::Resource(resource) => format!("Resource id #{}", resource.get_id()),
+    })
+}
+
+/// Format a float like PHP with `precision=14`
+pub(crate) fn format_float(x: f64) -> String {
+    if x.is_nan() {
+        return "NAN".to_string();
+    }
+    if x.is_infinite() {
+        return if x > 0.0 { "INF" } else { "-INF" }.to_string();
+    }
+    if x == 0.0 {
+        return if x.is_sign_negative() { "-0" } else { "0" }.to_string();
+    }
+    let scientific = format!("{:.13e}", x);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+    if !(-5..15).contains(&exponent) {
+        let mantissa = if mantissa.contains('.') { mantissa.to_string() } else { format!("{}.0", mantissa) };
+        return format!("{}E{}{}", mantissa, if exponent < 0 { '-' } else { '+' }, exponent.abs());
+    }
+    let sign = if x < 0.0 { "-" } else { "" };
+    let digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+    if exponent < 0 {
+        return format!("{}0.{}{}", sign, "0".repeat((-exponent - 1) as usize), digits);
+    }
+    let point = exponent as usize + 1;
+    if digits.len() <= point {
+        format!("{}{}{}", sign, digits, "0".repeat(point - digits.len()))
+    } else {
+        format!("{}{}.{}", sign, &digits[..point], &digits[point..])
+    }
+}
+
+/// Readable form of a value for error messages
+fn export(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("'{}'", s),
+        other => to_php_string(other).unwrap_or_else(|_| type_name(other).to_string()),
+    }
+}
+
+fn identical(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Null, Value::Null) => true,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Int(a), Value::Int(b)) => a == b,
+        (Value::Float(a), Value::Float(b)) => a == b,
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len()
+                && a.iter().zip(b.iter()).all(|((ka, va), (kb, vb))| identical(&ka, &kb) && identical(va, vb))
+        }
+        _ => false,
+    }
+}
+
+fn loose_equal(left: &Value, right: &Value) -> bool {
+    compare(left, right) == Some(Ordering::Equal)
+}
+
+/// PHP 8 loose comparison; `None` when the operands are not comparable
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::String(a), Value::String(b)) => match (parse_numeric(a), parse_numeric(b)) {
+            (Some((x, true)), Some((y, true))) => compare_numbers(x, y),
+            _ => Some(a.cmp(b)),
+        },
+        (Value::Null, Value::String(s)) => Some("".cmp(s.as_str())),
+        (Value::String(s), Value::Null) => Some(s.as_str().cmp("")),
+        (Value::Bool(_) | Value::Null, _) | (_, Value::Bool(_) | Value::Null) => Some(truthy(left).cmp(&truthy(right))),
+        (Value::Int(_) | Value::Float(_), Value::String(s)) => match parse_numeric(s) {
+            Some((number, true)) => compare_numbers(to_number(left).ok()?, number),
+            _ => Some(to_php_string(left).ok()?.as_str().cmp(s.as_str())),
+        },
+        (Value::String(_), Value::Int(_) | Value::Float(_)) => compare(right, left).map(Ordering::reverse),
+        (Value::Int(_) | Value::Float(_), Value::Int(_) | Value::Float(_)) => {
+            compare_numbers(to_number(left).ok()?, to_number(right).ok()?)
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            if a.len() != b.len() {
+                return Some(a.len().cmp(&b.len()));
+            }
+            for (key, value) in a.iter() {
+                let other = array_get(b, &array_key(&key).ok()?)?;
+                match compare(value, other)? {
+                    Ordering::Equal => {}
+                    ordering => return Some(ordering),
+                }
+            }
+            Some(Ordering::Equal)
+        }
+        (Value::Array(_), _) => Some(Ordering::Greater),
+        (_, Value::Array(_)) => Some(Ordering::Less),
+        _ => None,
+    }
+}
+
+fn compare_numbers(left: Num, right: Num) -> Option<Ordering> {
+    match (left, right) {
+        (Num::Int(a), Num::Int(b)) => Some(a.cmp(&b)),
+        (a, b) => num_float(a).partial_cmp(&num_float(b)),
+    }
+}
+
+pub(crate) fn array_key(key: &Value) -> InterpResult<Key> {
+    Ok(match key {
+        Value::Int(n) => Key::Int(*n),
+        Value::Bool(b) => Key::Int(*b as i64),
+        Value::Float(x) => Key::Int(*x as i64),
+        Value::Null => Key::Str(String::new()),
+        Value::String(s) => match s.parse::<i64>() {
+            Ok(n) if n.to_string() == *s => Key::Int(n),
+            _ => Key::Str(s.clone()),
+        },
+        other => {
+            return Err(error(
+                format!("Illegal offset type: {}", type_name(other)),
+                RuntimeErrorType::TypeError,
+            ))
+        }
+    })
+}
+
+impl Key {
+    pub(crate) fn to_key_string(&self) -> String {
+        match self {
+            Key::Int(n) => n.to_string(),
+            Key::Str(s) => s.clone(),
+        }
+    }
+}
+
+pub(crate) fn array_get<'v>(array: &'v Array, key: &Key) -> Option<&'v Value> {
+    match key {
+        Key::Int(n) if array.is_packed() => usize::try_from(*n).ok().and_then(|i| array.get(i)),
+        key => array.get_by_key(&key.to_key_string()),
+    }
+}
+
+/// Store an element; `None` appends with the next integer key
+pub(crate) fn array_set(array: &mut Array, key: Option<Key>, value: Value) -> InterpResult<()> {
+    if array.is_packed() {
+        match key {
+            Some(Key::Int(n)) if n >= 0 && (n as usize) < array.len() => return array.set(n as usize, value),
+            None => {
+                array.push(value);
+                return Ok(());
+            }
+            Some(Key::Int(n)) if n >= 0 && n as usize == array.len() => {
+                array.push(value);
+                return Ok(());
+            }
+            _ => {
+                // Non-sequential keys need a hash map
+                let mut map = Array::new(ArrayType::Associative);
+                for (k, v) in array.iter() {
+                    map.set_by_key(&to_php_string(&k)?, v.clone())?;
+                }
+                *array = map;
+            }
+        }
+    }
+    let key = match key {
+        Some(key) => key.to_key_string(),
+        None => {
+            let next = array.iter()
+                .filter_map(|(k, _)| match k {
+                    Value::Int(n) => Some(n + 1),
+                    _ => None,
+                })
+                .max()
+                .unwrap_or(0)
+                .max(0);
+            next.to_string()
+        }
+    };
+    array.set_by_key(&key, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::OutputBuffer;
+
+    fn int(n: i64) -> Expression {
+        Expression::Literal(Literal::Int(n))
+    }
+
+    fn var(name: &str) -> Expression {
+        Expression::Variable(name.to_string())
+    }
+
+    fn binary(left: Expression, op: BinaryOperator, right: Expression) -> Expression {
+        Expression::BinaryOp { left: Box::new(left), op, right: Box::new(right) }
+    }
+
+    fn statement(stmt: Statement) -> AstNode {
+        AstNode::Statement(Box::new(stmt))
+    }
+
+    fn echo(expr: Expression) -> Statement {
+        Statement::Echo(vec![expr, Expression::Constant("PHP_EOL".to_string())])
+    }
+
+    fn run(ast: &[AstNode]) -> (i32, String) {
+        let output = OutputBuffer::new();
+        let status = Interpreter::new().unwrap()
+            .with_output(Box::new(output.clone()))
+            .run(ast)
+            .unwrap();
+        (status, output.contents())
+    }
+
+    #[test]
+    fn test_recursive_function() {
+        // function fact($n) { if ($n <= 1) return 1; return $n * fact($n - 1); }
+        let fact = AstNode::Function(FunctionDecl {
+            name: "fact".to_string(),
+            parameters: vec![Parameter {
+                name: "n".to_string(),
+                typ: None,
+                default_value: None,
+                is_reference: false,
+                is_variadic: false,
+            }],
+            return_type: None,
+            body: Box::new(Statement::Block(vec![
+                Statement::If {
+                    condition: Box::new(binary(var("n"), BinaryOperator::LessEqual, int(1))),
+                    then_branch: Box::new(Statement::Return(Some(Box::new(int(1))))),
+                    else_branch: None,
+                },
+                Statement::Return(Some(Box::new(binary(
+                    var("n"),
+                    BinaryOperator::Mul,
+                    Expression::FunctionCall {
+                        name: Box::new(Expression::Constant("fact".to_string())),
+                        arguments: vec![binary(var("n"), BinaryOperator::Sub, int(1))],
+                    },
+                )))),
+            ])),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        });
+        let call = Expression::FunctionCall {
+            name: Box::new(Expression::Constant("fact".to_string())),
+            arguments: vec![int(20)],
+        };
+        let ast = vec![
+            statement(echo(call)),
+            statement(echo(binary(Expression::Literal(Literal::Float(0.1)), BinaryOperator::Add, Expression::Literal(Literal::Float(0.2))))),
+            fact,
+        ];
+
+        let (status, output) = run(&ast);
+        assert_eq!(status, 0);
+        assert_eq!(output, "2432902008176640000\n0.3\n");
+    }
+
+    #[test]
+    fn test_arrays_and_loops() {
+        // $a = [3 => 'x']; $a[] = 'y'; $a['k'] = 'z';
+        // foreach ($a as $k => $v) { if ($v === 'y') continue; echo $k, $v; }
+        let assign = |target: Expression, value: Expression| statement(Statement::Expression(Box::new(
+            Expression::Assignment { target: Box::new(target), op: AssignmentOperator::Assign, value: Box::new(value) },
+        )));
+        let string = |s: &str| Expression::Literal(Literal::String(s.to_string()));
+        let element = |array: &str, index: Expression| Expression::ArrayAccess {
+            array: Box::new(var(array)),
+            index: Box::new(index),
+        };
+        let ast = vec![
+            assign(var("a"), Expression::Array {
+                elements: vec![ArrayElement { key: Some(int(3)), value: string("x"), is_reference: false, is_spread: false }],
+            }),
+            assign(element("a", int(4)), string("y")),
+            assign(element("a", string("k")), string("z")),
+            statement(Statement::Foreach {
+                array: Box::new(var("a")),
+                key: Some("k".to_string()),
+                value: "v".to_string(),
+                body: Box::new(Statement::Block(vec![
+                    Statement::If {
+                        condition: Box::new(binary(var("v"), BinaryOperator::Identical, string("y"))),
+                        then_branch: Box::new(Statement::Continue(None)),
+                        else_branch: None,
+                    },
+                    Statement::Echo(vec![var("k"), var("v")]),
+                ])),
+            }),
+            statement(Statement::Die(Some(Box::new(int(3))))),
+            statement(echo(string("unreachable"))),
+        ];
+
+        let (status, output) = run(&ast);
+        assert_eq!(status, 3);
+        assert_eq!(output, "3xkz");
+    }
+
+    #[test]
+    fn test_strict_types() {
+        // function double(int $n) { return $n * 2; }
+        let double = AstNode::Function(FunctionDecl {
+            name: "double".to_string(),
+            parameters: vec![Parameter {
+                name: "n".to_string(),
+                typ: Some(types::Type::Int),
+                default_value: None,
+                is_reference: false,
+                is_variadic: false,
+            }],
+            return_type: None,
+            body: Box::new(Statement::Return(Some(Box::new(binary(var("n"), BinaryOperator::Mul, int(2)))))),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        });
+        let mut ast = vec![double];
+
+        let mut interpreter = Interpreter::new().unwrap();
+        interpreter.load(&ast);
+        assert!(matches!(interpreter.call("double", vec![Value::String("21".to_string())]), Ok(Value::Int(42))));
+
+        ast.insert(0, statement(Statement::Declare {
+            directives: vec![DeclareDirective {
+                name: "strict_types".to_string(),
+                value: int(1),
+            }],
+            body: Box::new(Statement::Block(vec![])),
+        }));
+        let mut interpreter = Interpreter::new().unwrap();
+        interpreter.load(&ast);
+        let error = interpreter.call("double", vec![Value::String("21".to_string())]).unwrap_err();
+        assert_eq!(error.error_type, RuntimeErrorType::TypeError);
+        assert_eq!(error.message, "double(): Argument #1 ($n) must be of type int, string given");
+
+        // The mode is that of the calling file: an included strict file
+        // does not make the includer's calls strict
+        let call = statement(echo(Expression::FunctionCall {
+            name: Box::new(Expression::Constant("double".to_string())),
+            arguments: vec![Expression::Literal(Literal::String("21".to_string()))],
+        }));
+        let (_, output) = run(&[AstNode::Program(ast.clone()), call.clone()]);
+        assert_eq!(output, "42\n");
+
+        // and a strict file's calls are checked strictly wherever the function is declared
+        let declare = ast.remove(0);
+        let program = vec![ast.remove(0), AstNode::Program(vec![declare, call])];
+        let error = Interpreter::new().unwrap().run(&program).unwrap_err();
+        assert_eq!(error.error_type, RuntimeErrorType::TypeError);
+    }
+
+    #[test]
+    fn test_value_semantics() {
+        assert!(loose_equal(&Value::String("1e1".to_string()), &Value::Int(10)));
+        assert!(!loose_equal(&Value::String("abc".to_string()), &Value::Int(0)));
+        assert!(loose_equal(&Value::Null, &Value::Bool(false)));
+        assert!(!identical(&Value::Int(1), &Value::Float(1.0)));
+        assert_eq!(format_float(1.0e15), "1.0E+15");
+        assert_eq!(format_float(-1.5), "-1.5");
+        assert_eq!(format_float(0.0001), "0.0001");
+        assert_eq!(to_int(&Value::String(" 12abc".to_string())).unwrap(), 12);
+    }
+}
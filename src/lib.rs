@@ -19,14 +19,70 @@
 //! This library provides a direct AOT compilation pipeline from PHP source code
 //! to native binaries, skipping C as an intermediate step.
 
+pub mod arrays;
 pub mod ast;
+pub mod autoload;
+pub mod backend;
+pub mod backtrace;
+pub mod build;
+pub mod bundle;
+pub mod coercion;
 pub mod compiler;
+#[cfg(feature = "cranelift")]
+pub mod cranelift;
+pub mod deadcode;
+pub mod definitions;
+pub mod defines;
+pub mod devirtualize;
+pub mod diagnostics;
+pub mod directives;
+pub mod embed;
 pub mod error;
+pub mod exceptions;
+pub mod explain;
+pub mod fallback;
+pub mod format;
+pub mod fuzz;
+pub mod generators;
+pub mod globals;
+pub mod header;
+pub mod includes;
+pub mod interp;
 pub mod ir;
+pub mod library;
+pub mod link;
+pub mod literals;
+#[cfg(feature = "inkwell")]
+pub mod llvm;
+pub mod manifest;
+pub mod members;
+pub mod mixed;
+pub mod module;
+pub mod names;
+pub mod objects;
 pub mod parser;
+pub mod phpdoc;
+pub mod phpt;
+pub mod profile;
 pub mod runtime;
+pub mod session;
+pub mod signals;
+pub mod snapshot;
+pub mod specialize;
+pub mod strings;
+pub mod stubs;
+pub mod tailcall;
+pub mod target;
+pub mod timings;
+pub mod trace;
+pub mod traits;
 pub mod types;
+pub mod unreachable;
 pub mod utils;
+pub mod variance;
+pub mod verifier;
+pub mod watch;
+pub mod watchdog;
 
 // Re-export main types for convenience
 pub use compiler::{Compiler, CompilerOptions};
@@ -65,6 +121,9 @@ pub fn is_target_supported(target: &str) -> bool {
         "x86_64-pc-windows-gnu",
         "aarch64-unknown-linux-gnu",
         "aarch64-apple-darwin",
+        "i686-unknown-linux-gnu",
+        "armv7-unknown-linux-gnueabihf",
+        "wasm32-unknown-unknown",
         "native",
     ];
     supported.contains(&target)
@@ -0,0 +1,161 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Static libraries, written by `--emit lib`.
+//!
+//! The program's files are compiled to objects as for an executable, but
+//! the driver with `main` is replaced by an entry object defining
+//! `<name>_init()` and `<name>_cleanup()`, which run the top-level code and
+//! shut the runtime down. The objects are archived into `lib<name>.a`
+//! (`<name>.lib` for MSVC) with `llvm-ar` or `ar` (`llvm-lib` or `lib.exe`).
+//! Next to the archive go
+//!
+//! - `<name>.h`, declaring the entry points and the `#[Export]` functions
+//! - `<name>.pc`, for `pkg-config --cflags --libs <name>`: the archive, the
+//!   runtime archive unless `--no-rt`, and the system libraries the runtime
+//!   needs
+//!
+//! so C and C++ programs, and build systems that know pkg-config, can embed
+//! the compiled PHP code.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::error::{CompileError, CompileResult};
+use crate::link::system_libraries;
+use crate::target::TargetSpec;
+use crate::utils::process::command_exists;
+
+/// Static library written for an output path
+#[derive(Debug, Clone)]
+pub struct StaticLibrary {
+    /// Library name: the output's file stem without a `lib` prefix
+    pub name: String,
+
+    /// Directory the library's files are written to
+    pub dir: PathBuf,
+
+    target: TargetSpec,
+}
+
+impl StaticLibrary {
+    pub fn new(output: &Path, target: TargetSpec) -> Self {
+        let stem = output.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let name = match stem.strip_prefix("lib") {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => stem,
+        };
+        let dir = output.parent().map(Path::to_path_buf).unwrap_or_default();
+        Self { name, dir, target }
+    }
+
+    /// `lib<name>.a`, or `<name>.lib` for MSVC
+    pub fn archive_path(&self) -> PathBuf {
+        if self.target.has("msvc") {
+            self.dir.join(format!("{}.lib", self.name))
+        } else {
+            self.dir.join(format!("lib{}.a", self.name))
+        }
+    }
+
+    pub fn header_path(&self) -> PathBuf {
+        self.dir.join(format!("{}.h", self.name))
+    }
+
+    pub fn pkg_config_path(&self) -> PathBuf {
+        self.dir.join(format!("{}.pc", self.name))
+    }
+
+    /// Prefix of the init and cleanup functions: the name as a C identifier
+    pub fn symbol_prefix(&self) -> String {
+        let mut prefix: String = self.name.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        if !prefix.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            prefix.insert(0, '_');
+        }
+        prefix
+    }
+
+    /// Command archiving `objects`, with the first archiver installed
+    ///
+    /// An existing archive must be removed first, as `ar` adds to it.
+    pub fn archive_command(&self, objects: &[PathBuf]) -> CompileResult<Command> {
+        let archive = self.archive_path();
+        let mut cmd = if self.target.has("msvc") {
+            let tool = ["llvm-lib", "lib.exe"].into_iter().find(|tool| command_exists(tool));
+            let mut cmd = Command::new(tool.ok_or_else(|| missing_archiver("llvm-lib or lib.exe"))?);
+            cmd.arg("/nologo").arg(format!("/out:{}", archive.display()));
+            cmd
+        } else {
+            let tool = ["llvm-ar", "ar"].into_iter().find(|tool| command_exists(tool));
+            let mut cmd = Command::new(tool.ok_or_else(|| missing_archiver("llvm-ar or ar"))?);
+            cmd.arg("rcs").arg(&archive);
+            cmd
+        };
+        cmd.args(objects);
+        Ok(cmd)
+    }
+
+    /// Contents of the pkg-config file, for a library written to the
+    /// absolute directory `prefix`
+    pub fn pkg_config(&self, prefix: &Path, runtime: Option<&Path>, version: &str) -> String {
+        let mut libs = vec!["-L${libdir}".to_string(), format!("-l{}", self.name)];
+        if let Some(runtime) = runtime {
+            libs.push(runtime.display().to_string());
+            libs.extend(system_libraries(&self.target).iter().map(|lib| lib.to_string()));
+        }
+        format!(
+            "prefix={}\n\
+             libdir=${{prefix}}\n\
+             includedir=${{prefix}}\n\
+             \n\
+             Name: {}\n\
+             Description: PHP code compiled by php2ir, started with {}_init()\n\
+             Version: {}\n\
+             Cflags: -I${{includedir}}\n\
+             Libs: {}\n",
+            prefix.display(), self.name, self.symbol_prefix(), version, libs.join(" ")
+        )
+    }
+}
+
+fn missing_archiver(tools: &str) -> CompileError {
+    CompileError::Configuration(format!("no archiver found for the static library; install {}", tools))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_static_library() {
+        let target = TargetSpec::from_target("x86_64-unknown-linux-gnu");
+        let library = StaticLibrary::new(Path::new("out/libmy-lib"), target.clone());
+        assert_eq!(library.name, "my-lib");
+        assert_eq!(library.archive_path(), Path::new("out/libmy-lib.a"));
+        assert_eq!(library.header_path(), Path::new("out/my-lib.h"));
+        assert_eq!(library.symbol_prefix(), "my_lib");
+
+        let pc = library.pkg_config(Path::new("/opt/app/out"), Some(Path::new("/opt/php2ir/libphp2ir.a")), "1.2.0");
+        assert!(pc.starts_with("prefix=/opt/app/out\nlibdir=${prefix}\n"));
+        assert!(pc.contains("Version: 1.2.0\n"));
+        assert!(pc.contains(&format!("Libs: -L${{libdir}} -lmy-lib /opt/php2ir/libphp2ir.a {}\n", system_libraries(&target).join(" "))));
+
+        let msvc = StaticLibrary::new(Path::new("calc.lib"), TargetSpec::from_target("x86_64-pc-windows-msvc"));
+        assert_eq!(msvc.archive_path(), Path::new("calc.lib"));
+        assert!(!msvc.pkg_config(Path::new("C:/calc"), None, "0.0.0").contains("php2ir.lib"));
+    }
+}
@@ -0,0 +1,1031 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Linking generated objects into an executable.
+//!
+//! For the host, a C compiler driver (`$CC`, or the first of `cc`, `clang`
+//! and `gcc` installed) runs the link, so the C start files and libc come
+//! in the way they do for a C program; the driver links with `ld.lld`, or
+//! `mold` for ELF, when one is installed. Other targets are linked by
+//! `clang --target`, the target's GNU cross compiler, or the lld of the
+//! target's object format, whichever is found first. Without any driver,
+//! an installed linker is called directly: `ld.lld` or `mold`, `lld-link`
+//! or `link.exe`. `--linker` picks a driver or a linker (`ld`, `ld.lld`,
+//! `mold`, `ld64`, `lld-link`, `link.exe`, ...) explicitly. A linker
+//! called directly is given the target's start files and C libraries from
+//! the `--sysroot`, or from the `/usr/<gnu triple>` of a Debian-style cross
+//! toolchain:
+//!
+//! - ELF: `Scrt1.o`, `crti.o`, `crtbeginS.o` ... `crtendS.o`, `crtn.o`, libc
+//!   and the dynamic linker of the architecture
+//! - MinGW: `crt2.o`, `crtbegin.o` ... `crtend.o` and the MinGW libraries
+//! - Mach-O: `libSystem` from a macOS SDK, which needs no start files
+//! - MSVC: the dynamic CRT (`msvcrt.lib`, `ucrt.lib`, `vcruntime.lib`) from
+//!   the `crt/` and `sdk/` layout of `xwin splat`, or from `%LIB%`
+//!
+//! Unless `--no-rt` is given, the runtime archive (`libphp2ir.a`, or
+//! `php2ir.lib` for MSVC), which building this crate produces next to the
+//! `php2ir` executable, is linked in with the system libraries the Rust
+//! standard library inside it needs.
+//! Cross builds take the runtime built for the target from `--stdlib`.
+//!
+//! `--release-size` drops unused sections of the generated objects too and
+//! strips the symbols of the executable.
+//!
+//! With `--lto`, objects are LLVM bitcode and the linker optimizes them as
+//! a whole: `ld.lld`, or `ld.gold` with LLVM's gold plugin. With lld, a
+//! runtime archive built with `RUSTFLAGS=-Clinker-plugin-lto` holds bitcode
+//! too and is optimized along with the program, given a Rust compiler on
+//! the same LLVM version as lld.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use crate::error::{CompileError, CompileResult};
+use crate::target::{ObjectFormat, TargetSpec};
+use crate::utils::process::command_exists;
+
+/// Environment variable naming the runtime archive to link
+pub const RUNTIME_ENV: &str = "PHP2IR_RUNTIME";
+
+/// File name of the runtime archive, which Rust names `php2ir.lib` for MSVC
+pub fn runtime_archive(target: &TargetSpec) -> String {
+    if target.has("msvc") {
+        format!("{}.lib", crate::RUNTIME_LIB.trim_start_matches("lib"))
+    } else {
+        format!("{}.a", crate::RUNTIME_LIB)
+    }
+}
+
+/// Locate the runtime archive
+///
+/// `stdlib` (the `--stdlib` option) may name the archive or a directory
+/// holding it. Otherwise the archive is looked for in `$PHP2IR_RUNTIME`,
+/// next to the running executable, in `../lib` relative to it, and in the
+/// Cargo target directory when running from `target/<profile>/deps`.
+pub fn find_runtime(stdlib: Option<&Path>, target: &TargetSpec) -> CompileResult<PathBuf> {
+    let archive = runtime_archive(target);
+    let mut candidates = Vec::new();
+    if let Some(path) = stdlib {
+        candidates.push(if path.is_dir() { path.join(&archive) } else { path.to_path_buf() });
+    } else {
+        if let Some(path) = std::env::var_os(RUNTIME_ENV) {
+            candidates.push(PathBuf::from(path));
+        }
+        if let Some(dir) = std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+            candidates.push(dir.join(&archive));
+            candidates.push(dir.join("../lib").join(&archive));
+            candidates.push(dir.join("..").join(&archive));
+        }
+    }
+
+    candidates.iter().find(|path| path.is_file()).cloned().ok_or_else(|| {
+        let searched: Vec<String> = candidates.iter().map(|p| p.display().to_string()).collect();
+        CompileError::Configuration(format!(
+            "runtime library {} not found (searched: {}); build it with `cargo build`, pass --stdlib, or link without it using --no-rt",
+            archive,
+            searched.join(", ")
+        ))
+    })
+}
+
+/// System libraries the runtime archive depends on
+pub fn system_libraries(target: &TargetSpec) -> &'static [&'static str] {
+    let triple = target.triple();
+    if triple.contains("linux") {
+        &["-lgcc_s", "-lutil", "-lrt", "-lpthread", "-lm", "-ldl", "-lc"]
+    } else if triple.contains("darwin") || triple.contains("macos") {
+        &["-lSystem", "-lc", "-lm"]
+    } else if triple.contains("windows") {
+        &["-lkernel32", "-ladvapi32", "-lbcrypt", "-lntdll", "-luserenv", "-lws2_32"]
+    } else if triple.contains("freebsd") {
+        &["-lexecinfo", "-lpthread", "-lgcc_s", "-lc", "-lm", "-lrt", "-lutil"]
+    } else {
+        &[]
+    }
+}
+
+/// Path of the executable written for `output`, with `.exe` added for Windows
+pub fn executable_path(output: &Path, target: &TargetSpec) -> PathBuf {
+    if target.object_format() == ObjectFormat::Coff && output.extension().is_none() {
+        output.with_extension("exe")
+    } else {
+        output.to_path_buf()
+    }
+}
+
+/// How a linker program is driven
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkerFlavor {
+    /// C compiler driver (`cc`, `clang`, `aarch64-linux-gnu-gcc`), which
+    /// adds the start files and libc itself
+    Driver,
+
+    /// GNU-style linker (`ld`, `ld.lld`, `ld.gold`, `mold`) for ELF and MinGW targets
+    Gnu,
+
+    /// Mach-O linker (`ld64`, `ld64.lld`)
+    Darwin,
+
+    /// MSVC-style PE/COFF linker (`lld-link`, `link.exe`)
+    Msvc,
+}
+
+/// Program running the link
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Linker {
+    program: PathBuf,
+    flavor: LinkerFlavor,
+}
+
+impl Linker {
+    /// `program`, driven the way its name suggests for `target`
+    ///
+    /// `ld` is ld64 for Mach-O targets, and the multi-flavor `lld` binary
+    /// takes the flavor of the target's object format.
+    pub fn new(program: impl Into<PathBuf>, target: &TargetSpec) -> Self {
+        let program = program.into();
+        let darwin = target.object_format() == ObjectFormat::MachO;
+        let msvc = target.has("msvc");
+        let flavor = match program_name(&program).as_str() {
+            "ld64" | "ld64.lld" => LinkerFlavor::Darwin,
+            "lld-link" | "link" => LinkerFlavor::Msvc,
+            "ld" | "lld" if darwin => LinkerFlavor::Darwin,
+            "lld" if msvc => LinkerFlavor::Msvc,
+            "ld" | "lld" | "ld.lld" | "ld.gold" | "ld.bfd" | "mold" | "ld.mold" | "wasm-ld" => LinkerFlavor::Gnu,
+            name if name.ends_with("-ld") || name.ends_with("-ld.bfd") || name.ends_with("-ld.gold") => LinkerFlavor::Gnu,
+            _ => LinkerFlavor::Driver,
+        };
+        Self { program, flavor }
+    }
+
+    /// `--linker` if given, otherwise `$CC`, or the first of the
+    /// [`candidates`](Self::candidates) for the target that is installed
+    pub fn select(linker: Option<&Path>, target: &TargetSpec) -> CompileResult<Self> {
+        if let Some(program) = linker {
+            if !command_exists(&program.to_string_lossy()) {
+                return Err(CompileError::Configuration(format!(
+                    "linker {} not found; install it or pass another --linker",
+                    program.display()
+                )));
+            }
+            return Ok(Self::new(program, target));
+        }
+        if let Some(cc) = std::env::var_os("CC") {
+            return Ok(Self::new(cc, target));
+        }
+
+        let candidates = Self::candidates(target);
+        candidates.iter()
+            .find(|program| command_exists(program))
+            .map(|program| Self::new(program, target))
+            .ok_or_else(|| CompileError::Configuration(format!(
+                "no linker found for {} (tried {}); install one or pass --linker",
+                target.triple(),
+                candidates.join(", ")
+            )))
+    }
+
+    /// Programs that can link for `target`, in order of preference
+    ///
+    /// Compiler drivers come first, as they know where the C libraries
+    /// are; then linkers of the target's object format, called directly.
+    pub fn candidates(target: &TargetSpec) -> Vec<String> {
+        let format = target.object_format();
+        let msvc = target.has("msvc");
+        let mut candidates = Vec::new();
+        if target.is_host() && !msvc {
+            candidates.push("cc".to_string());
+        }
+        candidates.push("clang".to_string());
+        if !msvc && !matches!(format, ObjectFormat::MachO | ObjectFormat::Wasm) {
+            candidates.push(if target.is_host() { "gcc".to_string() } else { format!("{}-gcc", gnu_triple(target)) });
+        }
+        let linkers: &[&str] = match format {
+            ObjectFormat::MachO => &["ld64.lld"],
+            ObjectFormat::Coff if msvc && target.is_host() => &["lld-link", "link.exe"],
+            ObjectFormat::Coff if msvc => &["lld-link"],
+            ObjectFormat::Wasm => &["wasm-ld"],
+            ObjectFormat::Elf => &["ld.lld", "mold"],
+            _ => &["ld.lld"],
+        };
+        candidates.extend(linkers.iter().map(|linker| linker.to_string()));
+        candidates
+    }
+
+    pub fn flavor(&self) -> LinkerFlavor {
+        self.flavor
+    }
+
+    /// Whether the linker is one of LLVM's, which read bitcode themselves
+    fn is_lld(&self) -> bool {
+        program_name(&self.program).contains("lld")
+    }
+}
+
+/// File name of a program, lowercase and without `.exe`
+fn program_name(program: &Path) -> String {
+    let name = program.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+    name.strip_suffix(".exe").map(str::to_string).unwrap_or(name)
+}
+
+/// A link of objects into an executable for a target
+#[derive(Debug, Clone)]
+pub struct Link {
+    target: TargetSpec,
+    linker: Linker,
+    sysroot: Option<PathBuf>,
+    runtime: Option<PathBuf>,
+    lto: Option<LtoLinker>,
+    release_size: bool,
+}
+
+impl Link {
+    pub fn new(target: TargetSpec, linker: Linker) -> Self {
+        Self {
+            target,
+            linker,
+            sysroot: None,
+            runtime: None,
+            lto: None,
+            release_size: false,
+        }
+    }
+
+    /// Root of the target's headers and libraries (`--sysroot`)
+    pub fn with_sysroot(mut self, sysroot: Option<PathBuf>) -> Self {
+        self.sysroot = sysroot;
+        self
+    }
+
+    /// Runtime archive linked after the objects
+    pub fn with_runtime(mut self, runtime: Option<PathBuf>) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    /// Optimize bitcode objects at link time
+    pub fn with_lto(mut self, lto: Option<LtoLinker>) -> Self {
+        self.lto = lto;
+        self
+    }
+
+    /// Link for `--release-size`: unused sections are dropped even without
+    /// the runtime, and symbols are stripped
+    pub fn with_release_size(mut self, release_size: bool) -> Self {
+        self.release_size = release_size;
+        self
+    }
+
+    /// Whether unused sections are dropped
+    fn collects_sections(&self) -> bool {
+        self.runtime.is_some() || self.release_size
+    }
+
+    /// Command linking `objects` into `output`
+    pub fn command(&self, objects: &[PathBuf], output: &Path) -> CompileResult<Command> {
+        let mut cmd = Command::new(&self.linker.program);
+        if program_name(&self.linker.program) == "lld" {
+            let flavor = match self.linker.flavor {
+                LinkerFlavor::Darwin => "darwin",
+                LinkerFlavor::Msvc => "link",
+                _ if self.target.object_format() == ObjectFormat::Wasm => "wasm",
+                _ => "gnu",
+            };
+            cmd.args(["-flavor", flavor]);
+        }
+        match self.linker.flavor {
+            LinkerFlavor::Driver => self.driver_args(&mut cmd, objects, output),
+            LinkerFlavor::Gnu => self.gnu_args(&mut cmd, objects, output)?,
+            LinkerFlavor::Darwin => self.darwin_args(&mut cmd, objects, output)?,
+            LinkerFlavor::Msvc => self.msvc_args(&mut cmd, objects, output)?,
+        }
+        Ok(cmd)
+    }
+
+    /// Arguments for a C compiler driver
+    ///
+    /// Targets other than the host are passed with `--target`, except to
+    /// GNU cross compilers, which only build for their own target.
+    fn driver_args(&self, cmd: &mut Command, objects: &[PathBuf], output: &Path) {
+        if !self.target.is_host() && !program_name(&self.linker.program).contains("gcc") {
+            cmd.arg(format!("--target={}", self.target.triple()));
+        }
+        if let Some(sysroot) = &self.sysroot {
+            cmd.arg(format!("--sysroot={}", sysroot.display()));
+        }
+        if let Some(lto) = &self.lto {
+            cmd.args(lto.args());
+        } else if let Some(linker) = self.faster_linker() {
+            cmd.arg(format!("-fuse-ld={}", linker));
+        }
+        cmd.arg("-o").arg(output).args(objects);
+
+        // Drop code the program never calls, with its references
+        if self.collects_sections() {
+            match self.target.object_format() {
+                ObjectFormat::MachO => {
+                    cmd.arg("-Wl,-dead_strip");
+                }
+                ObjectFormat::Coff => {}
+                _ => {
+                    cmd.arg("-Wl,--gc-sections");
+                }
+            }
+        }
+        if self.release_size {
+            // ld64 keeps the global symbols of an executable
+            cmd.arg(match self.target.object_format() {
+                ObjectFormat::MachO => "-Wl,-S,-x",
+                _ => "-s",
+            });
+        }
+        if let Some(runtime) = &self.runtime {
+            cmd.arg(runtime).args(system_libraries(&self.target));
+        }
+    }
+
+    /// Arguments for a GNU-style linker, with the target's start files
+    fn gnu_args(&self, cmd: &mut Command, objects: &[PathBuf], output: &Path) -> CompileResult<()> {
+        let format = self.target.object_format();
+        let start_files = match format {
+            ObjectFormat::Wasm => StartFiles::default(),
+            _ => StartFiles::find(&self.target, &self.library_root()?)?,
+        };
+        if let Some(sysroot) = &self.sysroot {
+            cmd.arg(format!("--sysroot={}", sysroot.display()));
+        }
+        if let Some(lto) = &self.lto {
+            cmd.args(lto.args());
+        }
+        match format {
+            ObjectFormat::Elf => {
+                cmd.args(["--eh-frame-hdr", "-pie", "-dynamic-linker"]).arg(dynamic_linker(&self.target)?);
+            }
+            ObjectFormat::Coff => {
+                cmd.args(["-m", pe_emulation(&self.target)?, "--subsystem", "console"]);
+            }
+            _ => {}
+        }
+        cmd.arg("-o").arg(output).args(&start_files.begin);
+        cmd.args(start_files.dirs.iter().map(|dir| format!("-L{}", dir.display())));
+        cmd.args(objects);
+
+        if self.collects_sections() {
+            cmd.arg("--gc-sections");
+        }
+        if self.release_size {
+            cmd.arg("--strip-all");
+        }
+        if let Some(runtime) = &self.runtime {
+            cmd.arg(runtime).args(system_libraries(&self.target));
+        }
+        match format {
+            ObjectFormat::Elf if !cmd.get_args().any(|arg| arg == "-lc") => {
+                cmd.arg("-lc");
+            }
+            ObjectFormat::Coff => {
+                cmd.args(["-lmingw32", "-lgcc", "-lgcc_eh", "-lmingwex", "-lmsvcrt", "-lkernel32"]);
+            }
+            _ => {}
+        }
+        cmd.args(&start_files.end);
+        Ok(())
+    }
+
+    /// Arguments for a Mach-O linker, which finds libSystem in the SDK
+    fn darwin_args(&self, cmd: &mut Command, objects: &[PathBuf], output: &Path) -> CompileResult<()> {
+        let arch = match self.target.arch() {
+            "aarch64" => "arm64",
+            arch => arch,
+        };
+        let version = macos_version(&self.target);
+        cmd.args(["-arch", arch, "-platform_version", "macos", &version, &version]);
+
+        let sdk = self.sysroot.clone().or_else(|| self.target.is_host().then(macos_sdk).flatten());
+        match sdk {
+            Some(sdk) => {
+                cmd.arg("-syslibroot").arg(sdk);
+            }
+            None if !self.target.is_host() => {
+                return Err(CompileError::Configuration(format!(
+                    "linking for {} needs --sysroot pointing at a macOS SDK",
+                    self.target.triple()
+                )));
+            }
+            None => {}
+        }
+        if let Some(lto) = &self.lto {
+            cmd.args(lto.args());
+        }
+        cmd.arg("-o").arg(output).args(objects);
+
+        if self.release_size {
+            cmd.args(["-S", "-x"]);
+        }
+        if self.collects_sections() {
+            cmd.arg("-dead_strip");
+        }
+        match &self.runtime {
+            Some(runtime) => {
+                cmd.arg(runtime).args(system_libraries(&self.target));
+            }
+            None => {
+                cmd.arg("-lSystem");
+            }
+        }
+        Ok(())
+    }
+
+    /// Arguments for an MSVC-style linker, with the dynamic CRT
+    ///
+    /// Rust links the runtime against the dynamic CRT, so the program
+    /// does too; its startup code comes from `msvcrt.lib`.
+    fn msvc_args(&self, cmd: &mut Command, objects: &[PathBuf], output: &Path) -> CompileResult<()> {
+        let machine = match self.target.arch() {
+            "x86_64" => "x64",
+            "i386" | "i586" | "i686" => "x86",
+            "aarch64" => "arm64",
+            arch if arch.starts_with("arm") || arch.starts_with("thumb") => "arm",
+            arch => arch,
+        };
+        cmd.args(["/nologo", "/subsystem:console"])
+            .arg(format!("/machine:{}", machine))
+            .arg(format!("/out:{}", output.display()));
+
+        if let Some(sysroot) = &self.sysroot {
+            let dirs: Vec<PathBuf> = ["crt/lib", "sdk/lib/um", "sdk/lib/ucrt", "lib"].iter()
+                .map(|dir| sysroot.join(dir).join(machine))
+                .filter(|dir| dir.is_dir())
+                .collect();
+            if dirs.is_empty() {
+                return Err(CompileError::Configuration(format!(
+                    "no MSVC libraries for {} under {} (expected the crt/ and sdk/ layout of `xwin splat`)",
+                    machine,
+                    sysroot.display()
+                )));
+            }
+            cmd.args(dirs.iter().map(|dir| format!("/libpath:{}", dir.display())));
+        }
+        if let Some(lto) = &self.lto {
+            cmd.args(lto.args());
+        }
+        cmd.args(objects);
+
+        if self.release_size {
+            cmd.args(["/debug:none", "/opt:icf"]);
+        }
+        if self.collects_sections() {
+            cmd.arg("/opt:ref");
+        }
+        if let Some(runtime) = &self.runtime {
+            cmd.arg(runtime);
+            cmd.args(system_libraries(&self.target).iter().map(|lib| format!("{}.lib", lib.trim_start_matches("-l"))));
+        }
+        cmd.args(["msvcrt.lib", "ucrt.lib", "vcruntime.lib"]);
+        Ok(())
+    }
+
+    /// Linker a driver is told to use instead of its default, when installed
+    ///
+    /// lld, or mold for ELF targets, links much faster than GNU ld.
+    fn faster_linker(&self) -> Option<&'static str> {
+        if command_exists("ld.lld") {
+            Some("lld")
+        } else if self.target.object_format() == ObjectFormat::Elf && command_exists("mold") {
+            Some("mold")
+        } else {
+            None
+        }
+    }
+
+    /// Directory the target's start files and C libraries are found under
+    fn library_root(&self) -> CompileResult<PathBuf> {
+        if let Some(sysroot) = &self.sysroot {
+            return Ok(sysroot.clone());
+        }
+        if self.target.is_host() {
+            return Ok(PathBuf::from("/"));
+        }
+        let cross = Path::new("/usr").join(gnu_triple(&self.target));
+        if cross.is_dir() {
+            return Ok(cross);
+        }
+        Err(CompileError::Configuration(format!(
+            "linking for {} with {} needs --sysroot",
+            self.target.triple(),
+            self.linker.program.display()
+        )))
+    }
+}
+
+/// C start files a linker called directly puts around the objects
+#[derive(Debug, Default)]
+struct StartFiles {
+    begin: Vec<PathBuf>,
+    end: Vec<PathBuf>,
+    dirs: Vec<PathBuf>,
+}
+
+impl StartFiles {
+    /// Start files of `target` under `root`, with the directories holding them and the C libraries
+    fn find(target: &TargetSpec, root: &Path) -> CompileResult<Self> {
+        let dirs = library_dirs(target, root);
+        let find = |name: &str| dirs.iter().map(|dir| dir.join(name)).find(|path| path.is_file());
+        let require = |name: &str| find(name).ok_or_else(|| CompileError::Configuration(format!(
+            "C start file {} for {} not found under {}; pass --sysroot",
+            name,
+            target.triple(),
+            root.display()
+        )));
+
+        let (begin, end) = if target.object_format() == ObjectFormat::Coff {
+            (vec![Some(require("crt2.o")?), find("crtbegin.o")], vec![find("crtend.o")])
+        } else {
+            (
+                vec![Some(require("Scrt1.o")?), Some(require("crti.o")?), find("crtbeginS.o")],
+                vec![find("crtendS.o"), Some(require("crtn.o")?)],
+            )
+        };
+        Ok(Self {
+            begin: begin.into_iter().flatten().collect(),
+            end: end.into_iter().flatten().collect(),
+            dirs,
+        })
+    }
+}
+
+/// Existing library directories of `target` under `root`, GCC's last
+fn library_dirs(target: &TargetSpec, root: &Path) -> Vec<PathBuf> {
+    let multiarch = multiarch(target);
+    let mut dirs = vec![
+        root.join("usr/lib").join(&multiarch),
+        root.join("lib").join(&multiarch),
+        root.join("usr/lib"),
+        root.join("lib"),
+        root.join("usr/lib64"),
+        root.join("lib64"),
+    ];
+    // GCC's start files and libgcc, from the sysroot or a cross toolchain
+    for base in [root, Path::new("/")] {
+        for gcc in ["usr/lib/gcc", "usr/lib/gcc-cross"] {
+            dirs.extend(newest_version(&base.join(gcc).join(gnu_triple(target))));
+        }
+    }
+    let mut seen = std::collections::HashSet::new();
+    dirs.retain(|dir| dir.is_dir() && seen.insert(dir.clone()));
+    dirs
+}
+
+/// Subdirectory of `dir` named after the highest version (`12`, `12-win32`)
+fn newest_version(dir: &Path) -> Option<PathBuf> {
+    let version = |path: &PathBuf| -> Vec<u32> {
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        name.split('.')
+            .map_while(|part| part.split(|c: char| !c.is_ascii_digit()).next().and_then(|n| n.parse().ok()))
+            .collect()
+    };
+    std::fs::read_dir(dir).ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir() && !version(path).is_empty())
+        .max_by_key(version)
+}
+
+/// Name of the target in GNU toolchains (`aarch64-linux-gnu`, `x86_64-w64-mingw32`)
+fn gnu_triple(target: &TargetSpec) -> String {
+    let arch = match target.arch() {
+        arch if arch.starts_with("armv") => "arm",
+        arch => arch,
+    };
+    if target.has("windows") {
+        return format!("{}-w64-mingw32", target.arch());
+    }
+    let parts: Vec<&str> = target.triple().split('-').collect();
+    match parts.as_slice() {
+        [_, _vendor, os, env @ ..] => std::iter::once(arch).chain(std::iter::once(*os)).chain(env.iter().copied()).collect::<Vec<_>>().join("-"),
+        [_, os] => format!("{}-{}", arch, os),
+        _ => target.triple().to_string(),
+    }
+}
+
+/// Debian multiarch directory name of the target
+fn multiarch(target: &TargetSpec) -> String {
+    let gnu = gnu_triple(target);
+    match target.arch() {
+        "i586" | "i686" => gnu.replacen(target.arch(), "i386", 1),
+        _ => gnu,
+    }
+}
+
+/// Program interpreter of dynamically linked ELF executables
+fn dynamic_linker(target: &TargetSpec) -> CompileResult<String> {
+    let arch = target.arch();
+    if target.has("musl") {
+        return Ok(format!("/lib/ld-musl-{}.so.1", arch));
+    }
+    if target.has("freebsd") {
+        return Ok("/libexec/ld-elf.so.1".to_string());
+    }
+    let interpreter = match arch {
+        "x86_64" => "/lib64/ld-linux-x86-64.so.2",
+        "aarch64" => "/lib/ld-linux-aarch64.so.1",
+        "i386" | "i586" | "i686" => "/lib/ld-linux.so.2",
+        arch if arch.starts_with("arm") && target.has("gnueabihf") => "/lib/ld-linux-armhf.so.3",
+        arch if arch.starts_with("arm") => "/lib/ld-linux.so.3",
+        "riscv64" => "/lib/ld-linux-riscv64-lp64d.so.1",
+        "powerpc64le" => "/lib64/ld64.so.2",
+        "s390x" => "/lib/ld64.so.1",
+        _ => {
+            return Err(CompileError::Configuration(format!(
+                "no dynamic linker known for {}; link with a C compiler driver through --linker",
+                target.triple()
+            )))
+        }
+    };
+    Ok(interpreter.to_string())
+}
+
+/// `-m` emulation of GNU linkers for MinGW targets
+fn pe_emulation(target: &TargetSpec) -> CompileResult<&'static str> {
+    match target.arch() {
+        "x86_64" => Ok("i386pep"),
+        "i386" | "i586" | "i686" => Ok("i386pe"),
+        "aarch64" => Ok("arm64pe"),
+        arch if arch.starts_with("arm") || arch.starts_with("thumb") => Ok("thumb2pe"),
+        _ => Err(CompileError::Configuration(format!("no PE emulation known for {}", target.triple()))),
+    }
+}
+
+/// Minimum macOS version of a target, from `macosx13.0`-style triples
+fn macos_version(target: &TargetSpec) -> String {
+    let stated = target.triple().split('-')
+        .find_map(|part| part.strip_prefix("macosx").or_else(|| part.strip_prefix("macos")))
+        .filter(|version| !version.is_empty());
+    match stated {
+        Some(version) if version.contains('.') => version.to_string(),
+        Some(version) => format!("{}.0", version),
+        // The first releases for each architecture that Rust supports
+        None if target.arch() == "aarch64" || target.arch() == "arm64" => "11.0".to_string(),
+        None => "10.12".to_string(),
+    }
+}
+
+/// SDK of the installed Xcode or command line tools
+fn macos_sdk() -> Option<PathBuf> {
+    let output = Command::new("xcrun").arg("--show-sdk-path").output().ok()?;
+    output.status.success().then(|| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+/// Link-time optimization mode (`--lto`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LtoMode {
+    /// Per-module summaries, optimized in parallel at link time
+    Thin,
+
+    /// All modules merged and optimized as one
+    Full,
+}
+
+impl LtoMode {
+    /// Pipeline preparing a module for link-time optimization
+    pub fn pre_link_pipeline(&self, optimization_level: &str) -> String {
+        let level = crate::backend::pipeline_level(optimization_level);
+        match self {
+            LtoMode::Thin => format!("thinlto-pre-link<{}>", level),
+            LtoMode::Full => format!("lto-pre-link<{}>", level),
+        }
+    }
+
+    /// `opt` options writing the module's bitcode
+    pub fn bitcode_options(&self) -> &'static [&'static str] {
+        match self {
+            LtoMode::Thin => &["-thinlto-bc"],
+            LtoMode::Full => &[],
+        }
+    }
+}
+
+impl FromStr for LtoMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "thin" => Ok(LtoMode::Thin),
+            "full" => Ok(LtoMode::Full),
+            _ => Err(format!("unknown LTO mode '{}' (expected: thin, full)", s)),
+        }
+    }
+}
+
+impl fmt::Display for LtoMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LtoMode::Thin => write!(f, "thin"),
+            LtoMode::Full => write!(f, "full"),
+        }
+    }
+}
+
+/// How a linker optimizes bitcode objects at link time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LtoLinker {
+    flavor: LinkerFlavor,
+
+    /// LLVM's gold plugin, or `None` for linkers that read bitcode themselves
+    plugin: Option<PathBuf>,
+
+    /// Optimization level of the link-time pipeline, 0 to 3
+    level: char,
+}
+
+impl LtoLinker {
+    /// Link-time optimization by `linker`
+    ///
+    /// A compiler driver links with `ld.lld` if installed, otherwise with
+    /// `ld.gold` and LLVM's gold plugin. The lld linkers and Apple's ld64
+    /// read bitcode themselves; GNU linkers need the gold plugin, and
+    /// `link.exe` cannot read it at all.
+    pub fn for_linker(linker: &Linker, optimization_level: &str) -> CompileResult<Self> {
+        let level = match optimization_level {
+            "O0" => '0',
+            "O1" => '1',
+            "O3" => '3',
+            _ => '2',
+        };
+        let plugin = match linker.flavor {
+            LinkerFlavor::Driver if command_exists("ld.lld") => None,
+            LinkerFlavor::Driver => match gold_plugin().filter(|_| command_exists("ld.gold")) {
+                Some(plugin) => Some(plugin),
+                None => {
+                    return Err(CompileError::Configuration(
+                        "--lto needs ld.lld, or ld.gold with LLVM's gold plugin (LLVMgold.so)".to_string(),
+                    ))
+                }
+            },
+            LinkerFlavor::Gnu if linker.is_lld() => None,
+            LinkerFlavor::Gnu => match gold_plugin() {
+                Some(plugin) => Some(plugin),
+                None => {
+                    return Err(CompileError::Configuration(format!(
+                        "--lto with {} needs LLVM's gold plugin (LLVMgold.so)",
+                        linker.program.display()
+                    )))
+                }
+            },
+            LinkerFlavor::Darwin => None,
+            LinkerFlavor::Msvc if linker.is_lld() => None,
+            LinkerFlavor::Msvc => {
+                return Err(CompileError::Configuration(
+                    "--lto for MSVC targets needs lld-link; link.exe cannot read LLVM bitcode".to_string(),
+                ))
+            }
+        };
+        Ok(Self { flavor: linker.flavor, plugin, level })
+    }
+
+    /// Arguments selecting the linker or plugin and its optimization level
+    pub fn args(&self) -> Vec<String> {
+        match (self.flavor, &self.plugin) {
+            (LinkerFlavor::Driver, None) => vec!["-fuse-ld=lld".to_string(), format!("-Wl,--lto-O{}", self.level)],
+            (LinkerFlavor::Driver, Some(plugin)) => vec![
+                "-fuse-ld=gold".to_string(),
+                format!("-Wl,-plugin,{}", plugin.display()),
+                format!("-Wl,-plugin-opt=O{}", self.level),
+            ],
+            (LinkerFlavor::Gnu, None) => vec![format!("--lto-O{}", self.level)],
+            (LinkerFlavor::Gnu, Some(plugin)) => vec![
+                "-plugin".to_string(),
+                plugin.display().to_string(),
+                format!("-plugin-opt=O{}", self.level),
+            ],
+            (LinkerFlavor::Darwin, _) => Vec::new(),
+            (LinkerFlavor::Msvc, _) => vec![format!("/opt:lldlto={}", self.level)],
+        }
+    }
+
+    /// Runtime archive to link, copied to `scratch` without embedded bitcode for gold
+    ///
+    /// rustc embeds bitcode in `.llvmbc` sections of the objects it writes.
+    /// The gold plugin reads it, and fails on bitcode of another LLVM
+    /// version; lld only optimizes bitcode files.
+    pub fn runtime(&self, runtime: &Path, scratch: &Path) -> CompileResult<PathBuf> {
+        if self.plugin.is_none() {
+            return Ok(runtime.to_path_buf());
+        }
+        let output = Command::new("llvm-objcopy")
+            .args(["--remove-section=.llvmbc", "--remove-section=.llvmcmd"])
+            .arg(runtime)
+            .arg(scratch)
+            .output()
+            .map_err(|e| CompileError::Internal(format!("Failed to run llvm-objcopy: {}", e)))?;
+        if !output.status.success() {
+            return Err(CompileError::Linking(String::from_utf8_lossy(&output.stderr).to_string()));
+        }
+        Ok(scratch.to_path_buf())
+    }
+}
+
+/// LLVMgold.so of the installed LLVM
+fn gold_plugin() -> Option<PathBuf> {
+    let libdir = Command::new("llvm-config").arg("--libdir").output().ok()
+        .filter(|output| output.status.success())
+        .map(|output| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()));
+    libdir.into_iter()
+        .chain(["/usr/lib", "/usr/local/lib"].map(PathBuf::from))
+        .map(|dir| dir.join("LLVMgold.so"))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arguments(cmd: &Command) -> Vec<String> {
+        cmd.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect()
+    }
+
+    fn link(triple: &str, linker: &str) -> Link {
+        let target = TargetSpec::from_target(triple);
+        let linker = Linker::new(linker, &target);
+        Link::new(target, linker)
+    }
+
+    #[test]
+    fn test_driver_link() {
+        let target = TargetSpec::from_target("aarch64-unknown-linux-gnu");
+        let objects = [PathBuf::from("app.o")];
+        let cmd = link("aarch64-unknown-linux-gnu", "clang")
+            .with_runtime(Some(PathBuf::from("rt/libphp2ir.a")))
+            .command(&objects, Path::new("app"))
+            .unwrap();
+        let args = arguments(&cmd);
+        assert_eq!(args[0], "--target=aarch64-unknown-linux-gnu");
+        let runtime = args.iter().position(|arg| arg == "rt/libphp2ir.a").unwrap();
+        assert!(args.iter().position(|arg| arg == "app.o").unwrap() < runtime);
+        assert_eq!(&args[runtime + 1..], system_libraries(&target));
+
+        let cross_gcc = link("aarch64-unknown-linux-gnu", "aarch64-linux-gnu-gcc")
+            .with_sysroot(Some(PathBuf::from("/sysroot")));
+        let args = arguments(&cross_gcc.command(&objects, Path::new("app")).unwrap());
+        assert_eq!(args[0], "--sysroot=/sysroot");
+        assert!(!args.iter().any(|arg| arg.starts_with("--target") || arg.starts_with("-l")));
+    }
+
+    #[test]
+    fn test_linker_flavors() {
+        let flavor = |triple: &str, program: &str| Linker::new(program, &TargetSpec::from_target(triple)).flavor();
+        assert_eq!(flavor("x86_64-unknown-linux-gnu", "/usr/bin/ld.lld"), LinkerFlavor::Gnu);
+        assert_eq!(flavor("aarch64-unknown-linux-gnu", "aarch64-linux-gnu-ld"), LinkerFlavor::Gnu);
+        assert_eq!(flavor("aarch64-apple-darwin", "ld"), LinkerFlavor::Darwin);
+        assert_eq!(flavor("aarch64-apple-darwin", "lld"), LinkerFlavor::Darwin);
+        assert_eq!(flavor("x86_64-pc-windows-msvc", "LLD-LINK.EXE"), LinkerFlavor::Msvc);
+        assert_eq!(flavor("x86_64-pc-windows-gnu", "x86_64-w64-mingw32-gcc"), LinkerFlavor::Driver);
+        assert_eq!(flavor("x86_64-unknown-linux-gnu", "mold"), LinkerFlavor::Gnu);
+        assert_eq!(flavor("x86_64-pc-windows-msvc", "link.exe"), LinkerFlavor::Msvc);
+        assert_eq!(gnu_triple(&TargetSpec::from_target("armv7-unknown-linux-gnueabihf")), "arm-linux-gnueabihf");
+        assert_eq!(multiarch(&TargetSpec::from_target("i686-unknown-linux-gnu")), "i386-linux-gnu");
+        assert_eq!(gnu_triple(&TargetSpec::from_target("x86_64-pc-windows-gnu")), "x86_64-w64-mingw32");
+
+        let target = TargetSpec::from_target("x86_64-pc-windows-gnu");
+        assert_eq!(executable_path(Path::new("out/app"), &target), PathBuf::from("out/app.exe"));
+        assert_eq!(executable_path(Path::new("app"), &TargetSpec::host()), PathBuf::from("app"));
+    }
+
+    #[test]
+    fn test_linker_candidates() {
+        let candidates = |triple: &str| Linker::candidates(&TargetSpec::from_target(triple));
+        assert_eq!(candidates("armv7-unknown-linux-gnueabihf"), ["clang", "arm-linux-gnueabihf-gcc", "ld.lld", "mold"]);
+        assert_eq!(candidates("wasm32-unknown-unknown"), ["clang", "wasm-ld"]);
+        let host = TargetSpec::host();
+        if host.object_format() != ObjectFormat::MachO {
+            assert_eq!(candidates("aarch64-apple-darwin"), ["clang", "ld64.lld"]);
+        }
+        if host.object_format() == ObjectFormat::Elf {
+            assert_eq!(Linker::candidates(&host)[..3], ["cc", "clang", "gcc"]);
+        }
+        if !host.triple().contains("windows") {
+            assert_eq!(candidates("x86_64-pc-windows-msvc"), ["clang", "lld-link"]);
+        }
+
+        let missing = Path::new("/nonexistent/ld.lld");
+        let error = Linker::select(Some(missing), &host).unwrap_err();
+        assert!(error.to_string().contains("linker /nonexistent/ld.lld not found"));
+    }
+
+    #[test]
+    fn test_elf_start_files() {
+        let sysroot = tempfile::tempdir().unwrap();
+        let libc = sysroot.path().join("usr/lib/aarch64-linux-gnu");
+        let gcc = sysroot.path().join("usr/lib/gcc/aarch64-linux-gnu");
+        for dir in [&libc, &gcc.join("9"), &gcc.join("12")] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        for file in [libc.join("Scrt1.o"), libc.join("crti.o"), libc.join("crtn.o"), gcc.join("12/crtbeginS.o")] {
+            std::fs::write(file, b"").unwrap();
+        }
+
+        let elf = link("aarch64-unknown-linux-gnu", "ld.lld").with_sysroot(Some(sysroot.path().to_path_buf()));
+        let objects = [PathBuf::from("app.o")];
+        let args = arguments(&elf.command(&objects, Path::new("app")).unwrap());
+        let position = |suffix: &str| args.iter().position(|arg| arg.ends_with(suffix)).unwrap();
+        assert!(args.contains(&"/lib/ld-linux-aarch64.so.1".to_string()));
+        assert!(position("Scrt1.o") < position("crti.o") && position("crti.o") < position("12/crtbeginS.o"));
+        assert!(position("crtbeginS.o") < position("app.o"));
+        assert_eq!(args.last().unwrap(), &libc.join("crtn.o").display().to_string());
+        assert!(args.contains(&format!("-L{}", gcc.join("12").display())));
+
+        std::fs::remove_file(libc.join("crti.o")).unwrap();
+        let error = elf.command(&objects, Path::new("app")).unwrap_err();
+        assert!(error.to_string().contains("C start file crti.o"));
+    }
+
+    #[test]
+    fn test_darwin_and_msvc_links() {
+        let objects = [PathBuf::from("app.o")];
+        let darwin = link("aarch64-apple-darwin", "ld64.lld");
+        if !TargetSpec::host().triple().contains("darwin") {
+            assert!(matches!(darwin.command(&objects, Path::new("app")), Err(CompileError::Configuration(_))));
+        }
+        let sdk = darwin.with_sysroot(Some(PathBuf::from("/sdk"))).with_runtime(Some(PathBuf::from("rt.a")));
+        let args = arguments(&sdk.command(&objects, Path::new("app")).unwrap());
+        assert_eq!(args[..8], ["-arch", "arm64", "-platform_version", "macos", "11.0", "11.0", "-syslibroot", "/sdk"]);
+        assert!(args.ends_with(&["-dead_strip".to_string(), "rt.a".to_string(), "-lSystem".to_string(), "-lc".to_string(), "-lm".to_string()]));
+
+        let msvc = link("x86_64-pc-windows-msvc", "lld-link").with_runtime(Some(PathBuf::from("php2ir.lib")));
+        let args = arguments(&msvc.command(&objects, Path::new("app.exe")).unwrap());
+        assert_eq!(args[..4], ["/nologo", "/subsystem:console", "/machine:x64", "/out:app.exe"]);
+        assert!(args.contains(&"kernel32.lib".to_string()));
+        assert_eq!(args[args.len() - 3..], ["msvcrt.lib", "ucrt.lib", "vcruntime.lib"]);
+
+        let lld = link("x86_64-pc-windows-msvc", "lld");
+        assert_eq!(arguments(&lld.command(&objects, Path::new("app.exe")).unwrap())[..2], ["-flavor", "link"]);
+    }
+
+    #[test]
+    fn test_release_size_link() {
+        let objects = [PathBuf::from("app.o")];
+        let driver = link("aarch64-unknown-linux-gnu", "clang").with_release_size(true);
+        let args = arguments(&driver.command(&objects, Path::new("app")).unwrap());
+        assert!(args.ends_with(&["app.o".to_string(), "-Wl,--gc-sections".to_string(), "-s".to_string()]));
+
+        let darwin = link("aarch64-apple-darwin", "ld64.lld")
+            .with_sysroot(Some(PathBuf::from("/sdk")))
+            .with_release_size(true);
+        let args = arguments(&darwin.command(&objects, Path::new("app")).unwrap());
+        assert!(args.ends_with(&["-S".to_string(), "-x".to_string(), "-dead_strip".to_string(), "-lSystem".to_string()]));
+
+        let msvc = link("x86_64-pc-windows-msvc", "lld-link").with_release_size(true);
+        let args = arguments(&msvc.command(&objects, Path::new("app.exe")).unwrap());
+        assert!(args.contains(&"/opt:ref".to_string()) && args.contains(&"/opt:icf".to_string()));
+    }
+
+    #[test]
+    fn test_lto_link() {
+        assert_eq!("thin".parse::<LtoMode>(), Ok(LtoMode::Thin));
+        assert!("fat".parse::<LtoMode>().is_err());
+        assert_eq!(LtoMode::Full.pre_link_pipeline("O3"), "lto-pre-link<O3>");
+        assert_eq!(LtoMode::Thin.pre_link_pipeline("Os"), "thinlto-pre-link<Os>");
+
+        let gold = LtoLinker { flavor: LinkerFlavor::Driver, plugin: Some(PathBuf::from("/llvm/lib/LLVMgold.so")), level: '3' };
+        let objects = [PathBuf::from("app.o")];
+        let cmd = link(TargetSpec::host().triple(), "cc").with_lto(Some(gold)).command(&objects, Path::new("app")).unwrap();
+        assert_eq!(arguments(&cmd)[..3], ["-fuse-ld=gold", "-Wl,-plugin,/llvm/lib/LLVMgold.so", "-Wl,-plugin-opt=O3"]);
+        let lld = LtoLinker { flavor: LinkerFlavor::Driver, plugin: None, level: '2' };
+        assert_eq!(lld.args(), ["-fuse-ld=lld", "-Wl,--lto-O2"]);
+
+        let target = TargetSpec::from_target("x86_64-pc-windows-msvc");
+        let lld_link = LtoLinker::for_linker(&Linker::new("lld-link", &target), "O1").unwrap();
+        assert_eq!(lld_link.args(), ["/opt:lldlto=1"]);
+        assert!(LtoLinker::for_linker(&Linker::new("link.exe", &target), "O2").is_err());
+    }
+
+    #[test]
+    fn test_find_runtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let host = TargetSpec::from_target("x86_64-unknown-linux-gnu");
+        assert!(matches!(find_runtime(Some(dir.path()), &host), Err(CompileError::Configuration(_))));
+
+        let archive = dir.path().join(runtime_archive(&host));
+        std::fs::write(&archive, b"!<arch>\n").unwrap();
+        assert_eq!(find_runtime(Some(dir.path()), &host).unwrap(), archive);
+        assert_eq!(find_runtime(Some(&archive), &host).unwrap(), archive);
+
+        let msvc = TargetSpec::from_target("x86_64-pc-windows-msvc");
+        assert_eq!(runtime_archive(&msvc), "php2ir.lib");
+        assert!(find_runtime(Some(dir.path()), &msvc).is_err());
+    }
+}
@@ -0,0 +1,1930 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Literal type inference.
+//!
+//! Propagates literal values (`'sorted'`, `42`, `true`) through variables
+//! and `define()`d constants as refinements of their base types, folding
+//! constant expressions on the way. The inferred types drive checks that
+//! need concrete values:
+//!
+//! * `match` without a default arm that misses a possible subject value,
+//!   including cases of an enum-typed subject
+//! * duplicate and illegal array keys
+//! * `printf`-family format strings against their arguments
+//! * calls through variables holding closures or function names, checked
+//!   against the callee's [`Signature`]
+//! * writes to typed and `readonly` properties of known classes
+//!
+//! Variables assigned inside loops, `switch`, `match` and `try` lose their
+//! refinement, and branches of `if` and `?:` are joined into unions. A
+//! variable passed to a callee that might take it by reference is
+//! forgotten.
+//!
+//! Property fetches and method calls are typed from the declared classes.
+//! A `?->` on a possibly-null object makes the rest of the chain nullable;
+//! `??`, `=== null` guards and `is_null()` checks narrow it back, and a
+//! comparison of a non-nullable value against `null` folds to a constant.
+
+use std::collections::{HashMap, HashSet};
+use crate::ast::*;
+use crate::ast::index::rewrite_statements;
+use crate::diagnostics::{codes, Diagnostic};
+use crate::coercion::{self, TypeMode};
+use crate::interp::parse_numeric;
+use crate::types::{IntWidth, LiteralType, Signature, SignatureParameter, Type, TypeContext, Value};
+
+/// Infers literal types and checks the constructs that depend on them
+pub struct LiteralChecker<'a> {
+    constants: HashMap<String, Type>,
+    scope: HashMap<String, Type>,
+    int_width: IntWidth,
+    type_mode: TypeMode,
+    file: String,
+    diagnostics: Vec<Diagnostic>,
+
+    /// Signatures of top-level functions, by lowercase name
+    functions: HashMap<String, Signature>,
+
+    /// Declared classes and interfaces with their parents
+    classes: TypeContext,
+
+    /// Cases of declared enums, by lowercase enum name: (enum, cases)
+    enums: HashMap<String, (String, Vec<String>)>,
+
+    /// `match` statements with an arm for every value of their subject,
+    /// by id: the subject's variable, if it is one
+    exhaustive_matches: HashMap<NodeId, Option<String>>,
+
+    /// Types returned so far by each enclosing function or closure
+    returns: Vec<Vec<Type>>,
+
+    /// Class whose method is being checked
+    class: Option<String>,
+
+    /// Calls through a variable known to hold a named function, by id of
+    /// the call expression: (variable, function)
+    direct_calls: HashMap<NodeId, (String, String)>,
+
+    /// Direct calls of user functions, by id of the call expression:
+    /// (lowercase function name, widened argument types)
+    call_types: HashMap<NodeId, (String, Vec<Type>)>,
+
+    /// Calls dispatched by name at run time, by id of the call expression:
+    /// why the callee is not known
+    dynamic_calls: HashMap<NodeId, String>,
+
+    /// Index of the checked program, which numbers its nodes
+    index: Option<AstIndex<'a>>,
+}
+
+/// Normalized array key
+#[derive(PartialEq, Eq, Hash)]
+enum Key {
+    Int(i64),
+    Str(String),
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Key::Int(n) => write!(f, "{}", n),
+            Key::Str(s) => write!(f, "{}", LiteralType::String(s.clone())),
+        }
+    }
+}
+
+impl<'a> LiteralChecker<'a> {
+    pub fn new(file: impl Into<String>) -> Self {
+        Self {
+            constants: HashMap::new(),
+            scope: HashMap::new(),
+            int_width: IntWidth::default(),
+            type_mode: TypeMode::default(),
+            file: file.into(),
+            diagnostics: Vec::new(),
+            functions: HashMap::new(),
+            classes: TypeContext::new(),
+            enums: HashMap::new(),
+            exhaustive_matches: HashMap::new(),
+            returns: Vec::new(),
+            class: None,
+            direct_calls: HashMap::new(),
+            call_types: HashMap::new(),
+            dynamic_calls: HashMap::new(),
+            index: None,
+        }
+    }
+
+    /// Set the width of PHP `int` used for constant folding
+    pub fn with_int_width(mut self, int_width: IntWidth) -> Self {
+        self.int_width = int_width;
+        self
+    }
+
+    /// Check literal arguments of calls through callables in strict or coercive mode
+    pub fn with_type_mode(mut self, type_mode: TypeMode) -> Self {
+        self.type_mode = type_mode;
+        self
+    }
+
+    /// Know the functions, classes and enums declared by stub files
+    pub fn with_stubs(mut self, stubs: &[AstNode]) -> Self {
+        self.collect_functions(stubs);
+        self.classes.register_declarations(stubs);
+        self.collect_enums(stubs);
+        self
+    }
+
+    /// Check a program
+    pub fn check(&mut self, ast: &'a [AstNode]) {
+        self.index = Some(AstIndex::build(ast));
+        self.collect_functions(ast);
+        self.classes.register_declarations(ast);
+        self.collect_enums(ast);
+        self.collect_constants(ast);
+        self.check_nodes(ast);
+    }
+
+    /// Take the diagnostics reported so far
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// Take the calls found to go through a variable holding a named function
+    ///
+    /// Calls are identified by the id of their expression in an
+    /// [`AstIndex`] of the checked program, which is the same for its copies.
+    pub(crate) fn take_direct_calls(&mut self) -> HashMap<NodeId, (String, String)> {
+        std::mem::take(&mut self.direct_calls)
+    }
+
+    /// Take the argument types of direct calls to user functions
+    ///
+    /// Keyed by call id like [`take_direct_calls`](Self::take_direct_calls).
+    pub(crate) fn take_call_types(&mut self) -> HashMap<NodeId, (String, Vec<Type>)> {
+        std::mem::take(&mut self.call_types)
+    }
+
+    /// Take the calls whose callee is only known at run time
+    ///
+    /// Keyed by call id like [`take_direct_calls`](Self::take_direct_calls).
+    pub(crate) fn take_dynamic_calls(&mut self) -> HashMap<NodeId, String> {
+        std::mem::take(&mut self.dynamic_calls)
+    }
+
+    /// Take the `match` statements found to cover every value of their subject
+    ///
+    /// Keyed by statement id like [`take_direct_calls`](Self::take_direct_calls).
+    pub(crate) fn take_exhaustive_matches(&mut self) -> HashMap<NodeId, Option<String>> {
+        std::mem::take(&mut self.exhaustive_matches)
+    }
+
+    /// Signature of the function a value calls, when it is callable
+    pub fn callable(&self, typ: &Type) -> Option<Signature> {
+        match typ {
+            Type::Callable(signature) => Some(signature.as_ref().clone()),
+            Type::Literal(LiteralType::String(name)) => {
+                self.functions.get(&name.trim_start_matches('\\').to_lowercase()).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    /// Type of an expression in the current scope
+    pub fn infer(&self, expr: &Expression) -> Type {
+        match expr {
+            Expression::Literal(Literal::Int(n)) => match self.int_width.literal(*n) {
+                Value::Int(n) => Type::Literal(LiteralType::Int(n)),
+                _ => Type::Float,
+            },
+            Expression::Literal(Literal::String(s)) => Type::Literal(LiteralType::String(s.clone())),
+            Expression::Literal(Literal::Bool(b)) => Type::Literal(LiteralType::Bool(*b)),
+            Expression::Literal(Literal::Float(_)) => Type::Float,
+            Expression::Literal(Literal::Null) => Type::Null,
+            Expression::Literal(Literal::Array(_)) | Expression::Array { .. } => Type::Array(Box::new(Type::Unknown)),
+            Expression::Variable(name) => self.scope.get(name).cloned().unwrap_or(Type::Unknown),
+            Expression::Constant(name) => self.constant(name),
+            Expression::BinaryOp { left, op: BinaryOperator::Coalesce, right }
+            | Expression::NullCoalescing { left, right } => coalesce(self.infer(left), self.infer(right)),
+            Expression::BinaryOp { left, op, right } => self.binary(op, self.infer(left), self.infer(right)),
+            Expression::PropertyAccess { .. } | Expression::MethodCall { .. } => match self.member(expr) {
+                (typ, true) => Type::union([typ, Type::Null]),
+                (typ, false) => typ,
+            },
+            Expression::UnaryOp { op, expr } => match (op, self.infer(expr)) {
+                (UnaryOperator::Not, Type::Literal(literal)) => Type::Literal(LiteralType::Bool(!truthy(&literal))),
+                (UnaryOperator::Not, _) => Type::Bool,
+                (UnaryOperator::Plus, Type::Literal(LiteralType::Int(n))) => Type::Literal(LiteralType::Int(n)),
+                (UnaryOperator::Minus, Type::Literal(LiteralType::Int(n))) => self.int(self.int_width.sub(0, n)),
+                _ => Type::Unknown,
+            },
+            Expression::Ternary { condition, true_expr, false_expr } => match self.infer(condition) {
+                Type::Literal(literal) if truthy(&literal) => self.infer(true_expr),
+                Type::Literal(_) | Type::Null => self.infer(false_expr),
+                _ => Type::union([self.infer(true_expr), self.infer(false_expr)]),
+            },
+            Expression::ShortTernary { condition, false_expr } => match self.infer(condition) {
+                Type::Literal(literal) if truthy(&literal) => Type::Literal(literal),
+                Type::Literal(_) | Type::Null => self.infer(false_expr),
+                typ => Type::union([typ, self.infer(false_expr)]),
+            },
+            Expression::Assignment { target, op, value } => match compound_operator(op) {
+                None if *op == AssignmentOperator::Assign => self.infer(value),
+                Some(op) => self.binary(&op, self.infer(target), self.infer(value)),
+                None => Type::Unknown,
+            },
+            Expression::Closure(closure) => Type::Callable(Box::new(signature(
+                &closure.parameters,
+                closure.return_type.clone().unwrap_or(Type::Unknown),
+                None,
+            ))),
+            Expression::ClassConstant { class, name } => match class.as_ref() {
+                Expression::Constant(class) => self.enums.get(&class.trim_start_matches('\\').to_lowercase())
+                    .filter(|(_, cases)| cases.contains(name))
+                    .map_or(Type::Unknown, |(class, _)| {
+                        Type::Literal(LiteralType::EnumCase(class.clone(), name.clone()))
+                    }),
+                _ => Type::Unknown,
+            },
+            Expression::New { class, .. } => match class.as_ref() {
+                // `new static` is typed as the enclosing class, which it may extend
+                Expression::Constant(name) if matches!(name.to_lowercase().as_str(), "self" | "static") => {
+                    self.class.clone().map_or(Type::Unknown, Type::Object)
+                }
+                Expression::Constant(name) if name.eq_ignore_ascii_case("parent") => self.class.as_deref()
+                    .and_then(|class| self.classes.get_class_info(class)?.parent.clone())
+                    .map_or(Type::Unknown, Type::Object),
+                Expression::Constant(name) => Type::Object(name.clone()),
+                _ => Type::Unknown,
+            },
+            Expression::Cast { target_type, expr } => match (target_type, self.infer(expr)) {
+                (Type::String, Type::Literal(literal)) => to_string(&literal)
+                    .map_or(Type::String, |s| Type::Literal(LiteralType::String(s))),
+                (Type::Bool, Type::Literal(literal)) => Type::Literal(LiteralType::Bool(truthy(&literal))),
+                (Type::Int, Type::Literal(LiteralType::Int(n))) => Type::Literal(LiteralType::Int(n)),
+                (Type::Int, Type::Literal(LiteralType::Bool(b))) => Type::Literal(LiteralType::Int(b as i64)),
+                (target, _) => target.clone(),
+            },
+            _ => Type::Unknown,
+        }
+    }
+
+    /// Type of a property fetch or method call, and whether a `?->` earlier
+    /// in the chain may have short-circuited it to `null`
+    fn member(&self, expr: &Expression) -> (Type, bool) {
+        let (object, nullsafe) = match expr {
+            Expression::PropertyAccess { object, nullsafe, .. } | Expression::MethodCall { object, nullsafe, .. } => {
+                (object.as_ref(), *nullsafe)
+            }
+            _ => return (self.infer(expr), false),
+        };
+        let (object_type, short) = match object {
+            Expression::PropertyAccess { .. } | Expression::MethodCall { .. } => self.member(object),
+            _ => (self.infer(object), false),
+        };
+        let short = short || (nullsafe && object_type.can_be_null());
+        let class = match object_type.non_null_type() {
+            Some(Type::Object(class)) => class,
+            _ => return (Type::Unknown, short),
+        };
+        let typ = match expr {
+            Expression::PropertyAccess { property, .. } => self.classes.property_type(&class, property),
+            Expression::MethodCall { method, .. } => self.classes.method_return_type(&class, method),
+            _ => None,
+        };
+        (typ.unwrap_or(Type::Unknown), short)
+    }
+
+    fn int(&self, value: Value) -> Type {
+        match value {
+            Value::Int(n) => Type::Literal(LiteralType::Int(n)),
+            _ => Type::Float,
+        }
+    }
+
+    /// Fold a binary operation over the operand types
+    fn binary(&self, op: &BinaryOperator, left: Type, right: Type) -> Type {
+        use LiteralType as L;
+        match (op, left, right) {
+            (BinaryOperator::Concat, Type::Literal(l), Type::Literal(r)) => match (to_string(&l), to_string(&r)) {
+                (Some(l), Some(r)) => Type::Literal(L::String(l + &r)),
+                _ => Type::String,
+            },
+            (BinaryOperator::Concat, ..) => Type::String,
+            (BinaryOperator::Add, Type::Literal(L::Int(a)), Type::Literal(L::Int(b))) => self.int(self.int_width.add(a, b)),
+            (BinaryOperator::Sub, Type::Literal(L::Int(a)), Type::Literal(L::Int(b))) => self.int(self.int_width.sub(a, b)),
+            (BinaryOperator::Mul, Type::Literal(L::Int(a)), Type::Literal(L::Int(b))) => self.int(self.int_width.mul(a, b)),
+            // A known type without null can never be identical to null
+            (BinaryOperator::Identical, Type::Null, other) | (BinaryOperator::Identical, other, Type::Null)
+                if other != Type::Unknown && !other.can_be_null() => Type::Literal(L::Bool(false)),
+            (BinaryOperator::NotIdentical, Type::Null, other) | (BinaryOperator::NotIdentical, other, Type::Null)
+                if other != Type::Unknown && !other.can_be_null() => Type::Literal(L::Bool(true)),
+            (BinaryOperator::Identical, Type::Literal(l), Type::Literal(r)) => Type::Literal(L::Bool(l == r)),
+            (BinaryOperator::NotIdentical, Type::Literal(l), Type::Literal(r)) => Type::Literal(L::Bool(l != r)),
+            (
+                BinaryOperator::Equal | BinaryOperator::NotEqual | BinaryOperator::Identical | BinaryOperator::NotIdentical
+                | BinaryOperator::Less | BinaryOperator::LessEqual | BinaryOperator::Greater | BinaryOperator::GreaterEqual
+                | BinaryOperator::And | BinaryOperator::Or | BinaryOperator::Xor,
+                ..,
+            ) => Type::Bool,
+            _ => Type::Unknown,
+        }
+    }
+
+    /// Type of a constant fetch
+    fn constant(&self, name: &str) -> Type {
+        // Unqualified constants fall back to the global namespace
+        let global = name.rsplit('\\').next().unwrap_or(name);
+        if let Some(typ) = self.constants.get(global) {
+            return typ.clone();
+        }
+        if let Some(value) = self.int_width.constant(global) {
+            return Type::Literal(LiteralType::Int(value));
+        }
+        match global.to_lowercase().as_str() {
+            "true" => Type::Literal(LiteralType::Bool(true)),
+            "false" => Type::Literal(LiteralType::Bool(false)),
+            "null" => Type::Null,
+            _ => match global {
+                "PHP_EOL" => Type::Literal(LiteralType::String("\n".to_string())),
+                "PHP_VERSION" => Type::Literal(LiteralType::String(crate::PHP_VERSION.to_string())),
+                "PHP_FLOAT_EPSILON" | "PHP_FLOAT_MAX" | "M_PI" | "NAN" | "INF" => Type::Float,
+                _ => Type::Unknown,
+            },
+        }
+    }
+
+    /// Record the signatures of top-level functions
+    fn collect_functions(&mut self, ast: &[AstNode]) {
+        for node in ast {
+            match node {
+                AstNode::Program(nodes) => self.collect_functions(nodes),
+                AstNode::Namespace(ns) => self.collect_functions(&ns.statements),
+                AstNode::Function(decl) => {
+                    let return_type = decl.return_type.clone().unwrap_or(Type::Unknown);
+                    let signature = signature(&decl.parameters, return_type, Some(decl.name.clone()));
+                    self.functions.insert(decl.name.to_lowercase(), signature);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Record the cases of top-level enums
+    fn collect_enums(&mut self, ast: &[AstNode]) {
+        for node in ast {
+            match node {
+                AstNode::Program(nodes) => self.collect_enums(nodes),
+                AstNode::Namespace(ns) => self.collect_enums(&ns.statements),
+                AstNode::Enum(decl) => {
+                    let cases = decl.cases.iter().map(|case| case.name.clone()).collect();
+                    self.enums.insert(decl.name.trim_start_matches('\\').to_lowercase(), (decl.name.clone(), cases));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Record unconditional top-level `define()`s with a known value
+    fn collect_constants(&mut self, ast: &[AstNode]) {
+        for node in ast {
+            let expr = match node {
+                AstNode::Program(nodes) => {
+                    self.collect_constants(nodes);
+                    continue;
+                }
+                AstNode::Expression(expr) => expr.as_ref(),
+                AstNode::Statement(stmt) => match stmt.as_ref() {
+                    Statement::Expression(expr) => expr.as_ref(),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let Expression::FunctionCall { name, arguments } = expr else { continue };
+            if !matches!(name.as_ref(), Expression::Constant(f) if f.eq_ignore_ascii_case("define")) {
+                continue;
+            }
+            if let [Expression::Literal(Literal::String(constant)), value, ..] = arguments.as_slice() {
+                let typ = self.infer(value);
+                if typ != Type::Unknown && !self.constants.contains_key(constant) {
+                    self.constants.insert(constant.clone(), typ);
+                }
+            }
+        }
+    }
+
+    fn check_nodes(&mut self, nodes: &[AstNode]) {
+        for node in nodes {
+            match node {
+                AstNode::Program(nodes) => {
+                    let outer = self.type_mode.enter(nodes);
+                    self.check_nodes(nodes);
+                    self.type_mode = outer;
+                }
+                AstNode::Namespace(ns) => self.check_nodes(&ns.statements),
+                AstNode::Expression(expr) => self.expression(expr),
+                AstNode::Statement(stmt) => self.statement(stmt),
+                AstNode::Function(decl) => self.function(decl, None),
+                AstNode::Class(class) => {
+                    self.check_properties(class);
+                    let outer = self.class.replace(class.name.clone());
+                    for method in &class.methods {
+                        self.function(method, (!method.is_static).then_some(class.name.as_str()));
+                    }
+                    self.class = outer;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Check a function body in a scope of its own, with `$this` bound to
+    /// an instance of `this` in non-static methods
+    fn function(&mut self, decl: &FunctionDecl, this: Option<&str>) {
+        let outer = std::mem::take(&mut self.scope);
+        if let Some(class) = this {
+            self.scope.insert("this".to_string(), Type::Object(class.to_string()));
+        }
+        for parameter in &decl.parameters {
+            if let Some(default) = &parameter.default_value {
+                self.expression(default);
+            }
+            if let (Some(typ), false) = (&parameter.typ, parameter.is_variadic) {
+                self.scope.insert(parameter.name.clone(), typ.clone());
+            }
+        }
+        self.returns.push(Vec::new());
+        self.statement(&decl.body);
+        self.returns.pop();
+        self.scope = outer;
+    }
+
+    /// Check a closure body and infer the closure's signature
+    ///
+    /// Arrow functions see the enclosing scope by value; other closures see
+    /// only the variables they capture by value with `use`.
+    fn closure(&mut self, closure: &ClosureDecl) -> Signature {
+        let mut scope = if closure.is_arrow { self.scope.clone() } else { HashMap::new() };
+        for captured in closure.uses.iter().filter(|captured| !captured.by_reference) {
+            if let Some(typ) = self.scope.get(&captured.name) {
+                scope.insert(captured.name.clone(), typ.clone());
+            }
+        }
+        for parameter in &closure.parameters {
+            if let Some(default) = &parameter.default_value {
+                self.expression(default);
+            }
+        }
+        let outer = std::mem::replace(&mut self.scope, scope);
+        for parameter in &closure.parameters {
+            self.scope.remove(&parameter.name);
+            if let (Some(typ), false) = (&parameter.typ, parameter.is_variadic) {
+                self.scope.insert(parameter.name.clone(), typ.clone());
+            }
+        }
+        self.returns.push(Vec::new());
+        self.statement(&closure.body);
+        let returns = self.returns.pop().unwrap_or_default();
+        self.scope = outer;
+
+        let return_type = match &closure.return_type {
+            Some(declared) => declared.clone(),
+            None if returns.is_empty() => Type::Null,
+            None => Type::union(returns),
+        };
+        signature(&closure.parameters, return_type, None)
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Expression(expr) | Statement::Print(expr) | Statement::Throw(expr) | Statement::Empty(expr) => {
+                self.expression(expr)
+            }
+            Statement::Echo(exprs) | Statement::Unset(exprs) | Statement::Isset(exprs) => {
+                exprs.iter().for_each(|e| self.expression(e))
+            }
+            Statement::Block(stmts) => stmts.iter().for_each(|s| self.statement(s)),
+            Statement::If { condition, then_branch, else_branch } => {
+                self.expression(condition);
+                let (when_true, when_false) = null_check(condition)
+                    .map_or((None, None), |(name, is_null)| if is_null { (None, Some(name)) } else { (Some(name), None) });
+                let before = self.scope.clone();
+                self.narrow(when_true);
+                self.statement(then_branch);
+                let then_scope = std::mem::replace(&mut self.scope, before);
+                self.narrow(when_false);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+                // A branch that never falls through does not reach the join
+                let else_terminates = else_branch.as_deref().is_some_and(crate::unreachable::terminates);
+                match (crate::unreachable::terminates(then_branch), else_terminates) {
+                    (true, _) => {}
+                    (false, true) => self.scope = then_scope,
+                    (false, false) => self.join(then_scope),
+                }
+            }
+            Statement::Return(expr) => {
+                if let Some(expr) = expr {
+                    self.expression(expr);
+                }
+                let typ = expr.as_ref().map_or(Type::Null, |expr| self.infer(expr));
+                if let Some(returns) = self.returns.last_mut() {
+                    returns.push(typ);
+                }
+            }
+            Statement::Break(expr) | Statement::Continue(expr) | Statement::Die(expr) => {
+                if let Some(expr) = expr {
+                    self.expression(expr);
+                }
+            }
+            Statement::Match { expression, arms, .. } => {
+                self.expression(expression);
+                self.check_match(stmt, expression, arms);
+                self.opaque(stmt, |this| {
+                    for arm in arms {
+                        arm.patterns.iter().for_each(|p| this.expression(p));
+                        this.statement(&arm.body);
+                    }
+                });
+            }
+            Statement::While { condition, body } | Statement::DoWhile { body, condition } => {
+                self.opaque(stmt, |this| {
+                    this.expression(condition);
+                    this.statement(body);
+                });
+            }
+            Statement::For { init, condition, update, body } => {
+                init.iter().for_each(|e| self.expression(e));
+                self.opaque(stmt, |this| {
+                    condition.iter().chain(update).for_each(|e| this.expression(e));
+                    this.statement(body);
+                });
+            }
+            Statement::Foreach { array, body, .. } => {
+                self.expression(array);
+                self.opaque(stmt, |this| this.statement(body));
+            }
+            Statement::Switch { expression, cases } => {
+                self.expression(expression);
+                self.opaque(stmt, |this| {
+                    for case in cases {
+                        if let Some(condition) = &case.condition {
+                            this.expression(condition);
+                        }
+                        case.statements.iter().for_each(|s| this.statement(s));
+                    }
+                });
+            }
+            Statement::Try { try_block, catch_blocks, finally_block } => {
+                self.opaque(stmt, |this| {
+                    this.statement(try_block);
+                    catch_blocks.iter().for_each(|catch| this.statement(&catch.body));
+                    if let Some(finally_block) = finally_block {
+                        this.statement(finally_block);
+                    }
+                });
+            }
+            Statement::Global(names) | Statement::Static(names) => {
+                names.iter().for_each(|name| { self.scope.remove(name); });
+            }
+            Statement::Declare { body, .. } => self.statement(body),
+            Statement::Declaration(node) => self.check_nodes(std::slice::from_ref(node)),
+        }
+    }
+
+    /// Check a statement whose variables may be assigned any number of times
+    fn opaque(&mut self, stmt: &Statement, check: impl FnOnce(&mut Self)) {
+        let mut assigned = Vec::new();
+        assigned_in_statement(stmt, &mut assigned);
+        for name in &assigned {
+            self.scope.remove(name);
+        }
+        check(self);
+        for name in &assigned {
+            self.scope.remove(name);
+        }
+    }
+
+    /// Remove `null` from the type of a variable known to be non-null
+    fn narrow(&mut self, name: Option<&str>) {
+        let Some(name) = name else { return };
+        if let Some(typ) = self.scope.get(name).and_then(Type::non_null_type) {
+            self.scope.insert(name.to_string(), typ);
+        }
+    }
+
+    /// Merge the scope of another control-flow path into the current one
+    fn join(&mut self, other: HashMap<String, Type>) {
+        let scope = std::mem::take(&mut self.scope);
+        self.scope = scope.into_iter()
+            .filter_map(|(name, typ)| {
+                let merged = Type::union([typ, other.get(&name)?.clone()]);
+                (merged != Type::Unknown).then_some((name, merged))
+            })
+            .collect();
+    }
+
+    /// Check an expression that only runs on some paths
+    fn conditional(&mut self, expr: &Expression) {
+        let before = self.scope.clone();
+        self.expression(expr);
+        self.join(before);
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Assignment { target, op, value } => {
+                let typ = match value.as_ref() {
+                    Expression::Closure(closure) => Type::Callable(Box::new(self.closure(closure))),
+                    value => {
+                        self.expression(value);
+                        self.infer(expr)
+                    }
+                };
+                match target.as_ref() {
+                    Expression::Variable(name) => {
+                        if typ == Type::Unknown {
+                            self.scope.remove(name);
+                        } else {
+                            self.scope.insert(name.clone(), typ);
+                        }
+                    }
+                    target => {
+                        self.check_property_write(target, op, &typ);
+                        self.expression(target);
+                        let mut assigned = Vec::new();
+                        assigned_in_expression(target, &mut assigned);
+                        for name in &assigned {
+                            self.scope.remove(name);
+                        }
+                    }
+                }
+            }
+            Expression::UnaryOp { op, expr } => {
+                self.expression(expr);
+                if matches!(op, UnaryOperator::PreInc | UnaryOperator::PreDec | UnaryOperator::PostInc | UnaryOperator::PostDec) {
+                    if let Expression::Variable(name) = expr.as_ref() {
+                        self.scope.remove(name);
+                    }
+                }
+            }
+            Expression::Ternary { condition, true_expr, false_expr } => {
+                self.expression(condition);
+                self.conditional(true_expr);
+                self.conditional(false_expr);
+            }
+            Expression::BinaryOp { left, op: BinaryOperator::And | BinaryOperator::Or | BinaryOperator::Coalesce, right }
+            | Expression::ShortTernary { condition: left, false_expr: right }
+            | Expression::NullCoalescing { left, right } => {
+                self.expression(left);
+                self.conditional(right);
+            }
+            Expression::Literal(Literal::Array(elements)) | Expression::Array { elements } => {
+                for element in elements {
+                    if let Some(key) = &element.key {
+                        self.expression(key);
+                    }
+                    self.expression(&element.value);
+                }
+                self.check_array_keys(elements);
+            }
+            Expression::ArrayAccess { array, index } => {
+                self.expression(array);
+                self.expression(index);
+                self.array_key(index);
+            }
+            Expression::FunctionCall { name, arguments } => {
+                self.expression(name);
+                // Typed as each is evaluated, before later arguments can reassign it
+                let types: Vec<Type> = arguments.iter()
+                    .map(|a| {
+                        self.expression(a);
+                        self.infer(a).widen()
+                    })
+                    .collect();
+                let callee = match name.as_ref() {
+                    Expression::Constant(function) => {
+                        let global = function.rsplit('\\').next().unwrap_or(function).to_lowercase();
+                        let format = match global.as_str() {
+                            "printf" | "sprintf" => Some(0),
+                            "fprintf" => Some(1),
+                            _ => None,
+                        };
+                        if let Some(format) = format {
+                            self.check_format(&global, format, arguments);
+                            return;
+                        }
+                        if matches!(global.as_str(), "extract" | "parse_str" | "eval") {
+                            // These can write any variable of the scope
+                            self.scope.clear();
+                        }
+                        let key = function.trim_start_matches('\\').to_lowercase();
+                        let callee = self.functions.get(&key).cloned();
+                        if let Some(signature) = &callee {
+                            if let Some(id) = self.expression_id(expr) {
+                                self.call_types.insert(id, (key, types));
+                            }
+                            for (i, argument) in arguments.iter().enumerate() {
+                                if let Type::Object(class) = self.infer(argument) {
+                                    self.check_instance_argument(signature, i, &class);
+                                }
+                            }
+                        }
+                        callee
+                    }
+                    Expression::Variable(variable) => {
+                        let callee = self.check_dynamic_call(expr, variable, arguments);
+                        if callee.as_ref().is_none_or(|signature| signature.target.is_none()) {
+                            self.dynamic_call(expr, format!("${} is not known to hold a named function", variable));
+                        }
+                        callee
+                    }
+                    _ => {
+                        self.dynamic_call(expr, "callee is computed at run time".to_string());
+                        None
+                    }
+                };
+                self.forget_arguments(callee.as_ref(), arguments);
+            }
+            Expression::MethodCall { object, method, arguments, .. } => {
+                self.expression(object);
+                let receiver = match object.as_ref() {
+                    Expression::PropertyAccess { .. } | Expression::MethodCall { .. } => self.member(object).0,
+                    _ => self.infer(object),
+                };
+                let reason = match receiver.non_null_type() {
+                    Some(Type::Object(class)) if self.classes.method_return_type(&class, method).is_some() => None,
+                    Some(Type::Object(class)) => Some(format!("{}::{}() is not declared", class, method)),
+                    _ => Some(format!("receiver of ->{}() has type {}", method, receiver)),
+                };
+                if let Some(reason) = reason {
+                    self.dynamic_call(expr, reason);
+                }
+                arguments.iter().for_each(|a| self.expression(a));
+                self.forget_arguments(None, arguments);
+            }
+            Expression::New { class: object, arguments } => {
+                self.expression(object);
+                arguments.iter().for_each(|a| self.expression(a));
+                self.forget_arguments(None, arguments);
+            }
+            Expression::Include { file, .. } => {
+                self.expression(file);
+                // The included file runs in this scope
+                self.scope.clear();
+            }
+            Expression::Closure(closure) => {
+                self.closure(closure);
+            }
+            _ => children(expr).into_iter().for_each(|child| self.expression(child)),
+        }
+    }
+
+    /// Forget variables passed where the callee might take them by reference
+    ///
+    /// A callee without a known signature may write any variable argument.
+    fn forget_arguments(&mut self, callee: Option<&Signature>, arguments: &[Expression]) {
+        for (i, argument) in arguments.iter().enumerate() {
+            let Expression::Variable(name) = argument else { continue };
+            let by_value = callee.is_some_and(|signature| {
+                signature.parameters.get(i)
+                    .or_else(|| signature.parameters.last().filter(|p| p.variadic))
+                    .is_some_and(|p| !p.by_reference)
+            });
+            if !by_value {
+                self.scope.remove(name);
+            }
+        }
+    }
+
+    /// Check a call through a variable against what the variable holds
+    fn check_dynamic_call(&mut self, call: &Expression, variable: &str, arguments: &[Expression]) -> Option<Signature> {
+        let typ = self.scope.get(variable)?.clone();
+        let Some(signature) = self.callable(&typ) else {
+            if matches!(typ.widen(), Type::Int | Type::Float | Type::Bool | Type::Null | Type::Array(_)) {
+                self.report(Diagnostic::error(
+                    codes::CALLABLE_CALL,
+                    format!("Value of type {} is not callable", coercion::declared_name(&typ.widen())),
+                ));
+            }
+            return None;
+        };
+
+        let required = signature.required_parameters();
+        if arguments.len() < required {
+            let bound = if required < signature.parameters.len() { "at least" } else { "exactly" };
+            self.report(
+                Diagnostic::error(
+                    codes::CALLABLE_CALL,
+                    format!(
+                        "Too few arguments to function {}(), {} passed and {} {} expected",
+                        signature.display_name(), arguments.len(), bound, required
+                    ),
+                )
+                .with_note(format!("${} has type {}", variable, signature)),
+            );
+        }
+
+        for (i, argument) in arguments.iter().enumerate() {
+            let Some(parameter) = signature.parameters.get(i)
+                .or_else(|| signature.parameters.last().filter(|p| p.variadic))
+            else {
+                break;
+            };
+            let typ = self.infer(argument);
+            if let Type::Object(class) = &typ {
+                self.check_instance_argument(&signature, i, class);
+            }
+            let Some(value) = runtime_value(&typ) else { continue };
+            if let Err(given) = coercion::coerce(value, &parameter.typ, self.type_mode) {
+                self.report(Diagnostic::error(
+                    codes::ARGUMENT_TYPE,
+                    format!(
+                        "{}(): Argument #{} (${}) must be of type {}, {} given",
+                        signature.display_name(), i + 1, parameter.name, coercion::declared_name(&parameter.typ), given
+                    ),
+                ));
+            }
+        }
+
+        if let (Some(target), Some(id)) = (&signature.target, self.expression_id(call)) {
+            self.direct_calls.insert(id, (variable.to_string(), target.clone()));
+        }
+        Some(signature)
+    }
+
+    /// Id of an expression of the checked program
+    fn expression_id(&self, expr: &Expression) -> Option<NodeId> {
+        self.index.as_ref()?.expression_id(expr)
+    }
+
+    /// Record a call whose callee is only known at run time
+    fn dynamic_call(&mut self, call: &Expression, reason: String) {
+        if let Some(id) = self.expression_id(call) {
+            self.dynamic_calls.insert(id, reason);
+        }
+    }
+
+    /// Report an instance of `class` passed where the parameter's class types reject it
+    fn check_instance_argument(&mut self, signature: &Signature, position: usize, class: &str) {
+        let Some(parameter) = signature.parameters.get(position)
+            .or_else(|| signature.parameters.last().filter(|p| p.variadic))
+        else {
+            return;
+        };
+        if self.classes.instance_satisfies(class, &parameter.typ) != Some(false) {
+            return;
+        }
+        let mut diagnostic = Diagnostic::error(
+            codes::ARGUMENT_TYPE,
+            format!(
+                "{}(): Argument #{} (${}) must be of type {}, {} given",
+                signature.display_name(), position + 1, parameter.name,
+                coercion::declared_name(&parameter.typ), class.trim_start_matches('\\')
+            ),
+        );
+        let intersection = match &parameter.typ {
+            Type::Intersection(members) => Some(members),
+            Type::Union(members) => members.iter().find_map(|m| match m {
+                Type::Intersection(members) => Some(members),
+                _ => None,
+            }),
+            _ => None,
+        };
+        if let Some(members) = intersection {
+            let missing: Vec<_> = members.iter()
+                .filter(|member| self.classes.instance_satisfies(class, member) == Some(false))
+                .map(coercion::declared_name)
+                .collect();
+            if !missing.is_empty() {
+                diagnostic = diagnostic.with_note(format!(
+                    "{} does not implement {}",
+                    class.trim_start_matches('\\'),
+                    missing.join(", ")
+                ));
+            }
+        }
+        self.report(diagnostic);
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic.with_file(self.file.clone()));
+    }
+
+    /// Report property declarations PHP rejects when the class is declared
+    fn check_properties(&mut self, class: &ClassDecl) {
+        for property in &class.properties {
+            let name = format!("{}::${}", class.name, property.name);
+            if property.is_readonly {
+                let problem = if property.is_static {
+                    Some(format!("Static property {} cannot be readonly", name))
+                } else if property.typ.is_none() {
+                    Some(format!("Readonly property {} must have type", name))
+                } else if property.default_value.is_some() {
+                    Some(format!("Readonly property {} cannot have default value", name))
+                } else {
+                    None
+                };
+                if let Some(problem) = problem {
+                    self.report(Diagnostic::error(codes::PROPERTY_WRITE, problem));
+                    continue;
+                }
+            }
+            // Defaults are checked without coercion, whatever the file's mode
+            let (Some(typ), Some(default)) = (&property.typ, &property.default_value) else { continue };
+            let Some(value) = runtime_value(&self.infer(default)) else { continue };
+            if let Err(given) = coercion::coerce(value, typ, TypeMode::Strict) {
+                self.report(Diagnostic::error(
+                    codes::PROPERTY_WRITE,
+                    format!(
+                        "Cannot use {} as default value for property {} of type {}",
+                        given, name, coercion::declared_name(typ)
+                    ),
+                ));
+            }
+        }
+    }
+
+    /// Report a write to a property of a known class that PHP would reject
+    /// with an `Error` or `TypeError`
+    fn check_property_write(&mut self, target: &Expression, op: &AssignmentOperator, value: &Type) {
+        let Expression::PropertyAccess { object, property, nullsafe: false } = target else { return };
+        let Type::Object(class) = self.infer(object) else { return };
+        let Some(declaring) = self.classes.declaring_class(&class, property) else { return };
+        let name = format!("{}::${}", declaring.name, property);
+        let typ = declaring.bind_relative(&declaring.properties[property], None);
+
+        let in_scope = self.class.as_deref().is_some_and(|current| current.eq_ignore_ascii_case(&declaring.name));
+        if declaring.readonly.contains(property) && !in_scope {
+            let scope = self.class.as_ref().map_or("global scope".to_string(), |class| format!("scope {}", class));
+            self.report(
+                Diagnostic::error(codes::PROPERTY_WRITE, format!("Cannot modify readonly property {}", name))
+                    .with_note(format!("readonly properties can only be initialized from the scope of {}, not {}", declaring.name, scope)),
+            );
+            return;
+        }
+
+        if *op != AssignmentOperator::Assign {
+            return;
+        }
+        let Some(value) = runtime_value(value) else { return };
+        if let Err(given) = coercion::coerce(value, &typ, self.type_mode) {
+            self.report(Diagnostic::error(
+                codes::PROPERTY_WRITE,
+                format!("Cannot assign {} to property {} of type {}", given, name, coercion::declared_name(&typ)),
+            ));
+        }
+    }
+
+    /// Report `match` subject values that no arm handles, and record
+    /// matches that handle them all
+    fn check_match(&mut self, stmt: &Statement, subject: &Expression, arms: &[MatchArm]) {
+        if arms.iter().any(|arm| arm.patterns.is_empty()) {
+            return;
+        }
+        let typ = self.infer(subject);
+        let Some(values) = self.singletons(&typ) else { return };
+        let mut covered = Vec::new();
+        for pattern in arms.iter().flat_map(|arm| &arm.patterns) {
+            match self.infer(pattern) {
+                value @ (Type::Literal(_) | Type::Null) => covered.push(value),
+                // A pattern of unknown value might handle anything
+                _ => return,
+            }
+        }
+        let missing: Vec<String> = values.iter()
+            .filter(|value| !covered.contains(value))
+            .map(ToString::to_string)
+            .collect();
+        if missing.is_empty() {
+            let variable = match subject {
+                Expression::Variable(name) => Some(name.clone()),
+                _ => None,
+            };
+            if let Some(id) = self.index.as_ref().and_then(|index| index.statement_id(stmt)) {
+                self.exhaustive_matches.insert(id, variable);
+            }
+            return;
+        }
+        let diagnostic = if values.len() == 1 {
+            Diagnostic::error(codes::UNHANDLED_MATCH, format!("Unhandled match case {}", missing[0]))
+        } else {
+            Diagnostic::warning(
+                codes::UNHANDLED_MATCH,
+                format!("Match is not exhaustive: unhandled case(s) {}", missing.join(", ")),
+            )
+        };
+        self.report(diagnostic.with_note(format!("the subject has type {}", typ)));
+    }
+
+    /// Every value of a type, with enums expanded to their cases
+    fn singletons(&self, typ: &Type) -> Option<Vec<Type>> {
+        match typ {
+            Type::Object(name) => {
+                let (name, cases) = self.enums.get(&name.trim_start_matches('\\').to_lowercase())?;
+                Some(cases.iter()
+                    .map(|case| Type::Literal(LiteralType::EnumCase(name.clone(), case.clone())))
+                    .collect())
+            }
+            Type::Union(members) => {
+                let mut values = Vec::new();
+                for member in members {
+                    for value in self.singletons(member)? {
+                        if !values.contains(&value) {
+                            values.push(value);
+                        }
+                    }
+                }
+                Some(values)
+            }
+            other => other.singletons(),
+        }
+    }
+
+    /// Report duplicate keys of an array literal
+    fn check_array_keys(&mut self, elements: &[ArrayElement]) {
+        let mut seen = HashSet::new();
+        let mut next_index = Some(0i64);
+        for element in elements {
+            if element.is_spread {
+                // The spread's elements, and so the next positions, are unknown
+                next_index = None;
+                continue;
+            }
+            let key = match &element.key {
+                Some(key) => self.array_key(key),
+                None => next_index.map(Key::Int),
+            };
+            match &key {
+                Some(Key::Int(n)) => next_index = next_index.map(|next| next.max(n.saturating_add(1))),
+                Some(Key::Str(_)) => {}
+                // An unknown key might be an integer
+                None if element.key.is_some() => next_index = None,
+                None => {}
+            }
+            let Some(key) = key else { continue };
+            let message = format!("Duplicate array key {}", key);
+            if !seen.insert(key) {
+                self.report(
+                    Diagnostic::warning(codes::ARRAY_KEY, message)
+                        .with_note("the later element overwrites the earlier one"),
+                );
+            }
+        }
+    }
+
+    /// Validate an array key and normalize it when its value is known
+    fn array_key(&mut self, key: &Expression) -> Option<Key> {
+        if let Expression::Literal(Literal::Float(x)) = key {
+            if x.fract() != 0.0 {
+                self.report(Diagnostic::warning(
+                    codes::ARRAY_KEY,
+                    format!("Implicit conversion from float {} to int loses precision", x),
+                ));
+            }
+            return x.is_finite().then_some(Key::Int(*x as i64));
+        }
+        match self.infer(key) {
+            Type::Literal(LiteralType::Int(n)) => Some(Key::Int(n)),
+            Type::Literal(LiteralType::Bool(b)) => Some(Key::Int(b as i64)),
+            Type::Literal(LiteralType::String(s)) => Some(match integer_key(&s) {
+                Some(n) => Key::Int(n),
+                None => Key::Str(s),
+            }),
+            Type::Null => Some(Key::Str(String::new())),
+            typ @ (Type::Array(_) | Type::AssociativeArray(_) | Type::Object(_) | Type::Literal(LiteralType::EnumCase(..))) => {
+                self.report(Diagnostic::error(
+                    codes::ARRAY_KEY,
+                    format!("Cannot use a value of type {} as an array key", typ.widen()),
+                ));
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Check a literal format string against the arguments passed with it
+    fn check_format(&mut self, function: &str, format: usize, arguments: &[Expression]) {
+        let Some(Type::Literal(LiteralType::String(text))) = arguments.get(format).map(|f| self.infer(f)) else {
+            return;
+        };
+        let conversions = match parse_format(&text) {
+            Ok(conversions) => conversions,
+            Err(message) => {
+                self.report(Diagnostic::error(codes::FORMAT_STRING, format!("{}(): {}", function, message)));
+                return;
+            }
+        };
+
+        let required = conversions.iter().map(|(argument, _)| *argument).max().unwrap_or(0);
+        let given = arguments.len() - format - 1;
+        if given < required {
+            self.report(Diagnostic::error(
+                codes::FORMAT_STRING,
+                format!(
+                    "{}(): {} arguments are required, {} given",
+                    function, required + format + 1, arguments.len()
+                ),
+            ));
+            return;
+        }
+
+        for (argument, specifier) in conversions {
+            if matches!(specifier, 's') {
+                continue;
+            }
+            let position = format + argument;
+            if let Type::Literal(LiteralType::String(s)) = self.infer(&arguments[position]) {
+                if parse_numeric(&s).is_none() {
+                    self.report(
+                        Diagnostic::warning(
+                            codes::FORMAT_STRING,
+                            format!(
+                                "{}(): Argument #{} for %{} is the non-numeric string {}",
+                                function, position + 1, specifier, LiteralType::String(s)
+                            ),
+                        )
+                        .with_note("it is formatted as 0"),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Parse a printf format into (1-based argument number, specifier) pairs
+fn parse_format(format: &str) -> Result<Vec<(usize, char)>, String> {
+    let mut conversions = Vec::new();
+    let mut next = 0;
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            continue;
+        }
+
+        // Argument number (`%2$s`), or a width when no `$` follows
+        let mut digits = String::new();
+        while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+            digits.push(*d);
+            chars.next();
+        }
+        let mut argument = None;
+        if !digits.is_empty() && chars.peek() == Some(&'$') {
+            chars.next();
+            match digits.parse::<usize>() {
+                Ok(n) if n > 0 => argument = Some(n),
+                _ => return Err("Argument number specifier must be greater than zero".to_string()),
+            }
+            digits.clear();
+        }
+
+        if digits.is_empty() {
+            // Flags, then width
+            loop {
+                match chars.peek() {
+                    Some('-' | '+' | ' ' | '0') => {
+                        chars.next();
+                    }
+                    Some('\'') => {
+                        chars.next();
+                        chars.next();
+                    }
+                    _ => break,
+                }
+            }
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                next += 1;
+                conversions.push((next, 'd'));
+            }
+            while chars.peek().is_some_and(char::is_ascii_digit) {
+                chars.next();
+            }
+        }
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                next += 1;
+                conversions.push((next, 'd'));
+            }
+            while chars.peek().is_some_and(char::is_ascii_digit) {
+                chars.next();
+            }
+        }
+        // Length modifiers are accepted and ignored
+        if chars.peek() == Some(&'l') {
+            chars.next();
+        }
+
+        match chars.next() {
+            Some(specifier @ ('b' | 'c' | 'd' | 'e' | 'E' | 'f' | 'F' | 'g' | 'G' | 'h' | 'H' | 'o' | 's' | 'u' | 'x' | 'X')) => {
+                let argument = argument.unwrap_or_else(|| {
+                    next += 1;
+                    next
+                });
+                conversions.push((argument, specifier));
+            }
+            Some(other) => return Err(format!("Unknown format specifier \"{}\"", other)),
+            None => return Err("Missing format specifier at end of string".to_string()),
+        }
+    }
+    Ok(conversions)
+}
+
+/// Mark `match` statements that handle every value of their subject as
+/// exhaustive, so code generation can omit the `UnhandledMatchError` path
+///
+/// Subjects held in variables that may change through an alias are not
+/// trusted. Returns the number of statements marked.
+pub fn mark_exhaustive_matches(ast: &mut [AstNode], int_width: IntWidth) -> usize {
+    let mut checker = LiteralChecker::new(String::new()).with_int_width(int_width);
+    checker.check(ast);
+    let matches = checker.take_exhaustive_matches();
+    if matches.is_empty() {
+        return 0;
+    }
+
+    let mut aliases = crate::devirtualize::Aliases::default();
+    ast.iter().for_each(|node| aliases.visit_node(node));
+    if aliases.dynamic_scope {
+        return 0;
+    }
+    let matches = matches.into_iter()
+        .filter(|(_, variable)| variable.as_ref().is_none_or(|v| !aliases.variables.contains(v)))
+        .map(|(id, _)| id);
+
+    let mut marked = 0;
+    rewrite_statements(ast, matches, |_, stmt| {
+        if let Statement::Match { exhaustive, .. } = stmt {
+            *exhaustive = true;
+            marked += 1;
+        }
+    });
+    marked
+}
+
+/// Signature of a function or closure from its declaration
+fn signature(parameters: &[Parameter], return_type: Type, target: Option<String>) -> Signature {
+    Signature {
+        parameters: parameters.iter()
+            .map(|parameter| {
+                let implicitly_nullable = matches!(parameter.default_value, Some(Expression::Literal(Literal::Null)));
+                let typ = match &parameter.typ {
+                    Some(typ) if implicitly_nullable => Type::union([typ.clone(), Type::Null]),
+                    Some(typ) => typ.clone(),
+                    None => Type::Unknown,
+                };
+                SignatureParameter {
+                    name: parameter.name.clone(),
+                    typ,
+                    optional: parameter.default_value.is_some(),
+                    variadic: parameter.is_variadic,
+                    by_reference: parameter.is_reference,
+                }
+            })
+            .collect(),
+        return_type,
+        target,
+    }
+}
+
+/// Runtime value of a literal type
+fn runtime_value(typ: &Type) -> Option<crate::runtime::Value> {
+    use crate::runtime::Value as RuntimeValue;
+    Some(match typ {
+        Type::Literal(LiteralType::Int(n)) => RuntimeValue::Int(*n),
+        Type::Literal(LiteralType::String(s)) => RuntimeValue::String(s.clone()),
+        Type::Literal(LiteralType::Bool(b)) => RuntimeValue::Bool(*b),
+        Type::Null => RuntimeValue::Null,
+        _ => return None,
+    })
+}
+
+/// Integer value of a string key that PHP stores as an integer
+fn integer_key(s: &str) -> Option<i64> {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    let canonical = s == "0"
+        || (!digits.is_empty() && !digits.starts_with('0') && digits.bytes().all(|b| b.is_ascii_digit()));
+    if canonical { s.parse().ok() } else { None }
+}
+
+fn truthy(literal: &LiteralType) -> bool {
+    match literal {
+        LiteralType::Int(n) => *n != 0,
+        LiteralType::String(s) => !s.is_empty() && s != "0",
+        LiteralType::Bool(b) => *b,
+        LiteralType::EnumCase(..) => true,
+    }
+}
+
+fn to_string(literal: &LiteralType) -> Option<String> {
+    Some(match literal {
+        LiteralType::Int(n) => n.to_string(),
+        LiteralType::String(s) => s.clone(),
+        LiteralType::Bool(b) => if *b { "1" } else { "" }.to_string(),
+        // Enums cannot be converted to string
+        LiteralType::EnumCase(..) => return None,
+    })
+}
+
+/// Variable compared against `null` by a condition, and whether the
+/// condition holds when it is null
+fn null_check(condition: &Expression) -> Option<(&str, bool)> {
+    match condition {
+        Expression::BinaryOp { left, op, right } => {
+            let is_null = match op {
+                BinaryOperator::Identical | BinaryOperator::Equal => true,
+                BinaryOperator::NotIdentical | BinaryOperator::NotEqual => false,
+                _ => return None,
+            };
+            match (left.as_ref(), right.as_ref()) {
+                (Expression::Variable(name), Expression::Literal(Literal::Null))
+                | (Expression::Literal(Literal::Null), Expression::Variable(name)) => Some((name, is_null)),
+                _ => None,
+            }
+        }
+        Expression::UnaryOp { op: UnaryOperator::Not, expr } => {
+            null_check(expr).map(|(name, is_null)| (name, !is_null))
+        }
+        Expression::FunctionCall { name, arguments } => match (name.as_ref(), arguments.as_slice()) {
+            (Expression::Constant(function), [Expression::Variable(variable)])
+                if function.trim_start_matches('\\').eq_ignore_ascii_case("is_null") =>
+            {
+                Some((variable, true))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Type of `left ?? right`: the right side only contributes when the left
+/// side may be null
+fn coalesce(left: Type, right: Type) -> Type {
+    match left {
+        Type::Null => right,
+        Type::Unknown => Type::Unknown,
+        left if !left.can_be_null() => left,
+        left => Type::union(left.non_null_type().into_iter().chain([right])),
+    }
+}
+
+/// Binary operator applied by a compound assignment
+fn compound_operator(op: &AssignmentOperator) -> Option<BinaryOperator> {
+    Some(match op {
+        AssignmentOperator::AddAssign => BinaryOperator::Add,
+        AssignmentOperator::SubAssign => BinaryOperator::Sub,
+        AssignmentOperator::MulAssign => BinaryOperator::Mul,
+        AssignmentOperator::ConcatAssign => BinaryOperator::Concat,
+        _ => return None,
+    })
+}
+
+/// Direct subexpressions of an expression
+fn children(expr: &Expression) -> Vec<&Expression> {
+    match expr {
+        Expression::Literal(Literal::Array(elements)) | Expression::Array { elements } => elements.iter()
+            .flat_map(|element| element.key.iter().chain(std::iter::once(&element.value)))
+            .collect(),
+        Expression::Literal(_) | Expression::Variable(_) | Expression::Constant(_) => vec![],
+        Expression::VariableVariable(inner) | Expression::Clone(inner) => vec![inner],
+        Expression::UnaryOp { expr, .. } | Expression::Cast { expr, .. } => vec![expr],
+        Expression::BinaryOp { left, right, .. }
+        | Expression::ShortTernary { condition: left, false_expr: right }
+        | Expression::NullCoalescing { left, right } => vec![left, right],
+        Expression::InstanceOf { expr, class } => vec![expr, class],
+        Expression::Ternary { condition, true_expr, false_expr } => vec![condition, true_expr, false_expr],
+        Expression::FunctionCall { name, arguments } | Expression::New { class: name, arguments } => {
+            std::iter::once(name.as_ref()).chain(arguments).collect()
+        }
+        Expression::MethodCall { object, arguments, .. } | Expression::StaticCall { class: object, arguments, .. } => {
+            std::iter::once(object.as_ref()).chain(arguments).collect()
+        }
+        Expression::PropertyAccess { object, .. }
+        | Expression::StaticPropertyAccess { class: object, .. }
+        | Expression::ClassConstant { class: object, .. } => vec![object],
+        Expression::ArrayAccess { array, index } => vec![array, index],
+        Expression::ArrayAppend { array } => vec![array],
+        Expression::Assignment { target, value, .. } => vec![target, value],
+        Expression::Include { file, .. } => vec![file],
+        Expression::Yield { key, value } => key.iter().chain(value).map(|e| e.as_ref()).collect(),
+        Expression::List { variables } => variables.iter().collect(),
+        // Closure bodies are checked as a scope of their own
+        Expression::Closure(_) => vec![],
+    }
+}
+
+/// Variable written through an assignment target such as `$a['k']->p`
+fn base_variable(target: &Expression) -> Option<&String> {
+    match target {
+        Expression::Variable(name) => Some(name),
+        Expression::ArrayAccess { array: inner, .. }
+        | Expression::ArrayAppend { array: inner }
+        | Expression::PropertyAccess { object: inner, .. } => base_variable(inner),
+        _ => None,
+    }
+}
+
+/// Variables an expression may assign
+fn assigned_in_expression(expr: &Expression, out: &mut Vec<String>) {
+    match expr {
+        Expression::Assignment { target, .. } => match target.as_ref() {
+            Expression::List { variables } => out.extend(variables.iter().filter_map(base_variable).cloned()),
+            target => out.extend(base_variable(target).cloned()),
+        },
+        Expression::UnaryOp {
+            op: UnaryOperator::PreInc | UnaryOperator::PreDec | UnaryOperator::PostInc | UnaryOperator::PostDec,
+            expr,
+        } => {
+            if let Expression::Variable(name) = expr.as_ref() {
+                out.push(name.clone());
+            }
+        }
+        _ => {}
+    }
+    children(expr).into_iter().for_each(|child| assigned_in_expression(child, out));
+}
+
+/// Variables a statement may assign
+pub(crate) fn assigned_in_statement(stmt: &Statement, out: &mut Vec<String>) {
+    match stmt {
+        Statement::Expression(expr) | Statement::Print(expr) | Statement::Throw(expr) | Statement::Empty(expr) => {
+            assigned_in_expression(expr, out)
+        }
+        Statement::Echo(exprs) | Statement::Unset(exprs) | Statement::Isset(exprs) => exprs.iter().for_each(|e| assigned_in_expression(e, out)),
+        Statement::Return(expr) | Statement::Break(expr) | Statement::Continue(expr) | Statement::Die(expr) => {
+            expr.iter().for_each(|e| assigned_in_expression(e, out))
+        }
+        Statement::Block(stmts) => stmts.iter().for_each(|s| assigned_in_statement(s, out)),
+        Statement::If { condition, then_branch, else_branch } => {
+            assigned_in_expression(condition, out);
+            assigned_in_statement(then_branch, out);
+            else_branch.iter().for_each(|s| assigned_in_statement(s, out));
+        }
+        Statement::While { condition, body } | Statement::DoWhile { body, condition } => {
+            assigned_in_expression(condition, out);
+            assigned_in_statement(body, out);
+        }
+        Statement::For { init, condition, update, body } => {
+            init.iter().chain(condition).chain(update).for_each(|e| assigned_in_expression(e, out));
+            assigned_in_statement(body, out);
+        }
+        Statement::Foreach { array, key, value, body } => {
+            assigned_in_expression(array, out);
+            out.extend(key.iter().cloned());
+            out.push(value.clone());
+            assigned_in_statement(body, out);
+        }
+        Statement::Switch { expression: subject, cases } => {
+            assigned_in_expression(subject, out);
+            for case in cases {
+                case.condition.iter().for_each(|c| assigned_in_expression(c, out));
+                case.statements.iter().for_each(|s| assigned_in_statement(s, out));
+            }
+        }
+        Statement::Match { expression: subject, arms, .. } => {
+            assigned_in_expression(subject, out);
+            for arm in arms {
+                arm.patterns.iter().for_each(|p| assigned_in_expression(p, out));
+                assigned_in_statement(&arm.body, out);
+            }
+        }
+        Statement::Try { try_block, catch_blocks, finally_block } => {
+            assigned_in_statement(try_block, out);
+            for catch in catch_blocks {
+                out.extend(catch.variable.iter().cloned());
+                assigned_in_statement(&catch.body, out);
+            }
+            finally_block.iter().for_each(|s| assigned_in_statement(s, out));
+        }
+        Statement::Global(names) | Statement::Static(names) => out.extend(names.iter().cloned()),
+        Statement::Declare { body, .. } => assigned_in_statement(body, out),
+        Statement::Declaration(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Expression {
+        Expression::Literal(Literal::String(s.to_string()))
+    }
+
+    fn assign(name: &str, value: Expression) -> AstNode {
+        AstNode::Expression(Box::new(Expression::Assignment {
+            target: Box::new(Expression::Variable(name.to_string())),
+            op: AssignmentOperator::Assign,
+            value: Box::new(value),
+        }))
+    }
+
+    fn check(ast: &[AstNode]) -> Vec<Diagnostic> {
+        let mut checker = LiteralChecker::new("app.php");
+        checker.check(ast);
+        checker.take_diagnostics()
+    }
+
+    #[test]
+    fn test_match_exhaustiveness() {
+        // $order = $desc ? 'sorted' : 'reversed'; match ($order) { 'sorted' => ... }
+        let order = assign("order", Expression::Ternary {
+            condition: Box::new(Expression::Variable("desc".to_string())),
+            true_expr: Box::new(string("sorted")),
+            false_expr: Box::new(Expression::Constant("REVERSED".to_string())),
+        });
+        let define = AstNode::Expression(Box::new(Expression::FunctionCall {
+            name: Box::new(Expression::Constant("define".to_string())),
+            arguments: vec![string("REVERSED"), string("reversed")],
+        }));
+        let check_match = |patterns: Vec<Expression>| AstNode::Statement(Box::new(Statement::Match {
+            expression: Box::new(Expression::Variable("order".to_string())),
+            arms: vec![MatchArm { patterns, body: Box::new(Statement::Block(vec![])) }],
+            exhaustive: false,
+        }));
+
+        let diagnostics = check(&[define.clone(), order.clone(), check_match(vec![string("sorted")])]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, codes::UNHANDLED_MATCH);
+        assert_eq!(diagnostics[0].message, "Match is not exhaustive: unhandled case(s) 'reversed'");
+
+        assert!(check(&[define, order, check_match(vec![string("sorted"), string("reversed")])]).is_empty());
+    }
+
+    #[test]
+    fn test_enum_match() {
+        let suit = AstNode::Enum(EnumDecl {
+            name: "Suit".to_string(),
+            backing_type: None,
+            cases: ["Hearts", "Spades"].iter()
+                .map(|name| EnumCase { name: name.to_string(), value: None })
+                .collect(),
+            methods: vec![],
+        });
+        let case = |name: &str| Expression::ClassConstant {
+            class: Box::new(Expression::Constant("Suit".to_string())),
+            name: name.to_string(),
+        };
+        // function color(Suit $suit) { match ($suit) { Suit::Hearts => ... } }
+        let color = |patterns: Vec<Expression>| AstNode::Function(FunctionDecl {
+            name: "color".to_string(),
+            parameters: vec![Parameter {
+                name: "suit".to_string(),
+                typ: Some(Type::Object("Suit".to_string())),
+                default_value: None,
+                is_reference: false,
+                is_variadic: false,
+            }],
+            return_type: None,
+            body: Box::new(Statement::Match {
+                expression: Box::new(Expression::Variable("suit".to_string())),
+                arms: vec![MatchArm { patterns, body: Box::new(Statement::Block(vec![])) }],
+                exhaustive: false,
+            }),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        });
+
+        let diagnostics = check(&[suit.clone(), color(vec![case("Hearts")])]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Match is not exhaustive: unhandled case(s) Suit::Spades");
+
+        let mut ast = vec![suit, color(vec![case("Spades"), case("Hearts")])];
+        assert!(check(&ast).is_empty());
+        assert_eq!(mark_exhaustive_matches(&mut ast, IntWidth::default()), 1);
+        let AstNode::Function(decl) = &ast[1] else { unreachable!() };
+        assert!(matches!(decl.body.as_ref(), Statement::Match { exhaustive: true, .. }));
+    }
+
+    #[test]
+    fn test_callables() {
+        let parameter = |name: &str| Parameter {
+            name: name.to_string(),
+            typ: Some(Type::Int),
+            default_value: None,
+            is_reference: false,
+            is_variadic: false,
+        };
+        let arrow = |parameters, body| Expression::Closure(Box::new(ClosureDecl {
+            parameters,
+            return_type: None,
+            uses: vec![],
+            body: Box::new(Statement::Return(Some(Box::new(body)))),
+            is_arrow: true,
+            is_static: false,
+        }));
+        let call = |variable: &str, arguments| AstNode::Expression(Box::new(Expression::FunctionCall {
+            name: Box::new(Expression::Variable(variable.to_string())),
+            arguments,
+        }));
+        let int = |n| Expression::Literal(Literal::Int(n));
+
+        // $add = fn(int $a, int $b) => $a + $b; $ready = fn() => 'ready'; $n = 5;
+        let ast = vec![
+            assign("add", arrow(vec![parameter("a"), parameter("b")], Expression::BinaryOp {
+                left: Box::new(Expression::Variable("a".to_string())),
+                op: BinaryOperator::Add,
+                right: Box::new(Expression::Variable("b".to_string())),
+            })),
+            assign("ready", arrow(vec![], string("ready"))),
+            assign("n", int(5)),
+            call("add", vec![int(1)]),
+            call("add", vec![int(1), string("x")]),
+            call("n", vec![]),
+        ];
+        let mut checker = LiteralChecker::new("app.php");
+        checker.check(&ast);
+        let messages: Vec<_> = checker.take_diagnostics().into_iter().map(|d| d.message).collect();
+        assert_eq!(messages, [
+            "Too few arguments to function {closure}(), 1 passed and exactly 2 expected",
+            "{closure}(): Argument #2 ($b) must be of type int, string given",
+            "Value of type int is not callable",
+        ]);
+        assert_eq!(
+            checker.infer(&Expression::Variable("ready".to_string())).to_string(),
+            "callable(): 'ready'"
+        );
+    }
+
+    #[test]
+    fn test_intersection_arguments() {
+        let class = |name: &str, implements: &[&str]| AstNode::Class(ClassDecl {
+            name: name.to_string(),
+            extends: None,
+            implements: implements.iter().map(|i| i.to_string()).collect(),
+            traits: vec![],
+            properties: vec![],
+            methods: vec![],
+            constants: vec![],
+            attributes: vec![],
+            is_abstract: false,
+            is_final: false,
+            is_trait: false,
+            is_interface: false,
+            is_enum: false,
+        });
+        let interface = |name: &str| AstNode::Interface(InterfaceDecl {
+            name: name.to_string(),
+            extends: vec![],
+            constants: vec![],
+            methods: vec![],
+        });
+        // function store(Countable&ArrayAccess $items) {}
+        let store = AstNode::Function(FunctionDecl {
+            name: "store".to_string(),
+            parameters: vec![Parameter {
+                name: "items".to_string(),
+                typ: Some(Type::Intersection(vec![
+                    Type::Object("Countable".to_string()),
+                    Type::Object("ArrayAccess".to_string()),
+                ])),
+                default_value: None,
+                is_reference: false,
+                is_variadic: false,
+            }],
+            return_type: None,
+            body: Box::new(Statement::Block(vec![])),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        });
+        let call = |class: &str| AstNode::Expression(Box::new(Expression::FunctionCall {
+            name: Box::new(Expression::Constant("store".to_string())),
+            arguments: vec![Expression::New {
+                class: Box::new(Expression::Constant(class.to_string())),
+                arguments: vec![],
+            }],
+        }));
+        let declarations = vec![
+            interface("Countable"),
+            interface("ArrayAccess"),
+            class("Bag", &["Countable", "ArrayAccess"]),
+            class("Counter", &["Countable"]),
+            class("Proxy", &["IteratorAggregate"]),
+            store,
+        ];
+
+        let diagnostics = check(&[declarations.clone(), vec![call("Bag"), call("Proxy")]].concat());
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+        let diagnostics = check(&[declarations, vec![call("\\Counter")]].concat());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, codes::ARGUMENT_TYPE);
+        assert_eq!(
+            diagnostics[0].message,
+            "store(): Argument #1 ($items) must be of type Countable&ArrayAccess, Counter given"
+        );
+        assert_eq!(diagnostics[0].notes, vec!["Counter does not implement ArrayAccess".to_string()]);
+    }
+
+    #[test]
+    fn test_nullsafe_chains() {
+        let class = |name: &str, properties: Vec<PropertyDecl>, methods: Vec<FunctionDecl>| AstNode::Class(ClassDecl {
+            name: name.to_string(),
+            extends: None,
+            implements: vec![],
+            traits: vec![],
+            properties,
+            methods,
+            constants: vec![],
+            attributes: vec![],
+            is_abstract: false,
+            is_final: false,
+            is_trait: false,
+            is_interface: false,
+            is_enum: false,
+        });
+        // class A { public ?B $b; }  class B { function c(): int {} }
+        let a = class("A", vec![PropertyDecl {
+            name: "b".to_string(),
+            typ: Some(Type::union([Type::Object("B".to_string()), Type::Null])),
+            default_value: None,
+            visibility: Visibility::Public,
+            is_static: false,
+            is_readonly: false,
+            doc_comment: None,
+        }], vec![]);
+        let b = class("B", vec![], vec![FunctionDecl {
+            name: "c".to_string(),
+            parameters: vec![],
+            return_type: Some(Type::Int),
+            body: Box::new(Statement::Block(vec![])),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        }]);
+        let variable = |name: &str| Box::new(Expression::Variable(name.to_string()));
+        // $a?->b?->c()
+        let chain = Expression::MethodCall {
+            object: Box::new(Expression::PropertyAccess { object: variable("a"), property: "b".to_string(), nullsafe: true }),
+            method: "c".to_string(),
+            arguments: vec![],
+            nullsafe: true,
+        };
+        let null_test = |name: &str| Expression::BinaryOp {
+            left: variable(name),
+            op: BinaryOperator::Identical,
+            right: Box::new(Expression::Literal(Literal::Null)),
+        };
+        let ast = vec![
+            a,
+            b,
+            assign("a", Expression::New { class: Box::new(Expression::Constant("A".to_string())), arguments: vec![] }),
+            assign("x", chain.clone()),
+            assign("y", Expression::NullCoalescing { left: Box::new(chain), right: Box::new(Expression::Literal(Literal::Int(0))) }),
+            assign("y_is_null", null_test("y")),
+            AstNode::Statement(Box::new(Statement::If {
+                condition: Box::new(null_test("x")),
+                then_branch: Box::new(Statement::Return(None)),
+                else_branch: None,
+            })),
+        ];
+        let mut checker = LiteralChecker::new("app.php");
+        checker.check(&ast);
+        assert!(checker.take_diagnostics().is_empty());
+
+        let nullable_int = Type::union([Type::Int, Type::Null]);
+        assert_eq!(
+            checker.infer(&Expression::Variable("y".to_string())),
+            Type::Union(vec![Type::Int, Type::Literal(LiteralType::Int(0))])
+        );
+        // The null test on the coalesced value is redundant
+        assert_eq!(checker.scope.get("y_is_null"), Some(&Type::Literal(LiteralType::Bool(false))));
+        // After the early return $x is known to be non-null
+        assert_eq!(checker.scope.get("x"), Some(&Type::Int));
+        assert_eq!(checker.infer(&Expression::Variable("a".to_string())), Type::Object("A".to_string()));
+        assert_eq!(coalesce(nullable_int.clone(), Type::Null), nullable_int);
+    }
+
+    #[test]
+    fn test_property_writes() {
+        let property = |name: &str, typ: Option<Type>, default_value: Option<Expression>, is_readonly| PropertyDecl {
+            name: name.to_string(),
+            typ,
+            default_value,
+            visibility: Visibility::Public,
+            is_static: false,
+            is_readonly,
+            doc_comment: None,
+        };
+        let write = |object: &str, property: &str, value: Expression| Statement::Expression(Box::new(Expression::Assignment {
+            target: Box::new(Expression::PropertyAccess {
+                object: Box::new(Expression::Variable(object.to_string())),
+                property: property.to_string(),
+                nullsafe: false,
+            }),
+            op: AssignmentOperator::Assign,
+            value: Box::new(value),
+        }));
+        // class Point { public readonly int $x; public string $label = 5; public readonly $y;
+        //     function __construct() { $this->x = 1; } }
+        let point = AstNode::Class(ClassDecl {
+            name: "Point".to_string(),
+            extends: None,
+            implements: vec![],
+            traits: vec![],
+            properties: vec![
+                property("x", Some(Type::Int), None, true),
+                property("label", Some(Type::String), Some(Expression::Literal(Literal::Int(5))), false),
+                property("y", None, None, true),
+            ],
+            methods: vec![FunctionDecl {
+                name: "__construct".to_string(),
+                parameters: vec![],
+                return_type: None,
+                body: Box::new(write("this", "x", Expression::Literal(Literal::Int(1)))),
+                attributes: vec![],
+                is_static: false,
+                visibility: Visibility::Public,
+                doc_comment: None,
+            }],
+            constants: vec![],
+            attributes: vec![],
+            is_abstract: false,
+            is_final: false,
+            is_trait: false,
+            is_interface: false,
+            is_enum: false,
+        });
+        let ast = vec![
+            point,
+            assign("p", Expression::New { class: Box::new(Expression::Constant("Point".to_string())), arguments: vec![] }),
+            AstNode::Statement(Box::new(write("p", "x", Expression::Literal(Literal::Int(2))))),
+            AstNode::Statement(Box::new(write("p", "label", Expression::Literal(Literal::Null)))),
+            AstNode::Statement(Box::new(write("p", "label", Expression::Literal(Literal::Int(3))))),
+        ];
+
+        let messages: Vec<_> = check(&ast).into_iter()
+            .inspect(|diagnostic| assert_eq!(diagnostic.code, codes::PROPERTY_WRITE))
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+        assert_eq!(messages, vec![
+            "Cannot use int as default value for property Point::$label of type string",
+            "Readonly property Point::$y must have type",
+            "Cannot modify readonly property Point::$x",
+            "Cannot assign null to property Point::$label of type string",
+        ]);
+    }
+
+    #[test]
+    fn test_array_keys_and_formats() {
+        // ['a' => 1, 1 => 2, '1' => 3, 'b']
+        let element = |key: Option<Expression>| ArrayElement {
+            key,
+            value: Expression::Literal(Literal::Int(0)),
+            is_reference: false,
+            is_spread: false,
+        };
+        let array = AstNode::Expression(Box::new(Expression::Array {
+            elements: vec![
+                element(Some(string("a"))),
+                element(Some(Expression::Literal(Literal::Int(1)))),
+                element(Some(string("1"))),
+                element(None),
+            ],
+        }));
+        let diagnostics = check(&[array]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Duplicate array key 1");
+
+        // printf("%s has %d items\n", $name, 'many')
+        let printf = |arguments: Vec<Expression>| AstNode::Expression(Box::new(Expression::FunctionCall {
+            name: Box::new(Expression::Constant("printf".to_string())),
+            arguments,
+        }));
+        let format = string("%s has %d items\n");
+        let diagnostics = check(&[printf(vec![format.clone(), Expression::Variable("name".to_string())])]);
+        assert_eq!(diagnostics[0].message, "printf(): 3 arguments are required, 2 given");
+
+        let diagnostics = check(&[printf(vec![format, Expression::Variable("name".to_string()), string("many")])]);
+        assert_eq!(diagnostics[0].severity, crate::diagnostics::Severity::Warning);
+        assert_eq!(diagnostics[0].message, "printf(): Argument #3 for %d is the non-numeric string 'many'");
+
+        assert_eq!(parse_format("%2$s %1$05.2f %%"), Ok(vec![(2, 's'), (1, 'f')]));
+        assert!(parse_format("%y").is_err());
+    }
+}
@@ -0,0 +1,242 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! In-process LLVM through `inkwell`.
+//!
+//! The IR produced by [`IrGenerator`](crate::ir::IrGenerator) is loaded into
+//! a real LLVM module, checked by the verifier, optimized with the new pass
+//! manager and written to an object file, all through the LLVM C API
+//! instead of an `llc` process. Invalid IR is rejected here with LLVM's own
+//! message rather than by a failing external tool, reported as an IR
+//! generation error against the function at fault.
+
+use std::path::Path;
+use std::sync::OnceLock;
+use inkwell::context::Context;
+use inkwell::memory_buffer::MemoryBuffer;
+use inkwell::module::Module;
+use inkwell::passes::PassBuilderOptions;
+use inkwell::targets::{CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine};
+use inkwell::OptimizationLevel;
+use log::info;
+use crate::backend::{self, pass_pipeline, CodeFile};
+use crate::error::{CompileError, CompileResult};
+use crate::profile::Profile;
+use crate::verifier::function_at_line;
+
+/// Parse textual IR into a module of `context` and verify it
+pub fn load_module<'ctx>(context: &'ctx Context, ir: &str) -> CompileResult<Module<'ctx>> {
+    let buffer = MemoryBuffer::create_from_memory_range_copy(ir.as_bytes(), "php2ir");
+    let module = context.create_module_from_ir(buffer).map_err(|e| {
+        let message = e.to_string();
+        let function = error_line(&message).and_then(|line| function_at_line(ir, line));
+        invalid_ir(function, &message)
+    })?;
+    module.verify().map_err(|e| {
+        let function = module.get_functions().find(|f| !f.verify(false));
+        let name = function.as_ref().map(|f| f.get_name().to_string_lossy());
+        invalid_ir(name.as_deref(), &e.to_string())
+    })?;
+    Ok(module)
+}
+
+/// Line of the IR an LLVM parser message (`php2ir:LINE:COLUMN: ...`) points at
+fn error_line(message: &str) -> Option<usize> {
+    message.strip_prefix("php2ir:")?.split(':').next()?.parse().ok()
+}
+
+fn invalid_ir(function: Option<&str>, message: &str) -> CompileError {
+    match function {
+        Some(name) => CompileError::IrGeneration(format!("invalid IR in function {}: {}", name, message.trim())),
+        None => CompileError::IrGeneration(format!("invalid IR: {}", message.trim())),
+    }
+}
+
+/// Check that textual IR parses and passes the LLVM verifier
+pub fn verify_ir(ir: &str) -> CompileResult<()> {
+    let context = Context::create();
+    load_module(&context, ir).map(|_| ())
+}
+
+/// Verify and optimize IR, instrumenting it for or optimizing it with a
+/// profile, then write it to an object or assembly file
+///
+/// With `sections`, every function and global is put in a section of its
+/// own, as `-ffunction-sections -fdata-sections` do.
+pub fn compile_ir(
+    ir: &str,
+    out_file: &Path,
+    optimization_level: &str,
+    passes: Option<&str>,
+    profile: &Profile,
+    kind: CodeFile,
+    sections: bool,
+) -> CompileResult<()> {
+    info!("Generating {} in-process", kind);
+
+    let context = Context::create();
+    let module = load_module(&context, &profile.prepare_ir(ir))?;
+    let machine = target_machine(&module, optimization_level)?;
+    module.set_data_layout(&machine.get_target_data().get_data_layout());
+
+    set_llvm_options(&profile.llvm_options())?;
+    let pipeline = format!("{}{}", profile.passes(), pass_pipeline(optimization_level, passes));
+    module.run_passes(&pipeline, &machine, PassBuilderOptions::create())
+        .map_err(|e| CompileError::LlvmCompilation(format!("optimization failed: {}", e)))?;
+    if sections {
+        assign_sections(&module);
+    }
+    let file_type = match kind {
+        CodeFile::Object => FileType::Object,
+        CodeFile::Assembly => FileType::Assembly,
+    };
+    machine.write_to_file(&module, file_type, out_file)
+        .map_err(|e| CompileError::LlvmCompilation(e.to_string()))?;
+
+    info!("{} generated: {}", kind, out_file.display());
+    Ok(())
+}
+
+/// Give every defined function and global without an explicit section one
+/// of its own
+///
+/// The LLVM C API has no switch for the function and data sections of the
+/// target machine, so they are named the way that option would name them.
+/// Only ELF linkers collect sections by name; Mach-O and COFF objects are
+/// left alone.
+fn assign_sections(module: &Module) {
+    let triple = module.get_triple();
+    let triple = triple.as_str().to_string_lossy();
+    if triple.contains("apple") || triple.contains("darwin") || triple.contains("windows") {
+        return;
+    }
+    for function in module.get_functions().filter(|f| f.count_basic_blocks() > 0 && f.get_section().is_none()) {
+        let name = format!(".text.{}", function.get_name().to_string_lossy());
+        function.set_section(Some(&name));
+    }
+    for global in module.get_globals().filter(|g| g.get_initializer().is_some() && g.get_section().is_none()) {
+        let prefix = if global.is_constant() { ".rodata" } else { ".data" };
+        let name = format!("{}.{}", prefix, global.get_name().to_string_lossy());
+        global.set_section(Some(&name));
+    }
+}
+
+/// Verify IR and run the pass pipeline over it, returning the optimized IR
+pub fn optimize_ir(ir: &str, optimization_level: &str, passes: Option<&str>) -> CompileResult<String> {
+    let context = Context::create();
+    let module = load_module(&context, ir)?;
+    let machine = target_machine(&module, optimization_level)?;
+    module.set_data_layout(&machine.get_target_data().get_data_layout());
+
+    let pipeline = pass_pipeline(optimization_level, passes);
+    module.run_passes(&pipeline, &machine, PassBuilderOptions::create())
+        .map_err(|e| CompileError::LlvmCompilation(format!("optimization failed: {}", e)))?;
+    Ok(module.print_to_string().to_string())
+}
+
+/// Set LLVM command-line options for the passes
+///
+/// LLVM rejects an option given twice, so they can be set once per process.
+fn set_llvm_options(options: &[String]) -> CompileResult<()> {
+    static SET: OnceLock<Vec<String>> = OnceLock::new();
+    if options.is_empty() {
+        return Ok(());
+    }
+    let set = SET.get_or_init(|| {
+        let args: Vec<&str> = std::iter::once("php2ir").chain(options.iter().map(String::as_str)).collect();
+        inkwell::support::parse_command_line_options(args.len() as i32, &args, "");
+        options.to_vec()
+    });
+    if set != options {
+        return Err(CompileError::Configuration(format!(
+            "LLVM options were already set to `{}` in this process",
+            set.join(" ")
+        )));
+    }
+    Ok(())
+}
+
+/// Verify IR and write it to a bitcode file, unoptimized
+pub fn write_bitcode(ir: &str, bc_file: &Path) -> CompileResult<()> {
+    info!("Generating bitcode file in-process");
+
+    let context = Context::create();
+    let module = load_module(&context, ir)?;
+    if !module.write_bitcode_to_path(bc_file) {
+        return Err(CompileError::LlvmCompilation(format!("failed to write bitcode to {}", bc_file.display())));
+    }
+
+    info!("Bitcode file generated: {}", bc_file.display());
+    Ok(())
+}
+
+/// Target machine for the module's triple, tuned for the host CPU when the
+/// module targets the host
+fn target_machine(module: &Module, optimization_level: &str) -> CompileResult<TargetMachine> {
+    Target::initialize_all(&InitializationConfig::default());
+
+    let triple = module.get_triple();
+    let triple = if triple.as_str().to_bytes().is_empty() { TargetMachine::get_default_triple() } else { triple };
+    let target = Target::from_triple(&triple)
+        .map_err(|e| CompileError::Configuration(format!("unsupported target {}: {}", triple, e)))?;
+
+    let (cpu, features) = if triple.as_str() == TargetMachine::get_default_triple().as_str() {
+        (TargetMachine::get_host_cpu_name().to_string(), TargetMachine::get_host_cpu_features().to_string())
+    } else {
+        ("generic".to_string(), String::new())
+    };
+    target
+        .create_target_machine(
+            &triple,
+            &cpu,
+            &features,
+            codegen_level(optimization_level),
+            RelocMode::PIC,
+            CodeModel::Default,
+        )
+        .ok_or_else(|| CompileError::Configuration(format!("cannot create a target machine for {}", triple)))
+}
+
+/// Code generator optimization level for an `-O` option
+fn codegen_level(optimization_level: &str) -> OptimizationLevel {
+    match backend::codegen_level(optimization_level) {
+        '0' => OptimizationLevel::None,
+        '1' => OptimizationLevel::Less,
+        '3' => OptimizationLevel::Aggressive,
+        _ => OptimizationLevel::Default,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::IrGenerator;
+
+    #[test]
+    fn test_verify_generated_ir() {
+        let ir = IrGenerator::new().unwrap().generate(&[]).unwrap();
+        assert!(verify_ir(&ir).is_ok());
+    }
+
+    #[test]
+    fn test_reject_invalid_ir() {
+        let ir = "define i32 @f() {\n  ret i64 0\n}\n";
+        match verify_ir(ir) {
+            Err(CompileError::IrGeneration(message)) => assert!(message.starts_with("invalid IR in function f:")),
+            other => panic!("expected an IR error, got {:?}", other),
+        }
+    }
+}
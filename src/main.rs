@@ -15,21 +15,35 @@
  */
 
 use clap::{Parser, Subcommand};
-use log::{error, info, LevelFilter};
+use log::{info, LevelFilter};
+use std::io::IsTerminal;
 use std::path::PathBuf;
 use std::process;
 
+use php2ir::ast::Literal;
+use php2ir::backend::BackendKind;
+use php2ir::build;
 use php2ir::compiler::{Compiler, CompilerOptions};
+use php2ir::defines;
+use php2ir::diagnostics::Diagnostic;
 use php2ir::error::CompileError;
+use php2ir::explain;
+use php2ir::fallback::FallbackReport;
+use php2ir::link::{self, LtoMode};
+use php2ir::phpt::{self, TestRunner};
+use php2ir::trace::Instrumentation;
+use php2ir::types::IntWidth;
+use php2ir::watch;
 
 #[derive(Parser)]
 #[command(name = "php2ir")]
 #[command(about = "PHP 8.x → LLVM-IR → native ELF/EXE/Mach-O compiler")]
 #[command(version)]
+#[command(subcommand_negates_reqs = true)]
 struct Cli {
-    /// Input PHP file
+    /// Input PHP file (default: the `entry` of php2ir.toml)
     #[arg(value_name = "INPUT")]
-    input: PathBuf,
+    input: Option<PathBuf>,
 
     /// Output file
     #[arg(short, long, value_name = "OUTPUT")]
@@ -43,19 +57,37 @@ struct Cli {
     #[arg(long)]
     emit_llvm_only: bool,
 
+    /// Emit only the given format: ll (IR text), bc (bitcode), asm (target assembly),
+    /// header (C prototypes of the #[Export] functions) or lib (static library with
+    /// its header and pkg-config file)
+    #[arg(long, value_name = "FORMAT", value_parser = ["ll", "bc", "asm", "header", "lib"])]
+    emit: Option<String>,
+
+    /// Emit target assembly only (same as --emit asm)
+    #[arg(short = 'S')]
+    assembly: bool,
+
     /// Optimization level
-    #[arg(long, value_name = "LEVEL", default_value = "O2")]
+    #[arg(long, value_name = "LEVEL", default_value = "O2", value_parser = ["O0", "O1", "O2", "O3", "Os", "Oz"])]
     opt: String,
 
-    /// LTO mode
+    /// LLVM pass pipeline replacing the one of --opt, as for `opt -passes=`
+    #[arg(long, value_name = "PIPELINE")]
+    passes: Option<String>,
+
+    /// Link-time optimization (thin, full); needs ld.lld or ld.gold with LLVMgold.so
     #[arg(long, value_name = "MODE")]
-    lto: Option<String>,
+    lto: Option<LtoMode>,
 
-    /// PGO generation mode
+    /// Smallest binary: -Oz, unused sections dropped and symbols stripped
+    #[arg(long)]
+    release_size: bool,
+
+    /// Instrument the program to write a profile (default.profraw) when it runs
     #[arg(long)]
     pgo_gen: bool,
 
-    /// PGO use mode with profile data
+    /// Optimize with a profile merged by llvm-profdata
     #[arg(long, value_name = "PROFDATA")]
     pgo_use: Option<PathBuf>,
 
@@ -63,7 +95,15 @@ struct Cli {
     #[arg(long, value_name = "TRIPLE")]
     target: Option<String>,
 
-    /// Standard library path
+    /// Target root holding its C libraries and start files
+    #[arg(long, value_name = "DIR")]
+    sysroot: Option<PathBuf>,
+
+    /// Compiler driver or linker (cc, clang, ld.lld, mold, ld64.lld, lld-link, link.exe, ...; default: detected)
+    #[arg(long, value_name = "PATH")]
+    linker: Option<PathBuf>,
+
+    /// Runtime library (libphp2ir.a) or a directory containing it
     #[arg(long, value_name = "PATH")]
     stdlib: Option<PathBuf>,
 
@@ -75,6 +115,78 @@ struct Cli {
     #[arg(long, value_name = "SANITIZER")]
     sanitize: Option<String>,
 
+    /// Integer width in bits (32 or 64, default: from target)
+    #[arg(long, value_name = "BITS", value_parser = parse_int_width)]
+    int_width: Option<IntWidth>,
+
+    /// Instrument generated code (trace: emit Chrome trace-event JSON at exit)
+    #[arg(long, value_name = "MODE")]
+    instrument: Option<Instrumentation>,
+
+    /// Additional PHP file compiled as a separate object and linked in (repeatable)
+    #[arg(long = "module", value_name = "FILE")]
+    modules: Vec<PathBuf>,
+
+    /// Signature-only .phpstub file declaring external functions and classes (repeatable)
+    #[arg(long, value_name = "FILE")]
+    stubs: Vec<PathBuf>,
+
+    /// Code generation backend (llvm, cranelift)
+    #[arg(long, value_name = "BACKEND", default_value = "llvm")]
+    backend: BackendKind,
+
+    /// Run the program with the interpreter instead of compiling it
+    #[arg(long)]
+    interpret: bool,
+
+    /// Do not run functions that code generation cannot handle in the interpreter
+    #[arg(long)]
+    no_interpreter_fallback: bool,
+
+    /// Report code falling back to boxed values or dynamic dispatch (warn, deny)
+    #[arg(long, value_name = "MODE")]
+    report_dynamic: Option<FallbackReport>,
+
+    /// Compile a file into the program for embedded_file(NAME) (repeatable)
+    #[arg(long, value_name = "NAME=PATH", value_parser = parse_embed)]
+    embed: Vec<(String, PathBuf)>,
+
+    /// Define a PHP constant at compile time, e.g. -D DEBUG=false (repeatable)
+    #[arg(short = 'D', long = "define", value_name = "NAME=VALUE", value_parser = parse_define)]
+    defines: Vec<(String, Literal)>,
+
+    /// Directory searched for relative include/require targets (repeatable)
+    #[arg(short = 'I', long = "include-path", value_name = "DIR")]
+    include_paths: Vec<PathBuf>,
+
+    /// Project manifest (default: php2ir.toml next to the input or in a parent directory)
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<PathBuf>,
+
+    /// Rebuild whenever a source file changes
+    #[arg(long)]
+    watch: bool,
+
+    /// Directory for intermediate files, kept between builds (default: a temporary directory)
+    #[arg(long, value_name = "DIR")]
+    build_dir: Option<PathBuf>,
+
+    /// Keep intermediate files (objects, IR) instead of removing them after the build
+    #[arg(long)]
+    keep_temps: bool,
+
+    /// Write the IR handed to code generation next to the output
+    #[arg(long)]
+    save_ir: bool,
+
+    /// Report the time and memory of each compilation phase, with AST and IR sizes
+    #[arg(long)]
+    timings: bool,
+
+    /// Explain a diagnostic code, such as T0304, and exit
+    #[arg(long, value_name = "CODE")]
+    explain: Option<String>,
+
     /// Verbose output
     #[arg(short, long)]
     verbose: bool,
@@ -97,20 +209,76 @@ enum Commands {
         #[arg(value_name = "INPUT")]
         input: PathBuf,
     },
-    /// Run tests
+    /// Parse and type-check without generating code; exits non-zero on errors
+    Check {
+        /// Input PHP file
+        #[arg(value_name = "INPUT")]
+        input: PathBuf,
+
+        /// Additional PHP file checked alongside the input (repeatable)
+        #[arg(long = "module", value_name = "FILE")]
+        modules: Vec<PathBuf>,
+
+        /// Signature-only .phpstub file declaring external functions and classes (repeatable)
+        #[arg(long, value_name = "FILE")]
+        stubs: Vec<PathBuf>,
+
+        /// Integer width in bits (32 or 64, default: from host)
+        #[arg(long, value_name = "BITS", value_parser = parse_int_width)]
+        int_width: Option<IntWidth>,
+
+        /// Report code falling back to boxed values or dynamic dispatch (warn, deny)
+        #[arg(long, value_name = "MODE")]
+        report_dynamic: Option<FallbackReport>,
+    },
+    /// Compile to a temporary binary and run it with the given arguments
+    Run {
+        /// Input PHP file
+        #[arg(value_name = "INPUT")]
+        input: PathBuf,
+
+        /// Optimization level
+        #[arg(long, value_name = "LEVEL", default_value = "O2", value_parser = ["O0", "O1", "O2", "O3", "Os", "Oz"])]
+        opt: String,
+
+        /// Additional PHP file compiled as a separate object and linked in (repeatable)
+        #[arg(long = "module", value_name = "FILE")]
+        modules: Vec<PathBuf>,
+
+        /// Rebuild and rerun whenever a source file changes
+        #[arg(long)]
+        watch: bool,
+
+        /// Arguments passed to the program
+        #[arg(value_name = "ARGS", trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Build the project of a php2ir.toml, compiling its sources in dependency order
+    Build {
+        /// Project manifest (default: php2ir.toml in this directory or a parent)
+        #[arg(long, value_name = "FILE")]
+        manifest: Option<PathBuf>,
+    },
+    /// Compile and run the .phpt tests of a directory
     Test {
-        /// Test directory
+        /// Test directory, searched recursively
         #[arg(value_name = "DIR")]
         dir: Option<PathBuf>,
+
+        /// Number of tests run at once (default: one per CPU)
+        #[arg(short, long, value_name = "N")]
+        jobs: Option<usize>,
     },
 }
 
 fn main() {
     let cli = Cli::parse();
 
-    // Setup logging
+    // Setup logging; `run` keeps quiet so only the program writes to the terminal
     let log_level = if cli.verbose {
         LevelFilter::Debug
+    } else if matches!(cli.command, Some(Commands::Run { .. })) {
+        LevelFilter::Warn
     } else {
         LevelFilter::Info
     };
@@ -123,79 +291,305 @@ fn main() {
     match cli.command {
         Some(Commands::Parse { input }) => {
             if let Err(e) = parse_php_file(&input) {
-                error!("Parse error: {}", e);
+                report_error(&e);
                 process::exit(1);
             }
         }
         Some(Commands::Ir { input }) => {
             if let Err(e) = show_ir(&input) {
-                error!("IR generation error: {}", e);
+                report_error(&e);
+                process::exit(1);
+            }
+        }
+        Some(Commands::Check { input, modules, stubs, int_width, report_dynamic }) => {
+            match check_php(input, modules, stubs, int_width, report_dynamic) {
+                Ok(true) => {}
+                Ok(false) => process::exit(1),
+                Err(e) => {
+                    report_error(&e);
+                    process::exit(1);
+                }
+            }
+        }
+        Some(Commands::Run { input, opt, modules, watch: rerun, args }) => match run_php(input, opt, modules, rerun, &args) {
+            Ok(status) => process::exit(status),
+            Err(e) => {
+                report_error(&e);
+                process::exit(1);
+            }
+        },
+        Some(Commands::Build { manifest }) => {
+            if let Err(e) = build_project(manifest) {
+                report_error(&e);
                 process::exit(1);
             }
         }
-        Some(Commands::Test { dir }) => {
-            if let Err(e) = run_tests(dir) {
-                error!("Test error: {}", e);
+        Some(Commands::Test { dir, jobs }) => match run_tests(dir, jobs) {
+            Ok(true) => {}
+            Ok(false) => process::exit(1),
+            Err(e) => {
+                report_error(&e);
+                process::exit(1);
+            }
+        },
+        None if cli.explain.is_some() => {
+            let code = cli.explain.as_deref().unwrap_or_default();
+            match explain::explain(code) {
+                Some(explanation) => print!("{}", explanation),
+                None => {
+                    eprintln!("error: no diagnostic code {} (codes look like P0001 or T0304)", code);
+                    process::exit(1);
+                }
+            }
+        }
+        None if cli.watch => {
+            if let Err(e) = watch_php(&cli) {
+                report_error(&e);
                 process::exit(1);
             }
         }
+        None if cli.interpret => match interpret_php(&cli) {
+            Ok(status) => process::exit(status),
+            Err(e) => {
+                report_error(&e);
+                process::exit(255);
+            }
+        },
         None => {
             // Main compilation path
             if let Err(e) = compile_php(&cli) {
-                error!("Compilation error: {}", e);
+                report_error(&e);
                 process::exit(1);
             }
         }
     }
 }
 
-fn compile_php(cli: &Cli) -> Result<(), CompileError> {
-    let output = cli.output.clone().unwrap_or_else(|| {
-        let mut path = cli.input.clone();
-        path.set_extension("");
-        path
-    });
+fn parse_int_width(s: &str) -> Result<IntWidth, String> {
+    s.parse::<u32>()
+        .ok()
+        .and_then(IntWidth::from_bits)
+        .ok_or_else(|| format!("invalid integer width '{}' (expected 32 or 64)", s))
+}
 
-    let options = CompilerOptions {
-        input: cli.input.clone(),
-        output,
+fn parse_embed(s: &str) -> Result<(String, PathBuf), String> {
+    match s.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => Ok((name.to_string(), PathBuf::from(path))),
+        _ => Err(format!("invalid embedded file '{}' (expected NAME=PATH)", s)),
+    }
+}
+
+fn parse_define(s: &str) -> Result<(String, Literal), String> {
+    let (name, value) = s.split_once('=').unwrap_or((s, "true"));
+    if !defines::is_constant_name(name) {
+        return Err(format!("invalid define '{}' (expected NAME=VALUE)", s));
+    }
+    Ok((name.to_string(), defines::define_literal(value)))
+}
+
+/// Options given on the command line; the rest come from php2ir.toml
+fn compiler_options(cli: &Cli) -> CompilerOptions {
+    CompilerOptions {
+        // Left empty for the manifest's `entry`, and the output derived from the input
+        input: cli.input.clone().unwrap_or_default(),
+        output: cli.output.clone().unwrap_or_default(),
         emit_llvm: cli.emit_llvm,
-        emit_llvm_only: cli.emit_llvm_only,
+        emit_llvm_only: cli.emit_llvm_only || cli.emit.as_deref() == Some("ll"),
+        emit_bitcode: cli.emit.as_deref() == Some("bc"),
+        emit_asm: cli.assembly || cli.emit.as_deref() == Some("asm"),
+        emit_header: cli.emit.as_deref() == Some("header"),
+        emit_library: cli.emit.as_deref() == Some("lib"),
+        version: None,
         optimization_level: cli.opt.clone(),
-        lto: cli.lto.clone(),
+        passes: cli.passes.clone(),
+        lto: cli.lto,
+        release_size: cli.release_size,
         pgo_gen: cli.pgo_gen,
         pgo_use: cli.pgo_use.clone(),
         target: cli.target.clone(),
+        sysroot: cli.sysroot.clone(),
+        linker: cli.linker.clone(),
         stdlib: cli.stdlib.clone(),
         no_runtime: cli.no_rt,
         sanitizer: cli.sanitize.clone(),
-    };
+        int_width: cli.int_width,
+        instrument: cli.instrument,
+        modules: cli.modules.clone(),
+        backend: cli.backend,
+        interpreter_fallback: !cli.no_interpreter_fallback,
+        report_dynamic: cli.report_dynamic,
+        stubs: cli.stubs.clone(),
+        include_paths: cli.include_paths.clone(),
+        defines: cli.defines.clone(),
+        manifest: cli.manifest.clone(),
+        sources: Vec::new(),
+        build_dir: cli.build_dir.clone(),
+        embed: cli.embed.clone(),
+        keep_temps: cli.keep_temps,
+        save_ir: cli.save_ir,
+    }
+}
 
-    info!("Compiling {} to {}", cli.input.display(), output.display());
+fn compile_php(cli: &Cli) -> Result<(), CompileError> {
+    let mut compiler = Compiler::new(compiler_options(cli))?;
+    let options = compiler.options();
+    info!("Compiling {} to {}", options.input.display(), options.output.display());
     
-    let mut compiler = Compiler::new(options)?;
-    compiler.compile()?;
+    let result = compiler.compile();
+    report_diagnostics(&compiler);
+    if cli.timings {
+        eprint!("{}", compiler.timings());
+    }
+    result?;
 
     info!("Compilation successful!");
     Ok(())
 }
 
+fn watch_php(cli: &Cli) -> Result<(), CompileError> {
+    let mut compiler = Compiler::new(compiler_options(cli))?;
+    watch::watch(&mut compiler, false, &[], report_diagnostics)
+}
+
+fn interpret_php(cli: &Cli) -> Result<i32, CompileError> {
+    let mut compiler = Compiler::new(compiler_options(cli))?;
+    info!("Interpreting {}", compiler.options().input.display());
+    
+    let result = compiler.interpret();
+    report_diagnostics(&compiler);
+    result
+}
+
+fn build_project(manifest: Option<PathBuf>) -> Result<(), CompileError> {
+    let mut compiler = Compiler::new(build::project_options(manifest.as_deref())?)?;
+    let plan = compiler.plan_build()?;
+    let result = compiler.build(&plan);
+    report_diagnostics(&compiler);
+    result?;
+    
+    info!("Build successful: {}", compiler.options().output.display());
+    Ok(())
+}
+
+/// Compile to a temporary directory and run the binary; returns its exit status
+///
+/// With `rerun`, rebuilds and restarts the program on every change instead.
+fn run_php(input: PathBuf, opt: String, modules: Vec<PathBuf>, rerun: bool, args: &[String]) -> Result<i32, CompileError> {
+    let dir = tempfile::tempdir()?;
+    let name = input.file_stem().map(PathBuf::from).unwrap_or_else(|| PathBuf::from("a.out"));
+    let options = CompilerOptions {
+        input,
+        output: dir.path().join(name),
+        optimization_level: opt,
+        modules,
+        ..CompilerOptions::default()
+    };
+    
+    let mut compiler = Compiler::new(options)?;
+    if rerun {
+        watch::watch(&mut compiler, true, args, report_diagnostics)?;
+        return Ok(0);
+    }
+    let result = compiler.compile();
+    report_diagnostics(&compiler);
+    result?;
+    
+    let options = compiler.options();
+    let binary = link::executable_path(&options.output, &options.resolved_target());
+    info!("Running {}", binary.display());
+    let status = process::Command::new(&binary).args(args).status()?;
+    Ok(exit_code(status))
+}
+
+/// Exit status to report for a finished program, `128 + N` for signal N as shells do
+fn exit_code(status: process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/// Type-check without touching LLVM or the linker; `Ok(false)` when errors were reported
+fn check_php(
+    input: PathBuf,
+    modules: Vec<PathBuf>,
+    stubs: Vec<PathBuf>,
+    int_width: Option<IntWidth>,
+    report_dynamic: Option<FallbackReport>,
+) -> Result<bool, CompileError> {
+    info!("Checking {}", input.display());
+    
+    let options = CompilerOptions {
+        input,
+        optimization_level: "O0".to_string(),
+        int_width,
+        modules,
+        interpreter_fallback: false,
+        report_dynamic,
+        stubs,
+        ..CompilerOptions::default()
+    };
+    let mut compiler = Compiler::new(options)?;
+    let result = compiler.check();
+    report_diagnostics(&compiler);
+    result?;
+    
+    let report = compiler.diagnostics();
+    if report.is_empty() {
+        println!("No problems found");
+    }
+    Ok(!report.has_errors())
+}
+
+fn report_diagnostics(compiler: &Compiler) {
+    let report = compiler.diagnostics();
+    if report.is_empty() {
+        return;
+    }
+    let source = |file: &str| std::fs::read_to_string(file).ok();
+    if use_color() {
+        eprint!("{}", report.render_colored(source));
+    } else {
+        eprint!("{}", report.render(source));
+    }
+    if let Some(diagnostic) = report.iter().next() {
+        explain_hint(diagnostic.code);
+    }
+}
+
+/// Report the error that stopped a command, showing the source line it points to
+fn report_error(error: &CompileError) {
+    let source = error.file().and_then(|file| std::fs::read_to_string(file).ok());
+    let diagnostic = Diagnostic::from_error(error, source.as_deref());
+    if use_color() {
+        eprint!("{}", diagnostic.render_colored(source.as_deref()));
+    } else {
+        eprint!("{}", diagnostic.render(source.as_deref()));
+    }
+    explain_hint(diagnostic.code);
+}
+
+fn explain_hint(code: &str) {
+    eprintln!("For more information about a diagnostic, try `php2ir --explain {}`.", code);
+}
+
+/// Color diagnostics on a terminal, unless `NO_COLOR` is set
+fn use_color() -> bool {
+    std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
 fn parse_php_file(input: &PathBuf) -> Result<(), CompileError> {
     info!("Parsing PHP file: {}", input.display());
     
     let options = CompilerOptions {
         input: input.clone(),
         output: PathBuf::from("/dev/null"),
-        emit_llvm: false,
-        emit_llvm_only: false,
         optimization_level: "O0".to_string(),
-        lto: None,
-        pgo_gen: false,
-        pgo_use: None,
-        target: None,
-        stdlib: None,
-        no_runtime: false,
-        sanitizer: None,
+        ..CompilerOptions::default()
     };
 
     let mut compiler = Compiler::new(options)?;
@@ -215,13 +609,7 @@ fn show_ir(input: &PathBuf) -> Result<(), CompileError> {
         emit_llvm: true,
         emit_llvm_only: true,
         optimization_level: "O0".to_string(),
-        lto: None,
-        pgo_gen: false,
-        pgo_use: None,
-        target: None,
-        stdlib: None,
-        no_runtime: false,
-        sanitizer: None,
+        ..CompilerOptions::default()
     };
 
     let mut compiler = Compiler::new(options)?;
@@ -232,11 +620,21 @@ fn show_ir(input: &PathBuf) -> Result<(), CompileError> {
     Ok(())
 }
 
-fn run_tests(dir: Option<PathBuf>) -> Result<(), CompileError> {
+/// Run the `.phpt` files under `dir`; `Ok(false)` when a test failed or broke
+fn run_tests(dir: Option<PathBuf>, jobs: Option<usize>) -> Result<bool, CompileError> {
     let test_dir = dir.unwrap_or_else(|| PathBuf::from("tests"));
-    info!("Running tests in: {}", test_dir.display());
+    let tests = phpt::discover(&test_dir);
+    if tests.is_empty() {
+        return Err(CompileError::Configuration(format!("no .phpt tests found in {}", test_dir.display())));
+    }
+    info!("Running {} tests in {}", tests.len(), test_dir.display());
     
-    // TODO: Implement test runner
-    info!("Test runner not yet implemented");
-    Ok(())
+    let work_dir = tempfile::tempdir()?;
+    let mut runner = TestRunner::new(work_dir.path());
+    if let Some(jobs) = jobs {
+        runner = runner.with_jobs(jobs);
+    }
+    let summary = runner.run(&tests, |result| println!("{}", result));
+    print!("{}", summary);
+    Ok(summary.success())
 }
@@ -0,0 +1,348 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `php2ir.toml` project manifests.
+//!
+//! A manifest records the options of a build so it can be repeated without
+//! a long command line:
+//!
+//! ```toml
+//! entry = "src/main.php"
+//! modules = ["src/worker.php"]
+//! sources = ["src/lib/**/*.php"]
+//! build-dir = "build"
+//! include-paths = ["lib"]
+//! stubs = ["stubs/libc.phpstub"]
+//! output = "build/app"
+//! version = "1.2.0"
+//! target = "aarch64-unknown-linux-gnu"
+//! opt = "O3"
+//! release-size = false
+//!
+//! [defines]
+//! DEBUG = false
+//! VERSION = "1.2.0"
+//!
+//! [embed]
+//! "config.json" = "assets/config.json"
+//!
+//! [runtime]
+//! int-width = 64
+//! interpreter-fallback = false
+//! ```
+//!
+//! Paths are relative to the manifest's directory. The manifest is found
+//! next to the input or in one of its parent directories, or in the current
+//! directory when no input is given. Options given to the compiler take
+//! precedence: the manifest only fills in those still at their default.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use log::info;
+use serde::Deserialize;
+use crate::ast::Literal;
+use crate::backend::BackendKind;
+use crate::compiler::CompilerOptions;
+use crate::error::{CompileError, CompileResult};
+use crate::trace::Instrumentation;
+use crate::types::IntWidth;
+use crate::utils::path::normalize;
+
+/// File name of a project manifest
+pub const MANIFEST_FILE: &str = "php2ir.toml";
+
+/// Build options of a project
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Manifest {
+    /// Main PHP file
+    pub entry: Option<PathBuf>,
+
+    /// Additional PHP files compiled as separate objects and linked in
+    pub modules: Vec<PathBuf>,
+
+    /// Glob patterns of further sources compiled by `php2ir build`
+    pub sources: Vec<String>,
+
+    /// Directory for the objects, and the binary of `php2ir build`
+    pub build_dir: Option<PathBuf>,
+
+    /// Directories searched for relative `include`/`require` targets
+    pub include_paths: Vec<PathBuf>,
+
+    /// Signature-only `.phpstub` files
+    pub stubs: Vec<PathBuf>,
+
+    /// Output file
+    pub output: Option<PathBuf>,
+
+    /// Version of the program, for the pkg-config file of `--emit lib`
+    pub version: Option<String>,
+
+    /// Target triple
+    pub target: Option<String>,
+
+    /// Optimization level
+    pub opt: Option<String>,
+
+    /// Code generation backend
+    pub backend: Option<String>,
+
+    /// Build for the smallest binary, as `--release-size`
+    pub release_size: bool,
+
+    /// Constants defined before the program runs
+    pub defines: BTreeMap<String, toml::Value>,
+
+    /// Files compiled into the program for `embedded_file()`, by name
+    pub embed: BTreeMap<String, PathBuf>,
+
+    /// Options of the runtime library and generated code
+    pub runtime: RuntimeSection,
+}
+
+/// The `[runtime]` table
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RuntimeSection {
+    /// Runtime library archive, or a directory containing it
+    pub stdlib: Option<PathBuf>,
+
+    /// Link without the runtime library
+    pub no_runtime: bool,
+
+    /// Sanitizer
+    pub sanitize: Option<String>,
+
+    /// Width of PHP `int` in bits
+    pub int_width: Option<u32>,
+
+    /// Instrumentation inserted into generated functions
+    pub instrument: Option<String>,
+
+    /// Run functions that code generation cannot handle in the interpreter
+    pub interpreter_fallback: Option<bool>,
+}
+
+impl Manifest {
+    /// Manifest in `dir` or the closest of its parent directories, with that directory
+    pub fn find(dir: &Path) -> CompileResult<Option<(Self, PathBuf)>> {
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        let dir = std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        match dir.ancestors().find(|dir| dir.join(MANIFEST_FILE).is_file()) {
+            Some(root) => Ok(Some((Self::load(&root.join(MANIFEST_FILE))?, root.to_path_buf()))),
+            None => Ok(None),
+        }
+    }
+
+    /// Read a manifest file
+    pub fn load(path: &Path) -> CompileResult<Self> {
+        let source = std::fs::read_to_string(path)?;
+        Self::parse(&source).map_err(|e| CompileError::Configuration(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Parse the contents of a manifest file
+    pub fn parse(source: &str) -> Result<Self, String> {
+        toml::from_str(source).map_err(|e| e.to_string())
+    }
+
+    /// Fill in the options still at their default, resolving paths against `root`
+    pub fn apply(&self, options: &mut CompilerOptions, root: &Path) -> CompileResult<()> {
+        let defaults = CompilerOptions::default();
+        let path = |p: &PathBuf| normalize(root.join(p));
+        let invalid = |key: &str, e: String| CompileError::Configuration(format!("{} `{}`: {}", MANIFEST_FILE, key, e));
+
+        if options.input.as_os_str().is_empty() {
+            options.input = self.entry.as_ref().map(path).unwrap_or_default();
+        }
+        if options.output.as_os_str().is_empty() {
+            if let Some(output) = &self.output {
+                options.output = path(output);
+            }
+        }
+        if options.modules.is_empty() {
+            options.modules = self.modules.iter().map(path).collect();
+        }
+        if options.sources.is_empty() {
+            for pattern in &self.sources {
+                options.sources.extend(expand(pattern, root).map_err(|e| invalid("sources", e))?);
+            }
+        }
+        if options.build_dir.is_none() {
+            options.build_dir = self.build_dir.as_ref().map(path);
+        }
+        if options.include_paths.is_empty() {
+            options.include_paths = self.include_paths.iter().map(path).collect();
+        }
+        if options.stubs.is_empty() {
+            options.stubs = self.stubs.iter().map(path).collect();
+        }
+        if options.version.is_none() {
+            options.version = self.version.clone();
+        }
+        if options.target.is_none() {
+            options.target = self.target.clone();
+        }
+        if let Some(opt) = self.opt.as_ref().filter(|_| options.optimization_level == defaults.optimization_level) {
+            if !matches!(opt.as_str(), "O0" | "O1" | "O2" | "O3" | "Os" | "Oz") {
+                return Err(invalid("opt", format!("unknown optimization level '{}'", opt)));
+            }
+            options.optimization_level = opt.clone();
+        }
+        if let Some(backend) = self.backend.as_ref().filter(|_| options.backend == defaults.backend) {
+            options.backend = backend.parse::<BackendKind>().map_err(|e| invalid("backend", e))?;
+        }
+        options.release_size |= self.release_size;
+        for (name, value) in &self.defines {
+            if !options.defines.iter().any(|(defined, _)| defined == name) {
+                let value = define_value(value).ok_or_else(|| invalid(name, "expected a string, number or boolean".to_string()))?;
+                options.defines.push((name.clone(), value));
+            }
+        }
+
+        if options.embed.is_empty() {
+            options.embed = self.embed.iter().map(|(name, file)| (name.clone(), path(file))).collect();
+        }
+
+        let runtime = &self.runtime;
+        if options.stdlib.is_none() {
+            options.stdlib = runtime.stdlib.as_ref().map(path);
+        }
+        options.no_runtime |= runtime.no_runtime;
+        if options.sanitizer.is_none() {
+            options.sanitizer = runtime.sanitize.clone();
+        }
+        if let Some(bits) = runtime.int_width.filter(|_| options.int_width.is_none()) {
+            let width = IntWidth::from_bits(bits).ok_or_else(|| invalid("int-width", format!("expected 32 or 64, found {}", bits)))?;
+            options.int_width = Some(width);
+        }
+        if let Some(instrument) = runtime.instrument.as_ref().filter(|_| options.instrument.is_none()) {
+            options.instrument = Some(instrument.parse::<Instrumentation>().map_err(|e| invalid("instrument", e))?);
+        }
+        if let Some(fallback) = runtime.interpreter_fallback.filter(|_| options.interpreter_fallback) {
+            options.interpreter_fallback = fallback;
+        }
+        Ok(())
+    }
+}
+
+/// Find the manifest for `options` and apply it
+///
+/// Looks next to the input, or in the current directory without one. An
+/// input that remains unset afterwards is an error, and an unset output is
+/// derived from the input.
+pub fn apply_manifest(options: &mut CompilerOptions) -> CompileResult<()> {
+    let found = match &options.manifest {
+        Some(file) => Some((Manifest::load(file)?, file.parent().map(Path::to_path_buf).unwrap_or_default())),
+        None => Manifest::find(options.input.parent().unwrap_or(Path::new("")))?,
+    };
+    if let Some((manifest, root)) = found {
+        if options.manifest.is_none() {
+            options.manifest = Some(root.join(MANIFEST_FILE));
+        }
+        info!("Using {}", options.manifest.as_ref().unwrap_or(&root).display());
+        manifest.apply(options, &root)?;
+    }
+
+    if options.input.as_os_str().is_empty() {
+        return Err(CompileError::Configuration(format!(
+            "no input file given and no {} with an `entry` found",
+            MANIFEST_FILE
+        )));
+    }
+    if options.output.as_os_str().is_empty() {
+        options.output = options.input.with_extension("");
+    }
+    Ok(())
+}
+
+/// Files matching a glob pattern relative to `root`, in sorted order
+fn expand(pattern: &str, root: &Path) -> Result<Vec<PathBuf>, String> {
+    let pattern = root.join(pattern);
+    let paths = glob::glob(&pattern.to_string_lossy()).map_err(|e| e.to_string())?;
+    let mut files = Vec::new();
+    for path in paths {
+        let path = path.map_err(|e| e.to_string())?;
+        if path.is_file() {
+            files.push(normalize(path));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// PHP value of a `[defines]` entry
+fn define_value(value: &toml::Value) -> Option<Literal> {
+    match value {
+        toml::Value::String(s) => Some(Literal::String(s.clone())),
+        toml::Value::Integer(n) => Some(Literal::Int(*n)),
+        toml::Value::Float(x) => Some(Literal::Float(*x)),
+        toml::Value::Boolean(b) => Some(Literal::Bool(*b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_manifest() {
+        let manifest = Manifest::parse(r#"
+            entry = "src/main.php"
+            include-paths = ["lib"]
+            opt = "O3"
+            target = "aarch64-unknown-linux-gnu"
+            version = "2.0.1"
+
+            [defines]
+            DEBUG = false
+            NAME = "app"
+
+            [runtime]
+            int-width = 32
+            interpreter-fallback = false
+        "#).unwrap();
+
+        let mut options = CompilerOptions {
+            input: PathBuf::new(),
+            target: Some("x86_64-unknown-linux-gnu".to_string()),
+            defines: vec![("NAME".to_string(), Literal::String("cli".to_string()))],
+            ..CompilerOptions::default()
+        };
+        manifest.apply(&mut options, Path::new("/project")).unwrap();
+        assert_eq!(options.input, PathBuf::from("/project/src/main.php"));
+        assert_eq!(options.include_paths, [PathBuf::from("/project/lib")]);
+        assert_eq!(options.optimization_level, "O3");
+        assert_eq!(options.target.as_deref(), Some("x86_64-unknown-linux-gnu"));
+        assert_eq!(options.version.as_deref(), Some("2.0.1"));
+        assert!(matches!(options.defines.as_slice(), [
+            (cli, Literal::String(value)),
+            (debug, Literal::Bool(false)),
+        ] if cli == "NAME" && value == "cli" && debug == "DEBUG"));
+        assert_eq!(options.int_width, Some(IntWidth::W32));
+        assert!(!options.interpreter_fallback);
+    }
+
+    #[test]
+    fn test_invalid_manifest() {
+        assert!(Manifest::parse("entyr = \"main.php\"").is_err());
+
+        let manifest = Manifest::parse("[runtime]\nint-width = 16").unwrap();
+        let error = manifest.apply(&mut CompilerOptions::default(), Path::new("/")).unwrap_err();
+        assert!(error.to_string().contains("expected 32 or 64, found 16"));
+    }
+}
@@ -0,0 +1,511 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Member access on objects whose class is only known at run time.
+//!
+//! Every compiled class lists its public properties and methods in two
+//! null-terminated tables of its [`PhpClass`]. A property entry gives the
+//! field's offset in the object and the kind of value stored there; a method
+//! entry gives a function taking its arguments boxed, whatever the method's
+//! signature.
+//!
+//! Each access site in generated code has a [`PhpCache`] holding the class
+//! it last saw and the member's slot in that class's table. When the
+//! object's class matches, the slot is used as is; otherwise
+//! [`php_cache_property`] or [`php_cache_method`] look the member up by name
+//! in a hashtable built on first use and refill the cache. A slot of -1
+//! stands for a missing member, reported by the access itself.
+//!
+//! A missing member goes to the class's magic methods when it has them:
+//! `__get` and `__set` for properties, which are not called again for a
+//! property they are already handling on the object, and `__call` for
+//! methods, with the arguments packed in an array.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::sync::{Arc, Mutex};
+use crate::arrays::{php_array_append, php_array_new, php_array_release, PhpArray};
+use crate::exceptions::{throw_new, ERROR};
+use crate::mixed::{
+    php_mixed_addref, php_mixed_release, php_mixed_to_array, php_mixed_to_bool, php_mixed_to_float,
+    php_mixed_to_int, php_mixed_to_object, php_mixed_to_string, php_value_set_mixed, PhpMixed, TAG_ARRAY, TAG_BOOL,
+    TAG_FLOAT, TAG_INT, TAG_OBJECT, TAG_STRING,
+};
+use crate::objects::{php_object_release, PhpClass, PhpObject};
+use crate::runtime;
+use crate::strings::{php_string_release, PhpString};
+
+/// Kind of a property holding a boxed [`PhpMixed`]; the other kinds are
+/// the tags of the values stored unboxed
+pub const FIELD_MIXED: u32 = 7;
+/// Kind of an `int` property on targets with 32-bit integers
+pub const FIELD_INT32: u32 = 8;
+
+/// Function through which a method is called with boxed arguments: the
+/// object, the number of arguments and a pointer to them
+pub type DynamicEntry = unsafe extern "C-unwind" fn(*mut PhpObject, i64, *const PhpMixed) -> PhpMixed;
+
+/// A public property in a class's table
+#[repr(C)]
+#[derive(Debug)]
+pub struct PhpProperty {
+    pub(crate) name: *const c_char,
+    /// Byte offset of the field from the start of the object
+    pub(crate) offset: u64,
+    pub(crate) kind: u32,
+    pub(crate) is_readonly: bool,
+}
+
+unsafe impl Sync for PhpProperty {}
+
+/// A public method in a class's table, by lowercase name
+#[repr(C)]
+#[derive(Debug)]
+pub struct PhpMethod {
+    pub(crate) name: *const c_char,
+    pub(crate) entry: Option<DynamicEntry>,
+}
+
+unsafe impl Sync for PhpMethod {}
+
+/// Inline cache of one access site
+#[repr(C)]
+#[derive(Debug)]
+pub struct PhpCache {
+    pub class: *const PhpClass,
+    pub slot: i64,
+}
+
+/// Slots of a class's members by name
+#[derive(Debug, Default)]
+struct Members {
+    properties: HashMap<String, i64>,
+    methods: HashMap<String, i64>,
+}
+
+/// Member hashtables of the classes seen so far, by class address
+static MEMBERS: Mutex<Vec<(usize, Arc<Members>)>> = Mutex::new(Vec::new());
+
+/// Slots of the entries of a null-terminated table, by name
+unsafe fn names<T>(table: *const T, name: impl Fn(&T) -> *const c_char) -> HashMap<String, i64> {
+    let mut names = HashMap::new();
+    let mut entry = table;
+    while let Some(member) = entry.as_ref() {
+        let Some(name) = name(member).as_ref() else {
+            break;
+        };
+        let slot = names.len() as i64;
+        names.insert(CStr::from_ptr(name).to_string_lossy().into_owned(), slot);
+        entry = entry.add(1);
+    }
+    names
+}
+
+fn members(class: &PhpClass) -> Arc<Members> {
+    let address = class as *const PhpClass as usize;
+    let mut classes = MEMBERS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((_, members)) = classes.iter().find(|(a, _)| *a == address) {
+        return members.clone();
+    }
+    let members = Arc::new(unsafe {
+        Members {
+            properties: names(class.properties, |p| p.name),
+            methods: names(class.methods, |m| m.name),
+        }
+    });
+    classes.push((address, members.clone()));
+    members
+}
+
+thread_local! {
+    /// `__get` and `__set` calls in progress, by object, method and property
+    static MAGIC_CALLS: RefCell<Vec<(usize, &'static str, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Removes a magic call from [`MAGIC_CALLS`] when it returns or unwinds
+struct MagicCall;
+
+impl Drop for MagicCall {
+    fn drop(&mut self) {
+        MAGIC_CALLS.with(|calls| calls.borrow_mut().pop());
+    }
+}
+
+/// Entry of a magic method of a class, by lowercase name
+unsafe fn magic_method(class: &PhpClass, method: &str) -> Option<DynamicEntry> {
+    let slot = *members(class).methods.get(method)?;
+    (*class.methods.add(slot as usize)).entry
+}
+
+/// Call `__get` or `__set` for a property with the name and `values`,
+/// unless the class has no such method or it is handling the property on
+/// the object already
+unsafe fn call_property_magic(o: *mut PhpObject, method: &'static str, name: *const c_char, values: &[PhpMixed]) -> Option<PhpMixed> {
+    let entry = magic_method((*o).class(), method)?;
+    let call = (o as usize, method, text(name).into_owned());
+    if MAGIC_CALLS.with(|calls| calls.borrow().contains(&call)) {
+        return None;
+    }
+    MAGIC_CALLS.with(|calls| calls.borrow_mut().push(call));
+    let _call = MagicCall;
+    let name = PhpMixed::string(PhpString::new(CStr::from_ptr(name).to_bytes().to_vec()));
+    let arguments: Vec<PhpMixed> = std::iter::once(name).chain(values.iter().copied()).collect();
+    let result = entry(o, arguments.len() as i64, arguments.as_ptr());
+    php_mixed_release(name);
+    Some(result)
+}
+
+/// Fill a cache with a class and a slot, returning the slot
+unsafe fn fill(cache: *mut PhpCache, class: *const PhpClass, slot: Option<&i64>) -> i64 {
+    let slot = slot.copied().unwrap_or(-1);
+    if let Some(cache) = cache.as_mut() {
+        cache.class = class;
+        cache.slot = slot;
+    }
+    slot
+}
+
+/// Text of a NUL-terminated name
+unsafe fn text<'a>(name: *const c_char) -> std::borrow::Cow<'a, str> {
+    CStr::from_ptr(name).to_string_lossy()
+}
+
+/// Type name of a value that is not an object, as used in error messages
+fn type_name(v: PhpMixed) -> &'static str {
+    match v.tag {
+        TAG_BOOL => "bool",
+        TAG_INT => "int",
+        TAG_FLOAT => "float",
+        TAG_STRING => "string",
+        TAG_ARRAY => "array",
+        _ => "null",
+    }
+}
+
+/// Object and property of a slot, if the value is an object that has it
+unsafe fn property<'a>(object: PhpMixed, slot: i64) -> Result<(*mut PhpObject, &'a PhpProperty), Option<&'a PhpClass>> {
+    if object.tag != TAG_OBJECT {
+        return Err(None);
+    }
+    let o = object.payload as *mut PhpObject;
+    let class = (*o).class();
+    if slot < 0 {
+        return Err(Some(class));
+    }
+    Ok((o, &*class.properties.add(slot as usize)))
+}
+
+/// Field of a property, boxed as a new reference
+unsafe fn read_field(o: *mut PhpObject, property: &PhpProperty) -> PhpMixed {
+    let field = (o as *mut u8).add(property.offset as usize);
+    let value = match property.kind {
+        TAG_BOOL => PhpMixed::bool(*field != 0),
+        TAG_INT => PhpMixed::int(*(field as *const i64)),
+        FIELD_INT32 => PhpMixed::int(*(field as *const i32) as i64),
+        TAG_FLOAT => PhpMixed::float(*(field as *const f64)),
+        TAG_STRING => PhpMixed::string(*(field as *const *mut PhpString)),
+        TAG_ARRAY => PhpMixed::array(*(field as *const *mut PhpArray)),
+        TAG_OBJECT => match *(field as *const *mut PhpObject) {
+            object if object.is_null() => PhpMixed::NULL,
+            object => PhpMixed { tag: TAG_OBJECT, payload: object as u64 },
+        },
+        FIELD_MIXED => *(field as *const PhpMixed),
+        _ => PhpMixed::NULL,
+    };
+    php_mixed_addref(value);
+    value
+}
+
+/// Store a value in the field of a property, converted to its kind, and
+/// release the previous value
+///
+/// Conversions are those of the `php_mixed_to_*` functions.
+unsafe fn write_field(o: *mut PhpObject, property: &PhpProperty, value: PhpMixed) {
+    let field = (o as *mut u8).add(property.offset as usize);
+    match property.kind {
+        TAG_BOOL => *field = php_mixed_to_bool(value) as u8,
+        TAG_INT => *(field as *mut i64) = php_mixed_to_int(value),
+        FIELD_INT32 => *(field as *mut i32) = php_mixed_to_int(value) as i32,
+        TAG_FLOAT => *(field as *mut f64) = php_mixed_to_float(value),
+        TAG_STRING => {
+            let previous = std::ptr::replace(field as *mut *mut PhpString, php_mixed_to_string(value));
+            php_string_release(previous);
+        }
+        TAG_ARRAY => {
+            let previous = std::ptr::replace(field as *mut *mut PhpArray, php_mixed_to_array(value));
+            php_array_release(previous);
+        }
+        TAG_OBJECT => {
+            let previous = std::ptr::replace(field as *mut *mut PhpObject, php_mixed_to_object(value));
+            php_object_release(previous);
+        }
+        FIELD_MIXED => {
+            php_mixed_addref(value);
+            let previous = std::ptr::replace(field as *mut PhpMixed, value);
+            php_mixed_release(previous);
+        }
+        _ => {}
+    }
+}
+
+// FFI functions called by generated code
+
+/// Look a property up in a class after a cache miss, refilling the cache
+///
+/// # Safety
+///
+/// `cache` must be null or point to a cache, `class` to class metadata and
+/// `name` to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn php_cache_property(cache: *mut PhpCache, class: *const PhpClass, name: *const c_char) -> i64 {
+    let members = members(&*class);
+    fill(cache, class, members.properties.get(text(name).as_ref()))
+}
+
+/// Look a method up in a class after a cache miss, refilling the cache
+///
+/// Method names are compared case-insensitively.
+///
+/// # Safety
+///
+/// See [`php_cache_property`].
+#[no_mangle]
+pub unsafe extern "C" fn php_cache_method(cache: *mut PhpCache, class: *const PhpClass, name: *const c_char) -> i64 {
+    let members = members(&*class);
+    fill(cache, class, members.methods.get(&text(name).to_lowercase()))
+}
+
+/// Read the property in a slot as a new reference
+///
+/// A property the object's class does not have is read through its
+/// `__get`. Reading a property of a value that is not an object, or a
+/// missing one without `__get`, warns and gives null.
+///
+/// # Safety
+///
+/// `object` must hold a live value, `slot` must come from the object's
+/// class and `name` must point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_mixed_get_property(object: PhpMixed, slot: i64, name: *const c_char) -> PhpMixed {
+    match property(object, slot) {
+        Ok((o, property)) => read_field(o, property),
+        Err(class) => {
+            if class.is_some() {
+                if let Some(value) = call_property_magic(object.payload as *mut PhpObject, "__get", name, &[]) {
+                    return value;
+                }
+            }
+            let _ = runtime::flush_output();
+            match class {
+                Some(class) => eprintln!("PHP Warning:  Undefined property: {}::${}", class.name(), text(name)),
+                None => eprintln!("PHP Warning:  Attempt to read property \"{}\" on {}", text(name), type_name(object)),
+            }
+            PhpMixed::NULL
+        }
+    }
+}
+
+/// Assign the property in a slot, borrowing the value
+///
+/// A property the object's class does not have is assigned through its
+/// `__set`. Assigning a property of a value that is not an object, a
+/// missing one without `__set`, or a readonly one throws an `Error`.
+///
+/// # Safety
+///
+/// See [`php_mixed_get_property`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_mixed_set_property(object: PhpMixed, slot: i64, name: *const c_char, value: PhpMixed) {
+    match property(object, slot) {
+        Ok((o, property)) if property.is_readonly => throw_new(
+            &ERROR,
+            &format!("Cannot modify readonly property {}::${}", (*o).class().name(), text(name)),
+        ),
+        Ok((o, property)) => write_field(o, property, value),
+        Err(Some(class)) => match call_property_magic(object.payload as *mut PhpObject, "__set", name, &[value]) {
+            Some(result) => php_mixed_release(result),
+            None => throw_new(
+                &ERROR,
+                &format!("Cannot create dynamic property {}::${}", class.name(), text(name)),
+            ),
+        },
+        Err(None) => throw_new(
+            &ERROR,
+            &format!("Attempt to assign property \"{}\" on {}", text(name), type_name(object)),
+        ),
+    }
+}
+
+/// Entry of the method in a slot, or null when the object's class does
+/// not have the method but has `__call`, which
+/// [`php_mixed_call_magic`] calls instead
+///
+/// Calling a method on a value that is not an object, or a missing one
+/// without `__call`, throws an `Error`.
+///
+/// # Safety
+///
+/// See [`php_mixed_get_property`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_mixed_method(object: PhpMixed, slot: i64, name: *const c_char) -> Option<DynamicEntry> {
+    if object.tag != TAG_OBJECT {
+        throw_new(
+            &ERROR,
+            &format!("Call to a member function {}() on {}", text(name), type_name(object)),
+        );
+    }
+    let class = (*(object.payload as *mut PhpObject)).class();
+    let entry = (slot >= 0).then(|| (*class.methods.add(slot as usize)).entry).flatten();
+    match entry {
+        Some(entry) => Some(entry),
+        None if magic_method(class, "__call").is_some() => None,
+        None => throw_new(&ERROR, &format!("Call to undefined method {}::{}()", class.name(), text(name))),
+    }
+}
+
+/// Call `__call` of the object's class for a missing method, with the
+/// method's name and its borrowed arguments packed in an array
+///
+/// # Safety
+///
+/// `o` must be a live object whose class has `__call`, `name` must point
+/// to a NUL-terminated string and `argv` to `argc` values.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_mixed_call_magic(o: *mut PhpObject, name: *const c_char, argc: i64, argv: *const PhpMixed) -> PhpMixed {
+    let Some(entry) = magic_method((*o).class(), "__call") else {
+        throw_new(&ERROR, &format!("Call to undefined method {}::{}()", (*o).class().name(), text(name)));
+    };
+    let mut arguments = php_array_new(true);
+    for index in 0..argc.max(0) as usize {
+        php_value_set_mixed(php_array_append(&mut arguments), *argv.add(index));
+    }
+    let values = [
+        PhpMixed::string(PhpString::new(CStr::from_ptr(name).to_bytes().to_vec())),
+        PhpMixed::array(arguments),
+    ];
+    let result = entry(o, values.len() as i64, values.as_ptr());
+    values.into_iter().for_each(|value| php_mixed_release(value));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mixed::TAG_NULL;
+    use crate::objects::php_object_new;
+
+    #[repr(C)]
+    struct Point {
+        header: [u64; 2],
+        x: i64,
+        label: PhpMixed,
+    }
+
+    unsafe extern "C-unwind" fn norm(o: *mut PhpObject, argc: i64, argv: *const PhpMixed) -> PhpMixed {
+        let scale = if argc > 0 { php_mixed_to_int(*argv) } else { 1 };
+        PhpMixed::int((*(o as *mut Point)).x * scale)
+    }
+
+    static PROPERTIES: [PhpProperty; 3] = [
+        PhpProperty { name: c"x".as_ptr(), offset: 16, kind: TAG_INT, is_readonly: false },
+        PhpProperty { name: c"label".as_ptr(), offset: 24, kind: FIELD_MIXED, is_readonly: false },
+        PhpProperty { name: std::ptr::null(), offset: 0, kind: TAG_NULL, is_readonly: false },
+    ];
+    static METHODS: [PhpMethod; 2] = [
+        PhpMethod { name: c"norm".as_ptr(), entry: Some(norm) },
+        PhpMethod { name: std::ptr::null(), entry: None },
+    ];
+    static POINT: PhpClass = PhpClass {
+        name: c"Point".as_ptr(),
+        size: std::mem::size_of::<Point>() as u64,
+        parent: std::ptr::null(),
+        free: None,
+        clone: None,
+        vtable: std::ptr::null(),
+        itables: std::ptr::null(),
+        properties: PROPERTIES.as_ptr(),
+        methods: METHODS.as_ptr(),
+    };
+
+    #[test]
+    fn test_cached_member_access() {
+        unsafe {
+            let mut cache = PhpCache { class: std::ptr::null(), slot: 0 };
+            assert_eq!(php_cache_property(&mut cache, &POINT, c"label".as_ptr()), 1);
+            assert!(std::ptr::eq(cache.class, &POINT));
+            assert_eq!(cache.slot, 1);
+            assert_eq!(php_cache_property(&mut cache, &POINT, c"y".as_ptr()), -1);
+            assert_eq!(php_cache_method(&mut cache, &POINT, c"NORM".as_ptr()), 0);
+
+            let o = php_object_new(&POINT);
+            let object = PhpMixed { tag: TAG_OBJECT, payload: o as u64 };
+            php_mixed_set_property(object, 0, c"x".as_ptr(), PhpMixed::float(3.9));
+            assert_eq!(php_mixed_get_property(object, 0, c"x".as_ptr()), PhpMixed::int(3));
+            php_mixed_set_property(object, 1, c"label".as_ptr(), PhpMixed::bool(true));
+            assert_eq!(php_mixed_get_property(object, 1, c"label".as_ptr()), PhpMixed::bool(true));
+            assert_eq!(php_mixed_get_property(object, -1, c"y".as_ptr()), PhpMixed::NULL);
+            assert_eq!(php_mixed_get_property(PhpMixed::int(1), -1, c"x".as_ptr()), PhpMixed::NULL);
+
+            let entry = php_mixed_method(object, 0, c"norm".as_ptr()).unwrap();
+            assert_eq!(entry(o, 1, &PhpMixed::int(2)), PhpMixed::int(6));
+            php_object_release(o);
+        }
+    }
+
+    /// `__get`: the number of arguments, plus one when reading `$y` again
+    /// inside it gives null rather than calling it again
+    unsafe extern "C-unwind" fn get(o: *mut PhpObject, argc: i64, _: *const PhpMixed) -> PhpMixed {
+        let object = PhpMixed { tag: TAG_OBJECT, payload: o as u64 };
+        let again = php_mixed_get_property(object, -1, c"y".as_ptr());
+        PhpMixed::int(argc + (again == PhpMixed::NULL) as i64)
+    }
+
+    /// `__set`: store the value in `$x`
+    unsafe extern "C-unwind" fn set(o: *mut PhpObject, _: i64, argv: *const PhpMixed) -> PhpMixed {
+        (*(o as *mut Point)).x = php_mixed_to_int(*argv.add(1));
+        PhpMixed::NULL
+    }
+
+    /// `__call`: the number of arguments in the array
+    unsafe extern "C-unwind" fn call(_: *mut PhpObject, _: i64, argv: *const PhpMixed) -> PhpMixed {
+        PhpMixed::int((*((*argv.add(1)).payload as *const PhpArray)).array().len() as i64)
+    }
+
+    static MAGIC_METHODS: [PhpMethod; 4] = [
+        PhpMethod { name: c"__get".as_ptr(), entry: Some(get) },
+        PhpMethod { name: c"__set".as_ptr(), entry: Some(set) },
+        PhpMethod { name: c"__call".as_ptr(), entry: Some(call) },
+        PhpMethod { name: std::ptr::null(), entry: None },
+    ];
+    static MAGIC: PhpClass = PhpClass { methods: MAGIC_METHODS.as_ptr(), ..POINT };
+
+    #[test]
+    fn test_magic_methods() {
+        unsafe {
+            let o = php_object_new(&MAGIC);
+            let object = PhpMixed { tag: TAG_OBJECT, payload: o as u64 };
+            assert_eq!(php_mixed_get_property(object, -1, c"y".as_ptr()), PhpMixed::int(2));
+            php_mixed_set_property(object, -1, c"y".as_ptr(), PhpMixed::int(5));
+            assert_eq!((*(o as *mut Point)).x, 5);
+            assert!(php_mixed_method(object, -1, c"missing".as_ptr()).is_none());
+            let arguments = [PhpMixed::int(1), PhpMixed::bool(true)];
+            assert_eq!(php_mixed_call_magic(o, c"missing".as_ptr(), 2, arguments.as_ptr()), PhpMixed::int(2));
+            php_object_release(o);
+        }
+    }
+}
@@ -0,0 +1,519 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Values of compiled code whose type is only known at run time.
+//!
+//! Variables, parameters, properties and results without a static type
+//! are `%php.mixed` values, laid out as [`PhpMixed`]: a type tag and a
+//! 64-bit payload holding the integer, the bits of the float, or the
+//! pointer to the string, array or object. The payload owns a reference to
+//! a refcounted value, which [`php_mixed_addref`] and [`php_mixed_release`]
+//! manage like the other refcounted types.
+//!
+//! Generated code boxes statically typed values itself and passes
+//! `%php.mixed` by value; the two fields travel in two integer registers,
+//! matching the C layout of [`PhpMixed`]. Conversions and operators run in
+//! the runtime with the interpreter's semantics; they borrow their
+//! operands and return new references.
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use crate::arrays::{php_array_addref, php_array_release, PhpArray};
+use crate::ast::BinaryOperator;
+use crate::coercion::{coerce, TypeMode};
+use crate::exceptions::{raise, throw_new, ERROR, TYPE_ERROR};
+use crate::interp::{self, to_float, to_int, to_php_string, truthy};
+use crate::objects::{php_object_addref, php_object_release, PhpObject};
+use crate::runtime::{self, Array, ArrayType, Value};
+use crate::strings::{php_string_addref, php_string_print, php_string_release, PhpString};
+use crate::types::Type;
+
+/// Tag of `null`, also the tag of a zeroed value
+pub const TAG_NULL: u32 = 0;
+/// Tag of a `bool`, with a payload of 0 or 1
+pub const TAG_BOOL: u32 = 1;
+/// Tag of an `int`
+pub const TAG_INT: u32 = 2;
+/// Tag of a `float`, with the bits of the `f64` as payload
+pub const TAG_FLOAT: u32 = 3;
+/// Tag of a `%php.string*`
+pub const TAG_STRING: u32 = 4;
+/// Tag of a `%php.array*`
+pub const TAG_ARRAY: u32 = 5;
+/// Tag of a `%php.object*`
+pub const TAG_OBJECT: u32 = 6;
+
+/// Tagged value shared by generated code and the runtime
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhpMixed {
+    pub tag: u32,
+    pub payload: u64,
+}
+
+impl PhpMixed {
+    pub const NULL: PhpMixed = PhpMixed { tag: TAG_NULL, payload: 0 };
+
+    pub fn int(n: i64) -> Self {
+        PhpMixed { tag: TAG_INT, payload: n as u64 }
+    }
+
+    pub fn float(x: f64) -> Self {
+        PhpMixed { tag: TAG_FLOAT, payload: x.to_bits() }
+    }
+
+    pub fn bool(b: bool) -> Self {
+        PhpMixed { tag: TAG_BOOL, payload: b as u64 }
+    }
+
+    /// Box a string, taking over the caller's reference
+    pub fn string(s: *mut PhpString) -> Self {
+        PhpMixed { tag: TAG_STRING, payload: s as u64 }
+    }
+
+    /// Box an array, taking over the caller's reference
+    pub fn array(a: *mut PhpArray) -> Self {
+        PhpMixed { tag: TAG_ARRAY, payload: a as u64 }
+    }
+
+    fn object(&self) -> Option<*mut PhpObject> {
+        (self.tag == TAG_OBJECT).then_some(self.payload as *mut PhpObject)
+    }
+}
+
+/// Copy of a value as the interpreter sees it; objects have no such copy
+///
+/// Unknown tags read as null.
+pub(crate) unsafe fn to_value(v: PhpMixed) -> Option<Value> {
+    Some(match v.tag {
+        TAG_BOOL => Value::Bool(v.payload != 0),
+        TAG_INT => Value::Int(v.payload as i64),
+        TAG_FLOAT => Value::Float(f64::from_bits(v.payload)),
+        TAG_STRING => {
+            let bytes = (v.payload as *const PhpString).as_ref().map_or(&[][..], |s| s.as_bytes());
+            Value::String(String::from_utf8_lossy(bytes).into_owned())
+        }
+        TAG_ARRAY => Value::Array(
+            (v.payload as *const PhpArray)
+                .as_ref()
+                .map_or_else(|| Array::new(ArrayType::Packed), |a| a.array().clone()),
+        ),
+        TAG_OBJECT => return None,
+        _ => Value::Null,
+    })
+}
+
+/// Box an interpreter value; its objects and resources become null
+pub(crate) fn from_value(value: Value) -> PhpMixed {
+    match value {
+        Value::Bool(b) => PhpMixed::bool(b),
+        Value::Int(n) => PhpMixed::int(n),
+        Value::Float(x) => PhpMixed::float(x),
+        Value::String(s) => PhpMixed::string(PhpString::new(s.into_bytes())),
+        Value::Array(array) => PhpMixed::array(PhpArray::new(array)),
+        Value::Null | Value::Object(_) | Value::Resource(_) => PhpMixed::NULL,
+    }
+}
+
+/// Type name used in error messages
+unsafe fn type_name(v: PhpMixed) -> &'static str {
+    match to_value(v) {
+        Some(value) => interp::type_name(&value),
+        None => "object",
+    }
+}
+
+/// Apply an operator to the interpreter's copies of two values, throwing
+/// the errors it reports
+unsafe fn apply(op: BinaryOperator, left: PhpMixed, right: PhpMixed) -> Value {
+    let (Some(a), Some(b)) = (to_value(left), to_value(right)) else {
+        throw_new(
+            &TYPE_ERROR,
+            &format!("Unsupported operand types: {} {} {}", type_name(left), op, type_name(right)),
+        );
+    };
+    match interp::binary(&op, &a, &b, runtime::int_width()) {
+        Ok(value) => value,
+        Err(e) => raise(&e),
+    }
+}
+
+// FFI functions called by generated code
+
+/// Take another reference to the value's payload
+///
+/// # Safety
+///
+/// `v` must hold a live value; the same holds for the other functions.
+#[no_mangle]
+pub unsafe extern "C" fn php_mixed_addref(v: PhpMixed) {
+    match v.tag {
+        TAG_STRING => php_string_addref(v.payload as *mut PhpString),
+        TAG_ARRAY => php_array_addref(v.payload as *mut PhpArray),
+        TAG_OBJECT => php_object_addref(v.payload as *mut PhpObject),
+        _ => {}
+    }
+}
+
+/// Drop the reference held by the value's payload
+///
+/// # Safety
+///
+/// See [`php_mixed_addref`]; the payload must not be used after its last
+/// reference is released.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_mixed_release(v: PhpMixed) {
+    match v.tag {
+        TAG_STRING => php_string_release(v.payload as *mut PhpString),
+        TAG_ARRAY => php_array_release(v.payload as *mut PhpArray),
+        TAG_OBJECT => php_object_release(v.payload as *mut PhpObject),
+        _ => {}
+    }
+}
+
+/// Value as an integer, converted like `(int)`; objects give 1
+///
+/// # Safety
+///
+/// See [`php_mixed_addref`].
+#[no_mangle]
+pub unsafe extern "C" fn php_mixed_to_int(v: PhpMixed) -> i64 {
+    to_value(v).map_or(1, |value| to_int(&value).unwrap_or(0))
+}
+
+/// Value as a float, converted like `(float)`; objects give 1
+///
+/// # Safety
+///
+/// See [`php_mixed_addref`].
+#[no_mangle]
+pub unsafe extern "C" fn php_mixed_to_float(v: PhpMixed) -> f64 {
+    to_value(v).map_or(1.0, |value| to_float(&value).unwrap_or(0.0))
+}
+
+/// PHP truthiness of the value
+///
+/// # Safety
+///
+/// See [`php_mixed_addref`].
+#[no_mangle]
+pub unsafe extern "C" fn php_mixed_to_bool(v: PhpMixed) -> bool {
+    to_value(v).as_ref().is_none_or(truthy)
+}
+
+/// Value as a new string reference
+///
+/// Objects throw an `Error`, as compiled classes have no `__toString()`
+/// support yet.
+///
+/// # Safety
+///
+/// See [`php_mixed_addref`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_mixed_to_string(v: PhpMixed) -> *mut PhpString {
+    if v.tag == TAG_STRING {
+        php_string_addref(v.payload as *mut PhpString);
+        return v.payload as *mut PhpString;
+    }
+    match v.object() {
+        Some(o) => throw_new(
+            &ERROR,
+            &format!("Object of class {} could not be converted to string", (*o).class().name()),
+        ),
+        None => PhpString::new(to_value(v).and_then(|value| to_php_string(&value).ok()).unwrap_or_default().into_bytes()),
+    }
+}
+
+/// Print the value as `echo` does
+///
+/// # Safety
+///
+/// See [`php_mixed_addref`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_mixed_print(v: PhpMixed) {
+    let s = php_mixed_to_string(v);
+    php_string_print(s);
+    php_string_release(s);
+}
+
+/// `exit()`/`die()` with a value, taking its reference: an int is the
+/// exit status, anything else is printed and exits with status 0
+///
+/// # Safety
+///
+/// See [`php_mixed_addref`] and [`runtime::php_exit`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_mixed_exit(v: PhpMixed) -> ! {
+    let status = match v.tag {
+        TAG_INT => v.payload as i32,
+        _ => {
+            php_mixed_print(v);
+            0
+        }
+    };
+    php_mixed_release(v);
+    runtime::php_exit(status)
+}
+
+/// Array held by the value as a new reference; other values give an
+/// empty array
+///
+/// # Safety
+///
+/// See [`php_mixed_addref`].
+#[no_mangle]
+pub unsafe extern "C" fn php_mixed_to_array(v: PhpMixed) -> *mut PhpArray {
+    if v.tag != TAG_ARRAY {
+        return std::ptr::null_mut();
+    }
+    php_array_addref(v.payload as *mut PhpArray);
+    v.payload as *mut PhpArray
+}
+
+/// Object held by the value as a new reference; other values give null
+///
+/// # Safety
+///
+/// See [`php_mixed_addref`].
+#[no_mangle]
+pub unsafe extern "C" fn php_mixed_to_object(v: PhpMixed) -> *mut PhpObject {
+    match v.object() {
+        Some(o) => {
+            php_object_addref(o);
+            o
+        }
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Scalar types a parameter may accept, with the tags of their values
+const SCALAR_TAGS: [(u32, Type); 5] = [
+    (TAG_NULL, Type::Null),
+    (TAG_BOOL, Type::Bool),
+    (TAG_INT, Type::Int),
+    (TAG_FLOAT, Type::Float),
+    (TAG_STRING, Type::String),
+];
+
+/// Bits `1 << TAG_*` of the values a parameter type accepts as they are,
+/// when it only has scalar and null members
+pub fn scalar_tags(typ: &Type) -> Option<u32> {
+    let members = match typ {
+        Type::Union(members) => members.as_slice(),
+        single => std::slice::from_ref(single),
+    };
+    members.iter().try_fold(0, |tags, member| {
+        let (tag, _) = SCALAR_TAGS.iter().find(|(_, typ)| typ == member)?;
+        Some(tags | 1 << tag)
+    })
+}
+
+/// Argument passed to a parameter of scalar type, converted as PHP does
+///
+/// `tags` are the parameter's [`scalar_tags`], `strict` whether the calling
+/// file declares `strict_types=1`, and `message` the start of the
+/// `TypeError` thrown for a rejected argument, up to the expected type.
+/// The argument is borrowed and the result is a new reference.
+///
+/// # Safety
+///
+/// See [`php_mixed_addref`]; `message` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_mixed_coerce_argument(v: PhpMixed, tags: u32, strict: bool, message: *const c_char) -> PhpMixed {
+    if v.tag < u32::BITS && tags & 1 << v.tag != 0 {
+        php_mixed_addref(v);
+        return v;
+    }
+    let typ = Type::Union(SCALAR_TAGS.iter().filter(|(tag, _)| tags & 1 << tag != 0).map(|(_, typ)| typ.clone()).collect());
+    let mode = if strict { TypeMode::Strict } else { TypeMode::Coercive };
+    let given = match (v.object(), to_value(v)) {
+        (Some(o), _) => (*o).class().name().to_string(),
+        (None, Some(value)) => match coerce(value, &typ, mode) {
+            Ok(value) => return from_value(value),
+            Err(given) => given.to_string(),
+        },
+        (None, None) => "mixed".to_string(),
+    };
+    throw_new(&TYPE_ERROR, &format!("{}, {} given", CStr::from_ptr(message).to_string_lossy(), given))
+}
+
+/// `+`, including the union of two arrays
+///
+/// # Safety
+///
+/// See [`php_mixed_addref`]; the same holds for the other operators.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_mixed_add(left: PhpMixed, right: PhpMixed) -> PhpMixed {
+    from_value(apply(BinaryOperator::Add, left, right))
+}
+
+/// # Safety
+///
+/// See [`php_mixed_add`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_mixed_sub(left: PhpMixed, right: PhpMixed) -> PhpMixed {
+    from_value(apply(BinaryOperator::Sub, left, right))
+}
+
+/// # Safety
+///
+/// See [`php_mixed_add`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_mixed_mul(left: PhpMixed, right: PhpMixed) -> PhpMixed {
+    from_value(apply(BinaryOperator::Mul, left, right))
+}
+
+/// `/`, an integer when it divides exactly
+///
+/// # Safety
+///
+/// See [`php_mixed_add`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_mixed_div(left: PhpMixed, right: PhpMixed) -> PhpMixed {
+    from_value(apply(BinaryOperator::Div, left, right))
+}
+
+/// `**`, an integer when the result fits
+///
+/// # Safety
+///
+/// See [`php_mixed_add`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_mixed_pow(left: PhpMixed, right: PhpMixed) -> PhpMixed {
+    from_value(apply(BinaryOperator::Pow, left, right))
+}
+
+/// PHP 8 loose comparison, as `<=>` gives it: -1, 0 or 1
+///
+/// An object equals itself and compares with other values by truthiness
+/// or not at all, which gives 1.
+///
+/// # Safety
+///
+/// See [`php_mixed_add`].
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_mixed_compare(left: PhpMixed, right: PhpMixed) -> i64 {
+    match (left.object(), right.object()) {
+        (Some(a), Some(b)) => return if a == b { 0 } else { 1 },
+        (Some(_), None) if matches!(right.tag, TAG_NULL | TAG_BOOL) => {
+            return (php_mixed_to_bool(right) as i64 - 1).abs();
+        }
+        (None, Some(_)) if matches!(left.tag, TAG_NULL | TAG_BOOL) => {
+            return php_mixed_to_bool(left) as i64 - 1;
+        }
+        (None, None) => {}
+        _ => return 1,
+    }
+    match apply(BinaryOperator::Spaceship, left, right) {
+        Value::Int(n) => n,
+        _ => 1,
+    }
+}
+
+/// `===`: same type and value, or the same object
+///
+/// # Safety
+///
+/// See [`php_mixed_add`].
+#[no_mangle]
+pub unsafe extern "C" fn php_mixed_identical(left: PhpMixed, right: PhpMixed) -> bool {
+    match (left.object(), right.object(), to_value(left), to_value(right)) {
+        (Some(a), Some(b), _, _) => a == b,
+        (None, None, Some(a), Some(b)) => {
+            matches!(interp::binary(&BinaryOperator::Identical, &a, &b, runtime::int_width()), Ok(Value::Bool(true)))
+        }
+        _ => false,
+    }
+}
+
+/// Store a copy of the value in an array element or generator slot;
+/// objects are stored as null, as boxed values cannot hold them yet
+///
+/// # Safety
+///
+/// See [`crate::arrays::php_value_set_int`] and [`php_mixed_addref`].
+#[no_mangle]
+pub unsafe extern "C" fn php_value_set_mixed(value: *mut Value, v: PhpMixed) {
+    if let Some(slot) = value.as_mut() {
+        *slot = to_value(v).unwrap_or(Value::Null);
+    }
+}
+
+/// Value of an array element or generator slot as a new reference
+///
+/// # Safety
+///
+/// See [`crate::arrays::php_value_get_int`].
+#[no_mangle]
+pub unsafe extern "C" fn php_value_get_mixed(value: *const Value) -> PhpMixed {
+    value.as_ref().map_or(PhpMixed::NULL, |value| from_value(value.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::raw::c_char;
+    use crate::strings::php_string_new;
+
+    unsafe fn string(s: &str) -> PhpMixed {
+        PhpMixed::string(php_string_new(s.as_ptr() as *const c_char, s.len() as i64))
+    }
+
+    #[test]
+    fn test_mixed_operators() {
+        unsafe {
+            let five = string("5");
+            let sum = php_mixed_add(five, PhpMixed::int(2));
+            assert_eq!(sum, PhpMixed::int(7));
+            let half = php_mixed_div(PhpMixed::int(1), PhpMixed::int(2));
+            assert_eq!(half, PhpMixed::float(0.5));
+            let big = php_mixed_mul(PhpMixed::int(i64::MAX), PhpMixed::int(2));
+            assert_eq!(big.tag, TAG_FLOAT);
+
+            let text = php_mixed_to_string(half);
+            assert_eq!((*text).as_bytes(), b"0.5");
+            php_string_release(text);
+            assert_eq!(php_mixed_to_int(five), 5);
+            let zero = string("0");
+            assert!(!php_mixed_to_bool(zero));
+            php_mixed_release(zero);
+
+            assert_eq!(php_mixed_compare(five, PhpMixed::int(5)), 0);
+            assert_eq!(php_mixed_compare(PhpMixed::NULL, PhpMixed::bool(true)), -1);
+            assert!(!php_mixed_identical(five, PhpMixed::int(5)));
+            assert!(php_mixed_identical(five, five));
+            php_mixed_release(five);
+        }
+    }
+
+    #[test]
+    fn test_coerce_argument() {
+        let int = scalar_tags(&Type::Int).unwrap();
+        assert_eq!(scalar_tags(&Type::Union(vec![Type::Int, Type::Null])), Some(int | 1 << TAG_NULL));
+        assert_eq!(scalar_tags(&Type::Object("Point".to_string())), None);
+
+        let message = b"f(): Argument #1 ($n) must be of type int\0".as_ptr() as *const c_char;
+        unsafe {
+            let five = string("5");
+            assert_eq!(php_mixed_coerce_argument(five, int, false, message), PhpMixed::int(5));
+            php_mixed_release(five);
+            assert_eq!(php_mixed_coerce_argument(PhpMixed::int(2), int, true, message), PhpMixed::int(2));
+            // Integers widen to floats even in strict mode
+            let float = scalar_tags(&Type::Float).unwrap();
+            assert_eq!(php_mixed_coerce_argument(PhpMixed::int(2), float, true, message), PhpMixed::float(2.0));
+        }
+    }
+}
@@ -0,0 +1,214 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Multi-object builds.
+//!
+//! Each PHP file compiled as a separate object gets a module prefix that is
+//! applied to its private artifacts (string constants, closures, its init
+//! function), so objects from different files never collide at link time.
+//! A small driver object holds the `@php_module_init` registry and the
+//! `main` function that runs every module's top-level code in order. A
+//! static library gets an entry object with init and cleanup functions
+//! instead of `main`.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use crate::target::TargetSpec;
+use crate::trace::Instrumentation;
+
+/// Symbol of the registry of module init functions
+pub const REGISTRY_SYMBOL: &str = "php_module_init";
+
+/// A PHP file compiled into its own object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleInfo {
+    /// Source file
+    pub path: PathBuf,
+
+    /// Prefix for module-private symbols
+    pub prefix: String,
+}
+
+impl ModuleInfo {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let prefix = module_prefix(&path);
+        Self { path, prefix }
+    }
+
+    /// Symbol of the function running this module's top-level code
+    pub fn init_symbol(&self) -> String {
+        format!("php.module.{}.init", self.prefix)
+    }
+
+    /// Symbol of the `index`-th private artifact of a kind (`str`, `const`, `closure`, `cache`)
+    pub fn private_symbol(&self, kind: &str, index: usize) -> String {
+        format!(".{}.{}.{}", kind, self.prefix, index)
+    }
+}
+
+/// Stable, symbol-safe prefix derived from a file path
+///
+/// The readable file stem is kept for debugging; the hash of the full path
+/// keeps `a/util.php` and `b/util.php` apart.
+pub fn module_prefix(path: &Path) -> String {
+    let stem: String = path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+
+    let mut hasher = DefaultHasher::new();
+    path.to_string_lossy().replace('\\', "/").hash(&mut hasher);
+    format!("{}_{:08x}", stem, hasher.finish() as u32)
+}
+
+/// Generate the driver module: the init registry and `main`
+pub fn generate_driver(modules: &[ModuleInfo], instrumentation: Option<Instrumentation>, target: &TargetSpec) -> String {
+    let mut ir = driver_prelude("php2ir.driver", modules, instrumentation, target);
+    ir.push_str("define i32 @main(i32 %argc, i8** %argv) {\n");
+    ir.push_str("entry:\n");
+    push_init_loop(&mut ir, modules.len());
+    push_cleanup(&mut ir, instrumentation);
+    ir.push_str("  ret i32 0\n");
+    ir.push_str("}\n");
+    ir
+}
+
+/// Generate the entry module of a static library, in place of the driver
+///
+/// `<prefix>_init()` starts the runtime and runs the top-level code of every
+/// module in order; `<prefix>_cleanup()` releases the variables they share
+/// and shuts the runtime down. A program embedding the library calls them
+/// around its calls to exported functions.
+pub fn generate_library_entry(modules: &[ModuleInfo], instrumentation: Option<Instrumentation>, target: &TargetSpec, prefix: &str) -> String {
+    let mut ir = driver_prelude("php2ir.library", modules, instrumentation, target);
+    ir.push_str(&format!("define void @{}_init() {{\n", prefix));
+    ir.push_str("entry:\n");
+    push_init_loop(&mut ir, modules.len());
+    ir.push_str("  ret void\n");
+    ir.push_str("}\n\n");
+
+    ir.push_str(&format!("define void @{}_cleanup() {{\n", prefix));
+    ir.push_str("entry:\n");
+    push_cleanup(&mut ir, instrumentation);
+    ir.push_str("  ret void\n");
+    ir.push_str("}\n");
+    ir
+}
+
+/// Module header, runtime declarations and the init registry
+fn driver_prelude(name: &str, modules: &[ModuleInfo], instrumentation: Option<Instrumentation>, target: &TargetSpec) -> String {
+    let mut ir = String::new();
+    ir.push_str(&format!("; ModuleID = '{}'\n", name));
+    ir.push_str(&format!("source_filename = \"{}\"\n", name));
+    ir.push_str(&target.module_header());
+    ir.push('\n');
+
+    ir.push_str("declare void @php_init()\n");
+    ir.push_str("declare void @php_cleanup()\n");
+    ir.push_str("declare void @php_globals_release()\n");
+    if instrumentation == Some(Instrumentation::Trace) {
+        ir.push_str("declare i32 @php2ir_trace_flush()\n");
+    }
+    for module in modules {
+        ir.push_str(&format!("declare hidden void @{}()\n", module.init_symbol()));
+    }
+
+    let entries: Vec<String> = modules.iter()
+        .map(|m| format!("void ()* @{}", m.init_symbol()))
+        .collect();
+    ir.push_str(&format!(
+        "\n@{} = hidden constant {} [{}]\n\n",
+        REGISTRY_SYMBOL, registry_type(modules.len()), entries.join(", ")
+    ));
+    ir
+}
+
+fn registry_type(count: usize) -> String {
+    format!("[{} x void ()*]", count)
+}
+
+/// Start the runtime and call every init function of the registry, ending
+/// in an `exit` block
+fn push_init_loop(ir: &mut String, count: usize) {
+    let slot_type = registry_type(count);
+    ir.push_str("  call void @php_init()\n");
+    ir.push_str("  br label %loop\n");
+    ir.push_str("loop:\n");
+    ir.push_str("  %i = phi i64 [ 0, %entry ], [ %next, %body ]\n");
+    ir.push_str(&format!("  %done = icmp eq i64 %i, {}\n", count));
+    ir.push_str("  br i1 %done, label %exit, label %body\n");
+    ir.push_str("body:\n");
+    ir.push_str(&format!(
+        "  %slot = getelementptr {0}, {0}* @{1}, i64 0, i64 %i\n",
+        slot_type, REGISTRY_SYMBOL
+    ));
+    ir.push_str("  %init = load void ()*, void ()** %slot\n");
+    ir.push_str("  call void %init()\n");
+    ir.push_str("  %next = add i64 %i, 1\n");
+    ir.push_str("  br label %loop\n");
+    ir.push_str("exit:\n");
+}
+
+/// Release the variables shared by the modules and shut the runtime down
+fn push_cleanup(ir: &mut String, instrumentation: Option<Instrumentation>) {
+    // Variables shared by the modules are released once all of them have run
+    ir.push_str("  call void @php_globals_release()\n");
+    if instrumentation == Some(Instrumentation::Trace) {
+        ir.push_str("  call i32 @php2ir_trace_flush()\n");
+    }
+    ir.push_str("  call void @php_cleanup()\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_prefix_is_unique_per_path() {
+        let a = ModuleInfo::new("src/a/util.php");
+        let b = ModuleInfo::new("src/b/util.php");
+        assert!(a.prefix.starts_with("util_"));
+        assert_ne!(a.prefix, b.prefix);
+        assert_eq!(a, ModuleInfo::new("src/a/util.php"));
+        assert!(a.private_symbol("str", 3).starts_with(".str.util_"));
+    }
+
+    #[test]
+    fn test_driver_registry() {
+        let modules = vec![ModuleInfo::new("main.php"), ModuleInfo::new("lib.php")];
+        let ir = generate_driver(&modules, None, &TargetSpec::from_target("aarch64-unknown-linux-gnu"));
+        assert!(ir.contains(&format!("@php_module_init = hidden constant [2 x void ()*] [void ()* @{}", modules[0].init_symbol())));
+        assert!(ir.contains("define i32 @main("));
+        assert!(ir.contains("%done = icmp eq i64 %i, 2"));
+        assert!(ir.contains("target triple = \"aarch64-unknown-linux-gnu\""));
+    }
+
+    #[test]
+    fn test_library_entry() {
+        let modules = vec![ModuleInfo::new("lib.php")];
+        let ir = generate_library_entry(&modules, None, &TargetSpec::from_target("x86_64-unknown-linux-gnu"), "mylib");
+        assert!(!ir.contains("@main"));
+        let init = &ir[ir.find("define void @mylib_init() {").unwrap()..ir.find("define void @mylib_cleanup() {").unwrap()];
+        assert!(init.contains("call void @php_init()"));
+        assert!(init.contains("%done = icmp eq i64 %i, 1"));
+        assert!(!init.contains("@php_cleanup"));
+        assert!(ir.contains("  call void @php_cleanup()\n  ret void\n}\n"));
+    }
+}
@@ -0,0 +1,376 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Fully-qualified name resolution.
+//!
+//! Rewrites declaration names and every class, function and constant
+//! reference to its fully-qualified form (without the leading `\`), applying
+//! the enclosing namespace and `use` imports. Unqualified function and
+//! constant references fall back to the global name unless the namespace
+//! declares them, mirroring PHP's runtime fallback. Attribute names are left
+//! as written.
+
+use std::collections::{HashMap, HashSet};
+use crate::ast::visit::{self, VisitorMut};
+use crate::ast::{AstNode, Expression, FunctionDecl, Statement, TraitAdaptation, UseDecl, UseKind};
+use crate::types::Type;
+
+/// Class-like names that never refer to a declared class
+const RESERVED_CLASS_NAMES: &[&str] = &[
+    "self", "static", "parent", "int", "float", "bool", "string", "array", "object",
+    "mixed", "void", "null", "never", "iterable", "callable", "true", "false",
+];
+
+/// Resolve names throughout an AST in place
+pub fn resolve_names(ast: &mut [AstNode]) {
+    let mut resolver = NameResolver::default();
+    collect_declarations(ast, None, &mut resolver);
+    resolver.resolve_scope(ast);
+}
+
+/// Name resolution state for the current namespace
+#[derive(Debug, Default)]
+pub struct NameResolver {
+    namespace: Option<String>,
+    /// `use` imports keyed by lowercased alias
+    class_imports: HashMap<String, String>,
+    function_imports: HashMap<String, String>,
+    /// `use const` imports keyed by alias (constants are case-sensitive)
+    constant_imports: HashMap<String, String>,
+    /// Lowercased fully-qualified names of declared functions
+    declared_functions: HashSet<String>,
+}
+
+impl NameResolver {
+    /// Resolve a class reference
+    pub fn resolve_class(&self, name: &str) -> String {
+        if RESERVED_CLASS_NAMES.contains(&name.to_ascii_lowercase().as_str()) {
+            return name.to_string();
+        }
+        self.resolve_with(name, &self.class_imports, |n| n.to_ascii_lowercase())
+            .unwrap_or_else(|| self.qualify(name))
+    }
+
+    /// Resolve a function reference
+    pub fn resolve_function(&self, name: &str) -> String {
+        self.resolve_with(name, &self.function_imports, |n| n.to_ascii_lowercase())
+            .unwrap_or_else(|| {
+                let qualified = self.qualify(name);
+                if self.declared_functions.contains(&qualified.to_ascii_lowercase()) {
+                    qualified
+                } else {
+                    name.to_string()
+                }
+            })
+    }
+
+    /// Resolve a constant reference
+    pub fn resolve_constant(&self, name: &str) -> String {
+        if matches!(name.to_ascii_lowercase().as_str(), "true" | "false" | "null") {
+            return name.to_string();
+        }
+        // Global fallback: constants declared with define() are only known at runtime
+        self.resolve_with(name, &self.constant_imports, |n| n.to_string())
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    /// Resolve every class name inside a type
+    pub fn resolve_type(&self, typ: &mut Type) {
+        match typ {
+            Type::Object(name) => *name = self.resolve_class(name),
+            Type::Array(inner) | Type::AssociativeArray(inner) => self.resolve_type(inner),
+            Type::Union(types) | Type::Intersection(types) => types.iter_mut().for_each(|t| self.resolve_type(t)),
+            Type::Function(params, ret) => {
+                params.iter_mut().for_each(|t| self.resolve_type(t));
+                self.resolve_type(ret);
+            }
+            // Generic types without arguments are template parameters
+            Type::Generic(name, args) if !args.is_empty() => {
+                *name = self.resolve_class(name);
+                args.iter_mut().for_each(|t| self.resolve_type(t));
+            }
+            _ => {}
+        }
+    }
+
+    /// Handle fully-qualified, `namespace\`-relative and imported names
+    ///
+    /// Returns `None` for unqualified, unimported names.
+    fn resolve_with(
+        &self,
+        name: &str,
+        imports: &HashMap<String, String>,
+        key: impl Fn(&str) -> String,
+    ) -> Option<String> {
+        if let Some(fully_qualified) = name.strip_prefix('\\') {
+            return Some(fully_qualified.to_string());
+        }
+        if let Some(relative) = name.strip_prefix("namespace\\") {
+            return Some(self.qualify(relative));
+        }
+
+        match name.split_once('\\') {
+            // Qualified names resolve their first segment against class imports
+            Some((first, rest)) => Some(match self.class_imports.get(&first.to_ascii_lowercase()) {
+                Some(prefix) => format!("{}\\{}", prefix, rest),
+                None => self.qualify(name),
+            }),
+            None => imports.get(&key(name)).cloned(),
+        }
+    }
+
+    fn qualify(&self, name: &str) -> String {
+        qualify(self.namespace.as_deref(), name)
+    }
+
+    fn import(&mut self, use_decl: &UseDecl) {
+        for clause in &use_decl.uses {
+            let target = clause.name.trim_start_matches('\\').to_string();
+            let alias = clause.alias.clone()
+                .unwrap_or_else(|| target.rsplit('\\').next().unwrap_or(&target).to_string());
+            match use_decl.kind {
+                UseKind::Normal => self.class_imports.insert(alias.to_ascii_lowercase(), target),
+                UseKind::Function => self.function_imports.insert(alias.to_ascii_lowercase(), target),
+                UseKind::Const => self.constant_imports.insert(alias, target),
+            };
+        }
+    }
+
+    /// Resolve the nodes of one namespace scope
+    fn resolve_scope(&mut self, nodes: &mut [AstNode]) {
+        for node in nodes {
+            match node {
+                AstNode::Program(nodes) => self.resolve_scope(nodes),
+                AstNode::Namespace(ns) => {
+                    let saved = (
+                        std::mem::replace(&mut self.namespace, ns.name.clone()),
+                        std::mem::take(&mut self.class_imports),
+                        std::mem::take(&mut self.function_imports),
+                        std::mem::take(&mut self.constant_imports),
+                    );
+                    self.resolve_scope(&mut ns.statements);
+                    (self.namespace, self.class_imports, self.function_imports, self.constant_imports) = saved;
+                }
+                AstNode::Use(use_decl) => self.import(use_decl),
+                AstNode::Function(function) => {
+                    function.name = self.qualify(&function.name);
+                    self.visit_function(function);
+                }
+                AstNode::Class(class) => {
+                    class.name = self.qualify(&class.name);
+                    class.extends = class.extends.as_ref().map(|e| self.resolve_class(e));
+                    class.implements = class.implements.iter().map(|i| self.resolve_class(i)).collect();
+                    for trait_use in &mut class.traits {
+                        trait_use.traits = trait_use.traits.iter().map(|t| self.resolve_class(t)).collect();
+                        for adaptation in &mut trait_use.adaptations {
+                            match adaptation {
+                                TraitAdaptation::Precedence { trait_name, instead_of, .. } => {
+                                    *trait_name = self.resolve_class(trait_name);
+                                    *instead_of = instead_of.iter().map(|t| self.resolve_class(t)).collect();
+                                }
+                                TraitAdaptation::Alias { trait_name, .. } => {
+                                    *trait_name = trait_name.as_ref().map(|t| self.resolve_class(t));
+                                }
+                            }
+                        }
+                    }
+                    for property in &mut class.properties {
+                        if let Some(typ) = &mut property.typ {
+                            self.resolve_type(typ);
+                        }
+                    }
+                    visit::walk_node(self, node);
+                }
+                AstNode::Interface(interface) => {
+                    interface.name = self.qualify(&interface.name);
+                    interface.extends = interface.extends.iter().map(|e| self.resolve_class(e)).collect();
+                    visit::walk_node(self, node);
+                }
+                AstNode::Trait(t) => {
+                    t.name = self.qualify(&t.name);
+                    for property in &mut t.properties {
+                        if let Some(typ) = &mut property.typ {
+                            self.resolve_type(typ);
+                        }
+                    }
+                    visit::walk_node(self, node);
+                }
+                AstNode::Enum(e) => {
+                    e.name = self.qualify(&e.name);
+                    if let Some(typ) = &mut e.backing_type {
+                        self.resolve_type(typ);
+                    }
+                    visit::walk_node(self, node);
+                }
+                _ => self.visit_node(node),
+            }
+        }
+    }
+}
+
+impl VisitorMut for NameResolver {
+    fn visit_function(&mut self, function: &mut FunctionDecl) {
+        for param in &mut function.parameters {
+            if let Some(typ) = &mut param.typ {
+                self.resolve_type(typ);
+            }
+        }
+        if let Some(typ) = &mut function.return_type {
+            self.resolve_type(typ);
+        }
+        visit::walk_function(self, function);
+    }
+
+    fn visit_statement(&mut self, stmt: &mut Statement) {
+        if let Statement::Declaration(node) = stmt {
+            self.resolve_scope(std::slice::from_mut(node.as_mut()));
+            return;
+        }
+        if let Statement::Try { catch_blocks, .. } = stmt {
+            for catch in catch_blocks.iter_mut() {
+                catch.types.iter_mut().for_each(|t| self.resolve_type(t));
+            }
+        }
+        visit::walk_statement(self, stmt);
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression) {
+        match expr {
+            Expression::Constant(name) => *name = self.resolve_constant(name),
+            Expression::FunctionCall { name, arguments } => {
+                match name.as_mut() {
+                    Expression::Constant(function) => *function = self.resolve_function(function),
+                    other => self.visit_expression(other),
+                }
+                arguments.iter_mut().for_each(|a| self.visit_expression(a));
+            }
+            Expression::New { class, arguments } | Expression::StaticCall { class, arguments, .. } => {
+                self.visit_class_reference(class);
+                arguments.iter_mut().for_each(|a| self.visit_expression(a));
+            }
+            Expression::InstanceOf { expr, class } => {
+                self.visit_expression(expr);
+                self.visit_class_reference(class);
+            }
+            Expression::StaticPropertyAccess { class, .. } | Expression::ClassConstant { class, .. } => {
+                self.visit_class_reference(class)
+            }
+            Expression::Cast { target_type, expr } => {
+                self.resolve_type(target_type);
+                self.visit_expression(expr);
+            }
+            _ => visit::walk_expression(self, expr),
+        }
+    }
+}
+
+impl NameResolver {
+    fn visit_class_reference(&mut self, class: &mut Expression) {
+        match class {
+            Expression::Constant(name) => *name = self.resolve_class(name),
+            other => self.visit_expression(other),
+        }
+    }
+}
+
+/// Prefix a name with a namespace
+pub fn qualify(namespace: Option<&str>, name: &str) -> String {
+    match namespace {
+        Some(ns) if !ns.is_empty() => format!("{}\\{}", ns.trim_matches('\\'), name),
+        _ => name.to_string(),
+    }
+}
+
+/// Record declared function names for the unqualified-call fallback
+fn collect_declarations(nodes: &[AstNode], namespace: Option<&str>, resolver: &mut NameResolver) {
+    for node in nodes {
+        match node {
+            AstNode::Program(nodes) => collect_declarations(nodes, namespace, resolver),
+            AstNode::Namespace(ns) => collect_declarations(&ns.statements, ns.name.as_deref(), resolver),
+            AstNode::Function(function) => {
+                resolver.declared_functions.insert(qualify(namespace, &function.name).to_ascii_lowercase());
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{NamespaceDecl, UseClause, Visibility};
+
+    fn function(name: &str, body: Vec<Statement>) -> AstNode {
+        AstNode::Function(FunctionDecl {
+            name: name.to_string(),
+            parameters: vec![],
+            return_type: Some(Type::Object("Response".to_string())),
+            body: Box::new(Statement::Block(body)),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        })
+    }
+
+    fn call(name: &str) -> Statement {
+        Statement::Expression(Box::new(Expression::FunctionCall {
+            name: Box::new(Expression::Constant(name.to_string())),
+            arguments: vec![],
+        }))
+    }
+
+    #[test]
+    fn test_resolve_rules() {
+        let mut resolver = NameResolver {
+            namespace: Some("App".to_string()),
+            ..Default::default()
+        };
+        resolver.class_imports.insert("http".to_string(), "Vendor\\Http".to_string());
+        resolver.declared_functions.insert("app\\helper".to_string());
+
+        assert_eq!(resolver.resolve_class("User"), "App\\User");
+        assert_eq!(resolver.resolve_class("\\DateTime"), "DateTime");
+        assert_eq!(resolver.resolve_class("Http\\Request"), "Vendor\\Http\\Request");
+        assert_eq!(resolver.resolve_class("self"), "self");
+        assert_eq!(resolver.resolve_function("helper"), "App\\helper");
+        assert_eq!(resolver.resolve_function("strlen"), "strlen");
+        assert_eq!(resolver.resolve_constant("PHP_EOL"), "PHP_EOL");
+        assert_eq!(resolver.resolve_constant("namespace\\LIMIT"), "App\\LIMIT");
+    }
+
+    #[test]
+    fn test_resolve_names_in_namespace() {
+        let mut ast = vec![AstNode::Namespace(NamespaceDecl {
+            name: Some("App".to_string()),
+            statements: vec![
+                AstNode::Use(UseDecl {
+                    uses: vec![UseClause { name: "Vendor\\Http\\Response".to_string(), alias: None }],
+                    kind: UseKind::Normal,
+                }),
+                function("helper", vec![]),
+                function("main", vec![call("helper"), call("printf")]),
+            ],
+        })];
+
+        resolve_names(&mut ast);
+        let rendered = format!("{:?}", ast);
+        assert!(rendered.contains("name: \"App\\\\main\""));
+        assert!(rendered.contains("Constant(\"App\\\\helper\")"));
+        assert!(rendered.contains("Constant(\"printf\")"));
+        assert!(rendered.contains("Object(\"Vendor\\\\Http\\\\Response\")"));
+    }
+}
@@ -0,0 +1,431 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Objects of compiled classes.
+//!
+//! Every compiled class has an LLVM struct starting with a [`PhpObject`]
+//! header, followed by the declared properties of the class and its
+//! ancestors, and a constant [`PhpClass`] describing it. Generated code
+//! handles objects as `%php.object*` pointers, refcounted like strings and
+//! arrays; the class's `free` function runs the destructor and releases the
+//! properties before the memory is returned. `clone` copies an object's
+//! memory, after which the class's `clone` function adds references to the
+//! values the copy shares and runs `__clone`.
+//!
+//! Methods that may be overridden are called through the class's vtable, an
+//! array of function pointers in which a subclass keeps its parent's slots.
+//! Calls through an interface type use the method table the class provides
+//! for that interface, found with [`php_object_itable`]. Members of objects
+//! whose class is not known statically are found through the class's
+//! member tables, as described in [`crate::members`].
+
+use std::alloc::{alloc, alloc_zeroed, dealloc, Layout};
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+use std::sync::Mutex;
+use crate::exceptions::{throw_new, ERROR, EXCEPTION_CLASSES};
+use crate::generators::GENERATOR;
+use crate::members::{PhpMethod, PhpProperty};
+
+/// Class metadata emitted by the code generator
+#[repr(C)]
+#[derive(Debug)]
+pub struct PhpClass {
+    pub(crate) name: *const c_char,
+    pub(crate) size: u64,
+    pub(crate) parent: *const PhpClass,
+    pub(crate) free: Option<unsafe extern "C-unwind" fn(*mut PhpObject)>,
+    /// Completes a copy made by `clone`; objects of classes without one
+    /// cannot be cloned
+    pub(crate) clone: Option<unsafe extern "C-unwind" fn(*mut PhpObject)>,
+    pub(crate) vtable: *const *const c_void,
+    /// Method tables of the implemented interfaces, ended by a null interface
+    pub(crate) itables: *const PhpItable,
+    /// Public properties, ended by a null name
+    pub(crate) properties: *const PhpProperty,
+    /// Public methods, ended by a null name
+    pub(crate) methods: *const PhpMethod,
+}
+
+// Class metadata is immutable data in the program image
+unsafe impl Sync for PhpClass {}
+
+/// Interface metadata emitted by the code generator, identified by address
+#[repr(C)]
+#[derive(Debug)]
+pub struct PhpInterface {
+    pub(crate) name: *const c_char,
+}
+
+unsafe impl Sync for PhpInterface {}
+
+impl PhpInterface {
+    /// Name of the interface as declared
+    pub fn name(&self) -> &str {
+        unsafe { CStr::from_ptr(self.name) }.to_str().unwrap_or_default()
+    }
+}
+
+/// A class's methods for one interface, in the interface's method order
+#[repr(C)]
+#[derive(Debug)]
+pub struct PhpItable {
+    pub(crate) interface: *const PhpInterface,
+    pub(crate) methods: *const *const c_void,
+}
+
+unsafe impl Sync for PhpItable {}
+
+impl PhpClass {
+    /// Name of the class as declared
+    pub fn name(&self) -> &str {
+        unsafe { CStr::from_ptr(self.name) }.to_str().unwrap_or_default()
+    }
+
+    /// Parent class, if any
+    pub fn parent(&self) -> Option<&PhpClass> {
+        unsafe { self.parent.as_ref() }
+    }
+
+    /// Whether this class is `ancestor` or extends it
+    pub fn is_subclass_of(&self, ancestor: &PhpClass) -> bool {
+        let mut class = Some(self);
+        while let Some(c) = class {
+            if std::ptr::eq(c, ancestor) {
+                return true;
+            }
+            class = c.parent();
+        }
+        false
+    }
+
+    /// Method table of an interface the class implements
+    pub fn itable(&self, interface: &PhpInterface) -> Option<*const *const c_void> {
+        let mut itable = self.itables;
+        while let Some(entry) = unsafe { itable.as_ref() } {
+            if entry.interface.is_null() {
+                break;
+            }
+            if std::ptr::eq(entry.interface, interface) {
+                return Some(entry.methods);
+            }
+            itable = unsafe { itable.add(1) };
+        }
+        None
+    }
+    
+    fn layout(&self) -> Layout {
+        let size = (self.size as usize).max(std::mem::size_of::<PhpObject>());
+        Layout::from_size_align(size, std::mem::align_of::<PhpObject>()).expect("object size overflows")
+    }
+}
+
+/// Header at the start of every object
+#[repr(C)]
+#[derive(Debug)]
+pub struct PhpObject {
+    refcount: usize,
+    class: *const PhpClass,
+}
+
+impl PhpObject {
+    /// Class of the object
+    pub fn class(&self) -> &PhpClass {
+        unsafe { &*self.class }
+    }
+
+    /// Number of references to the object
+    pub fn refcount(&self) -> usize {
+        self.refcount
+    }
+}
+
+/// Classes registered by the program, in registration order
+static CLASSES: Mutex<Vec<&'static PhpClass>> = Mutex::new(Vec::new());
+
+/// Registered or builtin class with a name, compared case-insensitively
+pub fn find_class(name: &str) -> Option<&'static PhpClass> {
+    let name = name.trim_start_matches('\\');
+    let classes = CLASSES.lock().unwrap_or_else(|e| e.into_inner());
+    classes.iter()
+        .chain(EXCEPTION_CLASSES)
+        .copied()
+        .chain(std::iter::once(&GENERATOR))
+        .find(|class| class.name().eq_ignore_ascii_case(name))
+}
+
+// FFI functions called by generated code
+
+/// Make a class known to the runtime by name
+///
+/// # Safety
+///
+/// `class` must point to class metadata that lives for the rest of the
+/// program.
+#[no_mangle]
+pub unsafe extern "C" fn php_class_register(class: *const PhpClass) {
+    if let Some(class) = class.as_ref() {
+        CLASSES.lock().unwrap_or_else(|e| e.into_inner()).push(class);
+    }
+}
+
+/// Allocate a zeroed object of a class with one reference
+///
+/// Properties start as zero values; the code generator stores their
+/// defaults and runs the constructor.
+///
+/// # Safety
+///
+/// `class` must point to class metadata that outlives the object.
+#[no_mangle]
+pub unsafe extern "C" fn php_object_new(class: *const PhpClass) -> *mut PhpObject {
+    let layout = (*class).layout();
+    let object = alloc_zeroed(layout) as *mut PhpObject;
+    if object.is_null() {
+        std::alloc::handle_alloc_error(layout);
+    }
+    (*object).refcount = 1;
+    (*object).class = class;
+    object
+}
+
+/// # Safety
+///
+/// `o` must be null or a live object.
+#[no_mangle]
+pub unsafe extern "C" fn php_object_addref(o: *mut PhpObject) {
+    if let Some(o) = o.as_mut() {
+        o.refcount += 1;
+    }
+}
+
+/// Method table of an interface for a call through the interface type
+///
+/// An object whose class does not implement the interface is a fatal error.
+///
+/// # Safety
+///
+/// `o` must be a live object and `interface` must point to interface metadata.
+#[no_mangle]
+pub unsafe extern "C" fn php_object_itable(o: *mut PhpObject, interface: *const PhpInterface) -> *const *const c_void {
+    let class = (*o).class();
+    match class.itable(&*interface) {
+        Some(methods) => methods,
+        None => {
+            eprintln!(
+                "PHP Fatal error:  Class {} does not implement interface {}",
+                class.name(),
+                (*interface).name()
+            );
+            std::process::exit(255);
+        }
+    }
+}
+
+/// Whether an object is an instance of a class or of one of its subclasses
+///
+/// # Safety
+///
+/// `o` must be a live object and `class` must point to class metadata.
+#[no_mangle]
+pub unsafe extern "C" fn php_object_instanceof(o: *mut PhpObject, class: *const PhpClass) -> bool {
+    (*o).class().is_subclass_of(&*class)
+}
+
+/// Whether an object's class implements an interface
+///
+/// # Safety
+///
+/// `o` must be a live object and `interface` must point to interface metadata.
+#[no_mangle]
+pub unsafe extern "C" fn php_object_implements(o: *mut PhpObject, interface: *const PhpInterface) -> bool {
+    (*o).class().itable(&*interface).is_some()
+}
+
+/// `instanceof`: whether an object's class is a class, extends it, or
+/// implements an interface
+///
+/// `class_id` is the address of the class's or the interface's metadata,
+/// looked for along the parents and in the interface tables. A null object
+/// is an instance of nothing.
+///
+/// # Safety
+///
+/// `o` must be null or a live object.
+#[no_mangle]
+pub unsafe extern "C" fn php_instance_of(o: *mut PhpObject, class_id: *const c_void) -> bool {
+    let Some(o) = o.as_ref() else {
+        return false;
+    };
+    let mut class = Some(o.class());
+    while let Some(c) = class {
+        if std::ptr::eq(c as *const PhpClass as *const c_void, class_id) {
+            return true;
+        }
+        let mut itable = c.itables;
+        while let Some(entry) = itable.as_ref() {
+            if entry.interface.is_null() {
+                break;
+            }
+            if std::ptr::eq(entry.interface as *const c_void, class_id) {
+                return true;
+            }
+            itable = itable.add(1);
+        }
+        class = c.parent();
+    }
+    false
+}
+
+/// `clone`: a shallow copy of an object with one reference
+///
+/// Objects of classes without a `clone` function, such as exceptions and
+/// generators, throw an `Error`, as does a value that is not an object.
+///
+/// # Safety
+///
+/// `o` must be null or a live object.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_object_clone(o: *mut PhpObject) -> *mut PhpObject {
+    let Some(object) = o.as_ref() else {
+        throw_new(&ERROR, "__clone method called on non-object");
+    };
+    let class = object.class();
+    let Some(clone) = class.clone else {
+        throw_new(&ERROR, &format!("Trying to clone an uncloneable object of class {}", class.name()));
+    };
+    let layout = class.layout();
+    let copy = alloc(layout) as *mut PhpObject;
+    if copy.is_null() {
+        std::alloc::handle_alloc_error(layout);
+    }
+    std::ptr::copy_nonoverlapping(o as *const u8, copy as *mut u8, layout.size());
+    (*copy).refcount = 1;
+    clone(copy);
+    copy
+}
+
+/// Drop a reference, destroying the object with the last one
+///
+/// The object holds one reference while its class's `free` function runs,
+/// so the destructor can use `$this` without freeing it again. An exception
+/// thrown by the destructor unwinds through this function.
+///
+/// # Safety
+///
+/// `o` must be null or a live object; it must not be used after its last
+/// reference is released.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_object_release(o: *mut PhpObject) {
+    let Some(object) = o.as_mut() else {
+        return;
+    };
+    object.refcount -= 1;
+    if object.refcount > 0 {
+        return;
+    }
+    object.refcount = 1;
+    let class = object.class();
+    if let Some(free) = class.free {
+        free(o);
+    }
+    dealloc(o as *mut u8, class.layout());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static FREED: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe extern "C-unwind" fn free(o: *mut PhpObject) {
+        // The destructor may take and drop references of its own
+        php_object_addref(o);
+        php_object_release(o);
+        FREED.fetch_add((*o).refcount(), Ordering::SeqCst);
+    }
+
+    static CLONED: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe extern "C-unwind" fn cloned(o: *mut PhpObject) {
+        CLONED.fetch_add((*o).refcount(), Ordering::SeqCst);
+    }
+
+    static SHAPE: PhpInterface = PhpInterface { name: c"Shape".as_ptr() };
+    static COUNTABLE: PhpInterface = PhpInterface { name: c"Countable".as_ptr() };
+    static AREA: [unsafe extern "C-unwind" fn(*mut PhpObject); 1] = [free];
+    static ITABLES: [PhpItable; 2] = [
+        PhpItable { interface: &SHAPE, methods: AREA.as_ptr() as *const *const c_void },
+        PhpItable { interface: std::ptr::null(), methods: std::ptr::null() },
+    ];
+
+    static BASE: PhpClass = PhpClass {
+        name: c"Base".as_ptr(),
+        size: 16,
+        parent: std::ptr::null(),
+        free: None,
+        clone: None,
+        vtable: std::ptr::null(),
+        itables: std::ptr::null(),
+        properties: std::ptr::null(),
+        methods: std::ptr::null(),
+    };
+    static POINT: PhpClass = PhpClass {
+        name: c"App\\Point".as_ptr(),
+        size: 32,
+        parent: &BASE,
+        free: Some(free),
+        clone: Some(cloned),
+        vtable: AREA.as_ptr() as *const *const c_void,
+        itables: ITABLES.as_ptr(),
+        properties: std::ptr::null(),
+        methods: std::ptr::null(),
+    };
+
+    #[test]
+    fn test_object_lifetime() {
+        unsafe {
+            php_class_register(&POINT);
+            let point = find_class("\\app\\point").unwrap();
+            assert!(point.is_subclass_of(&BASE));
+            assert!(!BASE.is_subclass_of(point));
+            assert_eq!(point.itable(&SHAPE), Some(AREA.as_ptr() as *const *const c_void));
+            assert_eq!(point.itable(&COUNTABLE), None);
+            assert_eq!(BASE.itable(&SHAPE), None);
+            assert!(find_class("RuntimeException").unwrap().parent().is_some());
+
+            let object = php_object_new(point);
+            assert_eq!((*object).class().name(), "App\\Point");
+            assert!(php_object_instanceof(object, &BASE));
+            assert!(php_object_implements(object, &SHAPE));
+            assert!(!php_object_implements(object, &COUNTABLE));
+            assert!(php_instance_of(object, &BASE as *const PhpClass as *const c_void));
+            assert!(php_instance_of(object, &SHAPE as *const PhpInterface as *const c_void));
+            assert!(!php_instance_of(object, &COUNTABLE as *const PhpInterface as *const c_void));
+            assert!(!php_instance_of(std::ptr::null_mut(), &BASE as *const PhpClass as *const c_void));
+            php_object_addref(object);
+            php_object_release(object);
+            assert_eq!(FREED.load(Ordering::SeqCst), 0);
+            let copy = php_object_clone(object);
+            assert_eq!(CLONED.load(Ordering::SeqCst), 1);
+            assert!(std::ptr::eq((*copy).class(), point));
+            php_object_release(copy);
+            php_object_release(object);
+            assert_eq!(FREED.load(Ordering::SeqCst), 2);
+        }
+    }
+}
@@ -0,0 +1,378 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! PHPDoc type extraction.
+//!
+//! Reads `@param`, `@return`, `@var` and `@template` tags from docblocks so
+//! that untyped legacy code still gets concrete types. Declared types always
+//! take precedence over documented ones.
+
+use log::debug;
+use crate::ast::{AstNode, ClassDecl, FunctionDecl, PropertyDecl};
+use crate::types::Type;
+
+/// Template parameter declared with `@template T [of Bound]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateTag {
+    pub name: String,
+    pub bound: Option<Type>,
+}
+
+/// Types extracted from a docblock
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocBlock {
+    /// `@param` types keyed by parameter name (without `$`)
+    pub params: Vec<(String, Type)>,
+    pub return_type: Option<Type>,
+    /// `@var` type with the optional variable name
+    pub var: Option<(Option<String>, Type)>,
+    pub templates: Vec<TemplateTag>,
+}
+
+impl DocBlock {
+    /// Parse a `/** ... */` comment; unknown tags and malformed types are skipped
+    pub fn parse(comment: &str) -> Self {
+        let mut doc = DocBlock::default();
+        let lines: Vec<&str> = comment.lines().map(clean_line).collect();
+
+        // Templates first so that other tags can refer to them
+        for line in &lines {
+            if let Some(rest) = tag(line, &["@template", "@psalm-template", "@phpstan-template"]) {
+                let mut words = rest.split_whitespace();
+                if let Some(name) = words.next() {
+                    let bound = match words.next() {
+                        Some("of") | Some("as") => words.next().and_then(|b| parse_doc_type(b, &doc.templates)),
+                        _ => None,
+                    };
+                    doc.templates.push(TemplateTag { name: name.to_string(), bound });
+                }
+            }
+        }
+
+        for line in &lines {
+            if let Some(rest) = tag(line, &["@param", "@psalm-param", "@phpstan-param"]) {
+                let (type_str, rest) = split_type(rest);
+                let name = rest.split_whitespace()
+                    .next()
+                    .map(|n| n.trim_start_matches("...").trim_start_matches('&'))
+                    .and_then(|n| n.strip_prefix('$'));
+                if let (Some(name), Some(typ)) = (name, parse_doc_type(type_str, &doc.templates)) {
+                    doc.params.retain(|(existing, _)| existing != name);
+                    doc.params.push((name.to_string(), typ));
+                }
+            } else if let Some(rest) = tag(line, &["@return", "@psalm-return", "@phpstan-return"]) {
+                let (type_str, _) = split_type(rest);
+                if let Some(typ) = parse_doc_type(type_str, &doc.templates) {
+                    doc.return_type = Some(typ);
+                }
+            } else if let Some(rest) = tag(line, &["@var", "@psalm-var", "@phpstan-var"]) {
+                let (type_str, rest) = split_type(rest);
+                let name = rest.split_whitespace()
+                    .next()
+                    .and_then(|n| n.strip_prefix('$'))
+                    .map(|n| n.to_string());
+                if let Some(typ) = parse_doc_type(type_str, &doc.templates) {
+                    doc.var = Some((name, typ));
+                }
+            }
+        }
+
+        doc
+    }
+
+    /// Documented type of a parameter
+    pub fn param_type(&self, name: &str) -> Option<&Type> {
+        let name = name.trim_start_matches('$');
+        self.params.iter().find(|(n, _)| n == name).map(|(_, t)| t)
+    }
+}
+
+/// Parse a PHPDoc type expression (`int|null`, `?Foo`, `array<string, int>`, `Foo[]`, ...)
+pub fn parse_doc_type(input: &str, templates: &[TemplateTag]) -> Option<Type> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+
+    if let Some(inner) = input.strip_prefix('?') {
+        return parse_doc_type(inner, templates).map(|t| union(vec![t, Type::Null]));
+    }
+
+    let alternatives = split_top_level(input, '|');
+    if alternatives.len() > 1 {
+        let types = alternatives.iter()
+            .map(|alt| parse_doc_type(alt, templates))
+            .collect::<Option<Vec<_>>>()?;
+        return Some(union(types));
+    }
+
+    if let Some(inner) = input.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        return parse_doc_type(inner, templates);
+    }
+
+    let members = split_top_level(input, '&');
+    if members.len() > 1 {
+        let types = members.iter()
+            .map(|member| parse_doc_type(member, templates))
+            .collect::<Option<Vec<_>>>()?;
+        return Some(Type::Intersection(types));
+    }
+
+    if let Some(element) = input.strip_suffix("[]") {
+        return parse_doc_type(element, templates).map(|t| Type::Array(Box::new(t)));
+    }
+
+    if let Some(open) = input.find('<') {
+        let close = input.rfind('>')?;
+        let base = &input[..open];
+        let args = split_top_level(&input[open + 1..close], ',')
+            .iter()
+            .map(|arg| parse_doc_type(arg, templates))
+            .collect::<Option<Vec<_>>>()?;
+        return Some(generic(base, args));
+    }
+
+    let lower = input.to_ascii_lowercase();
+    let typ = match lower.as_str() {
+        "int" | "integer" | "positive-int" | "negative-int" | "non-negative-int" => Type::Int,
+        "float" | "double" => Type::Float,
+        "bool" | "boolean" | "true" | "false" => Type::Bool,
+        "string" | "non-empty-string" | "class-string" | "numeric-string" => Type::String,
+        "null" | "void" => Type::Null,
+        "array" | "list" | "iterable" => Type::Array(Box::new(Type::Unknown)),
+        "callable" | "closure" => Type::Function(vec![], Box::new(Type::Unknown)),
+        "mixed" | "resource" | "scalar" | "numeric" | "object" => Type::Unknown,
+        _ if templates.iter().any(|t| t.name == input) => Type::Generic(input.to_string(), vec![]),
+        _ if is_class_name(input) => Type::Object(input.trim_start_matches('\\').to_string()),
+        _ => return None,
+    };
+    Some(typ)
+}
+
+/// Fill missing declared types from docblocks throughout an AST
+pub fn apply(ast: &mut [AstNode]) {
+    for node in ast {
+        match node {
+            AstNode::Program(nodes) => apply(nodes),
+            AstNode::Namespace(ns) => apply(&mut ns.statements),
+            AstNode::Function(function) => apply_to_function(function),
+            AstNode::Class(class) => apply_to_class(class),
+            AstNode::Interface(interface) => interface.methods.iter_mut().for_each(apply_to_function),
+            AstNode::Trait(t) => {
+                t.methods.iter_mut().for_each(apply_to_function);
+                t.properties.iter_mut().for_each(apply_to_property);
+            }
+            AstNode::Enum(e) => e.methods.iter_mut().for_each(apply_to_function),
+            _ => {}
+        }
+    }
+}
+
+/// Fill missing parameter and return types of a function from its docblock
+pub fn apply_to_function(function: &mut FunctionDecl) {
+    let Some(comment) = &function.doc_comment else {
+        return;
+    };
+    let doc = DocBlock::parse(comment);
+
+    for param in &mut function.parameters {
+        if param.typ.is_none() {
+            if let Some(typ) = doc.param_type(&param.name) {
+                debug!("{}(): ${} typed as {} from PHPDoc", function.name, param.name, typ);
+                param.typ = Some(typ.clone());
+            }
+        }
+    }
+    if function.return_type.is_none() {
+        function.return_type = doc.return_type;
+    }
+}
+
+/// Fill missing types of a class's methods and properties
+pub fn apply_to_class(class: &mut ClassDecl) {
+    class.methods.iter_mut().for_each(apply_to_function);
+    class.properties.iter_mut().for_each(apply_to_property);
+}
+
+/// Fill a missing property type from its `@var` tag
+pub fn apply_to_property(property: &mut PropertyDecl) {
+    if property.typ.is_some() {
+        return;
+    }
+    if let Some(comment) = &property.doc_comment {
+        property.typ = DocBlock::parse(comment).var.map(|(_, typ)| typ);
+    }
+}
+
+/// Strip comment delimiters and leading `*` from a docblock line
+fn clean_line(line: &str) -> &str {
+    let line = line.trim();
+    let line = line.strip_prefix("/**").unwrap_or(line);
+    let line = line.strip_suffix("*/").unwrap_or(line);
+    line.trim_start_matches('*').trim()
+}
+
+/// Match a line against tag names and return the rest of the line
+fn tag<'a>(line: &'a str, names: &[&str]) -> Option<&'a str> {
+    names.iter().find_map(|name| {
+        let rest = line.strip_prefix(name)?;
+        (rest.is_empty() || rest.starts_with(char::is_whitespace)).then(|| rest.trim_start())
+    })
+}
+
+/// Split the leading type expression (which may contain spaces inside `<>`) from the rest
+fn split_type(text: &str) -> (&str, &str) {
+    let mut depth = 0i32;
+    for (i, c) in text.char_indices() {
+        match c {
+            '<' | '(' | '{' => depth += 1,
+            '>' | ')' | '}' => depth -= 1,
+            c if c.is_whitespace() && depth <= 0 => return (&text[..i], text[i..].trim_start()),
+            _ => {}
+        }
+    }
+    (text, "")
+}
+
+/// Split on a separator outside of `<>`/`()` nesting
+fn split_top_level(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '<' | '(' | '{' => depth += 1,
+            '>' | ')' | '}' => depth -= 1,
+            c if c == separator && depth == 0 => {
+                parts.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(text[start..].trim());
+    parts
+}
+
+fn generic(base: &str, mut args: Vec<Type>) -> Type {
+    match (base.to_ascii_lowercase().as_str(), args.len()) {
+        ("array" | "list" | "non-empty-array" | "non-empty-list" | "iterable", 1) => {
+            Type::Array(Box::new(args.remove(0)))
+        }
+        ("array" | "non-empty-array" | "iterable", 2) => {
+            let value = args.remove(1);
+            match args[0] {
+                Type::String => Type::AssociativeArray(Box::new(value)),
+                _ => Type::Array(Box::new(value)),
+            }
+        }
+        _ => Type::Generic(base.trim_start_matches('\\').to_string(), args),
+    }
+}
+
+fn union(types: Vec<Type>) -> Type {
+    let mut flat: Vec<Type> = Vec::new();
+    for typ in types {
+        let members = match typ {
+            Type::Union(members) => members,
+            other => vec![other],
+        };
+        for member in members {
+            if !flat.contains(&member) {
+                flat.push(member);
+            }
+        }
+    }
+    if flat.len() == 1 {
+        flat.remove(0)
+    } else {
+        Type::Union(flat)
+    }
+}
+
+fn is_class_name(name: &str) -> bool {
+    let name = name.trim_start_matches('\\');
+    !name.is_empty()
+        && name.split('\\').all(|part| {
+            let mut chars = part.chars();
+            matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+                && chars.all(|c| c.is_alphanumeric() || c == '_')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_doc_types() {
+        assert_eq!(parse_doc_type("int", &[]), Some(Type::Int));
+        assert_eq!(parse_doc_type("?string", &[]), Some(Type::Union(vec![Type::String, Type::Null])));
+        assert_eq!(parse_doc_type("int[]", &[]), Some(Type::Array(Box::new(Type::Int))));
+        assert_eq!(
+            parse_doc_type("array<string, \\App\\User>", &[]),
+            Some(Type::AssociativeArray(Box::new(Type::Object("App\\User".to_string()))))
+        );
+        assert_eq!(parse_doc_type("int|float|int", &[]), Some(Type::Union(vec![Type::Int, Type::Float])));
+        assert_eq!(
+            parse_doc_type("(Countable&ArrayAccess)|null", &[]),
+            Some(Type::Union(vec![
+                Type::Intersection(vec![
+                    Type::Object("Countable".to_string()),
+                    Type::Object("ArrayAccess".to_string()),
+                ]),
+                Type::Null,
+            ]))
+        );
+        assert_eq!(parse_doc_type("not a type!", &[]), None);
+    }
+
+    #[test]
+    fn test_parse_docblock() {
+        let doc = DocBlock::parse(
+            "/**\n * Map items.\n *\n * @template T of Countable\n * @param array<int, T> $items the items\n * @param callable $fn\n * @return list<T>\n */",
+        );
+        assert_eq!(doc.templates[0].bound, Some(Type::Object("Countable".to_string())));
+        let t = Type::Generic("T".to_string(), vec![]);
+        assert_eq!(doc.param_type("items"), Some(&Type::Array(Box::new(t.clone()))));
+        assert!(doc.param_type("$fn").is_some());
+        assert_eq!(doc.return_type, Some(Type::Array(Box::new(t))));
+    }
+
+    #[test]
+    fn test_apply_to_function() {
+        use crate::ast::{Parameter, Statement, Visibility};
+
+        let mut function = FunctionDecl {
+            name: "add".to_string(),
+            parameters: vec![
+                Parameter { name: "a".to_string(), typ: None, default_value: None, is_reference: false, is_variadic: false },
+                Parameter { name: "b".to_string(), typ: Some(Type::Float), default_value: None, is_reference: false, is_variadic: false },
+            ],
+            return_type: None,
+            body: Box::new(Statement::Block(vec![])),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: Some("/** @param int $a\n * @param int $b\n * @return int */".to_string()),
+        };
+
+        apply_to_function(&mut function);
+        assert_eq!(function.parameters[0].typ, Some(Type::Int));
+        assert_eq!(function.parameters[1].typ, Some(Type::Float));
+        assert_eq!(function.return_type, Some(Type::Int));
+    }
+}
@@ -0,0 +1,527 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `.phpt` tests, run by `php2ir test`.
+//!
+//! A test file is split into sections by `--NAME--` lines, as in php-src:
+//!
+//! ```text
+//! --TEST--
+//! strlen() of a multibyte string
+//! --SKIPIF--
+//! <?php if (PHP_INT_SIZE < 8) echo "skip 64-bit only"; ?>
+//! --FILE--
+//! <?php echo strlen("héllo"), "\n";
+//! --EXPECT--
+//! 6
+//! ```
+//!
+//! `FILE` is compiled to a native binary and run from the test's directory;
+//! its output must equal `EXPECT`, or match `EXPECTF`, whose placeholders
+//! are those of php-src (`%s`, `%d`, `%a`, ...). Both sides are compared
+//! with line endings normalized and surrounding whitespace trimmed.
+//! `SKIPIF` runs in the interpreter first, and output starting with `skip`
+//! skips the test. A test with a section this runner does not know is
+//! skipped rather than run without it.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use regex::Regex;
+use crate::compiler::{Compiler, CompilerOptions};
+use crate::interp::{load_source, Interpreter};
+use crate::link::executable_path;
+use crate::runtime::OutputBuffer;
+use crate::utils::time::format_duration;
+
+/// Extension of test files
+pub const PHPT_EXTENSION: &str = "phpt";
+
+/// Output a test must produce
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expectation {
+    /// `EXPECT`: the exact output
+    Exact(String),
+
+    /// `EXPECTF`: the output with `%` placeholders
+    Format(String),
+}
+
+impl Expectation {
+    /// Whether a program's output meets the expectation
+    pub fn matches(&self, output: &str) -> bool {
+        let output = normalize(output);
+        match self {
+            Expectation::Exact(expected) => normalize(expected) == output,
+            Expectation::Format(format) => format_regex(&normalize(format)).is_match(&output),
+        }
+    }
+
+    /// Expected text, as shown in a diff
+    pub fn text(&self) -> &str {
+        match self {
+            Expectation::Exact(text) | Expectation::Format(text) => text,
+        }
+    }
+}
+
+/// Test parsed from a `.phpt` file
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhptTest {
+    pub path: PathBuf,
+
+    /// The `TEST` line
+    pub title: String,
+
+    /// PHP source of the program under test
+    pub file: String,
+
+    pub expect: Expectation,
+
+    /// PHP source deciding whether to skip the test
+    pub skipif: Option<String>,
+
+    /// Sections present but not supported, which make the test skip
+    pub unsupported: Vec<String>,
+}
+
+impl PhptTest {
+    /// Read and parse a test file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::parse(path, &source)
+    }
+
+    /// Parse the contents of a test file
+    pub fn parse(path: &Path, source: &str) -> Result<Self, String> {
+        let mut sections: Vec<(String, String)> = Vec::new();
+        for line in source.lines() {
+            let header = line.strip_prefix("--")
+                .and_then(|rest| rest.strip_suffix("--"))
+                .filter(|name| !name.is_empty() && name.chars().all(|c| c.is_ascii_uppercase() || c == '_'));
+            match (header, sections.last_mut()) {
+                (Some(name), _) => sections.push((name.to_string(), String::new())),
+                (None, Some((_, text))) => {
+                    text.push_str(line);
+                    text.push('\n');
+                }
+                (None, None) => return Err("expected a --TEST-- section first".to_string()),
+            }
+        }
+
+        let mut title = None;
+        let mut file = None;
+        let mut expect = None;
+        let mut skipif = None;
+        let mut unsupported = Vec::new();
+        for (name, text) in sections {
+            let duplicate = match name.as_str() {
+                "TEST" => title.replace(text.trim().to_string()).is_some(),
+                "FILE" => file.replace(text).is_some(),
+                "EXPECT" => expect.replace(Expectation::Exact(text)).is_some(),
+                "EXPECTF" => expect.replace(Expectation::Format(text)).is_some(),
+                "SKIPIF" => skipif.replace(text).is_some(),
+                _ => {
+                    unsupported.push(name);
+                    false
+                }
+            };
+            if duplicate {
+                return Err("duplicate or conflicting sections".to_string());
+            }
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            title: title.ok_or("missing --TEST-- section")?,
+            file: file.ok_or("missing --FILE-- section")?,
+            expect: expect.ok_or("missing --EXPECT-- or --EXPECTF-- section")?,
+            skipif,
+            unsupported,
+        })
+    }
+}
+
+/// How a test ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Passed,
+
+    /// The output did not meet the expectation
+    Failed { diff: String },
+
+    Skipped(String),
+
+    /// The test could not be run: an invalid file, or a compilation error
+    Broken(String),
+}
+
+/// Outcome of one test file
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub path: PathBuf,
+    pub title: String,
+    pub outcome: Outcome,
+    pub duration: Duration,
+}
+
+impl fmt::Display for TestResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match &self.outcome {
+            Outcome::Passed => "PASS",
+            Outcome::Failed { .. } => "FAIL",
+            Outcome::Skipped(_) => "SKIP",
+            Outcome::Broken(_) => "BORK",
+        };
+        write!(f, "{} {} [{}]", status, self.title, self.path.display())?;
+        match &self.outcome {
+            Outcome::Skipped(reason) | Outcome::Broken(reason) if !reason.is_empty() => write!(f, ": {}", reason),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Totals of a test run
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub results: Vec<TestResult>,
+    pub duration: Duration,
+}
+
+impl Summary {
+    /// Whether every test passed or was skipped
+    pub fn success(&self) -> bool {
+        self.results.iter().all(|result| matches!(result.outcome, Outcome::Passed | Outcome::Skipped(_)))
+    }
+
+    fn count(&self, matches: fn(&Outcome) -> bool) -> usize {
+        self.results.iter().filter(|result| matches(&result.outcome)).count()
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            if let Outcome::Failed { diff } = &result.outcome {
+                writeln!(f, "\n{}\n{}", result, diff)?;
+            }
+        }
+        writeln!(
+            f,
+            "\n{} tests: {} passed, {} failed, {} skipped, {} broken in {}",
+            self.results.len(),
+            self.count(|o| matches!(o, Outcome::Passed)),
+            self.count(|o| matches!(o, Outcome::Failed { .. })),
+            self.count(|o| matches!(o, Outcome::Skipped(_))),
+            self.count(|o| matches!(o, Outcome::Broken(_))),
+            format_duration(self.duration)
+        )
+    }
+}
+
+/// Runs `.phpt` files on several threads
+#[derive(Debug, Clone)]
+pub struct TestRunner {
+    work_dir: PathBuf,
+    jobs: usize,
+    timeout: Duration,
+}
+
+impl TestRunner {
+    /// Runner compiling tests in `work_dir`, one job per CPU
+    pub fn new(work_dir: &Path) -> Self {
+        let jobs = std::thread::available_parallelism().map(usize::from).unwrap_or(1);
+        Self { work_dir: work_dir.to_path_buf(), jobs, timeout: Duration::from_secs(60) }
+    }
+
+    /// Number of tests run at once
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Time after which a test program is killed and the test fails
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run `tests`, calling `report` as each one finishes
+    ///
+    /// The results of the summary are in the order of `tests`.
+    pub fn run(&self, tests: &[PathBuf], mut report: impl FnMut(&TestResult)) -> Summary {
+        let start = Instant::now();
+        let next = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+        let mut results: Vec<Option<TestResult>> = vec![None; tests.len()];
+        std::thread::scope(|scope| {
+            for _ in 0..self.jobs.min(tests.len()) {
+                let sender = sender.clone();
+                let next = &next;
+                scope.spawn(move || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = tests.get(index) else { break };
+                    if sender.send((index, self.run_test(index, path))).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(sender);
+            for (index, result) in receiver {
+                report(&result);
+                results[index] = Some(result);
+            }
+        });
+        Summary { results: results.into_iter().flatten().collect(), duration: start.elapsed() }
+    }
+
+    /// Run one test; `index` keeps its files apart from those of other tests
+    fn run_test(&self, index: usize, path: &Path) -> TestResult {
+        let start = Instant::now();
+        let (title, outcome) = match PhptTest::load(path) {
+            Ok(test) => {
+                let outcome = self.outcome(index, &test);
+                (test.title, outcome)
+            }
+            Err(e) => (String::new(), Outcome::Broken(e)),
+        };
+        TestResult { path: path.to_path_buf(), title, outcome, duration: start.elapsed() }
+    }
+
+    fn outcome(&self, index: usize, test: &PhptTest) -> Outcome {
+        if !test.unsupported.is_empty() {
+            return Outcome::Skipped(format!("unsupported section(s) {}", test.unsupported.join(", ")));
+        }
+        if let Some(skipif) = &test.skipif {
+            match skip_reason(skipif) {
+                Ok(Some(reason)) => return Outcome::Skipped(reason),
+                Ok(None) => {}
+                Err(e) => return Outcome::Broken(format!("SKIPIF: {}", e)),
+            }
+        }
+
+        let stem = test.path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let dir = self.work_dir.join(format!("{}-{}", index, stem));
+        let binary = match self.compile(test, &dir) {
+            Ok(binary) => binary,
+            Err(e) => return Outcome::Broken(e),
+        };
+        let cwd = test.path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let output = match run_with_timeout(Command::new(&binary).current_dir(cwd), self.timeout) {
+            Ok(output) => output,
+            Err(e) => return Outcome::Failed { diff: e },
+        };
+        if test.expect.matches(&output) {
+            Outcome::Passed
+        } else {
+            Outcome::Failed { diff: diff(&normalize(test.expect.text()), &normalize(&output)) }
+        }
+    }
+
+    /// Compile the test's program in `dir`, returning the binary
+    fn compile(&self, test: &PhptTest, dir: &Path) -> Result<PathBuf, String> {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let input = dir.join("test.php");
+        std::fs::write(&input, &test.file).map_err(|e| e.to_string())?;
+        let options = CompilerOptions {
+            input,
+            output: dir.join("test"),
+            ..CompilerOptions::default()
+        };
+        let mut compiler = Compiler::new(options).map_err(|e| e.to_string())?;
+        compiler.compile().map_err(|e| e.to_string())?;
+        let options = compiler.options();
+        Ok(executable_path(&options.output, &options.resolved_target()))
+    }
+}
+
+/// Test files under `dir`, sorted
+pub fn discover(dir: &Path) -> Vec<PathBuf> {
+    let mut tests: Vec<PathBuf> = walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .map(walkdir::DirEntry::into_path)
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == PHPT_EXTENSION))
+        .collect();
+    tests.sort();
+    tests
+}
+
+/// Run a `SKIPIF` section in the interpreter; output starting with `skip`
+/// gives the reason to skip
+fn skip_reason(source: &str) -> Result<Option<String>, String> {
+    let ast = load_source(source).map_err(|e| e.to_string())?;
+    let output = OutputBuffer::new();
+    Interpreter::new()
+        .map(|interpreter| interpreter.with_output(Box::new(output.clone())))
+        .and_then(|mut interpreter| interpreter.run(&ast))
+        .map_err(|e| e.to_string())?;
+    let output = output.contents();
+    let output = output.trim();
+    Ok(output.get(..4)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("skip"))
+        .map(|_| output[4..].trim_start_matches([':', ' ']).trim().to_string()))
+}
+
+/// Run a test program, killing it after `timeout`; returns its stdout, or
+/// why it did not exit normally
+fn run_with_timeout(command: &mut Command, timeout: Duration) -> Result<String, String> {
+    let mut child = command.stdout(Stdio::piped()).stderr(Stdio::null()).spawn()
+        .map_err(|e| format!("cannot run the test program: {}", e))?;
+    let mut stdout = child.stdout.take().ok_or("no stdout")?;
+    let reader = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        std::io::Read::read_to_end(&mut stdout, &mut output).map(|_| output)
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(status) => break Some(status),
+            None if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+            None => std::thread::sleep(Duration::from_millis(10)),
+        }
+    };
+    let output = reader.join().map_err(|_| "cannot read the test's output")?.map_err(|e| e.to_string())?;
+    let output = String::from_utf8_lossy(&output).into_owned();
+    match status {
+        None => Err(format!("timed out after {}", format_duration(timeout))),
+        Some(status) if status.code().is_none() => Err(format!("{} after output:\n{}", status, output)),
+        Some(_) => Ok(output),
+    }
+}
+
+/// Output with `\r\n` line endings turned into `\n` and surrounding
+/// whitespace removed
+fn normalize(output: &str) -> String {
+    output.replace("\r\n", "\n").trim().to_string()
+}
+
+/// Regex matching the output an `EXPECTF` section describes
+fn format_regex(format: &str) -> Regex {
+    let mut pattern = String::from("(?s)^");
+    let mut literal = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            literal.push(c);
+            continue;
+        }
+        let placeholder = match chars.next() {
+            Some('e') => regex::escape(std::path::MAIN_SEPARATOR_STR),
+            Some('s') => r"[^\r\n]+".to_string(),
+            Some('S') => r"[^\r\n]*".to_string(),
+            Some('a') => ".+".to_string(),
+            Some('A') => ".*".to_string(),
+            Some('w') => r"\s*".to_string(),
+            Some('i') => r"[+-]?\d+".to_string(),
+            Some('d') => r"\d+".to_string(),
+            Some('x') => "[0-9a-fA-F]+".to_string(),
+            Some('f') => r"[+-]?\.?\d+\.?\d*(?:[Ee][+-]?\d+)?".to_string(),
+            Some('c') => ".".to_string(),
+            Some('%') => {
+                literal.push('%');
+                continue;
+            }
+            other => {
+                literal.push('%');
+                literal.extend(other);
+                continue;
+            }
+        };
+        pattern.push_str(&regex::escape(&literal));
+        literal.clear();
+        pattern.push_str(&placeholder);
+    }
+    pattern.push_str(&regex::escape(&literal));
+    pattern.push('$');
+    // Every literal part is escaped, so the pattern is valid
+    Regex::new(&pattern).unwrap_or_else(|_| Regex::new("$^").unwrap())
+}
+
+/// Line diff of the expected and actual output: `-` lines are only
+/// expected, `+` lines only produced
+pub(crate) fn diff(expected: &str, actual: &str) -> String {
+    let a: Vec<&str> = expected.lines().collect();
+    let b: Vec<&str> = actual.lines().collect();
+    // Longest common subsequence lengths of the suffixes
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            out.push_str(&format!("  {}\n", a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push_str(&format!("- {}\n", a[i]));
+            i += 1;
+        } else {
+            out.push_str(&format!("+ {}\n", b[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_phpt() {
+        let source = "--TEST--\nAddition\n--SKIPIF--\n<?php echo 'skip'; ?>\n--FILE--\n<?php\necho 1 + 2, \"\\n\";\n--EXPECTF--\n%d\n";
+        let test = PhptTest::parse(Path::new("tests/add.phpt"), source).unwrap();
+        assert_eq!(test.title, "Addition");
+        assert_eq!(test.file, "<?php\necho 1 + 2, \"\\n\";\n");
+        assert_eq!(test.expect, Expectation::Format("%d\n".to_string()));
+        assert!(test.skipif.is_some());
+        assert!(test.unsupported.is_empty());
+
+        let test = PhptTest::parse(Path::new("ini.phpt"), "--TEST--\nt\n--INI--\nx=1\n--FILE--\n<?php\n--EXPECT--\n").unwrap();
+        assert_eq!(test.unsupported, ["INI"]);
+        assert!(PhptTest::parse(Path::new("bad.phpt"), "--TEST--\nt\n--FILE--\n<?php\n").is_err());
+        assert!(PhptTest::parse(Path::new("bad.phpt"), "<?php\n--TEST--\n").is_err());
+    }
+
+    #[test]
+    fn test_expectations() {
+        assert!(Expectation::Exact("a\nb\n".to_string()).matches("a\r\nb\r\n\n"));
+        assert!(!Expectation::Exact("a\nb".to_string()).matches("a\nc"));
+
+        let format = Expectation::Format("int(%d)\nfloat(%f) in %s on line %i\n100%% %c".to_string());
+        assert!(format.matches("int(42)\nfloat(-1.5e3) in /tmp/a.php on line 7\n100% x"));
+        assert!(!format.matches("int(x)\nfloat(1.0) in a on line 1\n100% x"));
+        assert!(Expectation::Format("[%a]".to_string()).matches("[a\nb]"));
+        assert!(!Expectation::Format("[%s]".to_string()).matches("[a\nb]"));
+        assert!(Expectation::Format("a.b%w(c)".to_string()).matches("a.b (c)"));
+        assert!(!Expectation::Format("a.b".to_string()).matches("axb"));
+
+        assert_eq!(diff("a\nb\nc", "a\nx\nc"), "  a\n- b\n+ x\n  c\n");
+    }
+}
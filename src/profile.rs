@@ -0,0 +1,344 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Profile-guided optimization.
+//!
+//! With `--pgo-gen`, LLVM's `pgo-instr-gen` and `instrprof` passes add block
+//! counters to every function, and a constructor appended to each module
+//! hands the bounds of the counter sections to the runtime. At exit the
+//! runtime writes them in LLVM's raw profile format (version 8, as written
+//! by LLVM 14 to 17) to `default.profraw`, or to `$LLVM_PROFILE_FILE` with
+//! `%p` replaced by the process id, so compiler-rt is not needed:
+//!
+//! ```text
+//! php2ir --pgo-gen app.php -o app && ./app
+//! llvm-profdata merge -o app.profdata default.profraw
+//! php2ir --pgo-use app.profdata app.php -o app
+//! ```
+//!
+//! With `--pgo-use`, `pgo-instr-use` reads the merged profile before the
+//! optimization pipeline runs. Counter sections are located through the
+//! linker's `__start_`/`__stop_` symbols, so instrumentation needs an ELF
+//! target.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use crate::compiler::CompilerOptions;
+use crate::error::{CompileError, CompileResult};
+use crate::target::ObjectFormat;
+
+/// Profile-guided optimization stage of a build
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Profile {
+    #[default]
+    None,
+
+    /// Instrument the program to record a profile when it runs
+    Generate,
+
+    /// Optimize with a profile merged by `llvm-profdata`
+    Use(PathBuf),
+}
+
+impl Profile {
+    /// Stage selected by `--pgo-gen` and `--pgo-use`
+    pub fn from_options(options: &CompilerOptions) -> CompileResult<Self> {
+        match (options.pgo_gen, &options.pgo_use) {
+            (true, Some(_)) => Err(CompileError::Configuration(
+                "--pgo-gen and --pgo-use cannot be combined".to_string(),
+            )),
+            (true, None) => {
+                let target = options.resolved_target();
+                if target.object_format() != ObjectFormat::Elf {
+                    return Err(CompileError::Configuration(format!(
+                        "--pgo-gen is not supported for {}: the profile runtime needs an ELF target",
+                        target.triple()
+                    )));
+                }
+                Ok(Profile::Generate)
+            }
+            (false, Some(path)) if !path.is_file() => Err(CompileError::Configuration(format!(
+                "profile {} not found; merge the raw profiles with `llvm-profdata merge -o {} *.profraw`",
+                path.display(),
+                path.display()
+            ))),
+            (false, Some(path)) => Ok(Profile::Use(path.clone())),
+            (false, None) => Ok(Profile::None),
+        }
+    }
+
+    /// Passes to run ahead of the `default<...>` pipeline, comma-terminated
+    pub fn passes(&self) -> &'static str {
+        match self {
+            Profile::None => "",
+            Profile::Generate => "pgo-instr-gen,instrprof,",
+            Profile::Use(_) => "pgo-instr-use,",
+        }
+    }
+
+    /// LLVM command-line options the passes read
+    ///
+    /// Value profiling is left out of instrumented builds; its records need
+    /// compiler-rt's allocator.
+    pub fn llvm_options(&self) -> Vec<String> {
+        match self {
+            Profile::None => Vec::new(),
+            Profile::Generate => vec!["-disable-vp".to_string()],
+            Profile::Use(path) => vec![format!("-pgo-test-profile-file={}", path.display())],
+        }
+    }
+
+    /// `ir` with, when instrumenting, the constructor registering its counters
+    pub fn prepare_ir(&self, ir: &str) -> String {
+        match self {
+            Profile::Generate => format!("{}{}", ir, REGISTRATION),
+            _ => ir.to_string(),
+        }
+    }
+}
+
+/// Constructor handing the counter sections of the linked program to the runtime
+///
+/// Every module of a multi-object build carries one; the runtime keeps the
+/// first, as the linker-defined bounds are the same in all of them.
+const REGISTRATION: &str = "
+@__start___llvm_prf_data = extern_weak hidden global i8
+@__stop___llvm_prf_data = extern_weak hidden global i8
+@__start___llvm_prf_cnts = extern_weak hidden global i8
+@__stop___llvm_prf_cnts = extern_weak hidden global i8
+@__start___llvm_prf_names = extern_weak hidden global i8
+@__stop___llvm_prf_names = extern_weak hidden global i8
+@llvm.global_ctors = appending global [1 x { i32, void ()*, i8* }] [{ i32, void ()*, i8* } { i32 65535, void ()* @php.profile.register, i8* null }]
+
+declare void @php_profile_register(i8*, i8*, i8*, i8*, i8*, i8*)
+
+define internal void @php.profile.register() noprofile {
+  call void @php_profile_register(i8* @__start___llvm_prf_data, i8* @__stop___llvm_prf_data, i8* @__start___llvm_prf_cnts, i8* @__stop___llvm_prf_cnts, i8* @__start___llvm_prf_names, i8* @__stop___llvm_prf_names)
+  ret void
+}
+";
+
+/// Check that the `opt` doing the instrumentation writes the profile format the runtime reads
+pub fn check_llvm_version(version_output: &str) -> CompileResult<()> {
+    let major = version_output
+        .split("LLVM version ")
+        .nth(1)
+        .and_then(|rest| rest.split('.').next())
+        .and_then(|major| major.trim().parse::<u32>().ok());
+    match major {
+        Some(14..=17) => Ok(()),
+        Some(major) => Err(CompileError::Configuration(format!(
+            "--pgo-gen needs LLVM 14 to 17 (raw profile version {}), found LLVM {}",
+            RAW_VERSION, major
+        ))),
+        None => Err(CompileError::Configuration("cannot determine the LLVM version of opt".to_string())),
+    }
+}
+
+/// Raw profile magic, `\xfflprofr\x81`
+const RAW_MAGIC: u64 = 0xff6c_7072_6f66_7281;
+
+/// Raw profile format version
+const RAW_VERSION: u64 = 8;
+
+/// Version flag of profiles recorded by IR-level instrumentation
+const VARIANT_MASK_IR_PROF: u64 = 1 << 56;
+
+/// Profile file written when `LLVM_PROFILE_FILE` is not set
+const DEFAULT_PROFILE_FILE: &str = "default.profraw";
+
+/// Per-function record in `__llvm_prf_data`
+#[repr(C)]
+#[allow(dead_code)]
+struct ProfileData {
+    name_ref: u64,
+    hash: u64,
+    counters: i64,
+    function: *const u8,
+    values: *const u8,
+    num_counters: u32,
+    num_value_sites: [u16; 2],
+}
+
+/// Counter sections of the running program
+struct Sections {
+    data: &'static [u8],
+    counters: &'static [u8],
+    names: &'static [u8],
+}
+
+static SECTIONS: OnceLock<Sections> = OnceLock::new();
+
+extern "C" {
+    fn atexit(function: extern "C" fn()) -> i32;
+}
+
+/// Record the counter sections of an instrumented program and write them at exit
+///
+/// # Safety
+///
+/// Each pair must bound a readable section of the program, or both be null.
+#[no_mangle]
+pub unsafe extern "C" fn php_profile_register(
+    data_begin: *const u8,
+    data_end: *const u8,
+    counters_begin: *const u8,
+    counters_end: *const u8,
+    names_begin: *const u8,
+    names_end: *const u8,
+) {
+    let section = |begin: *const u8, end: *const u8| -> &'static [u8] {
+        if begin.is_null() || end <= begin {
+            &[]
+        } else {
+            std::slice::from_raw_parts(begin, end as usize - begin as usize)
+        }
+    };
+    let sections = Sections {
+        data: section(data_begin, data_end),
+        counters: section(counters_begin, counters_end),
+        names: section(names_begin, names_end),
+    };
+    if SECTIONS.set(sections).is_ok() {
+        atexit(write_profile_at_exit);
+    }
+}
+
+extern "C" fn write_profile_at_exit() {
+    let Some(sections) = SECTIONS.get() else { return };
+    let path = profile_path(std::env::var("LLVM_PROFILE_FILE").ok().as_deref(), std::process::id());
+    if let Err(e) = write_profile(&path, sections) {
+        eprintln!("php2ir: cannot write profile {}: {}", path.display(), e);
+    }
+}
+
+fn write_profile(path: &Path, sections: &Sections) -> std::io::Result<()> {
+    let profile = raw_profile(sections.data, sections.counters, sections.names);
+    std::fs::File::create(path)?.write_all(&profile)
+}
+
+/// Path of the profile file for a `LLVM_PROFILE_FILE` pattern
+fn profile_path(pattern: Option<&str>, pid: u32) -> PathBuf {
+    match pattern.filter(|pattern| !pattern.is_empty()) {
+        Some(pattern) => PathBuf::from(pattern.replace("%p", &pid.to_string())),
+        None => PathBuf::from(DEFAULT_PROFILE_FILE),
+    }
+}
+
+/// Raw profile holding the given sections
+///
+/// Records point at their counters relative to themselves, so the sections
+/// are written back to back with the distance between the two in the header.
+fn raw_profile(data: &[u8], counters: &[u8], names: &[u8]) -> Vec<u8> {
+    let counters_delta = (counters.as_ptr() as u64).wrapping_sub(data.as_ptr() as u64);
+    let names_padding = (8 - names.len() % 8) % 8;
+    let header = [
+        RAW_MAGIC,
+        RAW_VERSION | VARIANT_MASK_IR_PROF,
+        0, // binary ids
+        (data.len() / std::mem::size_of::<ProfileData>()) as u64,
+        0, // padding before counters
+        (counters.len() / std::mem::size_of::<u64>()) as u64,
+        0, // padding after counters
+        names.len() as u64,
+        counters_delta,
+        names.as_ptr() as u64,
+        1, // last value kind
+    ];
+
+    let mut profile = Vec::with_capacity(header.len() * 8 + data.len() + counters.len() + names.len() + names_padding);
+    for field in header {
+        profile.extend_from_slice(&field.to_ne_bytes());
+    }
+    profile.extend_from_slice(data);
+    profile.extend_from_slice(counters);
+    profile.extend_from_slice(names);
+    profile.resize(profile.len() + names_padding, 0);
+    profile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> CompilerOptions {
+        CompilerOptions {
+            target: Some("x86_64-unknown-linux-gnu".to_string()),
+            ..CompilerOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_profile_from_options() {
+        assert_eq!(Profile::from_options(&options()).unwrap(), Profile::None);
+        let generate = CompilerOptions { pgo_gen: true, ..options() };
+        assert_eq!(Profile::from_options(&generate).unwrap(), Profile::Generate);
+
+        let darwin = CompilerOptions { target: Some("aarch64-apple-darwin".to_string()), ..generate.clone() };
+        assert!(matches!(Profile::from_options(&darwin), Err(CompileError::Configuration(_))));
+        let both = CompilerOptions { pgo_use: Some(PathBuf::from("app.profdata")), ..generate };
+        assert!(matches!(Profile::from_options(&both), Err(CompileError::Configuration(_))));
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let missing = CompilerOptions { pgo_use: Some(file.path().with_extension("missing")), ..options() };
+        assert!(matches!(Profile::from_options(&missing), Err(CompileError::Configuration(_))));
+        let profile = CompilerOptions { pgo_use: Some(file.path().to_path_buf()), ..options() };
+        let profile = Profile::from_options(&profile).unwrap();
+        assert_eq!(profile.passes(), "pgo-instr-use,");
+        assert_eq!(profile.llvm_options(), [format!("-pgo-test-profile-file={}", file.path().display())]);
+    }
+
+    #[test]
+    fn test_instrumented_ir_registers_counters() {
+        let ir = crate::ir::IrGenerator::new().unwrap().generate(&[]).unwrap();
+        assert_eq!(Profile::None.prepare_ir(&ir), ir);
+        let instrumented = Profile::Generate.prepare_ir(&ir);
+        assert!(instrumented.contains("@llvm.global_ctors = appending global"));
+        assert!(crate::verifier::verify_module(&instrumented).is_ok());
+    }
+
+    #[test]
+    fn test_llvm_version() {
+        assert!(check_llvm_version("Debian LLVM version 14.0.6\n  Optimized build.\n").is_ok());
+        assert!(check_llvm_version("LLVM (http://llvm.org/):\n  LLVM version 17.0.1\n").is_ok());
+        assert!(matches!(check_llvm_version("LLVM version 18.1.8"), Err(CompileError::Configuration(_))));
+        assert!(check_llvm_version("opt: unknown").is_err());
+    }
+
+    #[test]
+    fn test_raw_profile_layout() {
+        let block = [0u64; 12];
+        let bytes: &[u8] = unsafe { std::slice::from_raw_parts(block.as_ptr() as *const u8, 96) };
+        let record = std::mem::size_of::<ProfileData>();
+        let (data, counters) = bytes.split_at(record);
+        let profile = raw_profile(data, &counters[..16], b"names");
+
+        let field = |i: usize| u64::from_ne_bytes(profile[i * 8..i * 8 + 8].try_into().unwrap());
+        assert_eq!(field(0), RAW_MAGIC);
+        assert_eq!(field(1), 8 | 1 << 56);
+        assert_eq!((field(3), field(5), field(7)), (1, 2, 5));
+        assert_eq!(field(8), record as u64);
+        assert_eq!(profile.len(), 11 * 8 + record + 16 + 8);
+        assert_eq!(&profile[profile.len() - 8..], b"names\0\0\0");
+    }
+
+    #[test]
+    fn test_profile_path() {
+        assert_eq!(profile_path(None, 7), PathBuf::from("default.profraw"));
+        assert_eq!(profile_path(Some("out/app-%p.profraw"), 42), PathBuf::from("out/app-42.profraw"));
+    }
+}
@@ -14,11 +14,22 @@
  * limitations under the License.
  */
 
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::ffi::{CString, CStr};
+use std::io::Write;
 use std::os::raw::{c_char, c_int, c_long, c_double, c_void};
+use std::panic::AssertUnwindSafe;
 use std::ptr;
+use std::rc::Rc;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
 use log::info;
+use crate::interp::format_float;
+use crate::signals::{self, SignalHandler};
+use crate::types::IntWidth;
+use crate::watchdog::Watchdog;
 
 /// Runtime configuration
 #[derive(Debug, Clone)]
@@ -37,6 +48,15 @@ pub struct RuntimeConfig {
     
     /// Error handling mode
     pub error_mode: ErrorMode,
+    
+    /// Behavior of exit()/die()
+    pub exit_mode: ExitMode,
+    
+    /// Execution time limit (`max_execution_time`), unlimited if unset
+    pub max_execution_time: Option<Duration>,
+    
+    /// Shut down gracefully on SIGINT/SIGTERM (off for embedding hosts)
+    pub handle_signals: bool,
 }
 
 /// Garbage collection modes
@@ -94,6 +114,21 @@ pub enum ErrorMode {
     Abort,
 }
 
+/// Behavior of exit()/die()
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExitMode {
+    /// Terminate the host process (standalone binaries)
+    Process,
+    
+    /// Unwind back to the embedding host as a normal return
+    ///
+    /// exit() in compiled code gets back to [`RuntimeContext::run`] by
+    /// unwinding, so this needs a build with `panic = "unwind"`. Under
+    /// `panic = "abort"`, as in the release profile, it ends the process as
+    /// with `Process`; exit() called through the context still returns.
+    Return,
+}
+
 impl Default for RuntimeConfig {
     fn default() -> Self {
         Self {
@@ -102,6 +137,9 @@ impl Default for RuntimeConfig {
             hash_policy: HashPolicy::RobinHood,
             alloc_strategy: AllocStrategy::System,
             error_mode: ErrorMode::Exceptions,
+            exit_mode: ExitMode::Process,
+            max_execution_time: None,
+            handle_signals: false,
         }
     }
 }
@@ -113,6 +151,32 @@ pub struct RuntimeContext {
     functions: HashMap<String, Function>,
     classes: HashMap<String, Class>,
     error_handler: Option<Box<dyn Fn(RuntimeError)>>,
+    output: SharedOutput,
+    watchdog: Option<Watchdog>,
+    shutdown_functions: Vec<ShutdownFunction>,
+    signal_handlers: HashMap<i32, SignalHandler>,
+}
+
+/// Output sink of a context, shared with the compiled code it runs
+type SharedOutput = Rc<RefCell<Box<dyn Write>>>;
+
+thread_local! {
+    /// Output and exit mode of the context running compiled code on this
+    /// thread, set by [`RuntimeContext::run`]
+    static CURRENT: RefCell<Option<(SharedOutput, ExitMode)>> = const { RefCell::new(None) };
+}
+
+/// Payload of the unwind that exit() in compiled code starts under
+/// `ExitMode::Return`
+struct Exited(i32);
+
+/// Callback registered with register_shutdown_function()
+pub type ShutdownFunction = Box<dyn FnOnce(&mut RuntimeContext) -> Result<(), RuntimeError>>;
+
+/// Shared in-memory output sink for capturing program output
+#[derive(Debug, Clone, Default)]
+pub struct OutputBuffer {
+    buffer: Rc<RefCell<Vec<u8>>>,
 }
 
 /// Class implementation
@@ -182,10 +246,23 @@ pub struct Object {
     /// Properties
     properties: HashMap<String, Value>,
     
+    /// Declared types and `readonly` flags of properties
+    declarations: HashMap<String, PropertyDeclaration>,
+    
     /// Methods
     methods: HashMap<String, Function>,
 }
 
+/// Declared type and mutability of a property
+#[derive(Debug, Clone, Default)]
+pub struct PropertyDeclaration {
+    /// Declared type; untyped properties accept any value
+    pub typ: Option<crate::types::Type>,
+    
+    /// Whether the property can only be initialized once, from its class
+    pub readonly: bool,
+}
+
 /// Function implementation
 #[derive(Debug, Clone)]
 pub struct Function {
@@ -195,6 +272,9 @@ pub struct Function {
     /// Parameter types
     param_types: Vec<Type>,
     
+    /// Number of leading parameters that must be passed
+    required_params: usize,
+    
     /// Return type
     return_type: Type,
     
@@ -263,11 +343,20 @@ pub enum RuntimeErrorType {
     /// Division by zero
     DivisionByZero,
     
+    /// Other arithmetic error, such as `intdiv(PHP_INT_MIN, -1)`
+    Arithmetic,
+    
     /// Out of memory
     OutOfMemory,
     
     /// Invalid operation
     InvalidOperation,
+    
+    /// exit()/die() intercepted in `ExitMode::Return`
+    Exit,
+    
+    /// Maximum execution time exceeded
+    Timeout,
 }
 
 impl RuntimeContext {
@@ -279,9 +368,152 @@ impl RuntimeContext {
             functions: HashMap::new(),
             classes: HashMap::new(),
             error_handler: None,
+            output: Rc::new(RefCell::new(Box::new(std::io::stdout()))),
+            watchdog: None,
+            shutdown_functions: Vec::new(),
+            signal_handlers: HashMap::new(),
         }
     }
     
+    /// Redirect echo/print output into the given writer
+    pub fn with_output(mut self, output: Box<dyn Write>) -> Self {
+        self.set_output(output);
+        self
+    }
+    
+    /// Redirect echo/print output into the given writer
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = Rc::new(RefCell::new(output));
+    }
+    
+    /// Run a program body, translating an intercepted exit() into its status
+    ///
+    /// Compiled code the body calls prints to this context's output, and
+    /// its exit() follows this context's exit mode.
+    pub fn run<F>(&mut self, body: F) -> Result<i32, RuntimeError>
+    where
+        F: FnOnce(&mut RuntimeContext) -> Result<(), RuntimeError>,
+    {
+        let current = (self.output.clone(), self.config.exit_mode.clone());
+        let outer = CURRENT.with(|c| c.replace(Some(current)));
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| body(self)));
+        CURRENT.with(|c| c.replace(outer));
+        let result = match result {
+            Ok(result) => result,
+            Err(payload) => match payload.downcast::<Exited>() {
+                Ok(exited) => Err(exit_error(exited.0)),
+                Err(payload) => std::panic::resume_unwind(payload),
+            },
+        };
+        self.flush()?;
+        match result {
+            Ok(()) => Ok(0),
+            Err(error) if error.error_type == RuntimeErrorType::Exit => Ok(error.code),
+            Err(error) => Err(error),
+        }
+    }
+    
+    /// Handle exit()/die() according to the configured exit mode
+    pub fn exit(&self, status: i32) -> Result<(), RuntimeError> {
+        self.flush()?;
+        match self.config.exit_mode {
+            ExitMode::Process => std::process::exit(status),
+            ExitMode::Return => Err(exit_error(status)),
+        }
+    }
+    
+    /// Restart the execution time limit, as `set_time_limit()` does (0 = unlimited)
+    pub fn set_time_limit(&mut self, seconds: u64) {
+        let limit = (seconds > 0).then(|| Duration::from_secs(seconds));
+        match &mut self.watchdog {
+            Some(watchdog) => watchdog.reset(limit),
+            None if limit.is_some() => self.watchdog = Some(Watchdog::start(limit)),
+            None => {}
+        }
+    }
+    
+    /// Safe point: fail with a fatal error once the time limit has passed
+    pub fn check_time_limit(&self) -> Result<(), RuntimeError> {
+        match &self.watchdog {
+            Some(watchdog) if watchdog.take_expired() => {
+                let seconds = watchdog.limit().map(|limit| limit.as_secs_f64()).unwrap_or_default();
+                Err(RuntimeError::new(
+                    format!("Maximum execution time of {} seconds exceeded", seconds),
+                    RuntimeErrorType::Timeout,
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+    
+    /// Register a callback to run when the script finishes or is interrupted
+    pub fn register_shutdown_function(&mut self, function: ShutdownFunction) {
+        self.shutdown_functions.push(function);
+    }
+    
+    /// Set how a signal is handled, as `pcntl_signal()` does
+    pub fn pcntl_signal(&mut self, signal: i32, handler: SignalHandler) {
+        self.signal_handlers.insert(signal, handler);
+    }
+    
+    /// Safe point: act on a signal received since the last check
+    pub fn dispatch_signals(&mut self) -> Result<(), RuntimeError> {
+        if !self.config.handle_signals {
+            return Ok(());
+        }
+        let Some(signal) = signals::take_pending() else {
+            return Ok(());
+        };
+        
+        match self.signal_handlers.remove(&signal) {
+            Some(SignalHandler::Callback(mut callback)) => {
+                let result = callback(self, signal);
+                self.signal_handlers.entry(signal).or_insert(SignalHandler::Callback(callback));
+                result
+            }
+            Some(SignalHandler::Ignore) => {
+                self.signal_handlers.insert(signal, SignalHandler::Ignore);
+                Ok(())
+            }
+            Some(SignalHandler::Default) | None => {
+                info!("Received signal {}, shutting down", signal);
+                self.shutdown()?;
+                self.exit(signals::exit_status(signal))
+            }
+        }
+    }
+    
+    /// Run shutdown functions, then destructors of objects still held in globals
+    pub fn shutdown(&mut self) -> Result<(), RuntimeError> {
+        // Shutdown functions may register further shutdown functions
+        while !self.shutdown_functions.is_empty() {
+            let functions = std::mem::take(&mut self.shutdown_functions);
+            for function in functions {
+                function(self)?;
+            }
+        }
+        
+        let globals = std::mem::take(&mut self.globals);
+        for value in globals.values() {
+            if let Value::Object(object) = value {
+                if let Some(destructor) = object.get_method("__destruct") {
+                    (destructor.func_ptr)(std::slice::from_ref(value))?;
+                }
+            }
+        }
+        self.flush()
+    }
+    
+    /// Flush buffered output
+    pub fn flush(&self) -> Result<(), RuntimeError> {
+        self.output.borrow_mut().flush().map_err(output_error)
+    }
+    
+    /// Write raw text to the output sink
+    fn write_output(&self, text: &str) -> Result<(), RuntimeError> {
+        self.output.borrow_mut().write_all(text.as_bytes()).map_err(output_error)
+    }
+    
     /// Initialize runtime
     pub fn init(&mut self) -> Result<(), RuntimeError> {
         info!("Initializing PHP runtime");
@@ -298,6 +530,19 @@ impl RuntimeContext {
         // Initialize error handling
         self.init_error_handling()?;
         
+        // Install SIGINT/SIGTERM handling
+        if self.config.handle_signals {
+            signals::install_default_handlers().map_err(|e| RuntimeError::new(
+                format!("Failed to install signal handlers: {}", e),
+                RuntimeErrorType::InvalidOperation,
+            ))?;
+        }
+        
+        // Start the execution time watchdog
+        if let Some(limit) = self.config.max_execution_time {
+            self.watchdog = Some(Watchdog::start(Some(limit)));
+        }
+        
         info!("PHP runtime initialized successfully");
         Ok(())
     }
@@ -306,6 +551,9 @@ impl RuntimeContext {
     pub fn cleanup(&mut self) -> Result<(), RuntimeError> {
         info!("Cleaning up PHP runtime");
         
+        // Stop the watchdog
+        self.watchdog = None;
+        
         // Cleanup memory
         self.cleanup_memory()?;
         
@@ -375,6 +623,31 @@ impl RuntimeContext {
             }
         })?;
         
+        // Timing functions
+        self.register_function("sleep", vec![Type::Int], Type::Int, |args| {
+            let seconds = non_negative_int("sleep", args)?;
+            std::thread::sleep(Duration::from_secs(seconds));
+            Ok(Value::Int(0))
+        })?;
+        
+        self.register_function("usleep", vec![Type::Int], Type::Null, |args| {
+            let micros = non_negative_int("usleep", args)?;
+            std::thread::sleep(Duration::from_micros(micros));
+            Ok(Value::Null)
+        })?;
+        
+        self.register_function_with_optional("hrtime", vec![Type::Bool], 0, Type::Mixed, |args| {
+            let elapsed = monotonic_origin().elapsed();
+            if let Some(Value::Bool(true)) = args.first() {
+                Ok(Value::Int(elapsed.as_nanos() as i64))
+            } else {
+                let mut array = Array::new(ArrayType::Packed);
+                array.push(Value::Int(elapsed.as_secs() as i64));
+                array.push(Value::Int(elapsed.subsec_nanos() as i64));
+                Ok(Value::Array(array))
+            }
+        })?;
+        
         Ok(())
     }
     
@@ -443,10 +716,24 @@ impl RuntimeContext {
         param_types: Vec<Type>,
         return_type: Type,
         func: fn(&[Value]) -> Result<Value, RuntimeError>,
+    ) -> Result<(), RuntimeError> {
+        let required_params = param_types.len();
+        self.register_function_with_optional(name, param_types, required_params, return_type, func)
+    }
+    
+    /// Register a function whose trailing parameters are optional
+    pub fn register_function_with_optional(
+        &mut self,
+        name: &str,
+        param_types: Vec<Type>,
+        required_params: usize,
+        return_type: Type,
+        func: fn(&[Value]) -> Result<Value, RuntimeError>,
     ) -> Result<(), RuntimeError> {
         let function = Function {
             name: name.to_string(),
             param_types,
+            required_params,
             return_type,
             func_ptr: func,
         };
@@ -456,10 +743,13 @@ impl RuntimeContext {
     }
     
     /// Call a function
-    pub fn call_function(&self, name: &str, args: &[Value]) -> Result<Value, RuntimeError> {
+    pub fn call_function(&mut self, name: &str, args: &[Value]) -> Result<Value, RuntimeError> {
+        self.check_time_limit()?;
+        self.dispatch_signals()?;
+        
         if let Some(function) = self.functions.get(name) {
             // Check parameter count
-            if args.len() != function.param_types.len() {
+            if args.len() < function.required_params || args.len() > function.param_types.len() {
                 return Err(RuntimeError {
                     message: format!("{}() expects {} parameters, got {}", 
                         name, function.param_types.len(), args.len()),
@@ -538,30 +828,28 @@ impl RuntimeContext {
     /// Print value
     pub fn print(&self, value: &Value) -> Result<(), RuntimeError> {
         match value {
-            Value::Null => print!("null"),
-            Value::Bool(b) => print!("{}", b),
-            Value::Int(n) => print!("{}", n),
-            Value::Float(f) => print!("{}", f),
-            Value::String(s) => print!("{}", s),
+            Value::Null => self.write_output("null"),
+            Value::Bool(b) => self.write_output(&b.to_string()),
+            Value::Int(n) => self.write_output(&n.to_string()),
+            Value::Float(f) => self.write_output(&f.to_string()),
+            Value::String(s) => self.write_output(s),
             Value::Array(arr) => {
-                print!("Array");
                 // TODO: Implement array printing
+                self.write_output("Array")
             }
             Value::Object(obj) => {
-                print!("{} Object", obj.class_name);
+                self.write_output(&format!("{} Object", obj.class_name))
             }
             Value::Resource(res) => {
-                print!("Resource id #{}", res.id);
+                self.write_output(&format!("Resource id #{}", res.id))
             }
         }
-        Ok(())
     }
     
     /// Print line
     pub fn println(&self, value: &Value) -> Result<(), RuntimeError> {
         self.print(value)?;
-        println!();
-        Ok(())
+        self.write_output("\n")
     }
 }
 
@@ -588,6 +876,11 @@ impl Array {
         self.data.is_empty()
     }
     
+    /// Whether the array has positional keys only
+    pub fn is_packed(&self) -> bool {
+        self.map.is_none()
+    }
+    
     /// Push value to array
     pub fn push(&mut self, value: Value) {
         self.data.push(value);
@@ -598,6 +891,11 @@ impl Array {
         self.data.get(index)
     }
     
+    /// Get mutable value by index
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Value> {
+        self.data.get_mut(index)
+    }
+    
     /// Set value by index
     pub fn set(&mut self, index: usize, value: Value) -> Result<(), RuntimeError> {
         if index >= self.data.len() {
@@ -622,6 +920,12 @@ impl Array {
         None
     }
     
+    /// Get mutable value by key (for associative arrays)
+    pub fn get_by_key_mut(&mut self, key: &str) -> Option<&mut Value> {
+        let index = *self.map.as_ref()?.get(key)?;
+        self.data.get_mut(index)
+    }
+    
     /// Set value by key (for associative arrays)
     pub fn set_by_key(&mut self, key: &str, value: Value) -> Result<(), RuntimeError> {
         if let Some(ref mut map) = self.map {
@@ -646,6 +950,95 @@ impl Array {
             })
         }
     }
+
+    /// Iterate over key/value pairs in insertion order
+    ///
+    /// Keys are `Value::Int` for positions and integer-like string keys, and
+    /// `Value::String` otherwise.
+    pub fn iter(&self) -> impl Iterator<Item = (Value, &Value)> + '_ {
+        let mut keys: Vec<Value> = (0..self.data.len()).map(|i| Value::Int(i as i64)).collect();
+        if let Some(ref map) = self.map {
+            for (key, &index) in map {
+                keys[index] = match key.parse::<i64>() {
+                    Ok(n) if n.to_string() == *key => Value::Int(n),
+                    _ => Value::String(key.clone()),
+                };
+            }
+        }
+        keys.into_iter().zip(self.data.iter())
+    }
+}
+
+impl OutputBuffer {
+    /// Create an empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Get captured output as a string
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.buffer.borrow()).into_owned()
+    }
+    
+    /// Discard captured output
+    pub fn clear(&self) {
+        self.buffer.borrow_mut().clear();
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Convert an output I/O failure into a runtime error
+/// Reference point for hrtime()
+fn monotonic_origin() -> Instant {
+    static ORIGIN: OnceLock<Instant> = OnceLock::new();
+    *ORIGIN.get_or_init(Instant::now)
+}
+
+/// Read the single non-negative integer argument of a timing function
+fn non_negative_int(name: &str, args: &[Value]) -> Result<u64, RuntimeError> {
+    match args.first() {
+        Some(Value::Int(n)) if *n >= 0 => Ok(*n as u64),
+        _ => Err(RuntimeError::new(
+            format!("{}(): Argument #1 must be greater than or equal to 0", name),
+            RuntimeErrorType::InvalidOperation,
+        )),
+    }
+}
+
+fn output_error(error: std::io::Error) -> RuntimeError {
+    RuntimeError::new(format!("Failed to write output: {}", error), RuntimeErrorType::InvalidOperation)
+}
+
+/// Error carrying the status of an intercepted exit()
+fn exit_error(status: i32) -> RuntimeError {
+    RuntimeError::new(format!("exit({})", status), RuntimeErrorType::Exit).with_code(status)
+}
+
+/// Write compiled code's output to the context running it, or to standard
+/// output outside [`RuntimeContext::run`]
+pub(crate) fn write_output(bytes: &[u8]) -> std::io::Result<()> {
+    match CURRENT.with(|c| c.borrow().as_ref().map(|(output, _)| output.clone())) {
+        Some(output) => output.borrow_mut().write_all(bytes),
+        None => std::io::stdout().write_all(bytes),
+    }
+}
+
+/// Flush compiled code's output, as [`write_output`] writes it
+pub(crate) fn flush_output() -> std::io::Result<()> {
+    match CURRENT.with(|c| c.borrow().as_ref().map(|(output, _)| output.clone())) {
+        Some(output) => output.borrow_mut().flush(),
+        None => std::io::stdout().flush(),
+    }
 }
 
 impl Object {
@@ -654,15 +1047,74 @@ impl Object {
         Self {
             class_name,
             properties: HashMap::new(),
+            declarations: HashMap::new(),
             methods: HashMap::new(),
         }
     }
     
+    /// Declare a property's type and mutability
+    ///
+    /// A declared property stays uninitialized until it is first assigned.
+    pub fn declare_property(&mut self, name: &str, declaration: PropertyDeclaration) {
+        self.declarations.insert(name.to_string(), declaration);
+    }
+    
     /// Set property
     pub fn set_property(&mut self, name: &str, value: Value) {
         self.properties.insert(name.to_string(), value);
     }
     
+    /// Assign a property as PHP code does, enforcing its declaration
+    ///
+    /// `scope` is the class of the method performing the write, `None` for
+    /// global code. Values are coerced to the declared type in `mode`; a
+    /// rejected value is a `TypeError`, and writing a `readonly` property
+    /// twice or from another scope an `Error`.
+    pub fn assign_property(
+        &mut self,
+        name: &str,
+        value: Value,
+        scope: Option<&str>,
+        mode: crate::coercion::TypeMode,
+    ) -> Result<(), RuntimeError> {
+        let Some(declaration) = self.declarations.get(name) else {
+            self.set_property(name, value);
+            return Ok(());
+        };
+        let property = format!("{}::${}", self.class_name, name);
+        
+        if declaration.readonly {
+            if self.properties.contains_key(name) {
+                return Err(RuntimeError::new(
+                    format!("Cannot modify readonly property {}", property),
+                    RuntimeErrorType::InvalidOperation,
+                ));
+            }
+            if !scope.is_some_and(|scope| scope.eq_ignore_ascii_case(&self.class_name)) {
+                let scope = scope.map_or("global scope".to_string(), |scope| format!("scope {}", scope));
+                return Err(RuntimeError::new(
+                    format!("Cannot initialize readonly property {} from {}", property, scope),
+                    RuntimeErrorType::InvalidOperation,
+                ));
+            }
+        }
+        
+        let value = match &declaration.typ {
+            Some(typ) => crate::coercion::coerce(value, typ, mode).map_err(|given| {
+                RuntimeError::new(
+                    format!(
+                        "Cannot assign {} to property {} of type {}",
+                        given, property, crate::coercion::declared_name(typ)
+                    ),
+                    RuntimeErrorType::TypeError,
+                )
+            })?,
+            None => value,
+        };
+        self.set_property(name, value);
+        Ok(())
+    }
+    
     /// Get property
     pub fn get_property(&self, name: &str) -> Option<&Value> {
         self.properties.get(name)
@@ -753,21 +1205,102 @@ impl std::error::Error for RuntimeError {}
 #[no_mangle]
 pub extern "C" fn php_runtime_init() -> c_int {
     // TODO: Implement C interop
-    0
+    match signals::install_default_handlers() {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
 }
 
 #[no_mangle]
 pub extern "C" fn php_runtime_cleanup() -> c_int {
-    // TODO: Implement C interop
+    match flush_output() {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Start the runtime; the first call in a compiled program's `main`
+#[no_mangle]
+pub extern "C" fn php_init() {
+    php_runtime_init();
+}
+
+/// Shut the runtime down at the end of `main`, flushing buffered output
+#[no_mangle]
+pub extern "C" fn php_cleanup() {
+    php_runtime_cleanup();
+}
+
+/// Bits in the compiled program's integers, as set by [`php_set_int_width`]
+static INT_WIDTH: AtomicU32 = AtomicU32::new(64);
+
+/// Record the width of the compiled program's integers; `main` calls this
+/// after [`php_init`] when they are not 64-bit
+#[no_mangle]
+pub extern "C" fn php_set_int_width(bits: u32) {
+    INT_WIDTH.store(bits, Ordering::Relaxed);
+}
+
+/// Width of the compiled program's integers, which the runtime's own
+/// arithmetic overflows at
+pub fn int_width() -> IntWidth {
+    IntWidth::from_bits(INT_WIDTH.load(Ordering::Relaxed)).unwrap_or_default()
+}
+
+/// Print a NUL-terminated string
+///
+/// # Safety
+///
+/// `s` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn php_print_string(s: *const c_char) -> c_int {
+    if !s.is_null() {
+        let _ = write_output(CStr::from_ptr(s).to_bytes());
+    }
     0
 }
 
 #[no_mangle]
-pub extern "C" fn php_print_string(s: *const c_char) -> c_int {
-    // TODO: Implement C interop
+pub extern "C" fn php_print_int(value: i64) -> c_int {
+    let _ = write_output(value.to_string().as_bytes());
     0
 }
 
+/// Print a float as `echo` does, with PHP's precision
+#[no_mangle]
+pub extern "C" fn php_print_double(value: c_double) -> c_int {
+    let _ = write_output(format_float(value).as_bytes());
+    0
+}
+
+/// `exit()`/`die()` with a status, after flushing the output
+///
+/// Under `ExitMode::Return` this unwinds back to the [`RuntimeContext::run`]
+/// running the code, which returns the status; otherwise, or in a build
+/// that cannot unwind, it ends the process.
+///
+/// # Safety
+///
+/// Every frame between the caller and the context's `run` must have unwind
+/// information.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn php_exit(status: i32) -> ! {
+    let _ = flush_output();
+    let mode = CURRENT.with(|c| c.borrow().as_ref().map(|(_, mode)| mode.clone()));
+    if compiled_exit_returns(mode.as_ref()) {
+        std::panic::resume_unwind(Box::new(Exited(status)));
+    }
+    std::process::exit(status)
+}
+
+/// Whether exit() in compiled code returns to the running context instead
+/// of ending the process
+///
+/// Returning unwinds, which would abort a `panic = "abort"` build.
+fn compiled_exit_returns(mode: Option<&ExitMode>) -> bool {
+    cfg!(panic = "unwind") && mode == Some(&ExitMode::Return)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -812,6 +1345,149 @@ mod tests {
         assert_eq!(obj.get_property("y"), None);
     }
 
+    #[test]
+    fn test_property_declarations() {
+        use crate::coercion::TypeMode;
+        
+        let mut point = Object::new("Point".to_string());
+        point.declare_property("x", PropertyDeclaration { typ: Some(crate::types::Type::Int), readonly: true });
+        point.declare_property("label", PropertyDeclaration { typ: Some(crate::types::Type::String), readonly: false });
+        
+        let error = point.assign_property("x", Value::Int(1), None, TypeMode::Coercive).unwrap_err();
+        assert_eq!(error.message, "Cannot initialize readonly property Point::$x from global scope");
+        
+        // Coercive mode converts numeric strings, strict mode rejects them
+        point.assign_property("x", Value::String("7".to_string()), Some("Point"), TypeMode::Coercive).unwrap();
+        assert!(matches!(point.get_property("x"), Some(Value::Int(7))));
+        let error = point.assign_property("x", Value::Int(8), Some("Point"), TypeMode::Coercive).unwrap_err();
+        assert_eq!(error.message, "Cannot modify readonly property Point::$x");
+        assert_eq!(error.error_type, RuntimeErrorType::InvalidOperation);
+        
+        let error = point.assign_property("label", Value::Int(3), None, TypeMode::Strict).unwrap_err();
+        assert_eq!(error.message, "Cannot assign int to property Point::$label of type string");
+        assert_eq!(error.error_type, RuntimeErrorType::TypeError);
+    }
+
+    #[test]
+    fn test_output_capture() {
+        let buffer = OutputBuffer::new();
+        let context = RuntimeContext::new(RuntimeConfig::default())
+            .with_output(Box::new(buffer.clone()));
+        
+        context.print(&Value::String("answer: ".to_string())).unwrap();
+        context.println(&Value::Int(42)).unwrap();
+        assert_eq!(buffer.contents(), "answer: 42\n");
+    }
+
+    #[test]
+    fn test_exit_intercepted() {
+        let config = RuntimeConfig {
+            exit_mode: ExitMode::Return,
+            ..RuntimeConfig::default()
+        };
+        let buffer = OutputBuffer::new();
+        let mut context = RuntimeContext::new(config).with_output(Box::new(buffer.clone()));
+        
+        let status = context.run(|ctx| {
+            ctx.print(&Value::String("bye".to_string()))?;
+            ctx.exit(3)?;
+            ctx.print(&Value::String("unreachable".to_string()))
+        });
+        assert_eq!(status.unwrap(), 3);
+        assert_eq!(buffer.contents(), "bye");
+    }
+
+    #[test]
+    fn test_compiled_exit_requires_unwinding() {
+        assert_eq!(compiled_exit_returns(Some(&ExitMode::Return)), cfg!(panic = "unwind"));
+        assert!(!compiled_exit_returns(Some(&ExitMode::Process)));
+        assert!(!compiled_exit_returns(None));
+    }
+
+    #[cfg(panic = "unwind")]
+    #[test]
+    fn test_compiled_output_and_exit() {
+        /// What a compiled `main` calls for
+        /// `echo "answer: ", 42, " ", 0.5; exit(3); echo "unreachable";`
+        unsafe extern "C-unwind" fn program() {
+            php_print_string(c"answer: ".as_ptr());
+            php_print_int(42);
+            php_print_string(c" ".as_ptr());
+            php_print_double(0.5);
+            php_exit(3);
+        }
+        
+        let config = RuntimeConfig {
+            exit_mode: ExitMode::Return,
+            ..RuntimeConfig::default()
+        };
+        let buffer = OutputBuffer::new();
+        let mut context = RuntimeContext::new(config).with_output(Box::new(buffer.clone()));
+        
+        let status = context.run(|_| {
+            unsafe { program() };
+            unsafe { php_print_string(c"unreachable".as_ptr()) };
+            Ok(())
+        });
+        assert_eq!(status.unwrap(), 3);
+        assert_eq!(buffer.contents(), "answer: 42 0.5");
+        
+        // Output outside the run no longer goes to the context
+        unsafe { php_print_string(c"".as_ptr()) };
+        assert_eq!(buffer.contents(), "answer: 42 0.5");
+    }
+
+    #[test]
+    fn test_timing_builtins() {
+        let mut context = RuntimeContext::new(RuntimeConfig::default());
+        context.init().unwrap();
+        
+        assert!(matches!(context.call_function("usleep", &[Value::Int(10)]), Ok(Value::Null)));
+        assert!(context.call_function("sleep", &[Value::Int(-1)]).is_err());
+        assert!(matches!(context.call_function("hrtime", &[Value::Bool(true)]), Ok(Value::Int(_))));
+        assert!(matches!(context.call_function("hrtime", &[]), Ok(Value::Array(a)) if a.len() == 2));
+    }
+
+    #[test]
+    fn test_time_limit_exceeded() {
+        let config = RuntimeConfig {
+            max_execution_time: Some(Duration::from_millis(10)),
+            ..RuntimeConfig::default()
+        };
+        let mut context = RuntimeContext::new(config);
+        context.init().unwrap();
+        
+        std::thread::sleep(Duration::from_millis(50));
+        let error = context.call_function("usleep", &[Value::Int(0)]).unwrap_err();
+        assert_eq!(error.error_type, RuntimeErrorType::Timeout);
+        
+        // The error is catchable: execution may continue afterwards
+        context.set_time_limit(0);
+        assert!(context.call_function("usleep", &[Value::Int(0)]).is_ok());
+    }
+
+    #[test]
+    fn test_signal_graceful_shutdown() {
+        let config = RuntimeConfig {
+            exit_mode: ExitMode::Return,
+            handle_signals: true,
+            ..RuntimeConfig::default()
+        };
+        let buffer = OutputBuffer::new();
+        let mut context = RuntimeContext::new(config).with_output(Box::new(buffer.clone()));
+        context.init().unwrap();
+        
+        context.pcntl_signal(signals::SIGTERM, SignalHandler::Ignore);
+        signals::raise(signals::SIGTERM);
+        assert!(context.call_function("usleep", &[Value::Int(0)]).is_ok());
+        
+        context.register_shutdown_function(Box::new(|ctx| ctx.print(&Value::String("bye".to_string()))));
+        signals::raise(signals::SIGINT);
+        let status = context.run(|ctx| ctx.call_function("usleep", &[Value::Int(0)]).map(|_| ()));
+        assert_eq!(status.unwrap(), 130);
+        assert_eq!(buffer.contents(), "bye");
+    }
+
     #[test]
     fn test_type_compatibility() {
         let config = RuntimeConfig::default();
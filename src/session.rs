@@ -0,0 +1,198 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Compile sessions, for build servers and editors.
+//!
+//! A [`CompileSession`] runs a compilation like [`Compiler`] does, and
+//! reports every phase as it ends to a progress callback. Once its
+//! [`CancellationToken`] is cancelled, from any thread, the compilation
+//! stops at the end of the running phase with [`CompileError::Cancelled`],
+//! removing its intermediate files as usual.
+//!
+//! The phases are those of `--timings`. A multi-file build goes through
+//! them file by file, so it stops within the file being processed; a phase
+//! that is one long step, like the code generation of a module or the link,
+//! runs to its end.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::compiler::{Artifacts, Compiler, CompilerOptions};
+use crate::error::{CompileError, CompileResult};
+use crate::timings::{Counts, Phase, Timings};
+
+/// Flag cancelling the compilations that hold a clone of it
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop the compilations at the end of their running phase
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Progress of a compilation, reported as one of its phases ends
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    pub phase: Phase,
+
+    /// Time of the phase that ended
+    pub duration: Duration,
+
+    /// Time spent in all phases so far
+    pub elapsed: Duration,
+
+    /// What the compilation processed so far
+    pub counts: Counts,
+}
+
+/// Callback told about each phase that ends
+type ProgressCallback = Box<dyn Fn(&Progress)>;
+
+/// Progress callback and cancellation of the compilation a compiler runs
+#[derive(Default)]
+pub(crate) struct Hooks {
+    cancellation: Option<CancellationToken>,
+    progress: Option<ProgressCallback>,
+}
+
+impl Hooks {
+    /// Report a phase that ended; fails once the compilation is cancelled
+    pub(crate) fn phase_ended(&self, timings: &Timings, phase: Phase, duration: Duration) -> CompileResult<()> {
+        if let Some(progress) = &self.progress {
+            progress(&Progress { phase, duration, elapsed: timings.total(), counts: timings.counts() });
+        }
+        self.check()
+    }
+
+    /// Fail if the compilation is cancelled
+    pub(crate) fn check(&self) -> CompileResult<()> {
+        match &self.cancellation {
+            Some(token) if token.is_cancelled() => Err(CompileError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Compilation that reports its progress and can be cancelled
+pub struct CompileSession {
+    compiler: Compiler,
+}
+
+impl CompileSession {
+    /// Session compiling with `options`, with the manifest applied as by
+    /// [`Compiler::new`]
+    pub fn new(options: CompilerOptions) -> CompileResult<Self> {
+        Ok(Self::from_compiler(Compiler::new(options)?))
+    }
+
+    /// Session running the compilations of `compiler`, such as one made by
+    /// [`Compiler::in_memory`]
+    pub fn from_compiler(compiler: Compiler) -> Self {
+        Self { compiler }
+    }
+
+    /// Cancel the compilation when `token` is cancelled
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.compiler.hooks_mut().cancellation = Some(token);
+        self
+    }
+
+    /// Call `callback` whenever a phase ends
+    ///
+    /// It runs on the compiling thread, between phases; a slow callback
+    /// delays the compilation.
+    pub fn on_progress(mut self, callback: impl Fn(&Progress) + 'static) -> Self {
+        self.compiler.hooks_mut().progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Run the full compilation pipeline, as [`Compiler::compile`]
+    pub fn compile(&mut self) -> CompileResult<()> {
+        self.compiler.hooks_mut().check()?;
+        self.compiler.compile()
+    }
+
+    /// Plan and build the project of the options, as `php2ir build`
+    pub fn build(&mut self) -> CompileResult<()> {
+        self.compiler.hooks_mut().check()?;
+        let plan = self.compiler.plan_build()?;
+        self.compiler.build(&plan)
+    }
+
+    /// Compile source held in memory, as [`Compiler::compile_source`]
+    pub fn compile_source(&mut self, source: &str) -> CompileResult<Artifacts> {
+        self.compiler.hooks_mut().check()?;
+        self.compiler.compile_source(source)
+    }
+
+    /// The compiler, for its diagnostics and timings
+    pub fn compiler(&self) -> &Compiler {
+        &self.compiler
+    }
+
+    pub fn into_compiler(self) -> Compiler {
+        self.compiler
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    const SOURCE: &str = "<?php\necho 'Hello, World!';\n";
+
+    #[test]
+    fn test_compile_session() {
+        let phases = Rc::new(RefCell::new(Vec::new()));
+        let seen = phases.clone();
+        let compiler = Compiler::in_memory(CompilerOptions::default()).unwrap();
+        let mut session = CompileSession::from_compiler(compiler)
+            .on_progress(move |progress| seen.borrow_mut().push(progress.phase));
+        session.compile_source(SOURCE).unwrap();
+        assert_eq!(phases.borrow().first(), Some(&Phase::Parse));
+        assert!(phases.borrow().contains(&Phase::TypeCheck));
+        assert_eq!(phases.borrow().last(), Some(&Phase::IrGen));
+
+        // Cancelled after parsing
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        let compiler = Compiler::in_memory(CompilerOptions::default()).unwrap();
+        let mut session = CompileSession::from_compiler(compiler)
+            .with_cancellation(token)
+            .on_progress(move |progress| {
+                if progress.phase == Phase::Parse {
+                    canceller.cancel();
+                }
+            });
+        assert!(matches!(session.compile_source(SOURCE), Err(CompileError::Cancelled)));
+        assert_eq!(session.compiler().timings().duration(Phase::IrGen), Duration::ZERO);
+        assert!(matches!(session.compile_source(SOURCE), Err(CompileError::Cancelled)));
+    }
+}
@@ -0,0 +1,111 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! SIGINT/SIGTERM delivery for compiled programs.
+//!
+//! The OS-level handler only records the signal number; the runtime picks it
+//! up at its next safe point and either runs the handler installed with
+//! `pcntl_signal()` or performs a graceful shutdown.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use crate::runtime::{RuntimeContext, RuntimeError};
+
+/// Interrupt from keyboard (Ctrl-C)
+pub const SIGINT: i32 = 2;
+
+/// Termination request
+pub const SIGTERM: i32 = 15;
+
+/// Signals handled by default
+pub const DEFAULT_SIGNALS: &[i32] = &[SIGINT, SIGTERM];
+
+/// Callback installed with `pcntl_signal()`
+pub type SignalCallback = Box<dyn FnMut(&mut RuntimeContext, i32) -> Result<(), RuntimeError>>;
+
+/// User-level handler set with `pcntl_signal()`
+pub enum SignalHandler {
+    /// Graceful shutdown (`SIG_DFL`)
+    Default,
+
+    /// Discard the signal (`SIG_IGN`)
+    Ignore,
+
+    /// Call back into the program
+    Callback(SignalCallback),
+}
+
+impl std::fmt::Debug for SignalHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignalHandler::Default => write!(f, "SIG_DFL"),
+            SignalHandler::Ignore => write!(f, "SIG_IGN"),
+            SignalHandler::Callback(_) => write!(f, "Callback"),
+        }
+    }
+}
+
+/// Last received signal number, 0 if none is pending
+fn pending() -> &'static Arc<AtomicUsize> {
+    static PENDING: OnceLock<Arc<AtomicUsize>> = OnceLock::new();
+    PENDING.get_or_init(|| Arc::new(AtomicUsize::new(0)))
+}
+
+/// Install the OS handlers for [`DEFAULT_SIGNALS`] (idempotent)
+pub fn install_default_handlers() -> std::io::Result<()> {
+    static INSTALLED: OnceLock<()> = OnceLock::new();
+    if INSTALLED.get().is_some() {
+        return Ok(());
+    }
+
+    #[cfg(unix)]
+    for &signal in DEFAULT_SIGNALS {
+        signal_hook::flag::register_usize(signal, Arc::clone(pending()), signal as usize)?;
+    }
+
+    let _ = INSTALLED.set(());
+    Ok(())
+}
+
+/// Record a signal as if it had been delivered by the OS
+pub fn raise(signal: i32) {
+    pending().store(signal as usize, Ordering::SeqCst);
+}
+
+/// Take the pending signal, if any
+pub fn take_pending() -> Option<i32> {
+    match pending().swap(0, Ordering::SeqCst) {
+        0 => None,
+        signal => Some(signal as i32),
+    }
+}
+
+/// Conventional exit status of a process killed by `signal`
+pub fn exit_status(signal: i32) -> i32 {
+    128 + signal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_status() {
+        assert_eq!(exit_status(SIGINT), 130);
+        assert_eq!(exit_status(SIGTERM), 143);
+        assert_eq!(format!("{:?}", SignalHandler::Ignore), "SIG_IGN");
+    }
+}
@@ -0,0 +1,155 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Golden IR snapshots.
+//!
+//! Every fixture `<name>.php` of a directory is compiled to unoptimized IR
+//! for a fixed target, and the IR is compared with the checked-in
+//! `<name>.ll` next to it, so any change to generated code shows up as a
+//! diff. Blessing writes the current IR as the new snapshot instead; a
+//! fixture without a snapshot fails until it is blessed.
+//!
+//! The IR is independent of the host and of where the fixture lives: the
+//! target is [`SNAPSHOT_TARGET`], and the fixture's path is replaced by its
+//! file name.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::compiler::{Compiler, CompilerOptions};
+use crate::error::{CompileResult, ErrorContext};
+use crate::phpt::diff;
+
+/// Target snapshots are generated for
+pub const SNAPSHOT_TARGET: &str = "x86_64-unknown-linux-gnu";
+
+/// Extension of snapshot files
+pub const SNAPSHOT_EXTENSION: &str = "ll";
+
+/// How a fixture compared with its snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotOutcome {
+    Matched,
+
+    /// The IR differs from the snapshot
+    Changed { diff: String },
+
+    /// The fixture has no snapshot yet
+    Missing,
+
+    /// The snapshot was written from the current IR
+    Blessed,
+}
+
+/// Snapshot comparison of one fixture
+#[derive(Debug, Clone)]
+pub struct SnapshotResult {
+    pub fixture: PathBuf,
+    pub outcome: SnapshotOutcome,
+}
+
+impl SnapshotResult {
+    /// Whether the snapshot is up to date
+    pub fn passed(&self) -> bool {
+        matches!(self.outcome, SnapshotOutcome::Matched | SnapshotOutcome::Blessed)
+    }
+}
+
+impl fmt::Display for SnapshotResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let snapshot = snapshot_path(&self.fixture);
+        match &self.outcome {
+            SnapshotOutcome::Matched => write!(f, "ok {}", self.fixture.display()),
+            SnapshotOutcome::Changed { diff } => {
+                write!(f, "CHANGED {} (- {}, + generated):\n{}", self.fixture.display(), snapshot.display(), diff)
+            }
+            SnapshotOutcome::Missing => write!(f, "MISSING {}: bless to write {}", self.fixture.display(), snapshot.display()),
+            SnapshotOutcome::Blessed => write!(f, "blessed {}", snapshot.display()),
+        }
+    }
+}
+
+/// Snapshot file of a fixture
+pub fn snapshot_path(fixture: &Path) -> PathBuf {
+    fixture.with_extension(SNAPSHOT_EXTENSION)
+}
+
+/// IR of a fixture as its snapshot holds it
+pub fn snapshot_ir(fixture: &Path) -> CompileResult<String> {
+    let options = CompilerOptions {
+        input: fixture.to_path_buf(),
+        output: fixture.with_extension(SNAPSHOT_EXTENSION),
+        emit_llvm_only: true,
+        optimization_level: "O0".to_string(),
+        target: Some(SNAPSHOT_TARGET.to_string()),
+        interpreter_fallback: false,
+        ..CompilerOptions::default()
+    };
+    let ir = Compiler::new(options)?.generate_ir().with_context(|| fixture.display().to_string())?;
+    let name = fixture.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    Ok(ir.replace(&fixture.display().to_string(), &name))
+}
+
+/// Compare a fixture's IR with its snapshot, or with `bless` write the
+/// snapshot
+pub fn check_snapshot(fixture: &Path, bless: bool) -> CompileResult<SnapshotResult> {
+    let ir = snapshot_ir(fixture)?;
+    let snapshot = snapshot_path(fixture);
+    let outcome = match std::fs::read_to_string(&snapshot) {
+        Ok(expected) if expected == ir => SnapshotOutcome::Matched,
+        _ if bless => {
+            std::fs::write(&snapshot, &ir)?;
+            SnapshotOutcome::Blessed
+        }
+        Ok(expected) => SnapshotOutcome::Changed { diff: diff(&expected, &ir) },
+        Err(_) => SnapshotOutcome::Missing,
+    };
+    Ok(SnapshotResult { fixture: fixture.to_path_buf(), outcome })
+}
+
+/// Check the snapshots of every `.php` fixture in `dir`, sorted by name
+pub fn check_snapshots(dir: &Path, bless: bool) -> CompileResult<Vec<SnapshotResult>> {
+    let mut fixtures: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "php"))
+        .collect();
+    fixtures.sort();
+    fixtures.iter().map(|fixture| check_snapshot(fixture, bless)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = dir.path().join("echo.php");
+        std::fs::write(&fixture, "<?php\necho \"snapshot\\n\";\n").unwrap();
+
+        assert_eq!(check_snapshot(&fixture, false).unwrap().outcome, SnapshotOutcome::Missing);
+        assert_eq!(check_snapshot(&fixture, true).unwrap().outcome, SnapshotOutcome::Blessed);
+        let snapshot = std::fs::read_to_string(snapshot_path(&fixture)).unwrap();
+        assert!(snapshot.contains(&format!("target triple = \"{}\"", SNAPSHOT_TARGET)));
+        assert!(!snapshot.contains(&dir.path().display().to_string()));
+        assert_eq!(check_snapshot(&fixture, false).unwrap().outcome, SnapshotOutcome::Matched);
+
+        std::fs::write(snapshot_path(&fixture), format!("; stale\n{}", snapshot)).unwrap();
+        let result = check_snapshot(&fixture, false).unwrap();
+        assert!(matches!(result.outcome, SnapshotOutcome::Changed { ref diff } if diff.contains("- ; stale")));
+        assert!(!result.passed());
+    }
+}
@@ -0,0 +1,384 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Monomorphized specializations of untyped functions.
+//!
+//! When call sites pass arguments of statically known scalar types to a
+//! function with untyped parameters, a copy of the function is generated
+//! with those parameters typed, named after the types (`add$int$int`), and
+//! the call sites are rewritten to call it directly. Code generation then
+//! passes unboxed scalars instead of generic values. The generic function is
+//! kept for every other caller.
+//!
+//! A function is only specialized when that cannot change its behavior: its
+//! untyped parameters are never reassigned, it takes nothing by reference or
+//! variadically, and it does not inspect its own arguments or scope. The
+//! copy keeps the function's untyped return, and typed integer `+`, `-` and
+//! `*` promote to float on overflow just as generic arithmetic does, so a
+//! specialized call returns what the generic call would.
+
+use std::collections::HashMap;
+use crate::ast::index::rewrite_expressions;
+use crate::ast::visit::walk_expression_ref;
+use crate::ast::{AstNode, Expression, FunctionDecl, NodeId, Visitor};
+use crate::devirtualize::Aliases;
+use crate::literals::{assigned_in_statement, LiteralChecker};
+use crate::types::{IntWidth, Type};
+
+/// Most specializations generated for one function
+pub const MAX_SPECIALIZATIONS: usize = 4;
+
+/// Functions that read the arguments or variables of their caller's frame
+const INTROSPECTIVE_FUNCTIONS: &[&str] = &[
+    "func_get_args", "func_get_arg", "func_num_args", "get_defined_vars", "compact", "extract",
+];
+
+/// Generate specializations for calls with scalar arguments and call them
+///
+/// Returns the number of calls rewritten.
+pub fn specialize(ast: &mut Vec<AstNode>, int_width: IntWidth) -> usize {
+    let mut checker = LiteralChecker::new(String::new()).with_int_width(int_width);
+    checker.check(ast);
+    let calls = checker.take_call_types();
+
+    let mut aliases = Aliases::default();
+    ast.iter().for_each(|node| aliases.visit_node(node));
+    if calls.is_empty() || aliases.dynamic_scope {
+        return 0;
+    }
+
+    let mut functions = HashMap::new();
+    collect_candidates(ast, &mut functions);
+
+    // Parameter types of each call's specialization, and how often each is used
+    let mut variants: HashMap<NodeId, (String, Vec<Type>)> = HashMap::new();
+    let mut uses: HashMap<(String, Vec<Type>), usize> = HashMap::new();
+    for (id, (function, types)) in calls {
+        let Some(candidate) = functions.get(&function) else { continue };
+        let Some(variant) = variant(&candidate.untyped, &types) else { continue };
+        *uses.entry((function.clone(), variant.clone())).or_default() += 1;
+        variants.insert(id, (function, variant));
+    }
+
+    // Keep the most used variants of each function
+    let mut ranked: Vec<_> = uses.into_iter().collect();
+    ranked.sort_by(|(a, a_uses), (b, b_uses)| {
+        b_uses.cmp(a_uses).then_with(|| suffix(&a.1).cmp(&suffix(&b.1)))
+    });
+    let mut chosen: HashMap<String, Vec<Vec<Type>>> = HashMap::new();
+    for ((function, variant), _) in ranked {
+        let kept = chosen.entry(function).or_default();
+        if kept.len() < MAX_SPECIALIZATIONS {
+            kept.push(variant);
+        }
+    }
+
+    // Point the recorded calls at their chosen specializations, unless an
+    // argument is a variable that can change behind the checker's back
+    let mut used: HashMap<String, Vec<Vec<Type>>> = HashMap::new();
+    let mut rewritten = 0;
+    rewrite_expressions(ast, variants.keys().copied().collect::<Vec<_>>(), |id, expr| {
+        let Some((function, variant)) = variants.remove(&id) else { return };
+        let chosen = chosen.get(&function).is_some_and(|kept| kept.contains(&variant));
+        if let Expression::FunctionCall { name, arguments } = expr {
+            let aliased = arguments.iter().any(|argument| {
+                matches!(argument, Expression::Variable(v) if aliases.variables.contains(v))
+            });
+            if chosen && !aliased {
+                **name = Expression::Constant(specialized_name(&functions[&function].name, &variant));
+                rewritten += 1;
+                let used = used.entry(function).or_default();
+                if !used.contains(&variant) {
+                    used.push(variant);
+                }
+            }
+        }
+    });
+    insert_specializations(ast, &used);
+    rewritten
+}
+
+/// A function that may be specialized
+struct Candidate {
+    /// Name as declared
+    name: String,
+
+    /// Whether each parameter is untyped
+    untyped: Vec<bool>,
+}
+
+/// Specializable top-level functions, by lowercase name
+fn collect_candidates(nodes: &mut [AstNode], out: &mut HashMap<String, Candidate>) {
+    for node in nodes {
+        match node {
+            AstNode::Program(nodes) => collect_candidates(nodes, out),
+            AstNode::Function(decl) => {
+                out.extend(candidate(decl).map(|candidate| (candidate.name.to_lowercase(), candidate)));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn candidate(decl: &mut FunctionDecl) -> Option<Candidate> {
+    if !decl.attributes.is_empty()
+        || decl.parameters.iter().all(|p| p.typ.is_some())
+        || decl.parameters.iter().any(|p| p.is_reference || p.is_variadic)
+    {
+        return None;
+    }
+    let mut assigned = Vec::new();
+    assigned_in_statement(&decl.body, &mut assigned);
+    let reassigned = decl.parameters.iter().any(|p| p.typ.is_none() && assigned.contains(&p.name));
+
+    let mut introspection = Introspection::default();
+    introspection.visit_statement(&decl.body);
+    if reassigned || introspection.found {
+        return None;
+    }
+    let untyped = decl.parameters.iter().map(|p| p.typ.is_none()).collect();
+    Some(Candidate { name: decl.name.clone(), untyped })
+}
+
+/// Types of the untyped parameters for a call, when all are known scalars
+fn variant(untyped: &[bool], arguments: &[Type]) -> Option<Vec<Type>> {
+    if arguments.len() != untyped.len() {
+        return None;
+    }
+    untyped.iter().zip(arguments)
+        .filter(|(untyped, _)| **untyped)
+        .map(|(_, typ)| match typ {
+            Type::Int | Type::Float | Type::Bool | Type::String => Some(typ.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn suffix(variant: &[Type]) -> String {
+    variant.iter().map(|typ| format!("${}", typ)).collect()
+}
+
+/// Name of the specialization of `function` for `variant`
+pub fn specialized_name(function: &str, variant: &[Type]) -> String {
+    format!("{}{}", function, suffix(variant))
+}
+
+/// Add the chosen specializations after their generic functions
+fn insert_specializations(nodes: &mut Vec<AstNode>, chosen: &HashMap<String, Vec<Vec<Type>>>) {
+    let mut i = 0;
+    while i < nodes.len() {
+        let copies: Vec<AstNode> = match &mut nodes[i] {
+            AstNode::Program(inner) => {
+                insert_specializations(inner, chosen);
+                Vec::new()
+            }
+            AstNode::Function(decl) => chosen.get(&decl.name.to_lowercase())
+                .into_iter()
+                .flatten()
+                .map(|variant| AstNode::Function(specialized(decl, variant)))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let count = copies.len();
+        nodes.splice(i + 1..i + 1, copies);
+        i += count + 1;
+    }
+}
+
+/// Copy of a function with its untyped parameters given the variant's types
+fn specialized(decl: &FunctionDecl, variant: &[Type]) -> FunctionDecl {
+    let mut copy = decl.clone();
+    copy.name = specialized_name(&decl.name, variant);
+    let mut types = variant.iter();
+    for parameter in copy.parameters.iter_mut().filter(|p| p.typ.is_none()) {
+        parameter.typ = types.next().cloned();
+    }
+    copy
+}
+
+/// Whether a body calls a function that reads its own frame
+#[derive(Default)]
+pub(crate) struct Introspection {
+    pub(crate) found: bool,
+}
+
+impl Visitor for Introspection {
+    fn visit_expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::FunctionCall { name, .. } => match name.as_ref() {
+                Expression::Constant(function) => {
+                    let global = function.rsplit('\\').next().unwrap_or(function).to_lowercase();
+                    self.found |= INTROSPECTIVE_FUNCTIONS.contains(&global.as_str());
+                }
+                // The callee could be any of them
+                _ => self.found = true,
+            },
+            Expression::VariableVariable(_) => self.found = true,
+            _ => {}
+        }
+        walk_expression_ref(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{AssignmentOperator, BinaryOperator, Literal, Parameter, Statement, Visibility};
+
+    fn variable(name: &str) -> Box<Expression> {
+        Box::new(Expression::Variable(name.to_string()))
+    }
+
+    fn function(name: &str, body: Statement) -> AstNode {
+        let parameter = |name: &str| Parameter {
+            name: name.to_string(),
+            typ: None,
+            default_value: None,
+            is_reference: false,
+            is_variadic: false,
+        };
+        AstNode::Function(FunctionDecl {
+            name: name.to_string(),
+            parameters: vec![parameter("a"), parameter("b")],
+            return_type: None,
+            body: Box::new(body),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        })
+    }
+
+    fn sum() -> Statement {
+        Statement::Return(Some(Box::new(Expression::BinaryOp {
+            left: variable("a"),
+            op: BinaryOperator::Add,
+            right: variable("b"),
+        })))
+    }
+
+    fn call(function: &str, arguments: Vec<Expression>) -> AstNode {
+        AstNode::Expression(Box::new(Expression::FunctionCall {
+            name: Box::new(Expression::Constant(function.to_string())),
+            arguments,
+        }))
+    }
+
+    fn callee(node: &AstNode) -> &str {
+        match node {
+            AstNode::Expression(expr) => match expr.as_ref() {
+                Expression::FunctionCall { name, .. } => match name.as_ref() {
+                    Expression::Constant(name) => name,
+                    other => panic!("not a named call: {:?}", other),
+                },
+                other => panic!("not a call: {:?}", other),
+            },
+            other => panic!("not an expression: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_specializations() {
+        let int = |n| Expression::Literal(Literal::Int(n));
+        let mut ast = vec![
+            function("add", sum()),
+            AstNode::Expression(Box::new(Expression::Assignment {
+                target: variable("s"),
+                op: AssignmentOperator::Assign,
+                value: Box::new(Expression::Literal(Literal::String("x".to_string()))),
+            })),
+            call("add", vec![int(1), int(2)]),
+            call("ADD", vec![Expression::Literal(Literal::Float(1.5)), int(2)]),
+            call("add", vec![*variable("s"), *variable("s")]),
+            call("add", vec![*variable("unknown"), int(2)]),
+        ];
+        assert_eq!(specialize(&mut ast, IntWidth::default()), 3);
+
+        let names: Vec<_> = ast.iter()
+            .filter_map(|node| match node {
+                AstNode::Function(decl) => Some(decl.name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names.len(), 4);
+        assert_eq!(names[0], "add");
+        assert!(names.contains(&"add$float$int") && names.contains(&"add$string$string"));
+
+        let AstNode::Function(int_add) = ast.iter().find(|node| {
+            matches!(node, AstNode::Function(decl) if decl.name == "add$int$int")
+        }).unwrap() else { unreachable!() };
+        assert_eq!(int_add.parameters[0].typ, Some(Type::Int));
+        assert_eq!(int_add.parameters[1].typ, Some(Type::Int));
+
+        assert_eq!(callee(&ast[5]), "add$int$int");
+        assert_eq!(callee(&ast[6]), "add$float$int");
+        assert_eq!(callee(&ast[7]), "add$string$string");
+        assert_eq!(callee(&ast[8]), "add");
+    }
+
+    #[test]
+    fn test_specialization_overflow() {
+        use crate::interp::Interpreter;
+        use crate::runtime::OutputBuffer;
+
+        // function add($a, $b) { return $a + $b; } echo add(PHP_INT_MAX, 1);
+        let generic = vec![
+            function("add", sum()),
+            AstNode::Statement(Box::new(Statement::Echo(vec![Expression::FunctionCall {
+                name: Box::new(Expression::Constant("add".to_string())),
+                arguments: vec![Expression::Constant("PHP_INT_MAX".to_string()), Expression::Literal(Literal::Int(1))],
+            }]))),
+        ];
+        let mut specialized = generic.clone();
+        assert_eq!(specialize(&mut specialized, IntWidth::default()), 1);
+
+        let run = |ast: &[AstNode]| {
+            let output = OutputBuffer::new();
+            Interpreter::new().unwrap().with_output(Box::new(output.clone())).run(ast).unwrap();
+            output.contents()
+        };
+        assert_eq!(run(&specialized), run(&generic));
+        assert_eq!(run(&specialized), "9.2233720368548E+18");
+
+        // The compiled copy promotes too, and returns a boxed value as the generic function does
+        let ir = crate::ir::IrGenerator::new().unwrap().generate(&specialized).unwrap();
+        let body = &ir[ir.find("define hidden %php.mixed @php.add$int$int(i64 %a, i64 %b)").unwrap()..];
+        let body = &body[..body.find("\n}\n").unwrap()];
+        assert!(body.contains("@llvm.sadd.with.overflow.i64"));
+        assert!(body.contains("fadd double"));
+        assert!(ir.contains("define hidden %php.mixed @php.add(%php.mixed %a, %php.mixed %b)"));
+    }
+
+    #[test]
+    fn test_unsafe_functions_are_kept() {
+        // function f($a, $b) { $a = $a . $b; }
+        let reassigning = function("f", Statement::Expression(Box::new(Expression::Assignment {
+            target: variable("a"),
+            op: AssignmentOperator::Assign,
+            value: Box::new(Expression::BinaryOp { left: variable("a"), op: BinaryOperator::Concat, right: variable("b") }),
+        })));
+        // function g($a, $b) { return func_get_args(); }
+        let introspective = function("g", Statement::Return(Some(Box::new(Expression::FunctionCall {
+            name: Box::new(Expression::Constant("func_get_args".to_string())),
+            arguments: vec![],
+        }))));
+        let one = || Expression::Literal(Literal::Int(1));
+        let mut ast = vec![reassigning, introspective, call("f", vec![one(), one()]), call("g", vec![one(), one()])];
+        assert_eq!(specialize(&mut ast, IntWidth::default()), 0);
+        assert_eq!(ast.len(), 4);
+        assert_eq!(callee(&ast[2]), "f");
+    }
+}
@@ -0,0 +1,277 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Refcounted strings of compiled code.
+//!
+//! Generated code handles PHP strings as `%php.string*` pointers to
+//! [`PhpString`] values. Every runtime function returning a string returns
+//! a new reference, which the code generator releases once the value is no
+//! longer needed. A null pointer is the empty string.
+
+use std::cmp::Ordering;
+use std::os::raw::c_char;
+use crate::interp::{format_float, parse_numeric, Num};
+use crate::runtime;
+
+/// Immutable byte string with a reference count
+#[derive(Debug)]
+pub struct PhpString {
+    refcount: usize,
+    bytes: Vec<u8>,
+}
+
+impl PhpString {
+    /// Allocate a string with one reference
+    pub fn new(bytes: Vec<u8>) -> *mut PhpString {
+        Box::into_raw(Box::new(PhpString { refcount: 1, bytes }))
+    }
+
+    /// Bytes of the string
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Number of references to the string
+    pub fn refcount(&self) -> usize {
+        self.refcount
+    }
+}
+
+/// Bytes of a string from generated code, tolerating null pointers
+unsafe fn bytes<'a>(s: *const PhpString) -> &'a [u8] {
+    match s.as_ref() {
+        Some(s) => &s.bytes,
+        None => &[],
+    }
+}
+
+// FFI functions called by generated code
+
+/// Create a string from `len` bytes at `data`
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, or be null when `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn php_string_new(data: *const c_char, len: i64) -> *mut PhpString {
+    if data.is_null() || len <= 0 {
+        return PhpString::new(Vec::new());
+    }
+    PhpString::new(std::slice::from_raw_parts(data as *const u8, len as usize).to_vec())
+}
+
+#[no_mangle]
+pub extern "C" fn php_string_from_int(value: i64) -> *mut PhpString {
+    PhpString::new(value.to_string().into_bytes())
+}
+
+#[no_mangle]
+pub extern "C" fn php_string_from_float(value: f64) -> *mut PhpString {
+    PhpString::new(format_float(value).into_bytes())
+}
+
+#[no_mangle]
+pub extern "C" fn php_string_from_bool(value: bool) -> *mut PhpString {
+    PhpString::new(if value { b"1".to_vec() } else { Vec::new() })
+}
+
+/// Concatenate two strings into a new one
+///
+/// # Safety
+///
+/// Both arguments must be null or live strings.
+#[no_mangle]
+pub unsafe extern "C" fn php_string_concat(left: *const PhpString, right: *const PhpString) -> *mut PhpString {
+    let (left, right) = (bytes(left), bytes(right));
+    let mut result = Vec::with_capacity(left.len() + right.len());
+    result.extend_from_slice(left);
+    result.extend_from_slice(right);
+    PhpString::new(result)
+}
+
+/// # Safety
+///
+/// `s` must be null or a live string.
+#[no_mangle]
+pub unsafe extern "C" fn php_string_addref(s: *mut PhpString) {
+    if let Some(s) = s.as_mut() {
+        s.refcount += 1;
+    }
+}
+
+/// Drop a reference, freeing the string with the last one
+///
+/// # Safety
+///
+/// `s` must be null or a live string; it must not be used after its last
+/// reference is released.
+#[no_mangle]
+pub unsafe extern "C" fn php_string_release(s: *mut PhpString) {
+    let Some(string) = s.as_mut() else {
+        return;
+    };
+    string.refcount -= 1;
+    if string.refcount == 0 {
+        drop(Box::from_raw(s));
+    }
+}
+
+/// PHP truthiness: false for `""` and `"0"`
+///
+/// # Safety
+///
+/// `s` must be null or a live string.
+#[no_mangle]
+pub unsafe extern "C" fn php_string_truthy(s: *const PhpString) -> bool {
+    !matches!(bytes(s), b"" | b"0")
+}
+
+/// Integer value of a string: its leading number, or 0
+///
+/// # Safety
+///
+/// `s` must be null or a live string.
+#[no_mangle]
+pub unsafe extern "C" fn php_string_to_int(s: *const PhpString) -> i64 {
+    match number(s) {
+        Some(Num::Int(n)) => n,
+        Some(Num::Float(x)) if x.is_finite() => x as i64,
+        _ => 0,
+    }
+}
+
+/// Float value of a string: its leading number, or 0
+///
+/// # Safety
+///
+/// `s` must be null or a live string.
+#[no_mangle]
+pub unsafe extern "C" fn php_string_to_float(s: *const PhpString) -> f64 {
+    match number(s) {
+        Some(Num::Int(n)) => n as f64,
+        Some(Num::Float(x)) => x,
+        None => 0.0,
+    }
+}
+
+/// PHP 8 comparison of two strings: -1, 0 or 1
+///
+/// Numeric strings compare as numbers, others byte by byte.
+///
+/// # Safety
+///
+/// Both arguments must be null or live strings.
+#[no_mangle]
+pub unsafe extern "C" fn php_string_compare(left: *const PhpString, right: *const PhpString) -> i64 {
+    let (a, b) = (String::from_utf8_lossy(bytes(left)), String::from_utf8_lossy(bytes(right)));
+    let ordering = match (parse_numeric(&a), parse_numeric(&b)) {
+        (Some((Num::Int(x), true)), Some((Num::Int(y), true))) => x.cmp(&y),
+        (Some((x, true)), Some((y, true))) => float(x).partial_cmp(&float(y)).unwrap_or(Ordering::Greater),
+        _ => bytes(left).cmp(bytes(right)),
+    };
+    ordering as i64
+}
+
+/// Byte equality of two strings, as used by `===`
+///
+/// # Safety
+///
+/// Both arguments must be null or live strings.
+#[no_mangle]
+pub unsafe extern "C" fn php_string_equal(left: *const PhpString, right: *const PhpString) -> bool {
+    bytes(left) == bytes(right)
+}
+
+/// Leading number of a string
+unsafe fn number(s: *const PhpString) -> Option<Num> {
+    parse_numeric(&String::from_utf8_lossy(bytes(s))).map(|(number, _)| number)
+}
+
+fn float(number: Num) -> f64 {
+    match number {
+        Num::Int(n) => n as f64,
+        Num::Float(x) => x,
+    }
+}
+
+/// Length of a string in bytes
+///
+/// # Safety
+///
+/// `s` must be null or a live string.
+#[no_mangle]
+pub unsafe extern "C" fn php_string_length(s: *const PhpString) -> i64 {
+    bytes(s).len() as i64
+}
+
+/// Write a string to the output, as [`runtime::write_output`] does
+///
+/// # Safety
+///
+/// `s` must be null or a live string.
+#[no_mangle]
+pub unsafe extern "C" fn php_string_print(s: *const PhpString) {
+    let _ = runtime::write_output(bytes(s));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concat_and_release() {
+        unsafe {
+            let greeting = php_string_new(b"n=".as_ptr() as *const c_char, 2);
+            let number = php_string_from_int(-42);
+            let joined = php_string_concat(greeting, number);
+            php_string_release(greeting);
+            php_string_release(number);
+            assert_eq!((*joined).as_bytes(), b"n=-42");
+
+            php_string_addref(joined);
+            assert_eq!((*joined).refcount(), 2);
+            php_string_release(joined);
+            assert_eq!((*joined).refcount(), 1);
+
+            let float = php_string_from_float(0.1 + 0.2);
+            let empty = php_string_concat(std::ptr::null(), std::ptr::null());
+            assert_eq!((*float).as_bytes(), b"0.3");
+            assert!(!php_string_truthy(empty));
+            assert!(php_string_truthy(joined));
+            for s in [joined, float, empty] {
+                php_string_release(s);
+            }
+        }
+    }
+
+    #[test]
+    fn test_numeric_juggling() {
+        let string = |s: &str| PhpString::new(s.as_bytes().to_vec());
+        unsafe {
+            let (ten, float, word, padded) = (string("10"), string("1.5e1"), string("abc"), string(" 10 "));
+            assert_eq!(php_string_to_int(float), 15);
+            assert_eq!(php_string_to_float(ten), 10.0);
+            assert_eq!(php_string_to_int(word), 0);
+            assert_eq!(php_string_compare(ten, float), -1);
+            assert_eq!(php_string_compare(ten, padded), 0);
+            assert_eq!(php_string_compare(word, ten), 1);
+            assert!(!php_string_equal(ten, padded));
+            for s in [ten, float, word, padded] {
+                php_string_release(s);
+            }
+        }
+    }
+}
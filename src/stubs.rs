@@ -0,0 +1,124 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Signature-only stub files.
+//!
+//! A `.phpstub` file declares functions, classes and interfaces implemented
+//! outside the program, such as extensions or libraries that are not
+//! compiled yet. Calls into them are type-checked like calls to the
+//! program's own declarations, but no code is generated for them: their
+//! functions are declared as external symbols resolved at link time.
+
+use std::path::Path;
+use log::warn;
+use crate::ast::{AstNode, FunctionDecl, Statement};
+
+/// Extension of stub files
+pub const STUB_EXTENSION: &str = "phpstub";
+
+/// Check if a path names a stub file
+pub fn is_stub_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(STUB_EXTENSION))
+}
+
+/// Keep the declarations of a parsed stub file, without bodies
+///
+/// Anything else is ignored with a warning: stubs never run.
+pub fn stub_declarations(node: AstNode, file: &str, out: &mut Vec<AstNode>) {
+    match node {
+        AstNode::Program(nodes) => {
+            for node in nodes {
+                stub_declarations(node, file, out);
+            }
+        }
+        AstNode::Namespace(mut ns) => {
+            let mut statements = Vec::new();
+            for node in std::mem::take(&mut ns.statements) {
+                stub_declarations(node, file, &mut statements);
+            }
+            ns.statements = statements;
+            out.push(AstNode::Namespace(ns));
+        }
+        AstNode::Function(mut decl) => {
+            clear_body(&mut decl);
+            out.push(AstNode::Function(decl));
+        }
+        AstNode::Class(mut decl) => {
+            decl.methods.iter_mut().for_each(clear_body);
+            out.push(AstNode::Class(decl));
+        }
+        AstNode::Interface(_) | AstNode::Use(_) => out.push(node),
+        AstNode::Trait(mut decl) => {
+            decl.methods.iter_mut().for_each(clear_body);
+            out.push(AstNode::Trait(decl));
+        }
+        AstNode::Enum(mut decl) => {
+            decl.methods.iter_mut().for_each(clear_body);
+            out.push(AstNode::Enum(decl));
+        }
+        _ => warn!("Ignoring code outside declarations in stub file {}", file),
+    }
+}
+
+/// Functions declared by stubs
+pub fn stub_functions(stubs: &[AstNode]) -> Vec<&FunctionDecl> {
+    let mut functions = Vec::new();
+    for node in stubs {
+        match node {
+            AstNode::Function(decl) => functions.push(decl),
+            AstNode::Namespace(ns) => functions.extend(stub_functions(&ns.statements)),
+            _ => {}
+        }
+    }
+    functions
+}
+
+fn clear_body(decl: &mut FunctionDecl) {
+    *decl.body = Statement::Block(Vec::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Expression, Literal, Visibility};
+    use crate::types::Type;
+
+    #[test]
+    fn test_stub_declarations() {
+        assert!(is_stub_path(Path::new("ext/redis.phpstub")));
+        assert!(!is_stub_path(Path::new("lib/redis.php")));
+
+        let echo = Statement::Echo(vec![Expression::Literal(Literal::String("hi".to_string()))]);
+        let function = FunctionDecl {
+            name: "redis_connect".to_string(),
+            parameters: vec![],
+            return_type: Some(Type::Bool),
+            body: Box::new(Statement::Block(vec![echo.clone()])),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        };
+        let program = AstNode::Program(vec![AstNode::Function(function), AstNode::Statement(Box::new(echo))]);
+
+        let mut stubs = Vec::new();
+        stub_declarations(program, "redis.phpstub", &mut stubs);
+        assert_eq!(stubs.len(), 1);
+        let functions = stub_functions(&stubs);
+        assert_eq!(functions.len(), 1);
+        assert!(matches!(functions[0].body.as_ref(), Statement::Block(statements) if statements.is_empty()));
+    }
+}
@@ -0,0 +1,487 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Self tail calls as loops.
+//!
+//! `return f(...)` inside `f` itself, or `return self::f(...)` inside a
+//! static method `f`, becomes a jump back to the start of the body: the body
+//! is wrapped in `while (true)`, and the call assigns its arguments to the
+//! parameters and continues that loop. Deep recursion then runs in constant
+//! stack space with every backend.
+//!
+//! ```php
+//! function sum($n, $acc) {
+//!     if ($n == 0) return $acc;
+//!     return sum($n - 1, $acc + $n);
+//! }
+//! // is generated as
+//! function sum($n, $acc) {
+//!     while (true) {
+//!         if ($n == 0) return $acc;
+//!         $tail.0 = $n - 1; $acc = $acc + $n; $n = $tail.0;
+//!         continue;
+//!     }
+//! }
+//! ```
+//!
+//! A real call starts with fresh locals, so a function is only rewritten
+//! when its body cannot tell the difference: every local is assigned
+//! unconditionally before it is read, nothing is bound by reference,
+//! captured by a closure or imported with `global` or `static`, and the
+//! function does not inspect its own frame. Calls inside `try` or `foreach`
+//! are left alone, as are calls without an argument for a parameter that
+//! has no default.
+
+use std::collections::HashSet;
+use crate::ast::visit::{walk_expression_ref, walk_statement, walk_statement_ref};
+use crate::ast::{AssignmentOperator, AstNode, BinaryOperator, Expression, FunctionDecl, Literal, Parameter, Statement, Visitor, VisitorMut};
+use crate::devirtualize::Aliases;
+use crate::specialize::Introspection;
+
+/// Prefix of the variables holding the arguments of a rewritten call while
+/// the parameters are assigned; `.` cannot appear in PHP variable names
+const TEMPORARY_PREFIX: &str = "tail.";
+
+/// Rewrite self tail calls of functions and static methods into loops
+///
+/// Returns the number of calls rewritten.
+pub fn eliminate_tail_calls(ast: &mut [AstNode]) -> usize {
+    ast.iter_mut().map(eliminate_in_node).sum()
+}
+
+fn eliminate_in_node(node: &mut AstNode) -> usize {
+    match node {
+        AstNode::Program(nodes) => eliminate_tail_calls(nodes),
+        AstNode::Function(decl) => {
+            let callee = Callee::Function(decl.name.clone());
+            eliminate_in_function(decl, &callee)
+        }
+        AstNode::Class(class) => {
+            let name = class.name.clone();
+            class.methods.iter_mut()
+                .filter(|method| method.is_static)
+                .map(|method| {
+                    let callee = Callee::StaticMethod { class: name.clone(), method: method.name.clone() };
+                    eliminate_in_function(method, &callee)
+                })
+                .sum()
+        }
+        _ => 0,
+    }
+}
+
+/// What a call must name to call the function being rewritten
+enum Callee {
+    Function(String),
+    /// Called through `self::` or the class name; `static::` may reach an
+    /// override
+    StaticMethod { class: String, method: String },
+}
+
+impl Callee {
+    fn arguments<'e>(&self, expr: &'e Expression) -> Option<&'e [Expression]> {
+        match (self, expr) {
+            (Callee::Function(function), Expression::FunctionCall { name, arguments }) => match name.as_ref() {
+                Expression::Constant(name) if same_name(name, function) => Some(arguments.as_slice()),
+                _ => None,
+            },
+            (Callee::StaticMethod { class, method }, Expression::StaticCall { class: target, method: called, arguments }) => {
+                let Expression::Constant(target) = target.as_ref() else { return None };
+                let own_class = target.eq_ignore_ascii_case("self") || same_name(target, class);
+                (own_class && called.eq_ignore_ascii_case(method)).then_some(arguments.as_slice())
+            }
+            _ => None,
+        }
+    }
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim_start_matches('\\').eq_ignore_ascii_case(b.trim_start_matches('\\'))
+}
+
+fn eliminate_in_function(decl: &mut FunctionDecl, callee: &Callee) -> usize {
+    if !is_loopable(decl) {
+        return 0;
+    }
+    let mut sites = TailSites { callee, parameters: &decl.parameters, loops: 0, blocked: 0, rewritten: 0 };
+    sites.visit_statement(&mut decl.body);
+    let rewritten = sites.rewritten;
+    if rewritten > 0 {
+        // Falling off the end still returns instead of looping
+        let body = std::mem::replace(decl.body.as_mut(), Statement::Block(Vec::new()));
+        *decl.body = Statement::While {
+            condition: Box::new(Expression::Literal(Literal::Bool(true))),
+            body: Box::new(Statement::Block(vec![body, Statement::Return(None)])),
+        };
+    }
+    rewritten
+}
+
+/// Whether running the body again in the same frame behaves like a new call
+fn is_loopable(decl: &FunctionDecl) -> bool {
+    if decl.parameters.iter().any(|p| p.is_reference || p.is_variadic) {
+        return false;
+    }
+    let mut aliases = Aliases::default();
+    aliases.visit_statement(&decl.body);
+    let mut introspection = Introspection::default();
+    introspection.visit_statement(&decl.body);
+    if !aliases.variables.is_empty() || aliases.dynamic_scope || introspection.found {
+        return false;
+    }
+    let mut definitions = Definitions {
+        defined: decl.parameters.iter().map(|p| p.name.clone()).collect(),
+        conditional: 0,
+        is_opaque: false,
+    };
+    definitions.visit_statement(&decl.body);
+    !definitions.is_opaque
+}
+
+/// Locals assigned before they are read, in source order
+///
+/// Only assignments outside branches and loops define a variable. A read
+/// of anything else, or a construct that keeps state between calls, makes
+/// the body opaque.
+struct Definitions {
+    defined: HashSet<String>,
+    /// Number of branches and loops around the code being visited
+    conditional: usize,
+    is_opaque: bool,
+}
+
+impl Definitions {
+    fn conditionally(&mut self, visit: impl FnOnce(&mut Self)) {
+        self.conditional += 1;
+        visit(self);
+        self.conditional -= 1;
+    }
+}
+
+impl Visitor for Definitions {
+    // Nested functions and classes are scopes of their own
+    fn visit_node(&mut self, _node: &AstNode) {}
+
+    fn visit_statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Global(_) | Statement::Static(_) => self.is_opaque = true,
+            Statement::For { init, condition, update, body } => {
+                init.iter().for_each(|expr| self.visit_expression(expr));
+                self.conditionally(|this| {
+                    condition.iter().chain(update.iter()).for_each(|expr| this.visit_expression(expr));
+                    this.visit_statement(body);
+                });
+            }
+            Statement::Foreach { array, key, value, body } => {
+                self.visit_expression(array);
+                let bound: Vec<String> = key.iter().chain(std::iter::once(value))
+                    .filter(|name| !self.defined.contains(*name))
+                    .cloned()
+                    .collect();
+                self.defined.extend(bound.iter().cloned());
+                self.conditionally(|this| this.visit_statement(body));
+                bound.iter().for_each(|name| {
+                    self.defined.remove(name);
+                });
+            }
+            Statement::If { .. } | Statement::While { .. } | Statement::DoWhile { .. } | Statement::Switch { .. }
+                | Statement::Match { .. } | Statement::Try { .. } => {
+                self.conditionally(|this| walk_statement_ref(this, stmt));
+            }
+            _ => walk_statement_ref(self, stmt),
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Variable(name) => self.is_opaque |= !self.defined.contains(name),
+            Expression::Assignment { target, op: AssignmentOperator::Assign, value } => {
+                self.visit_expression(value);
+                match target.as_ref() {
+                    Expression::Variable(name) if self.conditional == 0 => {
+                        self.defined.insert(name.clone());
+                    }
+                    Expression::Variable(_) => {}
+                    _ => self.visit_expression(target),
+                }
+            }
+            Expression::BinaryOp { left, op: BinaryOperator::And | BinaryOperator::Or | BinaryOperator::Coalesce, right }
+                | Expression::NullCoalescing { left, right }
+                | Expression::ShortTernary { condition: left, false_expr: right } => {
+                self.visit_expression(left);
+                self.conditionally(|this| this.visit_expression(right));
+            }
+            Expression::Ternary { condition, true_expr, false_expr } => {
+                self.visit_expression(condition);
+                self.conditionally(|this| {
+                    this.visit_expression(true_expr);
+                    this.visit_expression(false_expr);
+                });
+            }
+            Expression::Closure(_) | Expression::Include { .. } | Expression::Yield { .. } => self.is_opaque = true,
+            _ => walk_expression_ref(self, expr),
+        }
+    }
+}
+
+/// Rewrites the self tail calls of one body
+struct TailSites<'a> {
+    callee: &'a Callee,
+    parameters: &'a [Parameter],
+    /// Loops and `switch` statements around the statement being visited,
+    /// which `continue` counts
+    loops: usize,
+    /// `try` and `foreach` statements around it, which a jump cannot leave
+    blocked: usize,
+    rewritten: usize,
+}
+
+impl TailSites<'_> {
+    /// Statements assigning the arguments to the parameters and starting the
+    /// body again
+    fn jump(&self, arguments: &[Expression]) -> Option<Statement> {
+        if arguments.len() > self.parameters.len() {
+            return None;
+        }
+        let values: Vec<Expression> = self.parameters.iter().enumerate()
+            .map(|(i, parameter)| arguments.get(i).or(parameter.default_value.as_ref()).cloned())
+            .collect::<Option<_>>()?;
+
+        // An argument is evaluated into a temporary when a later argument
+        // reads its parameter, or it reads a parameter already assigned
+        let reads: Vec<HashSet<String>> = values.iter().map(variables_read).collect();
+        let mut assigned = HashSet::new();
+        let mut statements = Vec::new();
+        let mut pending = Vec::new();
+        for (i, (parameter, value)) in self.parameters.iter().zip(values).enumerate() {
+            if matches!(&value, Expression::Variable(name) if *name == parameter.name) {
+                continue;
+            }
+            let read_later = reads[i + 1..].iter().any(|names| names.contains(&parameter.name));
+            let reads_assigned = reads[i].iter().any(|name| assigned.contains(name));
+            if read_later || reads_assigned {
+                let temporary = format!("{}{}", TEMPORARY_PREFIX, i);
+                statements.push(assign(&temporary, value));
+                pending.push(assign(&parameter.name, Expression::Variable(temporary)));
+            } else {
+                statements.push(assign(&parameter.name, value));
+                assigned.insert(parameter.name.clone());
+            }
+        }
+        statements.extend(pending);
+        let levels = self.loops + 1;
+        let level = (levels > 1).then(|| Box::new(Expression::Literal(Literal::Int(levels as i64))));
+        statements.push(Statement::Continue(level));
+        Some(Statement::Block(statements))
+    }
+}
+
+impl VisitorMut for TailSites<'_> {
+    fn visit_node(&mut self, _node: &mut AstNode) {}
+
+    fn visit_statement(&mut self, stmt: &mut Statement) {
+        match stmt {
+            Statement::Return(Some(expr)) if self.blocked == 0 => {
+                let jump = self.callee.arguments(expr).and_then(|arguments| self.jump(arguments));
+                if let Some(jump) = jump {
+                    *stmt = jump;
+                    self.rewritten += 1;
+                }
+            }
+            Statement::While { .. } | Statement::DoWhile { .. } | Statement::For { .. } | Statement::Switch { .. } => {
+                self.loops += 1;
+                walk_statement(self, stmt);
+                self.loops -= 1;
+            }
+            Statement::Foreach { .. } | Statement::Try { .. } => {
+                self.blocked += 1;
+                walk_statement(self, stmt);
+                self.blocked -= 1;
+            }
+            _ => walk_statement(self, stmt),
+        }
+    }
+
+    // Calls in expressions are not in tail position
+    fn visit_expression(&mut self, _expr: &mut Expression) {}
+}
+
+fn assign(name: &str, value: Expression) -> Statement {
+    Statement::Expression(Box::new(Expression::Assignment {
+        target: Box::new(Expression::Variable(name.to_string())),
+        op: AssignmentOperator::Assign,
+        value: Box::new(value),
+    }))
+}
+
+/// Names of the variables an expression reads
+fn variables_read(expr: &Expression) -> HashSet<String> {
+    struct Reads(HashSet<String>);
+
+    impl Visitor for Reads {
+        fn visit_expression(&mut self, expr: &Expression) {
+            if let Expression::Variable(name) = expr {
+                self.0.insert(name.clone());
+            }
+            walk_expression_ref(self, expr);
+        }
+    }
+
+    let mut reads = Reads(HashSet::new());
+    reads.visit_expression(expr);
+    reads.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Visibility;
+
+    fn variable(name: &str) -> Box<Expression> {
+        Box::new(Expression::Variable(name.to_string()))
+    }
+
+    fn int(n: i64) -> Box<Expression> {
+        Box::new(Expression::Literal(Literal::Int(n)))
+    }
+
+    fn binary(left: Box<Expression>, op: BinaryOperator, right: Box<Expression>) -> Box<Expression> {
+        Box::new(Expression::BinaryOp { left, op, right })
+    }
+
+    fn call(name: &str, arguments: Vec<Expression>) -> Box<Expression> {
+        Box::new(Expression::FunctionCall { name: Box::new(Expression::Constant(name.to_string())), arguments })
+    }
+
+    fn function(name: &str, parameters: &[&str], body: Vec<Statement>) -> FunctionDecl {
+        FunctionDecl {
+            name: name.to_string(),
+            parameters: parameters.iter().map(|name| Parameter {
+                name: name.to_string(),
+                typ: None,
+                default_value: None,
+                is_reference: false,
+                is_variadic: false,
+            }).collect(),
+            return_type: None,
+            body: Box::new(Statement::Block(body)),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        }
+    }
+
+    /// function sum($n, $acc) { if ($n == 0) return $acc; return sum($n - 1, $acc + $n); }
+    fn sum() -> FunctionDecl {
+        function("sum", &["n", "acc"], vec![
+            Statement::If {
+                condition: binary(variable("n"), BinaryOperator::Equal, int(0)),
+                then_branch: Box::new(Statement::Return(Some(variable("acc")))),
+                else_branch: None,
+            },
+            Statement::Return(Some(call("SUM", vec![
+                *binary(variable("n"), BinaryOperator::Sub, int(1)),
+                *binary(variable("acc"), BinaryOperator::Add, variable("n")),
+            ]))),
+        ])
+    }
+
+    fn assigned(stmt: &Statement) -> (String, String) {
+        let Statement::Expression(expr) = stmt else { panic!("not an assignment: {:?}", stmt) };
+        let Expression::Assignment { target, value, .. } = expr.as_ref() else { panic!("not an assignment: {:?}", expr) };
+        let Expression::Variable(target) = target.as_ref() else { panic!("not a variable: {:?}", target) };
+        let value = match value.as_ref() {
+            Expression::Variable(name) => name.clone(),
+            _ => "...".to_string(),
+        };
+        (target.clone(), value)
+    }
+
+    #[test]
+    fn test_tail_call_becomes_loop() {
+        let mut ast = vec![AstNode::Function(sum())];
+        assert_eq!(eliminate_tail_calls(&mut ast), 1);
+
+        let AstNode::Function(decl) = &ast[0] else { unreachable!() };
+        let Statement::While { condition, body } = decl.body.as_ref() else { panic!("no loop: {:?}", decl.body) };
+        assert!(matches!(condition.as_ref(), Expression::Literal(Literal::Bool(true))));
+        let Statement::Block(body) = body.as_ref() else { unreachable!() };
+        assert!(matches!(body[1], Statement::Return(None)));
+        let Statement::Block(original) = &body[0] else { unreachable!() };
+        let Statement::Block(jump) = &original[1] else { panic!("call kept: {:?}", original[1]) };
+
+        // $n is read by the second argument, so it goes through a temporary
+        let pairs: Vec<(String, String)> = jump[..3].iter().map(assigned).collect();
+        assert_eq!(pairs, [
+            ("tail.0".to_string(), "...".to_string()),
+            ("acc".to_string(), "...".to_string()),
+            ("n".to_string(), "tail.0".to_string()),
+        ]);
+        assert!(matches!(jump[3], Statement::Continue(None)));
+    }
+
+    #[test]
+    fn test_continue_counts_enclosing_loops() {
+        // function spin($n) { while (true) { return spin($n); } }
+        let mut ast = vec![AstNode::Function(function("spin", &["n"], vec![Statement::While {
+            condition: Box::new(Expression::Literal(Literal::Bool(true))),
+            body: Box::new(Statement::Return(Some(call("spin", vec![*binary(variable("n"), BinaryOperator::Add, int(1))])))),
+        }]))];
+        assert_eq!(eliminate_tail_calls(&mut ast), 1);
+
+        let AstNode::Function(decl) = &ast[0] else { unreachable!() };
+        let Statement::While { body, .. } = decl.body.as_ref() else { unreachable!() };
+        let Statement::Block(body) = body.as_ref() else { unreachable!() };
+        let Statement::Block(original) = &body[0] else { unreachable!() };
+        let Statement::While { body: inner, .. } = &original[0] else { unreachable!() };
+        let Statement::Block(jump) = inner.as_ref() else { panic!("call kept: {:?}", inner) };
+        let Some(Statement::Continue(Some(level))) = jump.last() else { panic!("no continue: {:?}", jump) };
+        assert!(matches!(level.as_ref(), Expression::Literal(Literal::Int(2))));
+    }
+
+    #[test]
+    fn test_unsafe_functions_are_kept() {
+        let tail = || Statement::Return(Some(call("f", vec![*int(1)])));
+        // A local read before it is assigned would see the previous call's value
+        let stale = function("f", &["n"], vec![
+            Statement::If {
+                condition: variable("n"),
+                then_branch: Box::new(Statement::Expression(Box::new(Expression::Assignment {
+                    target: variable("seen"),
+                    op: AssignmentOperator::Assign,
+                    value: int(1),
+                }))),
+                else_branch: None,
+            },
+            Statement::Echo(vec![*variable("seen")]),
+            tail(),
+        ]);
+        let global = function("f", &["n"], vec![Statement::Global(vec!["total".to_string()]), tail()]);
+        let inside_try = function("f", &["n"], vec![Statement::Try {
+            try_block: Box::new(tail()),
+            catch_blocks: vec![],
+            finally_block: Some(Box::new(Statement::Block(vec![]))),
+        }]);
+        // Not in tail position, and missing an argument without a default
+        let not_tail = function("f", &["n"], vec![Statement::Return(Some(binary(int(1), BinaryOperator::Add, call("f", vec![*int(1)]))))]);
+        let missing = function("f", &["n", "m"], vec![tail()]);
+
+        for decl in [stale, global, inside_try, not_tail, missing] {
+            assert_eq!(eliminate_tail_calls(&mut [AstNode::Function(decl)]), 0);
+        }
+    }
+}
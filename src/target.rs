@@ -0,0 +1,185 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Target triples and data layouts of generated modules.
+//!
+//! Every module states the triple it is compiled for and, when the target
+//! is known here, its data layout. For other triples the layout is left out
+//! and `llc` takes the target's default, which is still correct; a layout
+//! of the wrong target is accepted silently and miscompiles.
+
+/// Triple of the machine php2ir runs on
+pub fn host_triple() -> String {
+    let arch = match std::env::consts::ARCH {
+        "x86" => "i686",
+        arch => arch,
+    };
+    match std::env::consts::OS {
+        "linux" if arch == "x86_64" => "x86_64-pc-linux-gnu".to_string(),
+        "linux" => format!("{}-unknown-linux-gnu", arch),
+        "macos" => format!("{}-apple-darwin", arch),
+        "windows" if cfg!(target_env = "msvc") => format!("{}-pc-windows-msvc", arch),
+        "windows" => format!("{}-pc-windows-gnu", arch),
+        os => format!("{}-unknown-{}", arch, os),
+    }
+}
+
+/// Object file format of a target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectFormat {
+    Elf,
+    MachO,
+    Coff,
+    Wasm,
+}
+
+/// Target a module is generated for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetSpec {
+    triple: String,
+}
+
+impl TargetSpec {
+    /// Target of a `--target` value; `native` is the host
+    pub fn from_target(target: &str) -> Self {
+        match target {
+            "" | "native" => Self::host(),
+            triple => Self { triple: triple.to_string() },
+        }
+    }
+
+    /// The machine php2ir runs on
+    pub fn host() -> Self {
+        Self { triple: host_triple() }
+    }
+
+    pub fn triple(&self) -> &str {
+        &self.triple
+    }
+
+    /// Architecture, the first component of the triple
+    pub fn arch(&self) -> &str {
+        self.triple.split('-').next().unwrap_or("")
+    }
+
+    /// Whether the triple names `component` (an OS or environment) after the architecture
+    pub fn has(&self, component: &str) -> bool {
+        self.triple.split('-').skip(1).any(|part| part.starts_with(component))
+    }
+
+    /// Format of the target's object files and executables
+    pub fn object_format(&self) -> ObjectFormat {
+        if self.arch().starts_with("wasm") {
+            ObjectFormat::Wasm
+        } else if self.has("darwin") || self.has("macos") || self.has("ios") {
+            ObjectFormat::MachO
+        } else if self.has("windows") {
+            ObjectFormat::Coff
+        } else {
+            ObjectFormat::Elf
+        }
+    }
+
+    /// Whether this is the machine php2ir runs on
+    pub fn is_host(&self) -> bool {
+        self.triple == host_triple()
+    }
+
+    /// LLVM data layout of the target, if known
+    pub fn datalayout(&self) -> Option<&'static str> {
+        let mut parts = self.triple.split('-');
+        let arch = parts.next().unwrap_or("");
+        let rest: Vec<&str> = parts.collect();
+        let has = |name: &str| rest.iter().any(|part| part.starts_with(name));
+        let (darwin, windows) = (has("darwin") || has("macos") || has("ios"), has("windows"));
+
+        let layout = match arch {
+            "x86_64" | "amd64" if darwin => "e-m:o-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128",
+            "x86_64" | "amd64" if windows => "e-m:w-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128",
+            "x86_64" | "amd64" => "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128",
+            "aarch64" | "arm64" if darwin => "e-m:o-i64:64-i128:128-n32:64-S128",
+            "aarch64" if !windows => "e-m:e-i8:8:32-i16:16:32-i64:64-i128:128-n32:64-S128",
+            "i386" | "i586" | "i686" if !darwin && !windows => {
+                "e-m:e-p:32:32-p270:32:32-p271:32:32-p272:64:64-f64:32:64-f80:32-n8:16:32-S128"
+            }
+            "arm" | "armv7" | "armv7a" if !darwin && !windows => "e-m:e-p:32:32-Fi8-i64:64-v128:64:128-a:0:32-n32-S64",
+            "wasm32" => "e-m:e-p:32:32-p10:8:8-p20:8:8-i64:64-n32:64-S128-ni:1:10:20",
+            "riscv64" => "e-m:e-p:64:64-i64:64-i128:128-n64-S128",
+            "riscv32" => "e-m:e-p:32:32-i64:64-n32-S128",
+            "powerpc64le" => "e-m:e-i64:64-n32:64-S128-v256:256:256-v512:512:512",
+            "s390x" => "E-m:e-i1:8:16-i8:8:16-i64:64-f128:64-a:8:16-n32:64",
+            _ => return None,
+        };
+        Some(layout)
+    }
+
+    /// `target datalayout` and `target triple` lines of a module
+    pub fn module_header(&self) -> String {
+        let mut header = String::new();
+        if let Some(layout) = self.datalayout() {
+            header.push_str(&format!("target datalayout = \"{}\"\n", layout));
+        }
+        header.push_str(&format!("target triple = \"{}\"\n", self.triple));
+        header
+    }
+}
+
+impl Default for TargetSpec {
+    fn default() -> Self {
+        Self::host()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_native_is_host() {
+        let target = TargetSpec::from_target("native");
+        assert_eq!(target.triple(), host_triple());
+        assert!(target.is_host());
+        assert!(!TargetSpec::from_target("wasm32-unknown-unknown").is_host());
+    }
+
+    #[test]
+    fn test_datalayout_follows_triple() {
+        let layout = |triple: &str| TargetSpec::from_target(triple).datalayout();
+        assert_eq!(layout("aarch64-unknown-linux-gnu"), Some("e-m:e-i8:8:32-i16:16:32-i64:64-i128:128-n32:64-S128"));
+        assert_eq!(layout("aarch64-apple-darwin"), Some("e-m:o-i64:64-i128:128-n32:64-S128"));
+        assert!(layout("x86_64-pc-windows-gnu").unwrap().starts_with("e-m:w-"));
+        assert!(layout("armv7-unknown-linux-gnueabihf").unwrap().starts_with("e-m:e-p:32:32-"));
+        assert_eq!(layout("i686-pc-windows-msvc"), None);
+    }
+
+    #[test]
+    fn test_object_format() {
+        let format = |triple: &str| TargetSpec::from_target(triple).object_format();
+        assert_eq!(format("aarch64-unknown-linux-gnu"), ObjectFormat::Elf);
+        assert_eq!(format("arm64-apple-macosx13.0"), ObjectFormat::MachO);
+        assert_eq!(format("x86_64-pc-windows-msvc"), ObjectFormat::Coff);
+        assert_eq!(format("wasm32-unknown-unknown"), ObjectFormat::Wasm);
+        assert!(TargetSpec::from_target("x86_64-pc-windows-gnu").has("gnu"));
+    }
+
+    #[test]
+    fn test_module_header() {
+        let header = TargetSpec::from_target("riscv64-unknown-linux-gnu").module_header();
+        assert_eq!(header, "target datalayout = \"e-m:e-p:64:64-i64:64-i128:128-n64-S128\"\ntarget triple = \"riscv64-unknown-linux-gnu\"\n");
+        let header = TargetSpec::from_target("sparc64-unknown-linux-gnu").module_header();
+        assert_eq!(header, "target triple = \"sparc64-unknown-linux-gnu\"\n");
+    }
+}
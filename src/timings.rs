@@ -0,0 +1,286 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Phase timings and statistics of a compilation, shown by `--timings`.
+//!
+//! Every phase adds up its time over all the files of the compilation:
+//!
+//! - parse: reading and parsing source files
+//! - resolve: splicing in includes and autoloaded classes, docblock types,
+//!   name resolution and compile-time defines
+//! - typecheck: semantic analysis
+//! - optimize: the AST passes, and the LLVM pipeline for IR written out as such
+//! - irgen: lowering the AST to IR
+//! - codegen: the LLVM pipeline and code generation of compiled modules
+//! - link: the linker
+//!
+//! Memory is the peak resident set size of the process at the end of the
+//! phase, where the platform reports it (Linux).
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::time::Duration;
+use crate::ast::visit::{walk_expression, walk_function, walk_node, walk_statement};
+use crate::ast::{AstNode, Expression, FunctionDecl, Statement, VisitorMut};
+use crate::utils::time::format_duration;
+
+/// Phase of a compilation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Parse,
+    Resolve,
+    TypeCheck,
+    Optimize,
+    IrGen,
+    Codegen,
+    Link,
+}
+
+impl Phase {
+    /// Every phase, in pipeline order
+    pub const ALL: [Phase; 7] = [
+        Phase::Parse,
+        Phase::Resolve,
+        Phase::TypeCheck,
+        Phase::Optimize,
+        Phase::IrGen,
+        Phase::Codegen,
+        Phase::Link,
+    ];
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Phase::Parse => "parse",
+            Phase::Resolve => "resolve",
+            Phase::TypeCheck => "typecheck",
+            Phase::Optimize => "optimize",
+            Phase::IrGen => "irgen",
+            Phase::Codegen => "codegen",
+            Phase::Link => "link",
+        };
+        f.pad(name)
+    }
+}
+
+/// Sizes of what a compilation processed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    /// Source files parsed
+    pub files: usize,
+
+    /// Nodes, statements and expressions of the ASTs lowered to IR
+    pub ast_nodes: usize,
+
+    /// Functions and methods lowered to IR
+    pub functions: usize,
+
+    /// Instructions of the IR generated
+    pub ir_instructions: usize,
+}
+
+/// Time and memory of the phases of a compilation, with its [`Counts`]
+///
+/// Recording takes `&self`, so a phase can be timed from code that only
+/// borrows the compiler.
+#[derive(Debug, Default)]
+pub struct Timings {
+    durations: RefCell<[Duration; Phase::ALL.len()]>,
+    memory: RefCell<[Option<u64>; Phase::ALL.len()]>,
+    counts: Cell<Counts>,
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget everything recorded, for the next compilation
+    pub fn clear(&self) {
+        *self.durations.borrow_mut() = Default::default();
+        *self.memory.borrow_mut() = Default::default();
+        self.counts.set(Counts::default());
+    }
+
+    /// Add `duration` to the time of `phase`, which just ended
+    pub fn add(&self, phase: Phase, duration: Duration) {
+        let index = Self::index(phase);
+        self.durations.borrow_mut()[index] += duration;
+        if let Some(memory) = peak_memory() {
+            let mut recorded = self.memory.borrow_mut();
+            recorded[index] = Some(recorded[index].map_or(memory, |previous| previous.max(memory)));
+        }
+    }
+
+    /// Time spent in `phase` so far
+    pub fn duration(&self, phase: Phase) -> Duration {
+        self.durations.borrow()[Self::index(phase)]
+    }
+
+    /// Time spent in all phases
+    pub fn total(&self) -> Duration {
+        self.durations.borrow().iter().sum()
+    }
+
+    pub fn counts(&self) -> Counts {
+        self.counts.get()
+    }
+
+    /// Count a parsed source file
+    pub fn count_file(&self) {
+        let mut counts = self.counts.get();
+        counts.files += 1;
+        self.counts.set(counts);
+    }
+
+    /// Count the nodes and functions of an AST lowered to IR
+    pub fn count_ast(&self, ast: &mut [AstNode]) {
+        let mut counter = NodeCounter::default();
+        for node in ast.iter_mut() {
+            counter.visit_node(node);
+        }
+        let mut counts = self.counts.get();
+        counts.ast_nodes += counter.nodes;
+        counts.functions += counter.functions;
+        self.counts.set(counts);
+    }
+
+    /// Count the instructions of textual LLVM IR
+    pub fn count_ir(&self, ir: &str) {
+        let mut counts = self.counts.get();
+        counts.ir_instructions += ir_instructions(ir);
+        self.counts.set(counts);
+    }
+
+    fn index(phase: Phase) -> usize {
+        Phase::ALL.iter().position(|p| *p == phase).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        let memory = self.memory.borrow();
+        writeln!(f, "{:<10} {:>10} {:>7} {:>12}", "phase", "time", "share", "peak memory")?;
+        for (index, phase) in Phase::ALL.iter().enumerate() {
+            let duration = self.duration(*phase);
+            let share = if total.is_zero() { 0.0 } else { duration.as_secs_f64() / total.as_secs_f64() * 100.0 };
+            let memory = memory[index].map(format_memory).unwrap_or_else(|| "-".to_string());
+            writeln!(f, "{:<10} {:>10} {:>6.1}% {:>12}", phase, format_duration(duration), share, memory)?;
+        }
+        writeln!(f, "{:<10} {:>10}", "total", format_duration(total))?;
+        let counts = self.counts();
+        writeln!(
+            f,
+            "{} file(s), {} AST nodes, {} function(s), {} IR instructions",
+            counts.files, counts.ast_nodes, counts.functions, counts.ir_instructions
+        )
+    }
+}
+
+/// Counts the nodes of an AST
+#[derive(Default)]
+struct NodeCounter {
+    nodes: usize,
+    functions: usize,
+}
+
+impl VisitorMut for NodeCounter {
+    fn visit_node(&mut self, node: &mut AstNode) {
+        self.nodes += 1;
+        walk_node(self, node);
+    }
+
+    fn visit_function(&mut self, function: &mut FunctionDecl) {
+        self.functions += 1;
+        walk_function(self, function);
+    }
+
+    fn visit_statement(&mut self, stmt: &mut Statement) {
+        self.nodes += 1;
+        walk_statement(self, stmt);
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression) {
+        self.nodes += 1;
+        walk_expression(self, expr);
+    }
+}
+
+/// Instructions in the function bodies of textual LLVM IR
+fn ir_instructions(ir: &str) -> usize {
+    let mut in_function = false;
+    let mut count = 0;
+    for line in ir.lines() {
+        if line.starts_with("define ") {
+            in_function = true;
+        } else if line.starts_with('}') {
+            in_function = false;
+        } else if in_function && line.starts_with(' ') {
+            let instruction = line.trim_start();
+            if !instruction.is_empty() && !instruction.starts_with(';') {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+/// Peak resident set size of the process in bytes
+fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kilobytes: u64 = line["VmHWM:".len()..].trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+fn format_memory(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Literal;
+
+    #[test]
+    fn test_timings_report() {
+        let timings = Timings::new();
+        timings.add(Phase::Parse, Duration::from_millis(30));
+        timings.add(Phase::Parse, Duration::from_millis(10));
+        timings.add(Phase::Link, Duration::from_millis(60));
+        assert_eq!(timings.duration(Phase::Parse), Duration::from_millis(40));
+        assert_eq!(timings.total(), Duration::from_millis(100));
+
+        let mut ast = vec![AstNode::Statement(Box::new(Statement::Echo(vec![
+            Expression::Literal(Literal::Int(1)),
+            Expression::Variable("x".to_string()),
+        ])))];
+        timings.count_file();
+        timings.count_ast(&mut ast);
+        timings.count_ir("@g = global i64 0\n\ndefine i64 @main() {\nentry:\n  ; body\n  %1 = load i64, i64* @g\n  ret i64 %1\n}\n");
+        assert_eq!(timings.counts(), Counts { files: 1, ast_nodes: 4, functions: 0, ir_instructions: 2 });
+
+        let report = timings.to_string();
+        assert!(report.contains("parse"));
+        assert!(report.contains("40.0%"));
+        assert!(report.contains("1 file(s), 4 AST nodes, 0 function(s), 2 IR instructions"));
+
+        timings.clear();
+        assert!(timings.total().is_zero());
+    }
+}
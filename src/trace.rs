@@ -0,0 +1,226 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Function entry/exit tracing for instrumented binaries.
+//!
+//! Code compiled with `--instrument=trace` calls `php2ir_trace_enter` and
+//! `php2ir_trace_exit` around every PHP function. The events are collected in
+//! memory and written as Chrome trace-event JSON (viewable in Perfetto or
+//! `chrome://tracing`) when the program finishes.
+
+use std::ffi::CStr;
+use std::io::Write;
+use std::os::raw::{c_char, c_int};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Instant;
+use serde_json::json;
+
+/// Environment variable naming the trace output file
+pub const TRACE_FILE_ENV: &str = "PHP2IR_TRACE_FILE";
+
+/// Trace output file used when the environment variable is unset
+pub const DEFAULT_TRACE_FILE: &str = "php2ir-trace.json";
+
+/// Instrumentation inserted into generated code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instrumentation {
+    /// Call the trace hooks on every function entry and exit
+    Trace,
+}
+
+impl FromStr for Instrumentation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trace" => Ok(Instrumentation::Trace),
+            _ => Err(format!("unknown instrumentation '{}' (expected: trace)", s)),
+        }
+    }
+}
+
+/// Phase of a trace event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TracePhase {
+    Enter,
+    Exit,
+}
+
+impl TracePhase {
+    /// Chrome trace-event phase letter
+    fn as_str(&self) -> &'static str {
+        match self {
+            TracePhase::Enter => "B",
+            TracePhase::Exit => "E",
+        }
+    }
+}
+
+/// Single function entry or exit
+#[derive(Debug, Clone)]
+pub struct TraceEvent {
+    pub name: String,
+    pub file: String,
+    pub line: u32,
+    pub phase: TracePhase,
+    /// Microseconds since the tracer was created
+    pub timestamp: u64,
+}
+
+/// In-memory collector of trace events
+#[derive(Debug)]
+pub struct Tracer {
+    start: Instant,
+    events: Vec<TraceEvent>,
+}
+
+impl Tracer {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Record a function entry
+    pub fn enter(&mut self, name: &str, file: &str, line: u32) {
+        self.record(name, file, line, TracePhase::Enter);
+    }
+
+    /// Record a function exit
+    pub fn exit(&mut self, name: &str, file: &str, line: u32) {
+        self.record(name, file, line, TracePhase::Exit);
+    }
+
+    /// Recorded events in order
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+
+    /// Render the events as Chrome trace-event JSON
+    pub fn to_chrome_json(&self) -> String {
+        let events: Vec<_> = self.events.iter()
+            .map(|event| json!({
+                "name": event.name,
+                "cat": "php",
+                "ph": event.phase.as_str(),
+                "ts": event.timestamp,
+                "pid": std::process::id(),
+                "tid": 1,
+                "args": { "file": event.file, "line": event.line },
+            }))
+            .collect();
+
+        json!({ "traceEvents": events, "displayTimeUnit": "ms" }).to_string()
+    }
+
+    /// Write the trace to a file
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut file = std::fs::File::create(path)?;
+        file.write_all(self.to_chrome_json().as_bytes())
+    }
+
+    fn record(&mut self, name: &str, file: &str, line: u32, phase: TracePhase) {
+        self.events.push(TraceEvent {
+            name: name.to_string(),
+            file: file.to_string(),
+            line,
+            phase,
+            timestamp: self.start.elapsed().as_micros() as u64,
+        });
+    }
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Process-wide tracer used by the FFI hooks
+static TRACER: Mutex<Option<Tracer>> = Mutex::new(None);
+
+fn with_tracer(f: impl FnOnce(&mut Tracer)) {
+    if let Ok(mut guard) = TRACER.lock() {
+        f(guard.get_or_insert_with(Tracer::new));
+    }
+}
+
+/// Convert a C string from generated code, tolerating null pointers
+fn c_str(s: *const c_char) -> String {
+    if s.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(s) }.to_string_lossy().into_owned()
+}
+
+// FFI functions called by instrumented code
+#[no_mangle]
+pub extern "C" fn php2ir_trace_enter(name: *const c_char, file: *const c_char, line: c_int) {
+    with_tracer(|tracer| tracer.enter(&c_str(name), &c_str(file), line.max(0) as u32));
+}
+
+#[no_mangle]
+pub extern "C" fn php2ir_trace_exit(name: *const c_char, file: *const c_char, line: c_int) {
+    with_tracer(|tracer| tracer.exit(&c_str(name), &c_str(file), line.max(0) as u32));
+}
+
+#[no_mangle]
+pub extern "C" fn php2ir_trace_flush() -> c_int {
+    let tracer = match TRACER.lock() {
+        Ok(mut guard) => guard.take(),
+        Err(_) => return -1,
+    };
+
+    let Some(tracer) = tracer else {
+        return 0;
+    };
+    let path = std::env::var(TRACE_FILE_ENV).unwrap_or_else(|_| DEFAULT_TRACE_FILE.to_string());
+    match tracer.write_to(&path) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("php2ir: failed to write trace to {}: {}", path, e);
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_instrumentation() {
+        assert_eq!("trace".parse::<Instrumentation>(), Ok(Instrumentation::Trace));
+        assert!("coverage".parse::<Instrumentation>().is_err());
+    }
+
+    #[test]
+    fn test_chrome_json() {
+        let mut tracer = Tracer::new();
+        tracer.enter("main", "app.php", 3);
+        tracer.exit("main", "app.php", 3);
+
+        let json: serde_json::Value = serde_json::from_str(&tracer.to_chrome_json()).unwrap();
+        let events = json["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["ph"], "B");
+        assert_eq!(events[1]["ph"], "E");
+        assert_eq!(events[0]["args"]["file"], "app.php");
+    }
+}
@@ -0,0 +1,203 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Trait flattening.
+//!
+//! Code generation has no notion of traits: before a class is laid out, the
+//! methods and properties of the traits it uses are copied into it as if
+//! they were declared in the class body. Methods of the class itself win
+//! over trait methods, `insteadof` picks between traits that provide the
+//! same method, and `as` adds an alias or changes a method's visibility.
+
+use std::collections::HashMap;
+use crate::ast::{ClassDecl, FunctionDecl, TraitAdaptation, TraitDecl};
+use crate::error::{CompileError, CompileResult};
+
+/// Copy of a class with the members of its traits in its body
+///
+/// `traits` maps lowercase trait names to their declarations.
+pub fn flatten_traits(class: &ClassDecl, traits: &HashMap<String, &TraitDecl>) -> CompileResult<ClassDecl> {
+    let mut flattened = class.clone();
+    flattened.traits.clear();
+
+    // Trait methods, with the name of the trait that provides each
+    let mut methods: Vec<(String, FunctionDecl)> = Vec::new();
+    for trait_use in &class.traits {
+        let mut used = Vec::new();
+        for name in &trait_use.traits {
+            match traits.get(&key(name)) {
+                Some(decl) => used.push(*decl),
+                None => return Err(error(format!("Trait \"{}\" used by {} not found", name, class.name))),
+            }
+        }
+
+        let excluded = |trait_name: &str, method: &str| {
+            trait_use.adaptations.iter().any(|adaptation| match adaptation {
+                TraitAdaptation::Precedence { method: m, instead_of, .. } => {
+                    m.eq_ignore_ascii_case(method) && instead_of.iter().any(|t| key(t) == key(trait_name))
+                }
+                TraitAdaptation::Alias { .. } => false,
+            })
+        };
+        let start = methods.len();
+        for decl in &used {
+            for method in decl.methods.iter().filter(|m| !excluded(&decl.name, &m.name)) {
+                methods.push((decl.name.clone(), method.clone()));
+            }
+        }
+
+        for adaptation in &trait_use.adaptations {
+            let TraitAdaptation::Alias { trait_name, method, alias, visibility } = adaptation else {
+                continue;
+            };
+            let source = used.iter()
+                .filter(|decl| trait_name.as_ref().is_none_or(|t| key(t) == key(&decl.name)))
+                .find_map(|decl| {
+                    let found = decl.methods.iter().find(|m| m.name.eq_ignore_ascii_case(method))?;
+                    Some((decl.name.clone(), found.clone()))
+                });
+            let Some((source_trait, mut source)) = source else {
+                return Err(error(format!("An alias was defined for {} but this method does not exist", method)));
+            };
+            match alias {
+                Some(alias) => {
+                    source.name = alias.clone();
+                    if let Some(visibility) = visibility {
+                        source.visibility = visibility.clone();
+                    }
+                    methods.push((source_trait, source));
+                }
+                None => {
+                    // `method as protected` changes the method itself
+                    let changed = methods[start..].iter_mut()
+                        .filter(|(t, m)| *t == source_trait && m.name.eq_ignore_ascii_case(method));
+                    for (_, m) in changed {
+                        m.visibility = visibility.clone().unwrap_or(m.visibility.clone());
+                    }
+                }
+            }
+        }
+
+        for decl in &used {
+            for property in &decl.properties {
+                if !flattened.properties.iter().any(|p| p.name == property.name) {
+                    flattened.properties.push(property.clone());
+                }
+            }
+        }
+    }
+
+    let mut added: Vec<(String, String)> = Vec::new();
+    for (trait_name, method) in methods {
+        let name = method.name.to_lowercase();
+        if class.methods.iter().any(|m| m.name.to_lowercase() == name) {
+            continue;
+        }
+        if let Some((other, _)) = added.iter().find(|(_, n)| *n == name) {
+            if key(other) != key(&trait_name) {
+                return Err(error(format!(
+                    "Trait method {0}::{1} has not been applied as {2}::{1}, because of collision with {3}::{1}",
+                    trait_name, method.name, class.name, other
+                )));
+            }
+            continue;
+        }
+        added.push((trait_name, name));
+        flattened.methods.push(method);
+    }
+    Ok(flattened)
+}
+
+/// Key of a trait name in the trait table
+fn key(name: &str) -> String {
+    name.trim_start_matches('\\').to_lowercase()
+}
+
+fn error(message: String) -> CompileError {
+    CompileError::Type { message, location: None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Statement, TraitUse, Visibility};
+
+    fn method(name: &str) -> FunctionDecl {
+        FunctionDecl {
+            name: name.to_string(),
+            parameters: vec![],
+            return_type: None,
+            body: Box::new(Statement::Block(vec![])),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        }
+    }
+
+    fn class(adaptations: Vec<TraitAdaptation>) -> ClassDecl {
+        ClassDecl {
+            name: "Talker".to_string(),
+            extends: None,
+            implements: vec![],
+            traits: vec![TraitUse { traits: vec!["A".to_string(), "B".to_string()], adaptations }],
+            properties: vec![],
+            methods: vec![method("bigTalk")],
+            constants: vec![],
+            attributes: vec![],
+            is_abstract: false,
+            is_final: false,
+            is_trait: false,
+            is_interface: false,
+            is_enum: false,
+        }
+    }
+
+    #[test]
+    fn test_flatten_traits() {
+        let trait_decl = |name: &str, methods: &[&str]| TraitDecl {
+            name: name.to_string(),
+            properties: vec![],
+            methods: methods.iter().map(|m| method(m)).collect(),
+            constants: vec![],
+        };
+        let a = trait_decl("A", &["smallTalk", "bigTalk"]);
+        let b = trait_decl("B", &["smallTalk", "bigTalk"]);
+        let traits = HashMap::from([("a".to_string(), &a), ("b".to_string(), &b)]);
+
+        // Both traits provide smallTalk()
+        let error = flatten_traits(&class(vec![]), &traits).unwrap_err();
+        assert!(error.to_string().contains("B::smallTalk has not been applied as Talker::smallTalk"));
+
+        let flattened = flatten_traits(&class(vec![
+            TraitAdaptation::Precedence {
+                trait_name: "B".to_string(),
+                method: "smallTalk".to_string(),
+                instead_of: vec!["A".to_string()],
+            },
+            TraitAdaptation::Alias {
+                trait_name: Some("A".to_string()),
+                method: "smallTalk".to_string(),
+                alias: Some("whisper".to_string()),
+                visibility: Some(Visibility::Private),
+            },
+        ]), &traits).unwrap();
+        let names: Vec<&str> = flattened.methods.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["bigTalk", "smallTalk", "whisper"]);
+        assert_eq!(flattened.methods[2].visibility, Visibility::Private);
+        assert!(flattened.traits.is_empty());
+    }
+}
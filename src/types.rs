@@ -15,7 +15,7 @@
  */
 
 use std::fmt;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// PHP type representation
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -40,22 +40,117 @@ pub enum Type {
     /// Union types
     Union(Vec<Type>),
     
+    /// Intersection types (`A&B`), satisfied by classes implementing every member
+    Intersection(Vec<Type>),
+    
     /// Generic types
     Generic(String, Vec<Type>),
     
+    /// Literal value refining a scalar type (e.g. `'sorted'` or `42`)
+    Literal(LiteralType),
+    
+    /// Callable value with a known signature (closures, function names)
+    Callable(Box<Signature>),
+    
     /// Unknown type
     Unknown,
 }
 
+/// Value of a literal type
+///
+/// Floats are not tracked, so types stay `Eq` and `Hash`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LiteralType {
+    Int(i64),
+    String(String),
+    Bool(bool),
+    /// Case of an enum: (enum, case)
+    EnumCase(String, String),
+}
+
+impl LiteralType {
+    /// Scalar type the literal refines
+    pub fn base_type(&self) -> Type {
+        match self {
+            LiteralType::Int(_) => Type::Int,
+            LiteralType::String(_) => Type::String,
+            LiteralType::Bool(_) => Type::Bool,
+            LiteralType::EnumCase(name, _) => Type::Object(name.clone()),
+        }
+    }
+}
+
+impl fmt::Display for LiteralType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LiteralType::Int(n) => write!(f, "{}", n),
+            LiteralType::String(s) => write!(f, "'{}'", s.replace('\\', "\\\\").replace('\'', "\\'")),
+            LiteralType::Bool(b) => write!(f, "{}", b),
+            LiteralType::EnumCase(name, case) => write!(f, "{}::{}", name.trim_start_matches('\\'), case),
+        }
+    }
+}
+
+/// Parameters and return type of a callable value
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Signature {
+    pub parameters: Vec<SignatureParameter>,
+    pub return_type: Type,
+    
+    /// Named function the value calls, when known
+    pub target: Option<String>,
+}
+
+/// Parameter of a [`Signature`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SignatureParameter {
+    pub name: String,
+    pub typ: Type,
+    pub optional: bool,
+    pub variadic: bool,
+    pub by_reference: bool,
+}
+
+impl Signature {
+    /// Number of arguments a call must pass
+    pub fn required_parameters(&self) -> usize {
+        self.parameters.iter().filter(|p| !p.optional && !p.variadic).count()
+    }
+    
+    /// Name used in messages about calls through the value
+    pub fn display_name(&self) -> &str {
+        self.target.as_deref().unwrap_or("{closure}")
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "callable(")?;
+        for (i, parameter) in self.parameters.iter().enumerate() {
+            if i > 0 { write!(f, ", ")?; }
+            write!(f, "{}", parameter.typ)?;
+            if parameter.variadic {
+                write!(f, "...")?;
+            } else if parameter.optional {
+                write!(f, "=")?;
+            }
+        }
+        write!(f, "): {}", self.return_type)
+    }
+}
+
 impl Type {
     /// Check if type is scalar
     pub fn is_scalar(&self) -> bool {
-        matches!(self, Type::Int | Type::Float | Type::Bool | Type::String)
+        match self {
+            Type::Literal(literal) => literal.base_type().is_scalar(),
+            _ => matches!(self, Type::Int | Type::Float | Type::Bool | Type::String),
+        }
     }
     
     /// Check if type is numeric
     pub fn is_numeric(&self) -> bool {
-        matches!(self, Type::Int | Type::Float)
+        matches!(self, Type::Int | Type::Float | Type::Literal(LiteralType::Int(_)))
     }
     
     /// Check if type is array
@@ -101,6 +196,65 @@ impl Type {
             _ => Some(self.clone()),
         }
     }
+    
+    /// Union of the given types, flattened and without duplicates
+    ///
+    /// A single member is returned as is, and any `Unknown` member makes the
+    /// whole union unknown.
+    pub fn union(types: impl IntoIterator<Item = Type>) -> Type {
+        let mut members: Vec<Type> = Vec::new();
+        for typ in types {
+            let flattened = match typ {
+                Type::Union(inner) => inner,
+                Type::Unknown => return Type::Unknown,
+                other => vec![other],
+            };
+            for member in flattened {
+                if !members.contains(&member) {
+                    members.push(member);
+                }
+            }
+        }
+        match members.len() {
+            0 => Type::Unknown,
+            1 => members.pop().unwrap(),
+            _ => Type::Union(members),
+        }
+    }
+    
+    /// The type with literal refinements widened to their base types
+    pub fn widen(&self) -> Type {
+        match self {
+            Type::Literal(literal) => literal.base_type(),
+            Type::Union(members) => Type::union(members.iter().map(Type::widen)),
+            other => other.clone(),
+        }
+    }
+    
+    /// Every value of the type, when it has finitely many
+    ///
+    /// `bool` expands to `true|false`; `null` is included as `Type::Null`.
+    pub fn singletons(&self) -> Option<Vec<Type>> {
+        match self {
+            Type::Literal(_) | Type::Null => Some(vec![self.clone()]),
+            Type::Bool => Some(vec![
+                Type::Literal(LiteralType::Bool(true)),
+                Type::Literal(LiteralType::Bool(false)),
+            ]),
+            Type::Union(members) => {
+                let mut values = Vec::new();
+                for member in members {
+                    for value in member.singletons()? {
+                        if !values.contains(&value) {
+                            values.push(value);
+                        }
+                    }
+                }
+                Some(values)
+            }
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Type {
@@ -130,6 +284,14 @@ impl fmt::Display for Type {
                 }
                 write!(f, ")")
             }
+            Type::Intersection(types) => {
+                write!(f, "(")?;
+                for (i, t) in types.iter().enumerate() {
+                    if i > 0 { write!(f, " & ")?; }
+                    write!(f, "{}", t)?;
+                }
+                write!(f, ")")
+            }
             Type::Generic(name, params) => {
                 write!(f, "{}<", name)?;
                 for (i, param) in params.iter().enumerate() {
@@ -138,11 +300,140 @@ impl fmt::Display for Type {
                 }
                 write!(f, ">")
             }
+            Type::Literal(literal) => write!(f, "{}", literal),
+            Type::Callable(signature) => write!(f, "{}", signature),
             Type::Unknown => write!(f, "unknown"),
         }
     }
 }
 
+/// Width of PHP's native `int`
+///
+/// PHP uses the platform word size, so `PHP_INT_MAX` is 2^31-1 on 32-bit
+/// targets such as armv7 and wasm32.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum IntWidth {
+    W32,
+    #[default]
+    W64,
+}
+
+impl IntWidth {
+    /// Select the width matching a target triple (`native` uses the host)
+    pub fn from_target(target: &str) -> Self {
+        let arch = if target == "native" {
+            std::env::consts::ARCH
+        } else {
+            target.split('-').next().unwrap_or(target)
+        };
+        
+        match arch {
+            "x86" | "i386" | "i586" | "i686" | "arm" | "armv7" | "armv7a" | "thumbv7"
+            | "thumbv7em" | "mips" | "mipsel" | "powerpc" | "riscv32" | "wasm32" | "sparc" => IntWidth::W32,
+            a if a.starts_with("armv") || a.starts_with("thumbv") || a.starts_with("riscv32") => IntWidth::W32,
+            _ => IntWidth::W64,
+        }
+    }
+    
+    /// Parse an explicit `--int-width` value
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        match bits {
+            32 => Some(IntWidth::W32),
+            64 => Some(IntWidth::W64),
+            _ => None,
+        }
+    }
+    
+    /// Number of bits
+    pub fn bits(&self) -> u32 {
+        match self {
+            IntWidth::W32 => 32,
+            IntWidth::W64 => 64,
+        }
+    }
+    
+    /// Value of `PHP_INT_SIZE`
+    pub fn size(&self) -> i64 {
+        (self.bits() / 8) as i64
+    }
+    
+    /// Value of `PHP_INT_MAX`
+    pub fn max(&self) -> i64 {
+        match self {
+            IntWidth::W32 => i32::MAX as i64,
+            IntWidth::W64 => i64::MAX,
+        }
+    }
+    
+    /// Value of `PHP_INT_MIN`
+    pub fn min(&self) -> i64 {
+        match self {
+            IntWidth::W32 => i32::MIN as i64,
+            IntWidth::W64 => i64::MIN,
+        }
+    }
+    
+    /// LLVM integer type
+    pub fn llvm_type(&self) -> &'static str {
+        match self {
+            IntWidth::W32 => "i32",
+            IntWidth::W64 => "i64",
+        }
+    }
+    
+    /// Check if a value is representable as a PHP int
+    pub fn fits(&self, value: i64) -> bool {
+        value >= self.min() && value <= self.max()
+    }
+    
+    /// Value of an integer literal: literals out of range become floats, as in PHP
+    pub fn literal(&self, value: i64) -> Value {
+        if self.fits(value) {
+            Value::Int(value)
+        } else {
+            Value::Float(value as f64)
+        }
+    }
+    
+    /// Add with PHP overflow semantics (promotion to float)
+    pub fn add(&self, a: i64, b: i64) -> Value {
+        self.promote(a.checked_add(b), a as f64 + b as f64)
+    }
+    
+    /// Subtract with PHP overflow semantics (promotion to float)
+    pub fn sub(&self, a: i64, b: i64) -> Value {
+        self.promote(a.checked_sub(b), a as f64 - b as f64)
+    }
+    
+    /// Multiply with PHP overflow semantics (promotion to float)
+    pub fn mul(&self, a: i64, b: i64) -> Value {
+        self.promote(a.checked_mul(b), a as f64 * b as f64)
+    }
+    
+    /// Value of a `PHP_INT_*` constant
+    pub fn constant(&self, name: &str) -> Option<i64> {
+        match name {
+            "PHP_INT_MAX" => Some(self.max()),
+            "PHP_INT_MIN" => Some(self.min()),
+            "PHP_INT_SIZE" => Some(self.size()),
+            _ => None,
+        }
+    }
+    
+    fn promote(&self, exact: Option<i64>, float: f64) -> Value {
+        match exact {
+            Some(n) if self.fits(n) => Value::Int(n),
+            _ => Value::Float(float),
+        }
+    }
+}
+
+impl fmt::Display for IntWidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-bit", self.bits())
+    }
+}
+
 /// PHP value representation
 #[derive(Debug, Clone)]
 pub enum Value {
@@ -235,15 +526,71 @@ impl fmt::Display for Value {
     }
 }
 
+/// Kind of a variable scope
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    /// Top-level code
+    Global,
+    
+    /// Function or method body; sees nothing of the enclosing scope
+    Function,
+    
+    /// `function () use (...)` body; sees only its captures
+    Closure,
+    
+    /// `fn () =>` body; sees the enclosing scope by value
+    ArrowFunction,
+    
+    /// Branch or loop body; its variables stay visible after it
+    Block,
+}
+
+/// Variables of one scope
+#[derive(Debug)]
+struct Scope {
+    kind: ScopeKind,
+    variables: HashMap<String, Type>,
+    
+    /// Names bound to the global scope by a `global` statement
+    globals: HashSet<String>,
+    
+    /// Variables captured by reference, written back when the scope is popped
+    references: Vec<String>,
+}
+
+impl Scope {
+    fn new(kind: ScopeKind) -> Self {
+        Self {
+            kind,
+            variables: HashMap::new(),
+            globals: HashSet::new(),
+            references: Vec::new(),
+        }
+    }
+}
+
 /// Type context for tracking types during compilation
-#[derive(Debug, Default)]
+///
+/// Variables live in a stack of scopes, the global scope at the bottom.
+#[derive(Debug)]
 pub struct TypeContext {
     types: HashMap<String, Type>,
-    variables: HashMap<String, Type>,
+    scopes: Vec<Scope>,
     functions: HashMap<String, Type>,
     classes: HashMap<String, ClassInfo>,
 }
 
+impl Default for TypeContext {
+    fn default() -> Self {
+        Self {
+            types: HashMap::new(),
+            scopes: vec![Scope::new(ScopeKind::Global)],
+            functions: HashMap::new(),
+            classes: HashMap::new(),
+        }
+    }
+}
+
 impl TypeContext {
     pub fn new() -> Self {
         Self::default()
@@ -259,14 +606,98 @@ impl TypeContext {
         self.types.get(name)
     }
     
-    /// Register a variable type
+    /// Register a variable type in the current scope
     pub fn register_variable(&mut self, name: String, typ: Type) {
-        self.variables.insert(name, typ);
+        let index = self.owner(&name);
+        self.scopes[index].variables.insert(name, typ);
     }
     
-    /// Get variable type
+    /// Get variable type, as seen from the current scope
     pub fn get_variable_type(&self, name: &str) -> Option<&Type> {
-        self.variables.get(name)
+        for scope in self.scopes.iter().rev() {
+            if scope.globals.contains(name) {
+                return self.scopes[0].variables.get(name);
+            }
+            if let Some(typ) = scope.variables.get(name) {
+                return Some(typ);
+            }
+            if matches!(scope.kind, ScopeKind::Function | ScopeKind::Closure) {
+                break;
+            }
+        }
+        None
+    }
+    
+    /// Index of the scope an assignment to `name` writes to
+    fn owner(&self, name: &str) -> usize {
+        for scope in self.scopes.iter().rev() {
+            if scope.globals.contains(name) {
+                return 0;
+            }
+            if scope.kind != ScopeKind::Block {
+                break;
+            }
+        }
+        self.scopes.len() - 1
+    }
+    
+    /// Enter a scope
+    pub fn push_scope(&mut self, kind: ScopeKind) {
+        self.scopes.push(Scope::new(kind));
+    }
+    
+    /// Enter a closure body, copying its `use` captures from the current scope
+    pub fn push_closure_scope(&mut self, uses: &[crate::ast::ClosureUse]) {
+        let mut scope = Scope::new(ScopeKind::Closure);
+        for captured in uses {
+            let typ = self.get_variable_type(&captured.name).cloned().unwrap_or(Type::Unknown);
+            scope.variables.insert(captured.name.clone(), typ);
+            if captured.by_reference {
+                scope.references.push(captured.name.clone());
+            }
+        }
+        self.scopes.push(scope);
+    }
+    
+    /// Leave the current scope
+    ///
+    /// Variables of a block stay visible in the enclosing scope, joined with
+    /// their previous type since the block may not have run. Variables
+    /// captured by reference carry their type back out of a closure.
+    pub fn pop_scope(&mut self) {
+        if self.scopes.len() == 1 {
+            return;
+        }
+        let scope = self.scopes.pop().expect("global scope");
+        let carried: Vec<_> = match scope.kind {
+            ScopeKind::Block => scope.variables.into_iter().collect(),
+            ScopeKind::Closure => scope.references.into_iter()
+                .filter_map(|name| scope.variables.get(&name).cloned().map(|typ| (name, typ)))
+                .collect(),
+            _ => Vec::new(),
+        };
+        for (name, typ) in carried {
+            let typ = match self.get_variable_type(&name) {
+                Some(previous) => Type::union([previous.clone(), typ]),
+                None => typ,
+            };
+            self.register_variable(name, typ);
+        }
+    }
+    
+    /// Bind a name of the current function to the global variable (`global $x`)
+    pub fn declare_global(&mut self, name: String) {
+        let index = self.scopes.iter()
+            .rposition(|scope| scope.kind != ScopeKind::Block)
+            .unwrap_or(0);
+        if index > 0 {
+            self.scopes[index].globals.insert(name);
+        }
+    }
+    
+    /// Kind of the current scope
+    pub fn scope_kind(&self) -> ScopeKind {
+        self.scopes.last().map_or(ScopeKind::Global, |scope| scope.kind)
     }
     
     /// Register a function signature
@@ -288,6 +719,172 @@ impl TypeContext {
     pub fn get_class_info(&self, name: &str) -> Option<&ClassInfo> {
         self.classes.get(name)
     }
+    
+    /// Register the hierarchy of the top-level classes and interfaces of a program
+    pub fn register_declarations(&mut self, ast: &[crate::ast::AstNode]) {
+        use crate::ast::AstNode;
+        for node in ast {
+            let info = match node {
+                AstNode::Program(nodes) => {
+                    self.register_declarations(nodes);
+                    continue;
+                }
+                AstNode::Namespace(ns) => {
+                    self.register_declarations(&ns.statements);
+                    continue;
+                }
+                AstNode::Class(decl) => {
+                    let mut info = ClassInfo::new(decl.name.clone());
+                    if let Some(parent) = &decl.extends {
+                        info.set_parent(parent.clone());
+                    }
+                    decl.implements.iter().for_each(|i| info.add_interface(i.clone()));
+                    for property in &decl.properties {
+                        let typ = property.typ.clone().unwrap_or(Type::Unknown);
+                        if property.is_readonly {
+                            info.add_readonly_property(property.name.clone(), typ);
+                        } else {
+                            info.add_property(property.name.clone(), typ);
+                        }
+                    }
+                    decl.methods.iter().for_each(|m| info.add_method(m.name.clone(), method_type(m)));
+                    info
+                }
+                AstNode::Interface(decl) => {
+                    let mut info = ClassInfo::new(decl.name.clone());
+                    decl.extends.iter().for_each(|i| info.add_interface(i.clone()));
+                    decl.methods.iter().for_each(|m| info.add_method(m.name.clone(), method_type(m)));
+                    info
+                }
+                _ => continue,
+            };
+            self.register_class(info.name.clone(), info);
+        }
+    }
+    
+    /// Class info looked up case-insensitively, ignoring a leading `\`
+    fn find_class(&self, name: &str) -> Option<&ClassInfo> {
+        self.classes.get(name).or_else(|| {
+            let name = class_key(name);
+            self.classes.values().find(|info| class_key(&info.name) == name)
+        })
+    }
+    
+    /// Declared type of a property of `class` or its parents
+    ///
+    /// `self` and `parent` are bound to the declaring class and its parent.
+    pub fn property_type(&self, class: &str, property: &str) -> Option<Type> {
+        self.declaring_class(class, property).map(|info| info.bind_relative(&info.properties[property], None))
+    }
+    
+    /// The class among `class` and its parents that declares a property
+    pub fn declaring_class(&self, class: &str, property: &str) -> Option<&ClassInfo> {
+        let mut current = self.find_class(class);
+        let mut depth = 0;
+        while let Some(info) = current.filter(|_| depth < self.classes.len()) {
+            if info.properties.contains_key(property) {
+                return Some(info);
+            }
+            current = info.parent.as_deref().and_then(|parent| self.find_class(parent));
+            depth += 1;
+        }
+        None
+    }
+    
+    /// Declared return type of a method of `class`, its parents or interfaces
+    ///
+    /// `self` and `parent` are bound relative to the declaring class, and
+    /// `static` to `class`.
+    pub fn method_return_type(&self, class: &str, method: &str) -> Option<Type> {
+        let mut pending = vec![class.to_string()];
+        let mut seen = HashSet::new();
+        while let Some(name) = pending.pop() {
+            if !seen.insert(class_key(&name)) {
+                continue;
+            }
+            let Some(info) = self.find_class(&name) else { continue };
+            let found = info.methods.iter().find(|(m, _)| m.eq_ignore_ascii_case(method));
+            if let Some((_, Type::Function(_, returns))) = found {
+                return Some(info.bind_relative(returns, Some(class)));
+            }
+            // Parents first, so the nearest declaration wins
+            pending.extend(info.interfaces.iter().cloned());
+            pending.extend(info.parent.iter().cloned());
+        }
+        None
+    }
+    
+    /// Whether `class` is `ancestor` or extends or implements it, directly or not
+    ///
+    /// `None` when the answer depends on a class that is not registered,
+    /// such as a builtin interface.
+    pub fn is_subclass_of(&self, class: &str, ancestor: &str) -> Option<bool> {
+        let ancestor = class_key(ancestor);
+        let mut pending = vec![class.to_string()];
+        let mut seen = HashSet::new();
+        let mut complete = true;
+        while let Some(name) = pending.pop() {
+            if class_key(&name) == ancestor {
+                return Some(true);
+            }
+            if !seen.insert(class_key(&name)) {
+                continue;
+            }
+            match self.find_class(&name) {
+                Some(info) => pending.extend(info.parent.iter().chain(&info.interfaces).cloned()),
+                None => complete = false,
+            }
+        }
+        if complete { Some(false) } else { None }
+    }
+    
+    /// Whether an instance of `class` satisfies a declared type
+    ///
+    /// `None` when that cannot be decided from the registered classes.
+    pub fn instance_satisfies(&self, class: &str, typ: &Type) -> Option<bool> {
+        match typ {
+            Type::Object(name) if matches!(class_key(name).as_str(), "object" | "mixed") => Some(true),
+            Type::Object(name) => self.is_subclass_of(class, name),
+            Type::Intersection(members) => {
+                let results: Vec<_> = members.iter().map(|m| self.instance_satisfies(class, m)).collect();
+                if results.contains(&Some(false)) {
+                    Some(false)
+                } else if results.iter().all(|r| *r == Some(true)) {
+                    Some(true)
+                } else {
+                    None
+                }
+            }
+            Type::Union(members) => {
+                let results: Vec<_> = members.iter().map(|m| self.instance_satisfies(class, m)).collect();
+                if results.contains(&Some(true)) {
+                    Some(true)
+                } else if results.iter().all(|r| *r == Some(false)) {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            // Objects with __toString() pass as strings in coercive mode
+            Type::String => None,
+            Type::Int | Type::Float | Type::Bool | Type::Null | Type::Literal(_) => Some(false),
+            Type::Array(_) | Type::AssociativeArray(_) => Some(false),
+            Type::Function(..) | Type::Callable(_) | Type::Generic(..) | Type::Unknown => None,
+        }
+    }
+}
+
+/// Function type of a method declaration
+fn method_type(method: &crate::ast::FunctionDecl) -> Type {
+    Type::Function(
+        method.parameters.iter().map(|p| p.typ.clone().unwrap_or(Type::Unknown)).collect(),
+        Box::new(method.return_type.clone().unwrap_or(Type::Unknown)),
+    )
+}
+
+/// Normalized class name for comparisons
+fn class_key(name: &str) -> String {
+    name.trim_start_matches('\\').to_lowercase()
 }
 
 /// Class information
@@ -298,6 +895,8 @@ pub struct ClassInfo {
     pub methods: HashMap<String, Type>,
     pub parent: Option<String>,
     pub interfaces: Vec<String>,
+    /// Names of `readonly` properties
+    pub readonly: HashSet<String>,
 }
 
 impl ClassInfo {
@@ -308,6 +907,7 @@ impl ClassInfo {
             methods: HashMap::new(),
             parent: None,
             interfaces: Vec::new(),
+            readonly: HashSet::new(),
         }
     }
     
@@ -315,6 +915,11 @@ impl ClassInfo {
         self.properties.insert(name, typ);
     }
     
+    pub fn add_readonly_property(&mut self, name: String, typ: Type) {
+        self.readonly.insert(name.clone());
+        self.properties.insert(name, typ);
+    }
+    
     pub fn add_method(&mut self, name: String, typ: Type) {
         self.methods.insert(name, typ);
     }
@@ -326,6 +931,30 @@ impl ClassInfo {
     pub fn add_interface(&mut self, interface: String) {
         self.interfaces.push(interface);
     }
+    
+    /// Bind `self` and `parent` in a type declared by this class
+    ///
+    /// `static` is bound to `called`, the class the member is used through
+    /// (late static binding), and left as is when that is not known.
+    pub fn bind_relative(&self, typ: &Type, called: Option<&str>) -> Type {
+        match typ {
+            Type::Object(name) => match class_key(name).as_str() {
+                "self" => Type::Object(self.name.clone()),
+                "parent" => self.parent.as_ref().map_or_else(|| typ.clone(), |parent| Type::Object(parent.clone())),
+                "static" => called.map_or_else(|| typ.clone(), |called| Type::Object(called.to_string())),
+                _ => typ.clone(),
+            },
+            Type::Union(members) => Type::Union(members.iter().map(|t| self.bind_relative(t, called)).collect()),
+            Type::Intersection(members) => {
+                Type::Intersection(members.iter().map(|t| self.bind_relative(t, called)).collect())
+            }
+            Type::Function(parameters, returns) => Type::Function(
+                parameters.iter().map(|t| self.bind_relative(t, called)).collect(),
+                Box::new(self.bind_relative(returns, called)),
+            ),
+            other => other.clone(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -358,6 +987,157 @@ mod tests {
         assert!(!Value::Null.is_truthy());
     }
 
+    #[test]
+    fn test_int_width_from_target() {
+        assert_eq!(IntWidth::from_target("x86_64-unknown-linux-gnu"), IntWidth::W64);
+        assert_eq!(IntWidth::from_target("armv7-unknown-linux-gnueabihf"), IntWidth::W32);
+        assert_eq!(IntWidth::from_target("wasm32-unknown-unknown"), IntWidth::W32);
+        assert_eq!(IntWidth::W32.constant("PHP_INT_MAX"), Some(2147483647));
+        assert_eq!(IntWidth::W32.constant("PHP_INT_SIZE"), Some(4));
+    }
+
+    #[test]
+    fn test_int_width_overflow_promotion() {
+        assert_eq!(IntWidth::W64.add(1, 2).get_type(), Type::Int);
+        assert_eq!(IntWidth::W32.add(i32::MAX as i64, 1).get_type(), Type::Float);
+        assert_eq!(IntWidth::W64.mul(i64::MAX, 2).get_type(), Type::Float);
+        assert_eq!(IntWidth::W32.literal(3_000_000_000).get_type(), Type::Float);
+    }
+
+    #[test]
+    fn test_literal_types() {
+        let sorted = Type::Literal(LiteralType::String("sorted".to_string()));
+        let answer = Type::Literal(LiteralType::Int(42));
+        assert_eq!(sorted.to_string(), "'sorted'");
+        assert_eq!(answer.widen(), Type::Int);
+        
+        let union = Type::union([sorted.clone(), answer.clone(), sorted.clone()]);
+        assert_eq!(union, Type::Union(vec![sorted.clone(), answer]));
+        assert_eq!(union.widen(), Type::Union(vec![Type::String, Type::Int]));
+        assert_eq!(Type::union([sorted, Type::Unknown]), Type::Unknown);
+        assert_eq!(Type::union([Type::Bool, Type::Null]).singletons().map(|v| v.len()), Some(3));
+        assert_eq!(Type::String.singletons(), None);
+    }
+
+    #[test]
+    fn test_callable_signature() {
+        let parameter = |typ, optional, variadic| SignatureParameter {
+            name: "x".to_string(),
+            typ,
+            optional,
+            variadic,
+            by_reference: false,
+        };
+        let signature = Signature {
+            parameters: vec![
+                parameter(Type::Int, false, false),
+                parameter(Type::String, true, false),
+                parameter(Type::Unknown, false, true),
+            ],
+            return_type: Type::Bool,
+            target: None,
+        };
+        assert_eq!(signature.required_parameters(), 1);
+        assert_eq!(signature.display_name(), "{closure}");
+        assert_eq!(Type::Callable(Box::new(signature)).to_string(), "callable(int, string=, unknown...): bool");
+    }
+
+    #[test]
+    fn test_class_hierarchy() {
+        let mut ctx = TypeContext::new();
+        ctx.register_class("Countable".to_string(), ClassInfo::new("Countable".to_string()));
+        ctx.register_class("JsonSerializable".to_string(), ClassInfo::new("JsonSerializable".to_string()));
+        let mut base = ClassInfo::new("Base".to_string());
+        base.add_interface("Countable".to_string());
+        ctx.register_class("Base".to_string(), base);
+        let mut child = ClassInfo::new("App\\Child".to_string());
+        child.set_parent("Base".to_string());
+        ctx.register_class("App\\Child".to_string(), child);
+        
+        let countable = Type::Object("countable".to_string());
+        let both = Type::Intersection(vec![countable.clone(), Type::Object("JsonSerializable".to_string())]);
+        assert_eq!(ctx.instance_satisfies("\\App\\Child", &countable), Some(true));
+        assert_eq!(ctx.instance_satisfies("App\\Child", &both), Some(false));
+        assert_eq!(ctx.instance_satisfies("App\\Child", &Type::Union(vec![both, Type::Null])), Some(false));
+        assert_eq!(ctx.is_subclass_of("Base", "Countable"), Some(true));
+        
+        // IteratorAggregate is not registered, so the answer is unknown
+        let mut proxy = ClassInfo::new("Proxy".to_string());
+        proxy.add_interface("IteratorAggregate".to_string());
+        ctx.register_class("Proxy".to_string(), proxy);
+        assert_eq!(ctx.is_subclass_of("Proxy", "Countable"), None);
+    }
+
+    #[test]
+    fn test_late_static_binding() {
+        let fluent = |returns: &str| Type::Function(vec![], Box::new(Type::Object(returns.to_string())));
+        let mut ctx = TypeContext::new();
+        let mut builder = ClassInfo::new("Builder".to_string());
+        builder.add_method("where".to_string(), fluent("static"));
+        builder.add_method("copy".to_string(), fluent("self"));
+        ctx.register_class("Builder".to_string(), builder);
+        let mut query = ClassInfo::new("QueryBuilder".to_string());
+        query.set_parent("Builder".to_string());
+        query.add_method("base".to_string(), fluent("parent"));
+        ctx.register_class("QueryBuilder".to_string(), query);
+
+        let object = |name: &str| Some(Type::Object(name.to_string()));
+        assert_eq!(ctx.method_return_type("QueryBuilder", "where"), object("QueryBuilder"));
+        assert_eq!(ctx.method_return_type("Builder", "where"), object("Builder"));
+        assert_eq!(ctx.method_return_type("QueryBuilder", "copy"), object("Builder"));
+        assert_eq!(ctx.method_return_type("QueryBuilder", "base"), object("Builder"));
+
+        // Without a called class, `static` stays unbound
+        let info = ClassInfo::new("Builder".to_string());
+        let nullable = Type::Union(vec![Type::Object("static".to_string()), Type::Null]);
+        assert_eq!(info.bind_relative(&nullable, None), nullable);
+    }
+
+    #[test]
+    fn test_scopes() {
+        use crate::ast::ClosureUse;
+        let mut ctx = TypeContext::new();
+        ctx.register_variable("config".to_string(), Type::Array(Box::new(Type::Unknown)));
+        
+        // Two functions with a $x do not collide, and neither sees globals
+        ctx.push_scope(ScopeKind::Function);
+        ctx.register_variable("x".to_string(), Type::Int);
+        assert_eq!(ctx.get_variable_type("config"), None);
+        ctx.pop_scope();
+        ctx.push_scope(ScopeKind::Function);
+        assert_eq!(ctx.get_variable_type("x"), None);
+        ctx.register_variable("x".to_string(), Type::String);
+        
+        // ...unless declared global
+        ctx.push_scope(ScopeKind::Block);
+        ctx.declare_global("config".to_string());
+        ctx.register_variable("y".to_string(), Type::Bool);
+        ctx.pop_scope();
+        assert!(ctx.get_variable_type("config").is_some());
+        assert_eq!(ctx.get_variable_type("y"), Some(&Type::Bool));
+        
+        // Closures see their captures; by-reference ones flow back out
+        ctx.push_closure_scope(&[
+            ClosureUse { name: "x".to_string(), by_reference: true },
+            ClosureUse { name: "y".to_string(), by_reference: false },
+        ]);
+        assert_eq!(ctx.get_variable_type("x"), Some(&Type::String));
+        ctx.register_variable("x".to_string(), Type::Int);
+        ctx.register_variable("y".to_string(), Type::Null);
+        ctx.pop_scope();
+        assert_eq!(ctx.get_variable_type("x"), Some(&Type::Union(vec![Type::String, Type::Int])));
+        assert_eq!(ctx.get_variable_type("y"), Some(&Type::Bool));
+        
+        // Arrow functions see the enclosing scope
+        ctx.push_scope(ScopeKind::ArrowFunction);
+        assert_eq!(ctx.scope_kind(), ScopeKind::ArrowFunction);
+        assert_eq!(ctx.get_variable_type("y"), Some(&Type::Bool));
+        ctx.pop_scope();
+        ctx.pop_scope();
+        assert_eq!(ctx.scope_kind(), ScopeKind::Global);
+        assert_eq!(ctx.get_variable_type("x"), None);
+    }
+
     #[test]
     fn test_type_context() {
         let mut ctx = TypeContext::new();
@@ -0,0 +1,526 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::fmt;
+use crate::ast::index::rewrite_statements;
+use crate::ast::{AstIndex, AstNode, Expression, Literal, NodeId, Span, Statement};
+
+/// Warning produced for code that can never execute
+#[derive(Debug, Clone)]
+pub struct UnreachableWarning {
+    /// Human readable description
+    pub message: String,
+
+    /// Enclosing function, if any
+    pub function: Option<String>,
+
+    /// Source span of the removed code, when the AST carries one
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for UnreachableWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(function) = &self.function {
+            write!(f, " (in function {})", function)?;
+        }
+        Ok(())
+    }
+}
+
+/// AST-level unreachable code elimination
+///
+/// Removes statements following `return`, `throw`, `exit`/`die`, `break`
+/// and `continue`, and folds branches whose condition is a constant.
+/// Function and class declarations after a terminator are kept, since PHP
+/// declares them before the code around them runs.
+pub struct UnreachableCodeEliminator {
+    /// Source span of each node, by id
+    spans: HashMap<NodeId, Span>,
+}
+
+impl UnreachableCodeEliminator {
+    /// Create a new eliminator
+    pub fn new() -> Self {
+        Self { spans: HashMap::new() }
+    }
+
+    /// Take spans from an index of the program
+    pub fn with_index(mut self, index: &AstIndex) -> Self {
+        self.spans = index.ids().filter_map(|id| index.span(id).map(|span| (id, span))).collect();
+        self
+    }
+
+    /// Run the pass over a whole program, returning the collected warnings
+    ///
+    /// The program is analysed while indexed, then the edits are applied to
+    /// the statements by id.
+    pub fn run(&mut self, ast: &mut [AstNode]) -> Vec<UnreachableWarning> {
+        let index = AstIndex::build(ast);
+        let mut analysis = Analysis {
+            index: &index,
+            spans: &self.spans,
+            warnings: Vec::new(),
+            current_function: None,
+            edits: HashMap::new(),
+        };
+        for node in ast.iter() {
+            analysis.visit_node(node);
+        }
+        let Analysis { warnings, mut edits, .. } = analysis;
+        let ids: Vec<NodeId> = edits.keys().copied().collect();
+        drop(index);
+
+        rewrite_statements(ast, ids, |id, stmt| {
+            if let Some(edit) = edits.remove(&id) {
+                edit.apply(stmt);
+            }
+        });
+        warnings
+    }
+}
+
+impl Default for UnreachableCodeEliminator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Change to one statement, applied after the statements inside it
+#[derive(Debug)]
+enum Edit {
+    /// Replace an `if` with its then (`true`) or else branch
+    Fold(bool),
+    /// Replace a loop with an empty block
+    Remove,
+    /// Keep only the first statements and the declarations of each
+    /// statement list of a block or `switch`
+    Cut(Vec<Option<usize>>),
+}
+
+impl Edit {
+    fn apply(self, stmt: &mut Statement) {
+        match (self, stmt) {
+            (Edit::Fold(keep_then), stmt) => {
+                let Statement::If { then_branch, else_branch, .. } = stmt else {
+                    return;
+                };
+                *stmt = match (keep_then, else_branch.take()) {
+                    (true, _) => std::mem::replace(then_branch.as_mut(), Statement::Block(vec![])),
+                    (false, Some(else_stmt)) => *else_stmt,
+                    (false, None) => Statement::Block(vec![]),
+                };
+            }
+            (Edit::Remove, stmt) => *stmt = Statement::Block(vec![]),
+            (Edit::Cut(cuts), Statement::Block(statements)) => cut(statements, cuts[0]),
+            (Edit::Cut(cuts), Statement::Switch { cases, .. }) => {
+                for (case, at) in cases.iter_mut().zip(cuts) {
+                    cut(&mut case.statements, at);
+                }
+            }
+            (Edit::Cut(_), _) => {}
+        }
+    }
+}
+
+/// Remove everything but declarations after the first `at` statements
+fn cut(statements: &mut Vec<Statement>, at: Option<usize>) {
+    if let Some(at) = at {
+        let mut position = 0;
+        statements.retain(|stmt| {
+            position += 1;
+            position <= at || matches!(stmt, Statement::Declaration(_))
+        });
+    }
+}
+
+/// Unreachable code of an indexed program, found before any of it changes
+struct Analysis<'a> {
+    /// Index of the program, which numbers its statements
+    index: &'a AstIndex<'a>,
+
+    /// Source span of each node, by id
+    spans: &'a HashMap<NodeId, Span>,
+
+    /// Warnings collected during the pass
+    warnings: Vec<UnreachableWarning>,
+
+    /// Function currently being processed
+    current_function: Option<String>,
+
+    /// Edits to make, by statement id
+    edits: HashMap<NodeId, Edit>,
+}
+
+impl Analysis<'_> {
+    /// Visit a top-level node
+    fn visit_node(&mut self, node: &AstNode) {
+        match node {
+            AstNode::Program(nodes) => {
+                for node in nodes {
+                    self.visit_node(node);
+                }
+            }
+            AstNode::Function(func_decl) => {
+                self.visit_function(&func_decl.name, &func_decl.body);
+            }
+            AstNode::Class(class_decl) => {
+                for method in &class_decl.methods {
+                    let name = format!("{}::{}", class_decl.name, method.name);
+                    self.visit_function(&name, &method.body);
+                }
+            }
+            AstNode::Trait(trait_decl) => {
+                for method in &trait_decl.methods {
+                    let name = format!("{}::{}", trait_decl.name, method.name);
+                    self.visit_function(&name, &method.body);
+                }
+            }
+            AstNode::Enum(enum_decl) => {
+                for method in &enum_decl.methods {
+                    let name = format!("{}::{}", enum_decl.name, method.name);
+                    self.visit_function(&name, &method.body);
+                }
+            }
+            AstNode::Namespace(namespace) => {
+                for node in &namespace.statements {
+                    self.visit_node(node);
+                }
+            }
+            AstNode::Statement(stmt) => {
+                self.visit_statement(stmt);
+            }
+            _ => {}
+        }
+    }
+
+    /// Visit a function body with the given name as context
+    fn visit_function(&mut self, name: &str, body: &Statement) {
+        let previous = self.current_function.replace(name.to_string());
+        self.visit_statement(body);
+        self.current_function = previous;
+    }
+
+    /// Find the unreachable code of a statement
+    fn visit_statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Block(statements) => {
+                let at = self.visit_block(statements);
+                self.edit(stmt, Edit::Cut(vec![at]));
+            }
+            Statement::If { condition, then_branch, else_branch } => {
+                self.visit_statement(then_branch);
+                if let Some(else_stmt) = else_branch {
+                    self.visit_statement(else_stmt);
+                }
+
+                match constant_truthiness(condition) {
+                    Some(true) => {
+                        if let Some(else_stmt) = else_branch {
+                            let span = self.span(else_stmt);
+                            self.warn("else branch is unreachable: condition is always true", span);
+                        }
+                        self.edit(stmt, Edit::Fold(true));
+                    }
+                    Some(false) => {
+                        let span = self.span(then_branch);
+                        self.warn("if branch is unreachable: condition is always false", span);
+                        self.edit(stmt, Edit::Fold(false));
+                    }
+                    None => {}
+                }
+            }
+            Statement::While { condition, body } => {
+                if constant_truthiness(condition) == Some(false) {
+                    let span = self.span(body);
+                    self.warn("loop body is unreachable: condition is always false", span);
+                    self.edit(stmt, Edit::Remove);
+                } else {
+                    self.visit_statement(body);
+                }
+            }
+            Statement::DoWhile { body, .. }
+            | Statement::For { body, .. }
+            | Statement::Foreach { body, .. }
+            | Statement::Declare { body, .. } => {
+                self.visit_statement(body);
+            }
+            Statement::Switch { cases, .. } => {
+                let cuts = cases.iter().map(|case| self.visit_block(&case.statements)).collect();
+                self.edit(stmt, Edit::Cut(cuts));
+            }
+            Statement::Match { arms, .. } => {
+                for arm in arms {
+                    self.visit_statement(&arm.body);
+                }
+            }
+            Statement::Try { try_block, catch_blocks, finally_block } => {
+                self.visit_statement(try_block);
+                for catch in catch_blocks {
+                    self.visit_statement(&catch.body);
+                }
+                if let Some(finally) = finally_block {
+                    self.visit_statement(finally);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Find the statements of a list after a terminator, returning how many
+    /// to keep before the declarations among them
+    fn visit_block(&mut self, statements: &[Statement]) -> Option<usize> {
+        let mut cut = None;
+        for (i, stmt) in statements.iter().enumerate() {
+            self.visit_statement(stmt);
+            if terminates_folded(stmt) {
+                cut = Some(i + 1);
+                break;
+            }
+        }
+
+        let cut = cut?;
+        let removed: Vec<&Statement> = statements[cut..].iter()
+            .filter(|stmt| !matches!(stmt, Statement::Declaration(_)))
+            .collect();
+        if removed.is_empty() {
+            return None;
+        }
+        let span = removed.iter()
+            .filter_map(|stmt| self.span(stmt))
+            .reduce(|first, last| Span::new(first.start.min(last.start), first.end.max(last.end)));
+        let message = format!(
+            "{} unreachable statement{} after {}",
+            removed.len(),
+            if removed.len() == 1 { "" } else { "s" },
+            describe_terminator(&statements[cut - 1])
+        );
+        self.warn(&message, span);
+        Some(cut)
+    }
+
+    /// Record an edit of a statement, unless it changes nothing
+    fn edit(&mut self, stmt: &Statement, edit: Edit) {
+        if matches!(&edit, Edit::Cut(cuts) if cuts.iter().all(Option::is_none)) {
+            return;
+        }
+        if let Some(id) = self.index.statement_id(stmt) {
+            self.edits.insert(id, edit);
+        }
+    }
+
+    /// Source span of a statement
+    fn span(&self, stmt: &Statement) -> Option<Span> {
+        self.index.statement_id(stmt).and_then(|id| self.spans.get(&id)).copied()
+    }
+
+    /// Record a warning in the current context
+    fn warn(&mut self, message: &str, span: Option<Span>) {
+        self.warnings.push(UnreachableWarning {
+            message: message.to_string(),
+            function: self.current_function.clone(),
+            span,
+        });
+    }
+}
+
+/// Check whether control flow can never continue past a statement
+pub fn terminates(stmt: &Statement) -> bool {
+    match stmt {
+        Statement::Return(_)
+        | Statement::Throw(_)
+        | Statement::Die(_)
+        | Statement::Break(_)
+        | Statement::Continue(_) => true,
+        Statement::Block(statements) => statements.iter()
+            .rfind(|stmt| !matches!(stmt, Statement::Declaration(_)))
+            .is_some_and(terminates),
+        Statement::If { then_branch, else_branch: Some(else_branch), .. } => {
+            terminates(then_branch) && terminates(else_branch)
+        }
+        _ => false,
+    }
+}
+
+/// Check whether control flow can never continue past a statement once the
+/// pass has folded its constant branches and cut its blocks
+fn terminates_folded(stmt: &Statement) -> bool {
+    match stmt {
+        Statement::Block(statements) => statements.iter().any(terminates_folded),
+        Statement::If { condition, then_branch, else_branch } => {
+            let else_terminates = else_branch.as_deref().is_some_and(terminates_folded);
+            match constant_truthiness(condition) {
+                Some(true) => terminates_folded(then_branch),
+                Some(false) => else_terminates,
+                None => else_terminates && terminates_folded(then_branch),
+            }
+        }
+        _ => terminates(stmt),
+    }
+}
+
+/// Evaluate the truthiness of a condition if it is a constant literal
+pub fn constant_truthiness(expr: &Expression) -> Option<bool> {
+    match expr {
+        Expression::Literal(literal) => match literal {
+            Literal::Bool(b) => Some(*b),
+            Literal::Int(n) => Some(*n != 0),
+            Literal::Float(x) => Some(*x != 0.0),
+            Literal::String(s) => Some(!s.is_empty() && s != "0"),
+            Literal::Null => Some(false),
+            Literal::Array(elements) => Some(!elements.is_empty()),
+        },
+        _ => None,
+    }
+}
+
+/// Short description of the statement that ended a block
+fn describe_terminator(stmt: &Statement) -> &'static str {
+    match stmt {
+        Statement::If { condition, then_branch, else_branch } => match constant_truthiness(condition) {
+            Some(true) => describe_terminator(then_branch),
+            Some(false) => else_branch.as_deref().map_or("a terminating branch", describe_terminator),
+            None => "a terminating branch",
+        },
+        Statement::Return(_) => "return",
+        Statement::Throw(_) => "throw",
+        Statement::Die(_) => "exit",
+        Statement::Break(_) => "break",
+        Statement::Continue(_) => "continue",
+        _ => "a terminating branch",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{FunctionDecl, Visibility};
+
+    fn echo(s: &str) -> Statement {
+        Statement::Echo(vec![Expression::Literal(Literal::String(s.to_string()))])
+    }
+
+    #[test]
+    fn test_removes_statements_after_return() {
+        let mut ast = vec![AstNode::Statement(Box::new(Statement::Block(vec![
+            echo("a"),
+            Statement::Return(None),
+            echo("b"),
+            echo("c"),
+        ])))];
+
+        let warnings = UnreachableCodeEliminator::new().run(&mut ast);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.contains("2 unreachable statements after return"));
+
+        match &ast[0] {
+            AstNode::Statement(stmt) => match stmt.as_ref() {
+                Statement::Block(statements) => assert_eq!(statements.len(), 2),
+                _ => panic!("Expected block"),
+            },
+            _ => panic!("Expected statement"),
+        }
+    }
+
+    #[test]
+    fn test_keeps_declarations_after_return() {
+        let helper = FunctionDecl {
+            name: "helper".to_string(),
+            parameters: vec![],
+            return_type: None,
+            body: Box::new(Statement::Block(vec![])),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        };
+        let mut ast = vec![AstNode::Statement(Box::new(Statement::Block(vec![
+            Statement::Return(None),
+            echo("b"),
+            Statement::Declaration(Box::new(AstNode::Function(helper))),
+            echo("c"),
+        ])))];
+
+        let mut index = AstIndex::build(&ast);
+        let AstNode::Statement(block) = &ast[0] else { unreachable!() };
+        let Statement::Block(statements) = block.as_ref() else { unreachable!() };
+        index.set_span(index.statement_id(&statements[1]).unwrap(), Span::new(10, 20));
+        index.set_span(index.statement_id(&statements[3]).unwrap(), Span::new(40, 50));
+        let mut eliminator = UnreachableCodeEliminator::new().with_index(&index);
+
+        let warnings = eliminator.run(&mut ast);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.contains("2 unreachable statements after return"));
+        assert_eq!(warnings[0].span, Some(Span::new(10, 50)));
+
+        let AstNode::Statement(block) = &ast[0] else { unreachable!() };
+        let Statement::Block(statements) = block.as_ref() else { unreachable!() };
+        assert_eq!(statements.len(), 2);
+        assert!(matches!(&statements[1], Statement::Declaration(node) if matches!(node.as_ref(), AstNode::Function(_))));
+    }
+
+    #[test]
+    fn test_folds_constant_false_if() {
+        let mut ast = vec![AstNode::Statement(Box::new(Statement::If {
+            condition: Box::new(Expression::Literal(Literal::Bool(false))),
+            then_branch: Box::new(echo("dead")),
+            else_branch: Some(Box::new(echo("live"))),
+        }))];
+
+        let warnings = UnreachableCodeEliminator::new().run(&mut ast);
+        assert_eq!(warnings.len(), 1);
+
+        match &ast[0] {
+            AstNode::Statement(stmt) => match stmt.as_ref() {
+                Statement::Echo(exprs) => match &exprs[0] {
+                    Expression::Literal(Literal::String(s)) => assert_eq!(s, "live"),
+                    _ => panic!("Expected string literal"),
+                },
+                _ => panic!("Expected echo"),
+            },
+            _ => panic!("Expected statement"),
+        }
+    }
+
+    #[test]
+    fn test_cuts_inside_folded_branches() {
+        // if (true) { return; echo "a"; } echo "b";
+        let mut ast = vec![AstNode::Statement(Box::new(Statement::Block(vec![
+            Statement::If {
+                condition: Box::new(Expression::Literal(Literal::Bool(true))),
+                then_branch: Box::new(Statement::Block(vec![Statement::Return(None), echo("a")])),
+                else_branch: None,
+            },
+            echo("b"),
+        ])))];
+
+        let warnings = UnreachableCodeEliminator::new().run(&mut ast);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[1].message.contains("1 unreachable statement after a terminating branch"));
+
+        let AstNode::Statement(block) = &ast[0] else { unreachable!() };
+        let Statement::Block(statements) = block.as_ref() else { unreachable!() };
+        assert!(matches!(statements.as_slice(), [Statement::Block(inner)] if matches!(inner.as_slice(), [Statement::Return(None)])));
+    }
+
+    #[test]
+    fn test_constant_truthiness() {
+        assert_eq!(constant_truthiness(&Expression::Literal(Literal::String("0".to_string()))), Some(false));
+        assert_eq!(constant_truthiness(&Expression::Literal(Literal::Int(3))), Some(true));
+        assert_eq!(constant_truthiness(&Expression::Variable("x".to_string())), None);
+    }
+}
@@ -155,19 +155,25 @@ pub mod string {
             .collect()
     }
     
-    /// Escape string for LLVM IR
+    /// Escape string for an LLVM IR `c"..."` constant
+    ///
+    /// Each byte of the UTF-8 encoding that is not printable ASCII, or is a
+    /// quote or backslash, becomes one `\XX` escape, so the constant holds
+    /// `s.len()` bytes.
     pub fn llvm_escape(s: &str) -> String {
-        s.chars()
-            .map(|c| match c {
-                '\\' => "\\5C".to_string(),
-                '"' => "\\22".to_string(),
-                '\n' => "\\0A".to_string(),
-                '\r' => "\\0D".to_string(),
-                '\t' => "\\09".to_string(),
-                _ if c.is_ascii() && !c.is_control() => c.to_string(),
-                _ => format!("\\{:02X}", c as u8),
-            })
-            .collect()
+        llvm_escape_bytes(s.as_bytes())
+    }
+    
+    /// Escape bytes for an LLVM `c"..."` constant
+    pub fn llvm_escape_bytes(bytes: &[u8]) -> String {
+        let mut escaped = String::with_capacity(bytes.len());
+        for &byte in bytes {
+            match byte {
+                0x20..=0x7e if byte != b'"' && byte != b'\\' => escaped.push(byte as char),
+                _ => escaped.push_str(&format!("\\{:02X}", byte)),
+            }
+        }
+        escaped
     }
     
     /// Convert string to valid identifier
@@ -463,6 +469,8 @@ mod tests {
         assert_eq!(string::shell_escape("hello world"), "\"hello world\"");
         assert_eq!(string::to_identifier("hello-world"), "hello_world");
         assert_eq!(string::c_escape("hello\nworld"), "hello\\nworld");
+        assert_eq!(string::llvm_escape("a\"b\\\n"), "a\\22b\\5C\\0A");
+        assert_eq!(string::llvm_escape("é€"), "\\C3\\A9\\E2\\82\\AC");
     }
 
     #[test]
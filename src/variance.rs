@@ -0,0 +1,541 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Method signature compatibility.
+//!
+//! A method overriding a parent class or interface method must follow PHP
+//! 7.4's variance rules: parameter types are contravariant (the override
+//! accepts at least what the original accepts) and return types are
+//! covariant (it returns no more than the original). Parameter counts,
+//! by-reference passing, `static` and visibility must be compatible too.
+//!
+//! Violations name both declarations and carry their spans when an
+//! [`AstIndex`] of the program provides them.
+
+use std::collections::{HashMap, HashSet};
+use crate::ast::{AstIndex, AstNode, ClassDecl, Expression, FunctionDecl, InterfaceDecl, Literal, Parameter, Span, Visibility};
+use crate::coercion::declared_name;
+use crate::diagnostics::{codes, Diagnostic};
+use crate::types::{Type, TypeContext};
+
+/// A class or interface declaration
+#[derive(Clone, Copy)]
+enum Declaration<'a> {
+    Class(&'a ClassDecl),
+    Interface(&'a InterfaceDecl),
+}
+
+impl<'a> Declaration<'a> {
+    fn name(&self) -> &'a str {
+        match self {
+            Declaration::Class(decl) => &decl.name,
+            Declaration::Interface(decl) => &decl.name,
+        }
+    }
+
+    fn methods(&self) -> &'a [FunctionDecl] {
+        match self {
+            Declaration::Class(decl) => &decl.methods,
+            Declaration::Interface(decl) => &decl.methods,
+        }
+    }
+
+    fn parent(&self) -> Option<&'a str> {
+        match self {
+            Declaration::Class(decl) => decl.extends.as_deref(),
+            Declaration::Interface(_) => None,
+        }
+    }
+
+    /// Implemented interfaces of a class, extended interfaces of an interface
+    fn interfaces(&self) -> &'a [String] {
+        match self {
+            Declaration::Class(decl) => &decl.implements,
+            Declaration::Interface(decl) => &decl.extends,
+        }
+    }
+
+    fn method(&self, name: &str) -> Option<&'a FunctionDecl> {
+        self.methods().iter().find(|m| m.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Checks overriding methods against the methods they override
+pub struct VarianceChecker<'i> {
+    file: String,
+    index: Option<&'i AstIndex<'i>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'i> VarianceChecker<'i> {
+    pub fn new(file: impl Into<String>) -> Self {
+        Self {
+            file: file.into(),
+            index: None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Take declaration spans from an index of the checked program
+    pub fn with_index(mut self, index: &'i AstIndex<'i>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Check a program
+    pub fn check(&mut self, ast: &[AstNode]) {
+        let mut declarations = Vec::new();
+        collect_declarations(ast, &mut declarations);
+        let by_name: HashMap<String, Declaration> = declarations.iter()
+            .map(|decl| (class_key(decl.name()), *decl))
+            .collect();
+        let mut classes = TypeContext::new();
+        classes.register_declarations(ast);
+
+        for declaration in &declarations {
+            for method in declaration.methods() {
+                for (owner, overridden) in overridden_methods(&by_name, declaration, &method.name) {
+                    let reasons = compare(&classes, declaration.name(), method, owner, overridden);
+                    if !reasons.is_empty() {
+                        self.report(declaration.name(), method, owner, overridden, reasons);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Take the diagnostics reported so far
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn span(&self, decl: &FunctionDecl) -> Option<Span> {
+        let index = self.index?;
+        index.function_id(decl).and_then(|id| index.span(id))
+    }
+
+    fn report(&mut self, class: &str, method: &FunctionDecl, owner: &str, overridden: &FunctionDecl, reasons: Vec<String>) {
+        let mut diagnostic = Diagnostic::error(
+            codes::METHOD_SIGNATURE,
+            format!(
+                "Declaration of {} must be compatible with {}",
+                render_method(class, method),
+                render_method(owner, overridden)
+            ),
+        )
+        .with_file(self.file.clone())
+        .with_span(self.span(method));
+        for reason in reasons {
+            diagnostic = diagnostic.with_note(reason);
+        }
+        let related = format!("{}::{}() is declared here", owner.trim_start_matches('\\'), overridden.name);
+        self.diagnostics.push(diagnostic.with_related(related, Some(self.file.clone()), self.span(overridden)));
+    }
+}
+
+/// Top-level classes and interfaces in source order
+fn collect_declarations<'a>(ast: &'a [AstNode], out: &mut Vec<Declaration<'a>>) {
+    for node in ast {
+        match node {
+            AstNode::Program(nodes) => collect_declarations(nodes, out),
+            AstNode::Namespace(ns) => collect_declarations(&ns.statements, out),
+            AstNode::Class(decl) => out.push(Declaration::Class(decl)),
+            AstNode::Interface(decl) => out.push(Declaration::Interface(decl)),
+            _ => {}
+        }
+    }
+}
+
+/// Methods a method of `declaration` overrides: the nearest ancestor
+/// class's, and those of every interface it inherits
+fn overridden_methods<'a>(
+    declarations: &HashMap<String, Declaration<'a>>,
+    declaration: &Declaration<'a>,
+    method: &str,
+) -> Vec<(&'a str, &'a FunctionDecl)> {
+    let mut found = Vec::new();
+    let mut interfaces: Vec<&str> = declaration.interfaces().iter().map(String::as_str).collect();
+
+    // Private methods are not inherited; constructors of classes may change freely
+    let mut parent = declaration.parent();
+    let mut overriding = false;
+    let mut seen = HashSet::new();
+    while let Some(name) = parent {
+        let Some(class) = declarations.get(&class_key(name)).filter(|_| seen.insert(class_key(name))) else { break };
+        interfaces.extend(class.interfaces().iter().map(String::as_str));
+        if let Some(overridden) = class.method(method).filter(|_| !overriding) {
+            overriding = true;
+            if overridden.visibility != Visibility::Private && !method.eq_ignore_ascii_case("__construct") {
+                found.push((class.name(), overridden));
+            }
+        }
+        parent = class.parent();
+    }
+
+    let mut seen = HashSet::new();
+    while let Some(name) = interfaces.pop() {
+        if !seen.insert(class_key(name)) {
+            continue;
+        }
+        let Some(interface @ Declaration::Interface(_)) = declarations.get(&class_key(name)) else { continue };
+        interfaces.extend(interface.interfaces().iter().map(String::as_str));
+        if let Some(overridden) = interface.method(method) {
+            found.push((interface.name(), overridden));
+        }
+    }
+    found
+}
+
+/// Reasons `method` of `class` cannot override `overridden` of `owner`
+fn compare(classes: &TypeContext, class: &str, method: &FunctionDecl, owner: &str, overridden: &FunctionDecl) -> Vec<String> {
+    let mut reasons = Vec::new();
+    if method.is_static != overridden.is_static {
+        reasons.push(if method.is_static {
+            "cannot make a non-static method static".to_string()
+        } else {
+            "cannot make a static method non-static".to_string()
+        });
+    }
+    if rank(&method.visibility) > rank(&overridden.visibility) {
+        reasons.push(format!(
+            "access level must be {} (as in {}) or weaker",
+            visibility_name(&overridden.visibility),
+            owner.trim_start_matches('\\')
+        ));
+    }
+
+    let required = |f: &FunctionDecl| f.parameters.iter().filter(|p| p.default_value.is_none() && !p.is_variadic).count();
+    if required(method) > required(overridden) {
+        reasons.push(format!(
+            "requires {} argument(s) but the overridden method requires {}",
+            required(method),
+            required(overridden)
+        ));
+    }
+
+    let variadic = method.parameters.last().filter(|p| p.is_variadic);
+    for (i, expected) in overridden.parameters.iter().enumerate() {
+        let Some(actual) = method.parameters.get(i).filter(|p| !p.is_variadic).or(variadic) else {
+            reasons.push(format!("parameter ${} of the overridden method is missing", expected.name));
+            continue;
+        };
+        if actual.is_reference != expected.is_reference {
+            let negation = if expected.is_reference { "" } else { "not " };
+            reasons.push(format!("parameter ${} must {}be passed by reference", actual.name, negation));
+        }
+        // Parameters are contravariant: the override must accept every value the original accepts
+        let (accepted, wanted) = (parameter_type(classes, actual, class), parameter_type(classes, expected, owner));
+        if subtype(classes, &wanted, &accepted, owner) == Some(false) {
+            reasons.push(format!(
+                "parameter ${} of type {} does not accept {}",
+                actual.name,
+                declared_name(actual.typ.as_ref().unwrap_or(&Type::Unknown)),
+                declared_name(expected.typ.as_ref().unwrap_or(&Type::Unknown))
+            ));
+        }
+    }
+
+    // Returns are covariant; an undeclared return type is not checked
+    if let (Some(returned), Some(expected)) = (&method.return_type, &overridden.return_type) {
+        if subtype(classes, &resolve(classes, returned, class), &resolve(classes, expected, owner), class) == Some(false) {
+            reasons.push(format!(
+                "return type {} is not a subtype of {}",
+                declared_name(returned),
+                declared_name(expected)
+            ));
+        }
+    }
+    reasons
+}
+
+/// Declared type of a parameter, `null` included for a `null` default
+fn parameter_type(classes: &TypeContext, parameter: &Parameter, class: &str) -> Type {
+    match &parameter.typ {
+        Some(typ) if matches!(parameter.default_value, Some(Expression::Literal(Literal::Null))) => {
+            Type::union([resolve(classes, typ, class), Type::Null])
+        }
+        Some(typ) => resolve(classes, typ, class),
+        None => Type::Unknown,
+    }
+}
+
+/// Bind `self` and `parent` relative to the declaring class and normalize
+/// keyword types; `static` is kept for [`subtype`]
+fn resolve(classes: &TypeContext, typ: &Type, class: &str) -> Type {
+    match classes.get_class_info(class) {
+        Some(info) => normalize(&info.bind_relative(typ, None)),
+        None => normalize(typ),
+    }
+}
+
+fn normalize(typ: &Type) -> Type {
+    match typ {
+        Type::Object(name) => match class_key(name).as_str() {
+            "mixed" => Type::Unknown,
+            "void" | "null" => Type::Null,
+            _ => typ.clone(),
+        },
+        Type::Union(members) => Type::Union(members.iter().map(normalize).collect()),
+        Type::Intersection(members) => Type::Intersection(members.iter().map(normalize).collect()),
+        // Generic types without arguments are template parameters
+        Type::Generic(_, args) if args.is_empty() => Type::Unknown,
+        Type::Generic(name, _) => Type::Object(name.clone()),
+        other => other.clone(),
+    }
+}
+
+/// Whether every value of `sub` is a value of `sup`
+///
+/// `static` in `sub` stands for `class`. `None` when that depends on
+/// classes that are not declared in the program.
+fn subtype(classes: &TypeContext, sub: &Type, sup: &Type, class: &str) -> Option<bool> {
+    let each = |types: &[Type], f: &dyn Fn(&Type) -> Option<bool>| types.iter().map(f).collect::<Vec<_>>();
+    match (sub, sup) {
+        (_, Type::Unknown) => Some(true),
+        (Type::Unknown, _) => Some(false),
+        (Type::Union(members), _) => all(each(members, &|m| subtype(classes, m, sup, class))),
+        (_, Type::Intersection(members)) => all(each(members, &|m| subtype(classes, sub, m, class))),
+        (_, Type::Union(members)) => any(each(members, &|m| subtype(classes, sub, m, class))),
+        (Type::Intersection(members), _) => any(each(members, &|m| subtype(classes, m, sup, class))),
+        (Type::Literal(l), Type::Literal(r)) => Some(l == r),
+        (Type::Literal(l), _) => subtype(classes, &l.base_type(), sup, class),
+        (_, Type::Literal(_)) => Some(false),
+        (Type::Array(_) | Type::AssociativeArray(_), Type::Array(_) | Type::AssociativeArray(_)) => Some(true),
+        (Type::Array(_) | Type::AssociativeArray(_), Type::Object(name)) => Some(class_key(name) == "iterable"),
+        (Type::Object(name), _) if class_key(name) == "never" => Some(true),
+        (Type::Object(name), Type::Object(parent)) => match (class_key(name).as_str(), class_key(parent).as_str()) {
+            (a, b) if a == b => Some(true),
+            ("static", _) => subtype(classes, &Type::Object(class.to_string()), sup, class),
+            (_, "static") => Some(false),
+            ("iterable" | "callable" | "object", _) => Some(false),
+            (_, "object") => Some(true),
+            (_, "iterable") => classes.is_subclass_of(name, "Traversable"),
+            // Closures and invokable objects
+            (_, "callable") => None,
+            _ => classes.is_subclass_of(name, parent),
+        },
+        (Type::Function(..) | Type::Callable(_) | Type::Generic(..), _)
+        | (_, Type::Function(..) | Type::Callable(_) | Type::Generic(..)) => None,
+        (a, b) => Some(a == b),
+    }
+}
+
+fn all(results: Vec<Option<bool>>) -> Option<bool> {
+    if results.contains(&Some(false)) {
+        Some(false)
+    } else if results.iter().all(|r| *r == Some(true)) {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+fn any(results: Vec<Option<bool>>) -> Option<bool> {
+    if results.contains(&Some(true)) {
+        Some(true)
+    } else if results.iter().all(|r| *r == Some(false)) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// `Class::method(int $a, string $b = 'x'): bool`, as PHP prints it
+fn render_method(class: &str, method: &FunctionDecl) -> String {
+    let parameters: Vec<_> = method.parameters.iter()
+        .map(|p| {
+            let mut out = String::new();
+            if let Some(typ) = &p.typ {
+                out.push_str(&declared_name(typ));
+                out.push(' ');
+            }
+            if p.is_reference {
+                out.push('&');
+            }
+            if p.is_variadic {
+                out.push_str("...");
+            }
+            out.push('$');
+            out.push_str(&p.name);
+            match &p.default_value {
+                Some(Expression::Literal(Literal::Null)) => out.push_str(" = null"),
+                Some(Expression::Literal(Literal::Int(n))) => out.push_str(&format!(" = {}", n)),
+                Some(Expression::Literal(Literal::Bool(b))) => out.push_str(&format!(" = {}", b)),
+                Some(Expression::Literal(Literal::String(s))) => out.push_str(&format!(" = '{}'", s)),
+                Some(_) => out.push_str(" = <default>"),
+                None => {}
+            }
+            out
+        })
+        .collect();
+    let mut out = format!("{}::{}({})", class.trim_start_matches('\\'), method.name, parameters.join(", "));
+    if let Some(typ) = &method.return_type {
+        out.push_str(": ");
+        out.push_str(&declared_name(typ));
+    }
+    out
+}
+
+fn rank(visibility: &Visibility) -> u8 {
+    match visibility {
+        Visibility::Public => 0,
+        Visibility::Protected => 1,
+        Visibility::Private => 2,
+    }
+}
+
+fn visibility_name(visibility: &Visibility) -> &'static str {
+    match visibility {
+        Visibility::Public => "public",
+        Visibility::Protected => "protected",
+        Visibility::Private => "private",
+    }
+}
+
+/// Normalized class name for comparisons
+fn class_key(name: &str) -> String {
+    name.trim_start_matches('\\').to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Statement, Visibility};
+
+    fn method(name: &str, parameters: Vec<(&str, Option<Type>)>, return_type: Option<Type>) -> FunctionDecl {
+        FunctionDecl {
+            name: name.to_string(),
+            parameters: parameters.into_iter()
+                .map(|(name, typ)| Parameter {
+                    name: name.to_string(),
+                    typ,
+                    default_value: None,
+                    is_reference: false,
+                    is_variadic: false,
+                })
+                .collect(),
+            return_type,
+            body: Box::new(Statement::Block(vec![])),
+            attributes: vec![],
+            is_static: false,
+            visibility: Visibility::Public,
+            doc_comment: None,
+        }
+    }
+
+    fn class(name: &str, extends: Option<&str>, implements: &[&str], methods: Vec<FunctionDecl>) -> AstNode {
+        AstNode::Class(ClassDecl {
+            name: name.to_string(),
+            extends: extends.map(str::to_string),
+            implements: implements.iter().map(|i| i.to_string()).collect(),
+            traits: vec![],
+            properties: vec![],
+            methods,
+            constants: vec![],
+            attributes: vec![],
+            is_abstract: false,
+            is_final: false,
+            is_trait: false,
+            is_interface: false,
+            is_enum: false,
+        })
+    }
+
+    fn object(name: &str) -> Option<Type> {
+        Some(Type::Object(name.to_string()))
+    }
+
+    fn check(ast: &[AstNode]) -> Vec<Diagnostic> {
+        let mut checker = VarianceChecker::new("app.php");
+        checker.check(ast);
+        checker.take_diagnostics()
+    }
+
+    #[test]
+    fn test_variance() {
+        // Widening a parameter and narrowing the return type are allowed
+        let ast = vec![
+            class("Animal", None, &[], vec![]),
+            class("Dog", Some("Animal"), &[], vec![]),
+            class("Shelter", None, &[], vec![method("adopt", vec![("pet", object("Dog"))], object("Animal"))]),
+            class("DogShelter", Some("Shelter"), &[], vec![
+                method("adopt", vec![("pet", object("Animal"))], object("Dog")),
+            ]),
+        ];
+        assert!(check(&ast).is_empty());
+
+        // ...the opposite is not
+        let ast = vec![
+            class("Animal", None, &[], vec![]),
+            class("Dog", Some("Animal"), &[], vec![]),
+            class("Shelter", None, &[], vec![method("adopt", vec![("pet", object("Animal"))], object("Dog"))]),
+            class("DogShelter", Some("Shelter"), &[], vec![
+                method("adopt", vec![("pet", object("Dog"))], Some(Type::Union(vec![Type::Object("Dog".into()), Type::Null]))),
+            ]),
+        ];
+        let diagnostics = check(&ast);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Declaration of DogShelter::adopt(Dog $pet): ?Dog must be compatible with Shelter::adopt(Animal $pet): Dog"
+        );
+        assert_eq!(diagnostics[0].notes, vec![
+            "parameter $pet of type Dog does not accept Animal".to_string(),
+            "return type ?Dog is not a subtype of Dog".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn test_interface_methods() {
+        let interface = AstNode::Interface(InterfaceDecl {
+            name: "Shape".to_string(),
+            extends: vec![],
+            constants: vec![],
+            methods: vec![method("area", vec![], Some(Type::Float))],
+        });
+        let mut area = method("area", vec![("scale", Some(Type::Int))], Some(Type::Int));
+        area.visibility = Visibility::Protected;
+        let ast = vec![
+            interface,
+            class("Base", None, &["Shape"], vec![]),
+            class("Square", Some("Base"), &[], vec![area]),
+        ];
+        let mut index = AstIndex::build(&ast);
+        let interface_method = match &ast[0] {
+            AstNode::Interface(decl) => &decl.methods[0],
+            _ => unreachable!(),
+        };
+        let square_method = match &ast[2] {
+            AstNode::Class(decl) => &decl.methods[0],
+            _ => unreachable!(),
+        };
+        index.set_span(index.function_id(interface_method).unwrap(), Span::new(30, 40));
+        index.set_span(index.function_id(square_method).unwrap(), Span::new(90, 99));
+
+        let mut checker = VarianceChecker::new("app.php").with_index(&index);
+        checker.check(&ast);
+        let diagnostics = checker.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, codes::METHOD_SIGNATURE);
+        assert_eq!(diagnostics[0].span, Some(Span::new(90, 99)));
+        assert_eq!(diagnostics[0].related[0].span, Some(Span::new(30, 40)));
+        assert_eq!(diagnostics[0].notes, vec![
+            "access level must be public (as in Shape) or weaker".to_string(),
+            "requires 1 argument(s) but the overridden method requires 0".to_string(),
+            "return type int is not a subtype of float".to_string(),
+        ]);
+    }
+}
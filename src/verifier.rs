@@ -0,0 +1,261 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Structural checks of textual LLVM IR.
+//!
+//! Without the `inkwell` feature, IR goes to `llc` as text, and a mistake
+//! in code generation surfaces as an `llc` message about a line of a
+//! temporary file. This checker runs first and reports the function at
+//! fault instead. It only knows the shape of the IR that
+//! [`IrGenerator`](crate::ir::IrGenerator) writes, one instruction per line,
+//! and checks that:
+//!
+//! - every function body is closed and every block ends with exactly one
+//!   terminator
+//! - labels are unique and every branch target exists
+//! - every local value is defined once and every one used is defined
+//! - `ret` returns the function's return type
+
+use std::collections::HashSet;
+use crate::error::{CompileError, CompileResult};
+
+/// Instructions that end a basic block
+const TERMINATORS: &[&str] = &["ret", "br", "switch", "invoke", "resume", "unreachable", "indirectbr"];
+
+/// Keywords that may come between `define` and the return type
+const DEFINE_PREFIXES: &[&str] = &[
+    "hidden", "internal", "private", "dso_local", "linkonce_odr", "weak", "zeroext", "signext", "noundef",
+];
+
+/// Check textual IR, naming the offending function on failure
+pub fn verify_module(ir: &str) -> CompileResult<()> {
+    let types: HashSet<&str> = ir.lines()
+        .filter_map(|line| line.split_once(" = type "))
+        .map(|(name, _)| name.trim())
+        .collect();
+    let mut lines = ir.lines().enumerate();
+    while let Some((number, line)) = lines.next() {
+        if !line.starts_with("define ") {
+            continue;
+        }
+        let name = function_name(line).unwrap_or("<unnamed>");
+        let mut body = Vec::new();
+        let closed = lines.by_ref()
+            .inspect(|&(number, line)| body.push((number, line)))
+            .any(|(_, line)| line == "}");
+        let problem = if closed {
+            body.pop();
+            check_function(line, &body, &types)
+        } else {
+            Err((number, "missing closing `}`".to_string()))
+        };
+        problem.map_err(|(number, problem)| CompileError::IrGeneration(format!(
+            "invalid IR in function {} at line {}: {}",
+            name, number + 1, problem
+        )))?;
+    }
+    Ok(())
+}
+
+/// Name of the function defined at or before a line of the IR, 1-based
+pub fn function_at_line(ir: &str, line: usize) -> Option<&str> {
+    let mut current = None;
+    for text in ir.lines().take(line) {
+        if text.starts_with("define ") {
+            current = function_name(text);
+        } else if text == "}" {
+            current = None;
+        }
+    }
+    current
+}
+
+/// Name after the `@` of a `define` line, with any quotes removed
+fn function_name(define: &str) -> Option<&str> {
+    let rest = &define[define.find(" @")? + 2..];
+    match rest.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next(),
+        None => rest.split('(').next(),
+    }
+}
+
+type Problem = (usize, String);
+
+fn check_function(define: &str, body: &[(usize, &str)], types: &HashSet<&str>) -> Result<(), Problem> {
+    let return_type = return_type(define);
+    let mut defined: HashSet<&str> = parameters(define).collect();
+    let mut labels = HashSet::new();
+    let mut uses = Vec::new();
+    let mut targets = Vec::new();
+    // The entry block may have no label
+    let mut open_block = Some(body.first().map_or(0, |&(number, _)| number));
+
+    for (index, &(number, line)) in body.iter().enumerate() {
+        if let Some(label) = line.strip_suffix(':').filter(|_| !line.starts_with(' ')) {
+            if let Some(start) = open_block.filter(|_| index > 0) {
+                return Err((start.max(number.saturating_sub(1)), format!("block before `{}` has no terminator", label)));
+            }
+            if !labels.insert(label) {
+                return Err((number, format!("duplicate label `{}`", label)));
+            }
+            open_block = Some(number);
+            continue;
+        }
+        let instruction = line.trim();
+        if instruction.is_empty() {
+            continue;
+        }
+        // The normal and unwind destinations of an `invoke`
+        if instruction.starts_with("to label ") {
+            targets.extend(label_operands(instruction).map(|label| (number, label)));
+            continue;
+        }
+        if open_block.is_none() {
+            return Err((number, format!("instruction after the block's terminator: `{}`", instruction)));
+        }
+        let (result, operation) = match instruction.split_once(" = ") {
+            Some((result, operation)) if result.starts_with('%') => (Some(&result[1..]), operation),
+            _ => (None, instruction),
+        };
+        if let Some(result) = result {
+            if !defined.insert(result) {
+                return Err((number, format!("`%{}` is defined twice", result)));
+            }
+        }
+        let opcode = operation.split_whitespace().next().unwrap_or("");
+        if opcode == "ret" {
+            let returned = operation["ret".len()..].trim();
+            let matches = match return_type {
+                "void" => returned == "void",
+                ty => returned.strip_prefix(ty).is_some_and(|value| value.starts_with(' ')),
+            };
+            if !matches {
+                return Err((number, format!("`{}` in a function returning {}", instruction, return_type)));
+            }
+        }
+        targets.extend(label_operands(operation).map(|label| (number, label)));
+        uses.extend(local_operands(operation).map(|value| (number, value)));
+        if TERMINATORS.contains(&opcode) {
+            open_block = None;
+        }
+    }
+    if let Some(start) = open_block {
+        return Err((start, "last block has no terminator".to_string()));
+    }
+    if let Some((number, label)) = targets.into_iter().find(|(_, label)| !labels.contains(label)) {
+        return Err((number, format!("branch to unknown label `%{}`", label)));
+    }
+    let known = |value: &str| {
+        defined.contains(value) || labels.contains(value) || types.contains(format!("%{}", value).as_str())
+    };
+    match uses.into_iter().find(|(_, value)| !known(value)) {
+        Some((number, value)) => Err((number, format!("`%{}` is used but never defined", value))),
+        None => Ok(()),
+    }
+}
+
+/// Return type of a `define` line
+fn return_type(define: &str) -> &str {
+    let mut rest = define["define".len()..].trim_start();
+    while let Some(keyword) = DEFINE_PREFIXES.iter().find(|keyword| {
+        rest.strip_prefix(**keyword).is_some_and(|after| after.starts_with(' '))
+    }) {
+        rest = rest[keyword.len()..].trim_start();
+    }
+    rest.split(" @").next().unwrap_or(rest).trim()
+}
+
+/// Names of the parameters of a `define` line
+fn parameters(define: &str) -> impl Iterator<Item = &str> {
+    let start = define.find('(').map_or(define.len(), |i| i + 1);
+    let end = define.find(") ").or_else(|| define.rfind(')')).unwrap_or(define.len()).max(start);
+    local_operands(&define[start..end])
+}
+
+/// Labels an instruction branches to
+fn label_operands(instruction: &str) -> impl Iterator<Item = &str> {
+    instruction.match_indices("label %").filter_map(|(i, _)| identifier(&instruction[i + "label %".len()..]))
+}
+
+/// Local names (`%name`) an instruction mentions, types included
+fn local_operands(instruction: &str) -> impl Iterator<Item = &str> {
+    instruction.match_indices('%').filter_map(|(i, _)| identifier(&instruction[i + 1..]))
+}
+
+/// Identifier at the start of `text`, quoted or not
+fn identifier(text: &str) -> Option<&str> {
+    if let Some(quoted) = text.strip_prefix('"') {
+        return quoted.find('"').map(|end| &text[..end + 2]);
+    }
+    let end = text.find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '$' | '-')))
+        .unwrap_or(text.len());
+    (end > 0).then(|| &text[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{AstNode, Expression, Literal, Statement};
+    use crate::ir::IrGenerator;
+
+    fn problem(ir: &str) -> String {
+        match verify_module(ir) {
+            Err(CompileError::IrGeneration(message)) => message,
+            other => panic!("expected an IR error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_generated_ir_passes() {
+        let ast = vec![AstNode::Statement(Box::new(Statement::Echo(vec![
+            Expression::Literal(Literal::String("hi".to_string())),
+        ])))];
+        let ir = IrGenerator::new().unwrap().generate(&ast).unwrap();
+        assert!(verify_module(&ir).is_ok());
+    }
+
+    #[test]
+    fn test_problems_name_the_function() {
+        let message = problem("define i32 @f() {\n  ret i64 0\n}\n");
+        assert_eq!(message, "invalid IR in function f at line 2: `ret i64 0` in a function returning i32");
+
+        let missing_terminator = "define hidden void @\"php.A::run\"(i64 %n) {\n  %t.0 = add i64 %n, 1\nbb.0:\n  ret void\n}\n";
+        assert!(problem(missing_terminator).starts_with("invalid IR in function php.A::run at line 2: block before `bb.0`"));
+
+        let unknown_label = "define void @g() {\n  br label %bb.9\n}\n";
+        assert!(problem(unknown_label).ends_with("branch to unknown label `%bb.9`"));
+
+        let undefined = "%php.mixed = type { i32, i64 }\ndefine void @h(%php.mixed %v) {\n  %t.0 = extractvalue %php.mixed %v, 0\n  %t.1 = add i32 %t.0, %t.2\n  ret void\n}\n";
+        assert!(problem(undefined).ends_with("at line 4: `%t.2` is used but never defined"));
+
+        let twice = "define void @k() {\n  %t.0 = add i64 1, 1\n  %t.0 = add i64 2, 2\n  ret void\n}\n";
+        assert!(problem(twice).ends_with("`%t.0` is defined twice"));
+
+        let labeled_entry = "define i32 @main() {\nentry:\n  br label %done\ndone:\n  ret i32 0\n}\n";
+        assert!(verify_module(labeled_entry).is_ok());
+
+        let unclosed = "define void @u() {\n  ret void\n";
+        assert!(problem(unclosed).contains("function u at line 1: missing closing `}`"));
+    }
+
+    #[test]
+    fn test_function_at_line() {
+        let ir = "declare void @x()\ndefine void @a() {\n  ret void\n}\ndefine void @\"b c\"() {\n  ret void\n}\n";
+        assert_eq!(function_at_line(ir, 1), None);
+        assert_eq!(function_at_line(ir, 3), Some("a"));
+        assert_eq!(function_at_line(ir, 6), Some("b c"));
+    }
+}
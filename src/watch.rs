@@ -0,0 +1,210 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Watch mode: rebuild whenever a source changes.
+//!
+//! The directories of the input, the modules and the stubs are watched
+//! recursively for PHP files, stubs and project manifests. Changes arriving
+//! within [`DEBOUNCE`] of each other trigger one rebuild. It is skipped
+//! when only the input changed and it still parses to the same
+//! declarations and top-level code, as after editing a comment. Any other
+//! file may be included, autoloaded or linked in, so a change to it always
+//! rebuilds.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::Duration;
+use log::{error, info, warn};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use crate::compiler::{Compiler, CompilerOptions};
+use crate::error::{CompileError, CompileResult};
+use crate::link;
+use crate::manifest::MANIFEST_FILE;
+
+/// Quiet period collecting the events of one save into a single rebuild
+pub const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Compile, then compile again on every change until the process is stopped
+///
+/// With `run`, the binary is started after every successful build with
+/// `args`, and stopped before the next one. `report` is called after every
+/// build, to show its diagnostics.
+pub fn watch<R>(compiler: &mut Compiler, run: bool, args: &[String], mut report: R) -> CompileResult<()>
+where
+    R: FnMut(&Compiler),
+{
+    let (sender, events) = channel();
+    let mut watcher: RecommendedWatcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+    for dir in watched_directories(compiler.options()) {
+        info!("Watching {}", dir.display());
+        watcher.watch(&dir, RecursiveMode::Recursive).map_err(watch_error)?;
+    }
+
+    let mut program: Option<Child> = None;
+    let mut changed: Vec<PathBuf> = Vec::new();
+    let mut first = true;
+    loop {
+        // Checked on the first pass too, to record what the first build compiles
+        if needs_rebuild(compiler, &changed) || first {
+            stop(&mut program);
+            let result = compiler.compile();
+            report(compiler);
+            match result {
+                Ok(()) if run => match start(compiler.options(), args) {
+                    Ok(child) => program = Some(child),
+                    Err(e) => error!("Failed to start the program: {}", e),
+                },
+                Ok(()) => info!("Build finished: {}", compiler.options().output.display()),
+                Err(e) => error!("Compilation error: {}", e),
+            }
+        } else {
+            info!("No changes to the compiled program");
+        }
+        first = false;
+        info!("Waiting for changes...");
+        changed = wait(&events)?;
+    }
+}
+
+/// Directories holding the input, the modules, the stubs and the include paths
+fn watched_directories(options: &CompilerOptions) -> BTreeSet<PathBuf> {
+    let files = std::iter::once(&options.input)
+        .chain(&options.modules)
+        .chain(&options.stubs)
+        .chain(&options.manifest);
+    let mut dirs: BTreeSet<PathBuf> = files
+        .map(|file| match file.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        })
+        .chain(options.include_paths.iter().cloned())
+        .map(|dir| std::fs::canonicalize(&dir).unwrap_or(dir))
+        .collect();
+    // Watching a directory covers the ones below it
+    let nested: Vec<PathBuf> = dirs.iter()
+        .filter(|dir| dirs.iter().any(|other| other != *dir && dir.starts_with(other)))
+        .cloned()
+        .collect();
+    for dir in nested {
+        dirs.remove(&dir);
+    }
+    dirs
+}
+
+/// Whether a change to `path` can affect the build
+fn is_source(path: &Path) -> bool {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    matches!(extension, "php" | "phpstub" | "phar" | "zip")
+        || path.file_name().is_some_and(|name| name == MANIFEST_FILE)
+}
+
+/// Whether `changed` files need the program to be compiled again
+fn needs_rebuild(compiler: &mut Compiler, changed: &[PathBuf]) -> bool {
+    if !only_input(&compiler.options().input, changed) {
+        return true;
+    }
+    // A parse error is reported by the build
+    !compiler.parse_changes().is_ok_and(|(_, changes)| changes.is_empty())
+}
+
+/// Whether every changed file is the input itself
+fn only_input(input: &Path, changed: &[PathBuf]) -> bool {
+    let canonical = |path: &Path| std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let input = canonical(input);
+    changed.iter().all(|path| canonical(path) == input)
+}
+
+/// Block until sources change, then for the rest of the burst of changes
+fn wait(events: &Receiver<notify::Result<notify::Event>>) -> CompileResult<Vec<PathBuf>> {
+    let mut changed = BTreeSet::new();
+    loop {
+        let event = if changed.is_empty() {
+            events.recv().map_err(|_| watch_error("the file watcher stopped"))?
+        } else {
+            match events.recv_timeout(DEBOUNCE) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => return Ok(changed.into_iter().collect()),
+                Err(RecvTimeoutError::Disconnected) => return Err(watch_error("the file watcher stopped")),
+            }
+        };
+        match event {
+            Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) => {
+                changed.extend(event.paths.into_iter().filter(|path| is_source(path)));
+            }
+            Ok(_) => {}
+            Err(e) => warn!("File watcher: {}", e),
+        }
+    }
+}
+
+/// Start the built program
+fn start(options: &CompilerOptions, args: &[String]) -> CompileResult<Child> {
+    let binary = link::executable_path(&options.output, &options.resolved_target());
+    info!("Running {}", binary.display());
+    Ok(Command::new(&binary).args(args).spawn()?)
+}
+
+/// Stop the program started after the previous build, if it still runs
+fn stop(program: &mut Option<Child>) {
+    if let Some(mut child) = program.take() {
+        if let Ok(None) = child.try_wait() {
+            let _ = child.kill();
+        }
+        let _ = child.wait();
+    }
+}
+
+fn watch_error(e: impl std::fmt::Display) -> CompileError {
+    CompileError::Internal(format!("watch mode: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watched_directories() {
+        let options = CompilerOptions {
+            input: PathBuf::from("/app/src/main.php"),
+            modules: vec![PathBuf::from("/app/src/lib/db.php"), PathBuf::from("/vendor/lib.php")],
+            stubs: vec![PathBuf::from("/app/stubs/redis.phpstub")],
+            manifest: Some(PathBuf::from("/app/src/php2ir.toml")),
+            ..CompilerOptions::default()
+        };
+        let dirs: Vec<PathBuf> = watched_directories(&options).into_iter().collect();
+        assert_eq!(dirs, [PathBuf::from("/app/src"), PathBuf::from("/app/stubs"), PathBuf::from("/vendor")]);
+
+        assert!(is_source(Path::new("/app/src/main.php")));
+        assert!(is_source(Path::new("/app/php2ir.toml")));
+        assert!(!is_source(Path::new("/app/src/main")));
+        assert!(!is_source(Path::new("/app/src/.main.php.swp")));
+    }
+
+    #[test]
+    fn test_only_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("main.php");
+        let included = dir.path().join("lib.php");
+        std::fs::write(&input, "<?php\ninclude 'lib.php';\n").unwrap();
+        std::fs::write(&included, "<?php\necho 1;\n").unwrap();
+
+        assert!(only_input(&input, &[dir.path().join(".").join("main.php")]));
+        assert!(!only_input(&input, &[input.clone(), included.clone()]));
+        assert!(!only_input(&input, &[included]));
+    }
+}
@@ -0,0 +1,147 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Timer thread enforcing `max_execution_time`.
+//!
+//! The watchdog never interrupts the program directly: when the deadline
+//! passes it raises a flag that the runtime polls at safe points (function
+//! calls, loop back-edges) and turns into a catchable fatal error.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Shared state between the watchdog thread and its owner
+#[derive(Debug, Default)]
+struct State {
+    deadline: Option<Instant>,
+    shutdown: bool,
+}
+
+/// Background timer that flags execution time overruns
+#[derive(Debug)]
+pub struct Watchdog {
+    state: Arc<(Mutex<State>, Condvar)>,
+    expired: Arc<AtomicBool>,
+    limit: Option<Duration>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Start the timer thread, armed with `limit` if given
+    pub fn start(limit: Option<Duration>) -> Self {
+        let state = Arc::new((Mutex::new(State::default()), Condvar::new()));
+        let expired = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let state = Arc::clone(&state);
+            let expired = Arc::clone(&expired);
+            std::thread::Builder::new()
+                .name("php2ir-watchdog".to_string())
+                .spawn(move || run(&state, &expired))
+                .ok()
+        };
+
+        let mut watchdog = Self { state, expired, limit: None, thread };
+        watchdog.reset(limit);
+        watchdog
+    }
+
+    /// Restart the countdown with a new limit (`None` disables it)
+    pub fn reset(&mut self, limit: Option<Duration>) {
+        self.limit = limit;
+        self.expired.store(false, Ordering::SeqCst);
+
+        let (lock, condvar) = &*self.state;
+        if let Ok(mut state) = lock.lock() {
+            state.deadline = limit.map(|limit| Instant::now() + limit);
+            condvar.notify_one();
+        }
+    }
+
+    /// Current limit
+    pub fn limit(&self) -> Option<Duration> {
+        self.limit
+    }
+
+    /// Check and clear the overrun flag
+    pub fn take_expired(&self) -> bool {
+        self.expired.swap(false, Ordering::SeqCst)
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        let (lock, condvar) = &*self.state;
+        if let Ok(mut state) = lock.lock() {
+            state.shutdown = true;
+            condvar.notify_one();
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(state: &(Mutex<State>, Condvar), expired: &AtomicBool) {
+    let (lock, condvar) = state;
+    let Ok(mut guard) = lock.lock() else {
+        return;
+    };
+
+    while !guard.shutdown {
+        guard = match guard.deadline {
+            None => match condvar.wait(guard) {
+                Ok(guard) => guard,
+                Err(_) => return,
+            },
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    expired.store(true, Ordering::SeqCst);
+                    guard.deadline = None;
+                    continue;
+                }
+                match condvar.wait_timeout(guard, deadline - now) {
+                    Ok((guard, _)) => guard,
+                    Err(_) => return,
+                }
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchdog_expires() {
+        let watchdog = Watchdog::start(Some(Duration::from_millis(10)));
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(watchdog.take_expired());
+        assert!(!watchdog.take_expired());
+    }
+
+    #[test]
+    fn test_watchdog_reset() {
+        let mut watchdog = Watchdog::start(Some(Duration::from_millis(10)));
+        watchdog.reset(None);
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(!watchdog.take_expired());
+    }
+}
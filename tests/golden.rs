@@ -0,0 +1,163 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Golden end-to-end tests.
+//!
+//! Every `tests/golden/*.php` program is run through the `php2ir` binary and
+//! its stdout and exit status are compared with `<name>.out` and
+//! `<name>.exit` (0 when absent). Programs run in the interpreter
+//! (`--interpret`) and, when `llc` and `ld.lld` are installed, as compiled
+//! native binaries.
+//!
+//! Cases listed in `expected_failures.txt` must still fail, so a fix that
+//! makes one pass has to take it off the list.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const PHP2IR: &str = env!("CARGO_BIN_EXE_php2ir");
+
+/// One program of the corpus with its expected behavior
+struct Case {
+    name: String,
+    source: PathBuf,
+    stdout: String,
+    status: i32,
+}
+
+fn corpus_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+fn load_cases() -> Vec<Case> {
+    let dir = corpus_dir();
+    let mut cases: Vec<Case> = fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("cannot read {}: {}", dir.display(), e))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "php"))
+        .map(|source| {
+            let name = source.file_stem().unwrap().to_string_lossy().into_owned();
+            let stdout = fs::read_to_string(source.with_extension("out"))
+                .unwrap_or_else(|e| panic!("{}: missing {}.out: {}", name, name, e));
+            let status = match fs::read_to_string(source.with_extension("exit")) {
+                Ok(status) => status.trim().parse()
+                    .unwrap_or_else(|e| panic!("{}: invalid {}.exit: {}", name, name, e)),
+                Err(_) => 0,
+            };
+            Case { name, source, stdout, status }
+        })
+        .collect();
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    cases
+}
+
+fn expected_failures() -> Vec<String> {
+    fs::read_to_string(corpus_dir().join("expected_failures.txt"))
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+fn toolchain_available() -> bool {
+    ["llc", "ld.lld"].iter().all(|tool| {
+        Command::new(tool)
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    })
+}
+
+/// Compare one run with the case's expectations
+fn check(case: &Case, mode: &str, output: std::io::Result<Output>) -> Result<(), String> {
+    let output = output.map_err(|e| format!("{} [{}]: failed to run: {}", case.name, mode, e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let status = output.status.code().unwrap_or(-1);
+    if stdout != case.stdout || status != case.status {
+        return Err(format!(
+            "{} [{}]: expected exit {} and stdout {:?}, got exit {} and stdout {:?}\nstderr: {}",
+            case.name,
+            mode,
+            case.status,
+            case.stdout,
+            status,
+            stdout,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ));
+    }
+    Ok(())
+}
+
+fn run_case(case: &Case, native: bool, scratch: &Path) -> Result<(), String> {
+    check(case, "interpret", Command::new(PHP2IR).arg(&case.source).arg("--interpret").output())?;
+
+    if native {
+        let binary = scratch.join(&case.name);
+        let compile = Command::new(PHP2IR)
+            .arg(&case.source)
+            .arg("-o")
+            .arg(&binary)
+            .output()
+            .map_err(|e| format!("{} [native]: failed to run php2ir: {}", case.name, e))?;
+        if !compile.status.success() {
+            return Err(format!(
+                "{} [native]: compilation failed\nstderr: {}",
+                case.name,
+                String::from_utf8_lossy(&compile.stderr).trim_end()
+            ));
+        }
+        check(case, "native", Command::new(&binary).output())?;
+    }
+    Ok(())
+}
+
+#[test]
+fn golden_corpus() {
+    let cases = load_cases();
+    assert!(!cases.is_empty(), "no golden cases in {}", corpus_dir().display());
+
+    let expected_failures = expected_failures();
+    for name in &expected_failures {
+        assert!(
+            cases.iter().any(|case| &case.name == name),
+            "expected_failures.txt lists unknown case {}",
+            name
+        );
+    }
+
+    let native = toolchain_available();
+    if !native {
+        eprintln!("llc/ld.lld not found; golden cases run in the interpreter only");
+    }
+    let scratch = tempfile::tempdir().unwrap();
+
+    let mut problems = Vec::new();
+    for case in &cases {
+        let result = run_case(case, native, scratch.path());
+        match (result, expected_failures.contains(&case.name)) {
+            (Err(message), false) => problems.push(message),
+            (Ok(()), true) => problems.push(format!(
+                "{} passes now; remove it from expected_failures.txt",
+                case.name
+            )),
+            _ => {}
+        }
+    }
+    assert!(problems.is_empty(), "{} golden case(s) failed:\n\n{}", problems.len(), problems.join("\n\n"));
+}
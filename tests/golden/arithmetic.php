@@ -0,0 +1,13 @@
+<?php
+echo 7 + 5, "\n";
+echo 7 - 10, "\n";
+echo 6 * 7, "\n";
+echo 7 / 2, "\n";
+echo 8 / 2, "\n";
+echo 7 % 3, " ", -7 % 3, "\n";
+echo 2 ** 10, "\n";
+echo intdiv(17, 5), "\n";
+echo 0.1 + 0.2, "\n";
+echo PHP_INT_MAX + 1, "\n";
+echo 1 <=> 2, "\n";
+echo 5 & 3, " ", 5 | 3, " ", 5 ^ 3, " ", 1 << 4, "\n";
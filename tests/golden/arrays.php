@@ -0,0 +1,20 @@
+<?php
+$list = [3, 1, 2];
+$list[] = 5;
+echo count($list), "\n";
+echo implode(",", $list), "\n";
+
+$ages = ["ann" => 31, "bob" => 27];
+$ages["cy"] = 40;
+foreach ($ages as $name => $age) {
+    echo "$name=$age\n";
+}
+
+$matrix = [[1, 2], [3, 4]];
+$matrix[1][0] = 30;
+echo $matrix[1][0] + $matrix[0][1], "\n";
+
+echo isset($ages["bob"]) ? "yes" : "no", "\n";
+echo isset($ages["dan"]) ? "yes" : "no", "\n";
+echo in_array(2, $list) ? "found" : "missing", "\n";
+echo implode(" ", array_keys($ages)), "\n";
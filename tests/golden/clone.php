@@ -0,0 +1,43 @@
+<?php
+class Point {
+    public $x;
+    public $label;
+    public $copies = 0;
+
+    public function __construct($x, $label) {
+        $this->x = $x;
+        $this->label = $label;
+    }
+
+    public function __clone() {
+        $this->copies++;
+        $this->label .= " (copy)";
+    }
+}
+
+class Line {
+    public $start;
+
+    public function __construct(Point $start) {
+        $this->start = $start;
+    }
+}
+
+$a = new Point(1, "a");
+$b = clone $a;
+$b->x = 2;
+echo $a->x, " ", $a->label, " ", $a->copies, "\n";
+echo $b->x, " ", $b->label, " ", $b->copies, "\n";
+var_dump($b instanceof Point, $a === $b);
+
+// The copy shares the objects its properties hold
+$line = new Line($a);
+$copy = clone $line;
+$copy->start->x = 10;
+echo $line->start->x, "\n";
+
+try {
+    $e = clone new Exception("e");
+} catch (Error $error) {
+    echo get_class($error), ": ", $error->getMessage(), "\n";
+}
@@ -0,0 +1,34 @@
+<?php
+function sign(int $n): string {
+    if ($n > 0) {
+        return "positive";
+    } elseif ($n < 0) {
+        return "negative";
+    } else {
+        return "zero";
+    }
+}
+
+function describe(int $n): string {
+    switch ($n) {
+        case 1:
+            return "one";
+        case 2:
+        case 3:
+            return "two or three";
+        default:
+            return "many";
+    }
+}
+
+foreach ([5, -2, 0] as $n) {
+    echo sign($n), "\n";
+}
+echo describe(1), ", ", describe(3), ", ", describe(9), "\n";
+echo match (true) {
+    2 > 3 => "no",
+    default => "yes",
+}, "\n";
+$x = null;
+echo $x ?? "fallback", "\n";
+echo $x ?: "falsy", "\n";
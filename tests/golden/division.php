@@ -0,0 +1,14 @@
+<?php
+echo 7 / 2, " ", 8 / 2, " ", 7.5 / 2.5, "\n";
+echo PHP_INT_MIN / -1, "\n";
+echo -7 % 3, " ", 5 % -1, " ", PHP_INT_MIN % -1, "\n";
+echo intdiv(17, 5), " ", intdiv(-17, 5), "\n";
+echo fdiv(1, 0), " ", fdiv(-1, 0), " ", fdiv(0, 0), "\n";
+
+foreach ([fn() => 1 / 0, fn() => 1 % 0, fn() => intdiv(1, 0), fn() => intdiv(PHP_INT_MIN, -1)] as $f) {
+    try {
+        $f();
+    } catch (ArithmeticError $e) {
+        echo get_class($e), ": ", $e->getMessage(), "\n";
+    }
+}
@@ -0,0 +1,14 @@
+<?php
+function divide(int $a, int $b): int {
+    return intdiv($a, $b);
+}
+
+try {
+    echo divide(10, 2), "\n";
+    echo divide(1, 0), "\n";
+    echo "not reached\n";
+} catch (DivisionByZeroError $e) {
+    echo "caught: ", $e->getMessage(), "\n";
+} finally {
+    echo "finally\n";
+}
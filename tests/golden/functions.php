@@ -0,0 +1,39 @@
+<?php
+function fact(int $n): int {
+    return $n <= 1 ? 1 : $n * fact($n - 1);
+}
+
+function greet(string $who = "there"): string {
+    return "Hi " . $who;
+}
+
+function sum(int ...$values): int {
+    $total = 0;
+    foreach ($values as $v) {
+        $total += $v;
+    }
+    return $total;
+}
+
+function counter(): int {
+    static $count = 0;
+    return ++$count;
+}
+
+function add_to_total(int $n): void {
+    global $total;
+    $total += $n;
+}
+
+echo fact(10), "\n";
+echo greet(), "\n";
+echo greet("Ann"), "\n";
+echo sum(1, 2, 3, 4), "\n";
+counter();
+counter();
+echo counter(), "\n";
+
+$total = 0;
+add_to_total(5);
+add_to_total(7);
+echo $total, "\n";
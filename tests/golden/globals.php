@@ -0,0 +1,18 @@
+<?php
+$count = 1;
+$label = "total";
+
+function bump(): void {
+    global $count;
+    $count += 1;
+    $GLOBALS['total'] = $count * 10;
+}
+
+bump();
+bump();
+echo $count, " ", $total, "\n";
+
+$name = "label";
+echo $GLOBALS[$name], " ", $GLOBALS[$label], "\n";
+$GLOBALS['count'] = 7;
+echo $count, "\n";
@@ -0,0 +1,5 @@
+<?php
+require_once __DIR__ . '/lib/math.php';
+require_once __DIR__ . '/lib/math.php';
+
+echo square(7), "\n";
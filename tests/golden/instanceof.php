@@ -0,0 +1,25 @@
+<?php
+interface Shape {}
+
+class Base implements Shape {}
+
+class Square extends Base {}
+
+final class Circle implements Shape {}
+
+function describe($value) {
+    var_dump($value instanceof Shape, $value instanceof Base, $value instanceof Square);
+}
+
+$base = new Base();
+$square = new Square();
+$circle = new Circle();
+
+var_dump($base instanceof Shape);
+var_dump($base instanceof Square);
+var_dump($square instanceof Base);
+var_dump($circle instanceof Base);
+var_dump($base instanceof Missing);
+describe($square);
+describe($circle);
+describe(42);
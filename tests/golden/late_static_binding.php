@@ -0,0 +1,26 @@
+<?php
+abstract class Model {
+    public static function create(): static {
+        return new static();
+    }
+
+    public static function label(): string {
+        return "model";
+    }
+
+    public function describe(): string {
+        return static::label() . " " . self::label();
+    }
+}
+
+class User extends Model {
+    public static function label(): string {
+        return "user";
+    }
+}
+
+class Post extends Model {}
+
+echo User::create()->describe(), "\n";
+echo Post::create()->describe(), "\n";
+echo get_class(User::create()), "\n";
@@ -0,0 +1,4 @@
+<?php
+function square(int $n): int {
+    return $n * $n;
+}
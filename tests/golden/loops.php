@@ -0,0 +1,37 @@
+<?php
+for ($i = 0; $i < 5; $i++) {
+    if ($i == 3) {
+        continue;
+    }
+    echo $i;
+}
+echo "\n";
+
+$n = 0;
+while (true) {
+    $n++;
+    if ($n >= 4) {
+        break;
+    }
+}
+echo $n, "\n";
+
+$k = 10;
+do {
+    echo $k, ";";
+    $k -= 4;
+} while ($k > 0);
+echo "\n";
+
+for ($i = 1; $i <= 3; $i++) {
+    for ($j = 1; $j <= 3; $j++) {
+        if ($j == 2) {
+            continue 2;
+        }
+        if ($i == 3) {
+            break 2;
+        }
+        echo "$i$j;";
+    }
+}
+echo "\n";
@@ -0,0 +1,29 @@
+<?php
+class Record {
+    private array $data = [];
+
+    public function __get(string $name) {
+        echo "get $name\n";
+        return $this->data[$name] ?? null;
+    }
+
+    public function __set(string $name, $value): void {
+        echo "set $name\n";
+        $this->data[$name] = $value;
+    }
+
+    public function __call(string $name, array $arguments) {
+        return $name . "(" . implode(", ", $arguments) . ")";
+    }
+
+    public static function __callStatic(string $name, array $arguments) {
+        return "static " . $name . "(" . count($arguments) . ")";
+    }
+}
+
+$record = new Record();
+$record->title = "Hello";
+echo $record->title, "\n";
+var_dump($record->missing);
+echo $record->find(1, 2), "\n";
+echo Record::where("a", "b", "c"), "\n";
@@ -0,0 +1,23 @@
+<?php
+class Counter {
+    const STEP = 2;
+    const LABEL = "count: ";
+    const LIMITS = [1, 10];
+
+    public static $count = 0;
+
+    public static function bump(): int {
+        static::$count += self::STEP;
+        return static::$count;
+    }
+}
+
+class Sub extends Counter {
+    public static $count = 10;
+}
+
+echo Counter::LABEL, Counter::bump(), "\n";
+echo Counter::LABEL, Sub::bump(), "\n";
+echo Counter::$count, " ", Sub::$count, "\n";
+Counter::$count = 100;
+echo Counter::bump(), " ", Counter::LIMITS[1], "\n";
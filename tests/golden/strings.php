@@ -0,0 +1,14 @@
+<?php
+$name = "World";
+$greeting = "Hello, " . $name . "!";
+echo $greeting, "\n";
+echo strlen($greeting), "\n";
+echo strtoupper($name), "\n";
+echo str_repeat("ab", 3), "\n";
+$s = "x";
+$s .= "y";
+echo $s, "\n";
+echo "Value: {$name}\n";
+echo 'single $name', "\n";
+echo "10" + 5, "\n";
+echo "3" == "03" ? "equal" : "different", "\n";
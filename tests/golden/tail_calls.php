@@ -0,0 +1,27 @@
+<?php
+function sum(int $n, int $acc = 0): int {
+    if ($n == 0) {
+        return $acc;
+    }
+    return sum($n - 1, $acc + $n);
+}
+
+function gcd(int $a, int $b): int {
+    if ($b == 0) {
+        return $a;
+    }
+    return gcd($b, $a % $b);
+}
+
+class Countdown {
+    public static function run(int $n, string $out = ""): string {
+        if ($n == 0) {
+            return $out . "liftoff";
+        }
+        return self::run($n - 1, $out . $n . " ");
+    }
+}
+
+echo sum(1000000), "\n";
+echo gcd(1071, 462), "\n";
+echo Countdown::run(3), "\n";
@@ -0,0 +1,59 @@
+/*
+ * Copyright 2025 Mehmet T. AKALIN
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! IR snapshot tests.
+//!
+//! Every `tests/ir/*.php` fixture is compiled to IR and compared with its
+//! `<name>.ll` snapshot. After an intended change to generated code, update
+//! the snapshots with
+//!
+//! ```text
+//! cargo test --test ir_snapshots -- --bless
+//! ```
+//!
+//! (or `PHP2IR_BLESS=1`) and review their diff. A fixture without a
+//! snapshot is reported but does not fail until it has been blessed.
+
+use std::path::Path;
+use std::process::ExitCode;
+use php2ir::snapshot::{check_snapshots, SnapshotOutcome};
+
+fn main() -> ExitCode {
+    let bless = std::env::args().any(|arg| arg == "--bless")
+        || std::env::var_os("PHP2IR_BLESS").is_some_and(|value| !value.is_empty() && value != "0");
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("ir");
+
+    let results = match check_snapshots(&dir, bless) {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut changed = 0;
+    for result in &results {
+        println!("{}", result);
+        if matches!(result.outcome, SnapshotOutcome::Changed { .. }) {
+            changed += 1;
+        }
+    }
+    println!("\n{} snapshot(s) checked, {} changed", results.len(), changed);
+    if changed > 0 {
+        println!("If the changes are intended, run `cargo test --test ir_snapshots -- --bless`");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,10 @@
+--TEST--
+echo of strings and integers
+--FILE--
+<?php
+$name = "php2ir";
+echo "Hello, ", $name, "!\n";
+echo 6 * 7, "\n";
+--EXPECT--
+Hello, php2ir!
+42
@@ -0,0 +1,11 @@
+--TEST--
+PHP_INT_MAX on 64-bit targets
+--SKIPIF--
+<?php if (PHP_INT_SIZE != 8) echo "skip 64-bit integers only"; ?>
+--FILE--
+<?php
+var_dump(PHP_INT_MAX);
+var_dump(PHP_INT_MAX + 1);
+--EXPECTF--
+int(9223372036854775807)
+float(%f)